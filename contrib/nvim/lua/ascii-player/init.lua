@@ -0,0 +1,121 @@
+-- Video preview panes for Neovim, driven by `ascii-player --nvim`.
+--
+-- require("ascii-player").setup()
+--   :AsciiPlayer {file}       play in a split below the current window
+--   :AsciiPlayerSend {cmd}    send a command: pause, toggle, seek +10, screenshot, quit
+
+local M = {}
+
+M.config = {
+  cmd = "ascii-player",
+  -- Extra arguments, e.g. { "--palette", "grayscale", "--loop-playback" }
+  args = {},
+  height = 20,
+}
+
+-- Control FIFO of the most recently started player
+local fifo = nil
+-- Pipe reading that player's replies
+local replies = nil
+
+local function notify_reply(line)
+  if line:match("^error ") then
+    vim.notify("ascii-player: " .. line:sub(7), vim.log.levels.ERROR)
+  elseif line ~= "ok" then
+    vim.notify("ascii-player: " .. line:gsub("^ok ", ""), vim.log.levels.INFO)
+  end
+end
+
+-- Show the replies to sent commands, which arrive on their own FIFO so
+-- they stay out of the terminal buffer
+local function read_replies(path)
+  local uv = vim.uv or vim.loop
+  -- Non-blocking, so opening doesn't wait for the player to write
+  local fd = uv.fs_open(path, bit.bor(uv.constants.O_RDONLY, uv.constants.O_NONBLOCK), 0)
+  if not fd then
+    return
+  end
+  replies = uv.new_pipe(false)
+  replies:open(fd)
+  local pending = ""
+  replies:read_start(function(err, data)
+    if err or not data then
+      return
+    end
+    pending = pending .. data
+    for line in pending:gmatch("([^\n]*)\n") do
+      vim.schedule(function()
+        notify_reply(line)
+      end)
+    end
+    pending = pending:match("[^\n]*$")
+  end)
+end
+
+local function on_output(_, data)
+  for _, line in ipairs(data) do
+    local path = line:match("ascii%-player%-fifo=(%S+)")
+    if path then
+      fifo = path
+    end
+    path = line:match("ascii%-player%-replies=(%S+)")
+    if path then
+      read_replies(path)
+    end
+  end
+end
+
+local function close_replies()
+  if replies then
+    replies:close()
+    replies = nil
+  end
+end
+
+function M.open(file)
+  vim.cmd("botright " .. M.config.height .. "new")
+  local win = vim.api.nvim_get_current_win()
+  local args = { M.config.cmd, "--nvim" }
+  vim.list_extend(args, {
+    "--width", tostring(vim.api.nvim_win_get_width(win)),
+    "--height", tostring(vim.api.nvim_win_get_height(win)),
+  })
+  vim.list_extend(args, M.config.args)
+  table.insert(args, vim.fn.expand(file))
+
+  fifo = nil
+  close_replies()
+  vim.fn.termopen(args, {
+    on_stdout = on_output,
+    on_exit = function()
+      fifo = nil
+      close_replies()
+    end,
+  })
+end
+
+function M.send(command)
+  if not fifo then
+    vim.notify("ascii-player is not running", vim.log.levels.WARN)
+    return
+  end
+  local pipe = io.open(fifo, "w")
+  if not pipe then
+    vim.notify("ascii-player: cannot open " .. fifo, vim.log.levels.ERROR)
+    return
+  end
+  pipe:write(command .. "\n")
+  pipe:close()
+end
+
+function M.setup(opts)
+  M.config = vim.tbl_extend("force", M.config, opts or {})
+  vim.api.nvim_create_user_command("AsciiPlayer", function(cmd)
+    M.open(cmd.args)
+  end, { nargs = 1, complete = "file" })
+  vim.api.nvim_create_user_command("AsciiPlayerSend", function(cmd)
+    M.send(cmd.args)
+  end, { nargs = "+" })
+end
+
+return M
@@ -37,11 +37,15 @@
             
             # Testing and debugging
             gdb
+          ] ++ lib.optionals stdenv.isLinux [
+            # Sound output
+            alsa-lib
           ];
           
           # Environment variables for Rust development
           RUST_SRC_PATH = "${pkgs.rustPlatform.rustLibSrc}";
-          PKG_CONFIG_PATH = "${pkgs.openssl.dev}/lib/pkgconfig:${pkgs.ffmpeg_7.dev}/lib/pkgconfig";
+          PKG_CONFIG_PATH = "${pkgs.openssl.dev}/lib/pkgconfig:${pkgs.ffmpeg_7.dev}/lib/pkgconfig"
+            + pkgs.lib.optionalString pkgs.stdenv.isLinux ":${pkgs.alsa-lib.dev}/lib/pkgconfig";
           
           shellHook = ''
             echo "🎬 ASCII Player Development Environment"
@@ -77,6 +81,8 @@
           buildInputs = with pkgs; [
             ffmpeg_7
             openssl
+          ] ++ lib.optionals stdenv.isLinux [
+            alsa-lib
           ];
           
           # Skip tests during build (will be run separately)
@@ -0,0 +1,107 @@
+//! Looping a marked stretch of the video
+//!
+//! The `[` and `]` marks that bound a clip for export also make an A-B
+//! loop: once both are set, with A before B, playback goes back to A each
+//! time it reaches B, so a passage can be watched over and over or checked
+//! before it is exported. The marks show in the status line until `\`
+//! clears them.
+
+use crate::status::format_clock;
+
+/// The A and B marks
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AbLoop {
+    a: Option<f64>,
+    b: Option<f64>,
+}
+
+impl AbLoop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark where the loop starts
+    pub fn set_a(&mut self, position: f64) {
+        self.a = Some(position);
+    }
+
+    /// Mark where the loop ends
+    pub fn set_b(&mut self, position: f64) {
+        self.b = Some(position);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn a(&self) -> Option<f64> {
+        self.a
+    }
+
+    pub fn b(&self) -> Option<f64> {
+        self.b
+    }
+
+    /// Start and end of the loop, once both marks are set in order
+    pub fn range(&self) -> Option<(f64, f64)> {
+        match (self.a, self.b) {
+            (Some(a), Some(b)) if a < b => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    /// Where to go back to when playback has reached `position`
+    pub fn restart_at(&self, position: f64) -> Option<f64> {
+        self.range().and_then(|(a, b)| (position >= b).then_some(a))
+    }
+
+    /// The marks for the status line, None while neither is set
+    pub fn status(&self) -> Option<String> {
+        match (self.a, self.b) {
+            (None, None) => None,
+            (Some(a), None) => Some(format!("A {}", format_clock(a))),
+            (None, Some(b)) => Some(format!("B {}", format_clock(b))),
+            (Some(a), Some(b)) => Some(format!(
+                "A-B {}-{}{}",
+                format_clock(a),
+                format_clock(b),
+                if a < b { "" } else { " (B before A)" }
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loops_back_to_a_at_b() {
+        let mut ab = AbLoop::new();
+        assert_eq!(ab.status(), None);
+
+        ab.set_a(12.0);
+        // Half a loop does nothing yet
+        assert_eq!(ab.restart_at(100.0), None);
+        assert_eq!(ab.status().as_deref(), Some("A 00:12"));
+
+        ab.set_b(20.0);
+        assert_eq!(ab.restart_at(19.9), None);
+        assert_eq!(ab.restart_at(20.0), Some(12.0));
+        assert_eq!(ab.status().as_deref(), Some("A-B 00:12-00:20"));
+
+        ab.clear();
+        assert_eq!(ab.restart_at(20.0), None);
+        assert_eq!(ab.status(), None);
+    }
+
+    #[test]
+    fn test_marks_out_of_order_do_not_loop() {
+        let mut ab = AbLoop::new();
+        ab.set_a(30.0);
+        ab.set_b(10.0);
+        assert_eq!(ab.range(), None);
+        assert_eq!(ab.restart_at(40.0), None);
+        assert!(ab.status().unwrap().contains("B before A"));
+    }
+}
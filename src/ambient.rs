@@ -0,0 +1,118 @@
+//! Ambient background mode
+//!
+//! `--ambient FACTOR` plays the video as quiet ambience behind other
+//! terminal work, e.g. in a tmux pane: every output color is blended toward
+//! the terminal's background color, keeping FACTOR of its strength, and
+//! cell backgrounds are left transparent. The background color is asked
+//! from the terminal (OSC 11), falling back to `$COLORFGBG` and then black.
+
+use crate::termcaps;
+use log::debug;
+
+/// The 16 ANSI colors, for `$COLORFGBG`
+const ANSI_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Blend `color` toward `background`, keeping `factor` (0.0-1.0) of it
+pub fn blend(color: (u8, u8, u8), background: (u8, u8, u8), factor: f64) -> (u8, u8, u8) {
+    let mix = |channel: u8, base: u8| {
+        (base as f64 + (channel as f64 - base as f64) * factor)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    (
+        mix(color.0, background.0),
+        mix(color.1, background.1),
+        mix(color.2, background.2),
+    )
+}
+
+/// Background color of the terminal, black if it cannot be found
+pub fn terminal_background() -> (u8, u8, u8) {
+    let background = query_background()
+        .or_else(|| {
+            std::env::var("COLORFGBG")
+                .ok()
+                .and_then(|value| parse_colorfgbg(&value))
+        })
+        .unwrap_or((0, 0, 0));
+    debug!("Terminal background: {:?}", background);
+    background
+}
+
+/// Background from `$COLORFGBG`, set by rxvt and Konsole as `fg;bg` or
+/// `fg;default;bg`
+fn parse_colorfgbg(value: &str) -> Option<(u8, u8, u8)> {
+    let index: usize = value.rsplit(';').next()?.trim().parse().ok()?;
+    ANSI_COLORS.get(index).copied()
+}
+
+/// Color in an OSC 11 reply such as `ESC ] 11 ; rgb:1e1e/1e1e/2e2e BEL`
+fn parse_osc11_reply(reply: &str) -> Option<(u8, u8, u8)> {
+    let (_, spec) = reply.split_once("rgb:")?;
+    let spec = spec.trim_end_matches(['\x07', '\x1b', '\\']);
+    let mut channels = spec.split('/').map(|hex| {
+        // Channels have 1-4 hex digits; keep the top 8 bits
+        let value = u16::from_str_radix(hex.get(..hex.len().min(4))?, 16).ok()?;
+        let max = (1u32 << (4 * hex.len().min(4))) - 1;
+        Some((value as u32 * 255 / max) as u8)
+    });
+    let color = (channels.next()??, channels.next()??, channels.next()??);
+    Some(color)
+}
+
+/// Ask the terminal for its background color (OSC 11)
+fn query_background() -> Option<(u8, u8, u8)> {
+    let reply = termcaps::query("\x1b]11;?\x1b\\", |reply| {
+        reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\")
+    })?;
+    parse_osc11_reply(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        assert_eq!(blend((200, 100, 0), (0, 0, 0), 0.5), (100, 50, 0));
+        assert_eq!(blend((200, 100, 0), (40, 40, 40), 0.0), (40, 40, 40));
+        assert_eq!(blend((200, 100, 0), (40, 40, 40), 1.0), (200, 100, 0));
+        // Light backgrounds pull colors up
+        assert_eq!(blend((0, 0, 0), (255, 255, 255), 0.4), (153, 153, 153));
+    }
+
+    #[test]
+    fn test_parse_background() {
+        assert_eq!(
+            parse_osc11_reply("\x1b]11;rgb:1e1e/ffff/0000\x1b\\"),
+            Some((30, 255, 0))
+        );
+        assert_eq!(
+            parse_osc11_reply("\x1b]11;rgb:f/80/000\x07"),
+            Some((255, 128, 0))
+        );
+        assert_eq!(parse_osc11_reply(""), None);
+        assert_eq!(parse_osc11_reply("\x1b]11;rgb:zz/00/00\x07"), None);
+
+        assert_eq!(parse_colorfgbg("15;0"), Some((0, 0, 0)));
+        assert_eq!(parse_colorfgbg("0;default;15"), Some((255, 255, 255)));
+        assert_eq!(parse_colorfgbg("0;default"), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! Animated GIF and APNG input without FFmpeg
+//!
+//! GIFs and animated PNGs are decoded with the `image` crate, which composes
+//! each frame onto the canvas left by the ones before it as the frame's
+//! disposal method says. The whole file is decoded once when it is opened:
+//! the delays are needed up front, and keeping the frames makes seeking back
+//! free where decoding would have to start over from the first frame, at the
+//! cost of holding every frame in memory. Frames each have their own delay,
+//! while the player draws at a steady rate, so the animation is played at
+//! the rate of the greatest common divisor of its delays and a frame shown
+//! longer than that is repeated. The picture on screen changes exactly when
+//! the file says it does, down to 20ms steps; repeats cost little as only
+//! changed cells are redrawn.
+//!
+//! As in browsers, delays under 20ms are taken as 100ms, since many GIFs
+//! were made with a delay of 0 or 10ms and rely on that.
+
+use crate::captions::ClosedCaptions;
+use crate::decoder::{FrameSource, VideoFrame, TIMESTAMP_EPSILON};
+use anyhow::{anyhow, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::AnimationDecoder;
+use log::debug;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use tracing::debug_span;
+
+/// Delays shorter than this are taken as `DEFAULT_DELAY_MS`
+const MIN_DELAY_MS: u32 = 20;
+const DEFAULT_DELAY_MS: u32 = 100;
+
+/// Shortest interval frames are drawn at; finer delays are rounded to it
+const MIN_INTERVAL_MS: u32 = 20;
+
+/// Animated image formats decoded natively
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gif,
+    Apng,
+}
+
+/// Whether `path` is a GIF or an animated PNG, by its contents
+pub fn is_animated_image(path: &Path) -> bool {
+    format(path).is_some()
+}
+
+fn format(path: &Path) -> Option<Format> {
+    let mut header = [0u8; 8];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if header.starts_with(b"GIF8") {
+        return Some(Format::Gif);
+    }
+    if header.starts_with(b"\x89PNG") {
+        let decoder = PngDecoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+        return decoder.is_apng().then_some(Format::Apng);
+    }
+    None
+}
+
+/// A decoded frame of the file and when it leaves the screen, in
+/// milliseconds
+struct Shown {
+    data: Vec<u8>,
+    end: u64,
+}
+
+/// Frames of an animated GIF or PNG, decoded when it is opened
+pub struct AnimatedImage {
+    /// Every frame of the file, in order
+    frames: Vec<Shown>,
+    /// Delay of every frame in milliseconds
+    delays: Vec<u32>,
+    /// Interval frames are drawn at, in milliseconds
+    interval: u32,
+    dimensions: (u32, u32),
+    /// Index of the next frame to draw, counted in intervals
+    next: u64,
+    /// Images carry no captions; kept to lend out as empty
+    captions: ClosedCaptions,
+}
+
+impl AnimatedImage {
+    /// Open a GIF or APNG, decoding all of its frames
+    pub fn open(path: &Path) -> Result<Self> {
+        let format = format(path)
+            .ok_or_else(|| anyhow!("'{}' is not a GIF or animated PNG", path.display()))?;
+        let _span = debug_span!("decode").entered();
+        let mut frames: Vec<Shown> = Vec::new();
+        let mut delays = Vec::new();
+        for frame in decode_frames(path, format)? {
+            let frame =
+                frame.map_err(|e| anyhow!("Failed to decode '{}': {}", path.display(), e))?;
+            let delay = frame_delay(&frame);
+            let start = frames.last().map_or(0, |shown| shown.end);
+            let rgb = image::DynamicImage::ImageRgba8(frame.into_buffer()).into_rgb8();
+            frames.push(Shown {
+                data: rgb.into_raw(),
+                end: start + u64::from(delay),
+            });
+            delays.push(delay);
+        }
+        if frames.is_empty() {
+            return Err(anyhow!("'{}' has no frames", path.display()));
+        }
+        let interval = draw_interval(&delays);
+        let dimensions = image::image_dimensions(path)
+            .map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+        debug!(
+            "{:?} {}: {} frames, {}x{}, drawn every {}ms",
+            format,
+            path.display(),
+            delays.len(),
+            dimensions.0,
+            dimensions.1,
+            interval
+        );
+        Ok(Self {
+            frames,
+            delays,
+            interval,
+            dimensions,
+            next: 0,
+            captions: ClosedCaptions::default(),
+        })
+    }
+
+    /// Delay of each frame of the file in milliseconds
+    pub fn delays(&self) -> &[u32] {
+        &self.delays
+    }
+}
+
+impl FrameSource for AnimatedImage {
+    fn fps(&self) -> f64 {
+        1000.0 / f64::from(self.interval)
+    }
+
+    fn duration(&self) -> f64 {
+        self.delays
+            .iter()
+            .map(|&delay| u64::from(delay))
+            .sum::<u64>() as f64
+            / 1000.0
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn seek_to(&mut self, timestamp: f64) -> Result<()> {
+        let time_ms = timestamp.max(0.0) * 1000.0;
+        self.next = ((time_ms - TIMESTAMP_EPSILON) / f64::from(self.interval)).ceil() as u64;
+        debug!("Seeked to {:.2}s", timestamp);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+        let time_ms = self.next * u64::from(self.interval);
+        let index = self.frames.partition_point(|shown| shown.end <= time_ms);
+        let Some(shown) = self.frames.get(index) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        Ok(Some(VideoFrame {
+            data: shown.data.clone(),
+            width: self.dimensions.0,
+            height: self.dimensions.1,
+            timestamp: time_ms as f64 / 1000.0,
+            frame_number: self.next,
+        }))
+    }
+
+    fn captions(&self) -> &ClosedCaptions {
+        &self.captions
+    }
+}
+
+/// Composed frames of the file, decoded as they are read
+fn decode_frames(path: &Path, format: Format) -> Result<image::Frames<'static>> {
+    let reader = BufReader::new(
+        File::open(path).map_err(|e| anyhow!("Failed to open '{}': {}", path.display(), e))?,
+    );
+    let frames = match format {
+        Format::Gif => GifDecoder::new(reader).map(|decoder| decoder.into_frames()),
+        Format::Apng => PngDecoder::new(reader).map(|decoder| decoder.apng().into_frames()),
+    }
+    .map_err(|e| anyhow!("Failed to decode '{}': {}", path.display(), e))?;
+    Ok(frames)
+}
+
+/// Delay of a frame in whole milliseconds, with too short delays replaced
+fn frame_delay(frame: &image::Frame) -> u32 {
+    let (numerator, denominator) = frame.delay().numer_denom_ms();
+    let delay = numerator.checked_div(denominator).unwrap_or(0);
+    if delay < MIN_DELAY_MS {
+        DEFAULT_DELAY_MS
+    } else {
+        delay
+    }
+}
+
+/// Interval to draw at: the greatest common divisor of the delays, so each
+/// delay is a whole number of intervals, but not below `MIN_INTERVAL_MS`
+fn draw_interval(delays: &[u32]) -> u32 {
+    let gcd = delays
+        .iter()
+        .fold(0, |gcd, &delay| greatest_common_divisor(gcd, delay));
+    gcd.max(MIN_INTERVAL_MS)
+}
+
+fn greatest_common_divisor(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba, RgbaImage};
+
+    #[test]
+    fn test_draw_interval() {
+        assert_eq!(draw_interval(&[100, 100]), 100);
+        assert_eq!(draw_interval(&[40, 100]), 20);
+        assert_eq!(draw_interval(&[30, 50]), MIN_INTERVAL_MS);
+    }
+
+    #[test]
+    fn test_animated_gif() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("anim.gif");
+        {
+            let mut encoder = GifEncoder::new(File::create(&path).unwrap());
+            for (shade, delay) in [(0u8, 100), (255, 200)] {
+                let image = RgbaImage::from_pixel(4, 2, Rgba([shade, shade, shade, 255]));
+                let delay = Delay::from_numer_denom_ms(delay, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        assert!(is_animated_image(&path));
+
+        let mut animation = AnimatedImage::open(&path).unwrap();
+        assert_eq!(animation.delays(), [100, 200]);
+        assert_eq!(animation.fps(), 10.0);
+        assert!((animation.duration() - 0.3).abs() < 1e-9);
+
+        // The second frame is shown for two intervals
+        let shades: Vec<(u8, f64)> = std::iter::from_fn(|| animation.next_frame().unwrap())
+            .map(|frame| (frame.data[0], frame.timestamp))
+            .collect();
+        assert_eq!(shades, [(0, 0.0), (255, 0.1), (255, 0.2)]);
+
+        // Seeking back shows the first frame again
+        animation.seek_to(0.0).unwrap();
+        assert_eq!(animation.next_frame().unwrap().unwrap().data[0], 0);
+    }
+}
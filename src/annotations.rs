@@ -0,0 +1,211 @@
+//! Notes attached to moments of a video
+//!
+//! `a` pauses and opens a text field over the picture; the note typed into
+//! it is attached to the frame on screen. Notes are saved per input in the
+//! config file next to the bookmarks, show at the top of the picture for
+//! [`NOTE_DURATION`] seconds whenever playback passes them, and `{`/`}` jump
+//! between them. `ascii-player annotations FILE` lists them, or exports them
+//! as JSON or as SRT subtitles for review in other players.
+
+use crate::bookmarks::{mark_lines, TimedMark};
+use crate::config::Config;
+use crate::converter::AsciiFrame;
+use crate::decoder::source_location;
+use crate::overlay::{draw_text, OverlayContent, Placement, TimedOverlay};
+use crate::status::format_clock;
+use crate::textinput::{InputResult, TextInput};
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Seconds a note stays on screen during playback, and the longest an SRT
+/// cue lasts
+pub const NOTE_DURATION: f64 = 4.0;
+
+/// A note on a moment of a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Seconds into the video
+    pub time: f64,
+    pub text: String,
+}
+
+impl TimedMark for Annotation {
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn label(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Show `annotation` over the picture when playback passes it
+pub fn schedule_annotation(overlays: &mut TimedOverlay, annotation: &Annotation) {
+    let content = OverlayContent::Text(vec![annotation.text.clone()], Placement::Top);
+    overlays.add(annotation.time, annotation.time + NOTE_DURATION, content);
+}
+
+/// Note being typed for the frame on screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationPrompt {
+    time: f64,
+    input: TextInput,
+}
+
+impl AnnotationPrompt {
+    pub fn new(time: f64) -> Self {
+        Self {
+            time,
+            input: TextInput::new(),
+        }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> InputResult {
+        self.input.handle_key(code)
+    }
+
+    /// The note typed, None if nothing was
+    pub fn annotation(&self) -> Option<Annotation> {
+        let text = self.input.text();
+        let text = text.trim();
+        (!text.is_empty()).then(|| Annotation {
+            time: self.time,
+            text: text.to_string(),
+        })
+    }
+
+    /// Draw the text field above the bottom of `frame`
+    pub fn draw(&self, frame: &mut AsciiFrame) {
+        // Room for the padding draw_text adds and the "> " prefix
+        let width = (frame.width as usize).saturating_sub(4);
+        let lines = [
+            format!("Note at {}", format_clock(self.time)),
+            format!("> {}", self.input.visible(width)),
+            "Enter to save, Esc to cancel".to_string(),
+        ];
+        draw_text(frame, &lines, Placement::Bottom);
+    }
+}
+
+/// How `ascii-player annotations` prints the notes of a video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationFormat {
+    /// One `time  text` line each
+    Text,
+    Json,
+    /// Subtitle cues, each until the next note or for [`NOTE_DURATION`]
+    Srt,
+}
+
+#[derive(Serialize)]
+struct AnnotationExport<'a> {
+    file: String,
+    annotations: &'a [Annotation],
+}
+
+/// Notes of `input` in the given format
+pub fn list_annotations(config: &Config, input: &Path, format: AnnotationFormat) -> Result<String> {
+    let annotations = config.annotations(input);
+    let mut output = String::new();
+    match format {
+        AnnotationFormat::Json => {
+            let export = AnnotationExport {
+                file: source_location(input),
+                annotations,
+            };
+            output = serde_json::to_string_pretty(&export)? + "\n";
+        }
+        AnnotationFormat::Srt => {
+            for (index, annotation) in annotations.iter().enumerate() {
+                let next = annotations[index + 1..]
+                    .iter()
+                    .map(|next| next.time)
+                    .find(|&time| time > annotation.time);
+                let end = next.map_or(annotation.time + NOTE_DURATION, |next| {
+                    next.min(annotation.time + NOTE_DURATION)
+                });
+                let _ = writeln!(
+                    output,
+                    "{}\n{} --> {}\n{}\n",
+                    index + 1,
+                    srt_timestamp(annotation.time),
+                    srt_timestamp(end),
+                    annotation.text
+                );
+            }
+        }
+        AnnotationFormat::Text if annotations.is_empty() => {
+            output = format!("No annotations for {}\n", input.display());
+        }
+        AnnotationFormat::Text => output = mark_lines(annotations),
+    }
+    Ok(output)
+}
+
+/// `HH:MM:SS,mmm`, as SRT cues are timed
+fn srt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmarks::{next_mark, previous_mark};
+
+    fn annotation(time: f64, text: &str) -> Annotation {
+        Annotation {
+            time,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_annotation_export() {
+        let mut config = Config::default();
+        let input = Path::new("https://example.com/match.mp4");
+        config.add_annotation(input, annotation(62.5, "late tackle"));
+        config.add_annotation(input, annotation(61.0, "offside?"));
+        let annotations = config.annotations(input);
+        assert_eq!(next_mark(annotations, 0.0).unwrap().text, "offside?");
+        assert_eq!(
+            previous_mark(annotations, 70.0).unwrap().text,
+            "late tackle"
+        );
+
+        let srt = list_annotations(&config, input, AnnotationFormat::Srt).unwrap();
+        assert_eq!(
+            srt,
+            "1\n00:01:01,000 --> 00:01:02,500\noffside?\n\n\
+             2\n00:01:02,500 --> 00:01:06,500\nlate tackle\n\n"
+        );
+        let listed = list_annotations(&config, input, AnnotationFormat::Text).unwrap();
+        assert_eq!(listed, "   01:01  offside?\n   01:02  late tackle\n");
+        let json: serde_json::Value = serde_json::from_str(
+            &list_annotations(&config, input, AnnotationFormat::Json).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["annotations"][0]["time"], 61.0);
+    }
+
+    #[test]
+    fn test_annotation_prompt() {
+        let mut prompt = AnnotationPrompt::new(12.0);
+        assert_eq!(prompt.handle_key(KeyCode::Char(' ')), InputResult::Editing);
+        assert_eq!(prompt.annotation(), None);
+        for c in "handball".chars() {
+            prompt.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(prompt.handle_key(KeyCode::Enter), InputResult::Submitted);
+        assert_eq!(prompt.annotation(), Some(annotation(12.0, "handball")));
+    }
+}
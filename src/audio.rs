@@ -0,0 +1,478 @@
+//! Audio playback in sync with the picture
+//!
+//! The audio track is decoded on its own thread from a second handle on the
+//! input, so it can stay a second ahead of the picture, resampled by FFmpeg
+//! to what the default output device wants and played with cpal.
+//!
+//! The audio clock leads: before each frame, playback compares the frame's
+//! timestamp with the time of the next sample going to the device and
+//! lengthens or shortens its wait to take up the difference. When the two
+//! are further apart than [`RESYNC_THRESHOLD`], as after seeks, restarts,
+//! loops and speed changes, the audio seeks to the picture instead. Away
+//! from normal speed the decoded audio is time-stretched to keep its pitch
+//! (see [`crate::stretch`]) within [`STRETCH_RANGE`], and muted outside it
+//! or when pitch correction is turned off.
+
+use crate::stretch::{TimeStretch, STRETCH_RANGE};
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use log::{debug, info, warn};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Drift in seconds beyond which the audio seeks to the picture
+pub const RESYNC_THRESHOLD: f64 = 0.3;
+
+/// Seconds of audio decoded ahead of the device
+const BUFFER_SECONDS: f64 = 1.0;
+
+/// How long the decoding thread sleeps while the buffer is full
+const REFILL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Wait before the next frame: `frame_delay` lengthened when the picture is
+/// `drift` seconds ahead of the audio and shortened when it is behind, by at
+/// most one frame either way so corrections stay smooth
+pub fn corrected_delay(frame_delay: Duration, drift: f64) -> Duration {
+    let nominal = frame_delay.as_secs_f64();
+    Duration::from_secs_f64((nominal + drift).clamp(0.0, nominal * 2.0))
+}
+
+/// Decoded samples waiting for the device, interleaved
+#[derive(Debug)]
+struct SampleQueue {
+    samples: VecDeque<f32>,
+    /// Media time of the first queued sample, once known after a seek
+    time: Option<f64>,
+    playing: bool,
+    /// Media seconds each second of queued samples covers, once stretched
+    speed: f64,
+}
+
+impl Default for SampleQueue {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            time: None,
+            playing: false,
+            speed: 1.0,
+        }
+    }
+}
+
+impl SampleQueue {
+    /// Queue samples starting at media time `time`
+    fn push(&mut self, time: f64, samples: impl IntoIterator<Item = f32>) {
+        self.time.get_or_insert(time);
+        self.samples.extend(samples);
+    }
+
+    /// Fill `output` with the next samples, or silence while paused or
+    /// starved. `rate` is samples per second across all channels; the clock
+    /// moves on by the media time they cover at the queue's speed.
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    fn fill<T>(&mut self, output: &mut [T], rate: f64, convert: impl Fn(f32) -> T) {
+        let mut taken = 0;
+        for sample in output.iter_mut() {
+            let next = if self.playing {
+                self.samples.pop_front()
+            } else {
+                None
+            };
+            taken += usize::from(next.is_some());
+            *sample = convert(next.unwrap_or(0.0));
+        }
+        if let Some(ref mut time) = self.time {
+            *time += taken as f64 / rate * self.speed;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.time = None;
+    }
+}
+
+/// Plays the audio track of an input
+pub struct AudioPlayer {
+    queue: Arc<Mutex<SampleQueue>>,
+    seeks: Sender<f64>,
+    pitch_correction: bool,
+    _output: output::Output,
+}
+
+impl AudioPlayer {
+    /// Start decoding the best audio stream of `input` for the default
+    /// output device. Playback starts paused.
+    pub fn open(input: &Path) -> Result<Self> {
+        let queue = Arc::new(Mutex::new(SampleQueue::default()));
+        let (output, format) = output::open(queue.clone())?;
+        let (seeks, seek_requests) = mpsc::channel();
+
+        // Fail now if there is nothing to play
+        let decoder = AudioDecoder::new(input, format)?;
+        let decoder_queue = queue.clone();
+        thread::spawn(move || {
+            if let Err(e) = decoder.run(&decoder_queue, &seek_requests) {
+                warn!("Audio playback stopped: {}", e);
+            }
+        });
+
+        info!(
+            "Audio output: {} Hz, {} channels",
+            format.rate, format.channels
+        );
+        Ok(Self {
+            queue,
+            seeks,
+            pitch_correction: true,
+            _output: output,
+        })
+    }
+
+    /// Time-stretch the audio to keep its pitch away from normal speed;
+    /// without it the audio is only heard at normal speed
+    pub fn with_pitch_correction(mut self, pitch_correction: bool) -> Self {
+        self.pitch_correction = pitch_correction;
+        self
+    }
+
+    /// Whether the audio is heard at `speed`
+    pub fn audible_at(&self, speed: f64) -> bool {
+        speed == 1.0 || (self.pitch_correction && STRETCH_RANGE.contains(&speed))
+    }
+
+    /// Media time of the next sample going to the device, unless the audio
+    /// is still being refilled after a seek
+    pub fn clock(&self) -> Option<f64> {
+        self.queue.lock().ok()?.time
+    }
+
+    pub fn set_playing(&self, playing: bool) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.playing = playing;
+        }
+    }
+
+    /// Continue the audio from `position` seconds
+    pub fn seek(&self, position: f64) {
+        let _ = self.seeks.send(position);
+    }
+
+    /// Keep the audio with the picture at `position` playing at `speed`,
+    /// playing only if `playing` and audible at that speed. Returns how far
+    /// the picture is ahead of the audio, in media seconds, when the two are
+    /// close enough to correct by timing.
+    pub fn sync(&self, position: f64, speed: f64, playing: bool) -> Option<f64> {
+        let playing = playing && self.audible_at(speed);
+        self.set_playing(playing);
+        if !playing {
+            return None;
+        }
+        // Audio queued at another speed is restretched from the picture
+        let changed = match self.queue.lock() {
+            Ok(mut queue) if queue.speed != speed => {
+                queue.speed = speed;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            debug!("Audio speed changed to {:.2}x", speed);
+            self.seek(position);
+            return None;
+        }
+        let drift = position - self.clock()?;
+        if drift.abs() > RESYNC_THRESHOLD {
+            debug!("Audio is {:.2}s off, seeking to {:.2}s", drift, position);
+            self.seek(position);
+            return None;
+        }
+        Some(drift)
+    }
+}
+
+/// What the output device plays
+#[derive(Debug, Clone, Copy)]
+struct OutputFormat {
+    rate: u32,
+    channels: u16,
+}
+
+/// Decodes and resamples the audio stream into the queue
+struct AudioDecoder {
+    input: PathBuf,
+    input_context: ffmpeg::format::context::Input,
+    stream_index: usize,
+    time_base: f64,
+    decoder: ffmpeg::codec::decoder::Audio,
+    format: OutputFormat,
+}
+
+impl AudioDecoder {
+    fn new(input: &Path, format: OutputFormat) -> Result<Self> {
+        let _ = ffmpeg::init();
+        let input_context = ffmpeg::format::input(&input)
+            .map_err(|e| anyhow!("Failed to open '{}': {}", input.display(), e))?;
+        let stream = input_context
+            .streams()
+            .best(ffmpeg::media::Type::Audio)
+            .ok_or_else(|| anyhow!("No audio stream found in '{}'", input.display()))?;
+        let stream_index = stream.index();
+        let time_base = f64::from(stream.time_base());
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().audio())
+            .map_err(|e| anyhow!("Failed to create audio decoder: {}", e))?;
+
+        Ok(Self {
+            input: input.to_path_buf(),
+            input_context,
+            stream_index,
+            time_base,
+            decoder,
+            format,
+        })
+    }
+
+    /// Keep the queue a second ahead until the player is dropped
+    fn run(mut self, queue: &Mutex<SampleQueue>, seeks: &Receiver<f64>) -> Result<()> {
+        let capacity =
+            (BUFFER_SECONDS * self.format.rate as f64) as usize * self.format.channels as usize;
+        let mut resampler: Option<ffmpeg::software::resampling::Context> = None;
+        let mut discard_before: Option<f64> = None;
+        let mut stretch: Option<TimeStretch> = None;
+        let mut finished = false;
+
+        loop {
+            // Only the latest of several seeks matters
+            let mut target = None;
+            loop {
+                match seeks.try_recv() {
+                    Ok(position) => target = Some(position),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            if let Some(target) = target {
+                let timestamp = (target.max(0.0) * ffmpeg::ffi::AV_TIME_BASE as f64) as i64;
+                self.input_context
+                    .seek(timestamp, ..timestamp)
+                    .map_err(|e| anyhow!("Failed to seek audio to {:.2}s: {}", target, e))?;
+                self.decoder.flush();
+                let mut queue = lock(queue)?;
+                queue.clear();
+                // Seeks follow every speed change, so the stretch starts over
+                stretch = (queue.speed != 1.0)
+                    .then(|| TimeStretch::new(self.format.rate, self.format.channels, queue.speed));
+                drop(queue);
+                discard_before = Some(target);
+                finished = false;
+            }
+
+            if finished || lock(queue)?.samples.len() >= capacity {
+                thread::sleep(REFILL_INTERVAL);
+                continue;
+            }
+
+            match self.input_context.packets().next() {
+                Some((stream, packet)) => {
+                    if stream.index() != self.stream_index {
+                        continue;
+                    }
+                    if let Err(e) = self.decoder.send_packet(&packet) {
+                        debug!("Skipping audio packet: {}", e);
+                        continue;
+                    }
+                }
+                None => {
+                    debug!("Audio of '{}' decoded to the end", self.input.display());
+                    let _ = self.decoder.send_eof();
+                    finished = true;
+                }
+            }
+
+            let mut decoded = ffmpeg::frame::Audio::empty();
+            while self.decoder.receive_frame(&mut decoded).is_ok() {
+                let resampler = match resampler {
+                    Some(ref mut resampler) => resampler,
+                    None => resampler.insert(
+                        decoded
+                            .resampler(
+                                ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Packed),
+                                ffmpeg::ChannelLayout::default(i32::from(self.format.channels)),
+                                self.format.rate,
+                            )
+                            .map_err(|e| anyhow!("Failed to create audio resampler: {}", e))?,
+                    ),
+                };
+                let mut converted = ffmpeg::frame::Audio::empty();
+                resampler
+                    .run(&decoded, &mut converted)
+                    .map_err(|e| anyhow!("Failed to resample audio: {}", e))?;
+
+                let channels = usize::from(self.format.channels);
+                let samples: Vec<f32> = converted
+                    .data(0)
+                    .chunks_exact(4)
+                    .take(converted.samples() * channels)
+                    .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+                let time = decoded.pts().map_or(0.0, |pts| pts as f64 * self.time_base);
+
+                // Drop what lies before a seek target, which is usually
+                // inside the first frame after the keyframe seeked to
+                let skip = match discard_before {
+                    Some(target) => {
+                        let frames = samples.len() / channels.max(1);
+                        let end = time + frames as f64 / self.format.rate as f64;
+                        if end <= target {
+                            continue;
+                        }
+                        discard_before = None;
+                        ((target - time).max(0.0) * self.format.rate as f64) as usize
+                    }
+                    None => 0,
+                };
+                let start = time + skip as f64 / self.format.rate as f64;
+                let samples = match stretch {
+                    Some(ref mut stretch) => {
+                        stretch.process(&samples[(skip * channels).min(samples.len())..])
+                    }
+                    None => samples.into_iter().skip(skip * channels).collect(),
+                };
+                lock(queue)?.push(start, samples);
+            }
+        }
+    }
+}
+
+fn lock(queue: &Mutex<SampleQueue>) -> Result<std::sync::MutexGuard<'_, SampleQueue>> {
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Audio output thread panicked"))
+}
+
+#[cfg(feature = "audio")]
+mod output {
+    use super::{OutputFormat, SampleQueue};
+    use anyhow::{anyhow, Result};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, SampleFormat, SizedSample, StreamConfig};
+    use log::warn;
+    use std::sync::{Arc, Mutex};
+
+    pub type Output = cpal::Stream;
+
+    /// Start a stream on the default output device playing from `queue`
+    pub fn open(queue: Arc<Mutex<SampleQueue>>) -> Result<(Output, OutputFormat)> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| anyhow!("No audio output device"))?;
+        let supported = device
+            .default_output_config()
+            .map_err(|e| anyhow!("No usable audio output format: {}", e))?;
+        let config = supported.config();
+        let format = OutputFormat {
+            rate: config.sample_rate.0,
+            channels: config.channels,
+        };
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build::<f32>(&device, &config, queue),
+            SampleFormat::I16 => build::<i16>(&device, &config, queue),
+            SampleFormat::U16 => build::<u16>(&device, &config, queue),
+            other => return Err(anyhow!("Unsupported audio output format {:?}", other)),
+        }?;
+        stream
+            .play()
+            .map_err(|e| anyhow!("Failed to start audio output: {}", e))?;
+        Ok((stream, format))
+    }
+
+    fn build<T: SizedSample + FromSample<f32>>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        queue: Arc<Mutex<SampleQueue>>,
+    ) -> Result<Output> {
+        let rate = config.sample_rate.0 as f64 * config.channels as f64;
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _| match queue.lock() {
+                    Ok(mut queue) => queue.fill(data, rate, T::from_sample),
+                    Err(_) => data.fill(T::from_sample(0.0)),
+                },
+                |e| warn!("Audio output error: {}", e),
+                None,
+            )
+            .map_err(|e| anyhow!("Failed to open audio output: {}", e))
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod output {
+    use super::{OutputFormat, SampleQueue};
+    use anyhow::{anyhow, Result};
+    use std::sync::{Arc, Mutex};
+
+    pub type Output = ();
+
+    pub fn open(_queue: Arc<Mutex<SampleQueue>>) -> Result<(Output, OutputFormat)> {
+        Err(anyhow!("built without the audio feature"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_queue() {
+        let mut queue = SampleQueue::default();
+        let mut output = [1.0f32; 4];
+        // Starved or paused, the device gets silence and the clock stands still
+        queue.fill(&mut output, 2.0, |sample| sample);
+        assert_eq!(output, [0.0; 4]);
+
+        queue.push(10.0, [0.1, 0.2, 0.3]);
+        queue.push(99.0, [0.4]);
+        queue.fill(&mut output, 2.0, |sample| sample);
+        assert_eq!(queue.time, Some(10.0));
+
+        queue.playing = true;
+        let mut output = [1.0f32; 3];
+        queue.fill(&mut output, 2.0, |sample| sample);
+        assert_eq!(output, [0.1, 0.2, 0.3]);
+        assert_eq!(queue.time, Some(11.5));
+
+        // Running dry mid-buffer pads with silence
+        queue.fill(&mut output, 2.0, |sample| sample);
+        assert_eq!(output, [0.4, 0.0, 0.0]);
+        assert_eq!(queue.time, Some(12.0));
+
+        // Stretched samples cover more media time each; speed changes come
+        // with a seek, which clears the queue
+        queue.speed = 2.0;
+        queue.clear();
+        queue.push(20.0, [0.5, 0.5]);
+        queue.fill(&mut output, 2.0, |sample| sample);
+        assert_eq!(queue.time, Some(22.0));
+
+        queue.clear();
+        assert_eq!(queue.time, None);
+        assert_eq!(queue.speed, 2.0);
+    }
+
+    #[test]
+    fn test_drift_correction() {
+        let frame = Duration::from_millis(250);
+        assert_eq!(corrected_delay(frame, 0.0), frame);
+        assert_eq!(corrected_delay(frame, 0.125), Duration::from_millis(375));
+        // Corrections are capped at one frame either way
+        assert_eq!(corrected_delay(frame, 1.0), Duration::from_millis(500));
+        assert_eq!(corrected_delay(frame, -1.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,183 @@
+//! Character ramps fitted to each scene
+//!
+//! Footage that stays dark (a night scene) or bright (snow, a whiteboard)
+//! only ever reaches a few glyphs of a ramp laid out over the full
+//! luminance range, so most of the picture is drawn with two or three
+//! characters. `--auto-ramp` measures the luminance distribution of every
+//! frame and spreads the ramp over the range the scene actually uses, from
+//! its [`LOW_PERCENTILE`] to its [`HIGH_PERCENTILE`]. The range is fitted
+//! again at each scene change, found from the luminance histogram as
+//! `--describe` does, and otherwise only follows the picture once it has
+//! drifted by more than [`HYSTERESIS`], so glyphs don't flicker as the
+//! exposure wobbles.
+
+/// Share of the pixels left below and above the fitted range
+const LOW_PERCENTILE: f64 = 0.02;
+const HIGH_PERCENTILE: f64 = 0.98;
+
+/// Luminance steps an edge of the range must drift before it follows
+pub const HYSTERESIS: u8 = 16;
+
+/// Narrowest range the ramp is spread over, so flat scenes don't turn
+/// noise into glyphs
+const MIN_SPAN: u8 = 64;
+
+/// Coarse histogram compared between frames to find scene changes
+const SCENE_BINS: usize = 16;
+
+/// Share of the coarse histogram that must change for a new scene
+const SCENE_CHANGE_THRESHOLD: f64 = 0.4;
+
+/// Luminance range the ramp is spread over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RampWindow {
+    pub low: u8,
+    pub high: u8,
+}
+
+impl RampWindow {
+    /// Range from `low` to `high`, widened evenly to at least [`MIN_SPAN`]
+    fn new(low: u8, high: u8) -> Self {
+        let (low, high) = (low.min(high) as i32, low.max(high) as i32);
+        let missing = (MIN_SPAN as i32 - (high - low)).max(0);
+        let low = (low - missing / 2).clamp(0, 255 - MIN_SPAN as i32);
+        let high = (low + MIN_SPAN as i32).max(high).min(255);
+        Self {
+            low: low as u8,
+            high: high as u8,
+        }
+    }
+
+    /// Where `luminance` falls on the full ramp once the window is spread
+    /// over it
+    pub fn map(&self, luminance: u8) -> u8 {
+        let span = (self.high - self.low).max(1) as u32;
+        let offset = luminance.clamp(self.low, self.high) - self.low;
+        ((offset as u32 * 255 + span / 2) / span) as u8
+    }
+}
+
+/// Fits the ramp window frame by frame
+#[derive(Debug, Default)]
+pub struct AutoRamp {
+    /// Coarse histogram of the previous frame
+    previous: Option<[f64; SCENE_BINS]>,
+    window: Option<RampWindow>,
+    scenes: u64,
+}
+
+impl AutoRamp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Window for a frame with the luminance values `luma`
+    pub fn update(&mut self, luma: impl IntoIterator<Item = u8>) -> RampWindow {
+        let mut histogram = [0u32; 256];
+        let mut total = 0u32;
+        for value in luma {
+            histogram[value as usize] += 1;
+            total += 1;
+        }
+        if total == 0 {
+            return self.window.unwrap_or(RampWindow::new(0, 255));
+        }
+
+        let mut coarse = [0.0; SCENE_BINS];
+        for (value, &count) in histogram.iter().enumerate() {
+            coarse[value * SCENE_BINS / 256] += count as f64 / total as f64;
+        }
+        let scene_change = self.previous.is_none_or(|previous| {
+            let change: f64 = coarse
+                .iter()
+                .zip(&previous)
+                .map(|(a, b)| (a - b).abs())
+                .sum();
+            change / 2.0 > SCENE_CHANGE_THRESHOLD
+        });
+        self.previous = Some(coarse);
+
+        let percentile = |share: f64| {
+            let wanted = (share * total as f64).ceil().max(1.0) as u32;
+            let mut seen = 0;
+            for (value, &count) in histogram.iter().enumerate() {
+                seen += count;
+                if seen >= wanted {
+                    return value as u8;
+                }
+            }
+            255
+        };
+        let (low, high) = (percentile(LOW_PERCENTILE), percentile(HIGH_PERCENTILE));
+
+        let window = match self.window {
+            Some(current) if !scene_change => {
+                let follow = |edge: u8, target: u8| {
+                    if edge.abs_diff(target) > HYSTERESIS {
+                        target
+                    } else {
+                        edge
+                    }
+                };
+                let fitted = RampWindow::new(low, high);
+                RampWindow::new(
+                    follow(current.low, fitted.low),
+                    follow(current.high, fitted.high),
+                )
+            }
+            _ => {
+                self.scenes += 1;
+                RampWindow::new(low, high)
+            }
+        };
+        self.window = Some(window);
+        window
+    }
+
+    /// Scenes the ramp has been fitted to so far
+    pub fn scenes(&self) -> u64 {
+        self.scenes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Luminance values spread evenly from `low` to `high`
+    fn gradient(low: u8, high: u8) -> Vec<u8> {
+        (0..1000)
+            .map(|i| low + ((high - low) as u32 * i / 999) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_dark_scene_spreads_over_the_ramp() {
+        let mut ramp = AutoRamp::new();
+        let window = ramp.update(gradient(0, 80));
+        assert_eq!(window, RampWindow { low: 1, high: 78 });
+        assert_eq!(window.map(0), 0);
+        assert_eq!(window.map(40), 129);
+        assert_eq!(window.map(78), 255);
+        assert_eq!(window.map(200), 255);
+
+        // A nearly flat scene keeps the minimum span
+        let window = RampWindow::new(120, 124);
+        assert_eq!(window.high - window.low, MIN_SPAN);
+        assert!(window.low <= 120 && window.high >= 124);
+    }
+
+    #[test]
+    fn test_hysteresis_and_scene_changes() {
+        let mut ramp = AutoRamp::new();
+        let first = ramp.update(gradient(0, 80));
+        // Small drift within the scene leaves the window alone
+        assert_eq!(ramp.update(gradient(4, 86)), first);
+        assert_eq!(ramp.scenes(), 1);
+
+        // A cut to a bright scene refits at once
+        let bright = ramp.update(gradient(160, 255));
+        assert_eq!(ramp.scenes(), 2);
+        assert!(bright.low > 150 && bright.high > 250);
+    }
+}
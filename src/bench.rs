@@ -0,0 +1,202 @@
+//! Frame assembly benchmark
+//!
+//! `ascii-player bench` draws synthetic color frames the way the renderer
+//! does, into a sink that counts bytes and write calls instead of a
+//! terminal, so terminal speed does not enter into it. Two ways of building
+//! a frame are timed:
+//!
+//! - per cell: a cursor move, both colors and the glyph queued for every
+//!   cell onto line-buffered output, as stdout is
+//! - buffered: the renderer's frame buffer, which skips escapes that repeat
+//!   what the previous cell set and is written with one `write_all`
+
+use crate::converter::AsciiFrame;
+use crate::renderer::Renderer;
+use anyhow::Result;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use std::io::{self, LineWriter, Write};
+use std::time::{Duration, Instant};
+
+/// Glyphs of the synthetic frames
+const RAMP: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// Timing of one way of assembling frames
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub frames: u64,
+    pub elapsed: Duration,
+    /// Bytes and write calls that reached the output
+    pub bytes: u64,
+    pub writes: u64,
+}
+
+impl BenchResult {
+    /// Average time per frame in milliseconds
+    pub fn ms_per_frame(&self) -> f64 {
+        self.elapsed.as_secs_f64() * 1000.0 / self.frames.max(1) as f64
+    }
+}
+
+/// Output that only counts what is written to it
+#[derive(Debug, Default)]
+struct CountingSink {
+    bytes: u64,
+    writes: u64,
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len() as u64;
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Assemble `frames` frames of `width` x `height` cells both ways
+pub fn bench_assembly(width: u16, height: u16, frames: u64) -> Result<Vec<BenchResult>> {
+    if frames == 0 {
+        return Ok(Vec::new());
+    }
+    // Built up front, so only assembly is timed
+    let synthetic: Vec<AsciiFrame> = (0..frames.min(64))
+        .map(|n| synthetic_frame(width, height, n))
+        .collect();
+    let frame = |n: u64| &synthetic[(n % synthetic.len() as u64) as usize];
+
+    let mut output = LineWriter::new(CountingSink::default());
+    let started = Instant::now();
+    for n in 0..frames {
+        queue_per_cell(&mut output, frame(n))?;
+        output.flush()?;
+    }
+    let elapsed = started.elapsed();
+    let sink = output.get_ref();
+    let per_cell = BenchResult {
+        name: "per cell",
+        frames,
+        elapsed,
+        bytes: sink.bytes,
+        writes: sink.writes,
+    };
+
+    let mut renderer = Renderer::new(false, true)?.with_size(Some(width), Some(height));
+    let mut sink = CountingSink::default();
+    let started = Instant::now();
+    for n in 0..frames {
+        sink.write_all(renderer.encode_frame(frame(n))?)?;
+    }
+    let buffered = BenchResult {
+        name: "buffered",
+        frames,
+        elapsed: started.elapsed(),
+        bytes: sink.bytes,
+        writes: sink.writes,
+    };
+
+    Ok(vec![per_cell, buffered])
+}
+
+/// Queue every command of every cell, the simple way
+fn queue_per_cell<W: Write>(output: &mut W, frame: &AsciiFrame) -> Result<()> {
+    queue!(output, Clear(ClearType::All))?;
+    let width = frame.width.max(1) as usize;
+    for (index, &character) in frame.characters.iter().enumerate() {
+        let (r, g, b) = frame.fg_colors[index];
+        queue!(
+            output,
+            MoveTo((index % width) as u16, (index / width) as u16),
+            SetForegroundColor(Color::Rgb { r, g, b })
+        )?;
+        if let Some(&(r, g, b)) = frame.bg_colors.as_ref().and_then(|bg| bg.get(index)) {
+            queue!(output, SetBackgroundColor(Color::Rgb { r, g, b }))?;
+        }
+        queue!(output, Print(character))?;
+    }
+    queue!(output, ResetColor)?;
+    Ok(())
+}
+
+/// A frame of moving gradients; neighbouring cells often share colors,
+/// as in video
+fn synthetic_frame(width: u16, height: u16, n: u64) -> AsciiFrame {
+    let cells = width as usize * height as usize;
+    let mut characters = Vec::with_capacity(cells);
+    let mut fg_colors = Vec::with_capacity(cells);
+    let mut bg_colors = Vec::with_capacity(cells);
+    for y in 0..height as u64 {
+        for x in 0..width as u64 {
+            let level = (x / 4 + y / 2 + n) as usize;
+            characters.push(RAMP[level % RAMP.len()]);
+            let fg = (
+                (x / 4 * 16 + n) as u8,
+                (y / 2 * 16) as u8,
+                (level * 8) as u8,
+            );
+            fg_colors.push(fg);
+            bg_colors.push((fg.0 / 4, fg.1 / 4, fg.2 / 4));
+        }
+    }
+    let mut frame = AsciiFrame::new(width, height, characters, fg_colors);
+    frame.bg_colors = Some(bg_colors);
+    frame.frame_number = n;
+    frame
+}
+
+/// The results as a table, with the buffered speedup
+pub fn bench_report(width: u16, height: u16, results: &[BenchResult]) -> String {
+    let mut report = format!(
+        "Frame assembly, {}x{} cells, {} frames:\n",
+        width,
+        height,
+        results.first().map_or(0, |result| result.frames)
+    );
+    for result in results {
+        let frames = result.frames.max(1);
+        report.push_str(&format!(
+            "  {:<10} {:>8.3} ms/frame {:>9} bytes/frame {:>6.1} writes/frame\n",
+            result.name,
+            result.ms_per_frame(),
+            result.bytes / frames,
+            result.writes as f64 / frames as f64
+        ));
+    }
+    if let [per_cell, buffered] = results {
+        if buffered.elapsed > Duration::ZERO {
+            report.push_str(&format!(
+                "Buffered is {:.1}x as fast, with {:.0}% of the bytes\n",
+                per_cell.elapsed.as_secs_f64() / buffered.elapsed.as_secs_f64(),
+                buffered.bytes as f64 * 100.0 / per_cell.bytes.max(1) as f64
+            ));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_assembly() {
+        let results = bench_assembly(40, 10, 5).unwrap();
+        let [per_cell, buffered] = &results[..] else {
+            panic!("expected two results");
+        };
+        assert_eq!(per_cell.frames, 5);
+        // One write per frame, and less to write
+        assert_eq!(buffered.writes, 5);
+        assert!(per_cell.writes > buffered.writes);
+        assert!(buffered.bytes < per_cell.bytes);
+        assert!(bench_report(40, 10, &results).contains("buffered"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Timeline bookmarks
+//!
+//! During playback `b` asks for a name and bookmarks the current moment, and
+//! `j`/`k` jump to the next and previous bookmark. Bookmarks are kept per
+//! input in the config file, keyed by [`source_location`], and
+//! `ascii-player bookmarks FILE` lists them or prints them as JSON.
+//!
+//! Bookmarks and notes (see [`crate::annotations`]) are both [`TimedMark`]s,
+//! sharing how they are kept in order, stepped through and listed.
+
+use crate::config::Config;
+use crate::decoder::source_location;
+use crate::status::format_clock;
+use crate::textinput::{InputResult, TextInput};
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Within this many seconds after a mark, going back goes to the one before
+/// it instead of back to its start
+const PREVIOUS_SLACK: f64 = 1.0;
+
+/// Marks closer than this to the current position are not "next"
+const NEXT_SLACK: f64 = 0.01;
+
+/// A moment of a video with a label: a bookmark or a note
+pub trait TimedMark {
+    /// Seconds into the video
+    fn time(&self) -> f64;
+    /// Name or text of the mark
+    fn label(&self) -> &str;
+}
+
+/// First mark after `position`
+pub fn next_mark<T: TimedMark>(marks: &[T], position: f64) -> Option<&T> {
+    marks
+        .iter()
+        .find(|mark| mark.time() > position + NEXT_SLACK)
+}
+
+/// Last mark before `position`, skipping one that was just passed
+pub fn previous_mark<T: TimedMark>(marks: &[T], position: f64) -> Option<&T> {
+    marks
+        .iter()
+        .rev()
+        .find(|mark| mark.time() < position - PREVIOUS_SLACK)
+}
+
+/// The mark to jump to from `position`, and how to announce it
+pub fn jump_to_mark<T: TimedMark>(
+    marks: &[T],
+    position: f64,
+    forward: bool,
+) -> Option<(f64, String)> {
+    let mark = if forward {
+        next_mark(marks, position)
+    } else {
+        previous_mark(marks, position)
+    }?;
+    Some((
+        mark.time(),
+        format!("{} ({})", mark.label(), format_clock(mark.time())),
+    ))
+}
+
+/// Add `mark` to `marks`, keeping them in timeline order
+pub fn insert_mark<T: TimedMark>(marks: &mut Vec<T>, mark: T) {
+    let index = marks.partition_point(|existing| existing.time() <= mark.time());
+    marks.insert(index, mark);
+}
+
+/// Marks as text, one `time  label` line each
+pub fn mark_lines<T: TimedMark>(marks: &[T]) -> String {
+    let mut output = String::new();
+    for mark in marks {
+        let _ = writeln!(output, "{:>8}  {}", format_clock(mark.time()), mark.label());
+    }
+    output
+}
+
+/// A named moment in a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Seconds into the video
+    pub time: f64,
+    pub name: String,
+}
+
+impl TimedMark for Bookmark {
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn label(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Name being typed for a new bookmark
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkPrompt {
+    time: f64,
+    input: TextInput,
+    /// Name used if none is typed
+    default_name: String,
+}
+
+impl BookmarkPrompt {
+    /// Start naming a bookmark at `time`; `existing` is how many the video
+    /// already has
+    pub fn new(time: f64, existing: usize) -> Self {
+        Self {
+            time,
+            input: TextInput::new(),
+            default_name: format!("Bookmark {}", existing + 1),
+        }
+    }
+
+    /// Enter saves, Esc cancels, anything else edits the name
+    pub fn handle_key(&mut self, code: KeyCode) -> InputResult {
+        self.input.handle_key(code)
+    }
+
+    /// The bookmark named so far, with the default name if none was typed
+    pub fn bookmark(&self) -> Bookmark {
+        let name = self.input.text();
+        let name = name.trim();
+        Bookmark {
+            time: self.time,
+            name: if name.is_empty() {
+                self.default_name.clone()
+            } else {
+                name.to_string()
+            },
+        }
+    }
+
+    /// Prompt text for the status line, with an ASCII cursor like the rest
+    /// of the line
+    pub fn status(&self) -> String {
+        let name = if self.input.is_empty() {
+            format!("{}_", self.default_name)
+        } else {
+            self.input.visible_with(usize::MAX, '_')
+        };
+        format!(
+            "Bookmark {} as: {} (Enter to save, Esc to cancel)",
+            format_clock(self.time),
+            name
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct BookmarkExport<'a> {
+    file: String,
+    bookmarks: &'a [Bookmark],
+}
+
+/// Bookmarks of `input` as text, one `time  name` line each, or as JSON
+pub fn list_bookmarks(config: &Config, input: &Path, json: bool) -> Result<String> {
+    let bookmarks = config.bookmarks(input);
+    if json {
+        let export = BookmarkExport {
+            file: source_location(input),
+            bookmarks,
+        };
+        return Ok(serde_json::to_string_pretty(&export)? + "\n");
+    }
+
+    if bookmarks.is_empty() {
+        return Ok(format!("No bookmarks for {}\n", input.display()));
+    }
+    Ok(mark_lines(bookmarks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(time: f64, name: &str) -> Bookmark {
+        Bookmark {
+            time,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bookmark_navigation() {
+        let mut config = Config::default();
+        let input = Path::new("https://example.com/talk.mp4");
+        config.add_bookmark(input, bookmark(90.0, "demo"));
+        config.add_bookmark(input, bookmark(10.0, "intro"));
+        let bookmarks = config.bookmarks(input);
+        assert_eq!(bookmarks[0].name, "intro");
+
+        assert_eq!(next_mark(bookmarks, 0.0).unwrap().name, "intro");
+        assert_eq!(next_mark(bookmarks, 10.0).unwrap().name, "demo");
+        assert!(next_mark(bookmarks, 90.0).is_none());
+        // Just past a bookmark, back means the one before it
+        assert_eq!(previous_mark(bookmarks, 90.5).unwrap().name, "intro");
+        assert_eq!(previous_mark(bookmarks, 95.0).unwrap().name, "demo");
+        assert_eq!(
+            jump_to_mark(bookmarks, 95.0, false),
+            Some((90.0, "demo (01:30)".to_string()))
+        );
+
+        let listed = list_bookmarks(&config, input, false).unwrap();
+        assert_eq!(listed, "   00:10  intro\n   01:30  demo\n");
+        let json: serde_json::Value =
+            serde_json::from_str(&list_bookmarks(&config, input, true).unwrap()).unwrap();
+        assert_eq!(json["file"], "https://example.com/talk.mp4");
+        assert_eq!(json["bookmarks"][1]["time"], 90.0);
+    }
+
+    #[test]
+    fn test_bookmark_prompt() {
+        let mut prompt = BookmarkPrompt::new(75.0, 2);
+        assert!(prompt.status().contains("Bookmark 3"));
+        for c in "Demox".chars() {
+            prompt.handle_key(KeyCode::Char(c));
+        }
+        prompt.handle_key(KeyCode::Backspace);
+        // The name is edited at the cursor
+        prompt.handle_key(KeyCode::Home);
+        prompt.handle_key(KeyCode::Delete);
+        prompt.handle_key(KeyCode::Char('M'));
+        assert!(prompt.status().contains("as: M_mo "));
+        assert_eq!(prompt.handle_key(KeyCode::Enter), InputResult::Submitted);
+        assert_eq!(prompt.bookmark(), bookmark(75.0, "Memo"));
+
+        let mut unnamed = BookmarkPrompt::new(5.0, 0);
+        assert_eq!(unnamed.handle_key(KeyCode::Enter), InputResult::Submitted);
+        assert_eq!(unnamed.bookmark(), bookmark(5.0, "Bookmark 1"));
+        assert_eq!(unnamed.handle_key(KeyCode::Esc), InputResult::Cancelled);
+    }
+}
@@ -0,0 +1,620 @@
+//! Memory budget and frame caches
+//!
+//! `--max-mem` sets one budget shared by every cache that holds converted
+//! frames. The decoder and renderer only keep the frame in flight, so the
+//! budget is spent by caches: currently the loop cache, which lets passes of a
+//! looping video after the first skip decoding and conversion. When a cache
+//! needs room, its least recently used frames are written to a spill file if
+//! `--spill-dir` is set, and dropped otherwise.
+
+use crate::converter::{AsciiFrame, CellAttribute};
+use crate::recording::{RecordEntry, RecordingReader, RecordingWriter};
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Budget used when `--max-mem` is not given
+pub const DEFAULT_MAX_MEM: u64 = 256 * 1024 * 1024;
+
+/// A size in bytes, parsed from `512M`, `1.5G`, `64KiB` or a plain number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| anyhow!("Invalid size '{}'", s))?;
+
+        let unit = unit.trim().to_ascii_uppercase();
+        let unit = unit
+            .strip_suffix("IB")
+            .or_else(|| unit.strip_suffix('B'))
+            .unwrap_or(&unit);
+        let multiplier: u64 = match unit {
+            "" => 1,
+            "K" => 1 << 10,
+            "M" => 1 << 20,
+            "G" => 1 << 30,
+            "T" => 1 << 40,
+            _ => return Err(anyhow!("Unknown size unit in '{}', expected K, M or G", s)),
+        };
+        Ok(Self((number * multiplier as f64).round() as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Bytes of memory shared by all caches
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    used: AtomicU64,
+}
+
+impl MemoryBudget {
+    /// A budget of `limit` bytes to share between caches
+    pub fn new(limit: u64) -> Arc<Self> {
+        Arc::new(Self {
+            limit,
+            used: AtomicU64::new(0),
+        })
+    }
+
+    /// Total bytes caches may hold
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Bytes currently held
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Take `bytes` from the budget if they fit
+    pub fn try_reserve(&self, bytes: u64) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|&total| total <= self.limit)
+            })
+            .is_ok()
+    }
+
+    /// Return bytes taken with `try_reserve`
+    pub fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// Memory a converted frame occupies
+pub fn frame_size(frame: &AsciiFrame) -> u64 {
+    let cells = frame.characters.capacity() * std::mem::size_of::<char>()
+        + frame.fg_colors.capacity() * 3
+        + frame.bg_colors.as_ref().map_or(0, |bg| bg.capacity() * 3)
+        + frame.attributes.as_ref().map_or(0, |attributes| {
+            attributes.capacity() * std::mem::size_of::<CellAttribute>()
+        });
+    (std::mem::size_of::<AsciiFrame>() + cells) as u64
+}
+
+/// Append-only file holding frames evicted from memory
+struct SpillFile {
+    file: File,
+    path: PathBuf,
+    len: u64,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let path = dir.join(format!(
+            "ascii-player-{}-{}.spill",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create spill file {}", path.display()))?;
+        Ok(Self { file, path, len: 0 })
+    }
+
+    /// Store a frame as a one-frame recording; returns its offset and length
+    fn write(&mut self, frame: &AsciiFrame) -> Result<(u64, usize)> {
+        let mut bytes = Vec::new();
+        let mut writer = RecordingWriter::new(&mut bytes, 0.0)?;
+        writer.write_frame(frame)?;
+        writer.finish()?;
+
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&bytes)?;
+        self.len += bytes.len() as u64;
+        Ok((offset, bytes.len()))
+    }
+
+    fn read(&mut self, offset: u64, length: usize) -> Result<AsciiFrame> {
+        let mut bytes = vec![0u8; length];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut bytes)?;
+        match RecordingReader::new(bytes.as_slice())?.next_entry()? {
+            Some(RecordEntry::Frame(frame)) => Ok(frame),
+            _ => Err(anyhow!("Corrupt spill file {}", self.path.display())),
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+struct CacheEntry {
+    frame: Option<AsciiFrame>,
+    /// Position in the LRU order while the frame is in memory
+    last_used: u64,
+    spilled: Option<(u64, usize)>,
+}
+
+/// Frames by key, held within a shared memory budget with LRU eviction
+pub struct FrameCache {
+    budget: Arc<MemoryBudget>,
+    entries: HashMap<u64, CacheEntry>,
+    /// In-memory keys ordered from least to most recently used
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    held: u64,
+    dropped: u64,
+    spill: Option<SpillFile>,
+}
+
+impl FrameCache {
+    /// An empty cache drawing on `budget`
+    pub fn new(budget: Arc<MemoryBudget>) -> Self {
+        Self {
+            budget,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            held: 0,
+            dropped: 0,
+            spill: None,
+        }
+    }
+
+    /// Write evicted frames to a file in `dir` instead of dropping them
+    pub fn with_spill(mut self, dir: &Path) -> Result<Self> {
+        self.spill = Some(SpillFile::create(dir)?);
+        Ok(self)
+    }
+
+    /// Number of frames stored, in memory or spilled
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of the budget this cache holds
+    pub fn memory(&self) -> u64 {
+        self.held
+    }
+
+    /// Frames evicted without a spill file to keep them
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Store a frame, evicting others if needed; returns whether it was kept
+    pub fn insert(&mut self, key: u64, frame: AsciiFrame) -> Result<bool> {
+        self.remove(key);
+        let size = frame_size(&frame);
+        if !self.make_room(size)? {
+            if let Some(spill) = self.spill.as_mut() {
+                let spilled = spill.write(&frame)?;
+                self.entries.insert(
+                    key,
+                    CacheEntry {
+                        frame: None,
+                        last_used: 0,
+                        spilled: Some(spilled),
+                    },
+                );
+                return Ok(true);
+            }
+            self.dropped += 1;
+            return Ok(false);
+        }
+
+        self.held += size;
+        let last_used = self.touch(key);
+        self.entries.insert(
+            key,
+            CacheEntry {
+                frame: Some(frame),
+                last_used,
+                spilled: None,
+            },
+        );
+        Ok(true)
+    }
+
+    /// Look up a frame, reading it back from the spill file if needed
+    pub fn get(&mut self, key: u64) -> Result<Option<AsciiFrame>> {
+        let Some(entry) = self.entries.get(&key) else {
+            return Ok(None);
+        };
+
+        if entry.frame.is_some() {
+            self.lru.remove(&entry.last_used);
+            let last_used = self.touch(key);
+            let entry = self.entries.get_mut(&key).expect("entry exists");
+            entry.last_used = last_used;
+            return Ok(entry.frame.clone());
+        }
+
+        let Some((offset, length)) = entry.spilled else {
+            return Ok(None);
+        };
+        let frame = match self.spill.as_mut() {
+            Some(spill) => spill.read(offset, length)?,
+            None => return Ok(None),
+        };
+
+        // Bring it back into memory if that only pushes out other frames
+        let size = frame_size(&frame);
+        if self.make_room(size)? {
+            self.held += size;
+            let last_used = self.touch(key);
+            let entry = self.entries.get_mut(&key).expect("entry exists");
+            entry.frame = Some(frame.clone());
+            entry.last_used = last_used;
+        }
+        Ok(Some(frame))
+    }
+
+    /// Remove every frame and return their memory to the budget
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.budget.release(self.held);
+        self.held = 0;
+        self.dropped = 0;
+        if let Some(ref mut spill) = self.spill {
+            spill.len = 0;
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            if let Some(ref frame) = entry.frame {
+                self.lru.remove(&entry.last_used);
+                let size = frame_size(frame);
+                self.held -= size;
+                self.budget.release(size);
+            }
+        }
+    }
+
+    fn touch(&mut self, key: u64) -> u64 {
+        self.clock += 1;
+        self.lru.insert(self.clock, key);
+        self.clock
+    }
+
+    /// Evict least recently used frames until `bytes` fit in the budget;
+    /// returns false if they cannot fit
+    fn make_room(&mut self, bytes: u64) -> Result<bool> {
+        while !self.budget.try_reserve(bytes) {
+            if !self.evict_one()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn evict_one(&mut self) -> Result<bool> {
+        let Some((_, key)) = self.lru.pop_first() else {
+            return Ok(false);
+        };
+        let entry = self.entries.get_mut(&key).expect("LRU keys are cached");
+        let frame = entry.frame.take().expect("LRU entries are in memory");
+
+        match self.spill.as_mut() {
+            Some(spill) => {
+                if entry.spilled.is_none() {
+                    entry.spilled = Some(spill.write(&frame)?);
+                }
+            }
+            None => {
+                self.entries.remove(&key);
+                self.dropped += 1;
+            }
+        }
+
+        let size = frame_size(&frame);
+        self.held -= size;
+        self.budget.release(size);
+        Ok(true)
+    }
+}
+
+impl Drop for FrameCache {
+    fn drop(&mut self) {
+        self.budget.release(self.held);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoopState {
+    /// Storing the frames of the pass being decoded
+    Recording,
+    /// Holds a whole pass that can be replayed
+    Complete,
+    /// Missed part of the current pass; try again on the next one
+    Incomplete,
+    /// The video does not fit in the budget
+    Disabled,
+}
+
+/// Converted frames of one pass of a looping video
+pub struct LoopCache {
+    frames: FrameCache,
+    len: u64,
+    /// Terminal size the cached frames were converted for
+    grid: Option<(u16, u16)>,
+    state: LoopState,
+}
+
+impl LoopCache {
+    pub fn new(frames: FrameCache) -> Self {
+        Self {
+            frames,
+            len: 0,
+            grid: None,
+            state: LoopState::Recording,
+        }
+    }
+
+    /// Whether a whole pass is cached
+    pub fn is_complete(&self) -> bool {
+        self.state == LoopState::Complete
+    }
+
+    /// Number of frames in the cached pass
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Store frame `index` of the pass being decoded, before any overlays,
+    /// converted for a `grid` sized terminal
+    pub fn record(&mut self, index: u64, frame: &AsciiFrame, grid: (u16, u16)) {
+        if self.state != LoopState::Recording {
+            return;
+        }
+        if index != self.len || *self.grid.get_or_insert(grid) != grid {
+            self.abandon(LoopState::Incomplete);
+            return;
+        }
+
+        match self.frames.insert(index, frame.clone()) {
+            Ok(true) if self.frames.dropped() == 0 => self.len += 1,
+            Ok(_) => {
+                info!("Video does not fit in the memory budget, loops will decode again");
+                self.abandon(LoopState::Disabled);
+            }
+            Err(e) => {
+                warn!("Loop cache failed: {}", e);
+                self.abandon(LoopState::Disabled);
+            }
+        }
+    }
+
+    /// Decoding reached the end of the video; returns whether the next pass
+    /// can be replayed from the cache
+    pub fn finish_pass(&mut self) -> bool {
+        if self.state == LoopState::Recording && self.len > 0 {
+            info!(
+                "Cached {} frames for looping ({} in memory)",
+                self.len,
+                ByteSize(self.frames.memory())
+            );
+            self.state = LoopState::Complete;
+        }
+        self.is_complete()
+    }
+
+    /// Decoding jumped within the pass, so this pass cannot be cached
+    pub fn interrupt(&mut self) {
+        if self.state == LoopState::Recording {
+            self.abandon(LoopState::Incomplete);
+        }
+    }
+
+    /// Decoding starts over from the beginning of the video
+    pub fn restart(&mut self) {
+        if matches!(self.state, LoopState::Recording | LoopState::Incomplete) {
+            self.abandon(LoopState::Recording);
+        }
+    }
+
+    /// Frames are converted differently from now on, so the cached ones
+    /// are dropped; the next pass records again
+    pub fn discard(&mut self) {
+        if matches!(self.state, LoopState::Recording | LoopState::Complete) {
+            self.abandon(LoopState::Incomplete);
+        }
+    }
+
+    /// Frame `index` of the cached pass if it was converted for a `width` x
+    /// `height` terminal. Another terminal size or a frame that cannot be
+    /// read back discards the cache until the next pass.
+    pub fn frame(&mut self, index: u64, width: u16, height: u16) -> Option<AsciiFrame> {
+        if !self.is_complete() || index >= self.len {
+            return None;
+        }
+        if self.grid != Some((width, height)) {
+            self.abandon(LoopState::Incomplete);
+            return None;
+        }
+        match self.frames.get(index) {
+            Ok(Some(frame)) => Some(frame),
+            Ok(_) => {
+                self.abandon(LoopState::Incomplete);
+                None
+            }
+            Err(e) => {
+                warn!("Loop cache failed: {}", e);
+                self.abandon(LoopState::Disabled);
+                None
+            }
+        }
+    }
+
+    fn abandon(&mut self, state: LoopState) {
+        self.frames.clear();
+        self.len = 0;
+        self.grid = None;
+        self.state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(character: char, index: u64) -> AsciiFrame {
+        AsciiFrame {
+            characters: vec![character; 8],
+            fg_colors: vec![(1, 2, 3); 8],
+            bg_colors: None,
+            attributes: None,
+            width: 4,
+            height: 2,
+            timestamp: index as f64 / 10.0,
+            frame_number: index,
+        }
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!("512M".parse::<ByteSize>().unwrap().0, 512 << 20);
+        assert_eq!("1.5g".parse::<ByteSize>().unwrap().0, 3 << 29);
+        assert_eq!("64KiB".parse::<ByteSize>().unwrap().0, 64 << 10);
+        assert_eq!("4096".parse::<ByteSize>().unwrap().0, 4096);
+        assert!("12X".parse::<ByteSize>().is_err());
+        assert!("M".parse::<ByteSize>().is_err());
+        assert_eq!(ByteSize(512 << 20).to_string(), "512.0 MiB");
+    }
+
+    #[test]
+    fn test_lru_eviction_within_budget() {
+        let size = frame_size(&frame('a', 0));
+        // Every per-cell buffer counts
+        let mut styled = frame('a', 0);
+        styled.bg_colors = Some(vec![(0, 0, 0); 8]);
+        styled.attributes = Some(vec![CellAttribute::Bold; 8]);
+        assert_eq!(
+            frame_size(&styled),
+            size + 8 * 3 + 8 * std::mem::size_of::<CellAttribute>() as u64
+        );
+
+        let budget = MemoryBudget::new(size * 2);
+        let mut cache = FrameCache::new(budget.clone());
+
+        cache.insert(0, frame('a', 0)).unwrap();
+        cache.insert(1, frame('b', 1)).unwrap();
+        // Using 0 makes 1 the least recently used
+        assert!(cache.get(0).unwrap().is_some());
+        cache.insert(2, frame('c', 2)).unwrap();
+
+        assert_eq!(cache.get(1).unwrap(), None);
+        assert_eq!(cache.get(0).unwrap(), Some(frame('a', 0)));
+        assert_eq!(cache.dropped(), 1);
+        assert_eq!(budget.used(), size * 2);
+
+        drop(cache);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_spill_keeps_evicted_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let budget = MemoryBudget::new(frame_size(&frame('a', 0)));
+        let mut cache = FrameCache::new(budget.clone())
+            .with_spill(dir.path())
+            .unwrap();
+
+        for (index, character) in ['a', 'b', 'c'].into_iter().enumerate() {
+            assert!(cache
+                .insert(index as u64, frame(character, index as u64))
+                .unwrap());
+        }
+        assert_eq!(cache.dropped(), 0);
+        for (index, character) in ['a', 'b', 'c'].into_iter().enumerate() {
+            assert_eq!(
+                cache.get(index as u64).unwrap(),
+                Some(frame(character, index as u64))
+            );
+        }
+        assert!(budget.used() <= budget.limit());
+    }
+
+    #[test]
+    fn test_loop_cache_gives_up_when_too_large() {
+        let budget = MemoryBudget::new(frame_size(&frame('a', 0)) * 2);
+        let mut cache = LoopCache::new(FrameCache::new(budget.clone()));
+        // Frames are fitted inside the terminal, so they can be smaller
+        cache.record(0, &frame('a', 0), (6, 2));
+        cache.record(1, &frame('b', 1), (6, 2));
+        assert!(cache.finish_pass());
+        assert_eq!(cache.frame(1, 6, 2), Some(frame('b', 1)));
+        // A resized terminal needs new frames
+        assert_eq!(cache.frame(0, 8, 2), None);
+        assert!(!cache.is_complete());
+
+        cache.restart();
+        for index in 0..3 {
+            cache.record(index, &frame('a', index), (6, 2));
+        }
+        assert!(!cache.finish_pass());
+        assert_eq!(budget.used(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Character ramp calibration against font ink coverage
+//!
+//! Ramps are usually ordered by eye, which leaves uneven luminance steps
+//! (several near-identical glyphs followed by a big jump). Calibration sorts
+//! the glyphs by measured ink coverage and keeps the subset whose coverage
+//! steps are closest to uniform.
+
+use anyhow::{anyhow, Result};
+
+/// Ink coverage (fraction of the cell covered) for DejaVu Sans Mono.
+///
+/// Menlo and Bitstream Vera Sans Mono share the same outlines, so this table
+/// is representative for the default fonts of most Linux and macOS terminals.
+/// Covers printable ASCII and the Block Elements range.
+pub const DEJAVU_SANS_MONO_COVERAGE: &[(char, f32)] = &[
+    (' ', 0.000),
+    ('!', 0.085),
+    ('"', 0.068),
+    ('#', 0.240),
+    ('$', 0.213),
+    ('%', 0.184),
+    ('&', 0.231),
+    ('\'', 0.030),
+    ('(', 0.113),
+    (')', 0.113),
+    ('*', 0.098),
+    ('+', 0.114),
+    (',', 0.045),
+    ('-', 0.033),
+    ('.', 0.027),
+    ('/', 0.108),
+    ('0', 0.244),
+    ('1', 0.162),
+    ('2', 0.187),
+    ('3', 0.191),
+    ('4', 0.199),
+    ('5', 0.198),
+    ('6', 0.229),
+    ('7', 0.142),
+    ('8', 0.250),
+    ('9', 0.232),
+    (':', 0.052),
+    (';', 0.070),
+    ('<', 0.119),
+    ('=', 0.123),
+    ('>', 0.119),
+    ('?', 0.126),
+    ('@', 0.276),
+    ('A', 0.217),
+    ('B', 0.276),
+    ('C', 0.164),
+    ('D', 0.245),
+    ('E', 0.215),
+    ('F', 0.178),
+    ('G', 0.217),
+    ('H', 0.241),
+    ('I', 0.175),
+    ('J', 0.158),
+    ('K', 0.231),
+    ('L', 0.148),
+    ('M', 0.273),
+    ('N', 0.264),
+    ('O', 0.235),
+    ('P', 0.201),
+    ('Q', 0.252),
+    ('R', 0.251),
+    ('S', 0.198),
+    ('T', 0.155),
+    ('U', 0.221),
+    ('V', 0.188),
+    ('W', 0.269),
+    ('X', 0.200),
+    ('Y', 0.155),
+    ('Z', 0.196),
+    ('[', 0.127),
+    ('\\', 0.108),
+    (']', 0.127),
+    ('^', 0.071),
+    ('_', 0.033),
+    ('`', 0.023),
+    ('a', 0.194),
+    ('b', 0.222),
+    ('c', 0.130),
+    ('d', 0.221),
+    ('e', 0.189),
+    ('f', 0.152),
+    ('g', 0.241),
+    ('h', 0.193),
+    ('i', 0.141),
+    ('j', 0.146),
+    ('k', 0.201),
+    ('l', 0.127),
+    ('m', 0.216),
+    ('n', 0.166),
+    ('o', 0.176),
+    ('p', 0.219),
+    ('q', 0.215),
+    ('r', 0.107),
+    ('s', 0.150),
+    ('t', 0.147),
+    ('u', 0.164),
+    ('v', 0.141),
+    ('w', 0.189),
+    ('x', 0.148),
+    ('y', 0.173),
+    ('z', 0.148),
+    ('{', 0.151),
+    ('|', 0.114),
+    ('}', 0.151),
+    ('~', 0.061),
+    ('▀', 0.500),
+    ('▁', 0.125),
+    ('▂', 0.250),
+    ('▃', 0.375),
+    ('▄', 0.500),
+    ('▅', 0.625),
+    ('▆', 0.750),
+    ('▇', 0.875),
+    ('█', 1.000),
+    ('▉', 0.883),
+    ('▊', 0.750),
+    ('▋', 0.633),
+    ('▌', 0.500),
+    ('▍', 0.367),
+    ('▎', 0.250),
+    ('▏', 0.117),
+    ('▐', 0.500),
+    ('░', 0.192),
+    ('▒', 0.500),
+    ('▓', 0.821),
+    ('▔', 0.125),
+    ('▕', 0.117),
+    ('▖', 0.250),
+    ('▗', 0.250),
+    ('▘', 0.250),
+    ('▙', 0.750),
+    ('▚', 0.500),
+    ('▛', 0.750),
+    ('▜', 0.750),
+    ('▝', 0.250),
+    ('▞', 0.500),
+    ('▟', 0.750),
+];
+
+/// Glyphs whose coverage differs by less than this are treated as duplicates
+const DUPLICATE_EPSILON: f64 = 0.004;
+
+/// Look up the bundled ink coverage for a character
+pub fn coverage(c: char) -> Option<f64> {
+    DEJAVU_SANS_MONO_COVERAGE
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map(|(_, coverage)| *coverage as f64)
+}
+
+/// A ramp character paired with its measured coverage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibratedGlyph {
+    pub character: char,
+    pub coverage: f64,
+}
+
+/// Reorder and prune a ramp so luminance steps are perceptually uniform.
+///
+/// Characters without coverage data are dropped with a warning. At most
+/// `levels` glyphs are kept; the darkest and brightest glyph are always part
+/// of the result.
+pub fn calibrate_ramp(chars: &[char], levels: usize) -> Result<Vec<CalibratedGlyph>> {
+    if levels < 2 {
+        return Err(anyhow!("A calibrated ramp needs at least 2 levels"));
+    }
+
+    let mut glyphs: Vec<CalibratedGlyph> = Vec::with_capacity(chars.len());
+    for &character in chars {
+        match coverage(character) {
+            Some(coverage) => glyphs.push(CalibratedGlyph {
+                character,
+                coverage,
+            }),
+            None => log::warn!("No coverage data for '{}', skipping", character),
+        }
+    }
+
+    glyphs.sort_by(|a, b| a.coverage.total_cmp(&b.coverage));
+    glyphs.dedup_by(|b, a| b.coverage - a.coverage < DUPLICATE_EPSILON);
+
+    if glyphs.len() < 2 {
+        return Err(anyhow!(
+            "Ramp needs at least 2 characters with distinct coverage"
+        ));
+    }
+
+    if glyphs.len() <= levels {
+        return Ok(glyphs);
+    }
+
+    // Pick the glyph closest to each evenly spaced coverage target
+    let min = glyphs[0].coverage;
+    let max = glyphs[glyphs.len() - 1].coverage;
+    let mut selected: Vec<CalibratedGlyph> = Vec::with_capacity(levels);
+    let mut next_candidate = 0;
+
+    for level in 0..levels {
+        let target = min + (max - min) * level as f64 / (levels - 1) as f64;
+        // Leave enough glyphs for the remaining levels
+        let last_candidate = glyphs.len() - (levels - level);
+
+        let best = (next_candidate..=last_candidate)
+            .min_by(|&a, &b| {
+                let da = (glyphs[a].coverage - target).abs();
+                let db = (glyphs[b].coverage - target).abs();
+                da.total_cmp(&db)
+            })
+            .unwrap_or(next_candidate);
+
+        selected.push(glyphs[best]);
+        next_candidate = best + 1;
+    }
+
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_lookup() {
+        assert_eq!(coverage(' '), Some(0.0));
+        assert_eq!(coverage('█'), Some(1.0));
+        assert!(coverage('@').unwrap() > coverage('.').unwrap());
+        assert_eq!(coverage('あ'), None);
+    }
+
+    #[test]
+    fn test_calibration_orders_by_coverage() {
+        let ramp: Vec<char> = "@. #:".chars().collect();
+        let calibrated = calibrate_ramp(&ramp, 10).unwrap();
+
+        assert_eq!(calibrated.first().unwrap().character, ' ');
+        assert!(calibrated
+            .windows(2)
+            .all(|pair| pair[0].coverage < pair[1].coverage));
+    }
+
+    #[test]
+    fn test_calibration_prunes_to_levels() {
+        let ramp: Vec<char> =
+            " `.'^\",:;Il!i><~+_-?][}{1)(|/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@"
+                .chars()
+                .collect();
+        let calibrated = calibrate_ramp(&ramp, 8).unwrap();
+
+        assert_eq!(calibrated.len(), 8);
+        assert_eq!(calibrated[0].character, ' ');
+        assert!(calibrated[7].coverage > 0.27);
+    }
+
+    #[test]
+    fn test_calibration_rejects_unknown_ramp() {
+        assert!(calibrate_ramp(&['あ', 'い'], 4).is_err());
+        assert!(calibrate_ramp(&[' ', '@'], 1).is_err());
+    }
+}
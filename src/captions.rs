@@ -0,0 +1,573 @@
+//! Closed captions carried inside the video stream
+//!
+//! Broadcast video embeds captions as ATSC A/53 `cc_data` attached to each
+//! frame. Each entry is a triplet: a header byte (valid flag and type) and two
+//! data bytes. Types 0/1 carry CEA-608 byte pairs for field 1/2; types 2/3
+//! carry CEA-708 DTVCC packets.
+//!
+//! Only what is needed to show readable text is decoded: CC1 for 608 and
+//! the primary service for 708, ignoring pen styles and window layout.
+
+/// Rows and columns of the CEA-608 caption grid
+const ROWS: usize = 15;
+const COLUMNS: usize = 32;
+
+/// Maximum lines kept for CEA-708 text
+const MAX_708_LINES: usize = 4;
+
+/// Decoder for CEA-608 channel 1 (field 1)
+#[derive(Debug, Clone)]
+pub struct Cea608Decoder {
+    displayed: Vec<Vec<char>>,
+    non_displayed: Vec<Vec<char>>,
+    mode: Mode608,
+    row: usize,
+    column: usize,
+    /// Whether data currently belongs to CC1 (vs CC2 on the same field)
+    on_channel_one: bool,
+    /// Control codes are transmitted twice; the repeat is ignored
+    last_control: Option<(u8, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode608 {
+    PopOn,
+    PaintOn,
+    RollUp(usize),
+}
+
+impl Default for Cea608Decoder {
+    fn default() -> Self {
+        Self {
+            displayed: vec![vec![' '; COLUMNS]; ROWS],
+            non_displayed: vec![vec![' '; COLUMNS]; ROWS],
+            mode: Mode608::PopOn,
+            row: ROWS - 1,
+            column: 0,
+            on_channel_one: true,
+            last_control: None,
+        }
+    }
+}
+
+impl Cea608Decoder {
+    /// Feed one byte pair (with parity bits)
+    pub fn feed(&mut self, b1: u8, b2: u8) {
+        let (b1, b2) = (b1 & 0x7f, b2 & 0x7f);
+        if b1 == 0 && b2 == 0 {
+            return;
+        }
+
+        if (0x10..=0x1f).contains(&b1) {
+            if self.last_control == Some((b1, b2)) {
+                self.last_control = None;
+                return;
+            }
+            self.last_control = Some((b1, b2));
+
+            // Bit 3 selects the data channel (CC1/CC2)
+            self.on_channel_one = b1 & 0x08 == 0;
+            if self.on_channel_one {
+                self.control(b1 & 0x17, b2);
+            }
+            return;
+        }
+
+        self.last_control = None;
+        if !self.on_channel_one || b1 < 0x20 {
+            return;
+        }
+
+        self.write(basic_char(b1));
+        if b2 >= 0x20 {
+            self.write(basic_char(b2));
+        }
+    }
+
+    /// Text currently on screen, top to bottom
+    pub fn lines(&self) -> Vec<String> {
+        self.displayed
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Clear all state (after seeking)
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn control(&mut self, b1: u8, b2: u8) {
+        match (b1, b2) {
+            // Miscellaneous control codes
+            (0x14 | 0x15, 0x20..=0x2f) => self.command(b2),
+            // Tab offsets
+            (0x17, 0x21..=0x23) => {
+                self.column = (self.column + (b2 - 0x20) as usize).min(COLUMNS - 1)
+            }
+            // Mid-row style change: displayed as a space
+            (0x11, 0x20..=0x2f) => self.write(' '),
+            // Special characters
+            (0x11, 0x30..=0x3f) => self.write(SPECIAL_CHARS[(b2 - 0x30) as usize]),
+            // Extended characters replace the preceding standard character
+            (0x12 | 0x13, 0x20..=0x3f) => {
+                self.column = self.column.saturating_sub(1);
+                let table = if b1 == 0x12 {
+                    &EXTENDED_CHARS_12
+                } else {
+                    &EXTENDED_CHARS_13
+                };
+                self.write(table[(b2 - 0x20) as usize]);
+            }
+            // Preamble address codes position the cursor
+            (0x10..=0x17, 0x40..=0x7f) => self.preamble(b1, b2),
+            _ => {}
+        }
+    }
+
+    fn command(&mut self, code: u8) {
+        match code {
+            // Resume caption loading
+            0x20 => self.mode = Mode608::PopOn,
+            // Backspace
+            0x21 => {
+                self.column = self.column.saturating_sub(1);
+                let row = self.row;
+                let column = self.column;
+                self.buffer()[row][column] = ' ';
+            }
+            // Delete to end of row
+            0x24 => {
+                let (row, column) = (self.row, self.column);
+                self.buffer()[row][column..].fill(' ');
+            }
+            // Roll-up with 2, 3 or 4 rows
+            0x25..=0x27 => {
+                if !matches!(self.mode, Mode608::RollUp(_)) {
+                    clear(&mut self.displayed);
+                    self.row = ROWS - 1;
+                }
+                self.mode = Mode608::RollUp((code - 0x23) as usize);
+                self.column = 0;
+            }
+            // Resume direct captioning
+            0x29 => self.mode = Mode608::PaintOn,
+            // Erase displayed memory
+            0x2c => clear(&mut self.displayed),
+            // Carriage return
+            0x2d => self.carriage_return(),
+            // Erase non-displayed memory
+            0x2e => clear(&mut self.non_displayed),
+            // End of caption: flip memories
+            0x2f => {
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.mode = Mode608::PopOn;
+            }
+            _ => {}
+        }
+    }
+
+    fn preamble(&mut self, b1: u8, b2: u8) {
+        // First row addressed by each first byte; bit 5 of the second byte
+        // selects the following row
+        const BASE_ROWS: [usize; 8] = [11, 1, 3, 12, 14, 5, 7, 9];
+        let mut row = BASE_ROWS[(b1 & 0x07) as usize];
+        if b1 != 0x10 && b2 & 0x20 != 0 {
+            row += 1;
+        }
+        let row = row - 1;
+
+        if let Mode608::RollUp(rows) = self.mode {
+            // Move the roll-up window to the new base row
+            if row != self.row {
+                let window: Vec<Vec<char>> = (0..rows)
+                    .filter_map(|offset| self.row.checked_sub(offset))
+                    .map(|source| self.displayed[source].clone())
+                    .collect();
+                clear(&mut self.displayed);
+                for (offset, line) in window.into_iter().enumerate() {
+                    if let Some(target) = row.checked_sub(offset) {
+                        self.displayed[target] = line;
+                    }
+                }
+            }
+        }
+
+        self.row = row;
+        self.column = if b2 & 0x10 != 0 {
+            ((b2 & 0x0e) >> 1) as usize * 4
+        } else {
+            0
+        };
+    }
+
+    fn carriage_return(&mut self) {
+        match self.mode {
+            Mode608::RollUp(rows) => {
+                let top = (self.row + 1).saturating_sub(rows);
+                for row in top..self.row {
+                    self.displayed[row] = self.displayed[row + 1].clone();
+                }
+                // Rows above the window are no longer shown
+                for row in 0..top {
+                    self.displayed[row].fill(' ');
+                }
+                self.displayed[self.row].fill(' ');
+            }
+            _ => self.row = (self.row + 1).min(ROWS - 1),
+        }
+        self.column = 0;
+    }
+
+    fn write(&mut self, character: char) {
+        let (row, column) = (self.row, self.column);
+        self.buffer()[row][column] = character;
+        self.column = (column + 1).min(COLUMNS - 1);
+    }
+
+    /// Memory written by the current mode
+    fn buffer(&mut self) -> &mut Vec<Vec<char>> {
+        match self.mode {
+            Mode608::PopOn => &mut self.non_displayed,
+            Mode608::PaintOn | Mode608::RollUp(_) => &mut self.displayed,
+        }
+    }
+}
+
+fn clear(memory: &mut [Vec<char>]) {
+    for row in memory {
+        row.fill(' ');
+    }
+}
+
+/// CEA-608 replaces a few ASCII positions with accented letters
+fn basic_char(byte: u8) -> char {
+    match byte {
+        0x2a => 'á',
+        0x5c => 'é',
+        0x5e => 'í',
+        0x5f => 'ó',
+        0x60 => 'ú',
+        0x7b => 'ç',
+        0x7c => '÷',
+        0x7d => 'Ñ',
+        0x7e => 'ñ',
+        0x7f => '█',
+        _ => byte as char,
+    }
+}
+
+const SPECIAL_CHARS: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+const EXTENDED_CHARS_12: [char; 32] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '—', '©', '℠', '•', '“', '”', 'À', 'Â', 'Ç',
+    'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+];
+
+const EXTENDED_CHARS_13: [char; 32] = [
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä', 'Ö',
+    'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+];
+
+/// Decoder for the primary CEA-708 caption service
+#[derive(Debug, Clone, Default)]
+pub struct Cea708Decoder {
+    packet: Vec<u8>,
+    lines: Vec<String>,
+}
+
+impl Cea708Decoder {
+    /// Feed one `cc_data` entry of type 2 (packet data) or 3 (packet start)
+    pub fn feed(&mut self, cc_type: u8, b1: u8, b2: u8) {
+        if cc_type == 3 {
+            self.finish_packet();
+        }
+        self.packet.extend_from_slice(&[b1, b2]);
+
+        // The header encodes the packet length in byte pairs (0 means 64 pairs)
+        if let Some(&header) = self.packet.first() {
+            let pairs = match header & 0x3f {
+                0 => 64,
+                size => size as usize,
+            };
+            if self.packet.len() >= pairs * 2 {
+                self.finish_packet();
+            }
+        }
+    }
+
+    /// Caption text, oldest line first
+    pub fn lines(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Clear all state (after seeking)
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn finish_packet(&mut self) {
+        let packet = std::mem::take(&mut self.packet);
+        let mut position = 1;
+
+        while position < packet.len() {
+            let header = packet[position];
+            position += 1;
+            let mut service = header >> 5;
+            let size = (header & 0x1f) as usize;
+            if service == 0 {
+                break;
+            }
+            if service == 7 {
+                // Extended service number
+                let Some(&extended) = packet.get(position) else {
+                    break;
+                };
+                service = extended & 0x3f;
+                position += 1;
+            }
+
+            let end = (position + size).min(packet.len());
+            if service == 1 {
+                self.service_block(&packet[position..end]);
+            }
+            position = end;
+        }
+    }
+
+    fn service_block(&mut self, block: &[u8]) {
+        let mut index = 0;
+        while index < block.len() {
+            let code = block[index];
+            index += 1;
+
+            match code {
+                // End of text, ignored
+                0x03 => {}
+                // Backspace
+                0x08 => {
+                    if let Some(line) = self.lines.last_mut() {
+                        line.pop();
+                    }
+                }
+                // Form feed: clear the screen
+                0x0c => self.lines.clear(),
+                // Carriage return
+                0x0d => self.new_line(),
+                // Remaining C0 codes with parameters
+                0x10 => index += 1 + extended_parameters(block.get(index).copied()),
+                0x11..=0x17 => index += 1,
+                0x18..=0x1f => index += 2,
+                0x00..=0x1f => {}
+                // Printable G0 characters (0x7f is a music note)
+                0x20..=0x7e => self.push(code as char),
+                0x7f => self.push('♪'),
+                // C1 window and pen commands
+                0x80..=0x9f => {
+                    match code {
+                        // Clear, delete or reset windows
+                        0x88 | 0x8c | 0x8f => self.lines.clear(),
+                        _ => {}
+                    }
+                    index += c1_parameters(code);
+                }
+                // G1 Latin-1 characters
+                0xa0..=0xff => self.push(code as char),
+            }
+        }
+    }
+
+    fn push(&mut self, character: char) {
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        if let Some(line) = self.lines.last_mut() {
+            line.push(character);
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.lines.push(String::new());
+        if self.lines.len() > MAX_708_LINES {
+            self.lines.remove(0);
+        }
+    }
+}
+
+/// Parameter bytes following a C1 command
+fn c1_parameters(code: u8) -> usize {
+    match code {
+        0x88..=0x8d => 1,
+        0x90 | 0x92 => 2,
+        0x91 => 3,
+        0x97 => 4,
+        0x98..=0x9f => 6,
+        _ => 0,
+    }
+}
+
+/// Extra bytes after an EXT1 code (the extended code itself is consumed separately)
+fn extended_parameters(code: Option<u8>) -> usize {
+    match code {
+        // C2 extended control codes
+        Some(0x08..=0x0f) => 1,
+        Some(0x10..=0x17) => 2,
+        Some(0x18..=0x1f) => 3,
+        // C3 extended control codes
+        Some(0x80..=0x87) => 4,
+        Some(0x88..=0x8f) => 5,
+        _ => 0,
+    }
+}
+
+/// Combined 608/708 caption state for a video stream
+#[derive(Debug, Clone, Default)]
+pub struct ClosedCaptions {
+    cea608: Cea608Decoder,
+    cea708: Cea708Decoder,
+    has_608: bool,
+    has_708: bool,
+}
+
+impl ClosedCaptions {
+    /// Feed the A/53 `cc_data` attached to a frame
+    pub fn feed(&mut self, cc_data: &[u8]) {
+        for entry in cc_data.chunks_exact(3) {
+            let (header, b1, b2) = (entry[0], entry[1], entry[2]);
+            if header & 0x04 == 0 {
+                continue;
+            }
+
+            match header & 0x03 {
+                0 => {
+                    self.has_608 = true;
+                    self.cea608.feed(b1, b2);
+                }
+                // Field 2 carries CC3/CC4, which are rarely used
+                1 => {}
+                cc_type => {
+                    self.has_708 = true;
+                    self.cea708.feed(cc_type, b1, b2);
+                }
+            }
+        }
+    }
+
+    /// Whether any caption data has been seen
+    pub fn is_present(&self) -> bool {
+        self.has_608 || self.has_708
+    }
+
+    /// Caption lines to display. 608 is preferred since most 708 streams
+    /// also carry it and it preserves the caption layout better.
+    pub fn lines(&self) -> Vec<String> {
+        if self.has_608 {
+            self.cea608.lines()
+        } else {
+            self.cea708.lines()
+        }
+    }
+
+    /// Clear displayed captions (after seeking)
+    pub fn reset(&mut self) {
+        self.cea608.reset();
+        self.cea708.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Add odd parity to a 7-bit value
+    fn parity(byte: u8) -> u8 {
+        if byte.count_ones() & 1 == 0 {
+            byte | 0x80
+        } else {
+            byte
+        }
+    }
+
+    fn cc_data(pairs: &[(u8, u8)]) -> Vec<u8> {
+        pairs
+            .iter()
+            .flat_map(|&(b1, b2)| [0xfc, parity(b1), parity(b2)])
+            .collect()
+    }
+
+    fn text_pairs(text: &str) -> Vec<(u8, u8)> {
+        text.as_bytes()
+            .chunks(2)
+            .map(|chunk| (chunk[0], chunk.get(1).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    #[test]
+    fn test_pop_on_caption() {
+        let mut captions = ClosedCaptions::default();
+        let mut pairs = vec![(0x14, 0x20), (0x14, 0x20), (0x14, 0x70)];
+        pairs.extend(text_pairs("HELLO"));
+        captions.feed(&cc_data(&pairs));
+        // Nothing shown until end of caption
+        assert!(captions.lines().is_empty());
+
+        captions.feed(&cc_data(&[(0x14, 0x2f), (0x14, 0x2f)]));
+        assert_eq!(captions.lines(), vec!["HELLO"]);
+
+        captions.feed(&cc_data(&[(0x14, 0x2c)]));
+        assert!(captions.lines().is_empty());
+    }
+
+    #[test]
+    fn test_roll_up_scrolls() {
+        let mut decoder = Cea608Decoder::default();
+        decoder.feed(0x14, 0x25);
+        for (b1, b2) in text_pairs("ONE") {
+            decoder.feed(b1, b2);
+        }
+        decoder.feed(0x14, 0x2d);
+        for (b1, b2) in text_pairs("TWO") {
+            decoder.feed(b1, b2);
+        }
+        assert_eq!(decoder.lines(), vec!["ONE", "TWO"]);
+
+        decoder.feed(0x14, 0x2d);
+        for (b1, b2) in text_pairs("THREE") {
+            decoder.feed(b1, b2);
+        }
+        assert_eq!(decoder.lines(), vec!["TWO", "THREE"]);
+    }
+
+    #[test]
+    fn test_channel_two_is_ignored() {
+        let mut decoder = Cea608Decoder::default();
+        decoder.feed(0x1c, 0x29);
+        for (b1, b2) in text_pairs("CC2") {
+            decoder.feed(b1, b2);
+        }
+        assert!(decoder.lines().is_empty());
+    }
+
+    #[test]
+    fn test_cea708_service_text() {
+        let mut captions = ClosedCaptions::default();
+        // Packet header (sequence 0, 3 pairs), service 1 block of 4 bytes
+        let packet = [0x03, 0x24, b'H', b'I', 0x0d, b'!'];
+        let mut data = Vec::new();
+        for (index, pair) in packet.chunks(2).enumerate() {
+            let cc_type = if index == 0 { 0xff } else { 0xfe };
+            data.extend_from_slice(&[cc_type, pair[0], pair[1]]);
+        }
+        captions.feed(&data);
+
+        assert!(captions.is_present());
+        assert_eq!(captions.lines(), vec!["HI", "!"]);
+    }
+}
@@ -0,0 +1,160 @@
+//! asciinema v2 cast output
+//!
+//! `--export clip.cast` writes the escape sequences the player would send to
+//! a terminal, with their timing, as an [asciicast v2] file: a JSON header
+//! line followed by one `[time, "o", data]` event line per frame. The file
+//! can be replayed with `asciinema play` or uploaded to asciinema.org.
+//!
+//! Frames are drawn as for telnet clients, homing the cursor and redrawing
+//! in place, in 24-bit color. Frames identical to the one before are left
+//! out; the earlier frame simply stays on screen longer.
+//!
+//! [asciicast v2]: https://docs.asciinema.org/manual/asciicast/v2/
+
+use crate::converter::AsciiFrame;
+use crate::telnet::{encode_frame, ColorDepth};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Sent before the first frame: hide the cursor and clear the screen
+const CAST_PREAMBLE: &str = "\x1b[?25l\x1b[2J";
+
+#[derive(Serialize)]
+struct CastHeader<'a> {
+    version: u32,
+    width: u16,
+    height: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    env: CastEnv,
+}
+
+#[derive(Serialize)]
+struct CastEnv {
+    #[serde(rename = "TERM")]
+    term: &'static str,
+}
+
+/// Writes frames as an asciicast v2 stream
+pub struct CastWriter<W: Write> {
+    output: W,
+    title: Option<String>,
+    /// Timestamp of the first frame, once the header is written
+    start: Option<f64>,
+    /// Escapes of the last frame written, to skip repeats
+    previous: String,
+    frames: u64,
+}
+
+impl<W: Write> CastWriter<W> {
+    /// Start a cast; the header is written with the first frame, whose size
+    /// sets the terminal size
+    pub fn new(output: W, title: Option<String>) -> Self {
+        Self {
+            output,
+            title,
+            start: None,
+            previous: String::new(),
+            frames: 0,
+        }
+    }
+
+    /// Write one frame at its time since the first frame
+    pub fn write_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let mut data = encode_frame(frame, ColorDepth::TrueColor, "");
+        if data == self.previous {
+            return Ok(());
+        }
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                self.write_header(frame)?;
+                frame.timestamp
+            }
+        };
+        self.start = Some(start);
+        self.previous.clone_from(&data);
+        if self.frames == 0 {
+            data.insert_str(0, CAST_PREAMBLE);
+        }
+
+        let time = (frame.timestamp - start).max(0.0);
+        serde_json::to_writer(&mut self.output, &(time, "o", data))?;
+        self.output.write_all(b"\n")?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// The header line. The player's status line comes after the frame's
+    /// rows, so the terminal is one row taller than the frame.
+    fn write_header(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let header = CastHeader {
+            version: 2,
+            width: frame.width,
+            height: frame.height + 1,
+            title: self.title.as_deref(),
+            env: CastEnv {
+                term: "xterm-256color",
+            },
+        };
+        serde_json::to_writer(&mut self.output, &header)?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Number of frames written, not counting repeats
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Flush and return the output
+    pub fn finish(mut self) -> Result<W> {
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_events() {
+        let mut frame = AsciiFrame::new(2, 1, vec!['#', '@'], vec![(255, 0, 0); 2]);
+        frame.timestamp = 3.0;
+
+        let mut writer = CastWriter::new(Vec::new(), Some("clip".to_string()));
+        writer.write_frame(&frame).unwrap();
+        // A repeat is left out
+        frame.timestamp = 3.04;
+        writer.write_frame(&frame).unwrap();
+        frame.timestamp = 3.5;
+        frame.characters[0] = '.';
+        writer.write_frame(&frame).unwrap();
+        assert_eq!(writer.frames(), 2);
+
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            serde_json::json!({
+                "version": 2,
+                "width": 2,
+                "height": 2,
+                "title": "clip",
+                "env": {"TERM": "xterm-256color"},
+            })
+        );
+        assert_eq!(lines[1][0], 0.0);
+        assert_eq!(lines[1][1], "o");
+        let first = lines[1][2].as_str().unwrap();
+        assert!(first.starts_with(CAST_PREAMBLE) && first.contains("#@"));
+        assert_eq!(lines[2][0], 0.5);
+        assert!(lines[2][2].as_str().unwrap().contains(".@"));
+    }
+}
@@ -0,0 +1,260 @@
+//! Character set files
+//!
+//! A charset file is either a single line holding the ramp (darkest glyph
+//! first), or `key = value` lines:
+//!
+//! ```text
+//! # Lines starting with '#' are comments
+//! ramp = " .:-=+*#%@"
+//! weights = 0.0 0.03 0.05 0.03 0.09 0.11 0.10 0.25 0.18 0.28
+//! ```
+//!
+//! `weights` gives the visual density of each glyph (for example its ink
+//! coverage) so luminance can be mapped onto ramps whose steps are not evenly
+//! spaced. Weights only need to be relative; they are normalized on use.
+//!
+//! Any Unicode glyph works as long as it takes exactly one terminal cell:
+//! control characters, combining marks and double-width glyphs (CJK, most
+//! emoji) would shift the rest of the row and are rejected. Repeated glyphs
+//! and ramps that look reversed still play, with a warning.
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::path::Path;
+
+/// A character ramp with optional per-glyph density weights
+#[derive(Debug, Clone, PartialEq)]
+pub struct Charset {
+    /// Glyphs ordered from darkest to brightest
+    pub glyphs: Vec<char>,
+    /// Relative density of each glyph, same length as `glyphs`
+    pub weights: Option<Vec<f64>>,
+}
+
+impl Charset {
+    /// Load a charset file from disk
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read charset file '{}': {}", path.display(), e))?;
+        Self::parse(&contents)
+            .map_err(|e| anyhow!("Invalid charset file '{}': {}", path.display(), e))
+    }
+
+    /// Parse the contents of a charset file
+    pub fn parse(contents: &str) -> Result<Self> {
+        let has_keys = contents
+            .lines()
+            .any(|line| line.trim_start().starts_with("ramp") && line.contains('='));
+
+        if !has_keys {
+            // Plain ramp: the first line, verbatim (leading spaces are glyphs)
+            let line = contents.lines().next().unwrap_or("");
+            return Self::new(line.trim_end_matches('\r').chars().collect(), None);
+        }
+
+        let mut glyphs = None;
+        let mut weights = None;
+
+        for (number, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `key = value`", number + 1))?;
+
+            match key.trim() {
+                "ramp" => glyphs = Some(parse_quoted(value.trim(), number + 1)?),
+                "weights" => {
+                    let parsed = value
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .filter(|token| !token.is_empty())
+                        .map(|token| {
+                            token.parse::<f64>().map_err(|_| {
+                                anyhow!("line {}: invalid weight '{}'", number + 1, token)
+                            })
+                        })
+                        .collect::<Result<Vec<f64>>>()?;
+                    weights = Some(parsed);
+                }
+                other => return Err(anyhow!("line {}: unknown key '{}'", number + 1, other)),
+            }
+        }
+
+        let glyphs = glyphs.ok_or_else(|| anyhow!("missing `ramp`"))?;
+        Self::new(glyphs, weights)
+    }
+
+    /// Create a charset, validating glyph count and weights
+    pub fn new(glyphs: Vec<char>, weights: Option<Vec<f64>>) -> Result<Self> {
+        if glyphs.len() < 2 {
+            return Err(anyhow!("a ramp needs at least 2 characters"));
+        }
+        validate_glyphs(&glyphs)?;
+
+        if let Some(ref weights) = weights {
+            validate_weights(weights, glyphs.len())?;
+        }
+
+        Ok(Self { glyphs, weights })
+    }
+}
+
+/// Check that every glyph fills one cell, and warn when one repeats or the
+/// ramp seems to run from bright to dark
+pub fn validate_glyphs(glyphs: &[char]) -> Result<()> {
+    if let Some(glyph) = glyphs.iter().find(|glyph| !is_single_width(**glyph)) {
+        return Err(anyhow!(
+            "glyph {:?} (U+{:04X}) does not take exactly one terminal cell",
+            glyph,
+            *glyph as u32
+        ));
+    }
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        if glyphs[..index].contains(glyph) {
+            warn!("Ramp glyph {:?} appears more than once", glyph);
+        }
+    }
+
+    // A blank is the darkest glyph there is
+    if let (Some(first), Some(last)) = (glyphs.first(), glyphs.last()) {
+        if last.is_whitespace() && !first.is_whitespace() {
+            warn!(
+                "Ramp ends with a blank; ramps are ordered dark to bright, so it may be reversed"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a glyph takes exactly one terminal cell. Covers control
+/// characters, combining and zero-width marks, and the main double-width
+/// blocks; rarer wide glyphs get through.
+fn is_single_width(glyph: char) -> bool {
+    let zero_width = [
+        0x0300..=0x036F, // combining diacritics
+        0x1AB0..=0x1AFF, // combining diacritics extended
+        0x200B..=0x200F, // zero-width spaces and joiners
+        0x20D0..=0x20FF, // combining marks for symbols
+        0xFE00..=0xFE0F, // variation selectors
+        0xFE20..=0xFE2F, // combining half marks
+        0xFEFF..=0xFEFF, // byte order mark
+    ];
+    let double_width = [
+        0x1100..=0x115F,   // Hangul Jamo
+        0x2E80..=0xA4CF,   // CJK radicals through Yi
+        0xAC00..=0xD7A3,   // Hangul syllables
+        0xF900..=0xFAFF,   // CJK compatibility ideographs
+        0xFE30..=0xFE4F,   // CJK compatibility forms
+        0xFF00..=0xFF60,   // fullwidth forms
+        0xFFE0..=0xFFE6,   // fullwidth signs
+        0x1F300..=0x1F64F, // pictographs and emoticons
+        0x1F900..=0x1F9FF, // supplemental pictographs
+        0x20000..=0x3FFFD, // CJK extensions
+    ];
+    let code = glyph as u32;
+    !glyph.is_control()
+        && !zero_width
+            .iter()
+            .chain(double_width.iter())
+            .any(|range| range.contains(&code))
+}
+
+/// Check that weights match the ramp and increase from dark to bright
+pub fn validate_weights(weights: &[f64], glyph_count: usize) -> Result<()> {
+    if weights.len() != glyph_count {
+        return Err(anyhow!(
+            "expected {} weights (one per glyph), got {}",
+            glyph_count,
+            weights.len()
+        ));
+    }
+
+    if weights.iter().any(|weight| !weight.is_finite()) {
+        return Err(anyhow!("weights must be finite numbers"));
+    }
+
+    if weights.windows(2).any(|pair| pair[1] < pair[0]) {
+        return Err(anyhow!(
+            "weights must be non-decreasing (ramp is ordered dark to bright)"
+        ));
+    }
+
+    if weights[weights.len() - 1] <= weights[0] {
+        return Err(anyhow!("brightest weight must exceed the darkest"));
+    }
+
+    Ok(())
+}
+
+/// Parse a double-quoted string supporting `\"` and `\\` escapes
+fn parse_quoted(value: &str, line: usize) -> Result<Vec<char>> {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("line {}: ramp must be a double-quoted string", line))?;
+
+    let mut chars = Vec::new();
+    let mut escaped = false;
+    for c in inner.chars() {
+        if escaped {
+            chars.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else {
+            chars.push(c);
+        }
+    }
+
+    if escaped {
+        return Err(anyhow!("line {}: dangling escape in ramp", line));
+    }
+
+    Ok(chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_ramp() {
+        let charset = Charset::parse(" .:#@\n").unwrap();
+        assert_eq!(charset.glyphs, vec![' ', '.', ':', '#', '@']);
+        assert!(charset.weights.is_none());
+    }
+
+    #[test]
+    fn test_parse_weighted_ramp() {
+        let contents = "# density-weighted ramp\nramp = \" .\\\"#\"\nweights = 0, 0.1 0.2 0.9\n";
+        let charset = Charset::parse(contents).unwrap();
+        assert_eq!(charset.glyphs, vec![' ', '.', '"', '#']);
+        assert_eq!(charset.weights, Some(vec![0.0, 0.1, 0.2, 0.9]));
+    }
+
+    #[test]
+    fn test_rejects_bad_weights() {
+        assert!(Charset::parse("ramp = \" .#\"\nweights = 0 0.5").is_err());
+        assert!(Charset::parse("ramp = \" .#\"\nweights = 0 0.5 0.2").is_err());
+        assert!(Charset::parse("ramp = \" .#\"\nweights = 0 x 1").is_err());
+        assert!(Charset::parse("@").is_err());
+    }
+
+    #[test]
+    fn test_validate_glyphs() {
+        assert!(Charset::new(" ░▒▓█".chars().collect(), None).is_ok());
+        assert!(Charset::new(" ·•●".chars().collect(), None).is_ok());
+        // Reversed and repeated glyphs only warn
+        assert!(Charset::new("@#:. ".chars().collect(), None).is_ok());
+        assert!(Charset::new(" .:.#".chars().collect(), None).is_ok());
+        // Wide, combining and control glyphs
+        assert!(Charset::new(" .口".chars().collect(), None).is_err());
+        assert!(Charset::new(" .\u{301}#".chars().collect(), None).is_err());
+        assert!(Charset::new(" .\t#".chars().collect(), None).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Built-in charset presets
+//!
+//! `--charset-preset` picks one of these ramps instead of spelling glyphs
+//! out with `--charset`. Glyphs from other scripts do not get denser in a
+//! neat sequence the way `.:-=+*#%@` does, so each preset stores a density
+//! weight per glyph (its rough ink coverage in a typical monospace font)
+//! and luminance is mapped through the weights, as for weighted charset
+//! files. Every glyph is one cell wide; Katakana uses the half-width forms.
+
+use crate::charset::Charset;
+
+/// Named ramp selectable with `--charset-preset`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharsetPreset {
+    /// Half-width Katakana, for a falling-code look
+    Katakana,
+    /// Box-drawing lines, from thin strokes to double crossings
+    Boxes,
+    /// Elder Futhark runes
+    Runes,
+    /// The digits 0-9
+    Digits,
+}
+
+/// Half-width Katakana with ink coverage weights
+const KATAKANA: (&str, &[f64]) = (
+    " ･ｰｨﾉﾍﾆｼﾘﾂｦﾓﾎﾈﾗﾒﾑ",
+    &[
+        0.0, 0.03, 0.06, 0.08, 0.10, 0.11, 0.13, 0.15, 0.16, 0.17, 0.20, 0.22, 0.25, 0.27, 0.28,
+        0.29, 0.31,
+    ],
+);
+
+/// Box-drawing characters with ink coverage weights
+const BOXES: (&str, &[f64]) = (
+    " ╶─┌├┼═╪╋╬",
+    &[0.0, 0.04, 0.08, 0.10, 0.13, 0.16, 0.18, 0.26, 0.32, 0.36],
+);
+
+/// Runes with ink coverage weights
+const RUNES: (&str, &[f64]) = (
+    " ᛁᚲᛚᚢᚱᚦᛉᚠᛗᚻᛞ",
+    &[
+        0.0, 0.06, 0.08, 0.09, 0.12, 0.14, 0.15, 0.17, 0.18, 0.22, 0.24, 0.27,
+    ],
+);
+
+/// Digits with ink coverage weights
+const DIGITS: (&str, &[f64]) = (
+    " 1742356908",
+    &[
+        0.0, 0.10, 0.12, 0.15, 0.16, 0.17, 0.18, 0.20, 0.21, 0.22, 0.24,
+    ],
+);
+
+impl CharsetPreset {
+    /// Glyphs from dark to bright and their density weights
+    fn ramp(self) -> (&'static str, &'static [f64]) {
+        match self {
+            CharsetPreset::Katakana => KATAKANA,
+            CharsetPreset::Boxes => BOXES,
+            CharsetPreset::Runes => RUNES,
+            CharsetPreset::Digits => DIGITS,
+        }
+    }
+
+    /// The preset as a weighted charset
+    pub fn charset(self) -> Charset {
+        let (glyphs, weights) = self.ramp();
+        Charset::new(glyphs.chars().collect(), Some(weights.to_vec()))
+            .expect("built-in charset presets are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_presets_are_valid() {
+        for preset in CharsetPreset::value_variants() {
+            let (glyphs, weights) = preset.ramp();
+            assert_eq!(glyphs.chars().count(), weights.len(), "{:?}", preset);
+            let charset = preset.charset();
+            assert_eq!(charset.glyphs[0], ' ');
+        }
+        assert_eq!(CharsetPreset::Digits.charset().glyphs.last(), Some(&'8'));
+    }
+}
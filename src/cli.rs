@@ -1,12 +1,88 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::ambient::terminal_background;
+use crate::animation::{is_animated_image, AnimatedImage};
+use crate::cache::{ByteSize, FrameCache, LoopCache, MemoryBudget, DEFAULT_MAX_MEM};
+use crate::charset::Charset;
+use crate::charsets::CharsetPreset;
+use crate::config::Config;
+use crate::converter::{
+    AsciiConverter, ConversionConfig, FixedPalette, FrameConverter, Posterize, SplitConverter,
+    DEFAULT_MAX_CELLS,
+};
+use crate::crop::{detect_crop, CropRect, DEFAULT_BLACK_THRESHOLD, DEFAULT_SAMPLE_FRAMES};
+use crate::cvd::CvdFilter;
+use crate::decoder::{FrameIterator, FrameSource, VideoDecoder};
+use crate::describe::DEFAULT_DESCRIBE_INTERVAL;
+use crate::edl::CutList;
+use crate::effects::EffectChain;
+use crate::headless::DETERMINISTIC_SIZE;
+use crate::hooks::{BellHook, CommandHook, EventHooks, NotificationHook};
+use crate::keymap::Keymap;
+#[cfg(feature = "led-matrix")]
+use crate::led::{parse_led_size, LedTarget};
+use crate::palettes::Colormap;
+use crate::pipeline::{DecodedFrame, FilterStage, Pipeline, DECODE_QUEUE, FILTER_QUEUE};
+use crate::playlist::Playlist;
+use crate::scale::Scaler;
+use crate::schedule::{ClockTime, StartSchedule};
+use crate::sequence::{is_image_pattern, ImageSequence, DEFAULT_SEQUENCE_FPS};
+use crate::server::AccessToken;
+use crate::share::ASCIINEMA_UPLOAD_URL;
+use crate::stabilize::Stabilizer;
+use crate::status::{SketchyBar, StatusReporters, TerminalTitle};
+use crate::sync::{SyncSession, DEFAULT_SYNC_HOST_ADDR};
+use crate::termcaps::TermCaps;
+use crate::throttle::Bandwidth;
+use crate::wall::{Tile, WallLayout, DEFAULT_SYNC_ADDR};
+use crate::watchdog::{FrameWatchdog, DEFAULT_FRAME_TIMEOUT_MS};
+use clap::{Parser, Subcommand};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{SocketAddr, SocketAddrV4};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Ramp of the ASCII palette, also used where Unicode cannot be shown
+pub(crate) const ASCII_RAMP: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// Ramp of the grayscale and color palettes
+pub(crate) const BLOCK_RAMP: &[char] = &[' ', '░', '▒', '▓', '█'];
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    /// Path to the video file to play
-    #[arg(required = true)]
-    pub file_path: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
+    /// Path to the video file to play, or a pattern such as 'frames/*.png'
+    /// matching an image sequence
+    #[arg(required_unless_present_any = ["device", "playlist"])]
+    pub file_path: Option<PathBuf>,
+
+    /// More files, played after the first one
+    #[arg(value_name = "MORE_FILES", conflicts_with_all = ["export", "serve", "wall"])]
+    pub more_files: Vec<PathBuf>,
+
+    /// Play the files listed in a playlist file (one per line, as .m3u),
+    /// after any given as arguments
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["export", "serve", "wall"])]
+    pub playlist: Option<PathBuf>,
+
+    /// Play the files, and those in given directories, in random order
+    #[arg(long)]
+    pub shuffle: bool,
+
+    /// Play live from a camera instead of a file: an index such as 0 (on
+    /// Linux /dev/video0), a device path, or on Windows the camera's name
+    #[arg(
+        long,
+        value_name = "DEVICE",
+        conflicts_with_all = [
+            "file_path", "start_time", "end_time", "edl", "sub_track", "export", "serve",
+            "wall", "loop_playback", "speed_curve", "playlist",
+        ]
+    )]
+    pub device: Option<String>,
 
     /// Loop the video playback
     #[arg(short, long)]
@@ -16,6 +92,16 @@ pub struct Cli {
     #[arg(short, long, default_value_t = 1.0)]
     pub speed: f64,
 
+    /// Vary the speed over the video with keyframes from a JSON file, e.g.
+    /// to slow a section down; multiplies --speed
+    #[arg(long, value_name = "FILE")]
+    pub speed_curve: Option<PathBuf>,
+
+    /// Bind a key to an action during playback, e.g. --bind x=quit or
+    /// --bind ctrl+right=seek-forward-long; `none` unbinds a key
+    #[arg(long = "bind", value_name = "KEY=ACTION")]
+    pub bindings: Vec<String>,
+
     /// Enable transparent background by not drawing background colors
     #[arg(short, long)]
     pub transparent: bool,
@@ -36,18 +122,186 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Write per-stage timing spans (decode, convert, render, flush) to this
+    /// file in Chrome trace format
+    #[arg(long, value_name = "FILE")]
+    pub trace: Option<PathBuf>,
+
+    /// Render every frame to stdout as text as fast as possible, with fixed
+    /// timestamps and size and no config file, so runs can be compared exactly
+    #[arg(long, conflicts_with_all = ["serve", "export"])]
+    pub deterministic: bool,
+
+    /// Print text descriptions of the video (scene changes, brightness and
+    /// motion, with timestamps) to stdout instead of drawing it
+    #[arg(long, conflicts_with_all = ["serve", "export", "deterministic"])]
+    pub describe: bool,
+
+    /// Seconds between --describe summaries
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_DESCRIBE_INTERVAL)]
+    pub describe_interval: f64,
+
+    /// Play one tile of a video wall spread over COLSxROWS terminals, e.g. 2x2
+    #[arg(
+        long,
+        value_name = "COLSxROWS",
+        conflicts_with_all = ["serve", "export", "deterministic", "describe"]
+    )]
+    pub wall: Option<WallLayout>,
+
+    /// Which --wall tile this terminal shows, as COL,ROW from the top left;
+    /// tile 0,0 leads the shared clock
+    #[arg(long, value_name = "COL,ROW", default_value = "0,0", requires = "wall")]
+    pub tile: Tile,
+
+    /// UDP address (usually a multicast group) the --wall clock is shared on
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_SYNC_ADDR)]
+    pub sync_addr: SocketAddrV4,
+
+    /// Also take newline-delimited commands on stdin (pause, resume, toggle,
+    /// seek 12.5, seek +10, status, screenshot [FILE], quit), answered on stderr
+    #[arg(
+        long,
+        conflicts_with_all = ["serve", "export", "deterministic", "describe", "wall"]
+    )]
+    pub slave_mode: bool,
+
+    /// Play in a Neovim terminal window: print control and reply FIFO paths
+    /// as the first lines, take the --slave-mode commands on the first and
+    /// answer on the second (see contrib/nvim). Pass the window size as
+    /// --width/--height
+    #[arg(
+        long,
+        conflicts_with_all = ["serve", "export", "deterministic", "describe", "wall", "slave_mode"]
+    )]
+    pub nvim: bool,
+
+    /// Host a watch-together: players started with --sync-join connect here
+    /// and pause, resume and seek in step with this one. Listens on
+    /// 127.0.0.1:4300 unless given `--sync-host=ADDR`; use
+    /// `--sync-host=0.0.0.0:4300` to let other machines join
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_SYNC_HOST_ADDR,
+        conflicts_with_all = ["serve", "export", "deterministic", "describe", "wall"]
+    )]
+    pub sync_host: Option<SocketAddr>,
+
+    /// Join the watch-together hosted at HOST:PORT, playing this copy of the
+    /// video in step with the group
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["serve", "export", "deterministic", "describe", "wall", "sync_host"]
+    )]
+    pub sync_join: Option<String>,
+
+    /// Play on an RGB LED panel instead of the terminal: ft:HOST[:PORT] for a
+    /// Flaschen-Taschen server (rpi-rgb-led-matrix's ft-server) or
+    /// unicorn-hd[:DEVICE] for a Unicorn HAT HD
+    #[cfg(feature = "led-matrix")]
+    #[arg(
+        long,
+        value_name = "TARGET",
+        conflicts_with_all = ["serve", "export", "deterministic", "describe", "wall"]
+    )]
+    pub led: Option<LedTarget>,
+
+    /// LED panel size as WxH (32x32 by default; the Unicorn HAT HD is 16x16)
+    #[cfg(feature = "led-matrix")]
+    #[arg(long, value_name = "WxH", value_parser = parse_led_size, requires = "led")]
+    pub led_size: Option<(u16, u16)>,
+
     /// Set color palette (ascii, grayscale, color)
     #[arg(short, long, default_value = "color")]
     pub palette: ColorPalette,
 
+    /// Play without sound
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Time-stretch the sound to keep its pitch when playing from 0.5x to
+    /// 2x speed; with `=false`, sound is only heard at normal speed
+    #[arg(
+        long,
+        value_name = "BOOL",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = true,
+        default_missing_value = "true"
+    )]
+    pub audio_pitch_correction: bool,
+
+    /// Pause while the terminal window or tmux pane is not focused
+    #[arg(long)]
+    pub pause_when_hidden: bool,
+
+    /// Redraw paused frames and still images with characters matched to
+    /// the shapes in the picture, computed in the background
+    #[arg(long)]
+    pub refine: bool,
+
+    /// Keep the decode thread on this CPU core (Linux only)
+    #[arg(long, value_name = "CPU")]
+    pub decode_cpu: Option<usize>,
+
+    /// Keep the render thread on this CPU core (Linux only)
+    #[arg(long, value_name = "CPU")]
+    pub render_cpu: Option<usize>,
+
+    /// Lower the player's scheduling priority, from 0 to 19 like nice(1);
+    /// negative values need privileges
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    pub nice: Option<i32>,
+
     /// SketchyBar integration - update item with playback status
     #[arg(long, value_name = "ITEM_NAME")]
     pub sketchybar_item: Option<String>,
 
+    /// Draw the loudness of the audio track above the status line, so loud
+    /// sections can be found at a glance
+    #[arg(long)]
+    pub waveform: bool,
+
+    /// Fast-forward through runs of black frames longer than a second
+    #[arg(long)]
+    pub skip_black: bool,
+
+    /// Jump over silences of three seconds or more, found by measuring the
+    /// audio track in the background
+    #[arg(long)]
+    pub skip_silence: bool,
+
+    /// Show a QR code of the input path or URL in the top right corner for
+    /// the first seconds of the video
+    #[arg(long)]
+    pub qr: bool,
+
+    /// Show the file and playback time in the terminal's title bar,
+    /// restoring the old title on exit
+    #[arg(long)]
+    pub set_title: bool,
+
     /// Frame rate limit (FPS)
     #[arg(short, long)]
     pub fps: Option<f64>,
 
+    /// Keep terminal output under RATE (e.g. 200kbps or 64KiB/s) on slow
+    /// links such as SSH, redrawing only changed cells, using fewer colors
+    /// and skipping frames as needed
+    #[arg(long, value_name = "RATE")]
+    pub max_bandwidth: Option<Bandwidth>,
+
+    /// When converting or rendering a frame takes longer than this many
+    /// milliseconds, skip the frames playback fell behind by meanwhile; 0
+    /// never skips frames
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_FRAME_TIMEOUT_MS)]
+    pub frame_timeout: u64,
+
     /// Start playback from specific time (in seconds)
     #[arg(long)]
     pub start_time: Option<f64>,
@@ -63,6 +317,383 @@ pub struct Cli {
     /// Render a single frame for testing (debug mode)
     #[arg(long)]
     pub single_frame: bool,
+
+    /// Map each source pixel to whole cells when the video is smaller than the terminal
+    #[arg(long)]
+    pub scale_exact: bool,
+
+    /// Draw with dense characters and a few bright colors on black
+    #[arg(long)]
+    pub high_contrast: bool,
+
+    /// Update the picture at most 10 times per second and turn off animations
+    #[arg(long)]
+    pub reduced_motion: bool,
+
+    /// Filter used to shrink the picture to the grid: nearest (fastest),
+    /// bilinear, lanczos or area (averages every pixel under a cell, the
+    /// most detail at large reductions)
+    #[arg(long, value_name = "SCALER", default_value = "nearest")]
+    pub scaler: Scaler,
+
+    /// Sharpen the shrunk picture with an unsharp mask, by AMOUNT (0.0-2.0,
+    /// 0.5 if not given); pairs well with --scaler area
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0.5"
+    )]
+    pub sharpen: Option<f64>,
+
+    /// Most cells converted per frame (default 1000000); larger terminals
+    /// show a downsampled picture. 0 removes the limit
+    #[arg(long, value_name = "CELLS")]
+    pub max_cells: Option<usize>,
+
+    /// Character ramp from dark to bright, e.g. " .:-=+*#%@"; any glyphs
+    /// one cell wide, including Unicode blocks and shades
+    #[arg(long, value_name = "RAMP", conflicts_with = "charset_file")]
+    pub charset: Option<String>,
+
+    /// Load the character ramp (and optional density weights) from a charset file
+    #[arg(long, value_name = "FILE")]
+    pub charset_file: Option<PathBuf>,
+
+    /// Use a built-in ramp with density weights: katakana, boxes
+    /// (box-drawing), runes or digits
+    #[arg(
+        long,
+        value_name = "PRESET",
+        conflicts_with_all = ["charset", "charset_file"]
+    )]
+    pub charset_preset: Option<CharsetPreset>,
+
+    /// Color by luminance with a preset gradient (matrix, amber, cyan)
+    #[arg(long, conflicts_with = "theme_colors")]
+    pub theme: Option<Theme>,
+
+    /// Color by luminance with a custom 2-3 color gradient, e.g. "#000000,#00ff41"
+    #[arg(long, value_name = "COLORS")]
+    pub theme_colors: Option<String>,
+
+    /// Processing applied before characters are chosen (normal, motion,
+    /// heatmap, edges)
+    #[arg(long, default_value = "normal")]
+    pub style: Style,
+
+    /// Reduce colors to N levels per channel before characters are chosen
+    #[arg(long, value_name = "N")]
+    pub posterize: Option<u8>,
+
+    /// Dither characters and reduced colors so gradients don't band on
+    /// short ramps or with --posterize and --palette-file: fs (error
+    /// diffusion), ordered (8x8 Bayer, stable across frames), ordered4
+    /// (4x4 Bayer) or none
+    #[arg(long, value_name = "MODE", default_value = "none")]
+    pub dither: Dither,
+
+    /// Draw cells between two glyphs of the ramp bold or dim, for extra
+    /// brightness steps on terminals with few colors
+    #[arg(long)]
+    pub use_attributes: bool,
+
+    /// Spread the character ramp over the luminance range of each scene,
+    /// for more detail in consistently dark or bright footage
+    #[arg(long)]
+    pub auto_ramp: bool,
+
+    /// Snap colors to a fixed palette from a .hex file (one rrggbb per line)
+    #[arg(long, value_name = "FILE")]
+    pub palette_file: Option<PathBuf>,
+
+    /// Preview or compensate for color blindness: simulate:TYPE or
+    /// daltonize[:TYPE], TYPE being protanopia, deuteranopia or tritanopia
+    #[arg(long, value_name = "FILTER")]
+    pub cvd: Option<CvdFilter>,
+
+    /// Play as subtle ambience behind other work: fade colors toward the
+    /// terminal background, keeping FACTOR (0.0-1.0) of their strength.
+    /// Implies --transparent
+    #[arg(long, value_name = "FACTOR")]
+    pub ambient: Option<f64>,
+
+    /// Colormap for --style heatmap (turbo, inferno)
+    #[arg(long, default_value = "turbo")]
+    pub colormap: Colormap,
+
+    /// Compare two sets of conversion settings side by side, e.g. 'contrast=1.5' 'contrast=1.0'
+    #[arg(long, num_args = 2, value_names = ["LEFT", "RIGHT"])]
+    pub ab_compare: Option<Vec<String>>,
+
+    /// Ring the terminal bell when playback finishes or fails
+    #[arg(long)]
+    pub bell: bool,
+
+    /// Send a desktop notification (OSC 9) when playback finishes or fails
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Shell command to run when playback finishes
+    #[arg(long, value_name = "COMMAND")]
+    pub on_finish: Option<String>,
+
+    /// Shell command to run when playback fails
+    #[arg(long, value_name = "COMMAND")]
+    pub on_error: Option<String>,
+
+    /// Add an effect to the chain, e.g. --effect autocrop --effect
+    /// eq:contrast=1.1 --effect dither:floyd; replaces the chain from the
+    /// config file (see `ascii-player effects --list`)
+    #[arg(long = "effect", value_name = "EFFECT", conflicts_with = "profile")]
+    pub effects: Vec<String>,
+
+    /// Use the effects chain saved in the config file under NAME
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Effects chain resolved by `apply_effects`
+    #[arg(skip)]
+    pub effect_chain: EffectChain,
+
+    /// Detect black bars (letterbox/pillarbox) in early frames and crop them
+    #[arg(long)]
+    pub autocrop: bool,
+
+    /// Fill the whole terminal, cropping the picture instead of letterboxing it
+    #[arg(long)]
+    pub cover: bool,
+
+    /// With --cover, pan the crop toward the brightest or moving part of the picture
+    #[arg(long, requires = "cover")]
+    pub roi_follow: bool,
+
+    /// Cancel camera shake by cropping a little of each edge and following
+    /// the smoothed camera motion
+    #[arg(long)]
+    pub stabilize: bool,
+
+    /// Luminance (0-255) at or below which a border pixel counts as black
+    #[arg(long, value_name = "LUMA", default_value_t = DEFAULT_BLACK_THRESHOLD, requires = "autocrop")]
+    pub autocrop_threshold: u8,
+
+    /// Number of frames sampled for black bar detection
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_SAMPLE_FRAMES, requires = "autocrop")]
+    pub autocrop_frames: usize,
+
+    /// Subtitle track to display (0 = first subtitle stream); bitmap
+    /// subtitles such as PGS and DVB are burned into the picture
+    #[arg(long, value_name = "N")]
+    pub sub_track: Option<usize>,
+
+    /// Show closed captions (CEA-608/708) embedded in the video; toggle with 'c'
+    #[arg(long)]
+    pub captions: bool,
+
+    /// Memory budget for cached frames, e.g. 512M or 1G (default 256M);
+    /// 0 disables caching
+    #[arg(long, value_name = "SIZE")]
+    pub max_mem: Option<ByteSize>,
+
+    /// Write cached frames that exceed --max-mem to this directory instead
+    /// of dropping them
+    #[arg(long, value_name = "DIR")]
+    pub spill_dir: Option<PathBuf>,
+
+    /// Show a big-text title over the first seconds of the video
+    #[arg(long, value_name = "TEXT")]
+    pub title: Option<String>,
+
+    /// Wait and start playback at this local time (HH:MM or HH:MM:SS)
+    #[arg(long, value_name = "HH:MM")]
+    pub start_at_clock: Option<ClockTime>,
+
+    /// Show a big-text countdown of this many seconds before playback starts
+    #[arg(long, value_name = "SECONDS")]
+    pub countdown: Option<u64>,
+
+    /// Convert the video into an .ascv recording instead of playing it;
+    /// play the recording back by passing the .ascv file as the input. Use
+    /// `ndjson` (stdout) or a .ndjson file for one JSON object of cells per
+    /// frame, a .cast file for an asciinema recording, a .gif file for an
+    /// animated GIF of the rendered frames, a .html file for a web page
+    /// that plays the animation, or a .mp4, .webm, .mkv or .mov file for a
+    /// video of the rendered frames with the original audio
+    #[arg(long, value_name = "FILE", conflicts_with = "serve")]
+    pub export: Option<PathBuf>,
+
+    /// Write every converted frame to this directory as an .ans file of raw
+    /// escape sequences (frame_000001.ans, ...) instead of playing the video
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["serve", "export", "deterministic", "describe"]
+    )]
+    pub dump_ansi: Option<PathBuf>,
+
+    /// Directory the screenshots taken with `s` are saved in
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub screenshot_dir: PathBuf,
+
+    /// Save screenshots as a PNG picture too
+    #[arg(long)]
+    pub screenshot_png: bool,
+
+    /// Continue an interrupted --export from its checkpoint instead of
+    /// starting over
+    #[arg(long, requires = "export")]
+    pub resume_export: bool,
+
+    /// Upload the finished --export recording (.cast, or .ascv for servers
+    /// taking it) and print where it can be watched: to the given upload
+    /// URL (`--share=URL`), the config file's `share_url`, or asciinema.org
+    #[arg(long, value_name = "URL", requires = "export", require_equals = true)]
+    pub share: Option<Option<String>>,
+
+    /// Stream the video to telnet clients on this address (e.g. 0.0.0.0:2323)
+    /// instead of playing it locally; all clients share one playback clock
+    #[arg(long, value_name = "ADDR")]
+    pub serve: Option<String>,
+
+    /// Accept pause/resume/seek/status commands for all server clients on
+    /// this address, one command per line
+    #[arg(long, value_name = "ADDR", requires = "serve")]
+    pub admin: Option<String>,
+
+    /// Maximum number of simultaneous server clients
+    #[arg(long, value_name = "N", requires = "serve")]
+    pub max_clients: Option<usize>,
+
+    /// Per-client bandwidth ceiling in KiB/s; slower clients get fewer frames
+    #[arg(long, value_name = "KIBPS", requires = "serve")]
+    pub client_bandwidth: Option<u64>,
+
+    /// Disconnect server clients that neither take a frame nor send
+    /// anything for this many seconds
+    #[arg(long, value_name = "SECONDS", requires = "serve")]
+    pub idle_timeout: Option<u64>,
+
+    /// Require this token from telnet viewers and admin connections
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        requires = "serve",
+        conflicts_with = "auth_token_file"
+    )]
+    pub auth_token: Option<String>,
+
+    /// Read the auth token from the first line of a file, keeping it out of
+    /// the process list
+    #[arg(long, value_name = "FILE", requires = "serve")]
+    pub auth_token_file: Option<PathBuf>,
+
+    /// Skip the time ranges listed in a JSON cut list or mpv EDL file
+    #[arg(long, value_name = "FILE")]
+    pub edl: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Calibrate the character ramp against font ink coverage and save it to the config
+    Calibrate {
+        /// Characters to calibrate (defaults to the extended ASCII ramp)
+        #[arg(long)]
+        ramp: Option<String>,
+
+        /// Maximum number of characters to keep
+        #[arg(long, default_value_t = 16)]
+        levels: usize,
+
+        /// Print the calibrated ramp without saving it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the format version and contents of a recording or export checkpoint
+    Inspect {
+        /// Recording (.ascv) or export checkpoint to describe
+        file: PathBuf,
+    },
+    /// List the timeline bookmarks saved for a video
+    Bookmarks {
+        /// Video file or URL the bookmarks were made on
+        file: PathBuf,
+
+        /// Print the bookmarks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the notes made on a video, or export them
+    Annotations {
+        /// Video file or URL the notes were made on
+        file: PathBuf,
+
+        /// Print the notes as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Print the notes as SRT subtitles
+        #[arg(long, conflicts_with = "json")]
+        srt: bool,
+    },
+    /// Copy a range of a video to a new file without re-encoding
+    Clip {
+        /// Video file or URL to cut from
+        file: PathBuf,
+
+        /// Start, in seconds or as a bookmark name
+        #[arg(long)]
+        from: String,
+
+        /// End, in seconds or as a bookmark name
+        #[arg(long)]
+        to: String,
+
+        /// Output file; the container follows its extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print what the player detected about the terminal and environment,
+    /// for attaching to bug reports
+    Doctor {
+        /// First play a 2-second test pattern of colors, block and braille
+        /// glyphs and a moving marker, and report drawing speed and timing
+        #[arg(long)]
+        render_test: bool,
+    },
+    /// Restore a terminal left in raw mode by a player that did not exit
+    /// cleanly (the same as running `reset`)
+    FixTerminal,
+    /// List the effects a chain can use and the saved profiles, or save a
+    /// chain as a profile
+    Effects {
+        /// List effects and profiles (the default)
+        #[arg(long)]
+        list: bool,
+
+        /// Save the given effects as a profile under NAME
+        #[arg(long, value_name = "NAME", conflicts_with = "list")]
+        save: Option<String>,
+
+        /// Effects of the profile, in order
+        #[arg(value_name = "EFFECT", requires = "save")]
+        effects: Vec<String>,
+    },
+    /// Time how fast frames are assembled for the terminal, buffered into
+    /// one write against queueing each cell's commands
+    Bench {
+        /// Frame width in cells
+        #[arg(long, default_value_t = 160)]
+        width: u16,
+
+        /// Frame height in cells
+        #[arg(long, default_value_t = 48)]
+        height: u16,
+
+        /// Number of frames to assemble each way
+        #[arg(long, default_value_t = 500)]
+        frames: u64,
+    },
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -75,15 +706,251 @@ pub enum ColorPalette {
     Color,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    /// Green phosphor
+    Matrix,
+    /// Amber monochrome monitor
+    Amber,
+    /// Cyan terminal glow
+    Cyan,
+}
+
+impl Theme {
+    /// Gradient stops from dark to bright
+    pub fn stops(&self) -> Vec<(u8, u8, u8)> {
+        match self {
+            Theme::Matrix => vec![(0, 0, 0), (0, 143, 17), (0, 255, 65)],
+            Theme::Amber => vec![(0, 0, 0), (179, 107, 0), (255, 176, 0)],
+            Theme::Cyan => vec![(0, 0, 0), (0, 110, 130), (0, 255, 255)],
+        }
+    }
+}
+
+/// How the picture is processed before characters are chosen
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Style {
+    /// The picture as decoded
+    #[default]
+    Normal,
+    /// Only what changed since the previous frame: moving areas bright,
+    /// static areas blank
+    Motion,
+    /// False color by luminance through a colormap (see --colormap)
+    Heatmap,
+    /// Outlines drawn with directional glyphs (/ \ | - _) along strong
+    /// edges, flat areas with the ramp
+    Edges,
+}
+
+/// How rounding to the ramp and to reduced colors is hidden
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Round every cell on its own; gradients band into steps
+    #[default]
+    None,
+    /// Floyd–Steinberg error diffusion: the smoothest gradients, but the
+    /// pattern shimmers as the picture moves
+    #[value(name = "fs")]
+    FloydSteinberg,
+    /// 8x8 Bayer matrix: a fixed pattern per cell that stays put from
+    /// frame to frame
+    Ordered,
+    /// 4x4 Bayer matrix: a coarser fixed pattern, for small grids
+    Ordered4,
+}
+
+impl Dither {
+    /// Side of the Bayer matrix of an ordered mode
+    pub fn bayer_size(self) -> Option<usize> {
+        match self {
+            Dither::Ordered => Some(8),
+            Dither::Ordered4 => Some(4),
+            Dither::None | Dither::FloydSteinberg => None,
+        }
+    }
+}
+
+/// Parse a comma separated list of `#rrggbb` colors
+pub fn parse_color_list(list: &str) -> Result<Vec<(u8, u8, u8)>, String> {
+    list.split(',')
+        .map(|color| parse_hex_color(color.trim()))
+        .collect()
+}
+
+/// Parse a `#rrggbb` (or `rrggbb`) color
+pub fn parse_hex_color(color: &str) -> Result<(u8, u8, u8), String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color '{}', expected #rrggbb", color));
+    }
+
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).unwrap_or(0);
+    Ok((channel(0..2), channel(2..4), channel(4..6)))
+}
+
 impl Cli {
     /// Validate command line arguments
     pub fn validate(&self) -> Result<(), String> {
-        // Check if file exists
-        if !self.file_path.exists() {
-            return Err(format!(
-                "Video file does not exist: {}",
-                self.file_path.display()
-            ));
+        // Check if files exist
+        for file_path in self.file_path.iter().chain(&self.more_files) {
+            if !file_path.exists() && !is_image_pattern(file_path) {
+                return Err(format!(
+                    "Video file does not exist: {}",
+                    file_path.display()
+                ));
+            }
+        }
+
+        if let Some(ref playlist) = self.playlist {
+            if !playlist.exists() {
+                return Err(format!(
+                    "Playlist file does not exist: {}",
+                    playlist.display()
+                ));
+            }
+        }
+
+        if self
+            .ambient
+            .is_some_and(|factor| !(0.0..=1.0).contains(&factor))
+        {
+            return Err("Ambient factor must be between 0.0 and 1.0".to_string());
+        }
+
+        if self
+            .sharpen
+            .is_some_and(|amount| !(0.0..=2.0).contains(&amount))
+        {
+            return Err("Sharpen amount must be between 0.0 and 2.0".to_string());
+        }
+
+        if self.nice.is_some_and(|nice| !(-20..=19).contains(&nice)) {
+            return Err("Nice value must be between -20 and 19".to_string());
+        }
+
+        if self.posterize.is_some_and(|levels| levels < 2) {
+            return Err("Posterize needs at least 2 levels".to_string());
+        }
+
+        if let Some(ref palette_file) = self.palette_file {
+            if !palette_file.exists() {
+                return Err(format!(
+                    "Palette file does not exist: {}",
+                    palette_file.display()
+                ));
+            }
+        }
+
+        if let Some(ref ramp) = self.charset {
+            Charset::new(ramp.chars().collect(), None)
+                .map_err(|e| format!("Invalid --charset: {}", e))?;
+        }
+
+        if let Some(ref charset_file) = self.charset_file {
+            if !charset_file.exists() {
+                return Err(format!(
+                    "Charset file does not exist: {}",
+                    charset_file.display()
+                ));
+            }
+        }
+
+        if let Some(ref edl) = self.edl {
+            if !edl.exists() {
+                return Err(format!("EDL file does not exist: {}", edl.display()));
+            }
+        }
+
+        if let Some(ref curve) = self.speed_curve {
+            if !curve.exists() {
+                return Err(format!(
+                    "Speed curve file does not exist: {}",
+                    curve.display()
+                ));
+            }
+        }
+
+        if self.client_bandwidth == Some(0) || self.idle_timeout == Some(0) {
+            return Err("Client bandwidth and idle timeout must be positive".to_string());
+        }
+
+        if let Some(ref path) = self.auth_token_file {
+            if !path.exists() {
+                return Err(format!(
+                    "Auth token file does not exist: {}",
+                    path.display()
+                ));
+            }
+        }
+
+        if let Some(ref dir) = self.spill_dir {
+            if !dir.is_dir() {
+                return Err(format!("Spill directory does not exist: {}", dir.display()));
+            }
+        }
+
+        if self.autocrop_frames == 0 {
+            return Err("Autocrop needs at least one sample frame".to_string());
+        }
+
+        if let Some(ref colors) = self.theme_colors {
+            let stops = parse_color_list(colors)?;
+            if !(2..=3).contains(&stops.len()) {
+                return Err("Theme gradient needs 2 or 3 colors".to_string());
+            }
+        }
+
+        if let Some(ref specs) = self.ab_compare {
+            let mut sides = Vec::with_capacity(specs.len());
+            for spec in specs {
+                let mut side = ConversionConfig::default();
+                side.apply_overrides(spec)
+                    .map_err(|e| format!("Invalid --ab-compare settings '{}': {}", spec, e))?;
+                sides.push(side);
+            }
+            // The halves are drawn on one grid, so they must size frames alike
+            if let [left, right] = sides.as_slice() {
+                if !left.same_grid(right) {
+                    return Err(
+                        "--ab-compare sides must agree on scale-exact, cover and max-cells"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        EffectChain::parse(&self.effects).map_err(|e| format!("Invalid --effect: {}", e))?;
+        if let Some(Commands::Effects {
+            save: Some(_),
+            ref effects,
+            ..
+        }) = self.command
+        {
+            EffectChain::parse(effects).map_err(|e| format!("Invalid effect: {}", e))?;
+        }
+
+        if let Some(Commands::Calibrate { levels, .. }) = self.command {
+            if levels < 2 {
+                return Err("Calibration needs at least 2 levels".to_string());
+            }
+        }
+
+        if let Some(layout) = self.wall {
+            if !layout.contains(self.tile) {
+                return Err(format!(
+                    "Tile {},{} is outside the {} wall",
+                    self.tile.column, self.tile.row, layout
+                ));
+            }
+        }
+
+        if cfg!(not(unix)) && self.nvim {
+            return Err("--nvim needs named pipes, which this platform lacks".to_string());
+        }
+
+        if self.describe_interval <= 0.0 {
+            return Err("Describe interval must be positive".to_string());
         }
 
         // Validate speed factor
@@ -129,6 +996,94 @@ impl Cli {
         Ok(())
     }
 
+    /// Resolve the effects chain from `--effect`, `--profile` or the config
+    /// file's default chain, turning on its decoder effects
+    pub fn apply_effects(&mut self, config: &Config) -> anyhow::Result<()> {
+        let chain = match self.profile {
+            _ if !self.effects.is_empty() => EffectChain::parse(&self.effects)?,
+            Some(ref name) => {
+                let effects = config
+                    .profiles
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("No effects profile named '{}'", name))?;
+                EffectChain::parse(effects)
+                    .map_err(|e| anyhow::anyhow!("Invalid profile '{}': {}", name, e))?
+            }
+            None => EffectChain::parse(&config.effects)
+                .map_err(|e| anyhow::anyhow!("Invalid effects in config file: {}", e))?,
+        };
+
+        if let Some((threshold, frames)) = chain.autocrop() {
+            self.autocrop = true;
+            self.autocrop_threshold = threshold.unwrap_or(self.autocrop_threshold);
+            self.autocrop_frames = frames.unwrap_or(self.autocrop_frames);
+        }
+        self.stabilize |= chain.stabilize();
+        self.effect_chain = chain;
+        Ok(())
+    }
+
+    /// Default keys with those of the config file and --bind over them
+    pub fn keymap(&self, config: &Config) -> anyhow::Result<Keymap> {
+        Keymap::new(&config.keys, &self.bindings)
+    }
+
+    /// Whether frames come live from a --device, which can't seek or loop
+    pub fn is_live(&self) -> bool {
+        self.device.is_some()
+    }
+
+    /// Upload endpoint for --share, None without it
+    pub fn share_endpoint(&self, config: &Config) -> Option<String> {
+        let url = self.share.as_ref()?;
+        Some(
+            url.clone()
+                .or_else(|| config.share_url.clone())
+                .unwrap_or_else(|| ASCIINEMA_UPLOAD_URL.to_string()),
+        )
+    }
+
+    /// Files given as arguments followed by those of --playlist, with
+    /// directories standing for the files in them; empty for --device
+    pub fn playlist(&self) -> anyhow::Result<Playlist> {
+        let mut given: Vec<PathBuf> = self.file_path.iter().cloned().collect();
+        given.extend(self.more_files.iter().cloned());
+        if let Some(ref path) = self.playlist {
+            given.extend(Playlist::load(path)?);
+        }
+
+        let mut entries = Vec::new();
+        for entry in given {
+            if entry.is_dir() {
+                let files = Playlist::scan(&entry)?;
+                if files.is_empty() {
+                    log::warn!("No playable files in {}", entry.display());
+                }
+                entries.extend(files);
+            } else {
+                entries.push(entry);
+            }
+        }
+        if entries.is_empty() && self.device.is_none() {
+            return Err(anyhow::anyhow!("No files to play"));
+        }
+
+        let mut playlist = Playlist::new(entries);
+        if self.shuffle {
+            playlist.shuffle(RandomState::new().build_hasher().finish());
+        }
+        Ok(playlist)
+    }
+
+    /// Path of the video to play, or the --device captured from (one is
+    /// always present when no subcommand is given)
+    pub fn input_path(&self) -> &Path {
+        self.file_path
+            .as_deref()
+            .or(self.device.as_deref().map(Path::new))
+            .unwrap_or_else(|| Path::new(""))
+    }
+
     /// Get effective terminal dimensions
     pub fn get_terminal_size(&self) -> Result<(u16, u16), std::io::Error> {
         match (self.width, self.height) {
@@ -145,18 +1100,320 @@ impl Cli {
         }
     }
 
+    /// Frame size for `--deterministic`, which never asks the terminal
+    pub fn deterministic_size(&self) -> (u16, u16) {
+        (
+            self.width.unwrap_or(DETERMINISTIC_SIZE.0),
+            self.height.unwrap_or(DETERMINISTIC_SIZE.1),
+        )
+    }
+
     /// Get the ASCII character set based on palette
     pub fn get_ascii_chars(&self) -> &'static [char] {
         match self.palette {
-            ColorPalette::Ascii => &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'],
-            ColorPalette::Grayscale => &[' ', '░', '▒', '▓', '█'],
-            ColorPalette::Color => &[' ', '░', '▒', '▓', '█'],
+            ColorPalette::Ascii => ASCII_RAMP,
+            ColorPalette::Grayscale | ColorPalette::Color => BLOCK_RAMP,
+        }
+    }
+
+    /// Build the frame conversion settings from the command line options.
+    ///
+    /// The ramp comes from `--charset`, `--charset-file` or
+    /// `--charset-preset` if given, then
+    /// from a calibrated ramp saved in the config, and finally from the
+    /// palette's default ramp, or the ASCII ramp if `caps` shows the
+    /// terminal cannot display Unicode. A known cell shape from `caps`
+    /// replaces the default aspect ratio; output not meant for this
+    /// terminal passes `TermCaps::default()`. `--ambient` blends toward
+    /// `background`, asking the terminal for it when None. Converter
+    /// effects of the effects chain apply last.
+    pub fn conversion_config(
+        &self,
+        config: &Config,
+        caps: &TermCaps,
+        background: Option<(u8, u8, u8)>,
+    ) -> anyhow::Result<ConversionConfig> {
+        let charset = match (&self.charset, &self.charset_file) {
+            (Some(ramp), _) => Some(Charset::new(ramp.chars().collect(), None)?),
+            (None, Some(path)) => Some(Charset::load(path)?),
+            (None, None) => self
+                .charset_preset
+                .map(CharsetPreset::charset)
+                .or_else(|| config.charset()),
+        };
+
+        let (ascii_chars, char_weights) = match charset {
+            Some(charset) => (charset.glyphs, charset.weights),
+            None if caps.unicode => (self.get_ascii_chars().to_vec(), None),
+            None => (ASCII_RAMP.to_vec(), None),
+        };
+
+        let mut conversion = ConversionConfig::default()
+            .with_palette(self.palette.clone())
+            .with_transparent(self.transparent(), self.alpha_threshold)
+            .with_ascii_chars(ascii_chars, char_weights)
+            .with_scale_exact(self.scale_exact)
+            .with_max_cells(self.max_cells())
+            .with_high_contrast(self.high_contrast(config))
+            .with_theme(self.theme_stops())
+            .with_style(self.style)
+            .with_colormap(self.colormap)
+            .with_cover(self.cover, self.roi_follow)
+            .with_cvd(self.cvd)
+            .with_dither(self.dither)
+            .with_attributes(self.use_attributes)
+            .with_auto_ramp(self.auto_ramp)
+            .with_scaler(self.scaler, self.sharpen.unwrap_or(0.0));
+
+        if let Some(aspect_ratio) = caps.cell_aspect() {
+            conversion = conversion.with_aspect_ratio(aspect_ratio);
+        }
+        if let Some(factor) = self.ambient {
+            let background = background.unwrap_or_else(terminal_background);
+            conversion = conversion.with_ambient(Some(factor), background);
+        }
+
+        if let Some(levels) = self.posterize {
+            conversion = conversion.with_quantizer(Arc::new(Posterize::new(levels)));
+        }
+        if let Some(ref path) = self.palette_file {
+            conversion = conversion.with_quantizer(Arc::new(FixedPalette::load(path)?));
+        }
+        self.effect_chain.apply(&mut conversion)?;
+        Ok(conversion)
+    }
+
+    /// Open the input video with the requested time window, subtitles and cut list
+    pub fn open_video(&self) -> anyhow::Result<FrameIterator> {
+        if let Some(ref device) = self.device {
+            let source = Box::new(VideoDecoder::open_device(device)?);
+            return Ok(FrameIterator::from_source(source, None, None));
+        }
+        let path = self.input_path();
+        let images = is_image_pattern(path) || is_animated_image(path);
+        if images && self.sub_track.is_some() {
+            anyhow::bail!("Subtitle tracks need a video, not images");
+        }
+        let source: Box<dyn FrameSource> = if is_image_pattern(path) {
+            let fps = self.fps.unwrap_or(DEFAULT_SEQUENCE_FPS);
+            Box::new(ImageSequence::open(path, fps)?)
+        } else if is_animated_image(path) {
+            Box::new(AnimatedImage::open(path)?)
+        } else {
+            let mut decoder = VideoDecoder::new(self.input_path())?;
+            if let Some(track) = self.sub_track {
+                decoder.select_subtitle_track(track)?;
+            }
+            Box::new(decoder)
+        };
+        let frame_iter = FrameIterator::from_source(source, self.start_time, self.end_time);
+
+        match self.edl {
+            Some(ref edl) => Ok(frame_iter.with_cuts(CutList::load(edl)?)),
+            None => Ok(frame_iter),
+        }
+    }
+
+    /// Stabilizer for `--stabilize`, applied after autocrop
+    pub fn stabilizer(&self) -> Option<Stabilizer> {
+        self.stabilize.then(Stabilizer::new)
+    }
+
+    /// Filter stage cropping to `crop` and stabilizing with --stabilize.
+    /// Pipelines started from clones of it share one stabilizer.
+    pub fn frame_filter(&self, crop: Option<CropRect>) -> FilterStage {
+        FilterStage::new(crop, self.stabilizer())
+    }
+
+    /// Frames of `frames` decoded and passed through `filter` on their own
+    /// threads, ahead of playback
+    pub fn frame_pipeline(
+        &self,
+        frames: FrameIterator,
+        filter: &FilterStage,
+    ) -> Pipeline<DecodedFrame> {
+        Pipeline::decode_on(frames, DECODE_QUEUE, self.decode_cpu)
+            .stage(filter.clone(), FILTER_QUEUE)
+    }
+
+    /// Black bar crop for the input, if `--autocrop` is set and bars were found.
+    ///
+    /// Samples frames from a separate decoder so playback starts at the
+    /// first frame.
+    pub fn autocrop(&self) -> anyhow::Result<Option<CropRect>> {
+        if !self.autocrop {
+            return Ok(None);
+        }
+
+        detect_crop(
+            self.open_video()?,
+            self.autocrop_frames,
+            self.autocrop_threshold,
+        )
+    }
+
+    /// Build the converter for playback: a plain converter, or a split
+    /// converter when `--ab-compare` is given
+    pub fn build_converter(
+        &self,
+        config: &Config,
+        caps: &TermCaps,
+    ) -> anyhow::Result<Box<dyn AsciiConverter>> {
+        self.converter_for(self.conversion_config(config, caps, None)?)
+    }
+
+    /// Converter for `base` settings, split for --ab-compare
+    pub fn converter_for(&self, base: ConversionConfig) -> anyhow::Result<Box<dyn AsciiConverter>> {
+        match self.ab_compare.as_deref() {
+            Some([left_spec, right_spec]) => {
+                let mut left = base.clone();
+                left.apply_overrides(left_spec)?;
+                let mut right = base;
+                right.apply_overrides(right_spec)?;
+                Ok(Box::new(SplitConverter::new(left, right)))
+            }
+            _ => Ok(Box::new(FrameConverter::new(base))),
+        }
+    }
+
+    /// Register the completion/error cues requested on the command line
+    pub fn event_hooks(&self) -> EventHooks {
+        let mut hooks = EventHooks::new();
+
+        if self.bell {
+            hooks.register(Box::new(BellHook));
+        }
+        if self.notify {
+            hooks.register(Box::new(NotificationHook));
+        }
+        if let Some(ref command) = self.on_finish {
+            hooks.register(Box::new(CommandHook::new(command.clone(), "finished")));
+        }
+        if let Some(ref command) = self.on_error {
+            hooks.register(Box::new(CommandHook::new(command.clone(), "error")));
+        }
+
+        hooks
+    }
+
+    /// Places outside the player that show what is playing
+    pub fn status_reporters(&self) -> StatusReporters {
+        let mut reporters = StatusReporters::new();
+
+        if let Some(item_name) = self.sketchybar_item_name() {
+            reporters.register(Box::new(SketchyBar::new(item_name.to_string())));
+        }
+        if self.set_title {
+            reporters.register(Box::new(TerminalTitle::new(std::io::stdout())));
+        }
+
+        reporters
+    }
+
+    /// Whether cell backgrounds are left to the terminal, as with
+    /// `--transparent` or `--ambient`
+    pub fn transparent(&self) -> bool {
+        self.transparent || self.ambient.is_some()
+    }
+
+    /// Delayed start requested with `--start-at-clock` or `--countdown`
+    pub fn start_schedule(&self) -> Option<StartSchedule> {
+        StartSchedule::new(self.start_at_clock, self.countdown)
+    }
+
+    /// Token required by the server, from `--auth-token` or `--auth-token-file`
+    pub fn access_token(&self) -> anyhow::Result<Option<AccessToken>> {
+        match (&self.auth_token, &self.auth_token_file) {
+            (Some(token), _) => AccessToken::new(token).map(Some),
+            (None, Some(path)) => AccessToken::load(path).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Whether high-contrast mode is on, from the flag or the config file
+    pub fn high_contrast(&self, config: &Config) -> bool {
+        self.high_contrast || config.high_contrast
+    }
+
+    /// Whether reduced-motion mode is on, from the flag or the config file
+    pub fn reduced_motion(&self, config: &Config) -> bool {
+        self.reduced_motion || config.reduced_motion
+    }
+
+    /// Largest grid converted per frame
+    pub fn max_cells(&self) -> usize {
+        self.max_cells.unwrap_or(DEFAULT_MAX_CELLS)
+    }
+
+    /// Per-frame deadline from `--frame-timeout`
+    pub fn frame_watchdog(&self) -> FrameWatchdog {
+        FrameWatchdog::new(Duration::from_millis(self.frame_timeout))
+    }
+
+    /// Memory budget shared by frame caches
+    pub fn memory_budget(&self) -> Arc<MemoryBudget> {
+        MemoryBudget::new(self.max_mem.map_or(DEFAULT_MAX_MEM, |size| size.0))
+    }
+
+    /// The watch-together group this player hosts or joins, if any
+    pub async fn sync_session(&self) -> anyhow::Result<Option<SyncSession>> {
+        if let Some(addr) = self.sync_host {
+            return SyncSession::host(addr).await.map(Some);
+        }
+        match self.sync_join {
+            Some(ref addr) => SyncSession::join(addr).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Cache for replaying loops without decoding, when looping is on and the
+    /// budget allows. Captions come from the decoder, so they rule it out.
+    pub fn loop_cache(&self) -> Option<LoopCache> {
+        let budget = self.memory_budget();
+        if !self.loop_playback || self.captions || budget.limit() == 0 {
+            return None;
+        }
+
+        let frames = match self.spill_dir {
+            Some(ref dir) => FrameCache::new(budget.clone())
+                .with_spill(dir)
+                .unwrap_or_else(|e| {
+                    log::warn!("Not spilling cached frames: {}", e);
+                    FrameCache::new(budget)
+                }),
+            None => FrameCache::new(budget),
+        };
+        Some(LoopCache::new(frames))
+    }
+
+    /// Gradient stops for the selected theme, if any
+    pub fn theme_stops(&self) -> Option<Vec<(u8, u8, u8)>> {
+        match (self.theme, self.theme_colors.as_deref()) {
+            (Some(theme), _) => Some(theme.stops()),
+            (None, Some(colors)) => parse_color_list(colors).ok(),
+            (None, None) => None,
         }
     }
 
     /// Check if color output is enabled
     pub fn use_color(&self) -> bool {
-        matches!(self.palette, ColorPalette::Color | ColorPalette::Grayscale)
+        self.palette_uses_color(&self.palette)
+    }
+
+    /// Whether frames drawn with `palette` are sent with their colors
+    pub fn palette_uses_color(&self, palette: &ColorPalette) -> bool {
+        matches!(palette, ColorPalette::Color | ColorPalette::Grayscale)
+            || self.theme_stops().is_some()
+            || self.style == Style::Heatmap
+    }
+
+    /// Whether the ramp was chosen with `--charset`, `--charset-file`,
+    /// `--charset-preset` or a calibration, rather than going with the palette
+    pub fn charset_chosen(&self, config: &Config) -> bool {
+        self.charset.is_some()
+            || self.charset_file.is_some()
+            || self.charset_preset.is_some()
+            || config.charset().is_some()
     }
 
     /// Get SketchyBar item name if configured
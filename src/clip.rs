@@ -0,0 +1,329 @@
+//! Cutting a range of the input out to a new file
+//!
+//! During playback `[` and `]` mark the start and end of a range and `e`
+//! saves it to the working directory in the background, with progress
+//! in the status line. Without marks the bookmarks around the current
+//! position are used. `ascii-player clip` does the same from the command
+//! line, taking seconds or bookmark names.
+//!
+//! Clips are remuxed, not re-encoded: packets are copied as they are, so a
+//! clip is quick to write and loses no quality, but it starts at the
+//! keyframe at or before the requested start.
+
+use crate::bookmarks::Bookmark;
+use crate::config::Config;
+use crate::scheduling::release_thread;
+use crate::status::format_clock;
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Part of the video to cut out, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl ClipRange {
+    /// Range between two points in either order; None if they are the same
+    pub fn between(a: f64, b: f64) -> Option<Self> {
+        let (start, end) = if a <= b { (a, b) } else { (b, a) };
+        (end > start).then_some(Self { start, end })
+    }
+
+    fn fraction(&self, time: f64) -> f64 {
+        ((time - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+}
+
+/// Range to export from the marks, falling back to the bookmarks around
+/// `position` for a missing mark
+pub fn marked_range(
+    mark_in: Option<f64>,
+    mark_out: Option<f64>,
+    bookmarks: &[Bookmark],
+    position: f64,
+) -> Option<ClipRange> {
+    let start = mark_in.or_else(|| {
+        bookmarks
+            .iter()
+            .rev()
+            .find(|bookmark| bookmark.time <= position)
+            .map(|bookmark| bookmark.time)
+    })?;
+    let end = mark_out.or_else(|| {
+        bookmarks
+            .iter()
+            .find(|bookmark| bookmark.time > position)
+            .map(|bookmark| bookmark.time)
+    })?;
+    ClipRange::between(start, end)
+}
+
+/// Seconds, or the name of a bookmark saved for `input`
+pub fn resolve_point(config: &Config, input: &Path, point: &str) -> Result<f64> {
+    if let Ok(seconds) = point.parse::<f64>() {
+        return Ok(seconds);
+    }
+    config
+        .bookmarks(input)
+        .iter()
+        .find(|bookmark| bookmark.name == point)
+        .map(|bookmark| bookmark.time)
+        .ok_or_else(|| anyhow!("'{}' is neither a time nor a bookmark", point))
+}
+
+/// Default file for a clip: the input's name with the range appended, in
+/// the working directory
+pub fn clip_path(input: &Path, range: ClipRange) -> PathBuf {
+    let name = input.to_string_lossy();
+    // URLs may carry a query after the file name
+    let name = name.split(['?', '#']).next().unwrap_or_default();
+    let file = Path::new(name);
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_else(|| "clip".into());
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy())
+        .unwrap_or_else(|| "mkv".into());
+    let clock = |seconds: f64| format_clock(seconds).replace(':', ".");
+    PathBuf::from(format!(
+        "{}-{}-{}.{}",
+        stem,
+        clock(range.start),
+        clock(range.end),
+        extension
+    ))
+}
+
+/// Copy the streams of `input` within `range` to `output`, whose container
+/// is chosen by its extension. `progress` gets the fraction done.
+pub fn export_clip(
+    input: &Path,
+    range: ClipRange,
+    output: &Path,
+    mut progress: impl FnMut(f64),
+) -> Result<()> {
+    let _ = ffmpeg::init();
+    let mut input_context = ffmpeg::format::input(&input)
+        .map_err(|e| anyhow!("Failed to open '{}': {}", input.display(), e))?;
+    let mut output_context = ffmpeg::format::output(&output)
+        .map_err(|e| anyhow!("Failed to create '{}': {}", output.display(), e))?;
+
+    let video_index = input_context
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .map(|stream| stream.index())
+        .ok_or_else(|| anyhow!("No video stream found in '{}'", input.display()))?;
+
+    // Input stream index to output stream index, for the streams kept
+    let mut mapping = vec![None; input_context.nb_streams() as usize];
+    for stream in input_context.streams() {
+        let medium = stream.parameters().medium();
+        if !matches!(
+            medium,
+            ffmpeg::media::Type::Video | ffmpeg::media::Type::Audio | ffmpeg::media::Type::Subtitle
+        ) {
+            continue;
+        }
+        let mut output_stream = output_context
+            .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
+            .map_err(|e| anyhow!("Failed to add stream: {}", e))?;
+        output_stream.set_parameters(stream.parameters());
+        // Codec tags of one container can be invalid in another
+        unsafe {
+            (*output_stream.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+        mapping[stream.index()] = Some(output_stream.index());
+    }
+    output_context
+        .write_header()
+        .map_err(|e| anyhow!("Failed to write '{}': {}", output.display(), e))?;
+
+    // Copying has to start at a keyframe, which the seek lands on
+    let start_ts = (range.start * ffmpeg::ffi::AV_TIME_BASE as f64) as i64;
+    input_context
+        .seek(start_ts, ..start_ts)
+        .map_err(|e| anyhow!("Failed to seek to {:.2}s: {}", range.start, e))?;
+
+    // Time of the first video packet, which becomes zero in the clip
+    let mut first_video: Option<f64> = None;
+    let mut reported = -1.0;
+    for (stream, mut packet) in input_context.packets() {
+        let Some(output_index) = mapping[stream.index()] else {
+            continue;
+        };
+        let time_base = stream.time_base();
+        let Some(ts) = packet.dts().or(packet.pts()) else {
+            continue;
+        };
+        let time = ts as f64 * f64::from(time_base);
+
+        let origin = match first_video {
+            Some(origin) => origin,
+            None if stream.index() == video_index => *first_video.insert(time),
+            // Audio from before the first video frame would play over nothing
+            None => continue,
+        };
+        if time < origin {
+            continue;
+        }
+        if time > range.end {
+            if stream.index() == video_index {
+                break;
+            }
+            continue;
+        }
+
+        let shift = (origin / f64::from(time_base)).round() as i64;
+        packet.set_pts(packet.pts().map(|pts| pts - shift));
+        packet.set_dts(packet.dts().map(|dts| dts - shift));
+        let output_time_base = output_context
+            .stream(output_index)
+            .map(|stream| stream.time_base())
+            .unwrap_or(time_base);
+        packet.rescale_ts(time_base, output_time_base);
+        packet.set_position(-1);
+        packet.set_stream(output_index);
+        packet
+            .write_interleaved(&mut output_context)
+            .map_err(|e| anyhow!("Failed to write '{}': {}", output.display(), e))?;
+
+        let done = range.fraction(time);
+        if done - reported >= 0.01 {
+            progress(done);
+            reported = done;
+        }
+    }
+
+    output_context
+        .write_trailer()
+        .map_err(|e| anyhow!("Failed to finish '{}': {}", output.display(), e))?;
+    progress(1.0);
+    debug!(
+        "Clip starts at keyframe {:.2}s for requested {:.2}s",
+        first_video.unwrap_or(range.start),
+        range.start
+    );
+    Ok(())
+}
+
+/// Where a background export is
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipStatus {
+    /// Fraction done
+    Running(f64),
+    Finished,
+    Failed(String),
+}
+
+/// A clip being written on another thread
+pub struct ClipJob {
+    output: PathBuf,
+    events: Receiver<ClipStatus>,
+    status: ClipStatus,
+}
+
+impl ClipJob {
+    /// Start writing `range` of `input` to `output`
+    pub fn spawn(input: &Path, range: ClipRange, output: PathBuf) -> Self {
+        let (sender, events) = mpsc::channel();
+        let input = input.to_path_buf();
+        let path = output.clone();
+        info!(
+            "Exporting {}-{} to {}",
+            format_clock(range.start),
+            format_clock(range.end),
+            output.display()
+        );
+        thread::spawn(move || {
+            release_thread();
+            let progress = sender.clone();
+            let result = export_clip(&input, range, &path, |done| {
+                let _ = progress.send(ClipStatus::Running(done));
+            });
+            let _ = sender.send(match result {
+                Ok(()) => ClipStatus::Finished,
+                Err(e) => ClipStatus::Failed(e.to_string()),
+            });
+        });
+        Self {
+            output,
+            events,
+            status: ClipStatus::Running(0.0),
+        }
+    }
+
+    /// Latest status, taking in what the export thread reported
+    pub fn poll(&mut self) -> &ClipStatus {
+        loop {
+            match self.events.try_recv() {
+                Ok(status) => self.status = status,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if matches!(self.status, ClipStatus::Running(_)) {
+                        self.status = ClipStatus::Failed("export stopped".to_string());
+                    }
+                    break;
+                }
+            }
+        }
+        &self.status
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(time: f64, name: &str) -> Bookmark {
+        Bookmark {
+            time,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_clip_range() {
+        let bookmarks = [bookmark(10.0, "intro"), bookmark(90.0, "demo")];
+        let range = |start, end| Some(ClipRange { start, end });
+
+        assert_eq!(
+            marked_range(Some(30.0), Some(20.0), &[], 0.0),
+            range(20.0, 30.0)
+        );
+        assert_eq!(marked_range(Some(5.0), Some(5.0), &[], 0.0), None);
+        // Missing marks come from the bookmarks around the position
+        assert_eq!(
+            marked_range(None, None, &bookmarks, 40.0),
+            range(10.0, 90.0)
+        );
+        assert_eq!(
+            marked_range(Some(50.0), None, &bookmarks, 40.0),
+            range(50.0, 90.0)
+        );
+        assert_eq!(marked_range(None, None, &bookmarks, 95.0), None);
+
+        let mut config = Config::default();
+        let input = Path::new("https://example.com/talk.mp4?t=3");
+        config.add_bookmark(input, bookmark(90.0, "demo"));
+        assert_eq!(resolve_point(&config, input, "demo").unwrap(), 90.0);
+        assert_eq!(resolve_point(&config, input, "12.5").unwrap(), 12.5);
+        assert!(resolve_point(&config, input, "outro").is_err());
+
+        assert_eq!(
+            clip_path(input, ClipRange::between(10.0, 3725.0).unwrap()),
+            PathBuf::from("talk-00.10-1.02.05.mp4")
+        );
+    }
+}
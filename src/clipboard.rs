@@ -0,0 +1,77 @@
+//! Copying frames to the system clipboard
+//!
+//! `y` copies the frame on screen as plain text and `Y` as ANSI text with
+//! truecolor escapes. The text is sent with OSC 52, which asks the terminal
+//! to set the clipboard, so it also works over SSH. Terminals may ignore it
+//! or cap its size, and tmux only passes it on with `set-clipboard on`.
+
+use crate::converter::AsciiFrame;
+use crate::telnet::{encode_text, ColorDepth};
+use anyhow::Result;
+use std::io::Write;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for position in 0..4 {
+            if position <= chunk.len() {
+                let index = (group >> (18 - position * 6)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Text of a frame for the clipboard, with colors or without
+pub fn frame_text(frame: &AsciiFrame, with_colors: bool) -> String {
+    let depth = if with_colors {
+        ColorDepth::TrueColor
+    } else {
+        ColorDepth::Mono
+    };
+    encode_text(frame, depth)
+}
+
+/// Ask the terminal on `output` to put `text` on the clipboard
+pub fn copy_to_clipboard<W: Write>(output: &mut W, text: &str) -> Result<()> {
+    write!(output, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_frame() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+
+        let frame = AsciiFrame::new(2, 2, vec!['#', '.', ' ', '@'], vec![(255, 0, 0); 4]);
+        assert_eq!(frame_text(&frame, false), "#.\n @\n");
+        assert_eq!(
+            frame_text(&frame, true),
+            "\x1b[38;2;255;0;0m#.\x1b[0m\n\x1b[38;2;255;0;0m @\x1b[0m\n"
+        );
+
+        let mut output = Vec::new();
+        copy_to_clipboard(&mut output, "#.\n").unwrap();
+        assert_eq!(output, b"\x1b]52;c;Iy4K\x07");
+    }
+}
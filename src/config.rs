@@ -0,0 +1,182 @@
+use crate::annotations::Annotation;
+use crate::bookmarks::{insert_mark, Bookmark};
+use crate::charset::Charset;
+use crate::decoder::source_location;
+use crate::loop_point::LoopPoint;
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the config file location
+pub const CONFIG_ENV_VAR: &str = "ASCII_PLAYER_CONFIG";
+
+/// Persistent user configuration stored as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Character ramp produced by `ascii-player calibrate`
+    pub ramp: Option<String>,
+    /// Ink coverage of each `ramp` character, used as density weights
+    pub ramp_weights: Option<Vec<f64>>,
+    /// Always use high-contrast mode, as with `--high-contrast`
+    pub high_contrast: bool,
+    /// Always use reduced-motion mode, as with `--reduced-motion`
+    pub reduced_motion: bool,
+    /// Upload endpoint for `--share` given without a URL, instead of
+    /// asciinema.org
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_url: Option<String>,
+    /// Effects chain used when neither `--effect` nor `--profile` is given
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<String>,
+    /// Key bindings over the defaults, such as `"x": "quit"`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+    /// Named effects chains for `--profile`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Vec<String>>,
+    /// Timeline bookmarks per input, keyed by its source location
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub bookmarks: BTreeMap<String, Vec<Bookmark>>,
+    /// Notes on moments of each input, keyed by its source location
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, Vec<Annotation>>,
+    /// Detected loop points of short videos, keyed by source location
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub loop_points: BTreeMap<String, LoopPoint>,
+    /// Why the config file could not be loaded; saving would replace what
+    /// is in it with defaults, so it is refused
+    #[serde(skip)]
+    load_error: Option<String>,
+}
+
+impl Config {
+    /// Location of the config file.
+    ///
+    /// Uses `$ASCII_PLAYER_CONFIG` if set, otherwise
+    /// `$XDG_CONFIG_HOME/ascii-player/config.json` falling back to
+    /// `~/.config/ascii-player/config.json`.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV_VAR) {
+            return Some(PathBuf::from(path));
+        }
+
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("ascii-player").join("config.json"))
+    }
+
+    /// Load the config file, returning defaults if it does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        if !path.exists() {
+            debug!("No config file at {}, using defaults", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        let config = serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid config file '{}': {}", path.display(), e))?;
+        debug!("Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    /// Load the config file, falling back to defaults with a warning if it
+    /// can't be read. Defaults used that way are never saved.
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            warn!("Ignoring config file: {}", e);
+            Self {
+                load_error: Some(e.to_string()),
+                ..Self::default()
+            }
+        })
+    }
+
+    /// Write the config file, creating its directory if needed
+    pub fn save(&self) -> Result<PathBuf> {
+        if let Some(ref error) = self.load_error {
+            return Err(anyhow!(
+                "Not saving over a config file that could not be loaded ({})",
+                error
+            ));
+        }
+        let path = Self::path()
+            .ok_or_else(|| anyhow!("Cannot determine config location (HOME is not set)"))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        debug!("Saved config to {}", path.display());
+        Ok(path)
+    }
+
+    /// Calibrated ramp as a charset, if a valid one was saved
+    pub fn charset(&self) -> Option<Charset> {
+        let glyphs: Vec<char> = self.ramp.as_deref()?.chars().collect();
+        Charset::new(glyphs, self.ramp_weights.clone()).ok()
+    }
+
+    /// Bookmarks of an input in timeline order
+    pub fn bookmarks(&self, input: &Path) -> &[Bookmark] {
+        self.bookmarks
+            .get(&source_location(input))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a bookmark to an input, keeping its list in timeline order
+    pub fn add_bookmark(&mut self, input: &Path, bookmark: Bookmark) {
+        insert_mark(
+            self.bookmarks.entry(source_location(input)).or_default(),
+            bookmark,
+        );
+    }
+
+    /// Notes on an input in timeline order
+    pub fn annotations(&self, input: &Path) -> &[Annotation] {
+        self.annotations
+            .get(&source_location(input))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a note to an input, keeping its list in timeline order
+    pub fn add_annotation(&mut self, input: &Path, annotation: Annotation) {
+        insert_mark(
+            self.annotations.entry(source_location(input)).or_default(),
+            annotation,
+        );
+    }
+
+    /// Loop point found for an input, if it was searched before
+    pub fn loop_point(&self, input: &Path) -> Option<LoopPoint> {
+        self.loop_points.get(&source_location(input)).copied()
+    }
+
+    pub fn set_loop_point(&mut self, input: &Path, loop_point: LoopPoint) {
+        self.loop_points.insert(source_location(input), loop_point);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unreadable_config_is_not_saved_over() {
+        let config = Config {
+            load_error: Some("expected value at line 1 column 1".to_string()),
+            ..Config::default()
+        };
+        let error = config.save().unwrap_err().to_string();
+        assert!(error.contains("could not be loaded"), "{}", error);
+    }
+}
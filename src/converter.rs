@@ -1,10 +1,29 @@
-use crate::cli::ColorPalette;
+use crate::ambient::blend;
+use crate::autoramp::{AutoRamp, RampWindow};
+use crate::charset::validate_glyphs;
+use crate::cli::{parse_hex_color, ColorPalette, Dither, Style, Theme};
+use crate::crop::{crop_frame, CropRect};
+use crate::cvd::CvdFilter;
+use crate::damage::Damage;
 use crate::decoder::VideoFrame;
-use anyhow::Result;
-use log::debug;
+use crate::layout;
+use crate::palettes::Colormap;
+use crate::roi::{window_at, RoiTracker};
+use crate::scale::{self, Scaler};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use log::{debug, warn};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::debug_span;
 
-/// Represents an ASCII frame with characters and colors
-#[derive(Debug, Clone)]
+/// Represents an ASCII frame with characters and colors.
+///
+/// Build frames with `AsciiFrame::new` and the `with_*` methods; more fields
+/// may be added in future releases.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub struct AsciiFrame {
     /// ASCII characters for each position
     pub characters: Vec<char>,
@@ -12,6 +31,8 @@ pub struct AsciiFrame {
     pub fg_colors: Vec<(u8, u8, u8)>,
     /// Background colors for each position (RGB) - Optional
     pub bg_colors: Option<Vec<(u8, u8, u8)>>,
+    /// Text attribute for each position - Optional
+    pub attributes: Option<Vec<CellAttribute>>,
     /// Frame width in characters
     pub width: u16,
     /// Frame height in characters
@@ -22,8 +43,211 @@ pub struct AsciiFrame {
     pub frame_number: u64,
 }
 
-/// ASCII conversion configuration
+impl AsciiFrame {
+    /// A frame of `width` x `height` cells, row by row
+    pub fn new(
+        width: u16,
+        height: u16,
+        characters: Vec<char>,
+        fg_colors: Vec<(u8, u8, u8)>,
+    ) -> Self {
+        Self {
+            characters,
+            fg_colors,
+            bg_colors: None,
+            attributes: None,
+            width,
+            height,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    /// Give every cell a background color
+    pub fn with_background(mut self, bg_colors: Vec<(u8, u8, u8)>) -> Self {
+        self.bg_colors = Some(bg_colors);
+        self
+    }
+
+    /// Give every cell a text attribute
+    pub fn with_attributes(mut self, attributes: Vec<CellAttribute>) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+
+    /// Set the video timestamp and frame number the frame came from
+    pub fn with_timestamp(mut self, timestamp: f64, frame_number: u64) -> Self {
+        self.timestamp = timestamp;
+        self.frame_number = frame_number;
+        self
+    }
+}
+
+/// Intensity attribute of a cell. Bold draws brighter and dim darker on
+/// most terminals, adding steps between the glyphs of the ramp.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellAttribute {
+    #[default]
+    Normal,
+    Bold,
+    Dim,
+}
+
+/// Default for [`ConversionConfig::max_cells`]
+pub const DEFAULT_MAX_CELLS: usize = 1_000_000;
+
+/// Shrink a `width` x `height` grid, keeping its shape, until it has at most
+/// `max_cells` cells (0 means no limit)
+pub fn cap_grid(width: u16, height: u16, max_cells: usize) -> (u16, u16) {
+    let cells = width as usize * height as usize;
+    if max_cells == 0 || cells <= max_cells {
+        return (width, height);
+    }
+
+    let scale = (max_cells as f64 / cells as f64).sqrt();
+    (
+        ((width as f64 * scale) as u16).max(1),
+        ((height as f64 * scale) as u16).max(1),
+    )
+}
+
+/// Dense characters used by high-contrast mode, from dark to bright
+pub const HIGH_CONTRAST_RAMP: [char; 3] = [' ', '#', '█'];
+
+/// Bright colors high-contrast mode draws with on a black background
+pub const HIGH_CONTRAST_COLORS: [(u8, u8, u8); 5] = [
+    (255, 255, 255),
+    (255, 255, 0),
+    (0, 255, 255),
+    (0, 255, 0),
+    (255, 0, 255),
+];
+
+/// Nearest high-contrast color to the hue of `(r, g, b)`
+pub fn high_contrast_color(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    // Compare hues at full brightness so dark colors don't all become white
+    let max = r.max(g).max(b).max(1) as f64;
+    let scaled = [r, g, b].map(|channel| channel as f64 * 255.0 / max);
+    HIGH_CONTRAST_COLORS
+        .into_iter()
+        .min_by(|a, b| {
+            let distance = |(cr, cg, cb): (u8, u8, u8)| {
+                (scaled[0] - cr as f64).powi(2)
+                    + (scaled[1] - cg as f64).powi(2)
+                    + (scaled[2] - cb as f64).powi(2)
+            };
+            distance(*a).total_cmp(&distance(*b))
+        })
+        .unwrap_or((255, 255, 255))
+}
+
+/// Reduces the colors of the picture before characters are chosen
+pub trait ColorQuantizer: fmt::Debug + Send + Sync {
+    fn quantize(&self, color: (u8, u8, u8)) -> (u8, u8, u8);
+
+    /// Typical distance between neighboring values of a channel in the
+    /// output, which ordered dithering spreads colors over; 0 leaves colors
+    /// undithered
+    fn step(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Rounds every channel to one of `levels` evenly spaced values
+#[derive(Debug, Clone, Copy)]
+pub struct Posterize {
+    levels: u8,
+}
+
+impl Posterize {
+    /// Posterize to `levels` values per channel (at least 2)
+    pub fn new(levels: u8) -> Self {
+        Self {
+            levels: levels.max(2),
+        }
+    }
+}
+
+impl ColorQuantizer for Posterize {
+    fn quantize(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let steps = (self.levels - 1) as f64;
+        let snap = |value: u8| ((value as f64 / 255.0 * steps).round() * 255.0 / steps) as u8;
+        (snap(r), snap(g), snap(b))
+    }
+
+    fn step(&self) -> f32 {
+        255.0 / (self.levels - 1) as f32
+    }
+}
+
+/// Snaps every color to the nearest of a fixed palette
+#[derive(Debug, Clone)]
+pub struct FixedPalette {
+    colors: Vec<(u8, u8, u8)>,
+}
+
+impl FixedPalette {
+    pub fn new(colors: Vec<(u8, u8, u8)>) -> Result<Self> {
+        if colors.is_empty() {
+            return Err(anyhow!("Palette has no colors"));
+        }
+        Ok(Self { colors })
+    }
+
+    /// Parse a `.hex` palette: one `rrggbb` color per line, as exported by
+    /// Lospec. Blank lines and lines starting with `;` or `//` are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let colors = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with("//"))
+            .map(|line| parse_hex_color(line).map_err(|e| anyhow!(e)))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(colors)
+    }
+
+    /// Load a `.hex` palette file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read palette {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid palette {}", path.display()))
+    }
+
+    pub fn colors(&self) -> &[(u8, u8, u8)] {
+        &self.colors
+    }
+}
+
+impl ColorQuantizer for FixedPalette {
+    fn quantize(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let distance = |&(pr, pg, pb): &(u8, u8, u8)| {
+            let (dr, dg, db) = (
+                r as i32 - pr as i32,
+                g as i32 - pg as i32,
+                b as i32 - pb as i32,
+            );
+            dr * dr + dg * dg + db * db
+        };
+        self.colors
+            .iter()
+            .copied()
+            .min_by_key(distance)
+            .unwrap_or((r, g, b))
+    }
+
+    /// As if the colors were spread evenly over the color cube
+    fn step(&self) -> f32 {
+        let levels = (self.colors.len() as f32).cbrt().max(2.0);
+        255.0 / (levels - 1.0)
+    }
+}
+
+/// ASCII conversion configuration.
+///
+/// Start from `ConversionConfig::default()` and adjust it with the `with_*`
+/// methods; more options may be added in future releases.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ConversionConfig {
     /// Character palette to use
     pub palette: ColorPalette,
@@ -33,12 +257,51 @@ pub struct ConversionConfig {
     pub alpha_threshold: Option<u8>,
     /// Custom ASCII character set
     pub ascii_chars: Vec<char>,
+    /// Relative density of each character in `ascii_chars` (linear if `None`)
+    pub char_weights: Option<Vec<f64>>,
     /// Aspect ratio correction factor
     pub aspect_ratio: f64,
     /// Brightness adjustment (-1.0 to 1.0)
     pub brightness: f64,
     /// Contrast adjustment (0.0 to 2.0, 1.0 = normal)
     pub contrast: f64,
+    /// Gamma correction before brightness and contrast (1.0 = none,
+    /// higher lifts the shadows)
+    pub gamma: f64,
+    /// Map source pixels to whole cells when the source fits the terminal
+    pub scale_exact: bool,
+    /// Gradient stops (dark to bright) replacing source colors by luminance
+    pub theme: Option<Vec<(u8, u8, u8)>>,
+    /// Largest grid converted; bigger terminals get a downsampled frame
+    pub max_cells: usize,
+    /// Draw with a few bright colors on black
+    pub high_contrast: bool,
+    /// Processing applied to the picture before characters are chosen
+    pub style: Style,
+    /// Colors of `Style::Heatmap`
+    pub colormap: Colormap,
+    /// Color reductions applied in order after brightness and contrast
+    pub quantizers: Vec<Arc<dyn ColorQuantizer>>,
+    /// Fill the whole terminal, cropping the picture to its shape
+    pub cover: bool,
+    /// In cover mode, pan the crop toward bright or moving areas
+    pub roi_follow: bool,
+    /// Color vision deficiency filter; daltonizing comes before the
+    /// quantizers, simulation applies to the final cell colors
+    pub cvd: Option<CvdFilter>,
+    /// Strength kept by every color and the background it fades toward
+    pub ambient: Option<(f64, (u8, u8, u8))>,
+    /// Hide the rounding of each cell's character and quantized color so
+    /// gradients don't band
+    pub dither: Dither,
+    /// Mark cells brighter or darker than their glyph bold or dim
+    pub use_attributes: bool,
+    /// Filter shrinking the picture to the grid
+    pub scaler: Scaler,
+    /// Unsharp mask strength applied after scaling (0.0 is off)
+    pub sharpen: f64,
+    /// Spread the ramp over the luminance range of each scene
+    pub auto_ramp: bool,
 }
 
 impl Default for ConversionConfig {
@@ -48,22 +311,615 @@ impl Default for ConversionConfig {
             transparent: false,
             alpha_threshold: None,
             ascii_chars: vec![' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'],
+            char_weights: None,
             aspect_ratio: 0.5, // Terminal characters are typically twice as tall as wide
             brightness: 0.0,
             contrast: 1.0,
+            gamma: 1.0,
+            scale_exact: false,
+            theme: None,
+            max_cells: DEFAULT_MAX_CELLS,
+            high_contrast: false,
+            style: Style::Normal,
+            colormap: Colormap::Turbo,
+            quantizers: Vec::new(),
+            cover: false,
+            roi_follow: false,
+            cvd: None,
+            ambient: None,
+            dither: Dither::None,
+            use_attributes: false,
+            scaler: Scaler::Nearest,
+            sharpen: 0.0,
+            auto_ramp: false,
+        }
+    }
+}
+
+impl ConversionConfig {
+    /// Use a character palette
+    pub fn with_palette(mut self, palette: ColorPalette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Leave dark cells empty so the terminal background shows through,
+    /// optionally below a luminance threshold
+    pub fn with_transparent(mut self, transparent: bool, alpha_threshold: Option<u8>) -> Self {
+        self.transparent = transparent;
+        self.alpha_threshold = alpha_threshold;
+        self
+    }
+
+    /// Use a character ramp from dark to bright, with optional per-character
+    /// density weights
+    pub fn with_ascii_chars(mut self, ascii_chars: Vec<char>, weights: Option<Vec<f64>>) -> Self {
+        self.ascii_chars = ascii_chars;
+        self.char_weights = weights;
+        self
+    }
+
+    /// Set the width-to-height ratio of a terminal cell
+    pub fn with_aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    /// Set brightness (-1.0 to 1.0) and contrast (0.0 to 2.0)
+    pub fn with_adjustments(mut self, brightness: f64, contrast: f64) -> Self {
+        self.brightness = brightness;
+        self.contrast = contrast;
+        self
+    }
+
+    /// Set gamma correction (1.0 = none, higher lifts the shadows)
+    pub fn with_gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Map source pixels to whole cells when the source fits the terminal
+    pub fn with_scale_exact(mut self, scale_exact: bool) -> Self {
+        self.scale_exact = scale_exact;
+        self
+    }
+
+    /// Replace source colors by a gradient from dark to bright
+    pub fn with_theme(mut self, theme: Option<Vec<(u8, u8, u8)>>) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Limit the number of cells converted per frame (0 means no limit)
+    pub fn with_max_cells(mut self, max_cells: usize) -> Self {
+        self.max_cells = max_cells;
+        self
+    }
+
+    /// Process the picture before characters are chosen
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set the colormap of `Style::Heatmap`
+    pub fn with_colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    /// Fill the terminal by cropping instead of letterboxing, optionally
+    /// panning the crop to follow the region of interest
+    pub fn with_cover(mut self, cover: bool, roi_follow: bool) -> Self {
+        self.cover = cover;
+        self.roi_follow = roi_follow;
+        self
+    }
+
+    /// Add a color reduction after those already configured
+    pub fn with_quantizer(mut self, quantizer: Arc<dyn ColorQuantizer>) -> Self {
+        self.quantizers.push(quantizer);
+        self
+    }
+
+    /// Simulate or compensate for a color vision deficiency
+    pub fn with_cvd(mut self, cvd: Option<CvdFilter>) -> Self {
+        self.cvd = cvd;
+        self
+    }
+
+    /// Fade every color toward `background`, keeping `factor` (0.0-1.0)
+    /// of its strength
+    pub fn with_ambient(mut self, factor: Option<f64>, background: (u8, u8, u8)) -> Self {
+        self.ambient = factor.map(|factor| (factor, background));
+        self
+    }
+
+    /// Dither characters and quantized colors
+    pub fn with_dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Emit bold and dim attributes for cells between two glyphs
+    pub fn with_attributes(mut self, use_attributes: bool) -> Self {
+        self.use_attributes = use_attributes;
+        self
+    }
+
+    /// Shrink the picture with `scaler`, then sharpen it by `sharpen`
+    pub fn with_scaler(mut self, scaler: Scaler, sharpen: f64) -> Self {
+        self.scaler = scaler;
+        self.sharpen = sharpen;
+        self
+    }
+
+    /// Fit the ramp to the luminance range of each scene
+    pub fn with_auto_ramp(mut self, auto_ramp: bool) -> Self {
+        self.auto_ramp = auto_ramp;
+        self
+    }
+
+    /// Draw with [`HIGH_CONTRAST_RAMP`] and [`HIGH_CONTRAST_COLORS`] on
+    /// black, replacing the ramp and theme
+    pub fn with_high_contrast(mut self, high_contrast: bool) -> Self {
+        self.high_contrast = high_contrast;
+        if high_contrast {
+            self.ascii_chars = HIGH_CONTRAST_RAMP.to_vec();
+            self.char_weights = None;
+            self.theme = None;
+        }
+        self
+    }
+
+    /// Apply comma separated `key=value` overrides, e.g. `contrast=1.5,theme=amber`.
+    ///
+    /// Supported keys: `brightness`, `contrast`, `palette`, `theme` (a preset
+    /// or `none`), `ramp` (taken verbatim, so it cannot contain commas),
+    /// `scale-exact`, `max-cells`, `high-contrast`, `style`, `colormap`,
+    /// `posterize` (levels per channel, added after existing quantizers),
+    /// `cover`, `roi-follow`, `dither` (a mode), `cvd` (a filter or
+    /// `none`), `scaler`, `sharpen` (strength up to 2, 0 for off) and
+    /// `auto-ramp`.
+    pub fn apply_overrides(&mut self, spec: &str) -> Result<()> {
+        for pair in spec.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value, got '{}'", pair))?;
+            // Ramps keep their whitespace: a leading space is the darkest glyph
+            let (key, raw_value, value) = (key.trim(), value, value.trim());
+
+            let parse_f64 = |value: &str| {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Invalid number for {}: '{}'", key, value))
+            };
+
+            match key {
+                "brightness" => self.brightness = parse_f64(value)?,
+                "contrast" => self.contrast = parse_f64(value)?,
+                "palette" => {
+                    self.palette = ColorPalette::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown palette '{}'", value))?;
+                }
+                "theme" if value == "none" => self.theme = None,
+                "theme" => {
+                    let theme = Theme::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown theme '{}'", value))?;
+                    self.theme = Some(theme.stops());
+                }
+                "ramp" => {
+                    let chars: Vec<char> = raw_value.chars().collect();
+                    if chars.len() < 2 {
+                        return Err(anyhow!("Ramp needs at least 2 characters"));
+                    }
+                    validate_glyphs(&chars)?;
+                    self.ascii_chars = chars;
+                    self.char_weights = None;
+                }
+                "scale-exact" => {
+                    self.scale_exact = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid boolean for {}: '{}'", key, value))?;
+                }
+                "high-contrast" => {
+                    let high_contrast = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid boolean for {}: '{}'", key, value))?;
+                    *self = std::mem::take(self).with_high_contrast(high_contrast);
+                }
+                "style" => {
+                    self.style = Style::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown style '{}'", value))?;
+                }
+                "posterize" => {
+                    let levels: u8 = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid number for {}: '{}'", key, value))?;
+                    self.quantizers.push(Arc::new(Posterize::new(levels)));
+                }
+                "dither" => {
+                    self.dither = Dither::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown dither mode '{}'", value))?;
+                }
+                "cover" | "roi-follow" | "auto-ramp" => {
+                    let enabled = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid boolean for {}: '{}'", key, value))?;
+                    match key {
+                        "cover" => self.cover = enabled,
+                        "roi-follow" => self.roi_follow = enabled,
+                        _ => self.auto_ramp = enabled,
+                    }
+                }
+                "scaler" => {
+                    self.scaler = Scaler::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown scaler '{}'", value))?;
+                }
+                "sharpen" => {
+                    let amount = parse_f64(value)?;
+                    if !(0.0..=2.0).contains(&amount) {
+                        return Err(anyhow!("Sharpen amount must be between 0.0 and 2.0"));
+                    }
+                    self.sharpen = amount;
+                }
+                "cvd" if value == "none" => self.cvd = None,
+                "cvd" => self.cvd = Some(value.parse().map_err(|e: String| anyhow!(e))?),
+                "colormap" => {
+                    self.colormap = Colormap::from_str(value, true)
+                        .map_err(|_| anyhow!("Unknown colormap '{}'", value))?;
+                }
+                "max-cells" => {
+                    self.max_cells = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid number for {}: '{}'", key, value))?;
+                }
+                _ => return Err(anyhow!("Unknown setting '{}'", key)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `other` lays frames out on the same grid, so the two can be
+    /// converted side by side
+    pub fn same_grid(&self, other: &Self) -> bool {
+        self.scale_exact == other.scale_exact
+            && self.cover == other.cover
+            && self.max_cells == other.max_cells
+    }
+}
+
+/// Anything that turns decoded frames into ASCII frames
+pub trait AsciiConverter {
+    /// Convert a video frame for a terminal of the given size
+    fn convert(
+        &self,
+        frame: &VideoFrame,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<AsciiFrame>;
+
+    /// Convert a frame of which only `damage` changed since the frame that
+    /// gave `previous`. Also returns the cells that may differ from
+    /// `previous`, `None` for all of them; the default converts the whole
+    /// frame.
+    fn convert_damaged(
+        &self,
+        frame: &VideoFrame,
+        previous: &AsciiFrame,
+        damage: &Damage,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<(AsciiFrame, Option<Vec<usize>>)> {
+        let _ = (previous, damage);
+        let ascii = self.convert(frame, terminal_width, terminal_height)?;
+        Ok((ascii, None))
+    }
+}
+
+/// Amplification of frame differences in `Style::Motion`
+const MOTION_GAIN: u16 = 4;
+/// Channel differences below this are noise and stay blank
+const MOTION_NOISE_FLOOR: u8 = 8;
+
+/// Sobel gradient strength, in luminance steps per cell, above which
+/// `Style::Edges` draws a directional glyph
+pub const EDGE_THRESHOLD: f32 = 40.0;
+
+/// Directional glyph for every cell of a `width` x `height` luminance grid
+/// on a strong edge, from its Sobel gradient; None in flat areas
+fn edge_glyphs(luma: &[u8], width: usize, height: usize) -> Vec<Option<char>> {
+    // Edge pixels repeat the border
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        luma[y * width + x] as f32
+    };
+    let mut glyphs = Vec::with_capacity(width * height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            // Each kernel sums four times the step across the edge
+            let (gx, gy) = (gx / 4.0, gy / 4.0);
+            if gx.hypot(gy) < EDGE_THRESHOLD {
+                glyphs.push(None);
+                continue;
+            }
+
+            // Edges run across the gradient; rows grow downward, so an edge
+            // rising to the right has its gradient pointing down and right
+            let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+            let glyph = match angle {
+                a if !(22.5..157.5).contains(&a) => '|',
+                a if a < 67.5 => '/',
+                a if a < 112.5 => {
+                    // Brighter above: the line sits under the bright area
+                    if gy < 0.0 {
+                        '_'
+                    } else {
+                        '-'
+                    }
+                }
+                _ => '\\',
+            };
+            glyphs.push(Some(glyph));
+        }
+    }
+    glyphs
+}
+
+/// Previous frame kept by [`MotionFilter`]
+#[derive(Debug)]
+struct MotionReference {
+    timestamp: f64,
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+/// Temporal filter behind `Style::Motion`: replaces every pixel by how much
+/// it changed since the previous frame, as a shade of gray
+#[derive(Debug, Default)]
+pub struct MotionFilter {
+    previous: Mutex<Option<MotionReference>>,
+}
+
+impl MotionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter RGB `data` of a `width` x `height` frame in place. The first
+    /// frame, and the first after a resize or a jump back in time, is blank.
+    pub fn apply(&self, data: &mut [u8], width: u32, height: u32, timestamp: f64) {
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        let current = MotionReference {
+            timestamp,
+            width,
+            height,
+            data: data.to_vec(),
+        };
+
+        let reference = previous.replace(current).filter(|reference| {
+            reference.width == width
+                && reference.height == height
+                && reference.timestamp < timestamp
+        });
+        let Some(reference) = reference else {
+            data.fill(0);
+            return;
+        };
+
+        for (pixel, before) in data.chunks_exact_mut(3).zip(reference.data.chunks_exact(3)) {
+            let change = (0..3)
+                .map(|c| pixel[c].abs_diff(before[c]))
+                .max()
+                .unwrap_or(0);
+            let value = if change < MOTION_NOISE_FLOOR {
+                0
+            } else {
+                (change as u16 * MOTION_GAIN).min(255) as u8
+            };
+            pixel.fill(value);
+        }
+    }
+}
+
+/// Floyd–Steinberg error buffers for the row being converted and the next.
+///
+/// Both rows have a padding cell at each end so error spread past the edge
+/// of the frame is dropped without bounds checks.
+struct ErrorDiffusion {
+    channels: usize,
+    current: Vec<f32>,
+    next: Vec<f32>,
+}
+
+impl ErrorDiffusion {
+    fn new(width: usize, channels: usize) -> Self {
+        Self {
+            channels,
+            current: vec![0.0; (width + 2) * channels],
+            next: vec![0.0; (width + 2) * channels],
         }
     }
+
+    /// Error carried to column `x` of the current row
+    fn error(&self, x: usize, channel: usize) -> f32 {
+        self.current[(x + 1) * self.channels + channel]
+    }
+
+    /// Spread the error left at column `x`: 7/16 to the right, 3/16 below
+    /// left, 5/16 below and 1/16 below right
+    fn spread(&mut self, x: usize, channel: usize, error: f32) {
+        let index = (x + 1) * self.channels + channel;
+        self.current[index + self.channels] += error * 7.0 / 16.0;
+        self.next[index - self.channels] += error * 3.0 / 16.0;
+        self.next[index] += error * 5.0 / 16.0;
+        self.next[index + self.channels] += error * 1.0 / 16.0;
+    }
+
+    fn next_row(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.next.fill(0.0);
+    }
 }
 
+/// Threshold in (0, 1) of cell `(x, y)` in a `size` x `size` Bayer matrix
+/// (`size` a power of two), tiled over the frame
+fn bayer_threshold(size: usize, x: usize, y: usize) -> f32 {
+    // Each doubling of the matrix is [[4M, 4M+2], [4M+3, 4M+1]]
+    let mut rank = 0;
+    let mut half = size / 2;
+    let (mut x, mut y) = (x % size, y % size);
+    while half > 0 {
+        let quadrant = match (x >= half, y >= half) {
+            (false, false) => 0,
+            (true, false) => 2,
+            (false, true) => 3,
+            (true, true) => 1,
+        };
+        rank += quadrant * (size / half / 2).pow(2);
+        (x, y) = (x % half, y % half);
+        half /= 2;
+    }
+    (rank as f32 + 0.5) / (size * size) as f32
+}
+
+/// One converted cell: character, foreground, background and attribute
+type Cell = (char, (u8, u8, u8), (u8, u8, u8), CellAttribute);
+
 /// Video frame to ASCII converter
 pub struct FrameConverter {
     config: ConversionConfig,
+    /// Character index for every luminance value
+    char_lut: [usize; 256],
+    /// Luminance each character stands for, to measure dithering error
+    char_levels: Vec<f32>,
+    /// Theme or heatmap color for every luminance value
+    theme_lut: Option<Vec<(u8, u8, u8)>>,
+    /// Gamma corrected value of every channel value, unless gamma is 1.0
+    gamma_lut: Option<Vec<u8>>,
+    /// Frame differencing for `Style::Motion`
+    motion: Option<MotionFilter>,
+    /// Region of interest followed by the cover crop
+    roi: Option<Mutex<RoiTracker>>,
+    /// Ramp window fitted to the current scene
+    auto_ramp: Option<Mutex<AutoRamp>>,
 }
 
 impl FrameConverter {
     /// Create a new frame converter with the given configuration
     pub fn new(config: ConversionConfig) -> Self {
-        Self { config }
+        let char_lut = Self::build_char_lut(&config);
+        let char_levels = Self::build_char_levels(&config);
+        let theme_lut = match config.style {
+            Style::Heatmap => Some(config.colormap.lut()),
+            _ => config.theme.as_deref().and_then(Self::build_theme_lut),
+        };
+        let gamma_lut = (config.gamma != 1.0 && config.gamma > 0.0).then(|| {
+            (0..=255u8)
+                .map(|value| {
+                    (255.0 * (value as f64 / 255.0).powf(1.0 / config.gamma)).round() as u8
+                })
+                .collect()
+        });
+        let motion = (config.style == Style::Motion).then(MotionFilter::new);
+        let roi = (config.cover && config.roi_follow).then(|| Mutex::new(RoiTracker::new()));
+        let auto_ramp = config.auto_ramp.then(|| Mutex::new(AutoRamp::new()));
+        Self {
+            config,
+            char_lut,
+            char_levels,
+            theme_lut,
+            gamma_lut,
+            motion,
+            roi,
+            auto_ramp,
+        }
+    }
+
+    /// Sample the theme gradient once per luminance value
+    fn build_theme_lut(stops: &[(u8, u8, u8)]) -> Option<Vec<(u8, u8, u8)>> {
+        let colors: Vec<colorgrad::Color> = stops
+            .iter()
+            .map(|&(r, g, b)| colorgrad::Color::from_rgba8(r, g, b, 255))
+            .collect();
+
+        let gradient = match colorgrad::CustomGradient::new().colors(&colors).build() {
+            Ok(gradient) => gradient,
+            Err(e) => {
+                warn!("Ignoring invalid color theme: {}", e);
+                return None;
+            }
+        };
+
+        Some(
+            (0..=255u8)
+                .map(|luminance| {
+                    let [r, g, b, _] = gradient.at(luminance as f64 / 255.0).to_rgba8();
+                    (r, g, b)
+                })
+                .collect(),
+        )
+    }
+
+    /// Precompute luminance to character index mapping.
+    ///
+    /// Without weights the ramp is assumed evenly spaced. With weights each
+    /// luminance picks the glyph whose normalized density is closest.
+    fn build_char_lut(config: &ConversionConfig) -> [usize; 256] {
+        let count = config.ascii_chars.len().max(1);
+        let mut lut = [0usize; 256];
+
+        let weights = config
+            .char_weights
+            .as_ref()
+            .filter(|weights| weights.len() == count && count > 1);
+
+        for (luminance, entry) in lut.iter_mut().enumerate() {
+            let normalized = luminance as f64 / 255.0;
+
+            *entry = match weights {
+                Some(weights) => {
+                    let min = weights[0];
+                    let range = (weights[count - 1] - min).max(f64::EPSILON);
+                    (0..count)
+                        .min_by(|&a, &b| {
+                            let da = ((weights[a] - min) / range - normalized).abs();
+                            let db = ((weights[b] - min) / range - normalized).abs();
+                            da.total_cmp(&db)
+                        })
+                        .unwrap_or(0)
+                }
+                None => ((normalized * (count - 1) as f64).round() as usize).min(count - 1),
+            };
+        }
+
+        lut
+    }
+
+    /// Luminance (0-255) of each ramp character, on the same scale the
+    /// character lookup uses
+    fn build_char_levels(config: &ConversionConfig) -> Vec<f32> {
+        let count = config.ascii_chars.len().max(1);
+        match config.char_weights.as_ref() {
+            Some(weights) if weights.len() == count && count > 1 => {
+                let min = weights[0];
+                let range = (weights[count - 1] - min).max(f64::EPSILON);
+                weights
+                    .iter()
+                    .map(|weight| ((weight - min) / range * 255.0) as f32)
+                    .collect()
+            }
+            _ => (0..count)
+                .map(|index| index as f32 * 255.0 / (count - 1).max(1) as f32)
+                .collect(),
+        }
     }
 
     /// Convert a video frame to ASCII representation
@@ -73,23 +929,34 @@ impl FrameConverter {
         terminal_width: u16,
         terminal_height: u16,
     ) -> Result<AsciiFrame> {
+        let _span = debug_span!("convert").entered();
+        let (terminal_width, terminal_height) =
+            cap_grid(terminal_width, terminal_height, self.config.max_cells);
         debug!(
             "Converting frame {}x{} to terminal {}x{}",
             frame.width, frame.height, terminal_width, terminal_height
         );
 
-        // Calculate target dimensions with aspect ratio correction
-        let (target_width, target_height) = self.calculate_target_dimensions(
-            frame.width,
-            frame.height,
-            terminal_width,
-            terminal_height,
+        let (target_width, target_height, _) = layout::compute(
+            (frame.width, frame.height),
+            (terminal_width, terminal_height),
+            &self.config,
         );
 
+        // Cover mode crops the picture to the terminal's shape and fills it
+        let cropped;
+        let frame = if self.config.cover {
+            let window = self.cover_window(frame, terminal_width, terminal_height);
+            cropped = crop_frame(frame, window)?;
+            &cropped
+        } else {
+            frame
+        };
+
         debug!("Target dimensions: {}x{}", target_width, target_height);
 
         // Resize frame data
-        let resized_data = self.resize_frame_data(
+        let mut resized_data = self.resize_frame_data(
             &frame.data,
             frame.width,
             frame.height,
@@ -97,6 +964,28 @@ impl FrameConverter {
             target_height as u32,
         )?;
 
+        // Temporal filters work on the resized picture, before characters
+        if let Some(ref motion) = self.motion {
+            motion.apply(
+                &mut resized_data,
+                target_width as u32,
+                target_height as u32,
+                frame.timestamp,
+            );
+        }
+
+        // The ramp is fitted to the scene from the adjusted picture
+        let window = self.auto_ramp.as_ref().map(|auto_ramp| {
+            let luma = resized_data.chunks_exact(3).map(|pixel| {
+                let (r, g, b) = self.adjust_color(pixel[0], pixel[1], pixel[2]);
+                self.calculate_luminance(r, g, b)
+            });
+            auto_ramp
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .update(luma)
+        });
+
         // Convert pixels to ASCII
         let mut characters = Vec::with_capacity((target_width * target_height) as usize);
         let mut fg_colors = Vec::with_capacity((target_width * target_height) as usize);
@@ -105,71 +994,64 @@ impl FrameConverter {
         } else {
             Some(Vec::with_capacity((target_width * target_height) as usize))
         };
+        let mut attributes = self
+            .config
+            .use_attributes
+            .then(|| Vec::with_capacity((target_width * target_height) as usize));
+
+        // Strong edges get directional glyphs instead of the ramp
+        let edges = (self.config.style == Style::Edges).then(|| {
+            let luma: Vec<u8> = resized_data
+                .chunks_exact(3)
+                .map(|pixel| self.calculate_luminance(pixel[0], pixel[1], pixel[2]))
+                .collect();
+            edge_glyphs(&luma, target_width as usize, target_height as usize)
+        });
+
+        // Luminance error is spread over the characters; color error only
+        // when quantizers round the colors
+        let diffuse = self.config.dither == Dither::FloydSteinberg;
+        let mut luma_error = diffuse.then(|| ErrorDiffusion::new(target_width as usize, 1));
+        let mut color_error = (diffuse && !self.config.quantizers.is_empty())
+            .then(|| ErrorDiffusion::new(target_width as usize, 3));
 
         for y in 0..target_height {
+            if y > 0 {
+                for diffusion in luma_error.iter_mut().chain(color_error.iter_mut()) {
+                    diffusion.next_row();
+                }
+            }
             for x in 0..target_width {
                 let pixel_index = ((y * target_width + x) * 3) as usize;
 
-                if pixel_index + 2 < resized_data.len() {
-                    let r = resized_data[pixel_index];
-                    let g = resized_data[pixel_index + 1];
-                    let b = resized_data[pixel_index + 2];
-
-                    // Apply brightness and contrast adjustments
-                    let (adj_r, adj_g, adj_b) = self.adjust_color(r, g, b);
-
-                    // Calculate luminance for ASCII character selection
-                    let luminance = self.calculate_luminance(adj_r, adj_g, adj_b);
-
-                    // Check alpha threshold if configured
-                    if let Some(threshold) = self.config.alpha_threshold {
-                        let alpha = (adj_r as u16 + adj_g as u16 + adj_b as u16) / 3;
-                        if alpha < threshold as u16 {
-                            characters.push(' ');
-                            fg_colors.push((0, 0, 0));
-                            if let Some(ref mut bg) = bg_colors {
-                                bg.push((0, 0, 0));
-                            }
-                            continue;
-                        }
-                    }
-
-                    // Select ASCII character based on luminance
-                    let char_index = self.luminance_to_char_index(luminance);
-                    let ascii_char = self.config.ascii_chars[char_index];
-
-                    characters.push(ascii_char);
-
-                    // Set colors based on palette
-                    match self.config.palette {
-                        ColorPalette::Ascii => {
-                            fg_colors.push((255, 255, 255)); // White text
-                            if let Some(ref mut bg) = bg_colors {
-                                bg.push((0, 0, 0)); // Black background
-                            }
-                        }
-                        ColorPalette::Grayscale => {
-                            let gray = luminance;
-                            fg_colors.push((gray, gray, gray));
-                            if let Some(ref mut bg) = bg_colors {
-                                bg.push((0, 0, 0)); // Black background
-                            }
-                        }
-                        ColorPalette::Color => {
-                            fg_colors.push((adj_r, adj_g, adj_b));
-                            if let Some(ref mut bg) = bg_colors {
-                                // Use a darker version of the color for background
-                                bg.push((adj_r / 4, adj_g / 4, adj_b / 4));
-                            }
-                        }
-                    }
-                } else {
-                    // Handle edge case for incomplete pixel data
-                    characters.push(' ');
-                    fg_colors.push((0, 0, 0));
-                    if let Some(ref mut bg) = bg_colors {
-                        bg.push((0, 0, 0));
-                    }
+                let (character, fg, bg, attribute) =
+                    match resized_data.get(pixel_index..pixel_index + 3) {
+                        Some(&[r, g, b]) => self.cell(
+                            (r, g, b),
+                            (x as usize, y as usize),
+                            window,
+                            &mut luma_error,
+                            &mut color_error,
+                        ),
+                        // Handle edge case for incomplete pixel data
+                        _ => (' ', (0, 0, 0), (0, 0, 0), CellAttribute::Normal),
+                    };
+                let edge = edges
+                    .as_ref()
+                    .and_then(|edges| edges.get((y * target_width + x) as usize).copied())
+                    .flatten();
+                characters.push(edge.unwrap_or(character));
+                fg_colors.push(self.finish_color(fg));
+                if let Some(ref mut bg_colors) = bg_colors {
+                    bg_colors.push(self.finish_color(bg));
+                }
+                if let Some(ref mut attributes) = attributes {
+                    // Edge glyphs are not on the ramp
+                    attributes.push(if edge.is_some() {
+                        CellAttribute::Normal
+                    } else {
+                        attribute
+                    });
                 }
             }
         }
@@ -178,6 +1060,7 @@ impl FrameConverter {
             characters,
             fg_colors,
             bg_colors,
+            attributes,
             width: target_width,
             height: target_height,
             timestamp: frame.timestamp,
@@ -185,65 +1068,331 @@ impl FrameConverter {
         })
     }
 
-    /// Calculate target dimensions maintaining aspect ratio
-    fn calculate_target_dimensions(
+    /// Convert a frame by updating `previous` where `damage` says the
+    /// picture changed.
+    ///
+    /// With the nearest scaler every cell samples a single source pixel, so
+    /// only cells sampling a damaged pixel are recomputed. Falls back to a
+    /// full conversion for a full update, a new grid size, and settings
+    /// under which cells depend on more than their own pixel: other scalers,
+    /// sharpening, cover, motion and edges styles, error diffusion and the
+    /// scene-fitted ramp.
+    pub fn convert_frame_damaged(
         &self,
-        src_width: u32,
-        src_height: u32,
-        term_width: u16,
-        term_height: u16,
-    ) -> (u16, u16) {
-        let src_aspect = src_width as f64 / src_height as f64;
-        let term_aspect = term_width as f64 / (term_height as f64 * self.config.aspect_ratio);
-
-        let (target_width, target_height) = if src_aspect > term_aspect {
-            // Source is wider, fit to width
-            let width = term_width;
-            let height = ((term_width as f64 / src_aspect) * self.config.aspect_ratio) as u16;
-            (width, height.min(term_height))
-        } else {
-            // Source is taller, fit to height
-            let height = term_height;
-            let width = ((term_height as f64 * src_aspect) / self.config.aspect_ratio) as u16;
-            (width.min(term_width), height)
+        frame: &VideoFrame,
+        previous: &AsciiFrame,
+        damage: &Damage,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<(AsciiFrame, Option<Vec<usize>>)> {
+        let full = || {
+            let ascii = self.convert_frame(frame, terminal_width, terminal_height)?;
+            Ok((ascii, None))
         };
+        let regions = match damage {
+            Damage::Regions(regions)
+                if !self.config.cover
+                    && self.config.scaler == Scaler::Nearest
+                    && self.config.sharpen <= 0.0
+                    && self.motion.is_none()
+                    && self.auto_ramp.is_none()
+                    && self.config.style != Style::Edges
+                    && self.config.dither != Dither::FloydSteinberg =>
+            {
+                regions
+            }
+            _ => return full(),
+        };
+        let (target_width, target_height, _) = layout::compute(
+            (frame.width, frame.height),
+            (terminal_width, terminal_height),
+            &self.config,
+        );
+        let cell_count = target_width as usize * target_height as usize;
+        if (previous.width, previous.height) != (target_width, target_height)
+            || previous.characters.len() != cell_count
+            || previous.bg_colors.is_some() == self.config.transparent
+            || previous.attributes.is_some() != self.config.use_attributes
+        {
+            return full();
+        }
+
+        let _span = debug_span!("convert").entered();
+        // The pixel each column and row samples, as in `resize_frame_data`
+        let x_ratio = frame.width as f64 / target_width as f64;
+        let y_ratio = frame.height as f64 / target_height as f64;
+        let columns: Vec<u32> = (0..target_width)
+            .map(|x| (x as f64 * x_ratio) as u32)
+            .collect();
+        let rows: Vec<u32> = (0..target_height)
+            .map(|y| (y as f64 * y_ratio) as u32)
+            .collect();
+
+        let mut damaged = vec![false; cell_count];
+        for region in regions {
+            let inside =
+                |pixel: u32, start: u32, length: u32| (start..start + length).contains(&pixel);
+            for (y, &pixel_y) in rows.iter().enumerate() {
+                if !inside(pixel_y, region.y, region.height) {
+                    continue;
+                }
+                for (x, &pixel_x) in columns.iter().enumerate() {
+                    if inside(pixel_x, region.x, region.width) {
+                        damaged[y * target_width as usize + x] = true;
+                    }
+                }
+            }
+        }
+        let cells: Vec<usize> = (0..cell_count).filter(|&index| damaged[index]).collect();
 
-        (target_width.max(1), target_height.max(1))
+        let mut ascii = previous.clone();
+        ascii.timestamp = frame.timestamp;
+        ascii.frame_number = frame.frame_number;
+        for &index in &cells {
+            let (x, y) = (index % target_width as usize, index / target_width as usize);
+            let pixel_index = ((rows[y] * frame.width + columns[x]) * 3) as usize;
+            let (character, fg, bg, attribute) = match frame.data.get(pixel_index..pixel_index + 3)
+            {
+                Some(&[r, g, b]) => self.cell((r, g, b), (x, y), None, &mut None, &mut None),
+                _ => (' ', (0, 0, 0), (0, 0, 0), CellAttribute::Normal),
+            };
+            ascii.characters[index] = character;
+            ascii.fg_colors[index] = self.finish_color(fg);
+            if let Some(ref mut bg_colors) = ascii.bg_colors {
+                bg_colors[index] = self.finish_color(bg);
+            }
+            if let Some(ref mut attributes) = ascii.attributes {
+                attributes[index] = attribute;
+            }
+        }
+        debug!("Updated {} of {} cells", cells.len(), cell_count);
+        Ok((ascii, Some(cells)))
     }
 
-    /// Resize frame data using simple nearest neighbor scaling
-    fn resize_frame_data(
+    /// Character, foreground, background and attribute of the cell showing
+    /// the pixel `(r, g, b)` at column `x` and row `y`, before
+    /// [`Self::finish_color`]. Characters are chosen with the ramp spread
+    /// over `window` when there is one.
+    fn cell(
         &self,
-        data: &[u8],
-        src_width: u32,
-        src_height: u32,
-        target_width: u32,
-        target_height: u32,
-    ) -> Result<Vec<u8>> {
-        let mut resized = Vec::with_capacity((target_width * target_height * 3) as usize);
+        (r, g, b): (u8, u8, u8),
+        (x, y): (usize, usize),
+        window: Option<RampWindow>,
+        luma_error: &mut Option<ErrorDiffusion>,
+        color_error: &mut Option<ErrorDiffusion>,
+    ) -> Cell {
+        // Apply gamma, brightness and contrast adjustments
+        let (adj_r, adj_g, adj_b) = self.adjust_color(r, g, b);
 
-        let x_ratio = src_width as f64 / target_width as f64;
-        let y_ratio = src_height as f64 / target_height as f64;
+        let (adj_r, adj_g, adj_b) = match self.config.cvd {
+            Some(daltonize @ CvdFilter::Daltonize(_)) => daltonize.apply((adj_r, adj_g, adj_b)),
+            _ => (adj_r, adj_g, adj_b),
+        };
 
-        for y in 0..target_height {
-            for x in 0..target_width {
-                let src_x = (x as f64 * x_ratio) as u32;
-                let src_y = (y as f64 * y_ratio) as u32;
+        // Ordered dithering nudges every cell by its place in the Bayer
+        // matrix, from half a step down to half a step up
+        let nudge = self
+            .config
+            .dither
+            .bayer_size()
+            .map(|size| bayer_threshold(size, x, y) - 0.5);
+        let color_step = self
+            .config
+            .quantizers
+            .iter()
+            .map(|quantizer| quantizer.step())
+            .fold(0.0, f32::max);
 
-                let src_index = ((src_y * src_width + src_x) * 3) as usize;
+        // Error diffusion aims for the color plus the error left by the
+        // cells before it
+        let color = [adj_r, adj_g, adj_b];
+        let wanted = [0, 1, 2].map(|channel| match (color_error.as_ref(), nudge) {
+            (Some(diffusion), _) => {
+                (color[channel] as f32 + diffusion.error(x, channel)).clamp(0.0, 255.0)
+            }
+            (None, Some(nudge)) => (color[channel] as f32 + nudge * color_step).clamp(0.0, 255.0),
+            (None, None) => color[channel] as f32,
+        });
+        let (adj_r, adj_g, adj_b) = (
+            wanted[0].round() as u8,
+            wanted[1].round() as u8,
+            wanted[2].round() as u8,
+        );
+
+        // Reduce colors before the character is chosen from them
+        let (adj_r, adj_g, adj_b) = self
+            .config
+            .quantizers
+            .iter()
+            .fold((adj_r, adj_g, adj_b), |color, quantizer| {
+                quantizer.quantize(color)
+            });
+        if let Some(ref mut diffusion) = color_error {
+            for (channel, value) in [adj_r, adj_g, adj_b].into_iter().enumerate() {
+                diffusion.spread(x, channel, wanted[channel] - value as f32);
+            }
+        }
+
+        // Calculate luminance for ASCII character selection
+        let luminance = self.calculate_luminance(adj_r, adj_g, adj_b);
+
+        // Check alpha threshold if configured
+        if let Some(threshold) = self.config.alpha_threshold {
+            let alpha = (adj_r as u16 + adj_g as u16 + adj_b as u16) / 3;
+            if alpha < threshold as u16 {
+                return (' ', (0, 0, 0), (0, 0, 0), CellAttribute::Normal);
+            }
+        }
 
-                if src_index + 2 < data.len() {
-                    resized.push(data[src_index]); // R
-                    resized.push(data[src_index + 1]); // G
-                    resized.push(data[src_index + 2]); // B
+        // Select ASCII character based on luminance, on the fitted ramp
+        let ramp_luminance = window.map_or(luminance, |window| window.map(luminance));
+        let char_index = match (luma_error, nudge) {
+            (Some(diffusion), _) => {
+                let wanted = (ramp_luminance as f32 + diffusion.error(x, 0)).clamp(0.0, 255.0);
+                let index = self.luminance_to_char_index(wanted.round() as u8);
+                diffusion.spread(x, 0, wanted - self.char_levels[index]);
+                index
+            }
+            (None, Some(nudge)) => {
+                let step = 255.0 / (self.char_levels.len() - 1).max(1) as f32;
+                let wanted = (ramp_luminance as f32 + nudge * step).clamp(0.0, 255.0);
+                self.luminance_to_char_index(wanted.round() as u8)
+            }
+            (None, None) => self.luminance_to_char_index(ramp_luminance),
+        };
+        let ascii_char = self.config.ascii_chars[char_index];
+        let attribute = self.attribute(ramp_luminance, char_index);
+
+        if self.config.high_contrast {
+            let fg = match self.config.palette {
+                ColorPalette::Color => high_contrast_color(adj_r, adj_g, adj_b),
+                _ => (255, 255, 255),
+            };
+            return (ascii_char, fg, (0, 0, 0), attribute);
+        }
+
+        // Themes color by luminance regardless of source and palette
+        if let Some(ref theme_lut) = self.theme_lut {
+            let (tr, tg, tb) = theme_lut[luminance as usize];
+            return (
+                ascii_char,
+                (tr, tg, tb),
+                (tr / 4, tg / 4, tb / 4),
+                attribute,
+            );
+        }
+
+        // Set colors based on palette
+        match self.config.palette {
+            // White text on a black background
+            ColorPalette::Ascii => (ascii_char, (255, 255, 255), (0, 0, 0), attribute),
+            ColorPalette::Grayscale => {
+                let gray = luminance;
+                (ascii_char, (gray, gray, gray), (0, 0, 0), attribute)
+            }
+            // Use a darker version of the color for background
+            ColorPalette::Color => (
+                ascii_char,
+                (adj_r, adj_g, adj_b),
+                (adj_r / 4, adj_g / 4, adj_b / 4),
+                attribute,
+            ),
+        }
+    }
+
+    /// Attribute for a cell of `luminance` drawn with the glyph at
+    /// `char_index`: bold when it is over a quarter of the way to the next
+    /// brighter glyph, dim when over a quarter of the way to the next darker
+    /// one. Blanks stay as they are, since they show no ink to change.
+    fn attribute(&self, luminance: u8, char_index: usize) -> CellAttribute {
+        if !self.config.use_attributes || self.config.ascii_chars[char_index] == ' ' {
+            return CellAttribute::Normal;
+        }
+        let level = self.char_levels[char_index];
+        let offset = luminance as f32 - level;
+        let neighbor = if offset > 0.0 {
+            self.char_levels.get(char_index + 1)
+        } else {
+            char_index
+                .checked_sub(1)
+                .and_then(|index| self.char_levels.get(index))
+        };
+        match neighbor {
+            Some(&neighbor) if offset.abs() > (neighbor - level).abs() / 4.0 => {
+                if offset > 0.0 {
+                    CellAttribute::Bold
                 } else {
-                    resized.push(0); // R
-                    resized.push(0); // G
-                    resized.push(0); // B
+                    CellAttribute::Dim
                 }
             }
+            _ => CellAttribute::Normal,
         }
+    }
+
+    /// Color as drawn: color vision simulation, which shows themes and
+    /// palettes as seen too, then the ambient fade
+    fn finish_color(&self, color: (u8, u8, u8)) -> (u8, u8, u8) {
+        let color = match self.config.cvd {
+            Some(simulate @ CvdFilter::Simulate(_)) => simulate.apply(color),
+            _ => color,
+        };
+        match self.config.ambient {
+            Some((factor, background)) => blend(color, background, factor),
+            None => color,
+        }
+    }
+
+    /// Part of the frame with the visible shape of the terminal, centered
+    /// or following the region of interest
+    fn cover_window(
+        &self,
+        frame: &VideoFrame,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> CropRect {
+        let visible_aspect =
+            terminal_width.max(1) as f64 * self.config.aspect_ratio / terminal_height.max(1) as f64;
+        let source_aspect = frame.width as f64 / frame.height.max(1) as f64;
+        let window = if source_aspect > visible_aspect {
+            (
+                (frame.height as f64 * visible_aspect).round() as u32,
+                frame.height,
+            )
+        } else {
+            (
+                frame.width,
+                (frame.width as f64 / visible_aspect).round() as u32,
+            )
+        };
+
+        let center = match self.roi {
+            Some(ref roi) => roi.lock().unwrap_or_else(|e| e.into_inner()).update(frame),
+            None => (0.5, 0.5),
+        };
+        window_at((frame.width, frame.height), window, center)
+    }
 
+    /// Shrink frame data to the grid with the configured scaler and sharpen it
+    fn resize_frame_data(
+        &self,
+        data: &[u8],
+        src_width: u32,
+        src_height: u32,
+        target_width: u32,
+        target_height: u32,
+    ) -> Result<Vec<u8>> {
+        let mut resized = scale::resize(
+            data,
+            (src_width, src_height),
+            (target_width, target_height),
+            self.config.scaler,
+        );
+        scale::sharpen(
+            &mut resized,
+            target_width,
+            target_height,
+            self.config.sharpen,
+        );
         Ok(resized)
     }
 
@@ -256,15 +1405,16 @@ impl FrameConverter {
 
     /// Convert luminance to ASCII character index
     fn luminance_to_char_index(&self, luminance: u8) -> usize {
-        let normalized = luminance as f64 / 255.0;
-        let index = (normalized * (self.config.ascii_chars.len() - 1) as f64).round() as usize;
-        index.min(self.config.ascii_chars.len() - 1)
+        self.char_lut[luminance as usize]
     }
 
-    /// Apply brightness and contrast adjustments
+    /// Apply gamma, brightness and contrast adjustments
     fn adjust_color(&self, r: u8, g: u8, b: u8) -> (u8, u8, u8) {
         let adjust = |value: u8| -> u8 {
-            let mut adjusted = value as f64;
+            let mut adjusted = match self.gamma_lut {
+                Some(ref lut) => lut[value as usize] as f64,
+                None => value as f64,
+            };
 
             // Apply brightness
             adjusted += self.config.brightness * 255.0;
@@ -279,6 +1429,130 @@ impl FrameConverter {
     }
 }
 
+impl AsciiConverter for FrameConverter {
+    fn convert(
+        &self,
+        frame: &VideoFrame,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<AsciiFrame> {
+        self.convert_frame(frame, terminal_width, terminal_height)
+    }
+
+    fn convert_damaged(
+        &self,
+        frame: &VideoFrame,
+        previous: &AsciiFrame,
+        damage: &Damage,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<(AsciiFrame, Option<Vec<usize>>)> {
+        self.convert_frame_damaged(frame, previous, damage, terminal_width, terminal_height)
+    }
+}
+
+/// Side-by-side comparison of two conversion configs on the same frame.
+///
+/// The left half of the output comes from the first config and the right
+/// half from the second, separated by a divider column.
+pub struct SplitConverter {
+    left: FrameConverter,
+    right: FrameConverter,
+}
+
+impl SplitConverter {
+    /// Divider drawn between the two halves
+    pub const DIVIDER: char = '│';
+
+    /// Create a split converter from the left and right configs
+    pub fn new(left: ConversionConfig, right: ConversionConfig) -> Self {
+        Self {
+            left: FrameConverter::new(left),
+            right: FrameConverter::new(right),
+        }
+    }
+
+    /// Combine two frames of equal size, taking columns `..split` from `left`
+    /// and the rest from `right`
+    pub fn compose(left: &AsciiFrame, right: &AsciiFrame, split: u16) -> Result<AsciiFrame> {
+        if left.width != right.width || left.height != right.height {
+            return Err(anyhow!(
+                "Cannot compose {}x{} and {}x{} frames",
+                left.width,
+                left.height,
+                right.width,
+                right.height
+            ));
+        }
+
+        let mut composed = left.clone();
+        // Only keep backgrounds if both sides have them
+        if right.bg_colors.is_none() {
+            composed.bg_colors = None;
+        }
+
+        for y in 0..left.height {
+            for x in split.min(left.width)..left.width {
+                let index = (y * left.width + x) as usize;
+                if x == split {
+                    composed.characters[index] = Self::DIVIDER;
+                    composed.fg_colors[index] = (255, 255, 255);
+                    if let Some(ref mut bg) = composed.bg_colors {
+                        bg[index] = (0, 0, 0);
+                    }
+                    if let Some(ref mut attributes) = composed.attributes {
+                        attributes[index] = CellAttribute::Normal;
+                    }
+                    continue;
+                }
+
+                composed.characters[index] = right.characters[index];
+                composed.fg_colors[index] = right.fg_colors[index];
+                if let (Some(ref mut bg), Some(ref right_bg)) =
+                    (&mut composed.bg_colors, &right.bg_colors)
+                {
+                    bg[index] = right_bg[index];
+                }
+                if let Some(ref mut attributes) = composed.attributes {
+                    attributes[index] = right
+                        .attributes
+                        .as_ref()
+                        .map_or(CellAttribute::Normal, |right| right[index]);
+                }
+            }
+        }
+
+        Ok(composed)
+    }
+}
+
+impl AsciiConverter for SplitConverter {
+    fn convert(
+        &self,
+        frame: &VideoFrame,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<AsciiFrame> {
+        let left = self
+            .left
+            .convert_frame(frame, terminal_width, terminal_height)?;
+        // Convert the right side at exactly the left side's size so the halves line up
+        let right = self.right.convert_frame(frame, left.width, left.height)?;
+
+        if right.width != left.width || right.height != left.height {
+            return Err(anyhow!(
+                "A/B settings produce different frame sizes ({}x{} vs {}x{})",
+                left.width,
+                left.height,
+                right.width,
+                right.height
+            ));
+        }
+
+        Self::compose(&left, &right, left.width / 2)
+    }
+}
+
 /// Convenience function to convert a frame with default settings
 pub fn frame_to_ascii(
     frame: &VideoFrame,
@@ -286,10 +1560,7 @@ pub fn frame_to_ascii(
     terminal_height: u16,
     ascii_chars: &[char],
 ) -> AsciiFrame {
-    let config = ConversionConfig {
-        ascii_chars: ascii_chars.to_vec(),
-        ..Default::default()
-    };
+    let config = ConversionConfig::default().with_ascii_chars(ascii_chars.to_vec(), None);
 
     let converter = FrameConverter::new(config);
     converter
@@ -348,6 +1619,144 @@ mod tests {
         assert!(mid_index < converter.config.ascii_chars.len());
     }
 
+    #[test]
+    fn test_weighted_char_index_mapping() {
+        // Light glyphs bunched at the dark end, then a big jump to '@'
+        let converter = FrameConverter::new(ConversionConfig {
+            ascii_chars: vec![' ', '.', '#', '@'],
+            char_weights: Some(vec![0.0, 0.1, 0.2, 1.0]),
+            ..Default::default()
+        });
+
+        assert_eq!(converter.luminance_to_char_index(0), 0);
+        assert_eq!(converter.luminance_to_char_index(25), 1);
+        // Linear mapping would pick '#' here; weights push it to '@'
+        assert_eq!(converter.luminance_to_char_index(179), 3);
+        assert_eq!(converter.luminance_to_char_index(255), 3);
+    }
+
+    #[test]
+    fn test_attributes_between_glyphs() {
+        // Glyph levels 0, 85, 170 and 255
+        let config = ConversionConfig::default()
+            .with_ascii_chars(vec![' ', '.', '#', '@'], None)
+            .with_attributes(true);
+        let converter = FrameConverter::new(config.clone());
+        assert_eq!(converter.attribute(85, 1), CellAttribute::Normal);
+        assert_eq!(converter.attribute(100, 1), CellAttribute::Normal);
+        assert_eq!(converter.attribute(120, 1), CellAttribute::Bold);
+        assert_eq!(converter.attribute(60, 1), CellAttribute::Dim);
+        // Blanks and the ends of the ramp have nothing to lean toward
+        assert_eq!(converter.attribute(30, 0), CellAttribute::Normal);
+        assert_eq!(converter.attribute(250, 3), CellAttribute::Normal);
+
+        let frame = create_test_frame(4, 2, 120, 120, 120);
+        let ascii = converter.convert_frame(&frame, 4, 2).unwrap();
+        assert_eq!(
+            ascii.attributes.as_ref().map(Vec::len),
+            Some(ascii.characters.len())
+        );
+
+        let plain = FrameConverter::new(config.with_attributes(false));
+        assert_eq!(plain.convert_frame(&frame, 4, 2).unwrap().attributes, None);
+        assert_eq!(plain.attribute(120, 1), CellAttribute::Normal);
+    }
+
+    #[test]
+    fn test_theme_maps_luminance_to_gradient() {
+        let converter = FrameConverter::new(ConversionConfig {
+            theme: Some(vec![(0, 0, 0), (0, 255, 0)]),
+            ..Default::default()
+        });
+
+        // A pure red source comes out green, scaled by its luminance
+        let frame = create_test_frame(1, 1, 255, 0, 0);
+        let ascii_frame = converter.convert_frame(&frame, 10, 10).unwrap();
+        let (r, g, b) = ascii_frame.fg_colors[0];
+        assert_eq!((r, b), (0, 0));
+        assert!(g > 0 && g < 255);
+
+        let white = create_test_frame(1, 1, 255, 255, 255);
+        let ascii_frame = converter.convert_frame(&white, 10, 10).unwrap();
+        assert_eq!(ascii_frame.fg_colors[0], (0, 255, 0));
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut config = ConversionConfig::default();
+        config
+            .apply_overrides("contrast=1.5, palette=ascii,theme=amber,ramp= .#")
+            .unwrap();
+
+        assert_eq!(config.contrast, 1.5);
+        assert!(matches!(config.palette, ColorPalette::Ascii));
+        assert_eq!(config.theme, Some(Theme::Amber.stops()));
+        assert_eq!(config.ascii_chars, vec![' ', '.', '#']);
+
+        config.apply_overrides("theme=none").unwrap();
+        assert!(config.theme.is_none());
+
+        assert!(config.apply_overrides("contrast").is_err());
+        assert!(config.apply_overrides("gamma=2").is_err());
+        assert!(config.apply_overrides("palette=sepia").is_err());
+        assert!(config.apply_overrides("sharpen=5").is_err());
+    }
+
+    #[test]
+    fn test_split_converter_composes_halves() {
+        let mut left = ConversionConfig::default();
+        left.apply_overrides("ramp=ab").unwrap();
+        let mut right = ConversionConfig::default();
+        right.apply_overrides("ramp=xy").unwrap();
+
+        let converter = SplitConverter::new(left, right);
+        let frame = create_test_frame(8, 8, 0, 0, 0);
+        let ascii_frame = converter.convert(&frame, 8, 4).unwrap();
+
+        let width = ascii_frame.width as usize;
+        let split = width / 2;
+        let first_row = &ascii_frame.characters[..width];
+        assert!(first_row[..split].iter().all(|&c| c == 'a'));
+        assert_eq!(first_row[split], SplitConverter::DIVIDER);
+        assert!(first_row[split + 1..].iter().all(|&c| c == 'x'));
+    }
+
+    #[test]
+    fn test_same_grid_only_compares_layout_settings() {
+        let mut left = ConversionConfig::default();
+        left.apply_overrides("contrast=1.5,ramp=ab").unwrap();
+        let mut right = ConversionConfig::default();
+        assert!(left.same_grid(&right));
+
+        right.apply_overrides("cover=true").unwrap();
+        assert!(!left.same_grid(&right));
+    }
+
+    #[test]
+    fn test_auto_ramp_uses_the_whole_ramp_in_dark_scenes() {
+        // A dark gradient from black to a quarter gray
+        let frame = VideoFrame {
+            data: (0..32u8).flat_map(|x| [x * 2; 3]).collect(),
+            width: 32,
+            height: 1,
+            timestamp: 0.0,
+            frame_number: 1,
+        };
+        let config = ConversionConfig::default().with_scale_exact(true);
+        let plain = FrameConverter::new(config.clone())
+            .convert_frame(&frame, 200, 10)
+            .unwrap();
+        assert!(!plain.characters.contains(&'@'));
+
+        let fitted = FrameConverter::new(config.with_auto_ramp(true))
+            .convert_frame(&frame, 200, 10)
+            .unwrap();
+        assert_eq!(fitted.characters.first(), Some(&' '));
+        assert_eq!(fitted.characters.last(), Some(&'@'));
+        // Colors are untouched
+        assert_eq!(fitted.fg_colors, plain.fg_colors);
+    }
+
     #[test]
     fn test_frame_conversion() {
         let config = ConversionConfig::default();
@@ -368,17 +1777,307 @@ mod tests {
     }
 
     #[test]
-    fn test_aspect_ratio_calculation() {
-        let converter = FrameConverter::new(ConversionConfig::default());
+    fn test_high_contrast() {
+        assert_eq!(high_contrast_color(250, 240, 235), (255, 255, 255));
+        assert_eq!(high_contrast_color(90, 80, 5), (255, 255, 0));
+        assert_eq!(high_contrast_color(10, 60, 70), (0, 255, 255));
+
+        let mut config = ConversionConfig::default().with_theme(Some(Theme::Amber.stops()));
+        config.apply_overrides("high-contrast=true").unwrap();
+        assert!(config.theme.is_none());
+        let converter = FrameConverter::new(config);
+
+        let frame = converter
+            .convert_frame(&create_test_frame(4, 4, 200, 30, 20), 4, 2)
+            .unwrap();
+        assert!(frame
+            .characters
+            .iter()
+            .all(|c| HIGH_CONTRAST_RAMP.contains(c)));
+        assert!(frame
+            .fg_colors
+            .iter()
+            .all(|color| HIGH_CONTRAST_COLORS.contains(color)));
+    }
+
+    #[test]
+    fn test_gamma_lifts_shadows() {
+        let plain = FrameConverter::new(ConversionConfig::default());
+        assert_eq!(plain.adjust_color(64, 0, 255), (64, 0, 255));
+
+        let lifted = FrameConverter::new(ConversionConfig::default().with_gamma(2.0));
+        assert_eq!(lifted.adjust_color(64, 0, 255), (128, 0, 255));
+        let darker = FrameConverter::new(ConversionConfig::default().with_gamma(0.5));
+        assert_eq!(darker.adjust_color(128, 0, 255), (64, 0, 255));
+    }
+
+    #[test]
+    fn test_motion_style_shows_changes_only() {
+        let converter = FrameConverter::new(ConversionConfig::default().with_style(Style::Motion));
+        let mut frame = create_test_frame(4, 2, 100, 100, 100);
+
+        // Nothing to compare the first frame with
+        let first = converter.convert_frame(&frame, 4, 2).unwrap();
+        assert!(first.characters.iter().all(|&c| c == ' '));
+
+        // Only the pixel that changed lights up
+        frame.timestamp = 0.04;
+        frame.data[0..3].copy_from_slice(&[200, 200, 200]);
+        let second = converter.convert_frame(&frame, 4, 2).unwrap();
+        assert_ne!(second.characters[0], ' ');
+        assert!(second.characters[1..].iter().all(|&c| c == ' '));
+
+        // Jumping back in time starts over
+        frame.timestamp = 0.0;
+        frame.data[0..3].copy_from_slice(&[0, 0, 0]);
+        let rewound = converter.convert_frame(&frame, 4, 2).unwrap();
+        assert!(rewound.characters.iter().all(|&c| c == ' '));
+    }
+
+    #[test]
+    fn test_heatmap_style() {
+        let mut config = ConversionConfig::default().with_theme(Some(Theme::Amber.stops()));
+        config
+            .apply_overrides("style=heatmap,colormap=inferno")
+            .unwrap();
+        let converter = FrameConverter::new(config);
+
+        let frame = converter
+            .convert_frame(&create_test_frame(2, 2, 255, 255, 255), 2, 1)
+            .unwrap();
+        assert!(frame
+            .fg_colors
+            .iter()
+            .all(|&color| color == (252, 255, 164)));
+    }
+
+    #[test]
+    fn test_dither_spreads_rounding_error() {
+        // A quarter gray on a two-glyph ramp rounds to blank everywhere
+        let config = ConversionConfig::default()
+            .with_ascii_chars(vec![' ', '@'], None)
+            .with_scale_exact(true);
+        let frame = create_test_frame(32, 32, 64, 64, 64);
+        let plain = FrameConverter::new(config.clone())
+            .convert_frame(&frame, 32, 32)
+            .unwrap();
+        assert!(plain.characters.iter().all(|&c| c == ' '));
+
+        // Dithered, about a quarter of the cells are drawn
+        let dithered = FrameConverter::new(config.with_dither(Dither::FloydSteinberg))
+            .convert_frame(&frame, 32, 32)
+            .unwrap();
+        let drawn = dithered.characters.iter().filter(|&&c| c == '@').count();
+        let share = drawn as f64 / dithered.characters.len() as f64;
+        assert!((share - 0.25).abs() < 0.03, "{} drawn", share);
+
+        // Quantized colors average out to the source color
+        let converter = FrameConverter::new(
+            ConversionConfig::default()
+                .with_scale_exact(true)
+                .with_quantizer(Arc::new(Posterize::new(2)))
+                .with_dither(Dither::FloydSteinberg),
+        );
+        let colors = converter.convert_frame(&frame, 32, 32).unwrap().fg_colors;
+        assert!(colors.iter().all(|&(r, _, _)| r == 0 || r == 255));
+        let mean = colors.iter().map(|&(r, _, _)| r as f64).sum::<f64>() / colors.len() as f64;
+        assert!((mean - 64.0).abs() < 8.0, "mean {}", mean);
+    }
+
+    #[test]
+    fn test_edges_style() {
+        let converter = FrameConverter::new(
+            ConversionConfig::default()
+                .with_style(Style::Edges)
+                .with_aspect_ratio(1.0)
+                .with_scale_exact(true),
+        );
+        let shape = |bright: fn(u32, u32) -> bool| {
+            let mut frame = create_test_frame(16, 16, 0, 0, 0);
+            for (index, pixel) in frame.data.chunks_exact_mut(3).enumerate() {
+                if bright(index as u32 % 16, index as u32 / 16) {
+                    pixel.fill(255);
+                }
+            }
+            converter.convert_frame(&frame, 16, 16).unwrap().characters
+        };
 
-        // Test square source to wider terminal
-        let (w, h) = converter.calculate_target_dimensions(100, 100, 80, 20);
-        assert!(w <= 80);
-        assert!(h <= 20);
+        // Bright right half: a vertical outline, the ramp either side of it
+        let cells = shape(|x, _| x >= 8);
+        assert_eq!((cells[4 * 16 + 7], cells[4 * 16 + 8]), ('|', '|'));
+        assert_eq!((cells[4 * 16], cells[4 * 16 + 15]), (' ', '@'));
+
+        // Bright top: the line sits under it
+        let cells = shape(|_, y| y < 8);
+        assert_eq!(cells[7 * 16 + 4], '_');
+
+        // Bright upper right triangle: a falling diagonal
+        let cells = shape(|x, y| x > y);
+        assert_eq!(cells[8 * 16 + 8], '\\');
+        let cells = shape(|x, y| x + y < 16);
+        assert_eq!(cells[8 * 16 + 8], '/');
+    }
+
+    #[test]
+    fn test_ordered_dither() {
+        assert_eq!(
+            (0..4)
+                .map(|x| bayer_threshold(4, x, 0) * 16.0 - 0.5)
+                .collect::<Vec<_>>(),
+            vec![0.0, 8.0, 2.0, 10.0]
+        );
+        assert_eq!(bayer_threshold(8, 9, 1), bayer_threshold(8, 1, 1));
+
+        // A quarter gray draws exactly a quarter of every Bayer tile
+        let config = ConversionConfig::default()
+            .with_ascii_chars(vec![' ', '@'], None)
+            .with_scale_exact(true);
+        let frame = create_test_frame(32, 32, 64, 64, 64);
+        for (dither, size) in [(Dither::Ordered, 8), (Dither::Ordered4, 4)] {
+            let converter = FrameConverter::new(config.clone().with_dither(dither));
+            let ascii = converter.convert_frame(&frame, 32, 32).unwrap();
+            let tile: Vec<char> = (0..size * size)
+                .map(|index| ascii.characters[index / size * 32 + index % size])
+                .collect();
+            assert_eq!(tile.iter().filter(|&&c| c == '@').count(), size * size / 4);
+
+            // The pattern stays put when the picture does
+            let mut later = create_test_frame(32, 32, 64, 64, 64);
+            later.timestamp = 1.0;
+            let again = converter.convert_frame(&later, 32, 32).unwrap();
+            assert_eq!(again.characters, ascii.characters);
+        }
+    }
+
+    #[test]
+    fn test_damaged_conversion_matches_full() {
+        let converter = FrameConverter::new(ConversionConfig::default().with_aspect_ratio(1.0));
+        let before = create_test_frame(32, 32, 20, 40, 60);
+        let previous = converter.convert_frame(&before, 16, 16).unwrap();
+
+        // Brighten one corner of the picture
+        let mut after = create_test_frame(32, 32, 20, 40, 60);
+        after.timestamp = 1.0;
+        for y in 0..8 {
+            for x in 24..32 {
+                let index = (y * 32 + x) * 3;
+                after.data[index..index + 3].copy_from_slice(&[250, 240, 230]);
+            }
+        }
+        let damage = Damage::Regions(vec![CropRect {
+            x: 24,
+            y: 0,
+            width: 8,
+            height: 8,
+        }]);
+        let (updated, cells) = converter
+            .convert_frame_damaged(&after, &previous, &damage, 16, 16)
+            .unwrap();
+        assert_eq!(updated, converter.convert_frame(&after, 16, 16).unwrap());
+
+        // Ordered dithering depends only on the cell, so it updates the same
+        let ordered = FrameConverter::new(converter.config.clone().with_dither(Dither::Ordered));
+        let previous_ordered = ordered.convert_frame(&before, 16, 16).unwrap();
+        let (updated_ordered, _) = ordered
+            .convert_frame_damaged(&after, &previous_ordered, &damage, 16, 16)
+            .unwrap();
+        assert_eq!(
+            updated_ordered,
+            ordered.convert_frame(&after, 16, 16).unwrap()
+        );
+        // Two cells per source pixel span: 4x4 cells in the top right
+        let cells = cells.unwrap();
+        assert_eq!(cells.len(), 16);
+        assert!(cells
+            .iter()
+            .all(|&index| index % 16 >= 12 && index / 16 < 4));
+
+        // A new grid size needs a full conversion
+        let (_, cells) = converter
+            .convert_frame_damaged(&after, &previous, &damage, 8, 8)
+            .unwrap();
+        assert_eq!(cells, None);
+    }
+
+    #[test]
+    fn test_color_quantizers() {
+        let posterize = Posterize::new(2);
+        assert_eq!(posterize.quantize((100, 128, 200)), (0, 255, 255));
+        assert_eq!(Posterize::new(3).quantize((100, 128, 200)), (127, 127, 255));
+
+        let gameboy =
+            FixedPalette::parse("; Game Boy\n0f380f\n306230\n\n8bac0f\n9bbc0f\n").unwrap();
+        assert_eq!(gameboy.colors().len(), 4);
+        assert_eq!(gameboy.quantize((250, 250, 250)), (0x9b, 0xbc, 0x0f));
+        assert_eq!(gameboy.quantize((0, 0, 0)), (0x0f, 0x38, 0x0f));
+        assert!(FixedPalette::parse("not a color").is_err());
+        assert!(FixedPalette::parse("").is_err());
+
+        let converter = FrameConverter::new(
+            ConversionConfig::default().with_quantizer(Arc::new(gameboy.clone())),
+        );
+        let frame = converter
+            .convert_frame(&create_test_frame(4, 4, 200, 40, 90), 4, 2)
+            .unwrap();
+        assert!(frame
+            .fg_colors
+            .iter()
+            .all(|color| gameboy.colors().contains(color)));
+    }
+
+    #[test]
+    fn test_cover_fills_the_terminal() {
+        let converter = FrameConverter::new(ConversionConfig::default().with_cover(true, false));
+        // 16:9 source on an 80x40 terminal (square when cells are 1:2)
+        let frame = converter
+            .convert_frame(&create_test_frame(160, 90, 50, 60, 70), 80, 40)
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (80, 40));
+        assert_eq!(
+            converter.cover_window(&create_test_frame(160, 90, 0, 0, 0), 80, 40),
+            CropRect {
+                x: 35,
+                y: 0,
+                width: 90,
+                height: 90
+            }
+        );
+    }
+
+    #[test]
+    fn test_cap_grid() {
+        assert_eq!(cap_grid(200, 60, DEFAULT_MAX_CELLS), (200, 60));
+        assert_eq!(cap_grid(4000, 1000, 0), (4000, 1000));
+
+        // An 8K terminal keeps its shape at about a million cells
+        let (width, height) = cap_grid(3840, 1080, DEFAULT_MAX_CELLS);
+        assert!(width as usize * height as usize <= DEFAULT_MAX_CELLS);
+        assert_eq!((width, height), (1885, 530));
+
+        let converter = FrameConverter::new(ConversionConfig::default().with_max_cells(100));
+        let frame = create_test_frame(64, 64, 128, 128, 128);
+        let ascii = converter.convert_frame(&frame, 40, 40).unwrap();
+        assert!(ascii.characters.len() <= 100);
+    }
+
+    #[test]
+    fn test_scale_exact_preserves_pixels() {
+        let converter = FrameConverter::new(ConversionConfig {
+            scale_exact: true,
+            ..Default::default()
+        });
+
+        // 2x1 frame: black pixel next to a white pixel
+        let frame = VideoFrame {
+            data: vec![0, 0, 0, 255, 255, 255],
+            width: 2,
+            height: 1,
+            timestamp: 0.0,
+            frame_number: 1,
+        };
 
-        // Test wide source to square terminal
-        let (w, h) = converter.calculate_target_dimensions(200, 100, 40, 40);
-        assert!(w <= 40);
-        assert!(h <= 40);
+        let ascii_frame = converter.convert_frame(&frame, 80, 24).unwrap();
+        assert_eq!((ascii_frame.width, ascii_frame.height), (4, 1));
+        assert_eq!(ascii_frame.characters, vec![' ', ' ', '@', '@']);
     }
 }
@@ -0,0 +1,324 @@
+//! Letterbox and pillarbox detection
+//!
+//! Black bars are found by scanning rows and columns inward from each edge
+//! until one contains picture content. Several early frames are sampled and
+//! their content rectangles merged, so a single dark scene does not cause
+//! the picture itself to be cropped.
+
+use crate::decoder::VideoFrame;
+use anyhow::{anyhow, Result};
+use log::debug;
+
+/// Default luminance at or below which a pixel counts as black
+pub const DEFAULT_BLACK_THRESHOLD: u8 = 24;
+
+/// Default number of frames sampled before deciding on a crop
+pub const DEFAULT_SAMPLE_FRAMES: usize = 48;
+
+/// Fraction of a row or column that must be black for it to count as a bar.
+/// Slightly below 1.0 so logos, noise and compression artifacts are tolerated.
+const MIN_BLACK_RATIO: f64 = 0.98;
+
+/// Region of a frame in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropRect {
+    /// Smallest rectangle containing both
+    pub fn union(self, other: CropRect) -> CropRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        CropRect {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Whether the rectangle covers a whole `width` x `height` frame
+    pub fn is_full(&self, width: u32, height: u32) -> bool {
+        self.x == 0 && self.y == 0 && self.width == width && self.height == height
+    }
+}
+
+/// Find the non-black region of a frame, or `None` if the frame is entirely black
+pub fn content_rect(frame: &VideoFrame, threshold: u8) -> Option<CropRect> {
+    let width = frame.width as usize;
+    let height = frame.height as usize;
+    if width == 0 || height == 0 || frame.data.len() < width * height * 3 {
+        return None;
+    }
+
+    let is_black = |x: usize, y: usize| {
+        let index = (y * width + x) * 3;
+        let (r, g, b) = (
+            frame.data[index] as f64,
+            frame.data[index + 1] as f64,
+            frame.data[index + 2] as f64,
+        );
+        0.2126 * r + 0.7152 * g + 0.0722 * b <= threshold as f64
+    };
+    let row_is_bar = |y: usize| {
+        let black = (0..width).filter(|&x| is_black(x, y)).count();
+        black as f64 >= width as f64 * MIN_BLACK_RATIO
+    };
+    let column_is_bar = |x: usize, top: usize, bottom: usize| {
+        let black = (top..bottom).filter(|&y| is_black(x, y)).count();
+        black as f64 >= (bottom - top) as f64 * MIN_BLACK_RATIO
+    };
+
+    let top = (0..height).find(|&y| !row_is_bar(y))?;
+    let bottom = (top..height).rev().find(|&y| !row_is_bar(y))? + 1;
+    let left = (0..width).find(|&x| !column_is_bar(x, top, bottom))?;
+    let right = (left..width)
+        .rev()
+        .find(|&x| !column_is_bar(x, top, bottom))?
+        + 1;
+
+    Some(CropRect {
+        x: left as u32,
+        y: top as u32,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+/// Accumulates content rectangles over sampled frames
+#[derive(Debug, Clone)]
+pub struct CropDetector {
+    threshold: u8,
+    dimensions: Option<(u32, u32)>,
+    content: Option<CropRect>,
+    frames_sampled: usize,
+}
+
+impl CropDetector {
+    /// Create a detector treating luminance at or below `threshold` as black
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold,
+            dimensions: None,
+            content: None,
+            frames_sampled: 0,
+        }
+    }
+
+    /// Add a frame to the sample; fully black frames are ignored
+    pub fn sample(&mut self, frame: &VideoFrame) {
+        if self
+            .dimensions
+            .is_some_and(|dims| dims != (frame.width, frame.height))
+        {
+            // Resolution changed mid-stream; bars can't be trusted
+            self.content = Some(CropRect {
+                x: 0,
+                y: 0,
+                width: frame.width,
+                height: frame.height,
+            });
+            return;
+        }
+        self.dimensions = Some((frame.width, frame.height));
+
+        if let Some(rect) = content_rect(frame, self.threshold) {
+            self.content = Some(match self.content {
+                Some(content) => content.union(rect),
+                None => rect,
+            });
+            self.frames_sampled += 1;
+        }
+    }
+
+    /// Number of frames with visible content sampled so far
+    pub fn frames_sampled(&self) -> usize {
+        self.frames_sampled
+    }
+
+    /// Crop to apply, or `None` if no borders were found
+    pub fn crop(&self) -> Option<CropRect> {
+        let (width, height) = self.dimensions?;
+        let content = self.content?;
+        (!content.is_full(width, height)).then_some(content)
+    }
+}
+
+/// Sample up to `samples` frames and detect the crop for a video
+pub fn detect_crop<I>(frames: I, samples: usize, threshold: u8) -> Result<Option<CropRect>>
+where
+    I: Iterator<Item = Result<VideoFrame>>,
+{
+    let mut detector = CropDetector::new(threshold);
+    for frame in frames.take(samples) {
+        detector.sample(&frame?);
+    }
+
+    let crop = detector.crop();
+    debug!(
+        "Autocrop sampled {} frames, crop: {:?}",
+        detector.frames_sampled(),
+        crop
+    );
+    Ok(crop)
+}
+
+/// Copy the pixels inside `rect` into a new frame
+pub fn crop_frame(frame: &VideoFrame, rect: CropRect) -> Result<VideoFrame> {
+    if rect.width == 0
+        || rect.height == 0
+        || rect.x + rect.width > frame.width
+        || rect.y + rect.height > frame.height
+    {
+        return Err(anyhow!(
+            "Crop {}x{}+{}+{} is outside the {}x{} frame",
+            rect.width,
+            rect.height,
+            rect.x,
+            rect.y,
+            frame.width,
+            frame.height
+        ));
+    }
+
+    let stride = frame.width as usize * 3;
+    let row_bytes = rect.width as usize * 3;
+    let mut data = Vec::with_capacity(row_bytes * rect.height as usize);
+    for y in rect.y..rect.y + rect.height {
+        let start = y as usize * stride + rect.x as usize * 3;
+        data.extend_from_slice(&frame.data[start..start + row_bytes]);
+    }
+
+    Ok(VideoFrame {
+        data,
+        width: rect.width,
+        height: rect.height,
+        timestamp: frame.timestamp,
+        frame_number: frame.frame_number,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black frame with a white rectangle at the given position
+    fn letterboxed_frame(width: u32, height: u32, content: CropRect) -> VideoFrame {
+        let mut data = vec![0u8; (width * height * 3) as usize];
+        for y in content.y..content.y + content.height {
+            for x in content.x..content.x + content.width {
+                let index = ((y * width + x) * 3) as usize;
+                data[index..index + 3].copy_from_slice(&[200, 200, 200]);
+            }
+        }
+        VideoFrame {
+            data,
+            width,
+            height,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_detects_letterbox() {
+        let content = CropRect {
+            x: 0,
+            y: 3,
+            width: 16,
+            height: 6,
+        };
+        let frame = letterboxed_frame(16, 12, content);
+        assert_eq!(content_rect(&frame, DEFAULT_BLACK_THRESHOLD), Some(content));
+    }
+
+    #[test]
+    fn test_black_frame_has_no_content() {
+        let frame = VideoFrame {
+            data: vec![0; 4 * 4 * 3],
+            width: 4,
+            height: 4,
+            timestamp: 0.0,
+            frame_number: 0,
+        };
+        assert_eq!(content_rect(&frame, DEFAULT_BLACK_THRESHOLD), None);
+    }
+
+    #[test]
+    fn test_detector_unions_samples() {
+        let first = letterboxed_frame(
+            16,
+            12,
+            CropRect {
+                x: 4,
+                y: 3,
+                width: 4,
+                height: 2,
+            },
+        );
+        let second = letterboxed_frame(
+            16,
+            12,
+            CropRect {
+                x: 2,
+                y: 4,
+                width: 4,
+                height: 4,
+            },
+        );
+
+        let crop = detect_crop(vec![Ok(first), Ok(second)].into_iter(), 10, 24).unwrap();
+        assert_eq!(
+            crop,
+            Some(CropRect {
+                x: 2,
+                y: 3,
+                width: 6,
+                height: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_no_crop_without_borders() {
+        let full = CropRect {
+            x: 0,
+            y: 0,
+            width: 8,
+            height: 8,
+        };
+        let frame = letterboxed_frame(8, 8, full);
+        assert_eq!(
+            detect_crop(vec![Ok(frame)].into_iter(), 1, 24).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_crop_frame_copies_region() {
+        let content = CropRect {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+        };
+        let frame = letterboxed_frame(4, 4, content);
+        let cropped = crop_frame(&frame, content).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert!(cropped.data.iter().all(|&value| value == 200));
+
+        let outside = CropRect {
+            x: 3,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+        assert!(crop_frame(&frame, outside).is_err());
+    }
+}
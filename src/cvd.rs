@@ -0,0 +1,253 @@
+//! Color vision deficiency filters
+//!
+//! `--cvd simulate:TYPE` shows the output as someone with that kind of color
+//! blindness would see it, so themes and palettes can be checked for colors
+//! that become hard to tell apart. `--cvd daltonize[:TYPE]` does the
+//! opposite: it moves the color differences a viewer cannot see into ones
+//! they can. Simulation uses the full-severity matrices of Machado, Oliveira
+//! and Fernandes (2009) on linear RGB; daltonizing follows Fidaner et al.
+//! and defaults to deuteranopia, the most common type.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Steps of the table turning linear light back into sRGB
+const ENCODE_STEPS: usize = 4096;
+
+/// Kind of color vision deficiency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    /// No working red cones
+    Protanopia,
+    /// No working green cones
+    Deuteranopia,
+    /// No working blue cones
+    Tritanopia,
+}
+
+impl Deficiency {
+    const ALL: [Deficiency; 3] = [
+        Deficiency::Protanopia,
+        Deficiency::Deuteranopia,
+        Deficiency::Tritanopia,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Deficiency::Protanopia => "protanopia",
+            Deficiency::Deuteranopia => "deuteranopia",
+            Deficiency::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// Linear RGB as seen with this deficiency
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// Where the difference between the real and the seen color is moved
+    /// when daltonizing
+    fn correction(self) -> [[f32; 3]; 3] {
+        match self {
+            // Red-green differences become brightness and blue
+            Deficiency::Protanopia | Deficiency::Deuteranopia => {
+                [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]
+            }
+            // Blue-yellow differences become red and green
+            Deficiency::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+        }
+    }
+}
+
+impl FromStr for Deficiency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Deficiency::ALL
+            .into_iter()
+            .find(|deficiency| deficiency.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown color vision deficiency '{}', expected protanopia, deuteranopia or tritanopia",
+                    s
+                )
+            })
+    }
+}
+
+/// What `--cvd` does to the colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvdFilter {
+    /// Show colors as seen with the deficiency
+    Simulate(Deficiency),
+    /// Make colors easier to tell apart with the deficiency
+    Daltonize(Deficiency),
+}
+
+impl CvdFilter {
+    /// Filtered color
+    pub fn apply(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let decode = decode_table();
+        let color = [decode[r as usize], decode[g as usize], decode[b as usize]];
+        let filtered = match *self {
+            CvdFilter::Simulate(deficiency) => multiply(deficiency.matrix(), color),
+            CvdFilter::Daltonize(deficiency) => {
+                let seen = multiply(deficiency.matrix(), color);
+                let error = [0, 1, 2].map(|channel| color[channel] - seen[channel]);
+                let shift = multiply(deficiency.correction(), error);
+                [0, 1, 2].map(|channel| color[channel] + shift[channel])
+            }
+        };
+        let [r, g, b] = filtered.map(encode);
+        (r, g, b)
+    }
+}
+
+impl FromStr for CvdFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, deficiency) = s.split_once(':').unwrap_or((s, ""));
+        match mode.trim() {
+            "simulate" if deficiency.is_empty() => {
+                Err("simulate needs a deficiency, e.g. simulate:deuteranopia".to_string())
+            }
+            "simulate" => Ok(CvdFilter::Simulate(deficiency.parse()?)),
+            "daltonize" if deficiency.is_empty() => {
+                Ok(CvdFilter::Daltonize(Deficiency::Deuteranopia))
+            }
+            "daltonize" => Ok(CvdFilter::Daltonize(deficiency.parse()?)),
+            _ => Err(format!(
+                "Unknown CVD filter '{}', expected simulate:TYPE or daltonize[:TYPE]",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CvdFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvdFilter::Simulate(deficiency) => write!(f, "simulate:{}", deficiency.name()),
+            CvdFilter::Daltonize(deficiency) => write!(f, "daltonize:{}", deficiency.name()),
+        }
+    }
+}
+
+fn multiply(matrix: [[f32; 3]; 3], color: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * color[0] + row[1] * color[1] + row[2] * color[2])
+}
+
+/// Linear light of each sRGB channel value
+fn decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|value| {
+            let value = value as f32 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })
+}
+
+/// sRGB channel value of linear light, clamped to the displayable range
+fn encode(linear: f32) -> u8 {
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..ENCODE_STEPS)
+            .map(|step| {
+                let linear = step as f32 / (ENCODE_STEPS - 1) as f32;
+                let value = if linear <= 0.0031308 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (value * 255.0).round() as u8
+            })
+            .collect()
+    });
+    let step = (linear.clamp(0.0, 1.0) * (ENCODE_STEPS - 1) as f32).round() as usize;
+    table[step]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+        (a.0 as i32 - b.0 as i32).abs()
+            + (a.1 as i32 - b.1 as i32).abs()
+            + (a.2 as i32 - b.2 as i32).abs()
+    }
+
+    #[test]
+    fn test_parse_cvd_filter() {
+        assert_eq!(
+            "simulate:deuteranopia".parse(),
+            Ok(CvdFilter::Simulate(Deficiency::Deuteranopia))
+        );
+        assert_eq!(
+            "daltonize".parse(),
+            Ok(CvdFilter::Daltonize(Deficiency::Deuteranopia))
+        );
+        assert_eq!(
+            "daltonize:Tritanopia".parse(),
+            Ok(CvdFilter::Daltonize(Deficiency::Tritanopia))
+        );
+        assert!("simulate".parse::<CvdFilter>().is_err());
+        assert!("simulate:achromatopsia".parse::<CvdFilter>().is_err());
+        assert!("invert".parse::<CvdFilter>().is_err());
+        assert_eq!(
+            CvdFilter::Simulate(Deficiency::Protanopia).to_string(),
+            "simulate:protanopia"
+        );
+    }
+
+    #[test]
+    fn test_simulate() {
+        let simulate = CvdFilter::Simulate(Deficiency::Deuteranopia);
+        // Grays look the same
+        for gray in [0, 128, 255] {
+            assert!(distance(simulate.apply((gray, gray, gray)), (gray, gray, gray)) <= 3);
+        }
+        // Red and green move much closer together
+        let (red, green) = ((200, 40, 40), (40, 160, 40));
+        assert!(distance(simulate.apply(red), simulate.apply(green)) < distance(red, green) / 2);
+    }
+
+    #[test]
+    fn test_daltonize() {
+        let daltonize = CvdFilter::Daltonize(Deficiency::Deuteranopia);
+        let simulate = CvdFilter::Simulate(Deficiency::Deuteranopia);
+        assert!(distance(daltonize.apply((128, 128, 128)), (128, 128, 128)) <= 3);
+
+        // After daltonizing, red and green are easier to tell apart for the viewer
+        let (red, green) = ((200, 40, 40), (40, 160, 40));
+        let seen = distance(simulate.apply(red), simulate.apply(green));
+        let seen_daltonized = distance(
+            simulate.apply(daltonize.apply(red)),
+            simulate.apply(daltonize.apply(green)),
+        );
+        assert!(seen_daltonized > seen);
+    }
+}
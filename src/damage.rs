@@ -0,0 +1,251 @@
+//! Changed regions between decoded frames
+//!
+//! Screen recordings and slides leave most of the picture untouched from
+//! one frame to the next. The decode stage compares each frame with the one
+//! before it in tiles and reports the bounding boxes of the tiles that
+//! changed, so the converter recomputes only the cells sampling those
+//! regions and the renderer redraws only those cells.
+
+use crate::crop::CropRect;
+use crate::decoder::VideoFrame;
+
+/// Side of the square tiles frames are compared in, in pixels
+pub const TILE_SIZE: u32 = 16;
+
+/// Beyond this share of the picture changed, the frame counts as new
+const MAX_DAMAGED_SHARE: f64 = 0.5;
+
+/// What changed since the previous frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    /// Anything may have changed, e.g. the first frame or a new size
+    Full,
+    /// Only these regions changed; none for an identical frame
+    Regions(Vec<CropRect>),
+}
+
+impl Damage {
+    /// Damage in the part of the frame kept by `crop`, in its coordinates
+    pub fn crop(self, crop: CropRect) -> Damage {
+        let Damage::Regions(regions) = self else {
+            return Damage::Full;
+        };
+        let regions = regions
+            .into_iter()
+            .filter_map(|region| {
+                let x = region.x.max(crop.x);
+                let y = region.y.max(crop.y);
+                let right = (region.x + region.width).min(crop.x + crop.width);
+                let bottom = (region.y + region.height).min(crop.y + crop.height);
+                (right > x && bottom > y).then(|| CropRect {
+                    x: x - crop.x,
+                    y: y - crop.y,
+                    width: right - x,
+                    height: bottom - y,
+                })
+            })
+            .collect();
+        Damage::Regions(regions)
+    }
+}
+
+/// Compares each frame with the previous one
+#[derive(Debug, Default)]
+pub struct DamageTracker {
+    previous: Vec<u8>,
+    size: Option<(u32, u32)>,
+}
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Regions of `frame` that differ from the frame tracked before it
+    pub fn track(&mut self, frame: &VideoFrame) -> Damage {
+        let size = (frame.width, frame.height);
+        let comparable =
+            self.size == Some(size) && self.previous.len() == frame.data.len() && frame.width > 0;
+        let damage = if comparable {
+            self.compare(frame)
+        } else {
+            Damage::Full
+        };
+        self.previous.clear();
+        self.previous.extend_from_slice(&frame.data);
+        self.size = Some(size);
+        damage
+    }
+
+    fn compare(&self, frame: &VideoFrame) -> Damage {
+        let (width, height) = (frame.width, frame.height);
+        let stride = width as usize * 3;
+        let mut regions: Vec<CropRect> = Vec::new();
+        let mut damaged_area = 0u64;
+
+        for tile_y in (0..height).step_by(TILE_SIZE as usize) {
+            let tile_height = TILE_SIZE.min(height - tile_y);
+            let changed = |tile_x: u32| {
+                let tile_width = TILE_SIZE.min(width - tile_x) as usize;
+                (tile_y..tile_y + tile_height).any(|y| {
+                    let start = y as usize * stride + tile_x as usize * 3;
+                    let end = start + tile_width * 3;
+                    frame.data[start..end] != self.previous[start..end]
+                })
+            };
+
+            // Runs of changed tiles across this row
+            let mut row = Vec::new();
+            let mut run: Option<u32> = None;
+            for tile_x in (0..width).step_by(TILE_SIZE as usize).chain([width]) {
+                let is_changed = tile_x < width && changed(tile_x);
+                match (run, is_changed) {
+                    (None, true) => run = Some(tile_x),
+                    (Some(start), false) => {
+                        row.push((start, tile_x - start));
+                        run = None;
+                    }
+                    _ => {}
+                }
+            }
+
+            // A run spanning the same columns as a region just above extends it
+            for (x, run_width) in row {
+                damaged_area += run_width as u64 * tile_height as u64;
+                let above = regions.iter_mut().find(|region| {
+                    region.x == x && region.width == run_width && region.y + region.height == tile_y
+                });
+                match above {
+                    Some(region) => region.height += tile_height,
+                    None => regions.push(CropRect {
+                        x,
+                        y: tile_y,
+                        width: run_width,
+                        height: tile_height,
+                    }),
+                }
+            }
+        }
+
+        if damaged_area as f64 > width as f64 * height as f64 * MAX_DAMAGED_SHARE {
+            Damage::Full
+        } else {
+            Damage::Regions(regions)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32, bright: impl Fn(u32, u32) -> bool) -> VideoFrame {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend([if bright(x, y) { 255 } else { 0 }; 3]);
+            }
+        }
+        VideoFrame {
+            data,
+            width,
+            height,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_damage_regions() {
+        let mut tracker = DamageTracker::new();
+        assert_eq!(tracker.track(&frame(64, 64, |_, _| false)), Damage::Full);
+        assert_eq!(
+            tracker.track(&frame(64, 64, |_, _| false)),
+            Damage::Regions(Vec::new())
+        );
+
+        // A cursor blinking inside one tile
+        let cursor = frame(64, 64, |x, y| x == 20 && y == 40);
+        assert_eq!(
+            tracker.track(&cursor),
+            Damage::Regions(vec![CropRect {
+                x: 16,
+                y: 32,
+                width: 16,
+                height: 16,
+            }])
+        );
+
+        // A block of two by two tiles and a dot appear, the cursor goes
+        let changed = frame(64, 64, |x, y| (x < 20 && y < 20) || (x == 60 && y == 60));
+        assert_eq!(
+            tracker.track(&changed),
+            Damage::Regions(vec![
+                CropRect {
+                    x: 0,
+                    y: 0,
+                    width: 32,
+                    height: 32,
+                },
+                CropRect {
+                    x: 16,
+                    y: 32,
+                    width: 16,
+                    height: 16,
+                },
+                CropRect {
+                    x: 48,
+                    y: 48,
+                    width: 16,
+                    height: 16,
+                },
+            ])
+        );
+
+        // Most of the picture changing, or a new size, is a full update
+        assert_eq!(tracker.track(&frame(64, 64, |_, _| true)), Damage::Full);
+        assert_eq!(tracker.track(&frame(32, 64, |_, _| true)), Damage::Full);
+    }
+
+    #[test]
+    fn test_crop_damage() {
+        let damage = Damage::Regions(vec![
+            CropRect {
+                x: 0,
+                y: 0,
+                width: 16,
+                height: 16,
+            },
+            CropRect {
+                x: 32,
+                y: 40,
+                width: 16,
+                height: 16,
+            },
+        ]);
+        let crop = CropRect {
+            x: 8,
+            y: 10,
+            width: 30,
+            height: 40,
+        };
+        assert_eq!(
+            damage.crop(crop),
+            Damage::Regions(vec![
+                CropRect {
+                    x: 0,
+                    y: 0,
+                    width: 8,
+                    height: 6,
+                },
+                CropRect {
+                    x: 24,
+                    y: 30,
+                    width: 6,
+                    height: 10,
+                },
+            ])
+        );
+        assert_eq!(Damage::Full.crop(crop), Damage::Full);
+    }
+}
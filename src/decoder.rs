@@ -1,19 +1,50 @@
+use crate::captions::ClosedCaptions;
+use crate::edl::CutList;
+use crate::sniff;
+use crate::subtitles::SubtitleStream;
 use anyhow::{anyhow, Result};
 use ffmpeg_next as ffmpeg;
 use log::{debug, info};
 use std::path::Path;
+use tracing::debug_span;
 
 /// Video decoder that extracts frames from video files
 pub struct VideoDecoder {
     input_context: ffmpeg::format::context::Input,
     stream_index: usize,
     decoder: ffmpeg::codec::decoder::Video,
-    scaler: Option<ffmpeg::software::scaling::Context>,
+    scaler: Option<Scaler>,
     frame_count: u64,
     fps: f64,
     duration: f64,
+    time_base: ffmpeg::Rational,
+    /// Frames before this timestamp are decoded but dropped (set by seeking)
+    discard_before: Option<f64>,
+    subtitles: Option<SubtitleStream>,
+    captions: ClosedCaptions,
+    /// A capture device: timestamps count from the first frame and there
+    /// is nothing to seek in
+    live: bool,
+    /// Timestamp of the first frame of a capture device
+    live_origin: Option<f64>,
 }
 
+/// Scaling context that can move to another thread with its decoder.
+///
+/// `SwsContext` is not tied to the thread that created it; ffmpeg-next only
+/// leaves out the marker.
+struct Scaler(ffmpeg::software::scaling::Context);
+
+// SAFETY: the context is a raw `SwsContext` pointer that only this value
+// owns, so moving it moves the only handle. libswscale keeps no per-thread
+// state: a context may be used and freed (`sws_freeContext` on drop) from any
+// thread as long as calls don't overlap, and `Scaler` is not `Sync` and only
+// reached through `&mut VideoDecoder`, so they never do.
+unsafe impl Send for Scaler {}
+
+/// Tolerance when comparing frame timestamps against seek targets
+pub const TIMESTAMP_EPSILON: f64 = 1e-6;
+
 /// Represents a decoded video frame with metadata
 #[derive(Debug)]
 pub struct VideoFrame {
@@ -32,35 +63,60 @@ pub struct VideoFrame {
 impl VideoDecoder {
     /// Create a new VideoDecoder from a file path
     pub fn new(path: &Path) -> Result<Self> {
-        // Initialize FFmpeg with error handling
-        match ffmpeg::init() {
-            Ok(_) => debug!("FFmpeg initialized successfully"),
-            Err(e) => {
-                debug!("FFmpeg init error: {:?}", e);
-                // Continue anyway as this might not be fatal
-            }
-        }
+        init_ffmpeg();
 
         debug!("Attempting to open video file: {}", path.display());
+        sniff::check_input(path)?;
         let input_context = ffmpeg::format::input(&path).map_err(|e| {
             info!("FFmpeg error details: {:?}", e);
             anyhow!("Failed to open video file '{}': {}", path.display(), e)
         })?;
         debug!("Successfully opened video file");
 
+        Self::from_input(input_context, &format!("file '{}'", path.display()))
+    }
+
+    /// Capture live frames from a camera: an index such as `0` or a device
+    /// name or path, through v4l2 on Linux, AVFoundation on macOS and
+    /// DirectShow on Windows
+    pub fn open_device(device: &str) -> Result<Self> {
+        init_ffmpeg();
+
+        let (format_name, url) = capture_input(device)?;
+        let format = ffmpeg::device::input::video()
+            .find(|format| format.name() == format_name)
+            .ok_or_else(|| anyhow!("This FFmpeg build has no {} capture support", format_name))?;
+        let mut options = ffmpeg::Dictionary::new();
+        if format_name == "avfoundation" {
+            // AVFoundation refuses to open without a rate the camera offers
+            options.set("framerate", "30");
+        }
+
+        debug!("Opening capture device {} with {}", url, format_name);
+        let input_context = match ffmpeg::format::open_with(&url, &format, options) {
+            Ok(ffmpeg::format::context::Context::Input(input)) => input,
+            Ok(_) => return Err(anyhow!("'{}' is not a capture device", device)),
+            Err(e) => return Err(anyhow!("Failed to open camera '{}': {}", device, e)),
+        };
+
+        let mut decoder = Self::from_input(input_context, &format!("camera '{}'", device))?;
+        decoder.live = true;
+        decoder.duration = 0.0;
+        Ok(decoder)
+    }
+
+    /// Set up decoding of the best video stream of an opened input,
+    /// described as `source` in messages
+    fn from_input(input_context: ffmpeg::format::context::Input, source: &str) -> Result<Self> {
         // Find the best video stream
         let stream = input_context
             .streams()
             .best(ffmpeg::media::Type::Video)
-            .ok_or_else(|| anyhow!("No video stream found in file '{}'", path.display()))?;
+            .ok_or_else(|| anyhow!("No video stream found in {}", source))?;
 
         let stream_index = stream.index();
 
-        info!(
-            "Found video stream {} in file '{}'",
-            stream_index,
-            path.display()
-        );
+        info!("Found video stream {} in {}", stream_index, source);
 
         // Create decoder context
         let context_decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
@@ -79,6 +135,7 @@ impl VideoDecoder {
             25.0 // Default fallback FPS
         };
 
+        let time_base = stream.time_base();
         let duration = if stream.duration() != ffmpeg::ffi::AV_NOPTS_VALUE {
             stream.duration() as f64 * stream.time_base().numerator() as f64
                 / stream.time_base().denominator() as f64
@@ -102,9 +159,20 @@ impl VideoDecoder {
             frame_count: 0,
             fps,
             duration,
+            time_base,
+            discard_before: None,
+            subtitles: None,
+            captions: ClosedCaptions::default(),
+            live: false,
+            live_origin: None,
         })
     }
 
+    /// Whether frames come live from a capture device
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
     /// Get video FPS
     pub fn fps(&self) -> f64 {
         self.fps
@@ -120,15 +188,31 @@ impl VideoDecoder {
         (self.decoder.width(), self.decoder.height())
     }
 
-    /// Seek to a specific time in seconds
+    /// Burn the given bitmap subtitle track (0-based among subtitle streams)
+    /// into decoded frames
+    pub fn select_subtitle_track(&mut self, track: usize) -> Result<()> {
+        let dimensions = self.dimensions();
+        self.subtitles = Some(SubtitleStream::open(
+            &self.input_context,
+            track,
+            dimensions,
+        )?);
+        Ok(())
+    }
+
+    /// Seek to a specific time in seconds.
+    ///
+    /// The demuxer can only land on keyframes, so this seeks to the keyframe
+    /// at or before `timestamp` and then drops decoded frames until the
+    /// requested time is reached. The next frame returned is the first one
+    /// at or after `timestamp`.
     pub fn seek_to(&mut self, timestamp: f64) -> Result<()> {
-        let time_base = self
-            .input_context
-            .stream(self.stream_index)
-            .unwrap()
-            .time_base();
-        let timestamp_ts =
-            (timestamp / (time_base.numerator() as f64 / time_base.denominator() as f64)) as i64;
+        if self.live {
+            return Err(anyhow!("Live camera input can't seek"));
+        }
+        let timestamp = timestamp.max(0.0);
+        // Seeking without a stream index uses AV_TIME_BASE units
+        let timestamp_ts = (timestamp * ffmpeg::ffi::AV_TIME_BASE as f64) as i64;
 
         self.input_context
             .seek(timestamp_ts, ..timestamp_ts)
@@ -136,6 +220,11 @@ impl VideoDecoder {
 
         // Reset decoder state
         self.decoder.flush();
+        self.discard_before = Some(timestamp);
+        if let Some(ref mut subtitles) = self.subtitles {
+            subtitles.reset();
+        }
+        self.captions.reset();
 
         debug!("Seeked to timestamp: {:.2}s", timestamp);
         Ok(())
@@ -143,10 +232,20 @@ impl VideoDecoder {
 
     /// Get the next frame from the video
     pub fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+        let _span = debug_span!("decode").entered();
         let mut decoded_frame = ffmpeg::frame::Video::empty();
 
         // Try to decode frames until we get one from our video stream
         for (stream, packet) in self.input_context.packets() {
+            if let Some(ref mut subtitles) = self.subtitles {
+                if stream.index() == subtitles.stream_index() {
+                    if let Err(e) = subtitles.decode(&packet) {
+                        debug!("Skipping subtitle packet: {}", e);
+                    }
+                    continue;
+                }
+            }
+
             if stream.index() == self.stream_index {
                 self.decoder
                     .send_packet(&packet)
@@ -156,6 +255,20 @@ impl VideoDecoder {
                 match self.decoder.receive_frame(&mut decoded_frame) {
                     Ok(()) => {
                         self.frame_count += 1;
+
+                        // Drop frames between the keyframe and the seek target
+                        if let Some(target) = self.discard_before {
+                            let timestamp = frame_timestamp(
+                                &decoded_frame,
+                                self.time_base,
+                                self.frame_count,
+                                self.fps,
+                            );
+                            if timestamp + TIMESTAMP_EPSILON < target {
+                                continue;
+                            }
+                            self.discard_before = None;
+                        }
                         return self.convert_frame(&decoded_frame);
                     }
                     Err(ffmpeg::Error::Other { errno }) if errno == ffmpeg::ffi::EAGAIN => {
@@ -204,7 +317,7 @@ impl VideoDecoder {
                 height
             );
 
-            self.scaler = Some(
+            self.scaler = Some(Scaler(
                 ffmpeg::software::scaling::Context::get(
                     frame.format(),
                     width,
@@ -215,13 +328,13 @@ impl VideoDecoder {
                     ffmpeg::software::scaling::Flags::BILINEAR,
                 )
                 .map_err(|e| anyhow!("Failed to create scaling context: {}", e))?,
-            );
+            ));
         }
 
         // Create output frame with proper format and size
         let mut rgb_frame = ffmpeg::frame::Video::new(ffmpeg::format::Pixel::RGB24, width, height);
 
-        if let Some(ref mut scaler) = self.scaler {
+        if let Some(Scaler(ref mut scaler)) = self.scaler {
             scaler
                 .run(frame, &mut rgb_frame)
                 .map_err(|e| anyhow!("Failed to scale frame: {}", e))?;
@@ -229,21 +342,17 @@ impl VideoDecoder {
             return Err(anyhow!("Scaler not initialized"));
         }
 
+        // Closed captions ride along as A/53 side data
+        if let Some(cc_data) = frame.side_data(ffmpeg::frame::side_data::Type::A53CC) {
+            self.captions.feed(cc_data.data());
+        }
+
         // Calculate timestamp
-        let time_base = self
-            .input_context
-            .stream(self.stream_index)
-            .unwrap()
-            .time_base();
-        let timestamp = if let Some(ts) = frame.timestamp() {
-            if ts != ffmpeg::ffi::AV_NOPTS_VALUE {
-                ts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64
-            } else {
-                self.frame_count as f64 / self.fps
-            }
-        } else {
-            self.frame_count as f64 / self.fps
-        };
+        let mut timestamp = frame_timestamp(frame, self.time_base, self.frame_count, self.fps);
+        if self.live {
+            // Devices stamp frames with the system clock
+            timestamp -= *self.live_origin.get_or_insert(timestamp);
+        }
 
         // Extract RGB data safely
         let rgb_data = rgb_frame.data(0);
@@ -268,13 +377,24 @@ impl VideoDecoder {
             data.len()
         );
 
-        Ok(Some(VideoFrame {
+        let mut video_frame = VideoFrame {
             data,
             width,
             height,
             timestamp,
             frame_number: self.frame_count,
-        }))
+        };
+
+        if let Some(ref mut subtitles) = self.subtitles {
+            subtitles.burn_in(&mut video_frame);
+        }
+
+        Ok(Some(video_frame))
+    }
+
+    /// Closed captions decoded from the frames returned so far
+    pub fn captions(&self) -> &ClosedCaptions {
+        &self.captions
     }
 
     /// Get current frame count
@@ -283,28 +403,165 @@ impl VideoDecoder {
     }
 }
 
-/// Iterator wrapper for VideoDecoder
+/// Where a [`FrameIterator`] gets its frames: a video decoded with FFmpeg
+/// or an image sequence
+pub trait FrameSource: Send {
+    /// Frames per second
+    fn fps(&self) -> f64;
+
+    /// Length in seconds, 0 if unknown
+    fn duration(&self) -> f64;
+
+    /// Width and height of the frames
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Continue from the first frame at or after `timestamp` seconds
+    fn seek_to(&mut self, timestamp: f64) -> Result<()>;
+
+    /// The next frame, None at the end
+    fn next_frame(&mut self) -> Result<Option<VideoFrame>>;
+
+    /// Closed captions read with the frames returned so far
+    fn captions(&self) -> &ClosedCaptions;
+}
+
+impl FrameSource for VideoDecoder {
+    fn fps(&self) -> f64 {
+        VideoDecoder::fps(self)
+    }
+
+    fn duration(&self) -> f64 {
+        VideoDecoder::duration(self)
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        VideoDecoder::dimensions(self)
+    }
+
+    fn seek_to(&mut self, timestamp: f64) -> Result<()> {
+        VideoDecoder::seek_to(self, timestamp)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+        VideoDecoder::next_frame(self)
+    }
+
+    fn captions(&self) -> &ClosedCaptions {
+        VideoDecoder::captions(self)
+    }
+}
+
+fn init_ffmpeg() {
+    // Initialize FFmpeg with error handling
+    match ffmpeg::init() {
+        Ok(_) => debug!("FFmpeg initialized successfully"),
+        Err(e) => {
+            debug!("FFmpeg init error: {:?}", e);
+            // Continue anyway as this might not be fatal
+        }
+    }
+}
+
+/// FFmpeg input format and URL for capturing from `device`
+fn capture_input(device: &str) -> Result<(&'static str, String)> {
+    let index = device.parse::<u32>().ok();
+    if cfg!(target_os = "linux") {
+        let url = match index {
+            Some(index) => format!("/dev/video{}", index),
+            None => device.to_string(),
+        };
+        Ok(("v4l2", url))
+    } else if cfg!(target_os = "macos") {
+        // An index or a name, with no audio
+        Ok(("avfoundation", format!("{}:none", device)))
+    } else if cfg!(windows) {
+        if index.is_some() {
+            return Err(anyhow!(
+                "DirectShow opens cameras by name, e.g. --device \"Integrated Camera\""
+            ));
+        }
+        Ok(("dshow", format!("video={}", device)))
+    } else {
+        Err(anyhow!("Camera capture isn't supported on this platform"))
+    }
+}
+
+/// Presentation time of a decoded frame in seconds, falling back to the
+/// frame count when the frame carries no timestamp
+fn frame_timestamp(
+    frame: &ffmpeg::frame::Video,
+    time_base: ffmpeg::Rational,
+    frame_count: u64,
+    fps: f64,
+) -> f64 {
+    match frame.timestamp() {
+        Some(ts) if ts != ffmpeg::ffi::AV_NOPTS_VALUE => {
+            ts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64
+        }
+        _ => frame_count as f64 / fps,
+    }
+}
+
+/// Iterator over the frames of a video or another [`FrameSource`]
 pub struct FrameIterator {
-    decoder: VideoDecoder,
+    decoder: Box<dyn FrameSource>,
     start_time: Option<f64>,
     end_time: Option<f64>,
     has_seeked: bool,
+    cuts: Option<CutList>,
 }
 
 impl FrameIterator {
     /// Create a new frame iterator
     pub fn new(decoder: VideoDecoder, start_time: Option<f64>, end_time: Option<f64>) -> Self {
+        Self::from_source(Box::new(decoder), start_time, end_time)
+    }
+
+    /// Iterate over the frames of any source, such as an image sequence
+    pub fn from_source(
+        decoder: Box<dyn FrameSource>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    ) -> Self {
         Self {
             decoder,
             start_time,
             end_time,
             has_seeked: false,
+            cuts: None,
+        }
+    }
+
+    /// Skip the ranges of a cut list during iteration
+    pub fn with_cuts(mut self, cuts: CutList) -> Self {
+        self.cuts = Some(cuts);
+        self
+    }
+
+    /// Continue iteration from `timestamp` seconds
+    pub fn seek(&mut self, timestamp: f64) -> Result<()> {
+        self.has_seeked = true;
+        self.decoder.seek_to(timestamp)
+    }
+
+    /// Time `fraction` (0.0-1.0) of the way through what the iterator
+    /// plays: the source's duration narrowed to the start and end times.
+    /// None when the length is unknown, as for live input.
+    pub fn time_at(&self, fraction: f64) -> Option<f64> {
+        let duration = self.decoder.duration();
+        if duration <= 0.0 {
+            return None;
         }
+        let start = self.start_time.unwrap_or(0.0).clamp(0.0, duration);
+        let end = self
+            .end_time
+            .map_or(duration, |end| end.clamp(start, duration));
+        Some(start + (end - start) * fraction.clamp(0.0, 1.0))
     }
 
     /// Get the underlying decoder reference
-    pub fn decoder(&self) -> &VideoDecoder {
-        &self.decoder
+    pub fn decoder(&self) -> &dyn FrameSource {
+        self.decoder.as_ref()
     }
 }
 
@@ -322,18 +579,36 @@ impl Iterator for FrameIterator {
             }
         }
 
-        match self.decoder.next_frame() {
-            Ok(Some(frame)) => {
-                // Check if we've reached the end time
-                if let Some(end_time) = self.end_time {
-                    if frame.timestamp >= end_time {
-                        return None;
+        loop {
+            match self.decoder.next_frame() {
+                Ok(Some(frame)) => {
+                    // Jump over cut ranges
+                    let resume = self
+                        .cuts
+                        .as_ref()
+                        .and_then(|cuts| cuts.resume_point(frame.timestamp + TIMESTAMP_EPSILON));
+                    if let Some(resume) = resume {
+                        if !resume.is_finite() {
+                            return None;
+                        }
+                        debug!("Skipping cut {:.2}s -> {:.2}s", frame.timestamp, resume);
+                        if let Err(e) = self.decoder.seek_to(resume) {
+                            return Some(Err(e));
+                        }
+                        continue;
+                    }
+
+                    // Check if we've reached the end time
+                    if let Some(end_time) = self.end_time {
+                        if frame.timestamp >= end_time {
+                            return None;
+                        }
                     }
+                    return Some(Ok(frame));
                 }
-                Some(Ok(frame))
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
             }
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
         }
     }
 }
@@ -348,6 +623,18 @@ pub fn load_video(
     Ok(FrameIterator::new(decoder, start_time, end_time))
 }
 
+/// Stable name for an input: URLs as given, files as absolute paths
+pub fn source_location(input: &Path) -> String {
+    let text = input.to_string_lossy();
+    if text.contains("://") {
+        return text.into_owned();
+    }
+    input
+        .canonicalize()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| text.into_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,4 +660,71 @@ mod tests {
         let result = VideoDecoder::new(&invalid_path);
         assert!(result.is_err(), "Should fail for nonexistent file");
     }
+
+    /// A source of known length and no frames
+    struct Blank {
+        duration: f64,
+        captions: ClosedCaptions,
+    }
+
+    impl FrameSource for Blank {
+        fn fps(&self) -> f64 {
+            25.0
+        }
+
+        fn duration(&self) -> f64 {
+            self.duration
+        }
+
+        fn dimensions(&self) -> (u32, u32) {
+            (4, 4)
+        }
+
+        fn seek_to(&mut self, _timestamp: f64) -> Result<()> {
+            Ok(())
+        }
+
+        fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+            Ok(None)
+        }
+
+        fn captions(&self) -> &ClosedCaptions {
+            &self.captions
+        }
+    }
+
+    #[test]
+    fn test_time_at_follows_the_window() {
+        let blank = |duration| {
+            Box::new(Blank {
+                duration,
+                captions: ClosedCaptions::default(),
+            })
+        };
+        let whole = FrameIterator::from_source(blank(200.0), None, None);
+        assert_eq!(whole.time_at(0.0), Some(0.0));
+        assert_eq!(whole.time_at(0.5), Some(100.0));
+
+        // Trimmed, percentages are of what plays
+        let trimmed = FrameIterator::from_source(blank(200.0), Some(20.0), Some(120.0));
+        assert_eq!(trimmed.time_at(0.0), Some(20.0));
+        assert_eq!(trimmed.time_at(0.5), Some(70.0));
+        assert_eq!(trimmed.time_at(1.0), Some(120.0));
+
+        let live = FrameIterator::from_source(blank(0.0), None, None);
+        assert_eq!(live.time_at(0.5), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_capture_input() {
+        assert_eq!(
+            capture_input("0").unwrap(),
+            ("v4l2", "/dev/video0".to_string())
+        );
+        assert_eq!(
+            capture_input("/dev/video2").unwrap(),
+            ("v4l2", "/dev/video2".to_string())
+        );
+    }
 }
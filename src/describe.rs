@@ -0,0 +1,257 @@
+//! Text descriptions of a video for screen readers
+//!
+//! `--describe` plays the video without drawing it and prints short lines to
+//! stdout instead: a marker at every scene change, and every few seconds a
+//! summary of how bright the picture is and how much it moves, each prefixed
+//! with the playback time. Lines come out in real time so they can be read
+//! aloud as the video plays.
+
+use crate::cli::Cli;
+use crate::decoder::VideoFrame;
+use anyhow::Result;
+use log::info;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Default seconds between summaries
+pub const DEFAULT_DESCRIBE_INTERVAL: f64 = 5.0;
+
+/// Coarse grid frames are sampled on
+const SIGNATURE_WIDTH: u32 = 32;
+const SIGNATURE_HEIGHT: u32 = 18;
+const HISTOGRAM_BINS: usize = 16;
+
+/// Share of the luminance histogram that must change for a new scene
+const SCENE_CHANGE_THRESHOLD: f32 = 0.4;
+
+/// Luminance of a frame on a coarse grid
+struct Signature {
+    luma: Vec<f32>,
+    histogram: [f32; HISTOGRAM_BINS],
+}
+
+impl Signature {
+    fn new(frame: &VideoFrame) -> Self {
+        let mut luma = Vec::with_capacity((SIGNATURE_WIDTH * SIGNATURE_HEIGHT) as usize);
+        let mut histogram = [0.0; HISTOGRAM_BINS];
+        for gy in 0..SIGNATURE_HEIGHT {
+            for gx in 0..SIGNATURE_WIDTH {
+                let x = (gx * frame.width / SIGNATURE_WIDTH).min(frame.width.saturating_sub(1));
+                let y = (gy * frame.height / SIGNATURE_HEIGHT).min(frame.height.saturating_sub(1));
+                let index = ((y * frame.width + x) * 3) as usize;
+                let value = match frame.data.get(index..index + 3) {
+                    Some(&[r, g, b]) => {
+                        (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0
+                    }
+                    _ => 0.0,
+                };
+                luma.push(value);
+                histogram[((value * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)] +=
+                    1.0;
+            }
+        }
+
+        let total = luma.len().max(1) as f32;
+        for bin in &mut histogram {
+            *bin /= total;
+        }
+        Self { luma, histogram }
+    }
+
+    fn brightness(&self) -> f32 {
+        self.luma.iter().sum::<f32>() / self.luma.len().max(1) as f32
+    }
+
+    /// Mean luminance change per sample, 0 to 1
+    fn motion(&self, previous: &Signature) -> f32 {
+        let change: f32 = self
+            .luma
+            .iter()
+            .zip(&previous.luma)
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        change / self.luma.len().max(1) as f32
+    }
+
+    /// Share of the histogram that moved, 0 to 1
+    fn histogram_change(&self, previous: &Signature) -> f32 {
+        let change: f32 = self
+            .histogram
+            .iter()
+            .zip(&previous.histogram)
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        change / 2.0
+    }
+}
+
+fn brightness_words(brightness: f32) -> &'static str {
+    match brightness {
+        b if b < 0.15 => "very dark",
+        b if b < 0.35 => "dark",
+        b if b < 0.65 => "medium brightness",
+        b if b < 0.85 => "bright",
+        _ => "very bright",
+    }
+}
+
+fn motion_words(motion: f32) -> &'static str {
+    match motion {
+        m if m < 0.01 => "Still",
+        m if m < 0.04 => "Little motion",
+        m if m < 0.1 => "Some motion",
+        _ => "Lots of motion",
+    }
+}
+
+/// `[mm:ss]`, or `[h:mm:ss]` past an hour
+fn format_time(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("[{}:{:02}:{:02}]", hours, minutes, seconds)
+    } else {
+        format!("[{:02}:{:02}]", minutes, seconds)
+    }
+}
+
+/// Turns frames into description lines
+pub struct Describer {
+    interval: f64,
+    previous: Option<Signature>,
+    /// Start of the period the next summary covers
+    period_start: f64,
+    brightness_sum: f32,
+    motion_sum: f32,
+    samples: u32,
+}
+
+impl Describer {
+    /// Summarize every `interval` seconds of video
+    pub fn new(interval: f64) -> Self {
+        Self {
+            interval,
+            previous: None,
+            period_start: 0.0,
+            brightness_sum: 0.0,
+            motion_sum: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Take in the next frame, returning a line to print if one is due
+    pub fn observe(&mut self, frame: &VideoFrame) -> Option<String> {
+        let signature = Signature::new(frame);
+        let brightness = signature.brightness();
+        let time = format_time(frame.timestamp);
+
+        let Some(previous) = self.previous.take() else {
+            self.previous = Some(signature);
+            self.start_period(frame.timestamp);
+            return Some(format!("{} Start, {}.", time, brightness_words(brightness)));
+        };
+        let scene_change = signature.histogram_change(&previous) > SCENE_CHANGE_THRESHOLD;
+        let motion = signature.motion(&previous);
+        self.previous = Some(signature);
+
+        if scene_change {
+            self.start_period(frame.timestamp);
+            return Some(format!(
+                "{} Scene change, {}.",
+                time,
+                brightness_words(brightness)
+            ));
+        }
+
+        self.brightness_sum += brightness;
+        self.motion_sum += motion;
+        self.samples += 1;
+        if frame.timestamp - self.period_start < self.interval {
+            return None;
+        }
+
+        let samples = self.samples as f32;
+        let line = format!(
+            "{} {}, {}.",
+            time,
+            motion_words(self.motion_sum / samples),
+            brightness_words(self.brightness_sum / samples)
+        );
+        self.start_period(frame.timestamp);
+        Some(line)
+    }
+
+    fn start_period(&mut self, timestamp: f64) {
+        self.period_start = timestamp;
+        self.brightness_sum = 0.0;
+        self.motion_sum = 0.0;
+        self.samples = 0;
+    }
+}
+
+/// Play the input in real time, printing descriptions instead of frames
+pub async fn run_describe(cli: &Cli) -> Result<()> {
+    let frame_iter = cli.open_video()?;
+    let mut describer = Describer::new(cli.describe_interval);
+    info!(
+        "Describing {} every {:.1}s",
+        cli.input_path().display(),
+        cli.describe_interval
+    );
+
+    let mut clock: Option<(Instant, f64)> = None;
+    for frame in frame_iter {
+        let frame = frame?;
+
+        // Keep pace with the video so lines arrive as the scenes play
+        let (started, first) = *clock.get_or_insert((Instant::now(), frame.timestamp));
+        let due = Duration::from_secs_f64(((frame.timestamp - first) / cli.speed).max(0.0));
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            sleep(wait).await;
+        }
+
+        if let Some(line) = describer.observe(&frame) {
+            println!("{}", line);
+        }
+    }
+
+    println!("End.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(gray: u8, timestamp: f64) -> VideoFrame {
+        VideoFrame {
+            data: vec![gray; 64 * 36 * 3],
+            width: 64,
+            height: 36,
+            timestamp,
+            frame_number: (timestamp * 10.0) as u64,
+        }
+    }
+
+    #[test]
+    fn test_describer_reports_scenes_and_summaries() {
+        let mut describer = Describer::new(2.0);
+        let mut lines = Vec::new();
+        for tick in 0..60 {
+            let timestamp = tick as f64 / 10.0;
+            let gray = if tick < 30 { 20 } else { 230 };
+            lines.extend(describer.observe(&frame(gray, timestamp)));
+        }
+
+        assert_eq!(
+            lines,
+            vec![
+                "[00:00] Start, very dark.",
+                "[00:02] Still, very dark.",
+                "[00:03] Scene change, very bright.",
+                "[00:05] Still, very bright.",
+            ]
+        );
+        assert_eq!(format_time(3725.0), "[1:02:05]");
+    }
+}
@@ -0,0 +1,340 @@
+//! Self-test and capability report
+//!
+//! `ascii-player doctor` prints what the player detected about the terminal
+//! and its environment, in a form that can be pasted into a bug report.
+//! With `--render-test` it first plays a built-in pattern for
+//! [`RENDER_TEST_DURATION`]: a truecolor hue sweep, block and shade glyphs,
+//! braille patterns and a marker moving one column per frame, so a broken
+//! color depth, missing glyphs or uneven timing show up on screen. How long
+//! each frame took to draw and how late the frame timer fired go into the
+//! report.
+
+use crate::config::Config;
+use crate::converter::AsciiFrame;
+use crate::renderer::Renderer;
+use crate::telnet::ColorDepth;
+use crate::termcaps::TermCaps;
+use anyhow::Result;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// How long `--render-test` plays its pattern
+pub const RENDER_TEST_DURATION: Duration = Duration::from_secs(2);
+
+/// Frame rate the render test aims for
+const RENDER_TEST_FPS: f64 = 30.0;
+
+/// Shades and blocks of the second band
+const BLOCK_GLYPHS: &[char] = &[' ', '░', '▒', '▓', '█', '▀', '▄', '▌', '▐'];
+
+/// First of the 256 braille patterns
+const BRAILLE_BASE: u32 = 0x2800;
+
+/// Fully saturated color at `hue` (0.0-1.0) around the color wheel
+fn hue_color(hue: f64) -> (u8, u8, u8) {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let rising = ((sector % 1.0) * 255.0) as u8;
+    let falling = 255 - rising;
+    match sector as u8 {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, falling),
+    }
+}
+
+/// Frame `n` of the test pattern: four bands from top to bottom of hue
+/// sweep, block glyphs in grays, braille patterns and the timing marker
+pub fn test_pattern(width: u16, height: u16, n: u64) -> AsciiFrame {
+    let (columns, rows) = (width.max(1) as u64, height.max(1) as u64);
+    let mut characters = Vec::with_capacity((columns * rows) as usize);
+    let mut fg_colors = Vec::with_capacity((columns * rows) as usize);
+    for y in 0..rows {
+        let band = y * 4 / rows;
+        for x in 0..columns {
+            let (character, color) = match band {
+                0 => ('█', hue_color((x + n) as f64 / columns as f64)),
+                1 => {
+                    let gray = (x * 255 / columns) as u8;
+                    let glyph = BLOCK_GLYPHS[((x + y + n) % BLOCK_GLYPHS.len() as u64) as usize];
+                    (glyph, (gray, gray, gray))
+                }
+                2 => {
+                    let pattern = ((x + y * 7 + n) % 256) as u32;
+                    let glyph = char::from_u32(BRAILLE_BASE + pattern).unwrap_or(' ');
+                    (glyph, (0, 200, 255))
+                }
+                _ if x == n % columns => ('█', (255, 255, 255)),
+                _ => ('·', (80, 80, 80)),
+            };
+            characters.push(character);
+            fg_colors.push(color);
+        }
+    }
+    let bg_colors = vec![(0, 0, 0); characters.len()];
+    AsciiFrame::new(columns as u16, rows as u16, characters, fg_colors)
+        .with_background(bg_colors)
+        .with_timestamp(n as f64 / RENDER_TEST_FPS, n)
+}
+
+/// Measurements of a render test
+#[derive(Debug, Clone, Default)]
+pub struct RenderTest {
+    /// Grid the pattern was drawn at
+    pub size: (u16, u16),
+    pub elapsed: Duration,
+    /// Time taken to draw each frame
+    pub frame_times: Vec<Duration>,
+    /// How late each frame started after it was due
+    pub lateness: Vec<Duration>,
+    /// Bytes written to the terminal
+    pub bytes: u64,
+}
+
+impl RenderTest {
+    pub fn frames(&self) -> usize {
+        self.frame_times.len()
+    }
+
+    /// Frames drawn per second
+    pub fn fps(&self) -> f64 {
+        self.frames() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Shortest, mean and longest of `durations`, in milliseconds
+    fn spread(durations: &[Duration]) -> (f64, f64, f64) {
+        let ms: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        if ms.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+        let min = ms.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = ms.iter().copied().fold(0.0, f64::max);
+        (min, ms.iter().sum::<f64>() / ms.len() as f64, max)
+    }
+}
+
+/// Play the test pattern on the terminal for [`RENDER_TEST_DURATION`] and
+/// measure it; the terminal is restored before returning
+pub fn render_test(caps: &TermCaps) -> Result<RenderTest> {
+    let mut renderer = Renderer::new(false, true)?
+        .with_color_depth(caps.color_depth)
+        .with_synchronized_output(caps.synchronized_output);
+    renderer.init()?;
+    let result = play_pattern(&mut renderer);
+    renderer.cleanup()?;
+    result
+}
+
+fn play_pattern(renderer: &mut Renderer) -> Result<RenderTest> {
+    let (width, height) = renderer.dimensions();
+    // The last row holds the status line
+    let size = (width, height.saturating_sub(1).max(1));
+    let frame_delay = Duration::from_secs_f64(1.0 / RENDER_TEST_FPS);
+    let frames = (RENDER_TEST_DURATION.as_secs_f64() * RENDER_TEST_FPS) as u64;
+    let mut test = RenderTest {
+        size,
+        ..RenderTest::default()
+    };
+
+    let start = Instant::now();
+    for n in 0..frames {
+        let due = start + frame_delay * n as u32;
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        let began = Instant::now();
+        test.lateness.push(began.saturating_duration_since(due));
+
+        let frame = test_pattern(size.0, size.1, n);
+        let status = format!(
+            "ascii-player doctor: render test {}/{} (truecolor, blocks, braille, timing)",
+            n + 1,
+            frames
+        );
+        renderer.render_frame_with_status(&frame, &status)?;
+        test.frame_times.push(began.elapsed());
+    }
+    test.elapsed = start.elapsed();
+    test.bytes = renderer.bytes_written();
+    Ok(test)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Name of the default audio output device
+#[cfg(feature = "audio")]
+fn audio_output() -> String {
+    use cpal::traits::{DeviceTrait, HostTrait};
+    match cpal::default_host().default_output_device() {
+        Some(device) => device
+            .name()
+            .unwrap_or_else(|_| "unnamed device".to_string()),
+        None => "no output device".to_string(),
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn audio_output() -> String {
+    "built without the audio feature".to_string()
+}
+
+/// The capability report, with the render test results if one was run.
+/// Environment variables are read with `var`.
+pub fn report(
+    caps: &TermCaps,
+    var: impl Fn(&str) -> Option<String>,
+    render: Option<&RenderTest>,
+) -> String {
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "ascii-player {} ({} {})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    let _ = writeln!(report, "\nEnvironment:");
+    for name in [
+        "TERM",
+        "TERM_PROGRAM",
+        "COLORTERM",
+        "LANG",
+        "LC_ALL",
+        "TMUX",
+    ] {
+        let value = var(name).unwrap_or_else(|| "(unset)".to_string());
+        let _ = writeln!(report, "  {:<22} {}", name, value);
+    }
+
+    let _ = writeln!(report, "\nTerminal:");
+    let depth = match caps.color_depth {
+        ColorDepth::Mono => "none",
+        ColorDepth::Ansi256 => "256 colors",
+        ColorDepth::TrueColor => "truecolor",
+    };
+    let cell = caps
+        .cell_pixels
+        .map_or("unknown".to_string(), |(w, h)| format!("{}x{} px", w, h));
+    let rows = [
+        ("Colors", depth.to_string()),
+        ("Unicode", yes_no(caps.unicode).to_string()),
+        (
+            "Synchronized output",
+            yes_no(caps.synchronized_output).to_string(),
+        ),
+        ("Sixel", yes_no(caps.sixel).to_string()),
+        ("Kitty graphics", yes_no(caps.kitty_graphics).to_string()),
+        ("Cell size", cell),
+    ];
+    for (label, value) in rows {
+        let _ = writeln!(report, "  {:<22} {}", label, value);
+    }
+
+    let _ = writeln!(report, "\nPlayer:");
+    let format = ffmpeg_next::format::version();
+    let libavformat = format!(
+        "{}.{}.{}",
+        format >> 16,
+        (format >> 8) & 0xff,
+        format & 0xff
+    );
+    let config = match Config::path() {
+        Some(path) if path.exists() => path.display().to_string(),
+        Some(path) => format!("{} (not created)", path.display()),
+        None => "unknown location".to_string(),
+    };
+    for (label, value) in [
+        ("libavformat", libavformat),
+        ("Audio output", audio_output()),
+        ("Config file", config),
+    ] {
+        let _ = writeln!(report, "  {:<22} {}", label, value);
+    }
+
+    if let Some(test) = render {
+        let _ = writeln!(
+            report,
+            "\nRender test ({}x{} cells, {:.0} fps target):",
+            test.size.0, test.size.1, RENDER_TEST_FPS
+        );
+        let (min, mean, max) = RenderTest::spread(&test.frame_times);
+        let (_, late_mean, late_max) = RenderTest::spread(&test.lateness);
+        let per_frame = test.bytes / test.frames().max(1) as u64;
+        let rows = [
+            (
+                "Frames drawn",
+                format!("{} ({:.1} fps)", test.frames(), test.fps()),
+            ),
+            (
+                "Draw time",
+                format!("min {:.1} ms, mean {:.1} ms, max {:.1} ms", min, mean, max),
+            ),
+            (
+                "Timer lateness",
+                format!("mean {:.1} ms, max {:.1} ms", late_mean, late_max),
+            ),
+            (
+                "Output",
+                format!("{:.1} KiB/frame", per_frame as f64 / 1024.0),
+            ),
+        ];
+        for (label, value) in rows {
+            let _ = writeln!(report, "  {:<22} {}", label, value);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_bands() {
+        let frame = test_pattern(40, 8, 3);
+        assert_eq!(frame.characters.len(), 40 * 8);
+        let row = |y: usize| &frame.characters[y * 40..(y + 1) * 40];
+
+        assert!(row(0).iter().all(|&c| c == '█'));
+        assert!(row(2).iter().all(|c| BLOCK_GLYPHS.contains(c)));
+        assert!(row(4)
+            .iter()
+            .all(|&c| ('\u{2800}'..='\u{28ff}').contains(&c)));
+        // The marker is at column n of the bottom band
+        assert_eq!(row(7)[3], '█');
+        assert_eq!(row(7).iter().filter(|&&c| c == '█').count(), 1);
+
+        // The hue sweep moves with the frame number
+        assert_ne!(frame.fg_colors[0], test_pattern(40, 8, 4).fg_colors[0]);
+    }
+
+    #[test]
+    fn test_report_includes_render_results() {
+        let caps = TermCaps::default();
+        let var = |name: &str| (name == "TERM").then(|| "xterm-kitty".to_string());
+        let test = RenderTest {
+            size: (80, 23),
+            elapsed: Duration::from_secs(2),
+            frame_times: vec![Duration::from_millis(2), Duration::from_millis(4)],
+            lateness: vec![Duration::ZERO, Duration::from_millis(1)],
+            bytes: 4096,
+        };
+
+        let text = report(&caps, var, Some(&test));
+        assert!(text.contains("xterm-kitty"));
+        assert!(text.contains("truecolor"));
+        assert!(text.contains("min 2.0 ms, mean 3.0 ms, max 4.0 ms"));
+        assert!(text.contains("2.0 KiB/frame"));
+
+        assert!(!report(&caps, var, None).contains("Render test"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Cut lists for skipping parts of a video
+//!
+//! Two formats are accepted:
+//!
+//! * A JSON list of ranges to remove, either bare or wrapped in an object:
+//!   `[{"start": 12.0, "end": 30.5}]` or `{"cuts": [...]}`.
+//! * An mpv-style EDL (`# mpv EDL v0` header) whose `file,start,length`
+//!   entries list the segments to *keep*; everything between them is cut.
+//!   File names are ignored since the cuts apply to the file being played.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// A time range in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct CutRange {
+    pub start: f64,
+    /// End of the range; `f64::INFINITY` cuts through to the end of the file
+    pub end: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonCutList {
+    Bare(Vec<CutRange>),
+    Wrapped { cuts: Vec<CutRange> },
+}
+
+/// Sorted, non-overlapping list of ranges to skip
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CutList {
+    ranges: Vec<CutRange>,
+}
+
+impl CutList {
+    /// Build a cut list, merging overlapping ranges
+    pub fn new(mut ranges: Vec<CutRange>) -> Result<Self> {
+        for range in &ranges {
+            if range.start.is_nan() || range.end.is_nan() || range.start < 0.0 {
+                return Err(anyhow!("Invalid cut range {}-{}", range.start, range.end));
+            }
+            if range.end <= range.start {
+                return Err(anyhow!(
+                    "Cut range end ({}) must be after its start ({})",
+                    range.end,
+                    range.start
+                ));
+            }
+        }
+
+        ranges.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut merged: Vec<CutRange> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        Ok(Self { ranges: merged })
+    }
+
+    /// Load a cut list from a JSON or mpv EDL file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read cut list '{}': {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| anyhow!("Invalid cut list '{}': {}", path.display(), e))
+    }
+
+    /// Parse cut list contents, detecting the format
+    pub fn parse(contents: &str) -> Result<Self> {
+        if contents.trim_start().starts_with("# mpv EDL") {
+            return Self::parse_mpv_edl(contents);
+        }
+
+        let ranges = match serde_json::from_str::<JsonCutList>(contents)? {
+            JsonCutList::Bare(ranges) | JsonCutList::Wrapped { cuts: ranges } => ranges,
+        };
+        Self::new(ranges)
+    }
+
+    /// Parse an mpv EDL: kept segments become the gaps of the cut list
+    fn parse_mpv_edl(contents: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // file,start,length (optionally with named start=/length= fields)
+            let mut positional = Vec::new();
+            let mut start = None;
+            let mut length = None;
+            for field in line.split(',').skip(1) {
+                match field.split_once('=') {
+                    Some(("start", value)) => start = Some(value.to_string()),
+                    Some(("length", value)) => length = Some(value.to_string()),
+                    Some(_) => {}
+                    None => positional.push(field.to_string()),
+                }
+            }
+            let start = start.or_else(|| positional.first().cloned());
+            let length = length.or_else(|| positional.get(1).cloned());
+
+            let parse = |value: Option<String>, what: &str| -> Result<f64> {
+                value
+                    .ok_or_else(|| anyhow!("line {}: missing {}", number + 1, what))?
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("line {}: invalid {}", number + 1, what))
+            };
+
+            let start = parse(start, "start")?;
+            let length = parse(length, "length")?;
+            segments.push(CutRange {
+                start,
+                end: start + length,
+            });
+        }
+
+        if segments.is_empty() {
+            return Err(anyhow!("EDL has no segments"));
+        }
+
+        segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut cuts = Vec::new();
+        let mut position = 0.0;
+        for segment in &segments {
+            if segment.start > position {
+                cuts.push(CutRange {
+                    start: position,
+                    end: segment.start,
+                });
+            }
+            position = position.max(segment.end);
+        }
+        cuts.push(CutRange {
+            start: position,
+            end: f64::INFINITY,
+        });
+
+        Self::new(cuts)
+    }
+
+    /// Ranges to skip, sorted by start time
+    pub fn ranges(&self) -> &[CutRange] {
+        &self.ranges
+    }
+
+    /// If `timestamp` falls inside a cut, the time playback should resume at
+    pub fn resume_point(&self, timestamp: f64) -> Option<f64> {
+        self.ranges
+            .iter()
+            .find(|range| timestamp >= range.start && timestamp < range.end)
+            .map(|range| range.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_and_merge() {
+        let cuts = CutList::parse(
+            r#"[{"start": 5, "end": 8}, {"start": 1, "end": 2}, {"start": 7, "end": 10}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cuts.ranges(),
+            &[
+                CutRange {
+                    start: 1.0,
+                    end: 2.0
+                },
+                CutRange {
+                    start: 5.0,
+                    end: 10.0
+                }
+            ]
+        );
+
+        let wrapped = CutList::parse(r#"{"cuts": [{"start": 1, "end": 2}]}"#).unwrap();
+        assert_eq!(wrapped.ranges().len(), 1);
+    }
+
+    #[test]
+    fn test_resume_point() {
+        let cuts = CutList::parse(r#"[{"start": 5, "end": 8}]"#).unwrap();
+        assert_eq!(cuts.resume_point(4.9), None);
+        assert_eq!(cuts.resume_point(5.0), Some(8.0));
+        assert_eq!(cuts.resume_point(8.0), None);
+    }
+
+    #[test]
+    fn test_parse_mpv_edl_keeps_segments() {
+        let edl = "# mpv EDL v0\nmovie.mkv,10,5\nmovie.mkv,start=30,length=10\n";
+        let cuts = CutList::parse(edl).unwrap();
+        assert_eq!(cuts.resume_point(0.0), Some(10.0));
+        assert_eq!(cuts.resume_point(12.0), None);
+        assert_eq!(cuts.resume_point(20.0), Some(30.0));
+        assert_eq!(cuts.resume_point(45.0), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_rejects_invalid_ranges() {
+        assert!(CutList::parse(r#"[{"start": 5, "end": 5}]"#).is_err());
+        assert!(CutList::parse(r#"[{"start": -1, "end": 5}]"#).is_err());
+        assert!(CutList::parse("not json").is_err());
+    }
+}
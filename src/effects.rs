@@ -0,0 +1,338 @@
+//! Ordered chains of picture effects
+//!
+//! Filters such as black bar cropping, stabilization, color reduction and
+//! dithering each have their own options, which makes a favorite
+//! combination tedious to retype. An effects chain names them in one list,
+//! e.g. `["autocrop", "stabilize", "eq:contrast=1.1", "dither:floyd"]`,
+//! given with `--effect`, saved in the config file as the default chain or
+//! as a named profile for `--profile`, and listed with
+//! `ascii-player effects --list`.
+//!
+//! Every effect is `NAME` or `NAME:ARGS` and belongs to a stage. Decoder
+//! effects work on whole frames on the decode threads, autocrop always
+//! before stabilize. Converter effects adjust the conversion settings after
+//! the command line options, in chain order, so a later effect overrides an
+//! earlier one and color reductions apply in the order they are listed.
+
+use crate::config::Config;
+use crate::converter::ConversionConfig;
+use anyhow::{anyhow, bail, Result};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Where in the pipeline an effect works
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectStage {
+    /// On decoded frames, before conversion
+    Decoder,
+    /// On the conversion settings
+    Converter,
+}
+
+impl fmt::Display for EffectStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EffectStage::Decoder => "decoder",
+            EffectStage::Converter => "converter",
+        })
+    }
+}
+
+/// An entry of the effect registry
+#[derive(Debug)]
+pub struct EffectInfo {
+    pub name: &'static str,
+    pub stage: EffectStage,
+    /// Arguments after the colon, as shown by `effects --list`
+    pub args: &'static str,
+    pub about: &'static str,
+}
+
+/// Every effect a chain can name
+pub const EFFECTS: &[EffectInfo] = &[
+    EffectInfo {
+        name: "autocrop",
+        stage: EffectStage::Decoder,
+        args: "[threshold=LUMA,frames=N]",
+        about: "Crop black bars found in early frames (--autocrop)",
+    },
+    EffectInfo {
+        name: "stabilize",
+        stage: EffectStage::Decoder,
+        args: "",
+        about: "Cancel camera shake (--stabilize)",
+    },
+    EffectInfo {
+        name: "eq",
+        stage: EffectStage::Converter,
+        args: "brightness=B,contrast=C",
+        about: "Adjust brightness (-1.0 to 1.0) and contrast (0.0 to 2.0)",
+    },
+    EffectInfo {
+        name: "posterize",
+        stage: EffectStage::Converter,
+        args: "LEVELS",
+        about: "Reduce colors to LEVELS per channel (--posterize)",
+    },
+    EffectInfo {
+        name: "dither",
+        stage: EffectStage::Converter,
+        args: "fs|floyd|ordered|ordered4",
+        about: "Dither characters and reduced colors (--dither)",
+    },
+    EffectInfo {
+        name: "style",
+        stage: EffectStage::Converter,
+        args: "normal|motion|heatmap|edges",
+        about: "Process the picture before characters are chosen (--style)",
+    },
+    EffectInfo {
+        name: "scaler",
+        stage: EffectStage::Converter,
+        args: "nearest|bilinear|lanczos|area",
+        about: "Filter shrinking the picture to the grid (--scaler)",
+    },
+    EffectInfo {
+        name: "sharpen",
+        stage: EffectStage::Converter,
+        args: "[AMOUNT]",
+        about: "Unsharp mask after scaling, 0.5 by default (--sharpen)",
+    },
+    EffectInfo {
+        name: "cvd",
+        stage: EffectStage::Converter,
+        args: "simulate:TYPE|daltonize[:TYPE]",
+        about: "Simulate or compensate for color blindness (--cvd)",
+    },
+];
+
+/// Look up an effect of the registry by name
+pub fn effect_info(name: &str) -> Option<&'static EffectInfo> {
+    EFFECTS.iter().find(|info| info.name == name)
+}
+
+/// One effect of a chain with its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    Autocrop {
+        threshold: Option<u8>,
+        frames: Option<usize>,
+    },
+    Stabilize,
+    /// A converter effect, as `key=value` settings for
+    /// [`ConversionConfig::apply_overrides`]
+    Convert {
+        name: &'static str,
+        overrides: String,
+    },
+}
+
+impl FromStr for Effect {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (name, args) = match spec.split_once(':') {
+            Some((name, args)) => (name.trim(), Some(args.trim())),
+            None => (spec, None),
+        };
+        let info = effect_info(name).ok_or_else(|| {
+            anyhow!(
+                "Unknown effect '{}' (see `ascii-player effects --list`)",
+                name
+            )
+        })?;
+        let required = || args.ok_or_else(|| anyhow!("Effect '{}' needs {}", name, info.args));
+
+        let overrides = match info.name {
+            "autocrop" => return parse_autocrop(args),
+            "stabilize" if args.is_some() => bail!("Effect 'stabilize' takes no arguments"),
+            "stabilize" => return Ok(Effect::Stabilize),
+            "eq" => {
+                let args = required()?;
+                for pair in args.split(',') {
+                    let key = pair.split_once('=').map_or(pair, |(key, _)| key).trim();
+                    if !matches!(key, "brightness" | "contrast") {
+                        bail!("Effect 'eq' sets brightness and contrast, not '{}'", key);
+                    }
+                }
+                args.to_string()
+            }
+            "dither" => match required()? {
+                "floyd" => "dither=fs".to_string(),
+                mode => format!("dither={}", mode),
+            },
+            "sharpen" => format!("sharpen={}", args.unwrap_or("0.5")),
+            name => format!("{}={}", name, required()?),
+        };
+
+        // Reject bad values now rather than when playback starts
+        ConversionConfig::default()
+            .apply_overrides(&overrides)
+            .map_err(|e| anyhow!("Invalid effect '{}': {}", spec, e))?;
+        Ok(Effect::Convert {
+            name: info.name,
+            overrides,
+        })
+    }
+}
+
+fn parse_autocrop(args: Option<&str>) -> Result<Effect> {
+    let (mut threshold, mut frames) = (None, None);
+    for pair in args.into_iter().flat_map(|args| args.split(',')) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected key=value in autocrop, got '{}'", pair))?;
+        let (key, value) = (key.trim(), value.trim());
+        let invalid = || anyhow!("Invalid number for autocrop {}: '{}'", key, value);
+        match key {
+            "threshold" => threshold = Some(value.parse().map_err(|_| invalid())?),
+            "frames" => match value.parse() {
+                Ok(0) | Err(_) => return Err(invalid()),
+                Ok(count) => frames = Some(count),
+            },
+            _ => bail!("Effect 'autocrop' has no setting '{}'", key),
+        }
+    }
+    Ok(Effect::Autocrop { threshold, frames })
+}
+
+/// Effects in the order they were listed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EffectChain {
+    effects: Vec<Effect>,
+}
+
+impl EffectChain {
+    /// Parse a chain from effect specs such as `eq:contrast=1.1`
+    pub fn parse<S: AsRef<str>>(specs: &[S]) -> Result<Self> {
+        let effects = specs
+            .iter()
+            .map(|spec| spec.as_ref().parse())
+            .collect::<Result<Vec<Effect>>>()?;
+        Ok(Self { effects })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Black bar threshold and sample frames of an `autocrop` effect, each
+    /// None where it keeps the default
+    pub fn autocrop(&self) -> Option<(Option<u8>, Option<usize>)> {
+        self.effects.iter().rev().find_map(|effect| match *effect {
+            Effect::Autocrop { threshold, frames } => Some((threshold, frames)),
+            _ => None,
+        })
+    }
+
+    /// Whether the chain stabilizes frames
+    pub fn stabilize(&self) -> bool {
+        self.effects.contains(&Effect::Stabilize)
+    }
+
+    /// Apply the converter effects to `conversion`, in order
+    pub fn apply(&self, conversion: &mut ConversionConfig) -> Result<()> {
+        for effect in &self.effects {
+            if let Effect::Convert { overrides, .. } = effect {
+                conversion.apply_overrides(overrides)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The effect registry, the saved profiles and the default chain, as
+/// printed by `ascii-player effects --list`
+pub fn list_effects(config: &Config) -> String {
+    let mut output = String::from("Effects (NAME or NAME:ARGS):\n");
+    for info in EFFECTS {
+        let usage = match info.args {
+            "" => info.name.to_string(),
+            args => format!("{}:{}", info.name, args),
+        };
+        let _ = writeln!(output, "  {:<40} {:<10} {}", usage, info.stage, info.about);
+    }
+
+    if !config.profiles.is_empty() {
+        output.push_str("\nProfiles (--profile NAME):\n");
+        for (name, effects) in &config.profiles {
+            let _ = writeln!(output, "  {:<16} {}", name, effects.join(" "));
+        }
+    }
+    if !config.effects.is_empty() {
+        let _ = writeln!(output, "\nDefault chain: {}", config.effects.join(" "));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Dither, Style};
+
+    #[test]
+    fn test_chain_applies_converter_effects_in_order() {
+        let chain = EffectChain::parse(&[
+            "autocrop",
+            "stabilize",
+            "eq:contrast=1.1",
+            "dither:floyd",
+            "style:edges",
+            "eq:brightness=0.2",
+        ])
+        .unwrap();
+        assert_eq!(chain.autocrop(), Some((None, None)));
+        assert!(chain.stabilize());
+
+        let mut conversion = ConversionConfig::default();
+        chain.apply(&mut conversion).unwrap();
+        assert_eq!(conversion.contrast, 1.1);
+        assert_eq!(conversion.brightness, 0.2);
+        assert_eq!(conversion.dither, Dither::FloydSteinberg);
+        assert_eq!(conversion.style, Style::Edges);
+    }
+
+    #[test]
+    fn test_effect_arguments() {
+        assert_eq!(
+            "autocrop:threshold=24,frames=10".parse::<Effect>().unwrap(),
+            Effect::Autocrop {
+                threshold: Some(24),
+                frames: Some(10)
+            }
+        );
+        assert!(matches!(
+            "sharpen".parse::<Effect>().unwrap(),
+            Effect::Convert { ref overrides, .. } if overrides == "sharpen=0.5"
+        ));
+
+        for bad in [
+            "blur",
+            "eq",
+            "eq:ramp=abc",
+            "dither:sideways",
+            "posterize:lots",
+            "stabilize:strong",
+            "autocrop:frames=0",
+        ] {
+            assert!(bad.parse::<Effect>().is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_list_shows_registry_and_profiles() {
+        let mut config = Config::default();
+        config.profiles.insert(
+            "vhs".to_string(),
+            vec!["stabilize".to_string(), "posterize:4".to_string()],
+        );
+        let listing = list_effects(&config);
+        for info in EFFECTS {
+            assert!(listing.contains(info.name));
+        }
+        assert!(listing.contains("vhs"));
+        assert!(listing.contains("stabilize posterize:4"));
+        assert!(!listing.contains("Default chain"));
+    }
+}
@@ -0,0 +1,392 @@
+//! Re-encoding the ASCII rendering into a video file
+//!
+//! `--export clip.mp4` (or `.webm`, `.mkv`, `.mov`, `.m4v`) draws every
+//! converted frame with the bundled bitmap font (see [`crate::raster`]) and
+//! encodes the pictures with the container's usual video codec, H.264 for
+//! MP4 and VP9 for WebM, at the source frame rate. Each picture keeps the
+//! time of the frame it shows, so the video runs in step with the original.
+//!
+//! Unless `--mute` is given, the original audio track is copied over as it
+//! is, cut to the same time range, when the container can hold its codec.
+//! Audio is left out with `--edl`, since the cuts would put it out of step.
+
+use crate::converter::AsciiFrame;
+use crate::raster::{render_image, CELL_HEIGHT, CELL_WIDTH};
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use log::{debug, warn};
+use std::path::Path;
+
+/// Video containers `--export` re-encodes into, by extension
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "webm"];
+
+/// Constant quality asked of the encoder; text needs more than the
+/// encoders' defaults to keep glyph edges clean
+const CRF: &str = "20";
+
+/// Encodes rendered ASCII frames into a video file
+pub struct VideoWriter {
+    output: ffmpeg::format::context::Output,
+    encoder: ffmpeg::encoder::Video,
+    /// Output stream of the video and its time base after the header
+    stream_index: usize,
+    stream_time_base: ffmpeg::Rational,
+    encoder_time_base: ffmpeg::Rational,
+    /// Picture size the encoder was opened with
+    size: (u32, u32),
+    /// RGB to YUV conversion for pictures of the size noted
+    scaler: Option<((u32, u32), ffmpeg::software::scaling::Context)>,
+    audio: Option<AudioCopy>,
+    /// Timestamp of the first frame, which becomes zero
+    start: Option<f64>,
+    last_pts: Option<i64>,
+    /// Time of the last frame written, relative to the first
+    last_time: f64,
+    frames: u64,
+    path: String,
+}
+
+impl VideoWriter {
+    /// Create `path` for frames of `width` x `height` cells at `fps`. With
+    /// `audio_from` as `(input, start_time, end_time)`, the audio of `input`
+    /// in that range (seconds) is copied too.
+    pub fn create(
+        path: &Path,
+        (width, height): (u16, u16),
+        fps: f64,
+        audio_from: Option<(&Path, Option<f64>, Option<f64>)>,
+    ) -> Result<Self> {
+        let _ = ffmpeg::init();
+        let display = path.display().to_string();
+        let mut output = ffmpeg::format::output(&path)
+            .map_err(|e| anyhow!("Failed to create '{}': {}", display, e))?;
+        let size = picture_size(width, height);
+
+        let codec_id = output.format().codec(&path, ffmpeg::media::Type::Video);
+        let codec = ffmpeg::encoder::find(codec_id)
+            .ok_or_else(|| anyhow!("No encoder for {:?} available for '{}'", codec_id, display))?;
+        let global_header = output
+            .format()
+            .flags()
+            .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+        let frame_rate = ffmpeg::Rational::from(if fps > 0.0 { fps } else { 30.0 });
+        let encoder_time_base = frame_rate.invert();
+        let mut context = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .map_err(|e| anyhow!("Failed to set up the video encoder: {}", e))?;
+        context.set_width(size.0);
+        context.set_height(size.1);
+        context.set_format(ffmpeg::format::Pixel::YUV420P);
+        context.set_time_base(encoder_time_base);
+        context.set_frame_rate(Some(frame_rate));
+        if global_header {
+            context.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+        let mut options = ffmpeg::Dictionary::new();
+        options.set("crf", CRF);
+        let encoder = context
+            .open_with(options)
+            .map_err(|e| anyhow!("Failed to open the {:?} encoder: {}", codec_id, e))?;
+
+        let stream_index = {
+            let mut stream = output
+                .add_stream(codec)
+                .map_err(|e| anyhow!("Failed to add stream: {}", e))?;
+            stream.set_parameters(&encoder);
+            stream.index()
+        };
+
+        let mut audio = match audio_from {
+            Some((input, start, end)) => AudioCopy::open(input, &mut output, start, end)?,
+            None => None,
+        };
+
+        output
+            .write_header()
+            .map_err(|e| anyhow!("Failed to write '{}': {}", display, e))?;
+        let stream_time_base = output
+            .stream(stream_index)
+            .map(|stream| stream.time_base())
+            .unwrap_or(encoder_time_base);
+        if let Some(audio) = audio.as_mut() {
+            audio.output_time_base = output
+                .stream(audio.output_index)
+                .map(|stream| stream.time_base())
+                .unwrap_or(audio.input_time_base);
+        }
+
+        debug!(
+            "Encoding {}x{} {:?} at {:.3} fps to {}",
+            size.0, size.1, codec_id, fps, display
+        );
+        Ok(Self {
+            output,
+            encoder,
+            stream_index,
+            stream_time_base,
+            encoder_time_base,
+            size,
+            scaler: None,
+            audio,
+            start: None,
+            last_pts: None,
+            last_time: 0.0,
+            frames: 0,
+            path: display,
+        })
+    }
+
+    /// Draw and encode one frame at its time since the first frame
+    pub fn write_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let start = *self.start.get_or_insert(frame.timestamp);
+        let time = (frame.timestamp - start).max(0.0);
+        let pts = (time / f64::from(self.encoder_time_base)).round() as i64;
+        // Two frames in one tick of the time base: the later one is dropped
+        if self.last_pts.is_some_and(|last| pts <= last) {
+            return Ok(());
+        }
+
+        let image = render_image(frame);
+        let (width, height) = image.dimensions();
+        let mut rgb = ffmpeg::frame::Video::new(ffmpeg::format::Pixel::RGB24, width, height);
+        let stride = rgb.stride(0);
+        let row_bytes = width as usize * 3;
+        for (row, pixels) in image.as_raw().chunks_exact(row_bytes).enumerate() {
+            rgb.data_mut(0)[row * stride..row * stride + row_bytes].copy_from_slice(pixels);
+        }
+
+        let (target_width, target_height) = self.size;
+        if self.scaler.as_ref().map(|(size, _)| *size) != Some((width, height)) {
+            let scaler = ffmpeg::software::scaling::Context::get(
+                ffmpeg::format::Pixel::RGB24,
+                width,
+                height,
+                ffmpeg::format::Pixel::YUV420P,
+                target_width,
+                target_height,
+                ffmpeg::software::scaling::Flags::POINT,
+            )
+            .map_err(|e| anyhow!("Failed to create scaling context: {}", e))?;
+            self.scaler = Some(((width, height), scaler));
+        }
+        let mut yuv =
+            ffmpeg::frame::Video::new(ffmpeg::format::Pixel::YUV420P, target_width, target_height);
+        if let Some((_, ref mut scaler)) = self.scaler {
+            scaler
+                .run(&rgb, &mut yuv)
+                .map_err(|e| anyhow!("Failed to convert frame: {}", e))?;
+        }
+        yuv.set_pts(Some(pts));
+
+        // Audio up to this frame goes in first, keeping the file interleaved
+        if let Some(ref mut audio) = self.audio {
+            audio.copy_until(start, time, &mut self.output)?;
+        }
+        self.encoder
+            .send_frame(&yuv)
+            .map_err(|e| anyhow!("Failed to encode frame: {}", e))?;
+        self.write_packets()?;
+
+        self.last_pts = Some(pts);
+        self.last_time = time;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Number of frames encoded
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Flush the encoder, copy the rest of the audio and close the file
+    pub fn finish(mut self) -> Result<()> {
+        self.encoder
+            .send_eof()
+            .map_err(|e| anyhow!("Failed to finish encoding: {}", e))?;
+        self.write_packets()?;
+        if let (Some(audio), Some(start)) = (self.audio.as_mut(), self.start) {
+            let end = self.last_time + f64::from(self.encoder_time_base);
+            audio.copy_until(start, end, &mut self.output)?;
+        }
+        self.output
+            .write_trailer()
+            .map_err(|e| anyhow!("Failed to finish '{}': {}", self.path, e))?;
+        Ok(())
+    }
+
+    /// Write out the packets the encoder has ready
+    fn write_packets(&mut self) -> Result<()> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(self.stream_index);
+            packet.rescale_ts(self.encoder_time_base, self.stream_time_base);
+            packet
+                .write_interleaved(&mut self.output)
+                .map_err(|e| anyhow!("Failed to write '{}': {}", self.path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of the encoded picture for a frame of `width` x `height` cells.
+/// Cells are 8x16 pixels, so the size is always even as YUV 4:2:0 needs.
+pub fn picture_size(width: u16, height: u16) -> (u32, u32) {
+    (
+        width.max(1) as u32 * CELL_WIDTH,
+        height.max(1) as u32 * CELL_HEIGHT,
+    )
+}
+
+/// Copies the packets of the input's audio track into the output
+struct AudioCopy {
+    input: ffmpeg::format::context::Input,
+    input_index: usize,
+    input_time_base: ffmpeg::Rational,
+    output_index: usize,
+    output_time_base: ffmpeg::Rational,
+    end_time: Option<f64>,
+    /// Packet read past the time copied up to, written next time
+    pending: Option<(ffmpeg::Packet, f64)>,
+    done: bool,
+}
+
+impl AudioCopy {
+    /// Add an output stream for the audio of `path`, from `start_time`
+    /// (seconds) on. None when there is no audio or the container cannot
+    /// hold its codec.
+    fn open(
+        path: &Path,
+        output: &mut ffmpeg::format::context::Output,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    ) -> Result<Option<Self>> {
+        let mut input = ffmpeg::format::input(&path)
+            .map_err(|e| anyhow!("Failed to open '{}': {}", path.display(), e))?;
+        let Some(stream) = input.streams().best(ffmpeg::media::Type::Audio) else {
+            debug!("No audio track to copy");
+            return Ok(None);
+        };
+        let input_index = stream.index();
+        let input_time_base = stream.time_base();
+        let parameters = stream.parameters();
+
+        let codec_id = parameters.id();
+        // 1 if the container takes the codec, 0 if not, negative if unknown
+        let supported = unsafe {
+            ffmpeg::ffi::avformat_query_codec(
+                output.format().as_ptr(),
+                codec_id.into(),
+                ffmpeg::ffi::FF_COMPLIANCE_NORMAL as i32,
+            )
+        };
+        if supported == 0 {
+            warn!(
+                "Leaving out the audio: {} cannot hold {:?} audio",
+                output.format().name(),
+                codec_id
+            );
+            return Ok(None);
+        }
+
+        let mut output_stream = output
+            .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
+            .map_err(|e| anyhow!("Failed to add stream: {}", e))?;
+        output_stream.set_parameters(parameters);
+        // Codec tags of one container can be invalid in another
+        unsafe {
+            (*output_stream.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+        let output_index = output_stream.index();
+
+        if let Some(start) = start_time {
+            let start_ts = (start * ffmpeg::ffi::AV_TIME_BASE as f64) as i64;
+            input
+                .seek(start_ts, ..start_ts)
+                .map_err(|e| anyhow!("Failed to seek to {:.2}s: {}", start, e))?;
+        }
+
+        Ok(Some(Self {
+            input,
+            input_index,
+            input_time_base,
+            output_index,
+            output_time_base: input_time_base,
+            end_time,
+            pending: None,
+            done: false,
+        }))
+    }
+
+    /// Copy the audio before `until` seconds after `origin`, the source
+    /// time of the first video frame
+    fn copy_until(
+        &mut self,
+        origin: f64,
+        until: f64,
+        output: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        loop {
+            let (mut packet, time) = match self.pending.take() {
+                Some(pending) => pending,
+                None if self.done => return Ok(()),
+                None => {
+                    let mut packet = ffmpeg::Packet::empty();
+                    match packet.read(&mut self.input) {
+                        Ok(()) => {}
+                        Err(ffmpeg::Error::Eof) => {
+                            self.done = true;
+                            return Ok(());
+                        }
+                        Err(e) => return Err(anyhow!("Failed to read audio: {}", e)),
+                    }
+                    if packet.stream() != self.input_index {
+                        continue;
+                    }
+                    let Some(ts) = packet.pts().or(packet.dts()) else {
+                        continue;
+                    };
+                    (packet, ts as f64 * f64::from(self.input_time_base))
+                }
+            };
+
+            // Audio from before the first video frame would play over nothing
+            if time < origin {
+                continue;
+            }
+            if self.end_time.is_some_and(|end| time > end) {
+                self.done = true;
+                return Ok(());
+            }
+            if time - origin >= until {
+                self.pending = Some((packet, time));
+                return Ok(());
+            }
+
+            let shift = (origin / f64::from(self.input_time_base)).round() as i64;
+            packet.set_pts(packet.pts().map(|pts| pts - shift));
+            packet.set_dts(packet.dts().map(|dts| dts - shift));
+            packet.rescale_ts(self.input_time_base, self.output_time_base);
+            packet.set_position(-1);
+            packet.set_stream(self.output_index);
+            packet
+                .write_interleaved(output)
+                .map_err(|e| anyhow!("Failed to write audio: {}", e))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picture_size() {
+        assert_eq!(picture_size(80, 24), (640, 384));
+        // Always even, as YUV 4:2:0 needs
+        let (width, height) = picture_size(33, 7);
+        assert!(width % 2 == 0 && height % 2 == 0);
+        assert_eq!(picture_size(0, 0), (8, 16));
+    }
+}
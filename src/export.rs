@@ -0,0 +1,801 @@
+//! Exporting videos to `.ascv` recordings, NDJSON cell streams, asciinema
+//! casts, animated GIFs, self-contained HTML pages or re-encoded videos, and
+//! dumping frames as ANSI art files
+//!
+//! Long exports write a checkpoint next to the output every few seconds: the
+//! recording length at a record boundary, the timestamp of the last frame
+//! written and the totals so far. `--resume-export` truncates the recording to
+//! that length, seeks the decoder past the last frame and continues with a
+//! keyframe, so an interrupted export picks up where it stopped.
+//!
+//! Checkpoints are JSON tagged with a format name and version. Files from
+//! before the version field existed read as version 0, which has the same
+//! layout as version 1.
+//!
+//! GIFs are drawn with the bundled bitmap font (see [`crate::raster`]) for
+//! sharing renders where no terminal is at hand. GIF frame delays are whole
+//! hundredths of a second and most viewers slow down anything shorter than
+//! two, so frames are timed against the video clock in those steps and
+//! frames closer than that to the previous one are dropped.
+
+use crate::cast::CastWriter;
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::converter::{AsciiConverter, AsciiFrame};
+use crate::crop::{crop_frame, CropRect};
+use crate::decoder::{FrameIterator, TIMESTAMP_EPSILON};
+use crate::encode::{VideoWriter, VIDEO_EXTENSIONS};
+use crate::html::HtmlWriter;
+use crate::ndjson::NdjsonWriter;
+use crate::raster::render_image;
+use crate::recording::{RecordingStats, RecordingWriter};
+use crate::sequence::is_image_pattern;
+use crate::stabilize::Stabilizer;
+use crate::telnet::{encode_text, ColorDepth};
+use crate::termcaps::TermCaps;
+use anyhow::{anyhow, Context, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Wall-clock time between checkpoints
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shortest GIF frame delay viewers honor, in hundredths of a second
+const MIN_GIF_DELAY: u32 = 2;
+
+/// Color quantization speed for GIF frames, from 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
+
+/// Format name stored in every checkpoint
+pub const CHECKPOINT_FORMAT: &str = "ascii-player export checkpoint";
+/// Checkpoint version written by this release; older versions are still read
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+fn checkpoint_format() -> String {
+    CHECKPOINT_FORMAT.to_string()
+}
+
+/// Progress of an export, saved so it can be resumed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportCheckpoint {
+    #[serde(default = "checkpoint_format")]
+    pub format: String,
+    /// Layout version; 0 for checkpoints written before versioning
+    #[serde(default)]
+    pub version: u32,
+    /// Video being exported
+    pub input: PathBuf,
+    pub width: u16,
+    pub height: u16,
+    /// Length of the recording up to the last complete record
+    pub bytes: u64,
+    /// Timestamp of the last frame in the recording
+    pub timestamp: f64,
+    pub stats: RecordingStats,
+}
+
+impl ExportCheckpoint {
+    /// Progress after writing `bytes` of the recording, up to `timestamp`
+    pub fn new(
+        input: PathBuf,
+        (width, height): (u16, u16),
+        bytes: u64,
+        timestamp: f64,
+        stats: RecordingStats,
+    ) -> Self {
+        Self {
+            format: checkpoint_format(),
+            version: CHECKPOINT_VERSION,
+            input,
+            width,
+            height,
+            bytes,
+            timestamp,
+            stats,
+        }
+    }
+
+    /// Parse a checkpoint of this or an older version
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut checkpoint: Self = serde_json::from_str(contents)?;
+        if checkpoint.format != CHECKPOINT_FORMAT {
+            return Err(anyhow!("Not an export checkpoint ({})", checkpoint.format));
+        }
+        match checkpoint.version {
+            // Version 0 only lacked the format and version fields
+            0 => checkpoint.version = CHECKPOINT_VERSION,
+            CHECKPOINT_VERSION => {}
+            version => {
+                return Err(anyhow!(
+                    "Unsupported checkpoint version {} (this release reads up to {})",
+                    version,
+                    CHECKPOINT_VERSION
+                ))
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    /// Checkpoint file for the recording at `output`
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".checkpoint");
+        PathBuf::from(path)
+    }
+
+    /// Load the checkpoint for `output`, or `None` if there is none
+    pub fn load(output: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(output);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+        };
+        let checkpoint = Self::parse(&contents)
+            .with_context(|| format!("Invalid export checkpoint {}", path.display()))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Save the checkpoint for `output`, replacing the previous one atomically
+    pub fn save(&self, output: &Path) -> Result<()> {
+        let path = Self::path_for(output);
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        std::fs::write(&temp, serde_json::to_string(self)?)?;
+        std::fs::rename(&temp, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint for `output` once the export is complete
+    pub fn remove(output: &Path) -> Result<()> {
+        match std::fs::remove_file(Self::path_for(output)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the checkpoint belongs to an export with these settings
+    pub fn matches(&self, input: &Path, width: u16, height: u16) -> Result<()> {
+        if self.input != input {
+            return Err(anyhow!(
+                "Checkpoint is for {}, not {}",
+                self.input.display(),
+                input.display()
+            ));
+        }
+        if (self.width, self.height) != (width, height) {
+            return Err(anyhow!(
+                "Checkpoint is for {}x{} frames, not {}x{}",
+                self.width,
+                self.height,
+                width,
+                height
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `--export` target that selects NDJSON: `ndjson` for stdout
+pub const NDJSON_STDOUT: &str = "ndjson";
+
+/// Where an `--export` target sends NDJSON, or `None` for a recording.
+/// `Some(None)` is stdout.
+pub fn ndjson_output(path: &Path) -> Option<Option<&Path>> {
+    if path.as_os_str() == NDJSON_STDOUT {
+        Some(None)
+    } else if path.extension().is_some_and(|ext| ext == "ndjson") {
+        Some(Some(path))
+    } else {
+        None
+    }
+}
+
+/// Convert the input video into a recording at `path` as fast as it decodes,
+/// or into NDJSON when `path` is `ndjson` or ends in `.ndjson`
+pub fn export(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    if let Some(output) = ndjson_output(path) {
+        return export_ndjson(cli, config, output);
+    }
+    if has_extension(path, "cast") {
+        return export_cast(cli, config, path);
+    }
+    if has_extension(path, "gif") {
+        return export_gif(cli, config, path);
+    }
+    if has_extension(path, "html") || has_extension(path, "htm") {
+        return export_html(cli, config, path);
+    }
+    if VIDEO_EXTENSIONS
+        .iter()
+        .any(|extension| has_extension(path, extension))
+    {
+        return export_video(cli, config, path);
+    }
+
+    let mut frames = ExportFrames::open(cli, config)?;
+    let fps = frames.fps();
+    let (width, height) = frames.size();
+    let input = cli.input_path().to_path_buf();
+
+    let checkpoint = if cli.resume_export {
+        let checkpoint = ExportCheckpoint::load(path)?;
+        if checkpoint.is_none() {
+            warn!("No checkpoint for {}, starting over", path.display());
+        }
+        checkpoint
+    } else {
+        None
+    };
+
+    let mut writer = match checkpoint {
+        Some(checkpoint) => {
+            checkpoint.matches(&input, width, height)?;
+            info!(
+                "Resuming export to {} from {:.2}s",
+                path.display(),
+                checkpoint.timestamp
+            );
+            let mut file = OpenOptions::new()
+                .write(true)
+                .open(path)
+                .with_context(|| format!("Failed to open recording {}", path.display()))?;
+            file.set_len(checkpoint.bytes)?;
+            file.seek(SeekFrom::End(0))?;
+            frames.resume_after(checkpoint.timestamp)?;
+            RecordingWriter::resume(BufWriter::new(file), checkpoint.bytes, checkpoint.stats)
+        }
+        None => {
+            info!(
+                "Exporting {} to {} at {}x{}",
+                input.display(),
+                path.display(),
+                width,
+                height
+            );
+            RecordingWriter::create(path, fps)?
+        }
+    };
+
+    let mut next_checkpoint = Instant::now() + CHECKPOINT_INTERVAL;
+    for frame in frames {
+        let frame = frame?;
+        writer.write_frame(&frame)?;
+
+        if Instant::now() >= next_checkpoint {
+            ExportCheckpoint::new(
+                input.clone(),
+                (width, height),
+                writer.checkpoint()?,
+                frame.timestamp,
+                writer.stats(),
+            )
+            .save(path)?;
+            next_checkpoint = Instant::now() + CHECKPOINT_INTERVAL;
+        }
+    }
+
+    let stats = writer.finish()?;
+    ExportCheckpoint::remove(path)?;
+    let size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    println!(
+        "Wrote {} frames to {} ({} keyframes, {} deltas, {} repeated, {:.1} MiB)",
+        stats.frames,
+        path.display(),
+        stats.stored,
+        stats.deltas,
+        stats.repeated,
+        size as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Stream the converted frames as NDJSON to `output`, or stdout
+fn export_ndjson(cli: &Cli, config: &Config, output: Option<&Path>) -> Result<()> {
+    if cli.resume_export {
+        return Err(anyhow!("--resume-export only applies to .ascv recordings"));
+    }
+
+    let frames = ExportFrames::open(cli, config)?;
+    let (width, height) = frames.size();
+
+    let sink: Box<dyn Write> = match output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create {}", path.display())
+            })?))
+        }
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = NdjsonWriter::new(sink);
+    info!(
+        "Exporting {} as NDJSON at {}x{}",
+        cli.input_path().display(),
+        width,
+        height
+    );
+
+    for frame in frames {
+        writer.write_frame(&frame?)?;
+    }
+
+    // stdout carries the frames, so the summary goes to the log
+    info!("Wrote {} frames as NDJSON", writer.frames());
+    Ok(())
+}
+
+/// The input's frames as every export draws them: decoded, with black bars
+/// cropped and shake stabilized, then converted at the export size
+pub struct ExportFrames {
+    frames: FrameIterator,
+    converter: Box<dyn AsciiConverter>,
+    crop: Option<CropRect>,
+    stabilizer: Option<Stabilizer>,
+    size: (u16, u16),
+    /// Frames up to this timestamp are skipped when resuming
+    resume_after: Option<f64>,
+}
+
+impl ExportFrames {
+    /// Open the input for converting at `--width`/`--height`, 80x24 unless
+    /// given
+    pub fn open(cli: &Cli, config: &Config) -> Result<Self> {
+        Self::open_at(cli, config, cli.get_terminal_size().unwrap_or((80, 24)))
+    }
+
+    /// Open the input for converting at `size`
+    pub fn open_at(cli: &Cli, config: &Config, size: (u16, u16)) -> Result<Self> {
+        Ok(Self {
+            frames: cli.open_video()?,
+            converter: cli.build_converter(config, &TermCaps::default())?,
+            crop: cli.autocrop()?,
+            stabilizer: cli.stabilizer(),
+            size,
+            resume_after: None,
+        })
+    }
+
+    /// Frame rate of the input
+    pub fn fps(&self) -> f64 {
+        self.frames.decoder().fps()
+    }
+
+    /// Grid size frames are converted at
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Continue with the first frame after `timestamp`
+    pub fn resume_after(&mut self, timestamp: f64) -> Result<()> {
+        self.frames.seek(timestamp)?;
+        self.resume_after = Some(timestamp);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<AsciiFrame>> {
+        for frame in self.frames.by_ref() {
+            let frame = frame?;
+            // Seeking lands on a keyframe before the resume point
+            if self
+                .resume_after
+                .is_some_and(|last| frame.timestamp <= last + TIMESTAMP_EPSILON)
+            {
+                continue;
+            }
+            let frame = match self.crop {
+                Some(rect) => crop_frame(&frame, rect)?,
+                None => frame,
+            };
+            let frame = match self.stabilizer {
+                Some(ref mut stabilizer) => stabilizer.stabilize(frame)?,
+                None => frame,
+            };
+            let (width, height) = self.size;
+            return self.converter.convert(&frame, width, height).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for ExportFrames {
+    type Item = Result<AsciiFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+/// Whether `path` ends in `.extension`, in any case
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|found| found.eq_ignore_ascii_case(extension))
+}
+
+/// Write the converted frames as an asciinema cast at `path`
+fn export_cast(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    if cli.resume_export {
+        return Err(anyhow!("--resume-export only applies to .ascv recordings"));
+    }
+
+    let frames = ExportFrames::open(cli, config)?;
+    let (width, height) = frames.size();
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let title = cli
+        .input_path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let mut writer = CastWriter::new(BufWriter::new(file), title);
+    info!(
+        "Exporting {} to {} at {}x{}",
+        cli.input_path().display(),
+        path.display(),
+        width,
+        height
+    );
+
+    for frame in frames {
+        writer.write_frame(&frame?)?;
+    }
+
+    let frames = writer.frames();
+    writer.finish()?;
+    println!("Wrote {} frames to {}", frames, path.display());
+    Ok(())
+}
+
+/// Write the converted frames as a self-playing HTML page at `path`
+fn export_html(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    if cli.resume_export {
+        return Err(anyhow!("--resume-export only applies to .ascv recordings"));
+    }
+
+    let frames = ExportFrames::open(cli, config)?;
+    let (width, height) = frames.size();
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let title = cli
+        .input_path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "ascii-player".to_string());
+    let mut writer = HtmlWriter::new(BufWriter::new(file), &title)?;
+    info!(
+        "Exporting {} to {} at {}x{}",
+        cli.input_path().display(),
+        path.display(),
+        width,
+        height
+    );
+
+    for frame in frames {
+        writer.write_frame(&frame?)?;
+    }
+
+    let frames = writer.frames();
+    writer.finish()?;
+    let size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    println!(
+        "Wrote {} frames to {} ({:.1} MiB)",
+        frames,
+        path.display(),
+        size as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Draw the converted frames and encode them into a video at `path`, with
+/// the input's audio unless muted
+fn export_video(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    if cli.resume_export {
+        return Err(anyhow!("--resume-export only applies to .ascv recordings"));
+    }
+    check_not_input(cli.input_path(), path)?;
+
+    let frames = ExportFrames::open(cli, config)?;
+    let fps = frames.fps();
+    let (width, height) = frames.size();
+
+    if cli.edl.is_some() && !cli.mute {
+        warn!("Leaving out the audio, which would not follow the cuts of --edl");
+    }
+    let audio_from = (!cli.mute && cli.edl.is_none() && !is_image_pattern(cli.input_path()))
+        .then(|| (cli.input_path(), cli.start_time, cli.end_time));
+    let mut writer = None;
+    info!(
+        "Exporting {} to {} at {}x{}",
+        cli.input_path().display(),
+        path.display(),
+        width,
+        height
+    );
+
+    for ascii in frames {
+        let ascii = ascii?;
+        // The picture size comes from the first frame
+        let writer = match writer {
+            Some(ref mut writer) => writer,
+            None => writer.insert(VideoWriter::create(
+                path,
+                (ascii.width, ascii.height),
+                fps,
+                audio_from,
+            )?),
+        };
+        writer.write_frame(&ascii)?;
+    }
+
+    let writer = writer.ok_or_else(|| anyhow!("No frames to export"))?;
+    let frames = writer.frames();
+    writer.finish()?;
+    let size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    println!(
+        "Wrote {} frames to {} ({:.1} MiB)",
+        frames,
+        path.display(),
+        size as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Refuse to encode over the input itself, which FFmpeg would truncate while
+/// it is still being decoded. Paths are compared after resolving links and
+/// relative parts; an output that does not exist yet cannot be the input.
+fn check_not_input(input: &Path, output: &Path) -> Result<()> {
+    let (Ok(input), Ok(output)) = (input.canonicalize(), output.canonicalize()) else {
+        return Ok(());
+    };
+    if input == output {
+        return Err(anyhow!(
+            "Refusing to export over the input {}",
+            input.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Write every converted frame into `dir` as an ANSI art file: the frame's
+/// rows in 24-bit color escapes, ready to `cat` or load into an ANSI editor
+pub fn dump_ansi(cli: &Cli, config: &Config, dir: &Path) -> Result<()> {
+    let converted = ExportFrames::open(cli, config)?;
+    let (width, height) = converted.size();
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    info!(
+        "Dumping {} to {} at {}x{}",
+        cli.input_path().display(),
+        dir.display(),
+        width,
+        height
+    );
+
+    let mut frames = 0;
+    for ascii in converted {
+        let ascii = ascii?;
+        frames += 1;
+        let path = ansi_path(dir, frames);
+        std::fs::write(&path, encode_text(&ascii, ColorDepth::TrueColor))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    println!("Wrote {} frames to {}", frames, dir.display());
+    Ok(())
+}
+
+/// File for the `index`th frame dumped, counting from 1; zero-padded so the
+/// files sort in playback order
+fn ansi_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("frame_{:06}.ans", index))
+}
+
+/// Render the converted frames into an animated GIF at `path`
+fn export_gif(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    if cli.resume_export {
+        return Err(anyhow!("--resume-export only applies to .ascv recordings"));
+    }
+
+    let frames = ExportFrames::open(cli, config)?;
+    let fps = frames.fps();
+    let (width, height) = frames.size();
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = GifWriter::new(BufWriter::new(file))?;
+    info!(
+        "Exporting {} to {} at {}x{}",
+        cli.input_path().display(),
+        path.display(),
+        width,
+        height
+    );
+
+    for frame in frames {
+        writer.push(frame?)?;
+    }
+
+    let frames = writer.finish(fps)?;
+    let size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    println!(
+        "Wrote {} frames to {} ({:.1} MiB)",
+        frames,
+        path.display(),
+        size as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Encodes frames into a looping GIF. Each frame is held back until the
+/// next one arrives, since its delay is the time until the next.
+struct GifWriter<W: Write> {
+    encoder: GifEncoder<W>,
+    pending: Option<AsciiFrame>,
+    /// Timestamp of the first frame
+    start: f64,
+    /// Sum of the delays written, in hundredths of a second
+    elapsed: u32,
+    frames: u64,
+}
+
+impl<W: Write> GifWriter<W> {
+    fn new(writer: W) -> Result<Self> {
+        let mut encoder = GifEncoder::new_with_speed(writer, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(Self {
+            encoder,
+            pending: None,
+            start: 0.0,
+            elapsed: 0,
+            frames: 0,
+        })
+    }
+
+    /// Add the next frame, dropping it if it follows too closely
+    fn push(&mut self, frame: AsciiFrame) -> Result<()> {
+        let Some(pending) = self.pending.take() else {
+            self.start = frame.timestamp;
+            self.pending = Some(frame);
+            return Ok(());
+        };
+        // Delays are measured from the start so rounding does not drift
+        let target = ((frame.timestamp - self.start) * 100.0).round().max(0.0) as u32;
+        let delay = target.saturating_sub(self.elapsed);
+        if delay < MIN_GIF_DELAY {
+            self.pending = Some(pending);
+            return Ok(());
+        }
+        self.pending = Some(frame);
+        self.write(&pending, delay)
+    }
+
+    /// Write the last frame, shown for one frame at `fps`; returns the
+    /// number of frames written
+    fn finish(mut self, fps: f64) -> Result<u64> {
+        if let Some(last) = self.pending.take() {
+            let delay = if fps > 0.0 {
+                (100.0 / fps).round() as u32
+            } else {
+                0
+            };
+            self.write(&last, delay.max(MIN_GIF_DELAY))?;
+        }
+        Ok(self.frames)
+    }
+
+    fn write(&mut self, frame: &AsciiFrame, delay: u32) -> Result<()> {
+        let image = DynamicImage::ImageRgb8(render_image(frame)).into_rgba8();
+        let frame_delay = Delay::from_numer_denom_ms(delay * 10, 1);
+        self.encoder
+            .encode_frame(Frame::from_parts(image, 0, 0, frame_delay))?;
+        self.elapsed += delay;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_versions() {
+        let checkpoint = ExportCheckpoint::new(
+            PathBuf::from("talk.mp4"),
+            (120, 40),
+            4096,
+            12.5,
+            RecordingStats::default(),
+        );
+        let saved = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(ExportCheckpoint::parse(&saved).unwrap(), checkpoint);
+
+        // Written before checkpoints carried a format and version
+        let legacy = r#"{"input":"talk.mp4","width":120,"height":40,"bytes":4096,
+            "timestamp":12.5,"stats":{"frames":0,"stored":0,"deltas":0,"repeated":0}}"#;
+        assert_eq!(ExportCheckpoint::parse(legacy).unwrap(), checkpoint);
+
+        let newer = saved.replace("\"version\":1", "\"version\":9");
+        assert!(ExportCheckpoint::parse(&newer)
+            .unwrap_err()
+            .to_string()
+            .contains("version 9"));
+        let other = saved.replace(CHECKPOINT_FORMAT, "something else");
+        assert!(ExportCheckpoint::parse(&other).is_err());
+    }
+
+    #[test]
+    fn test_check_not_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("talk.mp4");
+        std::fs::write(&input, b"").unwrap();
+        let same = dir.path().join(".").join("talk.mp4");
+        assert!(check_not_input(&input, &same)
+            .unwrap_err()
+            .to_string()
+            .contains("over the input"));
+        assert!(check_not_input(&input, &dir.path().join("talk-ascii.mp4")).is_ok());
+    }
+
+    #[test]
+    fn test_ndjson_output() {
+        assert_eq!(ndjson_output(Path::new("ndjson")), Some(None));
+        let file = Path::new("out/frames.ndjson");
+        assert_eq!(ndjson_output(file), Some(Some(file)));
+        assert_eq!(ndjson_output(Path::new("talk.ascv")), None);
+    }
+
+    #[test]
+    fn test_gif_timing() {
+        use image::codecs::gif::GifDecoder;
+        use image::AnimationDecoder;
+
+        let frame = |timestamp: f64| AsciiFrame {
+            characters: vec!['#'; 4],
+            fg_colors: vec![(255, 255, 255); 4],
+            bg_colors: None,
+            attributes: None,
+            width: 2,
+            height: 2,
+            timestamp,
+            frame_number: 0,
+        };
+        let mut output = Vec::new();
+        let mut writer = GifWriter::new(&mut output).unwrap();
+        // The second frame is too close to the first and is dropped
+        for timestamp in [10.0, 10.005, 10.033, 10.066] {
+            writer.push(frame(timestamp)).unwrap();
+        }
+        assert_eq!(writer.finish(30.0).unwrap(), 3);
+
+        let frames = GifDecoder::new(std::io::Cursor::new(output))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        let delays: Vec<_> = frames
+            .iter()
+            .map(|frame| frame.delay().numer_denom_ms())
+            .collect();
+        assert_eq!(delays, [(30, 1), (40, 1), (30, 1)]);
+        assert_eq!(frames[0].buffer().dimensions(), (16, 32));
+
+        assert!(has_extension(Path::new("clip.GIF"), "gif"));
+        assert!(!has_extension(Path::new("clip.ascv"), "gif"));
+    }
+
+    #[test]
+    fn test_ansi_path() {
+        let dir = Path::new("frames");
+        assert_eq!(ansi_path(dir, 1), dir.join("frame_000001.ans"));
+        // Names sort in playback order
+        assert!(ansi_path(dir, 99) < ansi_path(dir, 100));
+    }
+}
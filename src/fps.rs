@@ -0,0 +1,104 @@
+//! Achieved frame rate
+//!
+//! The frame timer aims for the video's rate, but a slow terminal, a large
+//! grid or a busy machine can keep the player from drawing that many frames.
+//! [`FpsMeter`] counts the frames actually drawn over the last second, so
+//! the status line can show the rate achieved next to the target
+//! ("24/30fps") and a slowdown no longer goes unnoticed.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Span the achieved rate is averaged over
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// A pause between frames longer than this is a stop, not a slow frame;
+/// the average starts over after it
+const MAX_GAP: Duration = Duration::from_secs(1);
+
+/// Rolling average of the frames drawn per second
+#[derive(Debug, Default, Clone)]
+pub struct FpsMeter {
+    /// When each frame within the window was drawn, oldest first
+    frames: VecDeque<Instant>,
+}
+
+impl FpsMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a frame drawn at `now`
+    pub fn tick(&mut self, now: Instant) {
+        if self
+            .frames
+            .back()
+            .is_some_and(|&last| now.saturating_duration_since(last) > MAX_GAP)
+        {
+            self.frames.clear();
+        }
+        self.frames.push_back(now);
+        while self
+            .frames
+            .front()
+            .is_some_and(|&first| now.saturating_duration_since(first) > WINDOW)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Start over, e.g. after a pause or a seek
+    pub fn reset(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Frames per second over the window, None until two frames were drawn
+    pub fn fps(&self) -> Option<f64> {
+        let (first, last) = (self.frames.front()?, self.frames.back()?);
+        let span = last.saturating_duration_since(*first).as_secs_f64();
+        (span > 0.0).then(|| (self.frames.len() - 1) as f64 / span)
+    }
+
+    /// Share of `target` fps achieved, for anything adapting its work to
+    /// keep up
+    pub fn ratio(&self, target: f64) -> Option<f64> {
+        self.fps()
+            .filter(|_| target > 0.0)
+            .map(|fps| (fps / target).min(1.0))
+    }
+
+    /// Achieved and target rate for the status line, e.g. "24/30fps"
+    pub fn status(&self, target: f64) -> String {
+        match self.fps() {
+            Some(fps) => format!("{:.0}/{:.0}fps", fps, target),
+            None => format!("--/{:.0}fps", target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_fps() {
+        let start = Instant::now();
+        let mut meter = FpsMeter::new();
+        assert_eq!(meter.status(30.0), "--/30fps");
+
+        // 24 frames a second for two seconds; only the last second counts
+        for frame in 0..48 {
+            meter.tick(start + Duration::from_secs_f64(frame as f64 / 24.0));
+        }
+        assert!((meter.fps().unwrap() - 24.0).abs() < 0.01);
+        assert_eq!(meter.status(30.0), "24/30fps");
+        assert!((meter.ratio(30.0).unwrap() - 0.8).abs() < 0.01);
+        assert_eq!(meter.ratio(12.0), Some(1.0));
+
+        // A long stop starts the average over
+        meter.tick(start + Duration::from_secs(10));
+        assert_eq!(meter.fps(), None);
+        meter.reset();
+        assert_eq!(meter.fps(), None);
+    }
+}
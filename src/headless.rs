@@ -0,0 +1,149 @@
+//! Headless rendering for reproducible runs
+//!
+//! `--deterministic` decodes every frame as fast as possible and converts it
+//! at a fixed size, without a terminal, wall-clock pacing or anything else
+//! that depends on the machine. Frames are written to stdout as text with
+//! pseudo-timestamps (`frame / fps`) and a hash of their characters and
+//! colors, followed by a digest of the whole run, so two runs can be compared
+//! with `diff` or by their last line.
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::converter::AsciiFrame;
+use crate::export::ExportFrames;
+use crate::recording::frame_hash;
+use anyhow::Result;
+use log::info;
+use std::io::{self, BufWriter, Write};
+
+/// Size frames are converted at unless `--width`/`--height` are given
+pub const DETERMINISTIC_SIZE: (u16, u16) = (80, 24);
+
+/// Writes frames as plain text with a running digest
+pub struct HeadlessRenderer<W: Write> {
+    output: W,
+    frames: u64,
+    digest: u64,
+}
+
+impl<W: Write> HeadlessRenderer<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            frames: 0,
+            digest: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+
+    /// Write a frame: a header line, then one line per row
+    pub fn render_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let hash = frame_hash(frame);
+        writeln!(
+            self.output,
+            "frame {} t={:.6} hash={:016x}",
+            frame.frame_number, frame.timestamp, hash
+        )?;
+        for row in frame.characters.chunks(frame.width.max(1) as usize) {
+            let line: String = row.iter().collect();
+            writeln!(self.output, "{}", line)?;
+        }
+
+        self.frames += 1;
+        for byte in hash.to_le_bytes() {
+            self.digest = (self.digest ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+        Ok(())
+    }
+
+    /// Frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Hash of every frame written so far, in order
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Write the summary line and flush
+    pub fn finish(mut self) -> Result<u64> {
+        writeln!(
+            self.output,
+            "frames={} digest={:016x}",
+            self.frames, self.digest
+        )?;
+        self.output.flush()?;
+        Ok(self.digest)
+    }
+}
+
+/// Play the input headlessly and deterministically to stdout
+pub fn run_deterministic(cli: &Cli, config: &Config) -> Result<()> {
+    let frames = ExportFrames::open_at(cli, config, cli.deterministic_size())?;
+    let fps = cli.fps.unwrap_or_else(|| frames.fps());
+    let (width, height) = frames.size();
+
+    info!(
+        "Deterministic run of {} at {}x{}, {:.3} fps",
+        cli.input_path().display(),
+        width,
+        height,
+        fps
+    );
+
+    let stdout = io::stdout();
+    let mut renderer = HeadlessRenderer::new(BufWriter::new(stdout.lock()));
+    for (index, frame) in frames.enumerate() {
+        let mut ascii_frame = frame?;
+        ascii_frame.frame_number = index as u64;
+        ascii_frame.timestamp = index as f64 / fps;
+        renderer.render_frame(&ascii_frame)?;
+    }
+
+    let frames = renderer.frames();
+    let digest = renderer.finish()?;
+    info!("Rendered {} frames, digest {:016x}", frames, digest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(text: &str, frame_number: u64) -> AsciiFrame {
+        AsciiFrame {
+            characters: text.chars().collect(),
+            fg_colors: vec![(200, 200, 200); text.chars().count()],
+            bg_colors: None,
+            attributes: None,
+            width: 2,
+            height: 2,
+            timestamp: frame_number as f64 / 25.0,
+            frame_number,
+        }
+    }
+
+    #[test]
+    fn test_headless_output_is_reproducible() {
+        let run = |frames: &[AsciiFrame]| {
+            let mut output = Vec::new();
+            let mut renderer = HeadlessRenderer::new(&mut output);
+            for frame in frames {
+                renderer.render_frame(frame).unwrap();
+            }
+            let digest = renderer.finish().unwrap();
+            (String::from_utf8(output).unwrap(), digest)
+        };
+
+        let frames = [frame("ab#@", 0), frame("..::", 1)];
+        let (text, digest) = run(&frames);
+        assert_eq!(run(&frames), (text.clone(), digest));
+        assert!(text.starts_with("frame 0 t=0.000000 hash="));
+        assert!(text.contains("\nab\n#@\nframe 1 t=0.040000 hash="));
+        assert!(text.ends_with(&format!("frames=2 digest={:016x}\n", digest)));
+
+        // Order matters, not just content
+        let (_, swapped) = run(&[frames[1].clone(), frames[0].clone()]);
+        assert_ne!(digest, swapped);
+    }
+}
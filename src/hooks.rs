@@ -0,0 +1,251 @@
+//! Playback event hooks
+//!
+//! The player emits [`PlaybackEvent`]s at lifecycle points (start, natural
+//! end, user stop, error). Hooks registered on [`EventHooks`] receive every
+//! event, so library users can react to the same signals the CLI cues use.
+
+use anyhow::Result;
+use log::{debug, warn};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Lifecycle events emitted during playback
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackEvent {
+    /// Playback of a file started
+    Started { path: PathBuf },
+    /// The video played to its end
+    Finished { path: PathBuf, frames: u64 },
+    /// The user stopped playback before the end
+    Stopped { path: PathBuf, frames: u64 },
+    /// Playback aborted with an error
+    Error { path: PathBuf, message: String },
+}
+
+impl PlaybackEvent {
+    /// Short event name, also exported to hook commands as `ASCII_PLAYER_EVENT`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaybackEvent::Started { .. } => "started",
+            PlaybackEvent::Finished { .. } => "finished",
+            PlaybackEvent::Stopped { .. } => "stopped",
+            PlaybackEvent::Error { .. } => "error",
+        }
+    }
+
+    /// File the event refers to
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            PlaybackEvent::Started { path }
+            | PlaybackEvent::Finished { path, .. }
+            | PlaybackEvent::Stopped { path, .. }
+            | PlaybackEvent::Error { path, .. } => path,
+        }
+    }
+
+    /// Whether this event marks completion or failure (what cues announce)
+    pub fn is_terminal_cue(&self) -> bool {
+        matches!(
+            self,
+            PlaybackEvent::Finished { .. } | PlaybackEvent::Error { .. }
+        )
+    }
+
+    /// Human readable description for notifications
+    pub fn describe(&self) -> String {
+        let name = self
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path().display().to_string());
+
+        match self {
+            PlaybackEvent::Started { .. } => format!("Playing {}", name),
+            PlaybackEvent::Finished { .. } => format!("Finished playing {}", name),
+            PlaybackEvent::Stopped { .. } => format!("Stopped {}", name),
+            PlaybackEvent::Error { message, .. } => format!("Error playing {}: {}", name, message),
+        }
+    }
+}
+
+/// Receiver of playback events
+pub trait EventHook {
+    /// Handle an event; errors are logged and do not stop playback
+    fn on_event(&mut self, event: &PlaybackEvent) -> Result<()>;
+}
+
+/// Registry dispatching events to every registered hook
+#[derive(Default)]
+pub struct EventHooks {
+    hooks: Vec<Box<dyn EventHook>>,
+}
+
+impl EventHooks {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a hook
+    pub fn register(&mut self, hook: Box<dyn EventHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Number of registered hooks
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Whether no hooks are registered
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Deliver an event to all hooks
+    pub fn emit(&mut self, event: &PlaybackEvent) {
+        debug!("Emitting playback event: {:?}", event);
+        for hook in &mut self.hooks {
+            if let Err(e) = hook.on_event(event) {
+                warn!("Event hook failed for '{}': {}", event.name(), e);
+            }
+        }
+    }
+}
+
+/// Rings the terminal bell on completion or error
+pub struct BellHook;
+
+impl EventHook for BellHook {
+    fn on_event(&mut self, event: &PlaybackEvent) -> Result<()> {
+        if event.is_terminal_cue() {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends an OSC 9 desktop notification on completion or error
+pub struct NotificationHook;
+
+impl NotificationHook {
+    /// Build the OSC 9 escape sequence for a message
+    pub fn sequence(message: &str) -> String {
+        // Control characters would terminate or corrupt the sequence
+        let sanitized: String = message.chars().filter(|c| !c.is_control()).collect();
+        format!("\x1b]9;{}\x07", sanitized)
+    }
+}
+
+impl EventHook for NotificationHook {
+    fn on_event(&mut self, event: &PlaybackEvent) -> Result<()> {
+        if event.is_terminal_cue() {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(Self::sequence(&event.describe()).as_bytes())?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a shell command when a specific event occurs.
+///
+/// The command receives `ASCII_PLAYER_EVENT`, `ASCII_PLAYER_FILE` and
+/// `ASCII_PLAYER_MESSAGE` in its environment.
+pub struct CommandHook {
+    command: String,
+    event_name: &'static str,
+}
+
+impl CommandHook {
+    /// Run `command` whenever an event with the given name is emitted
+    pub fn new(command: String, event_name: &'static str) -> Self {
+        Self {
+            command,
+            event_name,
+        }
+    }
+}
+
+impl EventHook for CommandHook {
+    fn on_event(&mut self, event: &PlaybackEvent) -> Result<()> {
+        if event.name() != self.event_name {
+            return Ok(());
+        }
+
+        debug!("Running hook command: {}", self.command);
+        let status = shell_command(&self.command)
+            .env("ASCII_PLAYER_EVENT", event.name())
+            .env("ASCII_PLAYER_FILE", event.path())
+            .env("ASCII_PLAYER_MESSAGE", event.describe())
+            .status()?;
+
+        if !status.success() {
+            warn!("Hook command '{}' exited with {}", self.command, status);
+        }
+        Ok(())
+    }
+}
+
+/// Build a command that runs a string through the platform shell
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingHook(Arc<Mutex<Vec<String>>>);
+
+    impl EventHook for RecordingHook {
+        fn on_event(&mut self, event: &PlaybackEvent) -> Result<()> {
+            self.0.lock().unwrap().push(event.name().to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hooks_receive_all_events() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = EventHooks::new();
+        hooks.register(Box::new(RecordingHook(received.clone())));
+
+        let path = PathBuf::from("clip.mp4");
+        hooks.emit(&PlaybackEvent::Started { path: path.clone() });
+        hooks.emit(&PlaybackEvent::Finished { path, frames: 10 });
+
+        assert_eq!(*received.lock().unwrap(), vec!["started", "finished"]);
+    }
+
+    #[test]
+    fn test_notification_sequence_is_sanitized() {
+        let sequence = NotificationHook::sequence("done\x07\x1b]evil");
+        assert_eq!(sequence, "\x1b]9;done]evil\x07");
+    }
+
+    #[test]
+    fn test_describe_uses_file_name() {
+        let event = PlaybackEvent::Error {
+            path: PathBuf::from("/videos/clip.mp4"),
+            message: "boom".to_string(),
+        };
+        assert_eq!(event.describe(), "Error playing clip.mp4: boom");
+        assert!(event.is_terminal_cue());
+        assert!(!PlaybackEvent::Started {
+            path: PathBuf::new()
+        }
+        .is_terminal_cue());
+    }
+}
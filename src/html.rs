@@ -0,0 +1,269 @@
+//! Self-contained HTML animation output
+//!
+//! `--export clip.html` writes a single page that plays the converted frames
+//! in a browser, with nothing else to host or install. Every frame is
+//! pre-rendered to HTML, runs of cells sharing their colors becoming one
+//! `<span>`, and stored with its time in a script array; a small player
+//! swaps the frames into a `<pre>` at the video's timing and loops. Click
+//! the picture or press space to pause.
+//!
+//! As for casts, frames identical to the one before are left out.
+
+use crate::converter::AsciiFrame;
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Page up to the start of the frame array; `{title}` is replaced
+/// Text color of the page (`#ccc`), for cells missing from a short color list
+const PAGE_COLOR: (u8, u8, u8) = (0xcc, 0xcc, 0xcc);
+
+const PAGE_START: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { margin: 0; min-height: 100vh; display: flex; align-items: center; justify-content: center; background: #000; }
+pre { margin: 0; font: 12px/1 monospace; color: #ccc; cursor: pointer; }
+</style>
+</head>
+<body>
+<pre id="screen" title="Click or press space to pause"></pre>
+<script>
+const frames = [
+"#;
+
+/// Plays `frames`, looping after `duration` seconds
+const PLAYER_SCRIPT: &str = r#"const screen = document.getElementById("screen");
+let index = 0;
+let timer = null;
+function show() {
+  screen.innerHTML = frames[index][1];
+  const next = (index + 1) % frames.length;
+  const end = next === 0 ? duration : frames[next][0];
+  timer = setTimeout(show, Math.max(end - frames[index][0], 0) * 1000);
+  index = next;
+}
+function toggle() {
+  if (timer === null) {
+    show();
+  } else {
+    clearTimeout(timer);
+    timer = null;
+  }
+}
+screen.addEventListener("click", toggle);
+document.addEventListener("keydown", (event) => {
+  if (event.key === " ") {
+    event.preventDefault();
+    toggle();
+  }
+});
+if (frames.length > 0) {
+  show();
+}
+"#;
+
+/// Writes frames into a standalone HTML page
+pub struct HtmlWriter<W: Write> {
+    output: W,
+    /// Timestamp of the first frame
+    start: Option<f64>,
+    /// Time of the last frame written, relative to the first
+    last_time: f64,
+    /// How long the frame before the last one was shown, used for the last
+    last_delay: f64,
+    /// Markup of the last frame written, to skip repeats
+    previous: String,
+    frames: u64,
+}
+
+impl<W: Write> HtmlWriter<W> {
+    /// Start a page titled `title`
+    pub fn new(mut output: W, title: &str) -> Result<Self> {
+        output.write_all(PAGE_START.replace("{title}", &escape(title)).as_bytes())?;
+        Ok(Self {
+            output,
+            start: None,
+            last_time: 0.0,
+            last_delay: 0.0,
+            previous: String::new(),
+            frames: 0,
+        })
+    }
+
+    /// Add one frame at its time since the first frame
+    pub fn write_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let markup = frame_markup(frame);
+        if markup == self.previous {
+            return Ok(());
+        }
+        let start = *self.start.get_or_insert(frame.timestamp);
+        let time = (frame.timestamp - start).max(0.0);
+        if self.frames > 0 {
+            self.last_delay = time - self.last_time;
+        }
+        self.last_time = time;
+
+        // "</" is escaped so no frame can end the script early
+        let entry = serde_json::to_string(&markup)?.replace("</", "<\\/");
+        writeln!(self.output, "[{:.3},{}],", time, entry)?;
+        self.previous = markup;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Number of frames written, not counting repeats
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Close the page; the last frame is shown as long as the one before
+    /// it, or a tenth of a second if it is the only one
+    pub fn finish(mut self) -> Result<W> {
+        let hold = if self.frames > 1 {
+            self.last_delay
+        } else {
+            0.1
+        };
+        writeln!(
+            self.output,
+            "];\nconst duration = {:.3};\n{}</script>\n</body>\n</html>",
+            self.last_time + hold,
+            PLAYER_SCRIPT
+        )?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+/// One frame as HTML: rows separated by newlines, each run of cells with
+/// the same colors in one span
+pub fn frame_markup(frame: &AsciiFrame) -> String {
+    let width = (frame.width as usize).max(1);
+    let mut markup = String::with_capacity(frame.characters.len() * 2);
+    for (row, characters) in frame.characters.chunks(width).enumerate() {
+        if row > 0 {
+            markup.push('\n');
+        }
+        let mut run = String::new();
+        let mut run_colors = None;
+        for (column, &character) in characters.iter().enumerate() {
+            let index = row * width + column;
+            let colors = (
+                frame.fg_colors.get(index).copied().unwrap_or(PAGE_COLOR),
+                frame
+                    .bg_colors
+                    .as_ref()
+                    .and_then(|colors| colors.get(index).copied()),
+            );
+            if run_colors != Some(colors) {
+                if let Some(colors) = run_colors {
+                    push_span(&mut markup, colors, &run);
+                }
+                run.clear();
+                run_colors = Some(colors);
+            }
+            push_escaped(&mut run, character);
+        }
+        if let Some(colors) = run_colors {
+            push_span(&mut markup, colors, &run);
+        }
+    }
+    markup
+}
+
+type CellColors = ((u8, u8, u8), Option<(u8, u8, u8)>);
+
+fn push_span(markup: &mut String, (fg, bg): CellColors, text: &str) {
+    let _ = write!(
+        markup,
+        "<span style=\"color:#{:02x}{:02x}{:02x}",
+        fg.0, fg.1, fg.2
+    );
+    if let Some(bg) = bg {
+        let _ = write!(markup, ";background:#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2);
+    }
+    let _ = write!(markup, "\">{}</span>", text);
+}
+
+fn push_escaped(output: &mut String, character: char) {
+    match character {
+        '&' => output.push_str("&amp;"),
+        '<' => output.push_str("&lt;"),
+        '>' => output.push_str("&gt;"),
+        '"' => output.push_str("&quot;"),
+        _ => output.push(character),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        push_escaped(&mut escaped, character);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_markup() {
+        let mut frame = AsciiFrame::new(
+            3,
+            2,
+            vec!['#', '#', '<', '&', ' ', '@'],
+            vec![
+                (255, 0, 0),
+                (255, 0, 0),
+                (0, 255, 0),
+                (0, 0, 0),
+                (0, 0, 0),
+                (0, 0, 0),
+            ],
+        );
+        assert_eq!(
+            frame_markup(&frame),
+            "<span style=\"color:#ff0000\">##</span><span style=\"color:#00ff00\">&lt;</span>\n\
+             <span style=\"color:#000000\">&amp; @</span>"
+        );
+
+        frame.bg_colors = Some(vec![(0, 0, 255); 6]);
+        assert!(
+            frame_markup(&frame).starts_with("<span style=\"color:#ff0000;background:#0000ff\">##")
+        );
+
+        // Cells without colors take the page's
+        frame.fg_colors.truncate(3);
+        frame.bg_colors = None;
+        assert!(frame_markup(&frame).ends_with("<span style=\"color:#cccccc\">&amp; @</span>"));
+    }
+
+    #[test]
+    fn test_html_page() {
+        let mut frame = AsciiFrame::new(2, 1, vec!['#', '@'], vec![(255, 0, 0); 2]);
+        frame.timestamp = 3.0;
+
+        let mut writer = HtmlWriter::new(Vec::new(), "<clip>").unwrap();
+        writer.write_frame(&frame).unwrap();
+        // A repeat is left out
+        frame.timestamp = 3.04;
+        writer.write_frame(&frame).unwrap();
+        frame.timestamp = 3.5;
+        frame.characters[0] = '.';
+        writer.write_frame(&frame).unwrap();
+        assert_eq!(writer.frames(), 2);
+
+        let page = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(page.contains("<title>&lt;clip&gt;</title>"));
+        assert!(page.contains("[0.000,\"<span style=\\\"color:#ff0000\\\">#@<\\/span>\"],"));
+        assert!(page.contains("[0.500,\"<span style=\\\"color:#ff0000\\\">.@<\\/span>\"],"));
+        assert!(page.contains("const duration = 1.000;"));
+        assert!(page.trim_end().ends_with("</html>"));
+        // The only "</script" is the one closing the player
+        assert_eq!(page.matches("</script").count(), 1);
+    }
+}
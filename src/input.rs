@@ -0,0 +1,48 @@
+//! Terminal input as an async stream
+//!
+//! Keys, resizes and focus changes are read on a background thread and
+//! handed over through a channel, so the playback loop can wait on input,
+//! its frame timer and control commands at once instead of polling the
+//! terminal between frames.
+
+use crossterm::event::{self, Event};
+use log::{debug, warn};
+use std::thread;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+/// Terminal events read on their own thread
+pub struct InputEvents {
+    receiver: UnboundedReceiver<Event>,
+}
+
+impl InputEvents {
+    /// Start reading terminal events. Nothing else may read them while the
+    /// reader runs, or events go to whichever reads first.
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let spawned = thread::Builder::new()
+            .name("input".to_string())
+            .spawn(move || loop {
+                match event::read() {
+                    Ok(event) => {
+                        if sender.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Terminal input closed: {}", e);
+                        break;
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Keyboard input unavailable: {}", e);
+        }
+        Self { receiver }
+    }
+
+    /// Next terminal event; None once the terminal can no longer be read
+    pub async fn next(&mut self) -> Option<Event> {
+        self.receiver.recv().await
+    }
+}
@@ -0,0 +1,155 @@
+//! `ascii-player inspect`: describe the files ascii-player writes
+//!
+//! The file type is detected from its content rather than its name: `.ascv`
+//! recordings start with [`RECORDING_MAGIC`], and export checkpoints are
+//! JSON tagged with [`CHECKPOINT_FORMAT`].
+
+use crate::export::{ExportCheckpoint, CHECKPOINT_FORMAT, CHECKPOINT_VERSION};
+use crate::recording::{RecordEntry, RecordingReader, RECORDING_MAGIC, RECORDING_VERSION};
+use anyhow::{anyhow, Context, Result};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Describe the format and contents of a recording or export checkpoint
+pub fn inspect(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let size = file.metadata()?.len();
+
+    let mut magic = [0u8; 4];
+    let is_recording = file.read_exact(&mut magic).is_ok() && &magic == RECORDING_MAGIC;
+    if is_recording {
+        let reader = RecordingReader::new(BufReader::new(File::open(path)?))?;
+        return Ok(format!(
+            "{}file size:  {} bytes\n",
+            describe_recording(reader)?,
+            size
+        ));
+    }
+
+    let contents = std::fs::read_to_string(path).ok();
+    let is_checkpoint = contents
+        .as_deref()
+        .is_some_and(|contents| contents.trim_start().starts_with('{'));
+    if is_checkpoint {
+        let checkpoint = ExportCheckpoint::parse(contents.as_deref().unwrap_or_default())
+            .with_context(|| format!("Invalid export checkpoint {}", path.display()))?;
+        return Ok(describe_checkpoint(&checkpoint));
+    }
+
+    Err(anyhow!(
+        "{} is not a recording or export checkpoint",
+        path.display()
+    ))
+}
+
+/// Summarize a recording by reading it to the end
+pub fn describe_recording<R: Read>(mut reader: RecordingReader<R>) -> Result<String> {
+    let version = reader.version();
+    let fps = reader.fps();
+
+    let mut size = None;
+    let mut background = false;
+    let mut attributes = false;
+    let (mut keyframes, mut deltas, mut repeats, mut repeated) = (0u64, 0u64, 0u64, 0u64);
+    let mut duration = 0.0;
+    while let Some(entry) = reader.next_entry()? {
+        match entry {
+            RecordEntry::Frame(frame) => {
+                size.get_or_insert((frame.width, frame.height));
+                background |= frame.bg_colors.is_some();
+                attributes |= frame.attributes.is_some();
+                duration = frame.timestamp;
+                keyframes += 1;
+            }
+            RecordEntry::Delta(delta) => {
+                duration = delta.timestamp;
+                deltas += 1;
+            }
+            RecordEntry::Repeat { count, duration: d } => {
+                duration += d;
+                repeats += 1;
+                repeated += count as u64;
+            }
+        }
+    }
+    let frames = keyframes + deltas + repeated;
+    if frames > 0 {
+        // The last frame stays on screen for one frame interval
+        duration += 1.0 / fps;
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "format:     ASCV recording, version {} (this release writes {})",
+        version, RECORDING_VERSION
+    )?;
+    writeln!(out, "fps:        {:.3}", fps)?;
+    match size {
+        Some((width, height)) => writeln!(out, "size:       {}x{}", width, height)?,
+        None => writeln!(out, "size:       (no frames)")?,
+    }
+    writeln!(out, "background: {}", if background { "yes" } else { "no" })?;
+    writeln!(out, "attributes: {}", if attributes { "yes" } else { "no" })?;
+    writeln!(out, "frames:     {}", frames)?;
+    writeln!(
+        out,
+        "records:    {} keyframes, {} deltas, {} repeats",
+        keyframes, deltas, repeats
+    )?;
+    writeln!(out, "duration:   {:.2}s", duration)?;
+    Ok(out)
+}
+
+/// Summarize an export checkpoint
+pub fn describe_checkpoint(checkpoint: &ExportCheckpoint) -> String {
+    format!(
+        "format:     {}, version {} (this release writes {})\n\
+         input:      {}\n\
+         size:       {}x{}\n\
+         written:    {} bytes\n\
+         resumes at: {:.2}s\n\
+         frames:     {} ({} stored, {} deltas, {} repeated)\n",
+        CHECKPOINT_FORMAT,
+        checkpoint.version,
+        CHECKPOINT_VERSION,
+        checkpoint.input.display(),
+        checkpoint.width,
+        checkpoint.height,
+        checkpoint.bytes,
+        checkpoint.timestamp,
+        checkpoint.stats.frames,
+        checkpoint.stats.stored,
+        checkpoint.stats.deltas,
+        checkpoint.stats.repeated,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::AsciiFrame;
+    use crate::recording::RecordingWriter;
+
+    #[test]
+    fn test_describe_recording() {
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        for (index, text) in ["ab", "ab", "ab", "cd"].into_iter().enumerate() {
+            let frame = AsciiFrame::new(2, 1, text.chars().collect(), vec![(9, 9, 9); 2])
+                .with_timestamp(index as f64 / 10.0, index as u64);
+            writer.write_frame(&frame).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let summary = describe_recording(RecordingReader::new(buffer.as_slice()).unwrap()).unwrap();
+        assert!(summary.contains("version 3"));
+        assert!(summary.contains("attributes: no"));
+        assert!(summary.contains("size:       2x1"));
+        assert!(summary.contains("frames:     4"));
+        assert!(summary.contains("duration:   0.40s"));
+    }
+}
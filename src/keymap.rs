@@ -0,0 +1,526 @@
+//! Keys and what they do during playback
+//!
+//! Every key press is looked up in a [`Keymap`]: the default bindings, then
+//! those under `"keys"` in the config file, then each `--bind KEY=ACTION`.
+//! Binding a key replaces what it did before; binding it to `none` makes
+//! it do nothing. A key is a character (`x`, or `X` for Shift+X) or one of
+//! `space`, `esc`, `enter`, `tab`, `backspace`, `delete`, `insert`, `left`,
+//! `right`, `up`, `down`, `home`, `end`, `pageup`, `pagedown` and `f1` to
+//! `f12`, optionally after `ctrl+`, `alt+` or `shift+`. Ctrl+C always
+//! quits, whatever it is bound to.
+//!
+//! The help screen is written from the keymap, so it lists the keys as
+//! bound.
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Something a key does during playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Pause,
+    Quit,
+    SpeedUp,
+    SpeedDown,
+    Loop,
+    Captions,
+    Palette,
+    Charset,
+    BrightnessUp,
+    BrightnessDown,
+    ContrastUp,
+    ContrastDown,
+    GammaUp,
+    GammaDown,
+    Restart,
+    NextFile,
+    PreviousFile,
+    SeekBack,
+    SeekForward,
+    SeekBackLong,
+    SeekForwardLong,
+    StepForward,
+    StepBack,
+    Stats,
+    Screenshot,
+    Copy,
+    CopyColors,
+    Bookmark,
+    NextBookmark,
+    PreviousBookmark,
+    Note,
+    NextNote,
+    PreviousNote,
+    LoopStart,
+    LoopEnd,
+    ClearLoop,
+    ExportClip,
+    Help,
+    /// Jump to a tenth of the video, 0 to 9
+    Jump(u8),
+}
+
+/// Actions by name, with their line on the help screen, in help order
+const ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::Pause, "pause", "Pause/Resume"),
+    (Action::Quit, "quit", "Quit"),
+    (Action::SpeedUp, "speed-up", "Increase speed"),
+    (Action::SpeedDown, "speed-down", "Decrease speed"),
+    (Action::Loop, "loop", "Toggle loop"),
+    (Action::Captions, "captions", "Toggle closed captions"),
+    (
+        Action::Palette,
+        "palette",
+        "Cycle color/grayscale/ASCII palette",
+    ),
+    (Action::Charset, "charset", "Cycle character ramps"),
+    (Action::BrightnessUp, "brightness-up", "Increase brightness"),
+    (
+        Action::BrightnessDown,
+        "brightness-down",
+        "Decrease brightness",
+    ),
+    (Action::ContrastUp, "contrast-up", "Increase contrast"),
+    (Action::ContrastDown, "contrast-down", "Decrease contrast"),
+    (
+        Action::GammaUp,
+        "gamma-up",
+        "Increase gamma (lifts shadows)",
+    ),
+    (Action::GammaDown, "gamma-down", "Decrease gamma"),
+    (Action::Restart, "restart", "Restart video"),
+    (Action::NextFile, "next-file", "Next file of the playlist"),
+    (
+        Action::PreviousFile,
+        "previous-file",
+        "Previous file of the playlist",
+    ),
+    (
+        Action::SeekBack,
+        "seek-back",
+        "Seek back 5s (hold to keep going)",
+    ),
+    (
+        Action::SeekForward,
+        "seek-forward",
+        "Seek forward 5s (hold to keep going)",
+    ),
+    (Action::SeekBackLong, "seek-back-long", "Seek back 60s"),
+    (
+        Action::SeekForwardLong,
+        "seek-forward-long",
+        "Seek forward 60s",
+    ),
+    (
+        Action::StepForward,
+        "step-forward",
+        "Step forward one frame (pauses)",
+    ),
+    (
+        Action::StepBack,
+        "step-back",
+        "Step back one frame (pauses)",
+    ),
+    (Action::Stats, "stats", "Toggle stage timings"),
+    (
+        Action::Screenshot,
+        "screenshot",
+        "Save the frame as .txt and .ans",
+    ),
+    (Action::Copy, "copy", "Copy frame"),
+    (Action::CopyColors, "copy-colors", "Copy frame with colors"),
+    (Action::Bookmark, "bookmark", "Bookmark this moment"),
+    (
+        Action::NextBookmark,
+        "next-bookmark",
+        "Jump to next bookmark",
+    ),
+    (
+        Action::PreviousBookmark,
+        "previous-bookmark",
+        "Jump to previous bookmark",
+    ),
+    (Action::Note, "note", "Note on this frame"),
+    (Action::NextNote, "next-note", "Jump to next note"),
+    (
+        Action::PreviousNote,
+        "previous-note",
+        "Jump to previous note",
+    ),
+    (Action::LoopStart, "loop-start", "Mark loop/clip start (A)"),
+    (Action::LoopEnd, "loop-end", "Mark loop/clip end (B)"),
+    (Action::ClearLoop, "clear-loop", "Clear the A-B loop"),
+    (Action::ExportClip, "export-clip", "Export the marked clip"),
+    (Action::Help, "help", "Toggle this help"),
+];
+
+/// Name that unbinds a key
+const UNBOUND: &str = "none";
+
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("space", Action::Pause),
+    ("q", Action::Quit),
+    ("esc", Action::Quit),
+    ("ctrl+c", Action::Quit),
+    ("+", Action::SpeedUp),
+    ("=", Action::SpeedUp),
+    ("-", Action::SpeedDown),
+    ("l", Action::Loop),
+    ("c", Action::Captions),
+    ("m", Action::Palette),
+    ("t", Action::Charset),
+    ("I", Action::BrightnessUp),
+    ("i", Action::BrightnessDown),
+    ("O", Action::ContrastUp),
+    ("o", Action::ContrastDown),
+    ("G", Action::GammaUp),
+    ("g", Action::GammaDown),
+    ("r", Action::Restart),
+    ("n", Action::NextFile),
+    ("p", Action::PreviousFile),
+    ("left", Action::SeekBack),
+    ("right", Action::SeekForward),
+    ("down", Action::SeekBackLong),
+    ("up", Action::SeekForwardLong),
+    (".", Action::StepForward),
+    (",", Action::StepBack),
+    ("S", Action::Stats),
+    ("s", Action::Screenshot),
+    ("y", Action::Copy),
+    ("Y", Action::CopyColors),
+    ("b", Action::Bookmark),
+    ("B", Action::Bookmark),
+    ("j", Action::NextBookmark),
+    ("J", Action::NextBookmark),
+    ("k", Action::PreviousBookmark),
+    ("K", Action::PreviousBookmark),
+    ("a", Action::Note),
+    ("}", Action::NextNote),
+    ("{", Action::PreviousNote),
+    ("[", Action::LoopStart),
+    ("]", Action::LoopEnd),
+    ("\\", Action::ClearLoop),
+    ("e", Action::ExportClip),
+    ("E", Action::ExportClip),
+    ("h", Action::Help),
+    ("f1", Action::Help),
+];
+
+impl Action {
+    /// The action named `name`, as in `--bind` and the config file
+    pub fn parse(name: &str) -> Result<Self> {
+        if let Some(digit) = name.strip_prefix("jump-") {
+            return match digit.parse::<u8>() {
+                Ok(tenth @ 0..=9) => Ok(Action::Jump(tenth)),
+                _ => Err(anyhow!("Unknown action '{}' (jump-0 to jump-9)", name)),
+            };
+        }
+        ACTIONS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+            .ok_or_else(|| anyhow!("Unknown action '{}'", name))
+    }
+}
+
+/// A key press as looked up: Shift is part of the character for
+/// character keys, and only Ctrl, Alt and Shift count
+type Key = (KeyCode, KeyModifiers);
+
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    let mut modifiers =
+        modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+    let code = match code {
+        KeyCode::Char(c) => {
+            modifiers.remove(KeyModifiers::SHIFT);
+            // Terminals send Ctrl+Shift+X as Ctrl+x or Ctrl+X
+            if modifiers.contains(KeyModifiers::CONTROL) {
+                KeyCode::Char(c.to_ascii_lowercase())
+            } else {
+                KeyCode::Char(c)
+            }
+        }
+        code => code,
+    };
+    (code, modifiers)
+}
+
+/// Parse a key such as `q`, `space` or `ctrl+left`
+pub fn parse_key(spec: &str) -> Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let modifier = match lower.split_once('+') {
+            Some(("ctrl", key)) if !key.is_empty() => KeyModifiers::CONTROL,
+            Some(("alt", key)) if !key.is_empty() => KeyModifiers::ALT,
+            Some(("shift", key)) if !key.is_empty() => KeyModifiers::SHIFT,
+            _ => break,
+        };
+        modifiers |= modifier;
+        rest = &rest[rest.find('+').unwrap_or(0) + 1..];
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(n @ 1..=12)) => KeyCode::F(n),
+                _ => return Err(anyhow!("Unknown key '{}'", spec)),
+            },
+        },
+    };
+    Ok(normalize(code, modifiers))
+}
+
+/// How a key is written on the help screen
+fn key_label((code, modifiers): Key) -> String {
+    let mut label = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        label.push_str("Ctrl+");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        label.push_str("Alt+");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        label.push_str("Shift+");
+    }
+    match code {
+        KeyCode::Char(' ') => label.push_str("SPACE"),
+        KeyCode::Char(c) if c.is_ascii_uppercase() => {
+            let _ = write!(label, "Shift+{}", c);
+        }
+        KeyCode::Char(c) => label.push(c.to_ascii_uppercase()),
+        KeyCode::Esc => label.push_str("ESC"),
+        KeyCode::Left => label.push('←'),
+        KeyCode::Right => label.push('→'),
+        KeyCode::Up => label.push('↑'),
+        KeyCode::Down => label.push('↓'),
+        KeyCode::F(n) => {
+            let _ = write!(label, "F{}", n);
+        }
+        code => label.push_str(&format!("{:?}", code).to_uppercase()),
+    }
+    label
+}
+
+/// Which action each key triggers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        for (key, action) in DEFAULT_BINDINGS {
+            let key = parse_key(key).expect("default keys parse");
+            keymap.bindings.insert(key, *action);
+        }
+        for tenth in 0..=9u8 {
+            keymap.bindings.insert(
+                (KeyCode::Char((b'0' + tenth) as char), KeyModifiers::NONE),
+                Action::Jump(tenth),
+            );
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// The default keys with those of the config file and then `binds`
+    /// (each `KEY=ACTION`) bound over them
+    pub fn new(config_keys: &BTreeMap<String, String>, binds: &[String]) -> Result<Self> {
+        let mut keymap = Self::default();
+        for (key, action) in config_keys {
+            keymap
+                .bind(key, action)
+                .map_err(|e| anyhow!("Invalid key binding in config file: {}", e))?;
+        }
+        for bind in binds {
+            // The key may itself be `=`, so split after its first character
+            let split = bind
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| c == '=')
+                .map(|(index, _)| index)
+                .ok_or_else(|| anyhow!("Invalid --bind '{}', expected KEY=ACTION", bind))?;
+            keymap
+                .bind(&bind[..split], &bind[split + 1..])
+                .map_err(|e| anyhow!("Invalid --bind '{}': {}", bind, e))?;
+        }
+        Ok(keymap)
+    }
+
+    /// Make `key` trigger the action named `action`, or nothing for `none`
+    pub fn bind(&mut self, key: &str, action: &str) -> Result<()> {
+        let key = parse_key(key)?;
+        if action == UNBOUND {
+            self.bindings.remove(&key);
+        } else {
+            self.bindings.insert(key, Action::parse(action)?);
+        }
+        Ok(())
+    }
+
+    /// What a key press does, if anything
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = normalize(event.code, event.modifiers);
+        if key == (KeyCode::Char('c'), KeyModifiers::CONTROL) {
+            return Some(Action::Quit);
+        }
+        self.bindings.get(&key).copied()
+    }
+
+    /// Keys bound to `action`, as written on the help screen
+    fn labels(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<Key> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+            .collect();
+        // Plain characters first, then named keys, then with modifiers
+        keys.sort_by_key(|key| {
+            let label = key_label(*key);
+            (!key.1.is_empty(), label.chars().count() > 1, label)
+        });
+        keys.into_iter().map(key_label).collect()
+    }
+
+    /// The help screen: each bound action with its keys
+    pub fn help(&self) -> String {
+        let mut lines = Vec::new();
+        for (action, _, description) in ACTIONS {
+            let labels = self.labels(*action);
+            if !labels.is_empty() {
+                lines.push((labels.join("/"), description.to_string()));
+            }
+        }
+        let default_jumps = (0..=9u8).all(|tenth| {
+            self.labels(Action::Jump(tenth)) == [((b'0' + tenth) as char).to_string()]
+        });
+        if default_jumps {
+            lines.push(("0-9".to_string(), "Jump to 0%-90% of the video".to_string()));
+        } else {
+            for tenth in 0..=9u8 {
+                let labels = self.labels(Action::Jump(tenth));
+                if !labels.is_empty() {
+                    let description = format!("Jump to {}% of the video", tenth as u32 * 10);
+                    lines.push((labels.join("/"), description));
+                }
+            }
+        }
+
+        let width = lines
+            .iter()
+            .map(|(keys, _)| keys.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        let mut help = String::from("ASCII Player Controls:\n\n");
+        for (keys, description) in lines {
+            let _ = writeln!(help, "{:<width$} - {}", keys, description, width = width);
+        }
+        let hide = self.labels(Action::Help);
+        if let Some(key) = hide.first() {
+            let _ = write!(help, "\nPress {} again to hide this help.", key);
+        }
+        help
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let keymap = Keymap::default();
+        let action = |code| keymap.action(&press(code, KeyModifiers::NONE));
+        assert_eq!(action(KeyCode::Char(' ')), Some(Action::Pause));
+        assert_eq!(action(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(action(KeyCode::Left), Some(Action::SeekBack));
+        assert_eq!(action(KeyCode::Char('7')), Some(Action::Jump(7)));
+        assert_eq!(action(KeyCode::Char('x')), None);
+        // Shift is in the character
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('Y'), KeyModifiers::SHIFT)),
+            Some(Action::CopyColors)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+        let help = keymap.help();
+        assert!(help
+            .lines()
+            .any(|line| line.starts_with("Q/ESC/Ctrl+C ") && line.ends_with(" - Quit")));
+        assert!(help.contains("Press H again"));
+    }
+
+    #[test]
+    fn test_config_and_bind_flags() {
+        let config = BTreeMap::from([
+            ("x".to_string(), "quit".to_string()),
+            ("q".to_string(), "none".to_string()),
+        ]);
+        let binds = ["==speed-down".to_string(), "ctrl+Right=jump-9".to_string()];
+        let keymap = Keymap::new(&config, &binds).unwrap();
+        let action = |code, modifiers| keymap.action(&press(code, modifiers));
+        assert_eq!(
+            action(KeyCode::Char('x'), KeyModifiers::NONE),
+            Some(Action::Quit)
+        );
+        assert_eq!(action(KeyCode::Char('q'), KeyModifiers::NONE), None);
+        assert_eq!(
+            action(KeyCode::Char('='), KeyModifiers::NONE),
+            Some(Action::SpeedDown)
+        );
+        assert_eq!(
+            action(KeyCode::Right, KeyModifiers::CONTROL),
+            Some(Action::Jump(9))
+        );
+        assert_eq!(
+            action(KeyCode::Right, KeyModifiers::NONE),
+            Some(Action::SeekForward)
+        );
+        // Ctrl+C quits even when rebound
+        let keymap = Keymap::new(&BTreeMap::new(), &["ctrl+c=pause".to_string()]).unwrap();
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+
+        assert!(Keymap::new(&BTreeMap::new(), &["q".to_string()]).is_err());
+        assert!(Keymap::new(&BTreeMap::new(), &["q=fly".to_string()]).is_err());
+        assert!(Keymap::new(&BTreeMap::new(), &["hyper+q=quit".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Output size and placement of frames
+//!
+//! The grid a frame is converted to and where the renderer puts it depend
+//! only on the frame size, the terminal size and the conversion config, so
+//! frontends embedding the player (e.g. as a ratatui widget) can reserve the
+//! exact area before the first frame is decoded. The converter and the
+//! renderer use the same functions, so the reserved area always matches.
+
+use crate::converter::{cap_grid, ConversionConfig};
+
+/// Grid size of a converted frame and its offset from the top left of the
+/// terminal, as `(width, height, (offset_x, offset_y))` in cells.
+///
+/// `frame_dims` is the source size in pixels (after any cropping) and
+/// `term_dims` the terminal size in cells. Offsets center the frame, as the
+/// renderer does unless centering is turned off.
+///
+/// ```
+/// use ascii_player::{layout, ConversionConfig};
+///
+/// // A 16:9 video in a wide terminal is pillarboxed
+/// let config = ConversionConfig::default();
+/// let (width, height, offsets) = layout::compute((1920, 1080), (200, 50), &config);
+/// assert_eq!((width, height, offsets), (177, 50, (11, 0)));
+/// ```
+pub fn compute(
+    frame_dims: (u32, u32),
+    term_dims: (u16, u16),
+    config: &ConversionConfig,
+) -> (u16, u16, (u16, u16)) {
+    let (term_width, term_height) = cap_grid(term_dims.0, term_dims.1, config.max_cells);
+    let (width, height) = if config.cover {
+        // Cover mode crops the picture to the terminal's shape
+        (term_width.max(1), term_height.max(1))
+    } else {
+        fit(frame_dims, (term_width, term_height), config)
+    };
+    (width, height, centered((width, height), term_dims))
+}
+
+/// Largest grid within `term_dims` showing `frame_dims` at its aspect
+/// ratio, or at an integer scale with `scale_exact` when it fits
+pub(crate) fn fit(
+    frame_dims: (u32, u32),
+    term_dims: (u16, u16),
+    config: &ConversionConfig,
+) -> (u16, u16) {
+    let ((src_width, src_height), (term_width, term_height)) = (frame_dims, term_dims);
+    if config.scale_exact {
+        if let Some(dimensions) = exact_dimensions(frame_dims, term_dims) {
+            return dimensions;
+        }
+    }
+
+    let src_aspect = src_width as f64 / src_height as f64;
+    let term_aspect = term_width as f64 / (term_height as f64 * config.aspect_ratio);
+
+    let (target_width, target_height) = if src_aspect > term_aspect {
+        // Source is wider, fit to width
+        let width = term_width;
+        let height = ((term_width as f64 / src_aspect) * config.aspect_ratio) as u16;
+        (width, height.min(term_height))
+    } else {
+        // Source is taller, fit to height
+        let height = term_height;
+        let width = ((term_height as f64 * src_aspect) / config.aspect_ratio) as u16;
+        (width.min(term_width), height)
+    };
+
+    (target_width.max(1), target_height.max(1))
+}
+
+/// Integer-scaled dimensions for sources small enough to fit the grid.
+///
+/// Each source pixel becomes two cells side by side (which looks square on
+/// typical 1:2 terminal cells), or a single cell if the doubled width does
+/// not fit. Returns `None` when the source is larger than the terminal.
+fn exact_dimensions(
+    (src_width, src_height): (u32, u32),
+    (term_width, term_height): (u16, u16),
+) -> Option<(u16, u16)> {
+    if src_width == 0 || src_height == 0 || src_height > term_height as u32 {
+        return None;
+    }
+
+    if src_width * 2 <= term_width as u32 {
+        Some(((src_width * 2) as u16, src_height as u16))
+    } else if src_width <= term_width as u32 {
+        Some((src_width as u16, src_height as u16))
+    } else {
+        None
+    }
+}
+
+/// Offset that centers a `frame_dims` grid in `term_dims`
+pub fn centered(frame_dims: (u16, u16), term_dims: (u16, u16)) -> (u16, u16) {
+    (
+        term_dims.0.saturating_sub(frame_dims.0) / 2,
+        term_dims.1.saturating_sub(frame_dims.1) / 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aspect_ratio_calculation() {
+        let config = ConversionConfig::default();
+
+        // Test square source to wider terminal
+        let (w, h) = fit((100, 100), (80, 20), &config);
+        assert!(w <= 80);
+        assert!(h <= 20);
+
+        // Test wide source to square terminal
+        let (w, h) = fit((200, 100), (40, 40), &config);
+        assert!(w <= 40);
+        assert!(h <= 40);
+    }
+
+    #[test]
+    fn test_scale_exact_dimensions() {
+        let config = ConversionConfig {
+            scale_exact: true,
+            ..Default::default()
+        };
+
+        // Small sprite: each pixel doubled horizontally
+        assert_eq!(fit((16, 16), (80, 24), &config), (32, 16));
+
+        // Too wide to double, but fits 1:1
+        assert_eq!(fit((60, 20), (80, 24), &config), (60, 20));
+
+        // Larger than the terminal falls back to regular fitting
+        let (w, h) = fit((320, 240), (80, 24), &config);
+        assert!(w <= 80 && h <= 24);
+    }
+
+    #[test]
+    fn test_compute_layout() {
+        let config = ConversionConfig::default();
+        // A tall video is pillarboxed in the middle of the terminal
+        assert_eq!(compute((100, 200), (80, 24), &config), (24, 24, (28, 0)));
+
+        // Cover fills the terminal whatever the source
+        let cover = ConversionConfig {
+            cover: true,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(compute((100, 200), (80, 24), &cover), (80, 24, (0, 0)));
+
+        // A capped grid is centered in the full terminal
+        let capped = ConversionConfig {
+            cover: true,
+            max_cells: 20 * 6,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(compute((100, 200), (80, 24), &capped), (20, 6, (30, 9)));
+    }
+}
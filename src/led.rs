@@ -0,0 +1,278 @@
+//! LED matrix output
+//!
+//! Built with the `led-matrix` feature, `--led TARGET` sends each converted
+//! frame's cell colors to a physical RGB panel instead of the terminal, one
+//! LED per cell. Decoding, cropping and conversion are the same as for
+//! playback; only the characters are dropped. Two targets are supported:
+//!
+//! - `ft:HOST[:PORT]` sends frames over UDP in the Flaschen-Taschen protocol
+//!   (a binary PPM per packet), understood by the `ft-server` that ships
+//!   with rpi-rgb-led-matrix and by Flaschen-Taschen displays.
+//! - `unicorn-hd[:DEVICE]` writes to a Pimoroni Unicorn HAT HD over spidev,
+//!   `/dev/spidev0.0` by default. The HAT is always 16x16.
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::converter::{AsciiConverter, AsciiFrame, FrameConverter};
+use crate::crop::crop_frame;
+use crate::termcaps::TermCaps;
+use anyhow::{Context, Result};
+use log::info;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Panel size when `--led-size` is not given
+pub const DEFAULT_LED_SIZE: (u16, u16) = (32, 32);
+
+/// Port `ft-server` listens on
+const FLASCHEN_TASCHEN_PORT: u16 = 1337;
+
+const UNICORN_HD_DEVICE: &str = "/dev/spidev0.0";
+const UNICORN_HD_SIZE: (u16, u16) = (16, 16);
+/// Command byte that starts a Unicorn HAT HD frame
+const UNICORN_HD_START: u8 = 0x72;
+
+/// Where `--led` sends frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedTarget {
+    /// Flaschen-Taschen server at `host:port`
+    FlaschenTaschen(String),
+    /// Unicorn HAT HD on this spidev device
+    UnicornHd(PathBuf),
+}
+
+impl FromStr for LedTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = s.split_once(':').unwrap_or((s, ""));
+        match kind {
+            "ft" if rest.is_empty() => Err("ft needs a host, e.g. ft:matrix.local".to_string()),
+            "ft" if rest.contains(':') => Ok(LedTarget::FlaschenTaschen(rest.to_string())),
+            "ft" => Ok(LedTarget::FlaschenTaschen(format!(
+                "{}:{}",
+                rest, FLASCHEN_TASCHEN_PORT
+            ))),
+            "unicorn-hd" if rest.is_empty() => Ok(LedTarget::UnicornHd(UNICORN_HD_DEVICE.into())),
+            "unicorn-hd" => Ok(LedTarget::UnicornHd(rest.into())),
+            _ => Err(format!(
+                "Unknown LED target '{}', expected ft:HOST[:PORT] or unicorn-hd[:DEVICE]",
+                s
+            )),
+        }
+    }
+}
+
+/// Parse a panel size written as `WxH`
+pub fn parse_led_size(s: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("Invalid LED size '{}', expected e.g. 32x16", s);
+    let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (width.trim().parse::<u16>(), height.trim().parse::<u16>()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+/// A physical RGB panel
+pub trait LedPanel {
+    /// Width and height in LEDs
+    fn size(&self) -> (u16, u16);
+
+    /// Show one frame of `width * height` colors, row by row
+    fn show(&mut self, pixels: &[(u8, u8, u8)]) -> Result<()>;
+}
+
+/// Panel behind a Flaschen-Taschen UDP server
+pub struct FlaschenTaschen {
+    socket: UdpSocket,
+    size: (u16, u16),
+}
+
+impl FlaschenTaschen {
+    pub fn connect(addr: &str, size: (u16, u16)) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket
+            .connect(addr)
+            .with_context(|| format!("Failed to reach LED server {}", addr))?;
+        Ok(Self { socket, size })
+    }
+}
+
+impl LedPanel for FlaschenTaschen {
+    fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    fn show(&mut self, pixels: &[(u8, u8, u8)]) -> Result<()> {
+        let mut packet = format!("P6\n{} {}\n255\n", self.size.0, self.size.1).into_bytes();
+        packet.extend(pixels.iter().flat_map(|&(r, g, b)| [r, g, b]));
+        self.socket.send(&packet)?;
+        Ok(())
+    }
+}
+
+/// Pimoroni Unicorn HAT HD on a spidev device
+pub struct UnicornHd {
+    device: File,
+}
+
+impl UnicornHd {
+    pub fn open(device: &Path) -> Result<Self> {
+        let device = OpenOptions::new()
+            .write(true)
+            .open(device)
+            .with_context(|| format!("Failed to open SPI device {}", device.display()))?;
+        Ok(Self { device })
+    }
+}
+
+impl LedPanel for UnicornHd {
+    fn size(&self) -> (u16, u16) {
+        UNICORN_HD_SIZE
+    }
+
+    fn show(&mut self, pixels: &[(u8, u8, u8)]) -> Result<()> {
+        let mut message = Vec::with_capacity(1 + pixels.len() * 3);
+        message.push(UNICORN_HD_START);
+        message.extend(pixels.iter().flat_map(|&(r, g, b)| [r, g, b]));
+        self.device.write_all(&message)?;
+        Ok(())
+    }
+}
+
+/// Open the panel `target` names
+pub fn open_panel(target: &LedTarget, size: (u16, u16)) -> Result<Box<dyn LedPanel>> {
+    Ok(match target {
+        LedTarget::FlaschenTaschen(addr) => Box::new(FlaschenTaschen::connect(addr, size)?),
+        LedTarget::UnicornHd(device) => Box::new(UnicornHd::open(device)?),
+    })
+}
+
+/// Place a converted frame's cell colors on a `size` panel, centered on
+/// black when it is smaller
+pub fn frame_pixels(frame: &AsciiFrame, size: (u16, u16)) -> Vec<(u8, u8, u8)> {
+    let (panel_width, panel_height) = (size.0 as usize, size.1 as usize);
+    let mut pixels = vec![(0, 0, 0); panel_width * panel_height];
+    let width = (frame.width as usize).min(panel_width);
+    let height = (frame.height as usize).min(panel_height);
+    let left = (panel_width - width) / 2;
+    let top = (panel_height - height) / 2;
+
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(&color) = frame.fg_colors.get(y * frame.width as usize + x) {
+                pixels[(top + y) * panel_width + left + x] = color;
+            }
+        }
+    }
+    pixels
+}
+
+/// Play the input on an LED panel in real time
+pub async fn run_led(cli: &Cli, config: &Config, target: &LedTarget) -> Result<()> {
+    let size = cli.led_size.unwrap_or(DEFAULT_LED_SIZE);
+    let mut panel = open_panel(target, size)?;
+    let size = panel.size();
+    // LEDs are square, unlike terminal cells
+    let converter = FrameConverter::new(
+        cli.conversion_config(config, &TermCaps::default(), None)?
+            .with_aspect_ratio(1.0),
+    );
+    let crop = cli.autocrop()?;
+    let mut stabilizer = cli.stabilizer();
+    info!(
+        "Playing {} on {:?} at {}x{}",
+        cli.input_path().display(),
+        target,
+        size.0,
+        size.1
+    );
+
+    loop {
+        let mut clock: Option<(Instant, f64)> = None;
+        for frame in cli.open_video()? {
+            let frame = frame?;
+            let frame = match crop {
+                Some(rect) => crop_frame(&frame, rect)?,
+                None => frame,
+            };
+            let frame = match stabilizer {
+                Some(ref mut stabilizer) => stabilizer.stabilize(frame)?,
+                None => frame,
+            };
+
+            let (started, first) = *clock.get_or_insert((Instant::now(), frame.timestamp));
+            let due = Duration::from_secs_f64(((frame.timestamp - first) / cli.speed).max(0.0));
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                sleep(wait).await;
+            }
+
+            let converted = converter.convert(&frame, size.0, size.1)?;
+            panel.show(&frame_pixels(&converted, size))?;
+        }
+
+        if !cli.loop_playback {
+            break;
+        }
+    }
+
+    // Leave the panel dark rather than on the last frame
+    panel.show(&vec![(0, 0, 0); size.0 as usize * size.1 as usize])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_led_targets() {
+        assert_eq!(
+            "ft:matrix.local".parse(),
+            Ok(LedTarget::FlaschenTaschen("matrix.local:1337".to_string()))
+        );
+        assert_eq!(
+            "ft:10.0.0.2:2000".parse(),
+            Ok(LedTarget::FlaschenTaschen("10.0.0.2:2000".to_string()))
+        );
+        assert_eq!(
+            "unicorn-hd".parse(),
+            Ok(LedTarget::UnicornHd(PathBuf::from("/dev/spidev0.0")))
+        );
+        assert!("ft".parse::<LedTarget>().is_err());
+        assert!("hub75".parse::<LedTarget>().is_err());
+        assert_eq!(parse_led_size("64x32"), Ok((64, 32)));
+        assert!(parse_led_size("64").is_err());
+    }
+
+    #[test]
+    fn test_frames_reach_the_panel() {
+        let frame = AsciiFrame::new(2, 1, vec!['#', '.'], vec![(255, 0, 0), (0, 0, 255)]);
+        let pixels = frame_pixels(&frame, (4, 3));
+        assert_eq!(pixels.len(), 12);
+        assert_eq!(
+            &pixels[4..8],
+            &[(0, 0, 0), (255, 0, 0), (0, 0, 255), (0, 0, 0)]
+        );
+
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        let mut panel = open_panel(&LedTarget::FlaschenTaschen(addr), (4, 3)).unwrap();
+        panel.show(&pixels).unwrap();
+
+        let mut packet = [0u8; 128];
+        let length = server.recv(&mut packet).unwrap();
+        let header = b"P6\n4 3\n255\n";
+        assert_eq!(&packet[..header.len()], header);
+        assert_eq!(length, header.len() + 36);
+        assert_eq!(&packet[header.len() + 15..header.len() + 18], &[255, 0, 0]);
+    }
+}
@@ -4,15 +4,170 @@
 //! that can be played in the terminal with support for colors, transparency, and
 //! responsive resizing.
 
+pub mod abloop;
+pub mod ambient;
+pub mod animation;
+pub mod annotations;
+pub mod audio;
+pub mod autoramp;
+pub mod bench;
+pub mod bookmarks;
+pub mod cache;
+pub mod calibrate;
+pub mod captions;
+pub mod cast;
+pub mod charset;
+pub mod charsets;
 pub mod cli;
+pub mod clip;
+pub mod clipboard;
+pub mod config;
 pub mod converter;
+pub mod crop;
+pub mod cvd;
+pub mod damage;
 pub mod decoder;
+pub mod describe;
+pub mod doctor;
+pub mod edl;
+pub mod effects;
+pub mod encode;
+pub mod export;
+pub mod fps;
+pub mod headless;
+pub mod hooks;
+pub mod html;
+pub mod input;
+pub mod inspect;
+pub mod keymap;
+pub mod layout;
+#[cfg(feature = "led-matrix")]
+pub mod led;
+pub mod look;
+pub mod loop_point;
+pub mod ndjson;
+pub mod overlay;
+pub mod palettes;
+pub mod pipeline;
+pub mod playlist;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod qr;
+pub mod raster;
+pub mod recording;
+pub mod refine;
 pub mod renderer;
+pub mod roi;
+pub mod scale;
+pub mod schedule;
+pub mod scheduling;
+pub mod screenshot;
+pub mod seek;
+pub mod sequence;
+pub mod server;
+pub mod share;
+pub mod skip;
+pub mod slave;
+pub mod sniff;
+pub mod speedcurve;
+pub mod stabilize;
+pub mod status;
+pub mod stretch;
+pub mod subtitles;
+pub mod supervisor;
+pub mod sync;
+pub mod telnet;
+pub mod termcaps;
+pub mod termstate;
+pub mod textinput;
+pub mod throttle;
+pub mod trace;
+pub mod wall;
+pub mod watchdog;
+pub mod waveform;
 
-pub use cli::{Cli, ColorPalette};
-pub use converter::{frame_to_ascii, AsciiFrame, ConversionConfig, FrameConverter};
-pub use decoder::{load_video, FrameIterator, VideoDecoder, VideoFrame};
+pub use abloop::AbLoop;
+pub use animation::AnimatedImage;
+pub use annotations::{Annotation, AnnotationPrompt};
+pub use audio::AudioPlayer;
+pub use bench::{bench_assembly, BenchResult};
+pub use bookmarks::{Bookmark, BookmarkPrompt, TimedMark};
+pub use cache::{ByteSize, FrameCache, LoopCache, MemoryBudget};
+pub use captions::ClosedCaptions;
+pub use cast::CastWriter;
+pub use charset::Charset;
+pub use charsets::CharsetPreset;
+pub use cli::{Cli, ColorPalette, Commands, Dither, Style, Theme};
+pub use clip::{ClipJob, ClipRange, ClipStatus};
+pub use config::Config;
+pub use converter::{
+    frame_to_ascii, AsciiConverter, AsciiFrame, CellAttribute, ColorQuantizer, ConversionConfig,
+    FixedPalette, FrameConverter, Posterize, SplitConverter,
+};
+pub use crop::{crop_frame, CropDetector, CropRect};
+pub use cvd::{CvdFilter, Deficiency};
+pub use damage::{Damage, DamageTracker};
+pub use decoder::{load_video, FrameIterator, FrameSource, VideoDecoder, VideoFrame};
+pub use describe::Describer;
+pub use edl::{CutList, CutRange};
+pub use effects::{Effect, EffectChain};
+pub use encode::VideoWriter;
+pub use export::ExportCheckpoint;
+pub use fps::FpsMeter;
+pub use headless::HeadlessRenderer;
+pub use hooks::{EventHook, EventHooks, PlaybackEvent};
+pub use html::HtmlWriter;
+pub use input::InputEvents;
+pub use keymap::{Action, Keymap};
+#[cfg(feature = "led-matrix")]
+pub use led::{LedPanel, LedTarget};
+pub use look::{Adjustment, Look};
+pub use loop_point::{LoopDetector, LoopPoint};
+pub use ndjson::NdjsonWriter;
+pub use overlay::{
+    big_text, blank_frame, draw_big_text, draw_in_corner, draw_text, Corner, OverlayContent,
+    OverlayId, Placement, TimedOverlay,
+};
+pub use palettes::Colormap;
+pub use pipeline::{
+    ConvertStage, DecodedFrame, FilterStage, Pipeline, PipelineMetrics, PipelineStage, QueueStats,
+    RenderStage,
+};
+pub use playlist::Playlist;
+pub use raster::{render_image, CELL_HEIGHT, CELL_WIDTH};
+pub use recording::{frame_hash, FrameDelta, RecordEntry, RecordingReader, RecordingWriter};
+pub use refine::{GlyphConverter, RefineJob};
 pub use renderer::{calculate_frame_delay, render_frame, Renderer};
+pub use roi::RoiTracker;
+pub use scale::Scaler;
+pub use schedule::{ClockTime, StartSchedule};
+pub use scheduling::{pin_thread, set_nice};
+pub use screenshot::{save_screenshot_files, screenshot_stem};
+pub use seek::{HeldSeek, SeekPrefetcher};
+pub use sequence::ImageSequence;
+pub use server::{
+    AccessToken, AdminCommand, BandwidthLimiter, ClientLimits, FrameChannels, PlaybackClock,
+};
+pub use skip::BlackSkipper;
+#[cfg(unix)]
+pub use slave::ControlFifo;
+pub use slave::{CommandReader, SlaveCommand};
+pub use sniff::{sniff, Sniffed, UnsupportedFormat};
+pub use speedcurve::{SpeedCurve, SpeedKeyframe};
+pub use stabilize::Stabilizer;
+pub use status::{PlaybackStatus, StatusReporter, StatusReporters};
+pub use subtitles::{SubtitleBitmap, SubtitleCue, SubtitleTimeline};
+pub use supervisor::{install_panic_hook, Supervisor};
+pub use sync::{SyncMessage, SyncSession};
+pub use telnet::{encode_frame, encode_text, ColorDepth};
+pub use termcaps::TermCaps;
+pub use termstate::TerminalState;
+pub use textinput::TextInput;
+pub use throttle::{Bandwidth, OutputThrottle};
+pub use trace::{Stage, StageStats, StageTiming};
+pub use wall::{Tile, WallClock, WallLayout};
+pub use watchdog::FrameWatchdog;
+pub use waveform::{Waveform, WaveformLoader};
 
 /// Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -37,8 +192,9 @@ pub const EXTENDED_ASCII_RAMP: &[char] = &[
 /// Block character ramp for a more solid appearance
 pub const BLOCK_ASCII_RAMP: &[char] = &[' ', '░', '▒', '▓', '█'];
 
-/// Error types used throughout the application
+/// Error types used throughout the application; more variants may be added
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum AsciiPlayerError {
     #[error("Video decoding error: {0}")]
     VideoDecoding(#[from] ffmpeg_next::Error),
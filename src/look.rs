@@ -0,0 +1,264 @@
+//! Changing how the picture is drawn during playback
+//!
+//! `m` cycles the palette through color, grayscale and plain ASCII; unless
+//! a charset was chosen, the ramp goes along, from blocks to the ASCII ramp
+//! and back, as it would have at startup. `t` cycles the character ramp
+//! through the one playback started with, the ASCII ramp and, on terminals
+//! that show Unicode, blocks and the `--charset-preset` ramps. `i`/`I`, `o`/`O` and `g`/`G` turn brightness,
+//! contrast and gamma down and up, with their values shown over the picture
+//! for a moment; raising gamma brings out dark footage that would otherwise
+//! be drawn with nothing but spaces. [`Look`] keeps the conversion settings
+//! with these changes; the player builds a new converter from
+//! [`Look::config`] after each one, so the next frame is drawn with it.
+
+use crate::charsets::CharsetPreset;
+use crate::cli::{ColorPalette, ASCII_RAMP, BLOCK_RAMP};
+use crate::converter::{AsciiFrame, ConversionConfig};
+use crate::overlay::{draw_text, Placement};
+use clap::ValueEnum;
+use std::ops::RangeInclusive;
+
+/// A picture setting turned up and down with keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    Brightness,
+    Contrast,
+    Gamma,
+}
+
+impl Adjustment {
+    /// How much one key press changes the setting
+    fn step(self) -> f64 {
+        match self {
+            Adjustment::Brightness => 0.05,
+            Adjustment::Contrast | Adjustment::Gamma => 0.1,
+        }
+    }
+
+    /// Values the setting can take
+    fn range(self) -> RangeInclusive<f64> {
+        match self {
+            Adjustment::Brightness => -1.0..=1.0,
+            Adjustment::Contrast => 0.0..=2.0,
+            Adjustment::Gamma => 0.1..=4.0,
+        }
+    }
+}
+
+/// A character ramp `t` can switch to
+#[derive(Debug, Clone, PartialEq)]
+struct Ramp {
+    name: String,
+    glyphs: Vec<char>,
+    weights: Option<Vec<f64>>,
+}
+
+/// Conversion settings as changed during playback
+#[derive(Debug, Clone)]
+pub struct Look {
+    config: ConversionConfig,
+    ramps: Vec<Ramp>,
+    /// Index of the ramp in use
+    ramp: usize,
+    /// Switch ramps with the palette, until one is picked with `t`
+    ramp_follows_palette: bool,
+}
+
+impl Look {
+    /// Start from `config`, offering Unicode ramps if `unicode` is set
+    pub fn new(config: ConversionConfig, unicode: bool) -> Self {
+        let mut ramps = vec![Ramp {
+            name: "ascii".to_string(),
+            glyphs: ASCII_RAMP.to_vec(),
+            weights: None,
+        }];
+        if unicode {
+            ramps.push(Ramp {
+                name: "blocks".to_string(),
+                glyphs: BLOCK_RAMP.to_vec(),
+                weights: None,
+            });
+            for preset in CharsetPreset::value_variants() {
+                let charset = preset.charset();
+                ramps.push(Ramp {
+                    name: preset
+                        .to_possible_value()
+                        .map_or_else(String::new, |value| value.get_name().to_string()),
+                    glyphs: charset.glyphs,
+                    weights: charset.weights,
+                });
+            }
+        }
+
+        // A ramp of its own, such as a calibrated one, comes first
+        let ramp = match ramps
+            .iter()
+            .position(|ramp| ramp.glyphs == config.ascii_chars)
+        {
+            Some(index) => index,
+            None => {
+                ramps.insert(
+                    0,
+                    Ramp {
+                        name: "custom".to_string(),
+                        glyphs: config.ascii_chars.clone(),
+                        weights: config.char_weights.clone(),
+                    },
+                );
+                0
+            }
+        };
+        Self {
+            config,
+            ramps,
+            ramp,
+            ramp_follows_palette: false,
+        }
+    }
+
+    /// Change the ramp along with the palette, for a ramp that wasn't chosen
+    pub fn with_ramp_following_palette(mut self, follow: bool) -> Self {
+        self.ramp_follows_palette = follow;
+        self
+    }
+
+    /// Settings to convert frames with
+    pub fn config(&self) -> &ConversionConfig {
+        &self.config
+    }
+
+    /// Switch to the next palette; returns a notice naming it
+    pub fn cycle_palette(&mut self) -> String {
+        self.config.palette = match self.config.palette {
+            ColorPalette::Color => ColorPalette::Grayscale,
+            ColorPalette::Grayscale => ColorPalette::Ascii,
+            ColorPalette::Ascii => ColorPalette::Color,
+        };
+        if self.ramp_follows_palette {
+            let glyphs = match self.config.palette {
+                ColorPalette::Ascii => ASCII_RAMP,
+                ColorPalette::Color | ColorPalette::Grayscale => BLOCK_RAMP,
+            };
+            // Without Unicode there are no blocks, and ASCII stays
+            if let Some(index) = self.ramps.iter().position(|ramp| ramp.glyphs == glyphs) {
+                self.select_ramp(index);
+            }
+        }
+        let name = self
+            .config
+            .palette
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string());
+        format!("Palette: {}", name)
+    }
+
+    /// Switch to the next character ramp; returns a notice naming it
+    pub fn cycle_ramp(&mut self) -> String {
+        self.ramp_follows_palette = false;
+        self.select_ramp((self.ramp + 1) % self.ramps.len());
+        format!("Characters: {}", self.ramps[self.ramp].name)
+    }
+
+    fn select_ramp(&mut self, index: usize) {
+        self.ramp = index;
+        let ramp = &self.ramps[index];
+        self.config.ascii_chars = ramp.glyphs.clone();
+        self.config.char_weights = ramp.weights.clone();
+    }
+
+    /// Turn `adjustment` up (`steps` above 0) or down
+    pub fn adjust(&mut self, adjustment: Adjustment, steps: i32) {
+        let value = match adjustment {
+            Adjustment::Brightness => &mut self.config.brightness,
+            Adjustment::Contrast => &mut self.config.contrast,
+            Adjustment::Gamma => &mut self.config.gamma,
+        };
+        let range = adjustment.range();
+        let adjusted = *value + adjustment.step() * steps as f64;
+        // Whole hundredths, so repeated steps come back to where they started
+        *value = ((adjusted * 100.0).round() / 100.0).clamp(*range.start(), *range.end());
+    }
+
+    /// Brightness, contrast and gamma as shown over the picture
+    pub fn adjustments(&self) -> String {
+        format!(
+            "Brightness {:+.2}  Contrast {:.2}  Gamma {:.2}",
+            self.config.brightness, self.config.contrast, self.config.gamma
+        )
+    }
+
+    /// Show the adjustments at the top of `frame`
+    pub fn draw_adjustments(&self, frame: &mut AsciiFrame) {
+        draw_text(frame, &[self.adjustments()], Placement::Top);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles_palettes_and_ramps() {
+        let config = ConversionConfig::default().with_ascii_chars(BLOCK_RAMP.to_vec(), None);
+        let mut look = Look::new(config, true);
+        assert_eq!(look.cycle_palette(), "Palette: grayscale");
+        assert_eq!(look.cycle_palette(), "Palette: ascii");
+        assert_eq!(look.cycle_palette(), "Palette: color");
+
+        // From blocks through the presets and back around to ASCII
+        assert_eq!(look.cycle_ramp(), "Characters: katakana");
+        assert!(look.config().char_weights.is_some());
+        for _ in 1..CharsetPreset::value_variants().len() {
+            look.cycle_ramp();
+        }
+        assert_eq!(look.cycle_ramp(), "Characters: ascii");
+        assert_eq!(look.config().ascii_chars, ASCII_RAMP);
+        assert_eq!(look.config().char_weights, None);
+    }
+
+    #[test]
+    fn test_adjustments_step_within_range() {
+        let mut look = Look::new(ConversionConfig::default(), false);
+        look.adjust(Adjustment::Gamma, 3);
+        look.adjust(Adjustment::Brightness, -1);
+        assert_eq!(
+            look.adjustments(),
+            "Brightness -0.05  Contrast 1.00  Gamma 1.30"
+        );
+        look.adjust(Adjustment::Gamma, -3);
+        assert_eq!(look.config().gamma, 1.0);
+
+        look.adjust(Adjustment::Contrast, 50);
+        assert_eq!(look.config().contrast, 2.0);
+        look.adjust(Adjustment::Gamma, -50);
+        assert_eq!(look.config().gamma, 0.1);
+    }
+
+    #[test]
+    fn test_ramp_follows_palette_until_chosen() {
+        let config = ConversionConfig::default().with_ascii_chars(BLOCK_RAMP.to_vec(), None);
+        let mut look = Look::new(config, true).with_ramp_following_palette(true);
+        look.cycle_palette();
+        assert_eq!(look.config().ascii_chars, BLOCK_RAMP);
+        assert_eq!(look.cycle_palette(), "Palette: ascii");
+        assert_eq!(look.config().ascii_chars, ASCII_RAMP);
+        look.cycle_palette();
+        assert_eq!(look.config().ascii_chars, BLOCK_RAMP);
+
+        // A ramp picked with `t` stays through palette changes
+        look.cycle_ramp();
+        let picked = look.config().ascii_chars.clone();
+        look.cycle_palette();
+        look.cycle_palette();
+        assert_eq!(look.config().ascii_chars, picked);
+    }
+
+    #[test]
+    fn test_keeps_a_custom_ramp() {
+        let config = ConversionConfig::default().with_ascii_chars(vec![' ', 'o', 'O'], None);
+        let mut look = Look::new(config, false);
+        assert_eq!(look.cycle_ramp(), "Characters: ascii");
+        assert_eq!(look.cycle_ramp(), "Characters: custom");
+        assert_eq!(look.config().ascii_chars, [' ', 'o', 'O']);
+    }
+}
@@ -0,0 +1,131 @@
+//! Seamless loop points for GIFs and short clips
+//!
+//! Many looping GIFs end with a copy of their first frame, or repeat a few
+//! frames from the start, so restarting at the end of the container shows
+//! the same picture twice: a visible hitch at every loop. While the first
+//! pass of a short video plays, each decoded frame is hashed; at its end the
+//! first frame whose picture, and every frame after it, repeat the start
+//! becomes the loop point. Later passes restart there instead of at the end.
+//! The result is saved in the settings file so the pass runs once per input.
+
+use crate::decoder::VideoFrame;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Videos longer than this, in seconds, are not searched for a loop point
+pub const MAX_LOOP_DURATION: f64 = 30.0;
+
+/// Frames hashed at most, for short videos at high frame rates
+const MAX_LOOP_FRAMES: usize = 3000;
+
+/// A gap between frames larger than this, in seconds, means playback jumped
+const MAX_FRAME_GAP: f64 = 1.0;
+
+/// Where a short video starts repeating itself
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopPoint {
+    /// Timestamp of the first frame that repeats the start, None if the
+    /// video loops cleanly at its end
+    pub end: Option<f64>,
+}
+
+/// Hashes the frames of a first pass to find its loop point
+#[derive(Debug, Default)]
+pub struct LoopDetector {
+    hashes: Vec<u64>,
+    timestamps: Vec<f64>,
+}
+
+impl LoopDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take in the next decoded frame; false once the frames can no longer
+    /// give a loop point, because playback jumped or the video is too long
+    pub fn observe(&mut self, frame: &VideoFrame) -> bool {
+        let continuous = match self.timestamps.last() {
+            Some(&last) => (0.0..=MAX_FRAME_GAP).contains(&(frame.timestamp - last)),
+            None => frame.timestamp < MAX_FRAME_GAP,
+        };
+        if !continuous || self.hashes.len() >= MAX_LOOP_FRAMES {
+            debug!("Loop point detection stopped at {:.2}s", frame.timestamp);
+            return false;
+        }
+
+        // Only exact matches count, so the whole picture is hashed
+        let mut hasher = DefaultHasher::new();
+        (frame.width, frame.height).hash(&mut hasher);
+        frame.data.hash(&mut hasher);
+        self.hashes.push(hasher.finish());
+        self.timestamps.push(frame.timestamp);
+        true
+    }
+
+    /// Loop point of the frames seen, at the end of the pass
+    pub fn finish(self) -> LoopPoint {
+        let end = loop_start(&self.hashes).map(|index| self.timestamps[index]);
+        debug!(
+            "Loop point of {} frames: {:?}",
+            self.hashes.len(),
+            end.map(|end| format!("{:.3}s", end))
+        );
+        LoopPoint { end }
+    }
+}
+
+/// Index of the first frame from which the rest of `hashes` repeats the
+/// start; None for videos without one and for still pictures
+fn loop_start(hashes: &[u64]) -> Option<usize> {
+    let first = *hashes.first()?;
+    if hashes.iter().all(|&hash| hash == first) {
+        return None;
+    }
+    (1..hashes.len()).find(|&start| hashes[start..] == hashes[..hashes.len() - start])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(value: u8, timestamp: f64) -> VideoFrame {
+        VideoFrame {
+            data: vec![value; 4 * 4 * 3],
+            width: 4,
+            height: 4,
+            timestamp,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_loop_start() {
+        // The last frame repeats the first
+        assert_eq!(loop_start(&[1, 2, 3, 4, 1]), Some(4));
+        // The last two frames repeat the first two
+        assert_eq!(loop_start(&[1, 2, 3, 1, 2]), Some(3));
+        // A held frame in the middle is not a loop
+        assert_eq!(loop_start(&[1, 2, 2, 3, 4]), None);
+        // Nor is a frame that shows up again without the rest following
+        assert_eq!(loop_start(&[1, 2, 1, 3]), None);
+        assert_eq!(loop_start(&[5, 5, 5]), None);
+        assert_eq!(loop_start(&[]), None);
+    }
+
+    #[test]
+    fn test_loop_detector() {
+        let mut detector = LoopDetector::new();
+        for (index, value) in [10, 20, 30, 10].into_iter().enumerate() {
+            assert!(detector.observe(&frame(value, index as f64 * 0.25)));
+        }
+        assert_eq!(detector.finish(), LoopPoint { end: Some(0.75) });
+
+        // A jump, such as a seek, ends detection
+        let mut detector = LoopDetector::new();
+        assert!(detector.observe(&frame(10, 0.0)));
+        assert!(!detector.observe(&frame(20, 5.0)));
+        assert!(!LoopDetector::new().observe(&frame(10, 12.0)));
+    }
+}
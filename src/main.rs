@@ -1,76 +1,226 @@
+mod abloop;
+mod ambient;
+mod animation;
+mod annotations;
+mod audio;
+mod autoramp;
+mod bench;
+mod bookmarks;
+mod cache;
+mod calibrate;
+mod captions;
+mod cast;
+mod charset;
+mod charsets;
 mod cli;
+mod clip;
+mod clipboard;
+mod config;
 mod converter;
+mod crop;
+mod cvd;
+mod damage;
 mod decoder;
+mod describe;
+mod doctor;
+mod edl;
+mod effects;
+mod encode;
+mod export;
+mod fps;
+mod headless;
+mod hooks;
+mod html;
+mod input;
+mod inspect;
+mod keymap;
+mod layout;
+#[cfg(feature = "led-matrix")]
+mod led;
+mod look;
+mod loop_point;
+mod ndjson;
+mod overlay;
+mod palettes;
+mod pipeline;
+mod playlist;
+mod qr;
+mod raster;
+mod recording;
+mod refine;
 mod renderer;
+mod roi;
+mod scale;
+mod schedule;
+mod scheduling;
+mod screenshot;
+mod seek;
+mod sequence;
+mod server;
+mod share;
+mod skip;
+mod slave;
+mod sniff;
+mod speedcurve;
+mod stabilize;
+mod status;
+mod stretch;
+mod subtitles;
+mod supervisor;
+mod sync;
+mod telnet;
+mod termcaps;
+mod termstate;
+mod textinput;
+mod throttle;
+mod trace;
+mod wall;
+mod watchdog;
+mod waveform;
 
 pub mod prelude;
 
 // Re-export modules for library usage
+pub use abloop::*;
+pub use ambient::*;
+pub use animation::*;
+pub use annotations::*;
+pub use audio::*;
+pub use autoramp::*;
+pub use bench::*;
+pub use bookmarks::*;
+pub use cache::*;
+pub use calibrate::*;
+pub use captions::*;
+pub use cast::*;
+pub use charset::*;
+pub use charsets::*;
 pub use cli::*;
+pub use clip::*;
+pub use clipboard::*;
+pub use config::*;
 pub use converter::*;
+pub use crop::*;
+pub use cvd::*;
+pub use damage::*;
 pub use decoder::*;
+pub use describe::*;
+pub use doctor::*;
+pub use edl::*;
+pub use effects::*;
+pub use encode::*;
+pub use export::*;
+pub use fps::*;
+pub use headless::*;
+pub use hooks::*;
+pub use html::*;
+pub use input::*;
+pub use inspect::*;
+pub use keymap::*;
+pub use layout::*;
+#[cfg(feature = "led-matrix")]
+pub use led::*;
+pub use look::*;
+pub use loop_point::*;
+pub use ndjson::*;
+pub use overlay::*;
+pub use palettes::*;
+pub use pipeline::*;
+pub use playlist::*;
+pub use qr::*;
+pub use raster::*;
+pub use recording::*;
+pub use refine::*;
 pub use renderer::*;
-
-use anyhow::Result;
+pub use roi::*;
+pub use scale::*;
+pub use schedule::*;
+pub use scheduling::*;
+pub use screenshot::*;
+pub use seek::*;
+pub use sequence::*;
+pub use server::*;
+pub use share::*;
+pub use skip::*;
+pub use slave::*;
+pub use sniff::*;
+pub use speedcurve::*;
+pub use stabilize::*;
+pub use status::*;
+pub use stretch::*;
+pub use subtitles::*;
+pub use supervisor::*;
+pub use sync::*;
+pub use telnet::*;
+pub use termcaps::*;
+pub use termstate::*;
+pub use textinput::*;
+pub use throttle::*;
+pub use trace::*;
+pub use wall::*;
+pub use watchdog::*;
+pub use waveform::*;
+
+use anyhow::{anyhow, Result};
 use clap::Parser;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::Event;
 use log::{debug, error, info, warn};
-use std::process::Command;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::sleep;
-
-/// SketchyBar integration helper
-struct SketchyBarIntegration {
-    item_name: String,
+use tokio::time::{interval, sleep, MissedTickBehavior};
+
+/// Seconds of video the `--title` splash stays on screen
+const TITLE_SPLASH_DURATION: Duration = Duration::from_secs(3);
+
+/// Most picture updates per second of video in reduced-motion mode
+const REDUCED_MOTION_FPS: f64 = 10.0;
+
+/// How long the warning about a capped grid stays on screen
+const GRID_WARNING_DURATION: Duration = Duration::from_secs(3);
+
+/// How long confirmations such as "Copied" stay in the status line
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
+
+/// How often the screen is refreshed while paused, hidden or showing help
+const IDLE_TICK: Duration = Duration::from_millis(50);
+
+/// How playback of a file ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaybackEnd {
+    /// The video played to its end
+    Finished,
+    /// The user quit
+    Quit,
+    /// The file could not be opened or decoded
+    Failed,
+    /// `n` or `p` skipped to another file of the playlist
+    Next,
+    Previous,
 }
 
-impl SketchyBarIntegration {
-    fn new(item_name: String) -> Self {
-        Self { item_name }
-    }
-
-    fn update_status(&self, status: &str) -> Result<()> {
-        let output = Command::new("sketchybar")
-            .args(["--set", &self.item_name, "label", status])
-            .output();
-
-        match output {
-            Ok(result) => {
-                if result.status.success() {
-                    debug!("SketchyBar updated: {} -> {}", self.item_name, status);
-                } else {
-                    warn!(
-                        "SketchyBar command failed: {}",
-                        String::from_utf8_lossy(&result.stderr)
-                    );
-                }
-            }
-            Err(e) => {
-                warn!("Failed to execute sketchybar command: {}", e);
-            }
-        }
-        Ok(())
-    }
-
-    fn set_playing(&self, filename: &str) -> Result<()> {
-        let status = format!("▶ {}", filename);
-        self.update_status(&status)
-    }
-
-    fn set_paused(&self, filename: &str) -> Result<()> {
-        let status = format!("⏸ {}", filename);
-        self.update_status(&status)
-    }
-
-    fn clear(&self) -> Result<()> {
-        self.update_status("")
-    }
+/// What stays open from file to file of a playlist
+struct Session {
+    caps: TermCaps,
+    /// Terminal background for --ambient, asked for once up front
+    background: Option<(u8, u8, u8)>,
+    slave: Option<CommandReader>,
+    sync: Option<SyncSession>,
+    keymap: Keymap,
+    /// Terminal input, read from the first file's playback on
+    input: Option<InputEvents>,
+    /// The --at or --countdown start, waited for before the first file only
+    schedule: Option<StartSchedule>,
 }
 
-impl Drop for SketchyBarIntegration {
-    fn drop(&mut self) {
-        let _ = self.clear();
-    }
+/// What woke the playback loop
+enum Wake {
+    Input(Event),
+    Command(SlaveCommand),
+    /// A pause, seek or position from the watch-together group
+    Sync(SyncMessage),
+    /// The frame timer: time for the next frame, or for an idle refresh
+    Frame,
 }
 
 /// Application state for playback control
@@ -80,6 +230,12 @@ struct PlaybackState {
     speed: f64,
     loop_enabled: bool,
     show_help: bool,
+    show_captions: bool,
+    show_stats: bool,
+    /// The terminal lost focus with --pause-when-hidden
+    hidden: bool,
+    /// Show one more frame and stay paused, for `.` and `,`
+    stepping: bool,
 }
 
 impl Default for PlaybackState {
@@ -89,6 +245,10 @@ impl Default for PlaybackState {
             speed: 1.0,
             loop_enabled: false,
             show_help: false,
+            show_captions: false,
+            show_stats: false,
+            hidden: false,
+            stepping: false,
         }
     }
 }
@@ -98,8 +258,11 @@ async fn main() -> Result<()> {
     // Initialize logging
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    // Restore the terminal before reporting panics outside supervised work
+    install_panic_hook();
+
     // Parse command line arguments
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     // Validate CLI arguments
     if let Err(e) = cli.validate() {
@@ -112,32 +275,100 @@ async fn main() -> Result<()> {
         log::set_max_level(log::LevelFilter::Debug);
     }
 
-    info!("Starting ASCII Player v{}", env!("CARGO_PKG_VERSION"));
-    info!("Playing: {}", cli.file_path.display());
+    // A player that died mid-playback may have left this terminal in raw
+    // mode; put it back before printing anything
+    if !matches!(cli.command, Some(Commands::FixTerminal)) && termstate::recover() {
+        warn!("The terminal was left in raw mode by an earlier run and has been reset");
+    }
 
-    // Initialize SketchyBar integration if configured
-    let sketchybar = cli
-        .sketchybar_item_name()
-        .map(|item_name| SketchyBarIntegration::new(item_name.to_string()));
+    // The priority is set first so every thread started later inherits it
+    if let Some(nice) = cli.nice {
+        if let Err(e) = scheduling::set_nice(nice) {
+            warn!("Not changing priority to {}: {}", nice, e);
+        }
+    }
 
-    // Set up playback state
-    let mut state = PlaybackState {
-        speed: cli.speed,
-        loop_enabled: cli.loop_playback,
-        ..Default::default()
+    // Stage timings for the stats line, plus a Chrome trace with --trace
+    let (stage_stats, _trace_guard) = trace::init(cli.trace.as_deref())?;
+
+    // Load persistent configuration; deterministic runs must not depend on it
+    let mut config = if cli.deterministic {
+        Config::default()
+    } else {
+        Config::load_or_default()
     };
 
+    if let Some(ref command) = cli.command {
+        return run_command(command, config);
+    }
+
+    // With several files, or a directory, everything up to playback looks
+    // at the first
+    let mut playlist = cli.playlist()?;
+    if let Some(first) = playlist.current() {
+        cli.file_path = Some(first.to_path_buf());
+    }
+
+    // Effects from --effect, --profile or the config file
+    if let Err(e) = cli.apply_effects(&config) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(ref path) = cli.export {
+        let share = cli.share_endpoint(&config);
+        if let Some(ref endpoint) = share {
+            share::check_shareable(path, endpoint)?;
+        }
+        export::export(&cli, &config, path)?;
+        if let Some(ref endpoint) = share {
+            println!("Shared at {}", share::upload(path, endpoint)?);
+        }
+        return Ok(());
+    }
+
+    if let Some(ref dir) = cli.dump_ansi {
+        return export::dump_ansi(&cli, &config, dir);
+    }
+
+    if cli.deterministic {
+        return headless::run_deterministic(&cli, &config);
+    }
+
+    if cli.describe {
+        return describe::run_describe(&cli).await;
+    }
+
+    if let Some(layout) = cli.wall {
+        return wall::run_wall(&cli, &config, layout).await;
+    }
+
+    #[cfg(feature = "led-matrix")]
+    if let Some(ref target) = cli.led {
+        return led::run_led(&cli, &config, target).await;
+    }
+
+    if is_recording(cli.input_path()) {
+        return recording::replay(&cli, cli.input_path()).await;
+    }
+
+    if cli.serve.is_some() {
+        return server::run(&cli, &config).await;
+    }
+
+    info!("Starting ASCII Player v{}", env!("CARGO_PKG_VERSION"));
+
     // If info-only mode, skip terminal initialization and just get video info
     if cli.info_only {
         info!("Info-only mode: loading video information");
-        let frame_iter = load_video(&cli.file_path, cli.start_time, cli.end_time)?;
+        let frame_iter = cli.open_video()?;
 
         let video_fps = frame_iter.decoder().fps();
         let video_duration = frame_iter.decoder().duration();
         let (video_width, video_height) = frame_iter.decoder().dimensions();
 
         println!("Video Information:");
-        println!("  File: {}", cli.file_path.display());
+        println!("  File: {}", cli.input_path().display());
         println!("  Dimensions: {}x{}", video_width, video_height);
         println!("  Frame Rate: {:.2} FPS", video_fps);
         println!("  Duration: {:.2} seconds", video_duration);
@@ -151,7 +382,7 @@ async fn main() -> Result<()> {
     // If single-frame mode, decode one frame and show ASCII output
     if cli.single_frame {
         info!("Single frame mode: testing frame decoding and conversion");
-        let mut frame_iter = load_video(&cli.file_path, cli.start_time, cli.end_time)?;
+        let mut frame_iter = cli.open_video()?;
 
         let video_fps = frame_iter.decoder().fps();
         let (video_width, video_height) = frame_iter.decoder().dimensions();
@@ -161,9 +392,20 @@ async fn main() -> Result<()> {
             video_width, video_height, video_fps
         );
 
+        let crop = cli.autocrop()?;
+        if let Some(rect) = crop {
+            println!(
+                "Autocrop: {}x{} at +{}+{}",
+                rect.width, rect.height, rect.x, rect.y
+            );
+        }
+
         // Get first frame
         if let Some(frame_result) = frame_iter.next() {
-            let frame = frame_result?;
+            let mut frame = frame_result?;
+            if let Some(rect) = crop {
+                frame = crop_frame(&frame, rect)?;
+            }
             println!(
                 "Decoded frame: {}x{}, {} bytes",
                 frame.width,
@@ -172,16 +414,9 @@ async fn main() -> Result<()> {
             );
 
             // Convert to ASCII with terminal size 80x24
-            let conversion_config = ConversionConfig {
-                palette: cli.palette.clone(),
-                transparent: cli.transparent,
-                alpha_threshold: cli.alpha_threshold,
-                ascii_chars: cli.get_ascii_chars().to_vec(),
-                ..Default::default()
-            };
-            let converter = FrameConverter::new(conversion_config);
+            let converter = cli.build_converter(&config, &TermCaps::default())?;
 
-            let ascii_frame = converter.convert_frame(&frame, 80, 24)?;
+            let ascii_frame = converter.convert(&frame, 80, 24)?;
             println!(
                 "ASCII frame: {}x{}, {} chars",
                 ascii_frame.width,
@@ -209,8 +444,137 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    // Probe the terminal before anything else reads its input
+    let caps = TermCaps::detect();
+    let background = cli.ambient.map(|_| terminal_background());
+
+    // Commands from a frontend on stdin or, for Neovim, FIFOs for commands
+    // and replies announced before the first frame
+    #[cfg(unix)]
+    let control_fifos = if cli.nvim {
+        Some((
+            ControlFifo::create("commands")?,
+            ControlFifo::create("replies")?,
+        ))
+    } else {
+        None
+    };
+    #[cfg(unix)]
+    let slave = match control_fifos {
+        Some((ref commands, ref replies)) => {
+            println!("{}{}", FIFO_ANNOUNCEMENT, commands.path().display());
+            println!("{}{}", REPLY_ANNOUNCEMENT, replies.path().display());
+            slave::reply_to_fifo(replies.path().to_path_buf());
+            Some(CommandReader::from_fifo(commands.path().to_path_buf()))
+        }
+        None => cli.slave_mode.then(CommandReader::spawn),
+    };
+    #[cfg(not(unix))]
+    let slave = cli.slave_mode.then(CommandReader::spawn);
+
+    // The watch-together group, connected before the terminal is taken over
+    let sync = cli.sync_session().await?;
+
+    // Playback keys, with the config file's and --bind's over the defaults
+    let keymap = cli.keymap(&config)?;
+
+    // The scheduled start is worked out once, so later files don't wait
+    // for it again
+    let mut session = Session {
+        caps,
+        background,
+        slave,
+        sync,
+        keymap,
+        input: None,
+        schedule: cli.start_schedule(),
+    };
+
+    // Each file of a playlist gets a fresh decoder, terminal and status line
+    loop {
+        let end = play(&cli, &mut config, &stage_stats, &mut session, &playlist).await?;
+        match end {
+            PlaybackEnd::Quit => break,
+            PlaybackEnd::Previous => playlist.go_back(),
+            PlaybackEnd::Next | PlaybackEnd::Finished | PlaybackEnd::Failed => {
+                if end == PlaybackEnd::Failed {
+                    warn!(
+                        "Skipping {}, which could not be played",
+                        cli.input_path().display()
+                    );
+                    playlist.mark_failed();
+                }
+                if !playlist.advance() {
+                    break;
+                }
+            }
+        }
+        match playlist.current() {
+            Some(path) => cli.file_path = Some(path.to_path_buf()),
+            None => break,
+        }
+    }
+
+    let failed: Vec<&Path> = playlist.failed().collect();
+    if !failed.is_empty() {
+        warn!(
+            "Could not play {} of {} files:",
+            failed.len(),
+            playlist.len()
+        );
+        for path in failed {
+            warn!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Play the file `cli` points at until it ends, the user quits, or skips
+/// to another file of the playlist
+async fn play(
+    cli: &Cli,
+    config: &mut Config,
+    stage_stats: &StageStats,
+    session: &mut Session,
+    playlist: &Playlist,
+) -> Result<PlaybackEnd> {
+    let Session {
+        ref caps,
+        background,
+        ref mut slave,
+        ref mut sync,
+        ref keymap,
+        ref mut input,
+        ref mut schedule,
+    } = *session;
+    info!("Playing: {}", cli.input_path().display());
+    // Threads started for this file must not inherit the render thread's CPU;
+    // it is pinned again once they are running
+    scheduling::release_thread();
+
+    // SketchyBar item and terminal title, if requested
+    let mut reporters = cli.status_reporters();
+
+    // Set up playback state
+    let mut state = PlaybackState {
+        speed: cli.speed,
+        loop_enabled: cli.loop_playback,
+        show_captions: cli.captions,
+        ..Default::default()
+    };
+
+    // Completion/error cues
+    let mut hooks = cli.event_hooks();
+    let input_path = cli.input_path().to_path_buf();
+
     // Create renderer
-    let mut renderer = Renderer::new(cli.transparent, cli.use_color())?;
+    let reduced_motion = cli.reduced_motion(config);
+    let mut renderer = Renderer::new(cli.transparent(), cli.use_color())?
+        .with_color_depth(caps.color_depth)
+        .with_synchronized_output(caps.synchronized_output)
+        .with_reduced_motion(reduced_motion)
+        .with_size(cli.width, cli.height)
+        .with_focus_events(cli.pause_when_hidden);
 
     // Initialize renderer with error handling
     if let Err(e) = renderer.init() {
@@ -224,11 +588,21 @@ async fn main() -> Result<()> {
     renderer.display_loading("Loading video...")?;
 
     // Load video
-    let mut frame_iter = match load_video(&cli.file_path, cli.start_time, cli.end_time) {
+    let frame_iter = match cli.open_video() {
         Ok(iter) => iter,
         Err(e) => {
             renderer.display_error(&format!("Failed to load video: {}", e))?;
             tokio::time::sleep(Duration::from_secs(3)).await;
+            renderer.cleanup()?;
+            hooks.emit(&PlaybackEvent::Error {
+                path: input_path,
+                message: e.to_string(),
+            });
+            // The rest of a playlist plays on
+            if playlist.len() > 1 {
+                error!("Failed to load video: {}", e);
+                return Ok(PlaybackEnd::Failed);
+            }
             return Err(e);
         }
     };
@@ -237,75 +611,423 @@ async fn main() -> Result<()> {
     let video_fps = frame_iter.decoder().fps();
     let video_duration = frame_iter.decoder().duration();
     let (video_width, video_height) = frame_iter.decoder().dimensions();
+    // Where the number keys jump, 0% to 90% of the way through
+    let percent_points: Vec<Option<f64>> = (0..10)
+        .map(|digit| frame_iter.time_at(digit as f64 / 10.0))
+        .collect();
 
     info!(
         "Video info: {}x{}, {:.2} FPS, {:.2}s duration",
         video_width, video_height, video_fps, video_duration
     );
 
-    // Set up frame converter
-    let conversion_config = ConversionConfig {
-        palette: cli.palette.clone(),
-        transparent: cli.transparent,
-        alpha_threshold: cli.alpha_threshold,
-        ascii_chars: cli.get_ascii_chars().to_vec(),
-        ..Default::default()
+    // Set up frame converter; the palette and ramp can change as it plays
+    let mut look = Look::new(
+        cli.conversion_config(config, caps, background)?,
+        caps.unicode,
+    )
+    .with_ramp_following_palette(!cli.charset_chosen(config));
+    let mut converter = cli.converter_for(look.config().clone())?;
+
+    // Paused frames and stills are converted again by the glyph matcher;
+    // motion style has no meaning for a single frame
+    let mut refiner = if cli.refine && cli.style != Style::Motion {
+        Some(Arc::new(GlyphConverter::new(look.config().clone())))
+    } else {
+        None
+    };
+    let mut last_source: Option<Arc<VideoFrame>> = None;
+    let mut refine_job: Option<RefineJob> = None;
+    let mut refined = false;
+    // Set when the palette or ramp changes, to convert a paused frame again
+    let mut restyled = false;
+
+    // Black bars are detected once up front and cropped from every frame
+    let crop = match cli.autocrop() {
+        Ok(crop) => crop,
+        Err(e) => {
+            warn!("Autocrop detection failed: {}", e);
+            None
+        }
     };
-    let converter = FrameConverter::new(conversion_config);
+    if let Some(rect) = crop {
+        info!(
+            "Autocrop: {}x{} at +{}+{}",
+            rect.width, rect.height, rect.x, rect.y
+        );
+    }
+    // Decoding, cropping and stabilizing run ahead on their own threads;
+    // converting and rendering stay here with the terminal and the keys
+    let filter = cli.frame_filter(crop);
+    let mut frames = cli.frame_pipeline(frame_iter, &filter);
+    let mut captions = Vec::new();
+
+    // Arrow key seeks land on a frame decoded ahead by a second decoder;
+    // cut lists change where frames land, so they go without the preview
+    let mut held_seek = HeldSeek::new();
+    let prefetcher =
+        (cli.edl.is_none() && !cli.is_live()).then(|| SeekPrefetcher::spawn(cli.input_path()));
+    let mut preview: Option<VideoFrame> = None;
+    // The pipeline decodes the previewed frame again; it is skipped once
+    let mut preview_shown: Option<f64> = None;
 
     // Get filename for status display
     let filename = cli
-        .file_path
+        .input_path()
         .file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("unknown");
+    let title = match playlist.status() {
+        Some(position) => format!("{} ({})", filename, position),
+        None => filename.to_string(),
+    };
+
+    // The input reader lives on from file to file, as it can't be stopped
+    // while it waits for a key
+    let input = input.get_or_insert_with(InputEvents::spawn);
 
-    // Update SketchyBar
-    if let Some(ref sb) = sketchybar {
-        sb.set_playing(filename)?;
+    // Hold on a countdown screen until the scheduled start
+    if let Some(schedule) = schedule.take() {
+        info!(
+            "Waiting {:.0}s before playback",
+            schedule.remaining().as_secs_f64()
+        );
+        while !schedule.is_due() {
+            let key = tokio::time::timeout(Duration::from_millis(50), input.next()).await;
+            if let Ok(Some(Event::Key(key_event))) = key {
+                if keymap.action(&key_event) == Some(Action::Quit) {
+                    info!("Scheduled start cancelled by user");
+                    renderer.cleanup()?;
+                    return Ok(PlaybackEnd::Quit);
+                }
+            }
+
+            let (term_width, term_height) = renderer.dimensions();
+            let mut screen = blank_frame(term_width, term_height);
+            draw_big_text(&mut screen, &schedule.overlay_text(), Placement::Center);
+            renderer.render_frame_with_status(&screen, &schedule.status())?;
+        }
     }
 
     // Main playback loop
     let mut frame_count = 0u64;
     let playback_start = Instant::now();
     let effective_fps = cli.fps.unwrap_or(video_fps);
+    let mut playback_error: Option<String> = None;
+    let mut reached_end = false;
+
+    // Later passes of a loop replay converted frames from memory
+    let mut loop_cache = cli.loop_cache();
+    let mut replaying = false;
+    let mut last_timestamp = 0.0;
+
+    // Seek the frames to a time and play on from there; evaluates to
+    // whether playback moved, which live input never does
+    macro_rules! seek_to {
+        ($target:expr) => {
+            seek_video(
+                cli,
+                &mut frames,
+                $target,
+                video_duration,
+                &filter,
+                &mut loop_cache,
+            )
+            .map(|seeked| {
+                if let Some(position) = seeked {
+                    last_timestamp = position;
+                    replaying = false;
+                }
+                seeked.is_some()
+            })
+        };
+    }
+
+    // Terminals beyond --max-cells are converted at a reduced grid
+    let mut capped_grid: Option<(u16, u16)> = None;
+    let mut grid_warning_until: Option<Instant> = None;
+    // Brightness, contrast and gamma stay over the picture for a moment
+    // after they change
+    let mut adjustments_until: Option<Instant> = None;
+
+    // Timestamp of the last frame shown when frames are skipped for reduced
+    // motion or --max-bandwidth
+    let mut last_shown: Option<f64> = None;
+
+    // The previous decoded frame as converted, before overlays, which the
+    // next one's damage is relative to; None when the next frame does not
+    // follow it on screen
+    let mut last_converted: Option<AsciiFrame> = None;
+    // Overlays cover cells the damage knows nothing about
+    let mut overlaid = false;
+
+    // A panic converting or rendering a frame skips that frame, as does
+    // taking longer than --frame-timeout
+    let mut supervisor = Supervisor::new();
+    let mut watchdog = cli.frame_watchdog();
+
+    // The --title splash and the --qr code of the source, shown once from
+    // the start of the video
+    let mut overlays = TimedOverlay::new();
+    if let Some(ref title) = cli.title {
+        let content = OverlayContent::BigText(title.clone(), Placement::Center);
+        overlays.add(0.0, TITLE_SPLASH_DURATION.as_secs_f64(), content);
+    }
+    if cli.qr {
+        let lines = qr_lines(&source_location(cli.input_path()))?;
+        let content = OverlayContent::Corner(lines, Corner::TopRight);
+        overlays.add(0.0, QR_DURATION.as_secs_f64(), content);
+    }
+
+    // Audio loudness for --waveform and --skip-silence, measured in the background
+    let mut waveform =
+        (cli.waveform || cli.skip_silence).then(|| WaveformLoader::spawn(&input_path));
+    let mut black_skipper = cli.skip_black.then(BlackSkipper::new);
+
+    // Short videos loop where they start repeating themselves, found on the
+    // first pass unless saved from an earlier run
+    let trimmed = cli.start_time.is_some() || cli.end_time.is_some() || cli.edl.is_some();
+    let short_loop = !trimmed && video_duration > 0.0 && video_duration <= MAX_LOOP_DURATION;
+    let mut loop_end = None;
+    let mut loop_detector = None;
+    if short_loop {
+        match config.loop_point(&input_path) {
+            Some(loop_point) => loop_end = loop_point.end,
+            None => loop_detector = Some(LoopDetector::new()),
+        }
+    }
+
+    // The audio track, played in step with the frames; cameras are captured
+    // without sound
+    let audio = if cli.mute || cli.is_live() {
+        None
+    } else {
+        match AudioPlayer::open(&input_path) {
+            Ok(audio) => Some(audio.with_pitch_correction(cli.audio_pitch_correction)),
+            Err(e) => {
+                info!("Playing without sound: {}", e);
+                None
+            }
+        }
+    };
+
+    // Speed keyframes from --speed-curve, on top of the speed set by hand
+    let speed_curve = cli
+        .speed_curve
+        .as_deref()
+        .map(SpeedCurve::load)
+        .transpose()?;
+
+    // The frame on screen, for screenshots and copying
+    let mut last_frame: Option<AsciiFrame> = None;
+    let mut notice: Option<(String, Instant)> = None;
+    if let Some(addr) = sync.as_ref().and_then(SyncSession::local_addr) {
+        let text = format!("Hosting watch-together on {}", addr);
+        notice = Some((text, Instant::now() + NOTICE_DURATION));
+    }
+
+    // Name being typed for a new bookmark; keys go to it while it is open
+    let mut bookmark_prompt: Option<BookmarkPrompt> = None;
+
+    // Set by `n` and `p` to leave this file for another of the playlist
+    let mut skip_to: Option<PlaybackEnd> = None;
+
+    // Saved notes, shown as playback passes them, and the one being typed
+    let mut notes = TimedOverlay::new();
+    for annotation in config.annotations(&input_path) {
+        schedule_annotation(&mut notes, annotation);
+    }
+    let mut annotation_prompt: Option<AnnotationPrompt> = None;
+
+    // Range marked with [ and ], looped and exported as a clip
+    let mut ab_loop = AbLoop::new();
+    let mut clip_job: Option<ClipJob> = None;
+
+    hooks.emit(&PlaybackEvent::Started {
+        path: input_path.clone(),
+    });
+
+    // Converting and rendering run on this thread
+    scheduling::pin_thread_or_warn(cli.render_cpu, "render");
+
+    // The loop sleeps until terminal input, a control command or the frame
+    // timer wakes it. Input and commands are handled between frames; each
+    // frame sets the timer to when the next one is due.
+    let mut frame_timer = interval(calculate_frame_delay(effective_fps, 1.0));
+    frame_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Frames actually drawn per second, shown against the target
+    let mut fps_meter = FpsMeter::new();
+    // Output measured against --max-bandwidth, drawn more cheaply while over it
+    let mut throttle = cli.max_bandwidth.map(OutputThrottle::new);
 
     loop {
+        let wake = tokio::select! {
+            biased;
+            Some(event) = input.next() => Wake::Input(event),
+            Some(command) = next_command(slave) => Wake::Command(command),
+            Some(message) = next_sync(sync) => Wake::Sync(message),
+            _ = frame_timer.tick() => Wake::Frame,
+        };
+
+        // Follow the watch-together group; what arrives from it isn't sent back
+        if let Wake::Sync(message) = wake {
+            let (paused, target) = message.follow(last_timestamp, state.paused);
+            state.paused = paused;
+            if let Some(target) = target {
+                seek_to!(target)?;
+            }
+            continue;
+        }
+        let (was_paused, was_at) = (state.paused, last_timestamp);
+
+        // Handle commands on stdin
+        if let Wake::Command(ref command) = wake {
+            let target = match *command {
+                SlaveCommand::Quit => {
+                    info!("Quit requested on stdin");
+                    break;
+                }
+                SlaveCommand::Screenshot(ref path) => {
+                    let saved = take_screenshot(
+                        last_frame.as_ref(),
+                        path.as_deref(),
+                        &input_path,
+                        &cli.screenshot_dir,
+                        cli.screenshot_png,
+                    );
+                    match saved {
+                        Ok(paths) => slave::reply(&format!("screenshot={}", paths[0].display())),
+                        Err(e) => slave::reply_error(&e),
+                    }
+                    continue;
+                }
+                SlaveCommand::Admin(AdminCommand::Pause) => {
+                    state.paused = true;
+                    None
+                }
+                SlaveCommand::Admin(AdminCommand::Resume) => {
+                    state.paused = false;
+                    None
+                }
+                SlaveCommand::Admin(AdminCommand::Toggle) => {
+                    state.paused = !state.paused;
+                    None
+                }
+                SlaveCommand::Admin(AdminCommand::Seek(position)) => Some(position),
+                SlaveCommand::Admin(AdminCommand::SeekBy(offset)) => Some(last_timestamp + offset),
+                SlaveCommand::Admin(AdminCommand::Status) => None,
+            };
+
+            if let Some(target) = target {
+                // A failed seek is the frontend's error, not the end of playback
+                if let Err(e) = seek_to!(target) {
+                    warn!("Seek to {:.2}s failed: {:#}", target, e);
+                    slave::reply_error(&e);
+                    continue;
+                }
+            }
+            slave::reply(&format!(
+                "position={:.2} paused={} speed={:.2}",
+                last_timestamp, state.paused, state.speed
+            ));
+        }
+
         // Handle input events
-        if event::poll(Duration::from_millis(1))? {
-            match event::read()? {
-                Event::Key(key_event) => match key_event.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
+        if let Wake::Input(ref event) = wake {
+            match event {
+                Event::Key(key_event) if bookmark_prompt.is_some() => {
+                    let prompt = bookmark_prompt.as_mut().expect("prompt is open");
+                    match prompt.handle_key(key_event.code) {
+                        InputResult::Editing => {}
+                        InputResult::Cancelled => bookmark_prompt = None,
+                        InputResult::Submitted => {
+                            let bookmark = prompt.bookmark();
+                            bookmark_prompt = None;
+                            let text = format!(
+                                "Bookmarked {} at {}",
+                                bookmark.name,
+                                format_clock(bookmark.time)
+                            );
+                            config.add_bookmark(&input_path, bookmark);
+                            let text = match config.save() {
+                                Ok(_) => text,
+                                Err(e) => {
+                                    warn!("Failed to save bookmark: {}", e);
+                                    format!("Failed to save bookmark: {}", e)
+                                }
+                            };
+                            info!("{}", text);
+                            notice = Some((text, Instant::now() + NOTICE_DURATION));
+                        }
+                    }
+                }
+                Event::Key(key_event) if annotation_prompt.is_some() => {
+                    let prompt = annotation_prompt.as_mut().expect("prompt is open");
+                    let text = match prompt.handle_key(key_event.code) {
+                        InputResult::Editing => None,
+                        InputResult::Cancelled => Some("Note discarded".to_string()),
+                        InputResult::Submitted => Some(match prompt.annotation() {
+                            Some(annotation) => {
+                                let text = format!("Noted at {}", format_clock(annotation.time));
+                                schedule_annotation(&mut notes, &annotation);
+                                config.add_annotation(&input_path, annotation);
+                                match config.save() {
+                                    Ok(_) => text,
+                                    Err(e) => {
+                                        warn!("Failed to save note: {}", e);
+                                        format!("Failed to save note: {}", e)
+                                    }
+                                }
+                            }
+                            None => "Empty note discarded".to_string(),
+                        }),
+                    };
+                    if let Some(text) = text {
+                        annotation_prompt = None;
+                        info!("{}", text);
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                }
+                Event::Key(key_event) => match keymap.action(key_event) {
+                    Some(Action::Quit) => {
                         info!("Quit requested by user");
                         break;
                     }
-                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        info!("Ctrl+C pressed, exiting");
+                    Some(Action::NextFile) if playlist.has_next() => {
+                        info!("Skipping to the next file");
+                        skip_to = Some(PlaybackEnd::Next);
+                        break;
+                    }
+                    Some(Action::PreviousFile) if playlist.has_previous() => {
+                        info!("Going back to the previous file");
+                        skip_to = Some(PlaybackEnd::Previous);
                         break;
                     }
-                    KeyCode::Char(' ') => {
+                    Some(action @ (Action::NextFile | Action::PreviousFile)) => {
+                        let text = if action == Action::NextFile {
+                            "No next file"
+                        } else {
+                            "No previous file"
+                        };
+                        notice = Some((text.to_string(), Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Pause) => {
                         state.paused = !state.paused;
                         if state.paused {
                             info!("Playback paused");
-                            if let Some(ref sb) = sketchybar {
-                                sb.set_paused(filename)?;
-                            }
                         } else {
                             info!("Playback resumed");
-                            if let Some(ref sb) = sketchybar {
-                                sb.set_playing(filename)?;
-                            }
                         }
                     }
-                    KeyCode::Char('+') | KeyCode::Char('=') => {
+                    Some(Action::SpeedUp) => {
                         state.speed = (state.speed * 1.25).min(4.0);
                         info!("Speed increased to {:.2}x", state.speed);
                     }
-                    KeyCode::Char('-') => {
+                    Some(Action::SpeedDown) => {
                         state.speed = (state.speed / 1.25).max(0.25);
                         info!("Speed decreased to {:.2}x", state.speed);
                     }
-                    KeyCode::Char('l') => {
+                    Some(Action::Loop) => {
                         state.loop_enabled = !state.loop_enabled;
                         info!(
                             "Loop {}",
@@ -316,13 +1038,287 @@ async fn main() -> Result<()> {
                             }
                         );
                     }
-                    KeyCode::Char('h') => {
+                    Some(Action::Stats) => {
+                        state.show_stats = !state.show_stats;
+                    }
+                    Some(Action::Screenshot) => {
+                        let text = match last_frame {
+                            Some(ref frame) => {
+                                let stem = screenshot_stem(&input_path, frame.timestamp);
+                                let saved = save_screenshot_files(
+                                    frame,
+                                    &cli.screenshot_dir,
+                                    &stem,
+                                    cli.screenshot_png,
+                                );
+                                match saved {
+                                    Ok(paths) => {
+                                        let names: Vec<String> = paths
+                                            .iter()
+                                            .filter_map(|path| path.file_name())
+                                            .map(|name| name.to_string_lossy().into_owned())
+                                            .collect();
+                                        format!("Saved {}", names.join(", "))
+                                    }
+                                    Err(e) => {
+                                        warn!("Failed to save screenshot: {}", e);
+                                        format!("Failed to save screenshot: {}", e)
+                                    }
+                                }
+                            }
+                            None => "No frame to save yet".to_string(),
+                        };
+                        info!("{}", text);
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Help) => {
                         state.show_help = !state.show_help;
                     }
-                    KeyCode::Char('r') => {
+                    Some(action @ (Action::Copy | Action::CopyColors)) => {
+                        let with_colors = action == Action::CopyColors;
+                        let text = match last_frame {
+                            Some(ref frame) => {
+                                clipboard::copy_to_clipboard(
+                                    &mut std::io::stdout(),
+                                    &clipboard::frame_text(frame, with_colors),
+                                )?;
+                                if with_colors {
+                                    "Copied frame with colors"
+                                } else {
+                                    "Copied frame"
+                                }
+                            }
+                            None => "No frame to copy yet",
+                        };
+                        info!("{}", text);
+                        notice = Some((text.to_string(), Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(
+                        Action::Bookmark
+                        | Action::NextBookmark
+                        | Action::PreviousBookmark
+                        | Action::Note
+                        | Action::NextNote
+                        | Action::PreviousNote
+                        | Action::LoopStart
+                        | Action::LoopEnd,
+                    ) if cli.is_live() => {
+                        let text = "Live input has no timeline to mark".to_string();
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Bookmark) => {
+                        let existing = config.bookmarks(&input_path).len();
+                        bookmark_prompt = Some(BookmarkPrompt::new(last_timestamp, existing));
+                    }
+                    Some(
+                        action @ (Action::NextBookmark
+                        | Action::PreviousBookmark
+                        | Action::NextNote
+                        | Action::PreviousNote),
+                    ) => {
+                        let forward = matches!(action, Action::NextBookmark | Action::NextNote);
+                        let (target, kind) =
+                            if matches!(action, Action::NextBookmark | Action::PreviousBookmark) {
+                                let bookmarks = config.bookmarks(&input_path);
+                                (jump_to_mark(bookmarks, last_timestamp, forward), "bookmark")
+                            } else {
+                                let notes = config.annotations(&input_path);
+                                (jump_to_mark(notes, last_timestamp, forward), "note")
+                            };
+                        let text = match target {
+                            Some((time, text)) => {
+                                seek_to!(time)?;
+                                text
+                            }
+                            None if forward => format!("No later {}", kind),
+                            None => format!("No earlier {}", kind),
+                        };
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Note) => {
+                        state.paused = true;
+                        annotation_prompt = Some(AnnotationPrompt::new(last_timestamp));
+                    }
+                    Some(action @ (Action::LoopStart | Action::LoopEnd)) => {
+                        let label = if action == Action::LoopStart {
+                            ab_loop.set_a(last_timestamp);
+                            "Loop start (A)"
+                        } else {
+                            ab_loop.set_b(last_timestamp);
+                            "Loop end (B)"
+                        };
+                        let text = format!("{} at {}", label, format_clock(last_timestamp));
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::ClearLoop) => {
+                        ab_loop.clear();
+                        notice =
+                            Some(("Loop cleared".to_string(), Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::ExportClip) => {
+                        let range = marked_range(
+                            ab_loop.a(),
+                            ab_loop.b(),
+                            config.bookmarks(&input_path),
+                            last_timestamp,
+                        );
+                        let text = match range {
+                            _ if clip_job.is_some() => {
+                                "A clip is already being exported".to_string()
+                            }
+                            Some(range) => {
+                                let job = ClipJob::spawn(
+                                    &input_path,
+                                    range,
+                                    clip_path(&input_path, range),
+                                );
+                                let text = format!("Exporting {}", job.output().display());
+                                clip_job = Some(job);
+                                text
+                            }
+                            None => "Mark a range with [ and ] first".to_string(),
+                        };
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(
+                        action @ (Action::Palette
+                        | Action::Charset
+                        | Action::BrightnessUp
+                        | Action::BrightnessDown
+                        | Action::ContrastUp
+                        | Action::ContrastDown
+                        | Action::GammaUp
+                        | Action::GammaDown),
+                    ) => {
+                        let adjustment = match action {
+                            Action::BrightnessUp => Some((Adjustment::Brightness, 1)),
+                            Action::BrightnessDown => Some((Adjustment::Brightness, -1)),
+                            Action::ContrastUp => Some((Adjustment::Contrast, 1)),
+                            Action::ContrastDown => Some((Adjustment::Contrast, -1)),
+                            Action::GammaUp => Some((Adjustment::Gamma, 1)),
+                            Action::GammaDown => Some((Adjustment::Gamma, -1)),
+                            _ => None,
+                        };
+                        match adjustment {
+                            Some((adjustment, steps)) => {
+                                look.adjust(adjustment, steps);
+                                info!("{}", look.adjustments());
+                                adjustments_until = Some(Instant::now() + NOTICE_DURATION);
+                            }
+                            None => {
+                                let text = if action == Action::Palette {
+                                    let text = look.cycle_palette();
+                                    renderer.set_use_colors(
+                                        cli.palette_uses_color(&look.config().palette),
+                                    );
+                                    text
+                                } else {
+                                    look.cycle_ramp()
+                                };
+                                info!("{}", text);
+                                notice = Some((text, Instant::now() + NOTICE_DURATION));
+                            }
+                        }
+                        converter = cli.converter_for(look.config().clone())?;
+                        if let Some(ref mut refiner) = refiner {
+                            *refiner = Arc::new(GlyphConverter::new(look.config().clone()));
+                        }
+                        // Frames converted the old way can't be reused
+                        last_converted = None;
+                        if let Some(ref mut cache) = loop_cache {
+                            cache.discard();
+                        }
+                        refine_job = None;
+                        refined = false;
+                        restyled = true;
+                    }
+                    Some(Action::Captions) => {
+                        state.show_captions = !state.show_captions;
+                        info!(
+                            "Closed captions {}",
+                            if state.show_captions {
+                                "shown"
+                            } else {
+                                "hidden"
+                            }
+                        );
+                    }
+                    Some(
+                        Action::SeekBack
+                        | Action::SeekForward
+                        | Action::SeekBackLong
+                        | Action::SeekForwardLong,
+                    ) if cli.is_live() => {
+                        let text = "Live input can't seek".to_string();
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(
+                        action @ (Action::SeekBack
+                        | Action::SeekForward
+                        | Action::SeekBackLong
+                        | Action::SeekForwardLong),
+                    ) => {
+                        let step = match action {
+                            Action::SeekBack => -SEEK_STEP,
+                            Action::SeekForward => SEEK_STEP,
+                            Action::SeekBackLong => -SEEK_STEP_LONG,
+                            _ => SEEK_STEP_LONG,
+                        };
+                        let target = held_seek.press(last_timestamp, step, video_duration);
+                        if let Some(ref prefetcher) = prefetcher {
+                            prefetcher.prefetch(target, step, video_duration);
+                        }
+                        let text = format!("Seek to {}", format_clock(target));
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::StepForward) => {
+                        // Stepping while playing pauses on the next frame
+                        state.paused = true;
+                        state.stepping = true;
+                    }
+                    Some(Action::StepBack) if cli.is_live() => {
+                        let text = "Live input can't step back".to_string();
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::StepBack) => {
+                        if seek_to!(frame_before(last_timestamp, video_fps))? {
+                            last_converted = None;
+                        }
+                        state.paused = true;
+                        state.stepping = true;
+                    }
+                    Some(Action::Jump(_)) if cli.is_live() => {
+                        let text = "Live input can't seek".to_string();
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Jump(tenth)) => {
+                        let digit = tenth as usize;
+                        let text = match percent_points[digit] {
+                            Some(target) => {
+                                seek_to!(target)?;
+                                format!("{}% ({})", digit * 10, format_clock(target))
+                            }
+                            None => "Unknown length, can't jump".to_string(),
+                        };
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Restart) if cli.is_live() => {
+                        let text = "Live input can't restart".to_string();
+                        notice = Some((text, Instant::now() + NOTICE_DURATION));
+                    }
+                    Some(Action::Restart) => {
                         info!("Restarting video from beginning");
-                        frame_iter = load_video(&cli.file_path, cli.start_time, cli.end_time)?;
                         frame_count = 0;
+                        if let Some(ref sync) = *sync {
+                            sync.send(SyncMessage::Seek(0.0));
+                        }
+                        replaying = loop_cache.as_ref().is_some_and(|cache| cache.is_complete());
+                        if !replaying {
+                            frames = cli.frame_pipeline(cli.open_video()?, &filter);
+                            if let Some(ref mut cache) = loop_cache {
+                                cache.restart();
+                            }
+                        }
                     }
                     _ => {}
                 },
@@ -330,112 +1326,785 @@ async fn main() -> Result<()> {
                     debug!("Terminal resized to {}x{}", width, height);
                     renderer.update_dimensions()?;
                 }
+                Event::FocusLost if cli.pause_when_hidden => {
+                    info!("Terminal hidden, pausing");
+                    state.hidden = true;
+                }
+                Event::FocusGained if state.hidden => {
+                    info!("Terminal visible again, resuming");
+                    state.hidden = false;
+                }
                 _ => {}
             }
         }
 
+        // Tell the watch-together group about pauses and seeks made here
+        if let Some(ref sync) = *sync {
+            if last_timestamp != was_at {
+                sync.send(SyncMessage::Seek(last_timestamp));
+            }
+            if state.paused != was_paused {
+                sync.send(if state.paused {
+                    SyncMessage::Pause(last_timestamp)
+                } else {
+                    SyncMessage::Resume(last_timestamp)
+                });
+            }
+        }
+
+        // Input and commands only change state; frames wait for the timer
+        if !matches!(wake, Wake::Frame) {
+            continue;
+        }
+        // Paths that go on without waiting move straight to the next frame
+        frame_timer.reset_immediately();
+
+        if let Some(ref mut sync) = *sync {
+            sync.heartbeat(last_timestamp, state.paused);
+        }
+
+        // Report a finished clip export
+        if let Some(ref mut job) = clip_job {
+            let text = match job.poll() {
+                ClipStatus::Running(_) => None,
+                ClipStatus::Finished => Some(format!("Saved {}", job.output().display())),
+                ClipStatus::Failed(e) => Some(format!("Clip export failed: {}", e)),
+            };
+            if let Some(text) = text {
+                info!("{}", text);
+                notice = Some((text, Instant::now() + NOTICE_DURATION));
+                clip_job = None;
+            }
+        }
+
+        // Seek once the arrow keys are let go
+        if let Some(target) = held_seek.settled() {
+            if seek_to!(target)? {
+                if let Some(ref sync) = *sync {
+                    sync.send(SyncMessage::Seek(last_timestamp));
+                }
+                preview = prefetcher
+                    .as_ref()
+                    .and_then(|prefetcher| prefetcher.take(target));
+                preview_shown = None;
+            }
+        }
+
         // Show help if requested
         if state.show_help {
-            let help_text = r#"ASCII Player Controls:
+            if let Some(ref audio) = audio {
+                audio.set_playing(false);
+            }
+            renderer.display_message(&keymap.help())?;
+            frame_timer.reset_after(IDLE_TICK);
+            continue;
+        }
 
-SPACE  - Pause/Resume
-Q/ESC  - Quit
-+/=    - Increase speed
--      - Decrease speed
-L      - Toggle loop
-R      - Restart video
-H      - Toggle this help
+        // Skip frame processing if paused or out of sight, unless stepping
+        if (state.paused && !state.stepping) || state.hidden {
+            if let Some(ref audio) = audio {
+                audio.set_playing(false);
+            }
+            fps_meter.reset();
+
+            // Convert the frame on screen again after a palette or ramp change
+            let mut redraw = false;
+            if std::mem::take(&mut restyled) {
+                let shown = last_frame.as_ref().map(|frame| frame.timestamp);
+                let source = last_source
+                    .as_ref()
+                    .filter(|source| Some(source.timestamp) == shown);
+                if let Some(source) = source {
+                    let (width, height) = renderer.dimensions();
+                    last_frame = Some(converter.convert(source, width, height)?);
+                    redraw = true;
+                }
+            }
 
-Press H again to hide this help."#;
+            // Refine the frame on screen once per pause, then swap it in
+            let waiting = refine_job.is_some() || refined || state.hidden;
+            if let (Some(converter), false) = (&refiner, waiting) {
+                // Frames replayed from the loop cache have no source to refine
+                let shown = last_frame.as_ref().map(|frame| frame.timestamp);
+                let source = last_source
+                    .as_ref()
+                    .filter(|source| Some(source.timestamp) == shown);
+                if let Some(source) = source {
+                    let (width, height) = renderer.dimensions();
+                    refine_job = Some(RefineJob::spawn(
+                        Arc::clone(converter),
+                        Arc::clone(source),
+                        width,
+                        height,
+                    ));
+                }
+            }
+            if let Some(frame) = refine_job.as_ref().and_then(RefineJob::poll) {
+                debug!("Showing refined frame {}", frame.frame_number);
+                last_frame = Some(frame);
+                refine_job = None;
+                refined = true;
+                redraw = true;
+            }
 
-            renderer.display_message(help_text)?;
+            // Keep the bookmark prompt and notices visible over the still frame
+            let message = match (&bookmark_prompt, &notice) {
+                (Some(prompt), _) => Some(prompt.status()),
+                (None, Some((text, until))) if Instant::now() < *until => Some(text.clone()),
+                _ => None,
+            };
+            // Picture adjustments too, redrawing once more to clear them
+            let show_adjustments = adjustments_until.is_some_and(|until| Instant::now() < until);
+            if !show_adjustments && adjustments_until.take().is_some() {
+                redraw = true;
+            }
+            if let (Some(prompt), Some(frame)) = (&annotation_prompt, &last_frame) {
+                let mut frame = frame.clone();
+                prompt.draw(&mut frame);
+                renderer
+                    .render_frame_with_status(&frame, message.as_deref().unwrap_or_default())?;
+            } else if let (true, Some(frame)) = (show_adjustments, &last_frame) {
+                let mut frame = frame.clone();
+                look.draw_adjustments(&mut frame);
+                renderer
+                    .render_frame_with_status(&frame, message.as_deref().unwrap_or_default())?;
+            } else if let Some(frame) = last_frame.as_ref().filter(|_| message.is_some() || redraw)
+            {
+                renderer.render_frame_with_status(frame, message.as_deref().unwrap_or_default())?;
+            }
+            reporters.update(&PlaybackStatus {
+                name: filename,
+                position: last_timestamp,
+                duration: video_duration,
+                paused: true,
+            });
+            frame_timer.reset_after(IDLE_TICK);
             continue;
         }
+        refine_job = None;
+        refined = false;
+        restyled = false;
+
+        // Speed of this stretch of the video; audio can't follow a ramp
+        let speed = state.speed
+            * speed_curve
+                .as_ref()
+                .map_or(1.0, |curve| curve.at(last_timestamp));
+        let ramping = speed_curve
+            .as_ref()
+            .is_some_and(|curve| curve.ramping_at(last_timestamp));
 
-        // Skip frame processing if paused
-        if state.paused {
-            sleep(Duration::from_millis(50)).await;
-            continue;
+        // Get current terminal size
+        let (term_width, term_height) = renderer.dimensions();
+        let grid = cap_grid(term_width, term_height, cli.max_cells());
+        let capped = (grid != (term_width, term_height)).then_some(grid);
+        if capped.is_some() && capped != capped_grid {
+            warn!(
+                "Terminal is {}x{} cells, converting at {}x{} (see --max-cells)",
+                term_width, term_height, grid.0, grid.1
+            );
+            grid_warning_until = Some(Instant::now() + GRID_WARNING_DURATION);
+        }
+        capped_grid = capped;
+
+        // Jump to the end of a long silence
+        let silence = match waveform {
+            Some(ref mut loader) if cli.skip_silence => loader
+                .get()
+                .and_then(|waveform| silence_end(waveform.levels(), last_timestamp)),
+            _ => None,
+        };
+        if let Some(end) = silence {
+            info!("Skipping silence from {:.2}s", last_timestamp);
+            seek_to!(end)?;
+            let text = format!("Skipped silence to {}", format_clock(last_timestamp));
+            notice = Some((text, Instant::now() + NOTICE_DURATION));
         }
 
-        // Get next frame
-        let frame = match frame_iter.next() {
-            Some(Ok(frame)) => frame,
-            Some(Err(e)) => {
-                error!("Error reading frame: {}", e);
-                renderer.display_error(&format!("Playback error: {}", e))?;
-                sleep(Duration::from_secs(2)).await;
-                break;
-            }
-            None => {
-                // End of video
+        // Go back to A once B has been shown; live input can't go back
+        let restart = ab_loop
+            .restart_at(last_timestamp)
+            .filter(|_| !cli.is_live());
+        if let Some(start) = restart {
+            debug!(
+                "Reached B at {:.2}s, looping to {:.2}s",
+                last_timestamp, start
+            );
+            seek_to!(start)?;
+            last_converted = None;
+        }
+
+        let cached = match loop_cache {
+            Some(ref mut cache) if replaying => cache.frame(frame_count, term_width, term_height),
+            _ => None,
+        };
+        if replaying && cached.is_none() {
+            replaying = false;
+            if loop_cache.as_ref().is_some_and(|cache| cache.is_complete()) {
                 if state.loop_enabled {
-                    info!("Video ended, restarting loop");
-                    frame_iter = load_video(&cli.file_path, cli.start_time, cli.end_time)?;
+                    info!("Video ended, replaying loop from cache");
                     frame_count = 0;
+                    replaying = true;
                     continue;
-                } else {
-                    info!("Video playback completed");
-                    break;
                 }
+                info!("Video playback completed");
+                reached_end = true;
+                break;
             }
-        };
 
-        // Get current terminal size
-        let (term_width, term_height) = renderer.dimensions();
+            // The cached frames no longer fit the terminal
+            debug!("Loop cache dropped, decoding from {:.2}s", last_timestamp);
+            let mut frame_iter = cli.open_video()?;
+            frame_iter.seek(last_timestamp)?;
+            frames = cli.frame_pipeline(frame_iter, &filter);
+            continue;
+        }
 
-        // Convert frame to ASCII
-        let ascii_frame = match converter.convert_frame(&frame, term_width, term_height) {
-            Ok(frame) => frame,
-            Err(e) => {
-                error!("Error converting frame: {}", e);
-                continue;
+        // Cells that changed since the previous frame, None to redraw it all
+        let mut damaged_cells: Option<Vec<usize>> = None;
+        let mut ascii_frame = match cached {
+            Some(frame) => {
+                last_converted = None;
+                frame
+            }
+            None => {
+                // Get next frame
+                let decoded = match preview.take() {
+                    Some(frame) => {
+                        let frame = match crop {
+                            Some(rect) => crop_frame(&frame, rect),
+                            None => Ok(frame),
+                        };
+                        preview_shown = frame.as_ref().ok().map(|frame| frame.timestamp);
+                        Some(frame.map(|frame| DecodedFrame {
+                            frame,
+                            captions: Vec::new(),
+                            damage: Damage::Full,
+                        }))
+                    }
+                    None => {
+                        let skip_through = preview_shown.take();
+                        if skip_through.is_some() {
+                            // Frames decoded again after the preview are dropped
+                            last_converted = None;
+                        }
+                        loop {
+                            match frames.recv().await {
+                                Some(Ok(decoded))
+                                    if skip_through
+                                        .is_some_and(|shown| decoded.frame.timestamp <= shown) =>
+                                {
+                                    continue
+                                }
+                                next => break next,
+                            }
+                        }
+                    }
+                };
+                let next = match decoded {
+                    // The rest of the pass repeats the start
+                    Some(Ok(decoded))
+                        if state.loop_enabled
+                            && loop_end.is_some_and(|end| decoded.frame.timestamp >= end) =>
+                    {
+                        None
+                    }
+                    next => next,
+                };
+                let (frame, damage) = match next {
+                    Some(Ok(decoded)) => {
+                        captions = decoded.captions;
+                        (decoded.frame, decoded.damage)
+                    }
+                    Some(Err(e)) => {
+                        error!("Error reading frame: {}", e);
+                        renderer.display_error(&format!("Playback error: {}", e))?;
+                        sleep(Duration::from_secs(2)).await;
+                        playback_error = Some(e.to_string());
+                        break;
+                    }
+                    None => {
+                        // End of video
+                        if let Some(detector) = loop_detector.take() {
+                            let loop_point = detector.finish();
+                            if let Some(end) = loop_point.end {
+                                info!("Looping at {:.3}s", end);
+                            }
+                            loop_end = loop_point.end;
+                            config.set_loop_point(&input_path, loop_point);
+                            if !cli.deterministic {
+                                if let Err(e) = config.save() {
+                                    warn!("Failed to save loop point: {}", e);
+                                }
+                            }
+                        }
+                        if state.loop_enabled {
+                            frame_count = 0;
+                            replaying =
+                                loop_cache.as_mut().is_some_and(|cache| cache.finish_pass());
+                            if replaying {
+                                info!("Video ended, replaying loop from cache");
+                            } else {
+                                info!("Video ended, restarting loop");
+                                frames = cli.frame_pipeline(cli.open_video()?, &filter);
+                                if let Some(ref mut cache) = loop_cache {
+                                    cache.restart();
+                                }
+                            }
+                            continue;
+                        } else if refiner.is_some() && frame_count == 1 {
+                            // A still image stays on screen to be refined
+                            info!("Still image, showing it until quit");
+                            state.paused = true;
+                            continue;
+                        } else {
+                            info!("Video playback completed");
+                            reached_end = true;
+                            break;
+                        }
+                    }
+                };
+
+                if loop_detector
+                    .as_mut()
+                    .is_some_and(|detector| !detector.observe(&frame))
+                {
+                    loop_detector = None;
+                }
+
+                // Black frames past the start of a run are decoded but not shown
+                if let Some(ref mut skipper) = black_skipper {
+                    let black = is_black(&frame);
+                    if !black {
+                        if let Some(skipped) = skipper.finish_run(frame.timestamp) {
+                            let text = format!("Skipped {:.1}s of black", skipped);
+                            info!("{}", text);
+                            notice = Some((text, Instant::now() + NOTICE_DURATION));
+                        }
+                    }
+                    if skipper.skip(frame.timestamp, black) {
+                        last_timestamp = frame.timestamp;
+                        last_converted = None;
+                        continue;
+                    }
+                }
+
+                // Frames the clock passed while one overran are not converted
+                if watchdog.behind(frame.timestamp) {
+                    last_timestamp = frame.timestamp;
+                    last_converted = None;
+                    continue;
+                }
+
+                // Convert frame to ASCII
+                let previous = last_converted.take();
+                let started = Instant::now();
+                let converted = supervisor.run("converting", || match previous {
+                    Some(ref previous) => converter.convert_damaged(
+                        &frame,
+                        previous,
+                        &damage,
+                        term_width,
+                        term_height,
+                    ),
+                    None => converter
+                        .convert(&frame, term_width, term_height)
+                        .map(|frame| (frame, None)),
+                });
+                let ascii_frame = match converted {
+                    Some(Ok((frame, cells))) => {
+                        damaged_cells = cells;
+                        frame
+                    }
+                    Some(Err(e)) => {
+                        error!("Error converting frame: {}", e);
+                        continue;
+                    }
+                    None => continue,
+                };
+                // A slow frame is still drawn, but the ones after it are late
+                watchdog.overran("converting", started, frame.timestamp, speed);
+                last_converted = Some(ascii_frame.clone());
+
+                if let Some(ref mut cache) = loop_cache {
+                    cache.record(frame_count, &ascii_frame, (term_width, term_height));
+                }
+                last_source = Some(Arc::new(frame));
+                ascii_frame
             }
         };
+        last_timestamp = ascii_frame.timestamp;
+
+        // Reduced motion keeps frames on screen longer, as does a throttle
+        // drawing every nth frame; skipped frames still take their time so
+        // playback speed is unchanged
+        let frame_divisor = throttle
+            .as_ref()
+            .map_or(1, |throttle| throttle.fidelity().frame_divisor);
+        let mut shown_every = 0.0;
+        if reduced_motion {
+            shown_every = 1.0 / REDUCED_MOTION_FPS;
+        }
+        if frame_divisor > 1 {
+            // Half a frame short, so every nth frame makes it despite rounding
+            shown_every = f64::max(shown_every, (frame_divisor as f64 - 0.5) / effective_fps);
+        }
+        if shown_every > 0.0 {
+            let since_shown = last_shown.map(|shown| ascii_frame.timestamp - shown);
+            if since_shown.is_some_and(|since| (0.0..shown_every).contains(&since)) {
+                last_converted = None;
+                frame_count += 1;
+                frame_timer.reset_after(calculate_frame_delay(effective_fps * speed, 1.0));
+                continue;
+            }
+            last_shown = Some(ascii_frame.timestamp);
+        }
+
+        let was_overlaid = std::mem::replace(&mut overlaid, false);
+        if state.show_captions && !captions.is_empty() {
+            draw_text(&mut ascii_frame, &captions, Placement::Bottom);
+            overlaid = true;
+        }
+
+        overlays.prune(ascii_frame.timestamp);
+        if overlays.draw(&mut ascii_frame) {
+            overlaid = true;
+        }
+        if notes.draw(&mut ascii_frame) {
+            overlaid = true;
+        }
+
+        let shown_waveform = waveform.as_mut().filter(|_| cli.waveform);
+        if let Some(waveform) = shown_waveform.and_then(WaveformLoader::get) {
+            overlaid = true;
+            // A frame as tall as the terminal has its last row under the status line
+            let row = ascii_frame
+                .height
+                .saturating_sub(1 + u16::from(ascii_frame.height >= term_height));
+            let position = ascii_frame.timestamp;
+            waveform.draw(&mut ascii_frame, row, position, video_duration);
+        }
+
+        if adjustments_until.is_some_and(|until| Instant::now() < until) {
+            look.draw_adjustments(&mut ascii_frame);
+            overlaid = true;
+        }
+        if grid_warning_until.is_some_and(|until| Instant::now() < until) {
+            let warning = format!(
+                "Terminal too large: showing {}x{} of {}x{} cells",
+                grid.0, grid.1, term_width, term_height
+            );
+            draw_text(&mut ascii_frame, &[warning], Placement::Top);
+            overlaid = true;
+        }
+        if overlaid || was_overlaid {
+            damaged_cells = None;
+        }
 
         // Create status line
         let _elapsed = playback_start.elapsed().as_secs_f64();
         let progress = if video_duration > 0.0 {
-            (frame.timestamp / video_duration * 100.0).min(100.0)
+            (ascii_frame.timestamp / video_duration * 100.0).min(100.0)
         } else {
             0.0
         };
 
-        let status = format!(
-            "{} | Frame: {} | Time: {:.1}s/{:.1}s ({:.1}%) | Speed: {:.2}x | FPS: {:.1}",
-            filename,
+        fps_meter.tick(Instant::now());
+        // Reduced motion draws fewer frames on purpose
+        let mut target_rate = effective_fps * speed / frame_divisor as f64;
+        if reduced_motion {
+            target_rate = target_rate.min(REDUCED_MOTION_FPS);
+        }
+
+        let mut status = format!(
+            "{} | Frame: {} | Time: {:.1}s/{:.1}s ({:.1}%) | Speed: {:.2}x | {}",
+            title,
             frame_count,
-            frame.timestamp,
+            ascii_frame.timestamp,
             video_duration,
             progress,
-            state.speed,
-            effective_fps
+            speed,
+            fps_meter.status(target_rate)
         );
+        if let Some(ref throttle) = throttle {
+            status.push_str(" | ");
+            status.push_str(&throttle.status());
+        }
+        if state.show_stats {
+            status.push_str(&format!(
+                " | {} | queue {} | panics {} | timeouts {}",
+                stage_stats,
+                frames.metrics(),
+                supervisor.panics() + frames.metrics().panics(),
+                watchdog.timeouts()
+            ));
+        }
+        if let Some(marks) = ab_loop.status() {
+            status.push_str(" | ");
+            status.push_str(&marks);
+        }
+        if let Some(ref mut job) = clip_job {
+            if let ClipStatus::Running(done) = job.poll() {
+                status.push_str(&format!(" | Clip {:.0}%", done * 100.0));
+            }
+        }
+        if let Some(ref prompt) = bookmark_prompt {
+            status.push_str(" | ");
+            status.push_str(&prompt.status());
+        } else if let Some((ref text, until)) = notice {
+            if Instant::now() < until {
+                status.push_str(" | ");
+                status.push_str(text);
+            }
+        }
 
         // Render frame with status
-        renderer.render_frame_with_status(&ascii_frame, &status)?;
+        let started = Instant::now();
+        if let Some(result) = supervisor.run("rendering", || {
+            renderer.render_damaged(&ascii_frame, damaged_cells.as_deref(), &status)
+        }) {
+            result?;
+        } else {
+            // Whatever made it to the screen is unknown
+            last_converted = None;
+        }
+        // The frame is drawn by now, but the frames after it are late
+        watchdog.overran("rendering", started, ascii_frame.timestamp, speed);
+        if let Some(ref mut throttle) = throttle {
+            if let Some(fidelity) = throttle.record(renderer.bytes_written(), Instant::now()) {
+                let label = match fidelity.label {
+                    "" => "full output",
+                    label => label,
+                };
+                info!("Drawing with {} for --max-bandwidth", label);
+                renderer.set_color_depth(caps.color_depth.min(fidelity.max_depth));
+                renderer.set_diff_only(fidelity.diff_only);
+            }
+        }
+        reporters.update(&PlaybackStatus {
+            name: filename,
+            position: ascii_frame.timestamp,
+            duration: video_duration,
+            paused: state.paused,
+        });
+        last_frame = Some(ascii_frame);
 
         frame_count += 1;
 
-        // Calculate frame delay
-        let target_fps = effective_fps * state.speed;
-        let frame_delay = calculate_frame_delay(target_fps, 1.0);
+        // A stepped frame stays on screen, silent, until the next key
+        if std::mem::take(&mut state.stepping) {
+            if let Some(ref audio) = audio {
+                audio.set_playing(false);
+            }
+            fps_meter.reset();
+            frame_timer.reset_after(IDLE_TICK);
+            continue;
+        }
+
+        // Calculate frame delay, taking up drift from the audio while it is
+        // heard; drift is in media time, which passes faster at higher speeds
+        let target_fps = effective_fps * speed;
+        let mut frame_delay = calculate_frame_delay(target_fps, 1.0);
+        let drift = audio
+            .as_ref()
+            .and_then(|audio| audio.sync(last_timestamp, speed, !ramping));
+        if let Some(drift) = drift {
+            frame_delay = audio::corrected_delay(frame_delay, drift / speed);
+        }
 
-        // Sleep for frame timing
-        sleep(frame_delay).await;
+        // Wait for the next frame, handling input meanwhile
+        frame_timer.reset_after(frame_delay);
     }
 
     // Cleanup
     info!("Cleaning up and exiting");
     renderer.cleanup()?;
 
-    // Clear SketchyBar
-    if let Some(ref sb) = sketchybar {
-        sb.clear()?;
-    }
+    reporters.clear();
+
+    // Cues are emitted after cleanup so they reach a restored terminal
+    let failed = playback_error.is_some();
+    let end_event = match playback_error {
+        Some(message) => PlaybackEvent::Error {
+            path: input_path,
+            message,
+        },
+        None if reached_end => PlaybackEvent::Finished {
+            path: input_path,
+            frames: frame_count,
+        },
+        None => PlaybackEvent::Stopped {
+            path: input_path,
+            frames: frame_count,
+        },
+    };
+    hooks.emit(&end_event);
 
     info!("Playback finished. Total frames: {}", frame_count);
-    Ok(())
+    if let Some(ref skipper) = black_skipper {
+        info!("Skipped {:.1}s of black frames", skipper.skipped());
+    }
+    Ok(match skip_to {
+        Some(end) => end,
+        None if failed => PlaybackEnd::Failed,
+        None if reached_end => PlaybackEnd::Finished,
+        None => PlaybackEnd::Quit,
+    })
+}
+
+/// Next command from the frontend, or never without one
+async fn next_command(slave: &mut Option<CommandReader>) -> Option<SlaveCommand> {
+    match slave {
+        Some(slave) => slave.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the next message from the watch-together group, if in one
+async fn next_sync(sync: &mut Option<SyncSession>) -> Option<SyncMessage> {
+    match sync {
+        Some(sync) => sync.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reopen the input at `target` seconds, clamped to the video
+///
+/// Replaces `frames` with a pipeline starting there and returns the position
+/// it starts at, or None for live input, which can't seek. A loop cache being
+/// filled is dropped, a complete one stays for later loops.
+fn seek_video(
+    cli: &Cli,
+    frames: &mut Pipeline<DecodedFrame>,
+    target: f64,
+    duration: f64,
+    filter: &FilterStage,
+    loop_cache: &mut Option<LoopCache>,
+) -> Result<Option<f64>> {
+    if cli.is_live() {
+        info!("Live input, not seeking");
+        return Ok(None);
+    }
+    let target = clamp_target(target, duration);
+    info!("Seeking to {:.2}s", target);
+    if let Some(cache) = loop_cache {
+        cache.interrupt();
+    }
+    let mut frame_iter = cli.open_video()?;
+    frame_iter.seek(target)?;
+    *frames = cli.frame_pipeline(frame_iter, filter);
+    Ok(Some(target))
+}
+
+/// Run a subcommand instead of playing a video
+fn run_command(command: &Commands, mut config: Config) -> Result<()> {
+    match command {
+        Commands::Calibrate {
+            ramp,
+            levels,
+            dry_run,
+        } => {
+            let ramp: Vec<char> = match ramp {
+                Some(ramp) => ramp.chars().collect(),
+                None => ascii_player::EXTENDED_ASCII_RAMP.to_vec(),
+            };
+
+            let calibrated = calibrate_ramp(&ramp, *levels)?;
+
+            println!("Calibrated ramp (DejaVu Sans Mono / Menlo ink coverage):");
+            for glyph in &calibrated {
+                let bar_width = (glyph.coverage * 40.0).round() as usize;
+                println!(
+                    "  {}  {:.3}  {}",
+                    glyph.character.to_string().repeat(8),
+                    glyph.coverage,
+                    "█".repeat(bar_width)
+                );
+            }
+
+            let ramp: String = calibrated.iter().map(|glyph| glyph.character).collect();
+            println!("\nRamp: \"{}\"", ramp);
+
+            if *dry_run {
+                return Ok(());
+            }
+
+            config.ramp = Some(ramp);
+            config.ramp_weights = Some(calibrated.iter().map(|glyph| glyph.coverage).collect());
+            let path = config.save()?;
+            println!("Saved to {}", path.display());
+            Ok(())
+        }
+        Commands::Inspect { file } => {
+            print!("{}", inspect::inspect(file)?);
+            Ok(())
+        }
+        Commands::Bookmarks { file, json } => {
+            print!("{}", list_bookmarks(&config, file, *json)?);
+            Ok(())
+        }
+        Commands::Annotations { file, json, srt } => {
+            let format = match (json, srt) {
+                (true, _) => AnnotationFormat::Json,
+                (_, true) => AnnotationFormat::Srt,
+                _ => AnnotationFormat::Text,
+            };
+            print!("{}", list_annotations(&config, file, format)?);
+            Ok(())
+        }
+        Commands::Clip {
+            file,
+            from,
+            to,
+            output,
+        } => {
+            let range = ClipRange::between(
+                resolve_point(&config, file, from)?,
+                resolve_point(&config, file, to)?,
+            )
+            .ok_or_else(|| anyhow!("The clip range is empty"))?;
+            let output = output.clone().unwrap_or_else(|| clip_path(file, range));
+            export_clip(file, range, &output, |done| {
+                eprint!("\rExporting {:3.0}%", done * 100.0);
+            })?;
+            eprintln!();
+            println!("Saved {}", output.display());
+            Ok(())
+        }
+        Commands::Effects { save, effects, .. } => {
+            let Some(name) = save else {
+                print!("{}", list_effects(&config));
+                return Ok(());
+            };
+            config.profiles.insert(name.clone(), effects.clone());
+            let path = config.save()?;
+            println!("Saved profile '{}' to {}", name, path.display());
+            Ok(())
+        }
+        Commands::Doctor { render_test } => {
+            let caps = TermCaps::detect();
+            let render = render_test
+                .then(|| doctor::render_test(&caps))
+                .transpose()?;
+            let var = |name: &str| std::env::var(name).ok();
+            print!("{}", doctor::report(&caps, var, render.as_ref()));
+            Ok(())
+        }
+        Commands::FixTerminal => {
+            termstate::fix_terminal()?;
+            println!("Terminal reset");
+            Ok(())
+        }
+        Commands::Bench {
+            width,
+            height,
+            frames,
+        } => {
+            let results = bench_assembly(*width, *height, *frames)?;
+            print!("{}", bench_report(*width, *height, &results));
+            Ok(())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -449,18 +2118,8 @@ mod tests {
         assert_eq!(state.speed, 1.0);
         assert!(!state.loop_enabled);
         assert!(!state.show_help);
-    }
-
-    #[tokio::test]
-    async fn test_sketchybar_integration() {
-        let sb = SketchyBarIntegration::new("test_item".to_string());
-
-        // These tests will only work if sketchybar is installed
-        // In a real environment, you might want to mock the Command execution
-        let result = sb.set_playing("test.mp4");
-        assert!(result.is_ok());
-
-        let result = sb.clear();
-        assert!(result.is_ok());
+        assert!(!state.show_captions);
+        assert!(!state.show_stats);
+        assert!(!state.hidden);
     }
 }
@@ -0,0 +1,138 @@
+//! Structured frame output for external renderers
+//!
+//! `--export ndjson` writes one JSON object per converted frame, one per
+//! line, so programs such as web UIs or LED matrix drivers can draw the cells
+//! themselves instead of parsing ANSI escapes:
+//!
+//! ```json
+//! {"frame":0,"timestamp":0.0,"width":2,"height":1,"cells":[[{"ch":"#","fg":[255,0,0],"bg":null},{"ch":" ","fg":[0,0,0],"bg":null}]]}
+//! ```
+//!
+//! `cells` holds one array per row. Colors are `[r, g, b]`; `bg` is `null`
+//! unless the frame has background colors.
+
+use crate::converter::AsciiFrame;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize)]
+struct Cell {
+    ch: char,
+    fg: (u8, u8, u8),
+    bg: Option<(u8, u8, u8)>,
+}
+
+#[derive(Serialize)]
+struct FrameRecord {
+    frame: u64,
+    timestamp: f64,
+    width: u16,
+    height: u16,
+    cells: Vec<Vec<Cell>>,
+}
+
+impl FrameRecord {
+    fn new(frame: &AsciiFrame) -> Result<Self> {
+        let width = frame.width as usize;
+        let count = width * frame.height as usize;
+        if frame.characters.len() < count || frame.fg_colors.len() < count {
+            return Err(anyhow!(
+                "Frame {} has fewer cells than {}x{}",
+                frame.frame_number,
+                frame.width,
+                frame.height
+            ));
+        }
+
+        let cells = (0..frame.height as usize)
+            .map(|row| {
+                (row * width..(row + 1) * width)
+                    .map(|index| Cell {
+                        ch: frame.characters[index],
+                        fg: frame.fg_colors[index],
+                        bg: frame
+                            .bg_colors
+                            .as_ref()
+                            .and_then(|colors| colors.get(index).copied()),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            frame: frame.frame_number,
+            timestamp: frame.timestamp,
+            width: frame.width,
+            height: frame.height,
+            cells,
+        })
+    }
+}
+
+/// Writes frames as newline-delimited JSON
+pub struct NdjsonWriter<W: Write> {
+    output: W,
+    frames: u64,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self { output, frames: 0 }
+    }
+
+    /// Write one frame as a line, flushed so readers on a pipe see it at once
+    pub fn write_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        serde_json::to_writer(&mut self.output, &FrameRecord::new(frame)?)?;
+        self.output.write_all(b"\n")?;
+        self.output.flush()?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Number of frames written
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndjson_frames() {
+        let mut frame = AsciiFrame::new(2, 2, vec!['#', ' ', '.', '@'], vec![(255, 0, 0); 4]);
+        frame.timestamp = 1.5;
+        frame.frame_number = 7;
+
+        let mut writer = NdjsonWriter::new(Vec::new());
+        writer.write_frame(&frame).unwrap();
+        frame.bg_colors = Some(vec![(0, 0, 9); 4]);
+        writer.write_frame(&frame).unwrap();
+        assert_eq!(writer.frames(), 2);
+
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["frame"], 7);
+        assert_eq!(lines[0]["timestamp"], 1.5);
+        assert_eq!(
+            lines[0]["cells"][1][1],
+            serde_json::json!({"ch": "@", "fg": [255, 0, 0], "bg": null})
+        );
+        assert_eq!(lines[1]["cells"][0][0]["bg"], serde_json::json!([0, 0, 9]));
+
+        frame.characters.pop();
+        assert!(NdjsonWriter::new(std::io::sink())
+            .write_frame(&frame)
+            .is_err());
+    }
+}
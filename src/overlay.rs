@@ -0,0 +1,466 @@
+//! Text overlays drawn on top of converted frames
+//!
+//! Overlays are written directly into an [`AsciiFrame`] after conversion, so
+//! they render through the same path as the video and never flicker against
+//! it. Each line is padded with a space on both sides so it stays readable
+//! over busy picture content. Titles and countdowns can use [`big_text`], a
+//! block-character font that stays legible over ASCII video.
+//!
+//! [`TimedOverlay`] schedules overlays against media time, the timestamp of
+//! the frame being drawn, so they follow pauses, seeks and playback speed
+//! instead of the wall clock.
+
+use crate::converter::{AsciiFrame, CellAttribute};
+use crate::schedule::format_remaining;
+
+/// Text color used for overlays
+pub const OVERLAY_COLOR: (u8, u8, u8) = (255, 255, 255);
+
+/// Vertical placement of an overlay block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Above the bottom row, like subtitles
+    Bottom,
+    /// Below the top row
+    Top,
+    /// Vertically centered
+    Center,
+}
+
+/// Draw lines of text centered horizontally at the given placement.
+///
+/// Lines wider than the frame are truncated; lines that don't fit vertically
+/// are dropped from the top so the most recent text stays visible.
+pub fn draw_text(frame: &mut AsciiFrame, lines: &[String], placement: Placement) {
+    let width = frame.width as usize;
+    let height = frame.height as usize;
+    if width == 0 || height == 0 || lines.is_empty() {
+        return;
+    }
+
+    // Keep one row of margin at the top and bottom edges when possible
+    let margin = usize::from(height > lines.len() + 2);
+    let visible = lines.len().min(height - margin);
+    let lines = &lines[lines.len() - visible..];
+
+    let first_row = match placement {
+        Placement::Bottom => height - margin - visible,
+        Placement::Top => margin,
+        Placement::Center => (height - visible) / 2,
+    };
+
+    for (offset, line) in lines.iter().enumerate() {
+        let padded: Vec<char> = std::iter::once(' ')
+            .chain(line.chars())
+            .chain(std::iter::once(' '))
+            .take(width)
+            .collect();
+        let start_column = (width - padded.len()) / 2;
+        let row = first_row + offset;
+
+        for (column, character) in padded.into_iter().enumerate() {
+            let index = row * width + start_column + column;
+            if index < frame.characters.len() {
+                frame.characters[index] = character;
+                frame.fg_colors[index] = OVERLAY_COLOR;
+                if let Some(ref mut attributes) = frame.attributes {
+                    attributes[index] = CellAttribute::Normal;
+                }
+            }
+        }
+    }
+}
+
+/// Corner of the frame for pictures such as QR codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+}
+
+/// Draw lines of equal width as they are, one cell in from `corner`.
+/// Backgrounds under the block are cleared so the picture is exact. Returns
+/// false, drawing nothing, if the block doesn't fit.
+pub fn draw_in_corner(frame: &mut AsciiFrame, lines: &[String], corner: Corner) -> bool {
+    let width = frame.width as usize;
+    let block_width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    if block_width + 2 > width || lines.len() + 2 > frame.height as usize {
+        return false;
+    }
+
+    let first_column = match corner {
+        Corner::TopLeft => 1,
+        Corner::TopRight => width - 1 - block_width,
+    };
+    for (row, line) in lines.iter().enumerate() {
+        for (column, character) in line.chars().enumerate() {
+            let index = (row + 1) * width + first_column + column;
+            frame.characters[index] = character;
+            frame.fg_colors[index] = OVERLAY_COLOR;
+            if let Some(ref mut bg_colors) = frame.bg_colors {
+                bg_colors[index] = (0, 0, 0);
+            }
+            if let Some(ref mut attributes) = frame.attributes {
+                attributes[index] = CellAttribute::Normal;
+            }
+        }
+    }
+    true
+}
+
+/// An empty frame for overlays shown without video
+pub fn blank_frame(width: u16, height: u16) -> AsciiFrame {
+    let cells = width as usize * height as usize;
+    AsciiFrame::new(width, height, vec![' '; cells], vec![OVERLAY_COLOR; cells])
+}
+
+/// Height of big-text glyphs in rows
+pub const BIG_TEXT_HEIGHT: usize = 5;
+
+/// Block font for big text: `#` marks a filled cell
+const BIG_FONT: &[(char, [&str; BIG_TEXT_HEIGHT])] = &[
+    ('A', [" ## ", "#  #", "####", "#  #", "#  #"]),
+    ('B', ["### ", "#  #", "### ", "#  #", "### "]),
+    ('C', [" ###", "#   ", "#   ", "#   ", " ###"]),
+    ('D', ["### ", "#  #", "#  #", "#  #", "### "]),
+    ('E', ["####", "#   ", "### ", "#   ", "####"]),
+    ('F', ["####", "#   ", "### ", "#   ", "#   "]),
+    ('G', [" ###", "#   ", "# ##", "#  #", " ###"]),
+    ('H', ["#  #", "#  #", "####", "#  #", "#  #"]),
+    ('I', ["###", " # ", " # ", " # ", "###"]),
+    ('J', ["  ##", "   #", "   #", "#  #", " ## "]),
+    ('K', ["#  #", "# # ", "##  ", "# # ", "#  #"]),
+    ('L', ["#   ", "#   ", "#   ", "#   ", "####"]),
+    ('M', ["#   #", "## ##", "# # #", "#   #", "#   #"]),
+    ('N', ["#   #", "##  #", "# # #", "#  ##", "#   #"]),
+    ('O', [" ## ", "#  #", "#  #", "#  #", " ## "]),
+    ('P', ["### ", "#  #", "### ", "#   ", "#   "]),
+    ('Q', [" ## ", "#  #", "#  #", "# # ", " # #"]),
+    ('R', ["### ", "#  #", "### ", "# # ", "#  #"]),
+    ('S', [" ###", "#   ", " ## ", "   #", "### "]),
+    ('T', ["#####", "  #  ", "  #  ", "  #  ", "  #  "]),
+    ('U', ["#  #", "#  #", "#  #", "#  #", " ## "]),
+    ('V', ["#   #", "#   #", "#   #", " # # ", "  #  "]),
+    ('W', ["#   #", "#   #", "# # #", "## ##", "#   #"]),
+    ('X', ["#   #", " # # ", "  #  ", " # # ", "#   #"]),
+    ('Y', ["#   #", " # # ", "  #  ", "  #  ", "  #  "]),
+    ('Z', ["####", "   #", " ## ", "#   ", "####"]),
+    ('0', [" ## ", "# ##", "#  #", "## #", " ## "]),
+    ('1', [" # ", "## ", " # ", " # ", "###"]),
+    ('2', ["### ", "   #", " ## ", "#   ", "####"]),
+    ('3', ["### ", "   #", " ## ", "   #", "### "]),
+    ('4', ["#  #", "#  #", "####", "   #", "   #"]),
+    ('5', ["####", "#   ", "### ", "   #", "### "]),
+    ('6', [" ## ", "#   ", "### ", "#  #", " ## "]),
+    ('7', ["####", "   #", "  # ", " #  ", " #  "]),
+    ('8', [" ## ", "#  #", " ## ", "#  #", " ## "]),
+    ('9', [" ## ", "#  #", " ###", "   #", " ## "]),
+    (' ', ["  ", "  ", "  ", "  ", "  "]),
+    (':', [" ", "#", " ", "#", " "]),
+    ('.', [" ", " ", " ", " ", "#"]),
+    (',', ["  ", "  ", "  ", " #", "# "]),
+    ('!', ["#", "#", "#", " ", "#"]),
+    ('?', ["### ", "   #", " ## ", "    ", " #  "]),
+    ('-', ["   ", "   ", "###", "   ", "   "]),
+    ('+', ["   ", " # ", "###", " # ", "   "]),
+    ('\'', ["#", "#", " ", " ", " "]),
+    ('/', ["   #", "  # ", " #  ", "#   ", "    "]),
+];
+
+fn big_glyph(character: char) -> &'static [&'static str; BIG_TEXT_HEIGHT] {
+    let character = character.to_ascii_uppercase();
+    BIG_FONT
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .or_else(|| BIG_FONT.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| rows)
+        .expect("big font has a fallback glyph")
+}
+
+/// Render text in the block font, one output line per glyph row.
+///
+/// Newlines start a new block separated by a blank row. Characters without a
+/// glyph are drawn as `?`.
+pub fn big_text(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        if index > 0 {
+            lines.push(String::new());
+        }
+
+        for row in 0..BIG_TEXT_HEIGHT {
+            let rendered: Vec<String> = line
+                .chars()
+                .map(|character| big_glyph(character)[row].replace('#', "█"))
+                .collect();
+            lines.push(rendered.join(" ").trim_end().to_string());
+        }
+    }
+
+    lines
+}
+
+/// Draw big text, falling back to plain text when it doesn't fit the frame
+pub fn draw_big_text(frame: &mut AsciiFrame, text: &str, placement: Placement) {
+    let lines = big_text(text);
+    let widest = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    // Rows are padded by one cell on each side when drawn
+    if widest + 2 <= frame.width as usize && lines.len() <= frame.height as usize {
+        // Pad rows to a common width so glyphs line up when centered
+        let padded: Vec<String> = lines
+            .iter()
+            .map(|line| format!("{:<width$}", line, width = widest))
+            .collect();
+        draw_text(frame, &padded, placement);
+    } else {
+        let plain: Vec<String> = text.lines().map(str::to_string).collect();
+        draw_text(frame, &plain, placement);
+    }
+}
+
+/// What a scheduled overlay draws
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlayContent {
+    /// Lines of text, as [`draw_text`]
+    Text(Vec<String>, Placement),
+    /// Text in the block font, as [`draw_big_text`]
+    BigText(String, Placement),
+    /// A picture of equal width lines, as [`draw_in_corner`]
+    Corner(Vec<String>, Corner),
+    /// Whole seconds left until the overlay ends, in the block font
+    Countdown(Placement),
+}
+
+/// Handle to a scheduled overlay, for removing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlayId(u64);
+
+#[derive(Debug, Clone)]
+struct ScheduledOverlay {
+    id: OverlayId,
+    start: f64,
+    end: f64,
+    content: OverlayContent,
+}
+
+/// Overlays shown between two media timestamps.
+///
+/// Overlays are drawn in the order they were added, so later ones end up on
+/// top. They stay scheduled after they end, so seeking back shows them
+/// again, until removed or pruned.
+#[derive(Debug, Clone, Default)]
+pub struct TimedOverlay {
+    overlays: Vec<ScheduledOverlay>,
+    next_id: u64,
+}
+
+impl TimedOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `content` from `start` through `end` seconds of media time
+    pub fn add(&mut self, start: f64, end: f64, content: OverlayContent) -> OverlayId {
+        let id = OverlayId(self.next_id);
+        self.next_id += 1;
+        self.overlays.push(ScheduledOverlay {
+            id,
+            start,
+            end,
+            content,
+        });
+        id
+    }
+
+    /// Unschedule an overlay; false if it was already gone
+    pub fn remove(&mut self, id: OverlayId) -> bool {
+        let count = self.overlays.len();
+        self.overlays.retain(|overlay| overlay.id != id);
+        self.overlays.len() != count
+    }
+
+    /// Drop overlays that ended before `timestamp`, for overlays meant to
+    /// be seen once even when playback loops or seeks back
+    pub fn prune(&mut self, timestamp: f64) {
+        self.overlays.retain(|overlay| overlay.end >= timestamp);
+    }
+
+    /// Unschedule everything
+    pub fn clear(&mut self) {
+        self.overlays.clear();
+    }
+
+    /// Contents shown at `timestamp`, bottom first
+    pub fn active(&self, timestamp: f64) -> impl Iterator<Item = &OverlayContent> {
+        self.overlays
+            .iter()
+            .filter(move |overlay| (overlay.start..=overlay.end).contains(&timestamp))
+            .map(|overlay| &overlay.content)
+    }
+
+    /// Draw the overlays active at the frame's timestamp; returns whether any
+    /// were
+    pub fn draw(&self, frame: &mut AsciiFrame) -> bool {
+        let timestamp = frame.timestamp;
+        let mut drawn = false;
+        for overlay in &self.overlays {
+            if !(overlay.start..=overlay.end).contains(&timestamp) {
+                continue;
+            }
+            match overlay.content {
+                OverlayContent::Text(ref lines, placement) => draw_text(frame, lines, placement),
+                OverlayContent::BigText(ref text, placement) => {
+                    draw_big_text(frame, text, placement)
+                }
+                // Pictures that don't fit are left out
+                OverlayContent::Corner(ref lines, corner) => {
+                    if !draw_in_corner(frame, lines, corner) {
+                        continue;
+                    }
+                }
+                OverlayContent::Countdown(placement) => {
+                    // Round up so the display reads 1 during the final second
+                    let seconds = (overlay.end - timestamp).max(0.0).ceil() as u64;
+                    draw_big_text(frame, &format_remaining(seconds), placement);
+                }
+            }
+            drawn = true;
+        }
+        drawn
+    }
+
+    /// Number of scheduled overlays
+    pub fn len(&self) -> usize {
+        self.overlays.len()
+    }
+
+    /// Whether nothing is scheduled
+    pub fn is_empty(&self) -> bool {
+        self.overlays.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_frame(width: u16, height: u16) -> AsciiFrame {
+        let cells = width as usize * height as usize;
+        AsciiFrame {
+            characters: vec!['.'; cells],
+            fg_colors: vec![(0, 0, 0); cells],
+            bg_colors: None,
+            attributes: None,
+            width,
+            height,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    fn row(frame: &AsciiFrame, row: usize) -> String {
+        let width = frame.width as usize;
+        frame.characters[row * width..(row + 1) * width]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_bottom_placement_centers_text() {
+        let mut frame = blank_frame(10, 5);
+        draw_text(&mut frame, &["hi".to_string()], Placement::Bottom);
+
+        assert_eq!(row(&frame, 3), "... hi ...");
+        assert_eq!(row(&frame, 4), "..........");
+        assert_eq!(frame.fg_colors[3 * 10 + 4], OVERLAY_COLOR);
+    }
+
+    #[test]
+    fn test_overlay_cells_are_plain() {
+        let mut frame = blank_frame(10, 5).with_attributes(vec![CellAttribute::Bold; 50]);
+        draw_text(&mut frame, &["hi".to_string()], Placement::Bottom);
+        let attributes = frame.attributes.as_ref().unwrap();
+        assert_eq!(attributes[3 * 10 + 4], CellAttribute::Normal);
+        assert_eq!(attributes[4 * 10 + 4], CellAttribute::Bold);
+    }
+
+    #[test]
+    fn test_long_text_is_truncated() {
+        let mut frame = blank_frame(4, 3);
+        draw_text(
+            &mut frame,
+            &[
+                "zero".to_string(),
+                "first".to_string(),
+                "second".to_string(),
+                "third".to_string(),
+            ],
+            Placement::Top,
+        );
+
+        // Only the last lines fit, and each is cut to the frame width
+        assert_eq!(row(&frame, 0), " fir");
+        assert_eq!(row(&frame, 1), " sec");
+        assert_eq!(row(&frame, 2), " thi");
+    }
+
+    #[test]
+    fn test_big_text_renders_glyph_rows() {
+        let lines = big_text("1:");
+        assert_eq!(lines.len(), BIG_TEXT_HEIGHT);
+        assert_eq!(lines[0], " █");
+        assert_eq!(lines[1], "██  █");
+        assert_eq!(lines[4], "███");
+
+        // Unknown characters fall back to '?', lowercase maps to uppercase
+        assert_eq!(big_text("~"), big_text("?"));
+        assert_eq!(big_text("go"), big_text("GO"));
+        assert_eq!(big_text("A\nB").len(), BIG_TEXT_HEIGHT * 2 + 1);
+    }
+
+    #[test]
+    fn test_timed_overlay() {
+        let mut overlays = TimedOverlay::new();
+        let title = overlays.add(
+            0.0,
+            2.0,
+            OverlayContent::Text(vec!["hi".to_string()], Placement::Bottom),
+        );
+        overlays.add(5.0, 8.0, OverlayContent::Countdown(Placement::Center));
+
+        let mut frame = blank_frame(10, 5).with_timestamp(1.0, 30);
+        assert!(overlays.draw(&mut frame));
+        assert_eq!(row(&frame, 3), "... hi ...");
+
+        // Nothing is scheduled between the two
+        let mut frame = blank_frame(10, 5).with_timestamp(3.0, 90);
+        assert!(!overlays.draw(&mut frame));
+        assert_eq!(overlays.active(3.0).count(), 0);
+
+        // The countdown rounds up, and falls back to plain text when small
+        let mut frame = blank_frame(10, 3).with_timestamp(5.5, 165);
+        assert!(overlays.draw(&mut frame));
+        assert_eq!(row(&frame, 1), "... 3 ....");
+
+        overlays.prune(4.0);
+        assert_eq!(overlays.len(), 1);
+        assert!(!overlays.remove(title));
+    }
+
+    #[test]
+    fn test_big_text_falls_back_when_too_wide() {
+        let mut frame = blank_frame(8, 5);
+        draw_big_text(&mut frame, "WIDE", Placement::Center);
+        assert_eq!(row(&frame, 2), ". WIDE .");
+    }
+}
@@ -0,0 +1,106 @@
+//! Colormaps for false-color output
+//!
+//! `--style heatmap` colors each cell by its luminance through one of these
+//! maps. Each map is stored as 17 evenly spaced stops from dark to bright and
+//! interpolated linearly to a 256-entry lookup table.
+
+/// Perceptual colormap used by `--style heatmap`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Blue through green and yellow to red, like a thermal camera
+    #[default]
+    Turbo,
+    /// Black through purple and orange to pale yellow
+    Inferno,
+}
+
+/// Google's Turbo rainbow colormap
+const TURBO: [(u8, u8, u8); 17] = [
+    (48, 18, 59),
+    (73, 62, 175),
+    (68, 106, 238),
+    (50, 149, 247),
+    (38, 189, 225),
+    (41, 221, 187),
+    (64, 243, 146),
+    (102, 253, 109),
+    (150, 250, 80),
+    (198, 235, 59),
+    (238, 208, 45),
+    (255, 171, 36),
+    (255, 128, 29),
+    (238, 84, 21),
+    (201, 45, 12),
+    (161, 18, 2),
+    (122, 4, 3),
+];
+
+/// Matplotlib's Inferno colormap
+const INFERNO: [(u8, u8, u8); 17] = [
+    (0, 0, 4),
+    (11, 6, 44),
+    (33, 9, 74),
+    (59, 12, 93),
+    (86, 17, 104),
+    (112, 23, 108),
+    (138, 31, 105),
+    (162, 41, 96),
+    (186, 54, 82),
+    (207, 69, 62),
+    (226, 88, 42),
+    (241, 111, 24),
+    (249, 138, 15),
+    (250, 169, 19),
+    (247, 203, 44),
+    (243, 234, 93),
+    (252, 255, 164),
+];
+
+impl Colormap {
+    /// Evenly spaced stops from dark to bright
+    pub fn stops(&self) -> &'static [(u8, u8, u8)] {
+        match self {
+            Colormap::Turbo => &TURBO,
+            Colormap::Inferno => &INFERNO,
+        }
+    }
+
+    /// Color for every luminance value
+    pub fn lut(&self) -> Vec<(u8, u8, u8)> {
+        let stops = self.stops();
+        let segments = (stops.len() - 1) as f64;
+        (0..=255u8)
+            .map(|luminance| {
+                let position = luminance as f64 / 255.0 * segments;
+                let index = (position as usize).min(stops.len() - 2);
+                let t = position - index as f64;
+                let (from, to) = (stops[index], stops[index + 1]);
+                let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+                (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colormap_lut_spans_the_stops() {
+        for colormap in [Colormap::Turbo, Colormap::Inferno] {
+            let lut = colormap.lut();
+            let stops = colormap.stops();
+            assert_eq!(lut.len(), 256);
+            assert_eq!(lut[0], stops[0]);
+            assert_eq!(lut[255], stops[stops.len() - 1]);
+        }
+
+        // Inferno gets brighter all the way up
+        let inferno = Colormap::Inferno.lut();
+        let luma = |(r, g, b): (u8, u8, u8)| r as u32 * 299 + g as u32 * 587 + b as u32 * 114;
+        assert!(inferno
+            .windows(2)
+            .all(|pair| luma(pair[0]) <= luma(pair[1])));
+    }
+}
@@ -0,0 +1,704 @@
+//! Frame pipeline stages
+//!
+//! Playback is a chain of stages: decode → filter → convert → render. A
+//! [`Pipeline`] runs each stage on its own thread, connected by bounded
+//! async queues, so a slow stage holds back the ones before it instead of
+//! letting frames pile up in memory. [`PipelineMetrics`] reports how full
+//! each queue is and how often a stage had to wait for room (a stall), which
+//! the stats line (`Shift+S`) shows next to the stage timings.
+//!
+//! Stages are async: each stage thread drives its stage's futures on a
+//! small runtime of its own, so a stage can await timers or I/O without
+//! holding up the player's runtime, and blocking work such as decoding
+//! stays off it too. The output end is read with [`Pipeline::recv`] from
+//! async code, or as an iterator from anywhere else.
+//!
+//! The player decodes and filters (crop, stabilization) in the pipeline;
+//! converting and rendering stay on the main thread, which owns the
+//! terminal and the interactive state. Library users can build their own
+//! chains and insert stages anywhere:
+//!
+//! ```no_run
+//! # use ascii_player::{ConvertStage, ConversionConfig, FrameConverter, FrameIterator, Pipeline, RenderStage, Renderer, VideoDecoder};
+//! # use std::path::Path;
+//! # fn main() -> anyhow::Result<()> {
+//! let frames = FrameIterator::new(VideoDecoder::new(Path::new("video.mp4"))?, None, None);
+//! let pipeline = Pipeline::decode(frames, 4)
+//!     .filter("invert", 2, |mut decoded| {
+//!         decoded.frame.data.iter_mut().for_each(|value| *value = 255 - *value);
+//!         Ok(Some(decoded))
+//!     })
+//!     .stage(ConvertStage::new(FrameConverter::new(ConversionConfig::default()), 80, 24), 2)
+//!     .stage(RenderStage::new(Renderer::new(false, true)?, std::io::stdout()).with_fps(30.0), 1)
+//!     .tap("count", 1, |frame| eprintln!("frame {}", frame.frame_number));
+//! for frame in pipeline {
+//!     let _drawn = frame?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::converter::{AsciiConverter, AsciiFrame};
+use crate::crop::{crop_frame, CropRect};
+use crate::damage::{Damage, DamageTracker};
+use crate::decoder::{FrameIterator, VideoFrame};
+use crate::renderer::Renderer;
+use crate::scheduling::{pin_thread_or_warn, release_thread};
+use crate::stabilize::Stabilizer;
+use crate::supervisor::Supervisor;
+use anyhow::Result;
+use log::{debug, error, warn};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{sleep_until, Instant};
+
+/// Decoded frames the player keeps ready ahead of playback
+pub const DECODE_QUEUE: usize = 4;
+
+/// Cropped and stabilized frames the player keeps ready
+pub const FILTER_QUEUE: usize = 2;
+
+/// One step of a pipeline, run on its own thread
+pub trait PipelineStage: Send + 'static {
+    type Input: Send + 'static;
+    type Output: Send + 'static;
+
+    /// Short name for logs and the stats line
+    fn name(&self) -> &str;
+
+    /// Handle one item; `None` drops it. Errors are passed down the
+    /// pipeline to its consumer, which decides whether to stop. The future
+    /// runs on the stage's own thread, so it may block as well as await.
+    fn process(&mut self, input: Self::Input)
+        -> impl Future<Output = Result<Option<Self::Output>>>;
+}
+
+/// A decoded frame with the closed captions showing at its time
+#[derive(Debug)]
+pub struct DecodedFrame {
+    pub frame: VideoFrame,
+    pub captions: Vec<String>,
+    /// What changed since the frame before it; stages that move pixels
+    /// around must update or reset it
+    pub damage: Damage,
+}
+
+/// Counters of one queue, shared by its two ends and the metrics
+#[derive(Debug, Default)]
+struct QueueCounters {
+    stage: String,
+    capacity: usize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    stalls: AtomicU64,
+    panics: AtomicU64,
+}
+
+/// State of the queue after one stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Stage that fills the queue
+    pub stage: String,
+    /// Items waiting in the queue
+    pub depth: usize,
+    pub capacity: usize,
+    /// Times the stage found the queue full and waited
+    pub stalls: u64,
+    /// Items the stage dropped because it panicked on them
+    pub panics: u64,
+}
+
+/// Queue depths and stalls of a running pipeline
+#[derive(Debug, Clone, Default)]
+pub struct PipelineMetrics {
+    queues: Arc<Mutex<Vec<Arc<QueueCounters>>>>,
+}
+
+impl PipelineMetrics {
+    /// Every queue, in pipeline order
+    pub fn queues(&self) -> Vec<QueueStats> {
+        let queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues
+            .iter()
+            .map(|counters| QueueStats {
+                stage: counters.stage.clone(),
+                depth: counters.depth().min(counters.capacity),
+                capacity: counters.capacity,
+                stalls: counters.stalls.load(Ordering::Relaxed),
+                panics: counters.panics.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Items dropped by panicking stages
+    pub fn panics(&self) -> u64 {
+        self.queues().iter().map(|queue| queue.panics).sum()
+    }
+
+    fn add_queue(&self, stage: &str, capacity: usize) -> Arc<QueueCounters> {
+        let counters = Arc::new(QueueCounters {
+            stage: stage.to_string(),
+            capacity,
+            ..Default::default()
+        });
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues.push(Arc::clone(&counters));
+        counters
+    }
+}
+
+impl QueueCounters {
+    /// Items waiting; read `received` first so a frame taken in between is
+    /// not counted twice
+    fn depth(&self) -> usize {
+        let received = self.received.load(Ordering::Relaxed);
+        self.sent.load(Ordering::Relaxed).saturating_sub(received) as usize
+    }
+}
+
+impl fmt::Display for PipelineMetrics {
+    /// Fill and stalls of each queue, e.g. `decode 3/4 (12 stalls) filter 0/2`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, queue) in self.queues().iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{} {}/{}", queue.stage, queue.depth, queue.capacity)?;
+            match queue.stalls {
+                0 => {}
+                1 => write!(f, " (1 stall)")?,
+                stalls => write!(f, " ({} stalls)", stalls)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sending end of a bounded queue that keeps its counters
+struct QueueSender<T> {
+    sender: Sender<Result<T>>,
+    counters: Arc<QueueCounters>,
+}
+
+impl<T> QueueSender<T> {
+    /// Queue an item, waiting for room if needed; false once the consumer
+    /// is gone
+    async fn send(&self, item: Result<T>) -> bool {
+        let sent = match self.sender.try_send(item) {
+            Ok(()) => true,
+            Err(TrySendError::Full(item)) => {
+                self.counters.stalls.fetch_add(1, Ordering::Relaxed);
+                self.sender.send(item).await.is_ok()
+            }
+            Err(TrySendError::Closed(_)) => false,
+        };
+        if sent {
+            self.counters.sent.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+}
+
+/// Open a bounded queue after `stage`
+fn queue<T>(
+    metrics: &PipelineMetrics,
+    stage: &str,
+    capacity: usize,
+) -> (QueueSender<T>, Receiver<Result<T>>, Arc<QueueCounters>) {
+    let capacity = capacity.max(1);
+    let counters = metrics.add_queue(stage, capacity);
+    let (sender, receiver) = mpsc::channel(capacity);
+    let sender = QueueSender {
+        sender,
+        counters: Arc::clone(&counters),
+    };
+    (sender, receiver, counters)
+}
+
+/// Start a named pipeline thread, off the render thread's CPU, with a
+/// runtime for the stage's futures
+fn spawn(stage: &str, work: impl FnOnce(&Runtime) + Send + 'static) {
+    let name = stage.to_string();
+    let spawned = thread::Builder::new()
+        .name(format!("pipeline-{}", stage))
+        .spawn(move || {
+            release_thread();
+            match Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => work(&runtime),
+                Err(e) => error!("No runtime for pipeline stage {}: {}", name, e),
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start pipeline stage {}: {}", stage, e);
+    }
+}
+
+/// Output end of a chain of stages running on their own threads.
+///
+/// Receiving yields each item from the last stage, and ends once the source
+/// is exhausted and every stage has finished. Dropping the pipeline stops
+/// all of its threads.
+pub struct Pipeline<T> {
+    receiver: Receiver<Result<T>>,
+    counters: Arc<QueueCounters>,
+    metrics: PipelineMetrics,
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    /// Start a pipeline pulling items from `source` on its own thread,
+    /// queueing up to `capacity` of them. A panic in the source ends it.
+    pub fn source<I>(name: &str, capacity: usize, mut source: I) -> Self
+    where
+        I: Iterator<Item = Result<T>> + Send + 'static,
+    {
+        let metrics = PipelineMetrics::default();
+        let (sender, receiver, counters) = queue(&metrics, name, capacity);
+        let stage = name.to_string();
+        spawn(name, move |runtime| {
+            let mut supervisor = Supervisor::new();
+            let action = format!("reading {} for", stage);
+            while let Some(Some(item)) = supervisor.run(&action, || source.next()) {
+                if !runtime.block_on(sender.send(item)) {
+                    break;
+                }
+            }
+            sender
+                .counters
+                .panics
+                .fetch_add(supervisor.panics(), Ordering::Relaxed);
+            debug!("Pipeline source {} finished", stage);
+        });
+        Self {
+            receiver,
+            counters,
+            metrics,
+        }
+    }
+
+    /// Add a stage after the current last one, queueing up to `capacity`
+    /// of its outputs
+    pub fn stage<S: PipelineStage<Input = T>>(
+        self,
+        mut stage: S,
+        capacity: usize,
+    ) -> Pipeline<S::Output> {
+        let name = stage.name().to_string();
+        let (sender, receiver, counters) = queue(&self.metrics, &name, capacity);
+        let thread_name = name.clone();
+        let stage_counters = Arc::clone(&counters);
+        let metrics = self.metrics.clone();
+        let mut upstream = self;
+        spawn(&thread_name, move |runtime| {
+            let mut supervisor = Supervisor::new();
+            let action = format!("running {} on", name);
+            while let Some(item) = runtime.block_on(upstream.recv()) {
+                let output = match item {
+                    Ok(input) => {
+                        match supervisor.run(&action, || runtime.block_on(stage.process(input))) {
+                            Some(Ok(Some(output))) => Ok(output),
+                            Some(Ok(None)) => continue,
+                            Some(Err(e)) => Err(e),
+                            None => {
+                                stage_counters.panics.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+                if !runtime.block_on(sender.send(output)) {
+                    break;
+                }
+            }
+            debug!("Pipeline stage {} finished", name);
+        });
+        Pipeline {
+            receiver,
+            counters,
+            metrics,
+        }
+    }
+
+    /// Add a stage from a closure that may change, replace or drop items
+    pub fn filter<F>(self, name: &str, capacity: usize, filter: F) -> Pipeline<T>
+    where
+        F: FnMut(T) -> Result<Option<T>> + Send + 'static,
+    {
+        self.stage(
+            FnStage {
+                name: name.to_string(),
+                function: filter,
+                item: PhantomData,
+            },
+            capacity,
+        )
+    }
+
+    /// Add a stage that looks at every item and passes it on unchanged
+    pub fn tap<F>(self, name: &str, capacity: usize, mut tap: F) -> Pipeline<T>
+    where
+        F: FnMut(&T) + Send + 'static,
+    {
+        self.filter(name, capacity, move |item| {
+            tap(&item);
+            Ok(Some(item))
+        })
+    }
+
+    /// Queue depths and stalls of every stage so far
+    pub fn metrics(&self) -> &PipelineMetrics {
+        &self.metrics
+    }
+
+    /// Wait for the next item from the last stage; None once the pipeline
+    /// has finished
+    pub async fn recv(&mut self) -> Option<Result<T>> {
+        let item = self.receiver.recv().await?;
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+        Some(item)
+    }
+}
+
+impl Pipeline<DecodedFrame> {
+    /// Start a pipeline decoding `frames` on its own thread
+    pub fn decode(frames: FrameIterator, capacity: usize) -> Self {
+        Self::decode_on(frames, capacity, None)
+    }
+
+    /// Like [`Pipeline::decode`], with the decode thread pinned to `cpu`
+    /// where the platform allows it
+    pub fn decode_on(frames: FrameIterator, capacity: usize, cpu: Option<usize>) -> Self {
+        Self::source(
+            "decode",
+            capacity,
+            Decoder {
+                frames,
+                cpu,
+                damage: DamageTracker::new(),
+            },
+        )
+    }
+}
+
+/// Blocks for each item, so it must not be used from async code; await
+/// [`Pipeline::recv`] there instead
+impl<T> Iterator for Pipeline<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.receiver.blocking_recv()?;
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+        Some(item)
+    }
+}
+
+/// Frames of a `FrameIterator` with the captions at their time
+struct Decoder {
+    frames: FrameIterator,
+    /// CPU to pin to, on the first frame since that runs on the decode thread
+    cpu: Option<usize>,
+    damage: DamageTracker,
+}
+
+impl Iterator for Decoder {
+    type Item = Result<DecodedFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        pin_thread_or_warn(self.cpu.take(), "decode");
+        let frame = self.frames.next()?;
+        Some(frame.map(|frame| DecodedFrame {
+            damage: self.damage.track(&frame),
+            captions: self.frames.decoder().captions().lines(),
+            frame,
+        }))
+    }
+}
+
+/// Stage made from a closure
+struct FnStage<T, F> {
+    name: String,
+    function: F,
+    item: PhantomData<fn(T) -> T>,
+}
+
+impl<T, F> PipelineStage for FnStage<T, F>
+where
+    T: Send + 'static,
+    F: FnMut(T) -> Result<Option<T>> + Send + 'static,
+{
+    type Input = T;
+    type Output = T;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn process(&mut self, input: T) -> Result<Option<T>> {
+        (self.function)(input)
+    }
+}
+
+/// Crops black bars and camera shake, the player's filter stage.
+///
+/// Clones share one stabilizer, so tracking carries on when a seek starts
+/// a new pipeline instead of settling in again.
+#[derive(Clone, Default)]
+pub struct FilterStage {
+    crop: Option<CropRect>,
+    stabilizer: Option<Arc<Mutex<Stabilizer>>>,
+}
+
+impl FilterStage {
+    pub fn new(crop: Option<CropRect>, stabilizer: Option<Stabilizer>) -> Self {
+        Self {
+            crop,
+            stabilizer: stabilizer.map(|stabilizer| Arc::new(Mutex::new(stabilizer))),
+        }
+    }
+}
+
+impl PipelineStage for FilterStage {
+    type Input = DecodedFrame;
+    type Output = DecodedFrame;
+
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    async fn process(&mut self, mut decoded: DecodedFrame) -> Result<Option<DecodedFrame>> {
+        // Remove black bars so the picture fills the terminal
+        if let Some(rect) = self.crop {
+            decoded.damage = std::mem::replace(&mut decoded.damage, Damage::Full).crop(rect);
+            decoded.frame = match crop_frame(&decoded.frame, rect) {
+                Ok(cropped) => cropped,
+                Err(e) => {
+                    error!("Error cropping frame: {}", e);
+                    return Ok(None);
+                }
+            };
+        }
+        // Crop away camera shake, which moves the whole picture
+        if let Some(ref stabilizer) = self.stabilizer {
+            decoded.damage = Damage::Full;
+            let mut stabilizer = stabilizer.lock().unwrap_or_else(|e| e.into_inner());
+            decoded.frame = match stabilizer.stabilize(decoded.frame) {
+                Ok(stabilized) => stabilized,
+                Err(e) => {
+                    error!("Error stabilizing frame: {}", e);
+                    return Ok(None);
+                }
+            };
+        }
+        Ok(Some(decoded))
+    }
+}
+
+/// Converts decoded frames for a terminal of a fixed size
+pub struct ConvertStage<C> {
+    converter: C,
+    width: u16,
+    height: u16,
+}
+
+impl<C: AsciiConverter + Send + 'static> ConvertStage<C> {
+    pub fn new(converter: C, width: u16, height: u16) -> Self {
+        Self {
+            converter,
+            width,
+            height,
+        }
+    }
+}
+
+impl<C: AsciiConverter + Send + 'static> PipelineStage for ConvertStage<C> {
+    type Input = DecodedFrame;
+    type Output = AsciiFrame;
+
+    fn name(&self) -> &str {
+        "convert"
+    }
+
+    async fn process(&mut self, input: DecodedFrame) -> Result<Option<AsciiFrame>> {
+        self.converter
+            .convert(&input.frame, self.width, self.height)
+            .map(Some)
+    }
+}
+
+/// Draws converted frames to `output` and passes them on
+pub struct RenderStage<W> {
+    renderer: Renderer,
+    output: W,
+    /// Time between frames, None to draw them as they come
+    interval: Option<Duration>,
+    /// When the next frame may be drawn
+    due: Option<Instant>,
+}
+
+impl<W: Write + Send + 'static> RenderStage<W> {
+    /// Draw with `renderer`'s color settings; the terminal is left as it is,
+    /// so call [`Renderer::init`] first to draw on a cleared screen
+    pub fn new(renderer: Renderer, output: W) -> Self {
+        Self {
+            renderer,
+            output,
+            interval: None,
+            due: None,
+        }
+    }
+
+    /// Draw at most `fps` frames per second, waiting between them
+    pub fn with_fps(mut self, fps: f64) -> Self {
+        self.interval = (fps > 0.0).then(|| Duration::from_secs_f64(1.0 / fps));
+        self
+    }
+}
+
+impl<W: Write + Send + 'static> PipelineStage for RenderStage<W> {
+    type Input = AsciiFrame;
+    type Output = AsciiFrame;
+
+    fn name(&self) -> &str {
+        "render"
+    }
+
+    async fn process(&mut self, frame: AsciiFrame) -> Result<Option<AsciiFrame>> {
+        if let Some(due) = self.due {
+            sleep_until(due).await;
+        }
+        self.due = self.interval.map(|interval| Instant::now() + interval);
+
+        let output = self.renderer.encode_frame(&frame)?;
+        self.output.write_all(output)?;
+        self.output.flush()?;
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::{ConversionConfig, FrameConverter};
+    use anyhow::anyhow;
+    use std::sync::mpsc::channel;
+
+    fn numbers(count: u32) -> impl Iterator<Item = Result<u32>> + Send {
+        (0..count).map(Ok)
+    }
+
+    #[test]
+    fn test_pipeline_stages() {
+        let (seen_sender, seen) = channel();
+        let pipeline = Pipeline::source("numbers", 2, numbers(10))
+            .filter("odd", 2, |n| Ok((n % 2 == 1).then_some(n * 10)))
+            .tap("seen", 1, move |n| {
+                let _ = seen_sender.send(*n);
+            })
+            .filter("fail", 1, |n| {
+                if n == 50 {
+                    Err(anyhow!("no fifties"))
+                } else {
+                    Ok(Some(n))
+                }
+            });
+        let metrics = pipeline.metrics().clone();
+        let results: Vec<_> = pipeline
+            .map(|item| item.map_err(|e| e.to_string()))
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(10),
+                Ok(30),
+                Err("no fifties".to_string()),
+                Ok(70),
+                Ok(90)
+            ]
+        );
+        assert_eq!(seen.iter().collect::<Vec<_>>(), vec![10, 30, 50, 70, 90]);
+
+        let queues = metrics.queues();
+        let stages: Vec<_> = queues.iter().map(|queue| queue.stage.as_str()).collect();
+        assert_eq!(stages, ["numbers", "odd", "seen", "fail"]);
+        assert!(queues.iter().all(|queue| queue.depth == 0));
+        assert_eq!(metrics.panics(), 0);
+    }
+
+    #[test]
+    fn test_pipeline_backpressure() {
+        let pipeline = Pipeline::source("numbers", 2, numbers(20));
+        // Nothing is consumed yet, so the source fills its queue and waits
+        while pipeline.metrics().queues()[0].stalls == 0 {
+            thread::yield_now();
+        }
+        let queue = &pipeline.metrics().queues()[0];
+        assert_eq!((queue.depth, queue.capacity), (2, 2));
+        assert_eq!(pipeline.metrics().to_string(), "numbers 2/2 (1 stall)");
+        assert_eq!(pipeline.count(), 20);
+    }
+
+    #[test]
+    fn test_pipeline_panics_drop_items() {
+        crate::supervisor::install_panic_hook();
+        let pipeline = Pipeline::source("numbers", 4, numbers(4)).filter("picky", 4, |n| {
+            assert_ne!(n, 2, "two");
+            Ok(Some(n))
+        });
+        let metrics = pipeline.metrics().clone();
+        let results: Vec<u32> = pipeline.map(|item| item.unwrap()).collect();
+        assert_eq!(results, vec![0, 1, 3]);
+        assert_eq!(metrics.panics(), 1);
+    }
+
+    #[test]
+    fn test_pipeline_recv_from_async_code() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let mut pipeline = Pipeline::source("numbers", 2, numbers(3));
+        let received = runtime.block_on(async {
+            let mut received = Vec::new();
+            while let Some(item) = pipeline.recv().await {
+                received.push(item.unwrap());
+            }
+            received
+        });
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_convert_stage() {
+        let frame = VideoFrame {
+            data: vec![255; 8 * 8 * 3],
+            width: 8,
+            height: 8,
+            timestamp: 0.5,
+            frame_number: 3,
+        };
+        let mut stage = ConvertStage::new(FrameConverter::new(ConversionConfig::default()), 8, 4);
+        let decoded = DecodedFrame {
+            frame,
+            captions: Vec::new(),
+            damage: Damage::Full,
+        };
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let ascii = runtime.block_on(stage.process(decoded)).unwrap().unwrap();
+        assert_eq!(ascii.frame_number, 3);
+        assert!(ascii.characters.iter().all(|&c| c == '@'));
+
+        let renderer = Renderer::new(false, false).unwrap();
+        let mut stage = RenderStage::new(renderer, Vec::new());
+        let drawn = runtime.block_on(stage.process(ascii)).unwrap().unwrap();
+        assert_eq!(drawn.frame_number, 3);
+        let output = String::from_utf8_lossy(&stage.output);
+        assert_eq!(output.matches('@').count(), drawn.characters.len());
+    }
+}
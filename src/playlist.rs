@@ -0,0 +1,234 @@
+//! Several files played one after another
+//!
+//! Every file given on the command line is played in turn, followed by
+//! those listed in a `--playlist` file: one path or URL per line, blank
+//! lines and `#` comments skipped, relative paths taken from the list's
+//! directory (so `.m3u` lists work as they are). A directory stands for
+//! the files in it, by name, leaving out hidden files and those that
+//! [`check_input`] rejects; `--shuffle` plays everything in random order.
+//! `n` and `p` skip to the next and previous file; each file is opened
+//! afresh, with its own status line, bookmarks and notes.
+//!
+//! A file that fails to open or decode is skipped with a warning instead of
+//! ending playback, and `n` and `p` pass over it from then on.
+
+use crate::sniff::check_input;
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+/// Files to play and the one playing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playlist {
+    entries: Vec<PathBuf>,
+    current: usize,
+    /// Whether each entry failed to play
+    failed: Vec<bool>,
+}
+
+impl Playlist {
+    pub fn new(entries: Vec<PathBuf>) -> Self {
+        Self {
+            failed: vec![false; entries.len()],
+            entries,
+            current: 0,
+        }
+    }
+
+    /// Files in `dir` that look playable, sorted by name
+    pub fn scan(dir: &Path) -> Result<Vec<PathBuf>> {
+        let listing = std::fs::read_dir(dir)
+            .map_err(|e| anyhow!("Failed to read directory '{}': {}", dir.display(), e))?;
+        let mut files: Vec<PathBuf> = listing
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                !hidden && path.is_file() && check_input(path).is_ok()
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Read the entries of a playlist file
+    pub fn load(path: &Path) -> Result<Vec<PathBuf>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read playlist '{}': {}", path.display(), e))?;
+        Ok(Self::parse(
+            &contents,
+            path.parent().unwrap_or(Path::new("")),
+        ))
+    }
+
+    /// Entries of a playlist, relative ones resolved against `base`
+    pub fn parse(contents: &str, base: &Path) -> Vec<PathBuf> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let entry = Path::new(line);
+                if entry.is_absolute() || line.contains("://") {
+                    entry.to_path_buf()
+                } else {
+                    base.join(entry)
+                }
+            })
+            .collect()
+    }
+
+    /// The file playing, None for an empty playlist
+    pub fn current(&self) -> Option<&Path> {
+        self.entries.get(self.current).map(PathBuf::as_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.next_playable().is_some()
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.previous_playable().is_some()
+    }
+
+    /// Move on to the next file that hasn't failed; false once past the
+    /// last
+    pub fn advance(&mut self) -> bool {
+        match self.next_playable() {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => {
+                self.current = self.entries.len();
+                false
+            }
+        }
+    }
+
+    /// Go back to the previous file that hasn't failed, or start this one
+    /// again
+    pub fn go_back(&mut self) {
+        if let Some(index) = self.previous_playable() {
+            self.current = index;
+        }
+    }
+
+    /// Remember that the current file can't be played
+    pub fn mark_failed(&mut self) {
+        if let Some(failed) = self.failed.get_mut(self.current) {
+            *failed = true;
+        }
+    }
+
+    /// Files that failed to play so far
+    pub fn failed(&self) -> impl Iterator<Item = &Path> {
+        self.entries
+            .iter()
+            .zip(&self.failed)
+            .filter(|(_, &failed)| failed)
+            .map(|(entry, _)| entry.as_path())
+    }
+
+    /// Put the files in a random order from `seed`, starting from the first
+    pub fn shuffle(&mut self, seed: u64) {
+        // xorshift64*, enough to deal a playlist
+        let mut state = seed | 1;
+        let mut random = move || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        };
+        for i in (1..self.entries.len()).rev() {
+            let j = (random() % (i as u64 + 1)) as usize;
+            self.entries.swap(i, j);
+            self.failed.swap(i, j);
+        }
+        self.current = 0;
+    }
+
+    fn next_playable(&self) -> Option<usize> {
+        (self.current + 1..self.entries.len()).find(|&index| !self.failed[index])
+    }
+
+    fn previous_playable(&self) -> Option<usize> {
+        (0..self.current.min(self.entries.len()))
+            .rev()
+            .find(|&index| !self.failed[index])
+    }
+
+    /// Position for the status line, such as `2/5`; None for a single file
+    pub fn status(&self) -> Option<String> {
+        (self.entries.len() > 1).then(|| format!("{}/{}", self.current + 1, self.entries.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_playlist() {
+        let entries = Playlist::parse(
+            "#EXTM3U\n\nintro.mp4\n  /videos/talk.mkv \nhttps://example.com/live.m3u8\n",
+            Path::new("/home/me/lists"),
+        );
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/home/me/lists/intro.mp4"),
+                PathBuf::from("/videos/talk.mkv"),
+                PathBuf::from("https://example.com/live.m3u8"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_and_previous() {
+        let mut playlist = Playlist::new(vec!["a.mp4".into(), "b.mp4".into()]);
+        assert_eq!(playlist.status().as_deref(), Some("1/2"));
+        assert!(!playlist.has_previous());
+        playlist.go_back();
+        assert_eq!(playlist.current(), Some(Path::new("a.mp4")));
+
+        assert!(playlist.has_next());
+        assert!(playlist.advance());
+        assert_eq!(playlist.current(), Some(Path::new("b.mp4")));
+        assert!(!playlist.has_next());
+        assert!(!playlist.advance());
+        assert_eq!(playlist.current(), None);
+
+        assert_eq!(Playlist::new(vec!["a.mp4".into()]).status(), None);
+    }
+
+    #[test]
+    fn test_failed_files_and_shuffle() {
+        let mut playlist = Playlist::new(vec!["a.mp4".into(), "b.txt".into(), "c.mp4".into()]);
+        assert!(playlist.advance());
+        playlist.mark_failed();
+        assert!(playlist.advance());
+        assert_eq!(playlist.current(), Some(Path::new("c.mp4")));
+        playlist.go_back();
+        assert_eq!(playlist.current(), Some(Path::new("a.mp4")));
+        assert_eq!(playlist.failed().collect::<Vec<_>>(), [Path::new("b.txt")]);
+
+        // Shuffling deals the same files in another order
+        let files: Vec<PathBuf> = (0..20)
+            .map(|i| PathBuf::from(format!("{:02}.mp4", i)))
+            .collect();
+        let mut shuffled = Playlist::new(files.clone());
+        shuffled.shuffle(7);
+        assert_ne!(shuffled.entries, files);
+        shuffled.entries.sort();
+        assert_eq!(shuffled.entries, files);
+    }
+}
@@ -0,0 +1,293 @@
+//! Python bindings
+//!
+//! With the `python` feature the library is also the `ascii_player` Python
+//! module, for rendering ASCII previews of frames in notebooks and scripts.
+//! Build it with `maturin develop` (see `pyproject.toml`).
+//!
+//! ```python
+//! import ascii_player
+//!
+//! ascii_player.probe("clip.mp4")            # {'fps': 25.0, 'duration': ...}
+//! frame = ascii_player.preview("clip.mp4", time=12.0, columns=100)
+//! print(frame)                              # plain text
+//! frame                                     # colored, in Jupyter
+//!
+//! # Any RGB24 picture, e.g. a (height, width, 3) uint8 numpy array
+//! ascii_player.convert_frame(array.tobytes(), width, height, settings="contrast=1.3")
+//!
+//! # Every frame of a video, converted as it is decoded
+//! for frame in ascii_player.frames("clip.mp4", columns=60, rows=20):
+//!     ...
+//! ```
+//!
+//! Frames are read through [`FrameSource`] and converted by the same
+//! [`AsciiConverter`] as in the player; `settings` takes the `key=value`
+//! list of [`ConversionConfig::apply_overrides`]. Decoding and converting
+//! release the GIL, so other Python threads run meanwhile.
+
+use crate::clipboard::frame_text;
+use crate::converter::{AsciiConverter, AsciiFrame, ConversionConfig, FrameConverter};
+use crate::decoder::{load_video, FrameIterator, FrameSource, VideoFrame};
+use crate::headless::HeadlessRenderer;
+use crate::html::frame_markup;
+use pyo3::exceptions::{PyStopIteration, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::PathBuf;
+
+/// Grid frames are converted at unless `columns` and `rows` are given
+const DEFAULT_COLUMNS: u16 = 80;
+const DEFAULT_ROWS: u16 = 24;
+
+/// A converter with `settings` applied over the defaults
+fn converter(settings: Option<&str>) -> PyResult<FrameConverter> {
+    let mut config = ConversionConfig::default();
+    if let Some(settings) = settings {
+        config
+            .apply_overrides(settings)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    }
+    Ok(FrameConverter::new(config))
+}
+
+/// A converted frame
+#[pyclass(name = "AsciiFrame", module = "ascii_player")]
+pub struct PyAsciiFrame {
+    frame: AsciiFrame,
+}
+
+#[pymethods]
+impl PyAsciiFrame {
+    #[getter]
+    fn width(&self) -> u16 {
+        self.frame.width
+    }
+
+    #[getter]
+    fn height(&self) -> u16 {
+        self.frame.height
+    }
+
+    #[getter]
+    fn timestamp(&self) -> f64 {
+        self.frame.timestamp
+    }
+
+    #[getter]
+    fn frame_number(&self) -> u64 {
+        self.frame.frame_number
+    }
+
+    /// Foreground color of every cell, row by row
+    #[getter]
+    fn colors(&self) -> Vec<(u8, u8, u8)> {
+        self.frame.fg_colors.clone()
+    }
+
+    /// The characters, one line per row
+    fn text(&self) -> String {
+        frame_text(&self.frame, false)
+    }
+
+    /// The characters with truecolor escape sequences, for a terminal
+    fn ansi(&self) -> String {
+        frame_text(&self.frame, true)
+    }
+
+    /// The frame as `--deterministic` writes it: a header with the frame's
+    /// hash, then the rows
+    fn render(&self) -> PyResult<String> {
+        let mut output = Vec::new();
+        HeadlessRenderer::new(&mut output).render_frame(&self.frame)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    fn __str__(&self) -> String {
+        self.text()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<AsciiFrame {}x{} at {:.3}s>",
+            self.frame.width, self.frame.height, self.frame.timestamp
+        )
+    }
+
+    /// Colored rendering for Jupyter
+    fn _repr_html_(&self) -> String {
+        format!(
+            "<pre style=\"font-family: monospace; line-height: 1.1; background: #000; color: #fff\">{}</pre>",
+            frame_markup(&self.frame)
+        )
+    }
+}
+
+/// Converted frames of a video, in order
+#[pyclass(module = "ascii_player", unsendable)]
+pub struct FrameTap {
+    frames: FrameIterator,
+    converter: FrameConverter,
+    size: (u16, u16),
+}
+
+#[pymethods]
+impl FrameTap {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<PyAsciiFrame> {
+        let (frames, converter, (columns, rows)) = (&mut self.frames, &self.converter, self.size);
+        let next = py.allow_threads(|| {
+            frames
+                .next()
+                .map(|frame| converter.convert(&frame?, columns, rows))
+        });
+        match next {
+            Some(frame) => Ok(PyAsciiFrame { frame: frame? }),
+            None => Err(PyStopIteration::new_err(())),
+        }
+    }
+}
+
+/// Frame rate, duration and size of a video
+#[pyfunction]
+fn probe(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyDict>> {
+    let frames = py.allow_threads(|| load_video(&path, None, None))?;
+    let source: &dyn FrameSource = frames.decoder();
+    let (width, height) = source.dimensions();
+    let info = PyDict::new_bound(py);
+    info.set_item("fps", source.fps())?;
+    info.set_item("duration", source.duration())?;
+    info.set_item("width", width)?;
+    info.set_item("height", height)?;
+    Ok(info)
+}
+
+/// Convert an RGB24 picture of `width` x `height` pixels
+#[pyfunction]
+#[pyo3(signature = (data, width, height, columns = DEFAULT_COLUMNS, rows = DEFAULT_ROWS, settings = None))]
+fn convert_frame(
+    py: Python<'_>,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    columns: u16,
+    rows: u16,
+    settings: Option<&str>,
+) -> PyResult<PyAsciiFrame> {
+    let expected = width as usize * height as usize * 3;
+    if data.len() != expected {
+        return Err(PyValueError::new_err(format!(
+            "Expected {} bytes of RGB for {}x{}, got {}",
+            expected,
+            width,
+            height,
+            data.len()
+        )));
+    }
+    let frame = VideoFrame {
+        data: data.to_vec(),
+        width,
+        height,
+        timestamp: 0.0,
+        frame_number: 0,
+    };
+    let converter = converter(settings)?;
+    let frame = py.allow_threads(|| converter.convert(&frame, columns, rows))?;
+    Ok(PyAsciiFrame { frame })
+}
+
+/// The frame of a video shown at `time` seconds
+#[pyfunction]
+#[pyo3(signature = (path, time = 0.0, columns = DEFAULT_COLUMNS, rows = DEFAULT_ROWS, settings = None))]
+fn preview(
+    py: Python<'_>,
+    path: PathBuf,
+    time: f64,
+    columns: u16,
+    rows: u16,
+    settings: Option<&str>,
+) -> PyResult<PyAsciiFrame> {
+    let converter = converter(settings)?;
+    let frame = py.allow_threads(|| {
+        let mut frames = load_video(&path, None, None)?;
+        frames.seek(time)?;
+        frames
+            .next()
+            .map(|frame| converter.convert(&frame?, columns, rows))
+            .transpose()
+    })?;
+    let frame = frame.ok_or_else(|| PyValueError::new_err(format!("No frame at {:.2}s", time)))?;
+    Ok(PyAsciiFrame { frame })
+}
+
+/// Iterate over the converted frames of a video, optionally between
+/// `start` and `end` seconds
+#[pyfunction]
+#[pyo3(signature = (path, columns = DEFAULT_COLUMNS, rows = DEFAULT_ROWS, settings = None, start = None, end = None))]
+fn frames(
+    path: PathBuf,
+    columns: u16,
+    rows: u16,
+    settings: Option<&str>,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> PyResult<FrameTap> {
+    Ok(FrameTap {
+        converter: converter(settings)?,
+        frames: load_video(&path, start, end)?,
+        size: (columns, rows),
+    })
+}
+
+#[pymodule]
+fn ascii_player(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add_class::<PyAsciiFrame>()?;
+    module.add_class::<FrameTap>()?;
+    module.add_function(wrap_pyfunction!(probe, module)?)?;
+    module.add_function(wrap_pyfunction!(convert_frame, module)?)?;
+    module.add_function(wrap_pyfunction!(preview, module)?)?;
+    module.add_function(wrap_pyfunction!(frames, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_frame() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let frame = convert_frame(py, &[255; 4 * 2 * 3], 4, 2, 4, 2, None).unwrap();
+            assert_eq!((frame.width(), frame.height()), (4, 2));
+            assert!(frame.text().lines().all(|line| line == "@@@@"));
+
+            let error = convert_frame(py, &[0; 5], 4, 2, 4, 2, None).err().unwrap();
+            assert!(error.is_instance_of::<PyValueError>(py));
+            let error = convert_frame(py, &[0; 3], 1, 1, 4, 2, Some("gamma=2"))
+                .err()
+                .unwrap();
+            assert!(error.to_string().contains("Unknown setting"));
+        });
+    }
+
+    #[test]
+    fn test_module() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "ascii_player").unwrap();
+            ascii_player(&module).unwrap();
+            let frame = module
+                .getattr("convert_frame")
+                .unwrap()
+                .call1((vec![0u8; 3].as_slice(), 1, 1, 2, 1))
+                .unwrap();
+            assert_eq!(frame.str().unwrap().to_string(), "  \n");
+            assert!(module.getattr("preview").is_ok());
+            assert!(module.getattr("frames").is_ok());
+        });
+    }
+}
@@ -0,0 +1,76 @@
+//! QR code overlay pointing viewers at the source
+//!
+//! `--qr` shows a QR code of the input URL or path in the top right corner
+//! for the first few seconds, so people watching a demo screen can open the
+//! video themselves. Each cell holds two modules stacked with half blocks,
+//! drawn light on dark: light modules are filled, dark ones left blank.
+
+use crate::converter::AsciiFrame;
+use crate::overlay::{draw_in_corner, Corner};
+use anyhow::{anyhow, Result};
+use qrcodegen::{QrCode, QrCodeEcc};
+use std::time::Duration;
+
+/// Seconds of video the QR code stays on screen from the start
+pub const QR_DURATION: Duration = Duration::from_secs(10);
+
+/// Light border around the code, in modules. The standard asks for 4, but 2
+/// scans fine on a screen and keeps the code small.
+const QUIET_ZONE: i32 = 2;
+
+/// Rows of half-block characters drawing a QR code of `text`
+pub fn qr_lines(text: &str) -> Result<Vec<String>> {
+    let code = QrCode::encode_text(text, QrCodeEcc::Low)
+        .map_err(|_| anyhow!("'{}' is too long for a QR code", text))?;
+
+    let light = |x: i32, y: i32| !code.get_module(x, y);
+    let span = -QUIET_ZONE..code.size() + QUIET_ZONE;
+    Ok(span
+        .clone()
+        .step_by(2)
+        .map(|y| {
+            span.clone()
+                .map(|x| match (light(x, y), light(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Draw the code in the top right corner; false if the frame is too small
+pub fn draw_qr(frame: &mut AsciiFrame, lines: &[String]) -> bool {
+    draw_in_corner(frame, lines, Corner::TopRight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::source_location;
+    use crate::overlay::blank_frame;
+    use std::path::Path;
+
+    #[test]
+    fn test_qr_overlay() {
+        // Version 1 is 21 modules, plus the quiet zone on both sides
+        let lines = qr_lines("https://example.com/demo.mp4").unwrap();
+        let width = lines[0].chars().count();
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(width >= 21 + 4);
+        // The quiet zone and the finder pattern's light ring start each code
+        assert!(lines[0].chars().all(|c| c == '█'));
+
+        let mut frame = blank_frame(80, 24);
+        assert!(draw_qr(&mut frame, &lines));
+        let row: String = frame.characters[80..160].iter().collect();
+        assert!(row.ends_with(&format!("{} ", lines[0])));
+
+        let mut small = blank_frame(20, 10);
+        assert!(!draw_qr(&mut small, &lines));
+        assert_eq!(source_location(Path::new("rtsp://cam/1")), "rtsp://cam/1");
+    }
+}
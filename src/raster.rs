@@ -0,0 +1,226 @@
+//! Drawing ASCII frames as images
+//!
+//! Exports meant to be shared outside a terminal, such as GIFs, need the
+//! frame as pixels. Every cell is drawn with a bundled 8×8 bitmap font (the
+//! public domain `font8x8_basic` set) with each font row doubled, so cells
+//! are 8×16 pixels and keep the 1:2 shape the converter assumes. Shade,
+//! block and braille glyphs are generated rather than stored; glyphs the
+//! font lacks are drawn as `?`.
+
+use crate::converter::AsciiFrame;
+use image::{Rgb, RgbImage};
+
+/// Width of a cell in pixels
+pub const CELL_WIDTH: u32 = 8;
+/// Height of a cell in pixels
+pub const CELL_HEIGHT: u32 = 16;
+
+/// Rows of the printable ASCII glyphs, from space to `~`; bit 0 of each row
+/// is the leftmost pixel
+const FONT: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // '#'
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // '$'
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // '%'
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // '&'
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // '('
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // ')'
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // '*'
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ','
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // '.'
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // '/'
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // '0'
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // '1'
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // '2'
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // '3'
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // '4'
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // '5'
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // '6'
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // '7'
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // '8'
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ';'
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // '<'
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // '='
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // '>'
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // '?'
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // '@'
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // 'A'
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // 'B'
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // 'C'
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // 'D'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // 'E'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // 'F'
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // 'G'
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // 'H'
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'I'
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // 'J'
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // 'K'
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // 'L'
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // 'M'
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // 'N'
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // 'O'
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // 'P'
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // 'Q'
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // 'R'
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // 'S'
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'T'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // 'U'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'V'
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // 'W'
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // 'X'
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // 'Y'
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // 'Z'
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // '['
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // '\'
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ']'
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // '_'
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 'a'
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // 'b'
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // 'c'
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // 'd'
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 'e'
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // 'f'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'g'
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // 'h'
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'i'
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // 'j'
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // 'k'
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'l'
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // 'm'
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 'n'
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 'o'
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 'p'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // 'q'
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // 'r'
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // 's'
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // 't'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 'u'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'v'
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // 'w'
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // 'x'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'y'
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // 'z'
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // '{'
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // '|'
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // '}'
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+/// Whether the pixel at `(x, y)` of a cell, in 0..8 by 0..16, is inked in
+/// `glyph`
+fn inked(glyph: char, x: u32, y: u32) -> bool {
+    match glyph {
+        // Shades: every fourth pixel, a checkerboard, all but every fourth
+        '░' => x % 2 == 0 && y % 2 == 0,
+        '▒' => (x + y) % 2 == 0,
+        '▓' => !(x % 2 == 0 && y % 2 == 0),
+        '█' => true,
+        '▀' => y < CELL_HEIGHT / 2,
+        '▄' => y >= CELL_HEIGHT / 2,
+        '▌' => x < CELL_WIDTH / 2,
+        '▐' => x >= CELL_WIDTH / 2,
+        // Braille: dots 1-3 and 7 down the left, 4-6 and 8 down the right
+        '\u{2800}'..='\u{28FF}' => {
+            let dots = glyph as u32 - 0x2800;
+            let (column, row) = (x / 4, y / 4);
+            let bit = match (column, row) {
+                (0, 3) => 6,
+                (1, 3) => 7,
+                (0, row) => row,
+                (_, row) => row + 3,
+            };
+            // Round dots with a pixel of space around them
+            let inside = (1..3).contains(&(x % 4)) && (1..3).contains(&(y % 4));
+            inside && dots & (1 << bit) != 0
+        }
+        _ => {
+            let index = match glyph {
+                ' '..='~' => glyph as usize - ' ' as usize,
+                _ => '?' as usize - ' ' as usize,
+            };
+            FONT[index][(y / 2) as usize] & (1 << x) != 0
+        }
+    }
+}
+
+/// Draw `frame` as an image of `CELL_WIDTH` × `CELL_HEIGHT` pixel cells.
+///
+/// Cells without a background color are drawn on black.
+pub fn render_image(frame: &AsciiFrame) -> RgbImage {
+    let width = frame.width as u32;
+    let mut image = RgbImage::new(width * CELL_WIDTH, frame.height as u32 * CELL_HEIGHT);
+    for (index, &glyph) in frame.characters.iter().enumerate() {
+        let (column, row) = (index as u32 % width, index as u32 / width);
+        let foreground = frame
+            .fg_colors
+            .get(index)
+            .copied()
+            .unwrap_or((255, 255, 255));
+        let background = frame
+            .bg_colors
+            .as_ref()
+            .and_then(|colors| colors.get(index).copied())
+            .unwrap_or((0, 0, 0));
+        for y in 0..CELL_HEIGHT {
+            for x in 0..CELL_WIDTH {
+                let (r, g, b) = if inked(glyph, x, y) {
+                    foreground
+                } else {
+                    background
+                };
+                image.put_pixel(
+                    column * CELL_WIDTH + x,
+                    row * CELL_HEIGHT + y,
+                    Rgb([r, g, b]),
+                );
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyphs() {
+        // Top bar of 'T', rows doubled
+        assert!((0..6).all(|x| inked('T', x, 0) && inked('T', x, 1)));
+        assert!(!inked('T', 7, 0));
+        assert!(!(0..8).any(|x| (0..16).any(|y| inked(' ', x, y))));
+        // Unknown glyphs fall back to '?'
+        assert!((0..8).all(|x| (0..16).all(|y| inked('λ', x, y) == inked('?', x, y))));
+        assert!(inked('▀', 3, 2) && !inked('▀', 3, 12));
+        // ⠁ is dot 1 only, ⣿ all eight
+        assert!(inked('⠁', 1, 1) && !inked('⠁', 5, 1) && !inked('⠁', 1, 13));
+        assert!(inked('⣿', 5, 13));
+    }
+
+    #[test]
+    fn test_render_image() {
+        let frame = AsciiFrame {
+            characters: vec!['█', ' '],
+            fg_colors: vec![(255, 0, 0), (0, 255, 0)],
+            bg_colors: Some(vec![(0, 0, 0), (0, 0, 255)]),
+            attributes: None,
+            width: 2,
+            height: 1,
+            timestamp: 0.0,
+            frame_number: 0,
+        };
+        let image = render_image(&frame);
+        assert_eq!(image.dimensions(), (16, 16));
+        assert_eq!(image.get_pixel(3, 8), &Rgb([255, 0, 0]));
+        assert_eq!(image.get_pixel(12, 8), &Rgb([0, 0, 255]));
+    }
+}
@@ -0,0 +1,1006 @@
+//! `.ascv` recordings of converted frames
+//!
+//! A recording stores ASCII frames exactly as they were rendered, so it
+//! replays without FFmpeg and at any terminal size the frames fit in. Files
+//! are a small header followed by records:
+//!
+//! ```text
+//! "ASCV" version:u8 fps:f64
+//! FRAME  (1) timestamp:f64 frame_number:u64 width:u16 height:u16 flags:u8 cells...
+//! REPEAT (2) count:u32 duration:f64
+//! DELTA  (3) timestamp:f64 frame_number:u64 runs:u32 (start:u32 length:u16 cells...)...
+//! ```
+//!
+//! Each cell is a `u32` character and an RGB foreground, plus an RGB
+//! background when flag bit 0 is set and an attribute byte (0 normal, 1 bold,
+//! 2 dim) when flag bit 1 is set. All numbers are little-endian.
+//! Identical consecutive frames, found by hashing frame content, are stored
+//! once followed by a REPEAT marker covering how long they stayed on screen.
+//!
+//! Since version 2, a frame that differs from the previous one in only a few
+//! cells is stored as a DELTA: runs of changed cells patched onto the previous
+//! frame. A full FRAME (keyframe) is still written at least every
+//! [`KEYFRAME_INTERVAL`] frames so a damaged or cut-off file recovers quickly.
+//! Deltas carry the same cell fields as the keyframe before them.
+//!
+//! Version 3 added cell attributes (flag bit 1).
+
+use crate::cli::Cli;
+use crate::converter::{AsciiFrame, CellAttribute};
+use crate::renderer::Renderer;
+use anyhow::{anyhow, Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// File extension of recordings
+pub const RECORDING_EXTENSION: &str = "ascv";
+
+/// Bytes every recording starts with
+pub const RECORDING_MAGIC: &[u8; 4] = b"ASCV";
+/// Format version written by this release; older versions are still read
+pub const RECORDING_VERSION: u8 = 3;
+const TAG_FRAME: u8 = 1;
+const TAG_REPEAT: u8 = 2;
+const TAG_DELTA: u8 = 3;
+const FLAG_BACKGROUND: u8 = 1;
+const FLAG_ATTRIBUTES: u8 = 2;
+
+/// Most frames written as deltas between two keyframes
+pub const KEYFRAME_INTERVAL: u32 = 300;
+
+/// Unchanged cells between two changed ones that are sent anyway rather than
+/// starting a new run; a run header costs about as much as one cell
+const RUN_MERGE_GAP: usize = 1;
+
+/// Whether a path names a recording rather than a video
+pub fn is_recording(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(RECORDING_EXTENSION))
+}
+
+/// 64-bit FNV-1a hash of a frame's size, characters, colors and attributes
+pub fn frame_hash(frame: &AsciiFrame) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+        }
+    };
+
+    feed(&frame.width.to_le_bytes());
+    feed(&frame.height.to_le_bytes());
+    for (index, character) in frame.characters.iter().enumerate() {
+        feed(&(*character as u32).to_le_bytes());
+        let (r, g, b) = frame.fg_colors[index];
+        feed(&[r, g, b]);
+        if let Some(ref bg_colors) = frame.bg_colors {
+            let (r, g, b) = bg_colors[index];
+            feed(&[r, g, b]);
+        }
+        if let Some(ref attributes) = frame.attributes {
+            feed(&[attribute_code(attributes[index])]);
+        }
+    }
+    hash
+}
+
+/// Whether two frames show the same picture
+fn same_content(a: &AsciiFrame, b: &AsciiFrame) -> bool {
+    a.width == b.width
+        && a.height == b.height
+        && a.characters == b.characters
+        && a.fg_colors == b.fg_colors
+        && a.bg_colors == b.bg_colors
+        && a.attributes == b.attributes
+}
+
+/// Bytes a cell takes in a record
+fn cell_size(has_background: bool, has_attributes: bool) -> usize {
+    7 + if has_background { 3 } else { 0 } + usize::from(has_attributes)
+}
+
+fn attribute_code(attribute: CellAttribute) -> u8 {
+    match attribute {
+        CellAttribute::Normal => 0,
+        CellAttribute::Bold => 1,
+        CellAttribute::Dim => 2,
+    }
+}
+
+/// Unknown codes read as normal text
+fn attribute_from_code(code: u8) -> CellAttribute {
+    match code {
+        1 => CellAttribute::Bold,
+        2 => CellAttribute::Dim,
+        _ => CellAttribute::Normal,
+    }
+}
+
+fn encode_cell(record: &mut Vec<u8>, frame: &AsciiFrame, index: usize) {
+    record.extend_from_slice(&(frame.characters[index] as u32).to_le_bytes());
+    let (r, g, b) = frame.fg_colors[index];
+    record.extend_from_slice(&[r, g, b]);
+    if let Some(ref bg_colors) = frame.bg_colors {
+        let (r, g, b) = bg_colors[index];
+        record.extend_from_slice(&[r, g, b]);
+    }
+    if let Some(ref attributes) = frame.attributes {
+        record.push(attribute_code(attributes[index]));
+    }
+}
+
+/// Ranges of cells that differ between two frames of the same size
+fn changed_runs(previous: &AsciiFrame, frame: &AsciiFrame) -> Vec<(usize, usize)> {
+    let differs = |index: usize| {
+        previous.characters[index] != frame.characters[index]
+            || previous.fg_colors[index] != frame.fg_colors[index]
+            || match (&previous.bg_colors, &frame.bg_colors) {
+                (Some(a), Some(b)) => a[index] != b[index],
+                _ => false,
+            }
+            || match (&previous.attributes, &frame.attributes) {
+                (Some(a), Some(b)) => a[index] != b[index],
+                _ => false,
+            }
+    };
+
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for index in (0..frame.characters.len()).filter(|&index| differs(index)) {
+        match runs.last_mut() {
+            Some((start, length))
+                if index - (*start + *length) <= RUN_MERGE_GAP
+                    && index + 1 - *start <= u16::MAX as usize =>
+            {
+                *length = index + 1 - *start;
+            }
+            _ => runs.push((index, 1)),
+        }
+    }
+    runs
+}
+
+/// Changed cells of a frame relative to the one before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameDelta {
+    pub timestamp: f64,
+    pub frame_number: u64,
+    /// Runs of consecutive cells, starting at a cell index
+    pub runs: Vec<CellRun>,
+}
+
+/// Replacement cells starting at `start`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellRun {
+    pub start: usize,
+    pub characters: Vec<char>,
+    pub fg_colors: Vec<(u8, u8, u8)>,
+    pub bg_colors: Option<Vec<(u8, u8, u8)>>,
+    pub attributes: Option<Vec<CellAttribute>>,
+}
+
+impl FrameDelta {
+    /// Patch the previous frame into this one
+    pub fn apply(&self, frame: &mut AsciiFrame) -> Result<()> {
+        for run in &self.runs {
+            let end = run.start + run.characters.len();
+            if end > frame.characters.len() {
+                return Err(anyhow!(
+                    "Delta for frame {} writes past the end of the frame",
+                    self.frame_number
+                ));
+            }
+            frame.characters[run.start..end].copy_from_slice(&run.characters);
+            frame.fg_colors[run.start..end].copy_from_slice(&run.fg_colors);
+            if let (Some(target), Some(source)) = (frame.bg_colors.as_mut(), &run.bg_colors) {
+                target[run.start..end].copy_from_slice(source);
+            }
+            if let (Some(target), Some(source)) = (frame.attributes.as_mut(), &run.attributes) {
+                target[run.start..end].copy_from_slice(source);
+            }
+        }
+        frame.timestamp = self.timestamp;
+        frame.frame_number = self.frame_number;
+        Ok(())
+    }
+
+    /// Indices of every cell the delta changes
+    pub fn cells(&self) -> impl Iterator<Item = usize> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|run| run.start..run.start + run.characters.len())
+    }
+}
+
+/// An entry read back from a recording
+#[derive(Debug, Clone)]
+pub enum RecordEntry {
+    /// A full frame (keyframe) to display at its timestamp
+    Frame(AsciiFrame),
+    /// Changes to apply to the previous frame
+    Delta(FrameDelta),
+    /// The previous frame stays on screen for `count` more frames lasting
+    /// `duration` seconds
+    Repeat { count: u32, duration: f64 },
+}
+
+/// Totals reported when a recording is finished
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingStats {
+    /// Frames passed to the writer
+    pub frames: u64,
+    /// Frames stored in full
+    pub stored: u64,
+    /// Frames stored as changes to the previous frame
+    pub deltas: u64,
+    /// Frames replaced by repeat markers
+    pub repeated: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingRepeat {
+    count: u32,
+    duration: f64,
+}
+
+/// Writes frames to a recording, collapsing runs of identical frames
+pub struct RecordingWriter<W: Write> {
+    output: W,
+    previous: Option<(u64, AsciiFrame)>,
+    pending: Option<PendingRepeat>,
+    since_keyframe: u32,
+    bytes_written: u64,
+    stats: RecordingStats,
+}
+
+impl RecordingWriter<BufWriter<File>> {
+    /// Create a recording file
+    pub fn create(path: &Path, fps: f64) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        Self::new(BufWriter::new(file), fps)
+    }
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Start a recording on `output`
+    pub fn new(output: W, fps: f64) -> Result<Self> {
+        let mut writer = Self::resume(output, 0, RecordingStats::default());
+        writer.emit(RECORDING_MAGIC)?;
+        writer.emit(&[RECORDING_VERSION])?;
+        writer.emit(&fps.to_le_bytes())?;
+        Ok(writer)
+    }
+
+    /// Continue a recording whose first `bytes_written` bytes are already on
+    /// `output`. The next frame is written as a keyframe.
+    pub fn resume(output: W, bytes_written: u64, stats: RecordingStats) -> Self {
+        Self {
+            output,
+            previous: None,
+            pending: None,
+            since_keyframe: 0,
+            bytes_written,
+            stats,
+        }
+    }
+
+    /// Totals so far
+    pub fn stats(&self) -> RecordingStats {
+        self.stats
+    }
+
+    /// Write out everything buffered, including a pending repeat marker, and
+    /// return the length of the recording at this point
+    pub fn checkpoint(&mut self) -> Result<u64> {
+        self.flush_repeat()?;
+        self.output.flush()?;
+        Ok(self.bytes_written)
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<()> {
+        self.output.write_all(bytes)?;
+        self.bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Append a frame
+    pub fn write_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        self.stats.frames += 1;
+        let hash = frame_hash(frame);
+
+        if let Some((previous_hash, ref mut previous)) = self.previous {
+            if previous_hash == hash && same_content(previous, frame) {
+                let pending = self.pending.get_or_insert(PendingRepeat {
+                    count: 0,
+                    duration: 0.0,
+                });
+                pending.count += 1;
+                pending.duration += (frame.timestamp - previous.timestamp).max(0.0);
+                previous.timestamp = frame.timestamp;
+                self.stats.repeated += 1;
+                return Ok(());
+            }
+        }
+
+        self.flush_repeat()?;
+        if !self.write_delta(frame)? {
+            self.write_full_frame(frame)?;
+            self.since_keyframe = 0;
+            self.stats.stored += 1;
+        }
+        self.previous = Some((hash, frame.clone()));
+        Ok(())
+    }
+
+    /// Write any pending repeat marker and flush the output
+    pub fn finish(mut self) -> Result<RecordingStats> {
+        self.checkpoint()?;
+        Ok(self.stats)
+    }
+
+    fn flush_repeat(&mut self) -> Result<()> {
+        if let Some(pending) = self.pending.take() {
+            self.emit(&[TAG_REPEAT])?;
+            self.emit(&pending.count.to_le_bytes())?;
+            self.emit(&pending.duration.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Write the frame as a delta if that is allowed and smaller than a
+    /// keyframe; returns whether it was written
+    fn write_delta(&mut self, frame: &AsciiFrame) -> Result<bool> {
+        let Some((_, ref previous)) = self.previous else {
+            return Ok(false);
+        };
+        if self.since_keyframe >= KEYFRAME_INTERVAL
+            || previous.width != frame.width
+            || previous.height != frame.height
+            || previous.bg_colors.is_some() != frame.bg_colors.is_some()
+            || previous.attributes.is_some() != frame.attributes.is_some()
+            || previous.characters.len() != frame.characters.len()
+        {
+            return Ok(false);
+        }
+
+        let runs = changed_runs(previous, frame);
+        let size = cell_size(frame.bg_colors.is_some(), frame.attributes.is_some());
+        let changed: usize = runs.iter().map(|(_, length)| length).sum();
+        if runs.len() * 6 + changed * size >= frame.characters.len() * size {
+            return Ok(false);
+        }
+
+        let mut record = Vec::with_capacity(21 + runs.len() * 6 + changed * size);
+        record.push(TAG_DELTA);
+        record.extend_from_slice(&frame.timestamp.to_le_bytes());
+        record.extend_from_slice(&frame.frame_number.to_le_bytes());
+        record.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (start, length) in runs {
+            record.extend_from_slice(&(start as u32).to_le_bytes());
+            record.extend_from_slice(&(length as u16).to_le_bytes());
+            for index in start..start + length {
+                encode_cell(&mut record, frame, index);
+            }
+        }
+
+        self.emit(&record)?;
+        self.since_keyframe += 1;
+        self.stats.deltas += 1;
+        Ok(true)
+    }
+
+    fn write_full_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let cells = frame.width as usize * frame.height as usize;
+        if frame.characters.len() != cells
+            || frame.fg_colors.len() != cells
+            || frame.bg_colors.as_ref().is_some_and(|bg| bg.len() != cells)
+            || frame
+                .attributes
+                .as_ref()
+                .is_some_and(|attributes| attributes.len() != cells)
+        {
+            return Err(anyhow!(
+                "Frame {} has {} cells, expected {}",
+                frame.frame_number,
+                frame.characters.len(),
+                cells
+            ));
+        }
+
+        let mut flags = 0;
+        if frame.bg_colors.is_some() {
+            flags |= FLAG_BACKGROUND;
+        }
+        if frame.attributes.is_some() {
+            flags |= FLAG_ATTRIBUTES;
+        }
+        let mut record = Vec::with_capacity(24 + cells * 11);
+        record.push(TAG_FRAME);
+        record.extend_from_slice(&frame.timestamp.to_le_bytes());
+        record.extend_from_slice(&frame.frame_number.to_le_bytes());
+        record.extend_from_slice(&frame.width.to_le_bytes());
+        record.extend_from_slice(&frame.height.to_le_bytes());
+        record.push(flags);
+
+        for index in 0..cells {
+            encode_cell(&mut record, frame, index);
+        }
+
+        self.emit(&record)?;
+        Ok(())
+    }
+}
+
+/// Reads entries back from a recording
+pub struct RecordingReader<R: Read> {
+    input: R,
+    version: u8,
+    fps: f64,
+    /// Flags of the last keyframe, whose cell fields deltas share
+    flags: Option<u8>,
+}
+
+impl RecordingReader<BufReader<File>> {
+    /// Open a recording file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open recording {}", path.display()))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Read the header from `input`
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        input
+            .read_exact(&mut magic)
+            .map_err(|_| anyhow!("Not an ASCV recording"))?;
+        if &magic != RECORDING_MAGIC {
+            return Err(anyhow!("Not an ASCV recording"));
+        }
+
+        let version = read_u8(&mut input)?;
+        // Version 1 is version 2 without deltas
+        if !(1..=RECORDING_VERSION).contains(&version) {
+            return Err(anyhow!(
+                "Unsupported recording version {} (this release reads up to {})",
+                version,
+                RECORDING_VERSION
+            ));
+        }
+        let fps = f64::from_le_bytes(read_array(&mut input)?);
+
+        Ok(Self {
+            input,
+            version,
+            fps,
+            flags: None,
+        })
+    }
+
+    /// Format version of the recording
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Frame rate of the recorded video
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Read the next entry, or `None` at the end of the recording
+    pub fn next_entry(&mut self) -> Result<Option<RecordEntry>> {
+        let mut tag = [0u8; 1];
+        match self.input.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        match tag[0] {
+            TAG_FRAME => self
+                .read_frame()
+                .map(|frame| Some(RecordEntry::Frame(frame))),
+            TAG_DELTA if self.version >= 2 => self
+                .read_delta()
+                .map(|delta| Some(RecordEntry::Delta(delta))),
+            TAG_REPEAT => {
+                let count = u32::from_le_bytes(read_array(&mut self.input)?);
+                let duration = f64::from_le_bytes(read_array(&mut self.input)?);
+                Ok(Some(RecordEntry::Repeat { count, duration }))
+            }
+            tag => Err(anyhow!("Corrupt recording: unknown record type {}", tag)),
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<AsciiFrame> {
+        let timestamp = f64::from_le_bytes(read_array(&mut self.input)?);
+        let frame_number = u64::from_le_bytes(read_array(&mut self.input)?);
+        let width = u16::from_le_bytes(read_array(&mut self.input)?);
+        let height = u16::from_le_bytes(read_array(&mut self.input)?);
+        let flags = read_u8(&mut self.input)?;
+        self.flags = Some(flags);
+
+        let cells = self.read_cells(0, width as usize * height as usize, flags)?;
+
+        Ok(AsciiFrame {
+            characters: cells.characters,
+            fg_colors: cells.fg_colors,
+            bg_colors: cells.bg_colors,
+            attributes: cells.attributes,
+            width,
+            height,
+            timestamp,
+            frame_number,
+        })
+    }
+
+    fn read_delta(&mut self) -> Result<FrameDelta> {
+        let flags = self
+            .flags
+            .ok_or_else(|| anyhow!("Corrupt recording: delta before the first keyframe"))?;
+        let timestamp = f64::from_le_bytes(read_array(&mut self.input)?);
+        let frame_number = u64::from_le_bytes(read_array(&mut self.input)?);
+        let run_count = u32::from_le_bytes(read_array(&mut self.input)?);
+
+        let mut runs = Vec::new();
+        for _ in 0..run_count {
+            let start = u32::from_le_bytes(read_array(&mut self.input)?) as usize;
+            let length = u16::from_le_bytes(read_array(&mut self.input)?) as usize;
+            runs.push(self.read_cells(start, length, flags)?);
+        }
+
+        Ok(FrameDelta {
+            timestamp,
+            frame_number,
+            runs,
+        })
+    }
+
+    /// Read `count` cells laid out as `flags` says, as a run from `start`
+    fn read_cells(&mut self, start: usize, count: usize, flags: u8) -> Result<CellRun> {
+        let has_background = flags & FLAG_BACKGROUND != 0;
+        let has_attributes = flags & FLAG_ATTRIBUTES != 0;
+        let size = cell_size(has_background, has_attributes);
+        let mut data = vec![0u8; count * size];
+        self.input
+            .read_exact(&mut data)
+            .context("Recording ends in the middle of a frame")?;
+
+        let mut characters = Vec::with_capacity(count);
+        let mut fg_colors = Vec::with_capacity(count);
+        let mut bg_colors = has_background.then(|| Vec::with_capacity(count));
+        let mut attributes = has_attributes.then(|| Vec::with_capacity(count));
+        for cell in data.chunks_exact(size) {
+            let code = u32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]]);
+            characters.push(char::from_u32(code).unwrap_or('?'));
+            fg_colors.push((cell[4], cell[5], cell[6]));
+            if let Some(ref mut bg_colors) = bg_colors {
+                bg_colors.push((cell[7], cell[8], cell[9]));
+            }
+            if let Some(ref mut attributes) = attributes {
+                attributes.push(attribute_from_code(cell[size - 1]));
+            }
+        }
+
+        Ok(CellRun {
+            start,
+            characters,
+            fg_colors,
+            bg_colors,
+            attributes,
+        })
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = Result<RecordEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+fn read_u8(input: &mut impl Read) -> Result<u8> {
+    Ok(read_array::<1>(input)?[0])
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    input
+        .read_exact(&mut bytes)
+        .context("Recording is truncated")?;
+    Ok(bytes)
+}
+
+/// Play a recording in the terminal
+pub async fn replay(cli: &Cli, path: &Path) -> Result<()> {
+    let reader = RecordingReader::open(path)?;
+    let mut renderer = Renderer::new(cli.transparent(), cli.use_color())?;
+    renderer.init()?;
+
+    let result = replay_entries(reader, &mut renderer, cli.speed, path).await;
+    renderer.cleanup()?;
+    result
+}
+
+async fn replay_entries<R: Read>(
+    reader: RecordingReader<R>,
+    renderer: &mut Renderer,
+    speed: f64,
+    path: &Path,
+) -> Result<()> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("recording");
+    let started = Instant::now();
+    let mut first_timestamp = None;
+    let mut shown_until = 0.0;
+    let mut current: Option<AsciiFrame> = None;
+
+    for entry in reader {
+        let entry = entry?;
+        let due = match entry {
+            RecordEntry::Frame(ref frame) => frame.timestamp,
+            RecordEntry::Delta(ref delta) => delta.timestamp,
+            RecordEntry::Repeat { duration, .. } => {
+                first_timestamp.unwrap_or(0.0) + shown_until + duration
+            }
+        };
+        let first = *first_timestamp.get_or_insert(due);
+        shown_until = due - first;
+
+        let deadline = started + Duration::from_secs_f64(shown_until.max(0.0) / speed.max(0.01));
+        if !wait_for(deadline).await? {
+            debug!("Replay stopped by user");
+            return Ok(());
+        }
+
+        match entry {
+            RecordEntry::Frame(frame) => {
+                renderer.render_frame(&frame)?;
+                current = Some(frame);
+            }
+            // Only the cells that changed are redrawn
+            RecordEntry::Delta(delta) => {
+                let frame = current
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("Corrupt recording: delta without a keyframe"))?;
+                delta.apply(frame)?;
+                let cells: Vec<usize> = delta.cells().collect();
+                renderer.render_cells(frame, &cells)?;
+            }
+            // Repeats need no redraw; the frame on screen just stays longer
+            RecordEntry::Repeat { .. } => continue,
+        }
+
+        if let Some(ref frame) = current {
+            renderer.render_status(&format!(
+                "{} | Frame: {} | Time: {:.1}s | Speed: {:.2}x",
+                name, frame.frame_number, frame.timestamp, speed
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Wait until `deadline`, returning false if the user quits first
+pub(crate) async fn wait_for(deadline: Instant) -> Result<bool> {
+    loop {
+        if event::poll(Duration::from_millis(1))? {
+            if let Event::Key(key_event) = event::read()? {
+                let ctrl_c = key_event.code == KeyCode::Char('c')
+                    && key_event.modifiers.contains(KeyModifiers::CONTROL);
+                if matches!(key_event.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(true);
+        }
+        tokio::time::sleep(remaining.min(Duration::from_millis(20))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(text: &str, timestamp: f64) -> AsciiFrame {
+        AsciiFrame {
+            characters: text.chars().collect(),
+            fg_colors: vec![(10, 20, 30); text.chars().count()],
+            bg_colors: None,
+            attributes: None,
+            width: text.chars().count() as u16,
+            height: 1,
+            timestamp,
+            frame_number: (timestamp * 10.0) as u64,
+        }
+    }
+
+    #[test]
+    fn test_frame_hash_tracks_content() {
+        let a = frame("ab", 0.0);
+        let mut b = frame("ab", 5.0);
+        assert_eq!(frame_hash(&a), frame_hash(&b));
+
+        b.fg_colors[1] = (0, 0, 0);
+        assert_ne!(frame_hash(&a), frame_hash(&b));
+        assert_ne!(frame_hash(&a), frame_hash(&frame("ba", 0.0)));
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let mut file = RECORDING_MAGIC.to_vec();
+        file.push(1);
+        file.extend(25.0f64.to_le_bytes());
+        file.push(TAG_FRAME);
+        file.extend(0.5f64.to_le_bytes());
+        file.extend(12u64.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.push(0);
+        file.extend(('#' as u32).to_le_bytes());
+        file.extend([1, 2, 3]);
+
+        let mut reader = RecordingReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.version(), 1);
+        match reader.next_entry().unwrap() {
+            Some(RecordEntry::Frame(frame)) => {
+                assert_eq!(frame.characters, vec!['#']);
+                assert_eq!(frame.fg_colors, vec![(1, 2, 3)]);
+                assert_eq!(frame.frame_number, 12);
+            }
+            entry => panic!("unexpected entry {:?}", entry),
+        }
+        assert!(reader.next_entry().unwrap().is_none());
+
+        // Deltas did not exist in version 1
+        file.push(TAG_DELTA);
+        let mut reader = RecordingReader::new(file.as_slice()).unwrap();
+        reader.next_entry().unwrap();
+        assert!(reader.next_entry().is_err());
+
+        let mut newer = file.clone();
+        newer[4] = RECORDING_VERSION + 1;
+        assert!(RecordingReader::new(newer.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_identical_frames_become_repeats() {
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        for (text, timestamp) in [
+            ("ab", 0.0),
+            ("ab", 0.1),
+            ("ab", 0.2),
+            ("cd", 0.3),
+            ("cd", 0.4),
+        ] {
+            writer.write_frame(&frame(text, timestamp)).unwrap();
+        }
+        assert_eq!(
+            writer.finish().unwrap(),
+            RecordingStats {
+                frames: 5,
+                stored: 2,
+                deltas: 0,
+                repeated: 3,
+            }
+        );
+
+        let reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        assert_eq!(reader.fps(), 10.0);
+        let entries: Vec<RecordEntry> = reader.map(Result::unwrap).collect();
+
+        match entries.as_slice() {
+            [RecordEntry::Frame(first), RecordEntry::Repeat { count: 2, duration }, RecordEntry::Frame(second), RecordEntry::Repeat { count: 1, .. }] =>
+            {
+                assert_eq!(first.characters, vec!['a', 'b']);
+                assert!((duration - 0.2).abs() < 1e-9);
+                assert_eq!(second.characters, vec!['c', 'd']);
+                assert_eq!(second.timestamp, 0.3);
+            }
+            other => panic!("unexpected entries: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_changed_runs_merge_small_gaps() {
+        let previous = frame("abcdefghij", 0.0);
+        let next = frame("Xbcdefg_i_", 0.1);
+        assert_eq!(changed_runs(&previous, &next), vec![(0, 1), (7, 3)]);
+    }
+
+    #[test]
+    fn test_small_changes_are_stored_as_deltas() {
+        let frames = [
+            frame("..........", 0.0),
+            frame("....#.....", 0.1),
+            frame("....##....", 0.2),
+            frame("abcdefghij", 0.3),
+        ];
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+        // The last frame changes every cell, so a keyframe is smaller
+        assert_eq!((stats.stored, stats.deltas), (2, 2));
+
+        let mut reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        let mut current: Option<AsciiFrame> = None;
+        let mut replayed = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            match entry {
+                RecordEntry::Frame(frame) => current = Some(frame),
+                RecordEntry::Delta(delta) => {
+                    assert_eq!(delta.cells().count(), 1);
+                    delta.apply(current.as_mut().unwrap()).unwrap();
+                }
+                RecordEntry::Repeat { .. } => unreachable!(),
+            }
+            replayed.push(current.clone().unwrap());
+        }
+        assert_eq!(replayed, frames);
+    }
+
+    #[test]
+    fn test_keyframe_interval() {
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        for index in 0..=KEYFRAME_INTERVAL as usize + 1 {
+            let mut text = vec!['.'; 40];
+            text[index % 40] = '#';
+            let text: String = text.into_iter().collect();
+            writer.write_frame(&frame(&text, index as f64)).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+        assert_eq!(stats.stored, 2);
+        assert_eq!(stats.deltas, KEYFRAME_INTERVAL as u64);
+    }
+
+    #[test]
+    fn test_resume_after_checkpoint() {
+        let frames = [
+            frame("..........", 0.0),
+            frame("..........", 0.1),
+            frame("....#.....", 0.2),
+            frame("....##....", 0.3),
+        ];
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        writer.write_frame(&frames[0]).unwrap();
+        writer.write_frame(&frames[1]).unwrap();
+        let offset = writer.checkpoint().unwrap();
+        let stats = writer.stats();
+        // Output after the checkpoint is lost when the export is interrupted
+        writer.write_frame(&frames[2]).unwrap();
+        drop(writer);
+        assert!((offset as usize) < buffer.len());
+        buffer.truncate(offset as usize);
+
+        let mut writer = RecordingWriter::resume(&mut buffer, offset, stats);
+        writer.write_frame(&frames[2]).unwrap();
+        writer.write_frame(&frames[3]).unwrap();
+        let stats = writer.finish().unwrap();
+        assert_eq!((stats.frames, stats.stored, stats.deltas), (4, 2, 1));
+
+        let mut reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        let mut current: Option<AsciiFrame> = None;
+        let mut replayed = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            match entry {
+                RecordEntry::Frame(frame) => current = Some(frame),
+                RecordEntry::Delta(delta) => delta.apply(current.as_mut().unwrap()).unwrap(),
+                RecordEntry::Repeat { .. } => continue,
+            }
+            replayed.push(current.clone().unwrap());
+        }
+        assert_eq!(
+            replayed,
+            [frames[0].clone(), frames[2].clone(), frames[3].clone()]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_with_background() {
+        let mut original = frame("é█", 1.5);
+        original.bg_colors = Some(vec![(1, 2, 3), (4, 5, 6)]);
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 24.0).unwrap();
+        writer.write_frame(&original).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        match reader.next_entry().unwrap() {
+            Some(RecordEntry::Frame(frame)) => assert_eq!(frame, original),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        assert!(reader.next_entry().unwrap().is_none());
+
+        // A cut-off file is an error, not a silent end
+        let mut truncated = RecordingReader::new(&buffer[..buffer.len() - 3]).unwrap();
+        assert!(truncated.next_entry().is_err());
+    }
+
+    #[test]
+    fn test_round_trip_with_attributes() {
+        let original = frame("abcdefgh", 0.0).with_attributes(vec![CellAttribute::Bold; 8]);
+        let mut changed = frame("abcdefgh", 0.1).with_attributes(vec![CellAttribute::Bold; 8]);
+        changed.attributes.as_mut().unwrap()[3] = CellAttribute::Dim;
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        writer.write_frame(&original).unwrap();
+        writer.write_frame(&changed).unwrap();
+        assert_eq!(writer.finish().unwrap().deltas, 1);
+
+        let mut reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        let Some(RecordEntry::Frame(mut replayed)) = reader.next_entry().unwrap() else {
+            panic!("expected a keyframe");
+        };
+        assert_eq!(replayed, original);
+        let Some(RecordEntry::Delta(delta)) = reader.next_entry().unwrap() else {
+            panic!("expected a delta");
+        };
+        delta.apply(&mut replayed).unwrap();
+        assert_eq!(replayed, changed);
+    }
+
+    #[test]
+    fn test_long_runs_are_split() {
+        let cells = 400 * 400;
+        let original = AsciiFrame::new(400, 400, vec!['a'; cells], vec![(0, 0, 0); cells]);
+        // A run one cell short of the limit, then a gap it would merge across
+        let mut changed = original.clone();
+        for index in (0..65_534).chain(65_535..70_000) {
+            changed.characters[index] = 'b';
+        }
+        changed.frame_number = 1;
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordingWriter::new(&mut buffer, 10.0).unwrap();
+        writer.write_frame(&original).unwrap();
+        writer.write_frame(&changed).unwrap();
+        assert_eq!(writer.finish().unwrap().deltas, 1);
+
+        let mut reader = RecordingReader::new(buffer.as_slice()).unwrap();
+        let Some(RecordEntry::Frame(mut replayed)) = reader.next_entry().unwrap() else {
+            panic!("expected a keyframe");
+        };
+        let Some(RecordEntry::Delta(delta)) = reader.next_entry().unwrap() else {
+            panic!("expected a delta");
+        };
+        delta.apply(&mut replayed).unwrap();
+        assert_eq!(replayed, changed);
+        assert!(reader.next_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(RecordingReader::new(&b"RIFF...."[..]).is_err());
+        assert!(RecordingReader::new(&b"ASC"[..]).is_err());
+        assert!(is_recording(Path::new("movie.ASCV")));
+        assert!(!is_recording(Path::new("movie.mkv")));
+    }
+}
@@ -0,0 +1,257 @@
+//! Preview then refine
+//!
+//! With `--refine`, a paused frame or a still image is shown at once with
+//! the normal converter, then converted again in the background by
+//! [`GlyphConverter`], which picks characters by the shape of the picture
+//! inside each cell instead of by its brightness alone, so edges come out
+//! as `/`, `|`, `_` and the like. The refined frame replaces the preview
+//! when it is ready; playback never waits for it.
+
+use crate::converter::{AsciiConverter, AsciiFrame, ConversionConfig, FrameConverter};
+use crate::decoder::VideoFrame;
+use crate::scheduling::release_thread;
+use anyhow::Result;
+use log::debug;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// Cells are compared with glyphs on a grid of this many rows and columns
+const GRID: usize = 3;
+
+/// Cells whose brightness varies less than this (0-255) keep their ramp
+/// character
+const MIN_CONTRAST: f64 = 32.0;
+
+/// Cells fitting no glyph better than this keep their ramp character
+const MAX_MISMATCH: f64 = 1.5;
+
+/// Where each glyph has ink on a 3x3 grid, top row first
+const GLYPHS: &[(char, [u8; GRID * GRID])] = &[
+    ('-', [0, 0, 0, 1, 1, 1, 0, 0, 0]),
+    ('_', [0, 0, 0, 0, 0, 0, 1, 1, 1]),
+    ('=', [1, 1, 1, 0, 0, 0, 1, 1, 1]),
+    ('|', [0, 1, 0, 0, 1, 0, 0, 1, 0]),
+    ('/', [0, 0, 1, 0, 1, 0, 1, 0, 0]),
+    ('\\', [1, 0, 0, 0, 1, 0, 0, 0, 1]),
+    ('(', [0, 1, 0, 1, 0, 0, 0, 1, 0]),
+    (')', [0, 1, 0, 0, 0, 1, 0, 1, 0]),
+    ('[', [1, 1, 0, 1, 0, 0, 1, 1, 0]),
+    (']', [0, 1, 1, 0, 0, 1, 0, 1, 1]),
+    ('<', [0, 0, 1, 1, 0, 0, 0, 0, 1]),
+    ('>', [1, 0, 0, 0, 0, 1, 1, 0, 0]),
+    ('^', [0, 1, 0, 1, 0, 1, 0, 0, 0]),
+    ('v', [0, 0, 0, 1, 0, 1, 0, 1, 0]),
+    ('+', [0, 1, 0, 1, 1, 1, 0, 1, 0]),
+    ('X', [1, 0, 1, 0, 1, 0, 1, 0, 1]),
+    ('T', [1, 1, 1, 0, 1, 0, 0, 1, 0]),
+    ('L', [1, 0, 0, 1, 0, 0, 1, 1, 1]),
+    ('J', [0, 0, 1, 0, 0, 1, 1, 1, 1]),
+    ('7', [1, 1, 1, 0, 0, 1, 0, 0, 1]),
+    ('r', [0, 0, 0, 1, 1, 1, 1, 0, 0]),
+    (':', [0, 1, 0, 0, 0, 0, 0, 1, 0]),
+    ('.', [0, 0, 0, 0, 0, 0, 0, 1, 0]),
+    ('\'', [0, 1, 0, 0, 0, 0, 0, 0, 0]),
+];
+
+/// Converter choosing characters by the shape of each cell's picture.
+///
+/// Colors, grid size and flat cells come from [`FrameConverter`] with the
+/// same settings; only cells with a clear edge get a shape glyph. Much
+/// slower than the ramp alone, so it is meant for single frames.
+pub struct GlyphConverter {
+    base: FrameConverter,
+    /// Cover mode crops the source, so cells no longer map onto the frame
+    cover: bool,
+}
+
+impl GlyphConverter {
+    pub fn new(config: ConversionConfig) -> Self {
+        Self {
+            cover: config.cover,
+            base: FrameConverter::new(config),
+        }
+    }
+}
+
+impl AsciiConverter for GlyphConverter {
+    fn convert(
+        &self,
+        frame: &VideoFrame,
+        terminal_width: u16,
+        terminal_height: u16,
+    ) -> Result<AsciiFrame> {
+        let mut ascii = self
+            .base
+            .convert_frame(frame, terminal_width, terminal_height)?;
+        if self.cover {
+            return Ok(ascii);
+        }
+
+        let (width, height) = (ascii.width as usize, ascii.height as usize);
+        for row in 0..height {
+            for column in 0..width {
+                let shape = cell_shape(frame, (column, row), (width, height));
+                if let Some(glyph) = shape.and_then(|shape| match_glyph(&shape)) {
+                    ascii.characters[row * width + column] = glyph;
+                }
+            }
+        }
+        Ok(ascii)
+    }
+}
+
+/// Mean luma (0-255) of each part of a cell's grid, None if the cell covers
+/// too few source pixels to have a shape
+fn cell_shape(
+    frame: &VideoFrame,
+    (column, row): (usize, usize),
+    (columns, rows): (usize, usize),
+) -> Option<[f64; GRID * GRID]> {
+    let (frame_width, frame_height) = (frame.width as usize, frame.height as usize);
+    let left = column * frame_width / columns;
+    let top = row * frame_height / rows;
+    let cell_width = (column + 1) * frame_width / columns - left;
+    let cell_height = (row + 1) * frame_height / rows - top;
+    if cell_width < GRID || cell_height < GRID {
+        return None;
+    }
+
+    let mut shape = [0.0; GRID * GRID];
+    for (part, mean) in shape.iter_mut().enumerate() {
+        let (part_x, part_y) = (part % GRID, part / GRID);
+        let xs = left + part_x * cell_width / GRID..left + (part_x + 1) * cell_width / GRID;
+        let ys = top + part_y * cell_height / GRID..top + (part_y + 1) * cell_height / GRID;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for y in ys {
+            for x in xs.clone() {
+                let index = (y * frame_width + x) * 3;
+                if let Some(pixel) = frame.data.get(index..index + 3) {
+                    sum += 0.2126 * pixel[0] as f64
+                        + 0.7152 * pixel[1] as f64
+                        + 0.0722 * pixel[2] as f64;
+                    count += 1;
+                }
+            }
+        }
+        *mean = sum / count.max(1) as f64;
+    }
+    Some(shape)
+}
+
+/// Glyph whose ink best follows the bright parts of `shape`, if the shape
+/// has enough contrast and some glyph fits it well
+fn match_glyph(shape: &[f64; GRID * GRID]) -> Option<char> {
+    let darkest = shape.iter().copied().fold(f64::INFINITY, f64::min);
+    let brightest = shape.iter().copied().fold(0.0, f64::max);
+    if brightest - darkest < MIN_CONTRAST {
+        return None;
+    }
+    let normalized = shape.map(|luma| (luma - darkest) / (brightest - darkest));
+
+    let mismatch = |mask: &[u8; GRID * GRID]| -> f64 {
+        normalized
+            .iter()
+            .zip(mask)
+            .map(|(&value, &ink)| (value - ink as f64).powi(2))
+            .sum()
+    };
+    GLYPHS
+        .iter()
+        .map(|(glyph, mask)| (*glyph, mismatch(mask)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, mismatch)| mismatch <= MAX_MISMATCH)
+        .map(|(glyph, _)| glyph)
+}
+
+/// A frame being refined on another thread
+pub struct RefineJob {
+    receiver: Receiver<AsciiFrame>,
+}
+
+impl RefineJob {
+    /// Start converting `frame` for a terminal of `width` x `height`
+    pub fn spawn(
+        converter: Arc<GlyphConverter>,
+        frame: Arc<VideoFrame>,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            release_thread();
+            match converter.convert(&frame, width, height) {
+                Ok(refined) => {
+                    let _ = sender.send(refined);
+                }
+                Err(e) => debug!("Refining frame {} failed: {}", frame.frame_number, e),
+            }
+        });
+        Self { receiver }
+    }
+
+    /// The refined frame, once it is ready
+    pub fn poll(&self) -> Option<AsciiFrame> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 30x30 frame, black with white pixels where `bright` says
+    fn frame(bright: impl Fn(usize, usize) -> bool) -> VideoFrame {
+        let mut data = Vec::new();
+        for y in 0..30 {
+            for x in 0..30 {
+                let value = if bright(x, y) { 255 } else { 0 };
+                data.extend([value; 3]);
+            }
+        }
+        VideoFrame {
+            data,
+            width: 30,
+            height: 30,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_match_glyph() {
+        let line = |mask: [u8; 9]| mask.map(|ink| ink as f64 * 200.0 + 20.0);
+        assert_eq!(match_glyph(&line([0, 0, 1, 0, 1, 0, 1, 0, 0])), Some('/'));
+        assert_eq!(match_glyph(&line([0, 0, 0, 0, 0, 0, 1, 1, 1])), Some('_'));
+        // Flat cells and noise keep their ramp character
+        assert_eq!(match_glyph(&[128.0; 9]), None);
+        assert_eq!(
+            match_glyph(&[255.0, 0.0, 255.0, 0.0, 255.0, 255.0, 255.0, 0.0, 0.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_glyph_converter() {
+        let config = ConversionConfig::default().with_aspect_ratio(1.0);
+        let converter = GlyphConverter::new(config);
+
+        // A vertical line down the middle of a one-cell picture
+        let vertical = frame(|x, _| (10..20).contains(&x));
+        let ascii = converter.convert(&vertical, 1, 1).unwrap();
+        assert_eq!(ascii.characters, vec!['|']);
+
+        // A diagonal from bottom left to top right
+        let diagonal = frame(|x, y| (x + y).abs_diff(29) < 5);
+        let ascii = converter.convert(&diagonal, 1, 1).unwrap();
+        assert_eq!(ascii.characters, vec!['/']);
+
+        // Cells too small for a shape are left alone
+        let ascii = converter.convert(&vertical, 30, 30).unwrap();
+        let base = FrameConverter::new(ConversionConfig::default().with_aspect_ratio(1.0))
+            .convert_frame(&vertical, 30, 30)
+            .unwrap();
+        assert_eq!(ascii, base);
+    }
+}
@@ -1,13 +1,23 @@
-use crate::converter::AsciiFrame;
+use crate::converter::{AsciiFrame, CellAttribute};
+use crate::layout;
+use crate::telnet::{ansi256, encode_text, ColorDepth};
+use crate::termstate::{self, TerminalState};
 use anyhow::Result;
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
+    event::{DisableFocusChange, EnableFocusChange},
     execute, queue,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    style::{
+        Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::{
+        disable_raw_mode, enable_raw_mode, BeginSynchronizedUpdate, Clear, ClearType,
+        EndSynchronizedUpdate,
+    },
 };
 use log::debug;
 use std::io::{stdout, Stdout, Write};
+use tracing::debug_span;
 
 /// Terminal renderer for ASCII frames
 pub struct Renderer {
@@ -17,8 +27,57 @@ pub struct Renderer {
     center_output: bool,
     terminal_width: u16,
     terminal_height: u16,
+    /// Skip animations such as the loading spinner
+    reduced_motion: bool,
+    /// Width and height that override the detected terminal size
+    size_override: (Option<u16>, Option<u16>),
+    /// Ask the terminal to report focus changes
+    focus_events: bool,
+    /// Size of the frame last drawn by `render_damaged`, None once anything
+    /// else was drawn over it
+    drawn: Option<(u16, u16)>,
+    /// Colors the terminal can show; frame colors are reduced to them
+    color_depth: ColorDepth,
+    /// Wrap each frame in a synchronized update so it appears at once
+    synchronized_output: bool,
+    /// A synchronized update was begun and not yet ended
+    updating: bool,
+    /// Output queued since the last flush, written with a single
+    /// `write_all`; cleared but kept allocated for the next frame
+    frame_buffer: Vec<u8>,
+    /// What the queued output leaves set, so cells repeating it skip the
+    /// escape sequences
+    pen: Pen,
+    /// `init` was called, so dropping the renderer restores the terminal;
+    /// one only assembling frames leaves it alone
+    initialized: bool,
+    /// Redraw only the cells that look different from `on_screen`
+    diff_only: bool,
+    /// The frame as last drawn by `render_damaged` in diff-only mode, with
+    /// the cells left alone as they were
+    on_screen: Option<AsciiFrame>,
+    /// Bytes of frame output written to the terminal so far
+    bytes_written: u64,
 }
 
+/// Cursor position, colors and intensity at the end of the queued output;
+/// None where unknown
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Pen {
+    cursor: Option<(u16, u16)>,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    attribute: Option<CellAttribute>,
+}
+
+/// Bytes per cell reserved in the frame buffer up front: a glyph with both
+/// colors changing in 24-bit color
+const CELL_BYTES: usize = 40;
+
+/// In diff-only mode, colors within this much on every channel of the one
+/// on screen are left as they are
+const DIFF_TOLERANCE: u8 = 12;
+
 /// Rendering statistics
 #[derive(Debug, Default)]
 pub struct RenderStats {
@@ -51,11 +110,83 @@ impl Renderer {
             center_output: true,
             terminal_width,
             terminal_height,
+            reduced_motion: false,
+            size_override: (None, None),
+            focus_events: false,
+            drawn: None,
+            color_depth: ColorDepth::TrueColor,
+            synchronized_output: false,
+            updating: false,
+            frame_buffer: Vec::new(),
+            pen: Pen::default(),
+            initialized: false,
+            diff_only: false,
+            on_screen: None,
+            bytes_written: 0,
         })
     }
 
+    /// Draw for a fixed width and/or height instead of the detected size
+    pub fn with_size(mut self, width: Option<u16>, height: Option<u16>) -> Self {
+        self.size_override = (width, height);
+        self.terminal_width = width.unwrap_or(self.terminal_width);
+        self.terminal_height = height.unwrap_or(self.terminal_height);
+        self
+    }
+
+    /// Report focus changes as `FocusGained` and `FocusLost` events
+    pub fn with_focus_events(mut self, focus_events: bool) -> Self {
+        self.focus_events = focus_events;
+        self
+    }
+
+    /// Send frame colors at `color_depth` instead of as 24-bit RGB
+    pub fn with_color_depth(mut self, color_depth: ColorDepth) -> Self {
+        self.color_depth = color_depth;
+        self
+    }
+
+    /// Change the color depth frames are sent at, e.g. to save bandwidth
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth;
+    }
+
+    /// Send frame colors or leave them out, e.g. as the palette changes;
+    /// the next frame is drawn in full
+    pub fn set_use_colors(&mut self, use_colors: bool) {
+        self.use_colors = use_colors;
+        self.drawn = None;
+        self.on_screen = None;
+        self.pen = Pen::default();
+    }
+
+    /// Have `render_damaged` redraw only cells that look different from the
+    /// screen, leaving small color changes out, whatever damage it is given
+    pub fn set_diff_only(&mut self, diff_only: bool) {
+        self.diff_only = diff_only;
+        self.on_screen = None;
+    }
+
+    /// Bytes of frame output written to the terminal so far
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Draw frames as synchronized updates, for terminals supporting them
+    pub fn with_synchronized_output(mut self, synchronized_output: bool) -> Self {
+        self.synchronized_output = synchronized_output;
+        self
+    }
+
+    /// Turn off animations for reduced-motion mode
+    pub fn with_reduced_motion(mut self, reduced_motion: bool) -> Self {
+        self.reduced_motion = reduced_motion;
+        self
+    }
+
     /// Initialize the terminal for rendering
     pub fn init(&mut self) -> Result<()> {
+        self.initialized = true;
         // Check if we're in a proper terminal environment
         if !atty::is(atty::Stream::Stdout) {
             debug!("Not running in a terminal, skipping raw mode");
@@ -68,6 +199,12 @@ impl Renderer {
                 match execute!(self.stdout, Hide, Clear(ClearType::All)) {
                     Ok(()) => {
                         debug!("Terminal initialized for rendering");
+                        if self.focus_events {
+                            if let Err(e) = execute!(self.stdout, EnableFocusChange) {
+                                debug!("Failed to enable focus events: {}", e);
+                            }
+                        }
+                        self.record_state();
                         Ok(())
                     }
                     Err(e) => {
@@ -85,10 +222,29 @@ impl Renderer {
         }
     }
 
+    /// Note the modes changed by `init`, so a later run can restore them
+    /// if this one dies before `cleanup`
+    fn record_state(&self) {
+        let state = TerminalState {
+            raw_mode: true,
+            cursor_hidden: true,
+            focus_events: self.focus_events,
+            synchronized_output: self.synchronized_output,
+            ..TerminalState::current()
+        };
+        if let Err(e) = state.record() {
+            debug!("Failed to record terminal state: {}", e);
+        }
+    }
+
     /// Restore terminal to normal state
     pub fn cleanup(&mut self) -> Result<()> {
+        if self.focus_events {
+            execute!(self.stdout, DisableFocusChange)?;
+        }
         execute!(self.stdout, Show, ResetColor, Clear(ClearType::All))?;
         disable_raw_mode()?;
+        termstate::clear();
         debug!("Terminal restored to normal state");
         Ok(())
     }
@@ -96,8 +252,11 @@ impl Renderer {
     /// Update terminal dimensions
     pub fn update_dimensions(&mut self) -> Result<(u16, u16)> {
         let (width, height) = crossterm::terminal::size()?;
+        let width = self.size_override.0.unwrap_or(width);
+        let height = self.size_override.1.unwrap_or(height);
         self.terminal_width = width;
         self.terminal_height = height;
+        self.drawn = None;
         debug!("Terminal dimensions updated: {}x{}", width, height);
         Ok((width, height))
     }
@@ -109,82 +268,67 @@ impl Renderer {
 
     /// Render an ASCII frame to the terminal
     pub fn render_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
-        let start_time = std::time::Instant::now();
+        self.begin_update()?;
+        self.queue_frame(frame)?;
+        self.flush()
+    }
+
+    /// Assemble a frame's output as `render_frame` would draw it, without
+    /// writing it. The buffer is reused by the next frame, keeping its
+    /// allocation.
+    pub fn encode_frame(&mut self, frame: &AsciiFrame) -> Result<&[u8]> {
+        self.frame_buffer.clear();
+        self.pen = Pen::default();
+        self.queue_frame(frame)?;
+        Ok(&self.frame_buffer)
+    }
 
-        // Calculate centering offsets
-        let (offset_x, offset_y) = if self.center_output {
-            let offset_x = (self.terminal_width.saturating_sub(frame.width)) / 2;
-            let offset_y = (self.terminal_height.saturating_sub(frame.height)) / 2;
-            (offset_x, offset_y)
+    /// The frame as ANSI-escaped text, one line per row, with the
+    /// renderer's color settings. Nothing is written to the terminal and
+    /// raw mode is left alone, so frames can be embedded in another
+    /// interface or compared in tests. Transparent mode leaves out the
+    /// background colors.
+    pub fn render_to_string(&self, frame: &AsciiFrame) -> String {
+        let depth = if self.use_colors {
+            self.color_depth
         } else {
-            (0, 0)
+            ColorDepth::Mono
         };
+        if self.transparent_mode && frame.bg_colors.is_some() {
+            let frame = AsciiFrame {
+                bg_colors: None,
+                ..frame.clone()
+            };
+            return encode_text(&frame, depth);
+        }
+        encode_text(frame, depth)
+    }
 
-        // Clear the screen
-        queue!(self.stdout, Clear(ClearType::All))?;
-
-        // Render frame content
-        for y in 0..frame.height {
-            for x in 0..frame.width {
-                let index = (y * frame.width + x) as usize;
-
-                if index < frame.characters.len() {
-                    let character = frame.characters[index];
-                    let (fg_r, fg_g, fg_b) = frame.fg_colors[index];
+    /// Bytes the frame buffer holds without growing
+    pub fn buffer_capacity(&self) -> usize {
+        self.frame_buffer.capacity()
+    }
 
-                    // Position cursor
-                    queue!(self.stdout, MoveTo(offset_x + x, offset_y + y))?;
+    /// Queue a whole frame over a cleared screen
+    fn queue_frame(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let _span = debug_span!("render").entered();
+        let (offset_x, offset_y) = self.offsets(frame);
+        self.frame_buffer
+            .reserve(frame.characters.len() * CELL_BYTES);
 
-                    // Skip rendering spaces in transparent mode
-                    if self.transparent_mode && character == ' ' {
-                        continue;
-                    }
-
-                    // Set colors if enabled
-                    if self.use_colors {
-                        queue!(
-                            self.stdout,
-                            SetForegroundColor(Color::Rgb {
-                                r: fg_r,
-                                g: fg_g,
-                                b: fg_b
-                            })
-                        )?;
-
-                        // Set background color if not in transparent mode
-                        if !self.transparent_mode {
-                            if let Some(ref bg_colors) = frame.bg_colors {
-                                if index < bg_colors.len() {
-                                    let (bg_r, bg_g, bg_b) = bg_colors[index];
-                                    queue!(
-                                        self.stdout,
-                                        SetBackgroundColor(Color::Rgb {
-                                            r: bg_r,
-                                            g: bg_g,
-                                            b: bg_b
-                                        })
-                                    )?;
-                                }
-                            }
-                        }
-                    }
+        // Clear the screen
+        queue!(self.frame_buffer, Clear(ClearType::All))?;
 
-                    // Print the character
-                    queue!(self.stdout, Print(character))?;
-                }
-            }
+        // Render frame content
+        for index in 0..frame.characters.len() {
+            self.queue_cell(frame, index, (offset_x, offset_y))?;
         }
 
-        // Reset colors and flush output
-        if self.use_colors {
-            queue!(self.stdout, ResetColor)?;
-        }
-        self.stdout.flush()?;
+        self.queue_reset(frame)?;
+        self.drawn = None;
 
-        let render_time = start_time.elapsed().as_millis() as u64;
         debug!(
-            "Frame rendered in {}ms ({}x{} -> {}x{} at offset {},{}) ",
-            render_time,
+            "Frame rendered ({}x{} -> {}x{} at offset {},{})",
             frame.width,
             frame.height,
             self.terminal_width,
@@ -196,42 +340,310 @@ impl Renderer {
         Ok(())
     }
 
+    /// Redraw only the given cells of a frame already on screen
+    pub fn render_cells(&mut self, frame: &AsciiFrame, cells: &[usize]) -> Result<()> {
+        let _span = debug_span!("render").entered();
+        self.begin_update()?;
+        self.queue_cells(frame, cells)?;
+        self.flush()
+    }
+
+    /// Queue the given cells over the frame on screen. Transparent mode
+    /// normally leaves blank cells undrawn, but a cell that turned blank
+    /// has to be written to erase the glyph that was there.
+    fn queue_cells(&mut self, frame: &AsciiFrame, cells: &[usize]) -> Result<()> {
+        let (offset_x, offset_y) = self.offsets(frame);
+        for &index in cells {
+            if self.transparent_mode && frame.width > 0 && frame.characters.get(index) == Some(&' ')
+            {
+                let x = offset_x + (index % frame.width as usize) as u16;
+                let y = offset_y + (index / frame.width as usize) as u16;
+                if self.pen.cursor != Some((x, y)) {
+                    queue!(self.frame_buffer, MoveTo(x, y))?;
+                }
+                queue!(self.frame_buffer, Print(' '))?;
+                self.pen.cursor = Some((x + 1, y));
+            } else {
+                self.queue_cell(frame, index, (offset_x, offset_y))?;
+            }
+        }
+        self.drawn = None;
+        self.queue_reset(frame)
+    }
+
+    /// Render a frame over the one on screen, redrawing only `cells` when
+    /// given and possible, then the status line.
+    ///
+    /// `cells` are relative to the frame of the previous call. Transparent
+    /// mode leaves blank cells undrawn, so it always redraws the whole
+    /// frame, as does anything drawn in between or a new frame size. In
+    /// diff-only mode the cells are found by comparing with the screen.
+    pub fn render_damaged(
+        &mut self,
+        frame: &AsciiFrame,
+        cells: Option<&[usize]>,
+        status: &str,
+    ) -> Result<()> {
+        let redrawable = !self.transparent_mode && self.drawn == Some((frame.width, frame.height));
+        let changed = match self.on_screen {
+            Some(ref screen) if self.diff_only && redrawable && same_layout(screen, frame) => {
+                Some(self.changed_cells(screen, frame))
+            }
+            _ => None,
+        };
+        let cells = if self.diff_only {
+            changed.as_deref()
+        } else {
+            cells.filter(|_| redrawable)
+        };
+        let Some(cells) = cells else {
+            self.render_frame_with_status(frame, status)?;
+            self.drawn = Some((frame.width, frame.height));
+            if self.diff_only {
+                self.on_screen = Some(frame.clone());
+            }
+            return Ok(());
+        };
+        if let Some(ref mut screen) = self.on_screen {
+            for &index in cells {
+                copy_cell(frame, screen, index);
+            }
+        }
+
+        let _span = debug_span!("render").entered();
+        self.begin_update()?;
+        // The status line is drawn over the frame; clear its row and put
+        // back the frame cells there before the new status
+        let status_y = self.terminal_height.saturating_sub(1);
+        let (offset_x, offset_y) = self.offsets(frame);
+        let status_row = status_y
+            .checked_sub(offset_y)
+            .filter(|row| *row < frame.height);
+        queue!(
+            self.frame_buffer,
+            MoveTo(0, status_y),
+            Clear(ClearType::CurrentLine)
+        )?;
+        let row_cells = status_row.map(|row| {
+            let start = row as usize * frame.width as usize;
+            start..start + frame.width as usize
+        });
+        for index in cells.iter().copied().chain(row_cells.into_iter().flatten()) {
+            self.queue_cell(frame, index, (offset_x, offset_y))?;
+        }
+        self.queue_reset(frame)?;
+        self.queue_status(status)?;
+        self.flush()
+    }
+
     /// Render frame with additional status information
     pub fn render_frame_with_status(&mut self, frame: &AsciiFrame, status: &str) -> Result<()> {
-        self.render_frame(frame)?;
+        self.begin_update()?;
+        self.queue_frame(frame)?;
+        self.queue_status(status)?;
+        self.flush()
+    }
+
+    /// Draw the status line at the bottom of the terminal
+    pub fn render_status(&mut self, status: &str) -> Result<()> {
+        self.queue_status(status)?;
+        self.flush()
+    }
 
-        // Render status line at the bottom
+    /// Queue the status line at the bottom of the terminal
+    fn queue_status(&mut self, status: &str) -> Result<()> {
         if !status.is_empty() {
             let status_y = self.terminal_height.saturating_sub(1);
-            queue!(self.stdout, MoveTo(0, status_y))?;
+            queue!(self.frame_buffer, MoveTo(0, status_y))?;
 
             if self.use_colors {
-                queue!(self.stdout, SetForegroundColor(Color::White))?;
-                queue!(self.stdout, SetBackgroundColor(Color::DarkGrey))?;
+                queue!(self.frame_buffer, SetForegroundColor(Color::White))?;
+                queue!(self.frame_buffer, SetBackgroundColor(Color::DarkGrey))?;
             }
 
-            // Truncate status to fit terminal width
-            let truncated_status = if status.len() > self.terminal_width as usize {
-                &status[..self.terminal_width as usize]
-            } else {
-                status
+            // Truncate status to fit terminal width, by characters so a
+            // cut never lands inside one
+            let truncated_status = match status.char_indices().nth(self.terminal_width as usize) {
+                Some((end, _)) => &status[..end],
+                None => status,
             };
 
-            queue!(self.stdout, Print(truncated_status))?;
+            queue!(self.frame_buffer, Print(truncated_status))?;
 
             if self.use_colors {
-                queue!(self.stdout, ResetColor)?;
+                queue!(self.frame_buffer, ResetColor)?;
+            }
+            self.pen = Pen::default();
+        }
+
+        Ok(())
+    }
+
+    /// Start a synchronized update, if enabled, for the output queued
+    /// until the next flush
+    fn begin_update(&mut self) -> Result<()> {
+        if self.synchronized_output && !self.updating {
+            queue!(self.frame_buffer, BeginSynchronizedUpdate)?;
+            self.updating = true;
+        }
+        Ok(())
+    }
+
+    /// Write queued output to the terminal in one go, ending any
+    /// synchronized update
+    fn flush(&mut self) -> Result<()> {
+        let _span = debug_span!("flush").entered();
+        if self.updating {
+            queue!(self.frame_buffer, EndSynchronizedUpdate)?;
+            self.updating = false;
+        }
+        self.bytes_written += self.frame_buffer.len() as u64;
+        let written = self
+            .stdout
+            .write_all(&self.frame_buffer)
+            .and_then(|()| self.stdout.flush());
+        self.frame_buffer.clear();
+        self.pen = Pen::default();
+        written?;
+        Ok(())
+    }
+
+    /// A frame color at the terminal's color depth; None for mono terminals
+    fn color(&self, (r, g, b): (u8, u8, u8)) -> Option<Color> {
+        match self.color_depth {
+            ColorDepth::TrueColor => Some(Color::Rgb { r, g, b }),
+            ColorDepth::Ansi256 => Some(Color::AnsiValue(ansi256((r, g, b)))),
+            ColorDepth::Mono => None,
+        }
+    }
+
+    /// Offset that centers a frame in the terminal
+    fn offsets(&self, frame: &AsciiFrame) -> (u16, u16) {
+        if self.center_output {
+            layout::centered(
+                (frame.width, frame.height),
+                (self.terminal_width, self.terminal_height),
+            )
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Queue one cell of a frame at its screen position
+    fn queue_cell(
+        &mut self,
+        frame: &AsciiFrame,
+        index: usize,
+        (offset_x, offset_y): (u16, u16),
+    ) -> Result<()> {
+        if frame.width == 0 || index >= frame.characters.len() {
+            return Ok(());
+        }
+        let x = (index % frame.width as usize) as u16;
+        let y = (index / frame.width as usize) as u16;
+        let character = frame.characters[index];
+        let fg_color = frame.fg_colors[index];
+
+        // Skip rendering spaces in transparent mode
+        if self.transparent_mode && character == ' ' {
+            return Ok(());
+        }
+
+        // Position cursor, unless the previous cell left it here
+        let (x, y) = (offset_x + x, offset_y + y);
+        if self.pen.cursor != Some((x, y)) {
+            queue!(self.frame_buffer, MoveTo(x, y))?;
+        }
+
+        // Set colors if enabled and not already set
+        if self.use_colors {
+            if let Some(color) = self.color(fg_color).filter(|&c| self.pen.fg != Some(c)) {
+                queue!(self.frame_buffer, SetForegroundColor(color))?;
+                self.pen.fg = Some(color);
             }
 
-            self.stdout.flush()?;
+            // Set background color if not in transparent mode
+            if !self.transparent_mode {
+                let bg_color = frame
+                    .bg_colors
+                    .as_ref()
+                    .and_then(|bg_colors| bg_colors.get(index))
+                    .and_then(|&bg_color| self.color(bg_color))
+                    .filter(|&c| self.pen.bg != Some(c));
+                if let Some(color) = bg_color {
+                    queue!(self.frame_buffer, SetBackgroundColor(color))?;
+                    self.pen.bg = Some(color);
+                }
+            }
         }
 
+        // Bold and dim apply with or without colors
+        if let Some(&attribute) = frame
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.get(index))
+            .filter(|&&attribute| self.pen.attribute != Some(attribute))
+        {
+            queue!(self.frame_buffer, SetAttribute(Attribute::NormalIntensity))?;
+            match attribute {
+                CellAttribute::Normal => {}
+                CellAttribute::Bold => queue!(self.frame_buffer, SetAttribute(Attribute::Bold))?,
+                CellAttribute::Dim => queue!(self.frame_buffer, SetAttribute(Attribute::Dim))?,
+            }
+            self.pen.attribute = Some(attribute);
+        }
+
+        // Print the character; only ASCII is known to be one column wide
+        queue!(self.frame_buffer, Print(character))?;
+        self.pen.cursor = character.is_ascii().then_some((x + 1, y));
+        Ok(())
+    }
+
+    /// Cells of `frame` that look different from `screen`: another glyph or
+    /// attribute, or a color off by more than `DIFF_TOLERANCE`
+    fn changed_cells(&self, screen: &AsciiFrame, frame: &AsciiFrame) -> Vec<usize> {
+        let close = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            let near = |x: u8, y: u8| x.abs_diff(y) <= DIFF_TOLERANCE;
+            !self.use_colors
+                || self.color(a) == self.color(b)
+                || (near(a.0, b.0) && near(a.1, b.1) && near(a.2, b.2))
+        };
+        let attribute =
+            |frame: &AsciiFrame, index: usize| frame.attributes.as_ref().map(|a| a[index]);
+        let bg = |frame: &AsciiFrame, index: usize| frame.bg_colors.as_ref().map(|bg| bg[index]);
+        (0..frame.characters.len())
+            .filter(|&index| {
+                let same_bg = match (bg(screen, index), bg(frame, index)) {
+                    (Some(a), Some(b)) => close(a, b),
+                    (a, b) => a == b,
+                };
+                screen.characters[index] != frame.characters[index]
+                    || attribute(screen, index) != attribute(frame, index)
+                    || !close(screen.fg_colors[index], frame.fg_colors[index])
+                    || !same_bg
+            })
+            .collect()
+    }
+
+    /// Reset the colors and attributes the cells of `frame` set
+    fn queue_reset(&mut self, frame: &AsciiFrame) -> Result<()> {
+        if self.use_colors {
+            queue!(self.frame_buffer, ResetColor)?;
+        }
+        if frame.attributes.is_some() {
+            queue!(self.frame_buffer, SetAttribute(Attribute::NormalIntensity))?;
+        }
+        self.pen = Pen {
+            cursor: self.pen.cursor,
+            ..Pen::default()
+        };
         Ok(())
     }
 
     /// Clear the screen
     pub fn clear(&mut self) -> Result<()> {
         execute!(self.stdout, Clear(ClearType::All))?;
+        self.drawn = None;
         debug!("Screen cleared");
         Ok(())
     }
@@ -242,6 +654,7 @@ impl Renderer {
         let start_y = (self.terminal_height / 2).saturating_sub(lines.len() as u16 / 2);
 
         execute!(self.stdout, Clear(ClearType::All))?;
+        self.drawn = None;
 
         for (i, line) in lines.iter().enumerate() {
             let y = start_y + i as u16;
@@ -266,6 +679,10 @@ impl Renderer {
 
     /// Display loading screen
     pub fn display_loading(&mut self, message: &str) -> Result<()> {
+        if self.reduced_motion {
+            return self.display_message(message);
+        }
+
         let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
         let spinner_index = (std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -283,6 +700,7 @@ impl Renderer {
     /// Display error message
     pub fn display_error(&mut self, error: &str) -> Result<()> {
         execute!(self.stdout, Clear(ClearType::All))?;
+        self.drawn = None;
 
         let y = self.terminal_height / 2;
         let x = (self.terminal_width / 2).saturating_sub(error.len() as u16 / 2);
@@ -322,7 +740,28 @@ impl Renderer {
 impl Drop for Renderer {
     fn drop(&mut self) {
         // Ensure terminal is restored on drop
-        let _ = self.cleanup();
+        if self.initialized {
+            let _ = self.cleanup();
+        }
+    }
+}
+
+/// Whether two frames have the same cells, colors and attributes to compare
+fn same_layout(a: &AsciiFrame, b: &AsciiFrame) -> bool {
+    a.characters.len() == b.characters.len()
+        && a.bg_colors.is_some() == b.bg_colors.is_some()
+        && a.attributes.is_some() == b.attributes.is_some()
+}
+
+/// Copy cell `index` of `from` into `to`, a frame of the same layout
+fn copy_cell(from: &AsciiFrame, to: &mut AsciiFrame, index: usize) {
+    to.characters[index] = from.characters[index];
+    to.fg_colors[index] = from.fg_colors[index];
+    if let (Some(from), Some(to)) = (&from.bg_colors, &mut to.bg_colors) {
+        to[index] = from[index];
+    }
+    if let (Some(from), Some(to)) = (&from.attributes, &mut to.attributes) {
+        to[index] = from[index];
     }
 }
 
@@ -352,6 +791,7 @@ mod tests {
             characters: vec!['#', ' ', '@', ' '],
             fg_colors: vec![(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)],
             bg_colors: Some(vec![(0, 0, 0), (0, 0, 0), (0, 0, 0), (0, 0, 0)]),
+            attributes: None,
             width: 2,
             height: 2,
             timestamp: 1.0,
@@ -365,6 +805,22 @@ mod tests {
         assert!(result.is_ok(), "Should be able to create renderer");
     }
 
+    #[test]
+    fn test_status_is_cut_by_characters() {
+        let mut renderer = Renderer::new(false, false)
+            .unwrap()
+            .with_size(Some(4), Some(2));
+        // Byte 4 is inside the block
+        renderer.queue_status("é█üxyz").unwrap();
+        let output = String::from_utf8(renderer.frame_buffer.clone()).unwrap();
+        assert!(output.ends_with("é█üx"));
+
+        renderer.frame_buffer.clear();
+        renderer.queue_status("aé█").unwrap();
+        let output = String::from_utf8(renderer.frame_buffer.clone()).unwrap();
+        assert!(output.ends_with("aé█"));
+    }
+
     #[test]
     fn test_frame_delay_calculation() {
         let delay = calculate_frame_delay(30.0, 1.0);
@@ -391,4 +847,91 @@ mod tests {
         let renderer = Renderer::new(false, false).unwrap();
         assert!(!renderer.uses_colors());
     }
+
+    #[test]
+    fn test_color_depth() {
+        let renderer = Renderer::new(false, true).unwrap();
+        assert_eq!(
+            renderer.color((255, 0, 0)),
+            Some(Color::Rgb { r: 255, g: 0, b: 0 })
+        );
+
+        let renderer = renderer.with_color_depth(ColorDepth::Ansi256);
+        assert_eq!(renderer.color((255, 0, 0)), Some(Color::AnsiValue(196)));
+
+        let renderer = renderer.with_color_depth(ColorDepth::Mono);
+        assert_eq!(renderer.color((255, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        let mut frame = create_test_frame();
+        frame.bg_colors = None;
+        frame.fg_colors = vec![(255, 0, 0); 4];
+        let mut renderer = Renderer::new(false, true)
+            .unwrap()
+            .with_size(Some(2), Some(2));
+        let output = String::from_utf8(renderer.encode_frame(&frame).unwrap().to_vec()).unwrap();
+        // One cursor move per row and the color set once
+        assert_eq!(output.matches('H').count(), 2);
+        assert_eq!(output.matches("38;2;255;0;0").count(), 1);
+        assert!(output.contains("# ") && output.contains("@ "));
+
+        // The buffer is reused and the next frame starts afresh
+        let capacity = renderer.buffer_capacity();
+        assert!(capacity >= output.len());
+        assert_eq!(renderer.encode_frame(&frame).unwrap(), output.as_bytes());
+        assert_eq!(renderer.buffer_capacity(), capacity);
+    }
+
+    #[test]
+    fn test_changed_cells() {
+        let screen = create_test_frame();
+        let mut frame = screen.clone();
+        frame.characters[1] = '+';
+        // A slight shift in color is left alone, a larger one is not
+        frame.fg_colors[2] = (0, 0, 245);
+        frame.fg_colors[3] = (200, 255, 255);
+        let renderer = Renderer::new(false, true).unwrap();
+        assert_eq!(renderer.changed_cells(&screen, &frame), [1, 3]);
+
+        // Without colors only the glyph counts
+        let renderer = Renderer::new(false, false).unwrap();
+        assert_eq!(renderer.changed_cells(&screen, &frame), [1]);
+
+        let mut copy = screen.clone();
+        copy_cell(&frame, &mut copy, 3);
+        assert_eq!(copy.fg_colors[3], (200, 255, 255));
+    }
+
+    #[test]
+    fn test_transparent_cells_are_erased() {
+        let mut frame = create_test_frame();
+        frame.characters[0] = ' ';
+        let mut renderer = Renderer::new(true, false)
+            .unwrap()
+            .with_size(Some(2), Some(2));
+        renderer.queue_cells(&frame, &[0]).unwrap();
+        let output = String::from_utf8(renderer.frame_buffer.clone()).unwrap();
+        assert_eq!(output, "\x1b[1;1H ");
+
+        // A full frame still leaves blanks to the background
+        let output = String::from_utf8(renderer.encode_frame(&frame).unwrap().to_vec()).unwrap();
+        assert!(!output.contains(' '));
+    }
+
+    #[test]
+    fn test_render_to_string() {
+        let frame = create_test_frame();
+        let renderer = Renderer::new(false, false).unwrap();
+        assert_eq!(renderer.render_to_string(&frame), "# \n@ \n");
+
+        let renderer = Renderer::new(false, true).unwrap();
+        let text = renderer.render_to_string(&frame);
+        assert!(text.starts_with("\x1b[38;2;255;0;0m\x1b[48;2;0;0;0m#"));
+        assert!(text.ends_with("\x1b[0m\n"));
+
+        let renderer = Renderer::new(true, true).unwrap();
+        assert!(!renderer.render_to_string(&frame).contains("\x1b[48;"));
+    }
 }
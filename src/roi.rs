@@ -0,0 +1,160 @@
+//! Following a region of interest when cropping
+//!
+//! In `--cover` mode the picture is cropped to the shape of the terminal.
+//! `--roi-follow` pans that window toward where the action is: each frame is
+//! scored on a coarse grid by brightness and by how much it changed since the
+//! previous frame, and the window is centered on the score-weighted centroid,
+//! smoothed over time so it glides instead of jumping. Face detection is not
+//! part of this; bright or moving areas stand in for them.
+
+use crate::crop::CropRect;
+use crate::decoder::VideoFrame;
+
+/// Coarse grid frames are scored on
+const GRID_WIDTH: u32 = 32;
+const GRID_HEIGHT: u32 = 18;
+
+/// Weight of change since the previous frame relative to brightness
+const MOTION_WEIGHT: f32 = 4.0;
+
+/// Weight of the previous center; higher pans more slowly
+const ROI_SMOOTHING: f64 = 0.9;
+
+/// Tracks the bright or moving part of the picture
+#[derive(Debug, Default)]
+pub struct RoiTracker {
+    previous: Option<Vec<f32>>,
+    source: (u32, u32),
+    last_timestamp: f64,
+    /// Smoothed center as fractions of the width and height
+    center: Option<(f64, f64)>,
+}
+
+impl RoiTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take in a frame and return the smoothed center of interest as
+    /// fractions of the width and height. Tracking starts over after a
+    /// resize or a jump back in time.
+    pub fn update(&mut self, frame: &VideoFrame) -> (f64, f64) {
+        if self.source != (frame.width, frame.height) || frame.timestamp < self.last_timestamp {
+            self.previous = None;
+            self.center = None;
+        }
+        self.source = (frame.width, frame.height);
+        self.last_timestamp = frame.timestamp;
+
+        let luma = sample_luma(frame);
+        let (mut total, mut sum_x, mut sum_y) = (0.0f64, 0.0f64, 0.0f64);
+        for (index, &value) in luma.iter().enumerate() {
+            let change = self
+                .previous
+                .as_ref()
+                .map_or(0.0, |previous| (value - previous[index]).abs());
+            let score = value + MOTION_WEIGHT * change;
+            // Squaring favors the strongest areas over a dim background
+            let weight = (score * score) as f64;
+            let gx = (index as u32 % GRID_WIDTH) as f64 + 0.5;
+            let gy = (index as u32 / GRID_WIDTH) as f64 + 0.5;
+            total += weight;
+            sum_x += weight * gx / GRID_WIDTH as f64;
+            sum_y += weight * gy / GRID_HEIGHT as f64;
+        }
+        self.previous = Some(luma);
+
+        let target = if total > f64::EPSILON {
+            (sum_x / total, sum_y / total)
+        } else {
+            (0.5, 0.5)
+        };
+        let center = match self.center {
+            Some((x, y)) => (
+                x * ROI_SMOOTHING + target.0 * (1.0 - ROI_SMOOTHING),
+                y * ROI_SMOOTHING + target.1 * (1.0 - ROI_SMOOTHING),
+            ),
+            None => target,
+        };
+        self.center = Some(center);
+        center
+    }
+}
+
+fn sample_luma(frame: &VideoFrame) -> Vec<f32> {
+    let mut luma = Vec::with_capacity((GRID_WIDTH * GRID_HEIGHT) as usize);
+    for gy in 0..GRID_HEIGHT {
+        let y =
+            ((gy * 2 + 1) * frame.height / (GRID_HEIGHT * 2)).min(frame.height.saturating_sub(1));
+        for gx in 0..GRID_WIDTH {
+            let x =
+                ((gx * 2 + 1) * frame.width / (GRID_WIDTH * 2)).min(frame.width.saturating_sub(1));
+            let index = ((y * frame.width + x) * 3) as usize;
+            luma.push(match frame.data.get(index..index + 3) {
+                Some(&[r, g, b]) => {
+                    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0
+                }
+                _ => 0.0,
+            });
+        }
+    }
+    luma
+}
+
+/// A `window` sized crop of a `frame` sized picture, centered as close to
+/// `center` (fractions of the width and height) as fits
+pub fn window_at(frame: (u32, u32), window: (u32, u32), center: (f64, f64)) -> CropRect {
+    let width = window.0.clamp(1, frame.0.max(1));
+    let height = window.1.clamp(1, frame.1.max(1));
+    let place = |size: u32, extent: u32, center: f64| {
+        let start = center * size as f64 - extent as f64 / 2.0;
+        start.round().clamp(0.0, (size - extent) as f64) as u32
+    };
+
+    CropRect {
+        x: place(frame.0.max(1), width, center.0),
+        y: place(frame.1.max(1), height, center.1),
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dark frame with a bright square whose left edge is at `x`
+    fn frame_with_square(x: u32, timestamp: f64) -> VideoFrame {
+        let (width, height) = (320, 180);
+        let mut data = vec![10u8; (width * height * 3) as usize];
+        for py in 70..110 {
+            for px in x..x + 40 {
+                let index = ((py * width + px) * 3) as usize;
+                data[index..index + 3].copy_from_slice(&[255, 255, 255]);
+            }
+        }
+        VideoFrame {
+            data,
+            width,
+            height,
+            timestamp,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_roi_follows_bright_region() {
+        let mut tracker = RoiTracker::new();
+        let (x, y) = tracker.update(&frame_with_square(260, 0.0));
+        assert!(x > 0.8, "center {} should be near the square", x);
+        assert!((y - 0.5).abs() < 0.1);
+
+        // The square jumps left; the window follows gradually
+        let (moved, _) = tracker.update(&frame_with_square(20, 0.04));
+        assert!(moved < x && moved > 0.5);
+
+        let window = window_at((320, 180), (100, 180), (x, y));
+        assert_eq!((window.x, window.width), (220, 100));
+        assert_eq!(window_at((320, 180), (100, 180), (0.0, 0.5)).x, 0);
+    }
+}
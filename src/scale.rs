@@ -0,0 +1,210 @@
+//! Downscaling of decoded frames to the character grid
+//!
+//! A terminal grid is often a twentieth of the video's width, so the filter
+//! used to shrink the picture decides how much detail survives. Nearest
+//! neighbor keeps one source pixel per cell and is the cheapest, but thin
+//! lines and text flicker in and out; area averaging gives every source
+//! pixel a say. `--sharpen` adds an unsharp mask on the small picture, which
+//! restores the edges that averaging softens.
+
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+
+/// Filter used to shrink frames to the grid
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scaler {
+    /// One source pixel per cell: fastest, but fine detail flickers
+    #[default]
+    Nearest,
+    /// Linear interpolation over the pixels each cell covers
+    Bilinear,
+    /// Lanczos (3 lobes): crisp, with slight ringing at hard edges
+    Lanczos,
+    /// Average of every pixel a cell covers: the most stable for big
+    /// reductions
+    Area,
+}
+
+/// Shrink (or enlarge) RGB `data` of a `src_width` x `src_height` picture to
+/// `dst_width` x `dst_height`. Pixels missing from short `data` count as
+/// black.
+pub fn resize(
+    data: &[u8],
+    (src_width, src_height): (u32, u32),
+    (dst_width, dst_height): (u32, u32),
+    scaler: Scaler,
+) -> Vec<u8> {
+    if src_width == 0 || src_height == 0 || dst_width == 0 || dst_height == 0 {
+        return vec![0; (dst_width * dst_height * 3) as usize];
+    }
+
+    let filter = match scaler {
+        Scaler::Nearest => return nearest(data, (src_width, src_height), (dst_width, dst_height)),
+        Scaler::Area => return area(data, (src_width, src_height), (dst_width, dst_height)),
+        Scaler::Bilinear => FilterType::Triangle,
+        Scaler::Lanczos => FilterType::Lanczos3,
+    };
+
+    let mut pixels = data.to_vec();
+    pixels.resize((src_width * src_height * 3) as usize, 0);
+    match RgbImage::from_raw(src_width, src_height, pixels) {
+        Some(image) => imageops::resize(&image, dst_width, dst_height, filter).into_raw(),
+        None => nearest(data, (src_width, src_height), (dst_width, dst_height)),
+    }
+}
+
+/// The source pixel at the top left of each cell
+fn nearest(
+    data: &[u8],
+    (src_width, src_height): (u32, u32),
+    (width, height): (u32, u32),
+) -> Vec<u8> {
+    let mut resized = Vec::with_capacity((width * height * 3) as usize);
+    let x_ratio = src_width as f64 / width as f64;
+    let y_ratio = src_height as f64 / height as f64;
+
+    for y in 0..height {
+        let src_y = (y as f64 * y_ratio) as u32;
+        for x in 0..width {
+            let src_x = (x as f64 * x_ratio) as u32;
+            let index = ((src_y * src_width + src_x) * 3) as usize;
+            match data.get(index..index + 3) {
+                Some(pixel) => resized.extend_from_slice(pixel),
+                None => resized.extend_from_slice(&[0, 0, 0]),
+            }
+        }
+    }
+    resized
+}
+
+/// Source pixels `[start, end)` covered by output pixel `index` of `count`,
+/// always at least one
+fn span(index: u32, count: u32, source: u32) -> (u32, u32) {
+    let start = (index as u64 * source as u64 / count as u64) as u32;
+    let end = ((index as u64 + 1) * source as u64).div_ceil(count as u64) as u32;
+    (start.min(source - 1), end.clamp(start + 1, source))
+}
+
+/// The mean of every source pixel each cell covers
+fn area(data: &[u8], (src_width, src_height): (u32, u32), (width, height): (u32, u32)) -> Vec<u8> {
+    let columns: Vec<(u32, u32)> = (0..width).map(|x| span(x, width, src_width)).collect();
+    let mut resized = Vec::with_capacity((width * height * 3) as usize);
+
+    for y in 0..height {
+        let (top, bottom) = span(y, height, src_height);
+        for &(left, right) in &columns {
+            let mut sum = [0u64; 3];
+            for src_y in top..bottom {
+                let row = (src_y * src_width) as usize;
+                for src_x in left..right {
+                    let index = (row + src_x as usize) * 3;
+                    if let Some(pixel) = data.get(index..index + 3) {
+                        for (total, &value) in sum.iter_mut().zip(pixel) {
+                            *total += value as u64;
+                        }
+                    }
+                }
+            }
+            let count = ((bottom - top) * (right - left)) as u64;
+            resized.extend(sum.map(|total| ((total + count / 2) / count) as u8));
+        }
+    }
+    resized
+}
+
+/// Unsharp mask over RGB `data` of a `width` x `height` picture, in place:
+/// every pixel moves away from its 3x3 Gaussian blur by `amount` times the
+/// difference. Sized for grids where one pixel is a whole cell.
+pub fn sharpen(data: &mut [u8], width: u32, height: u32, amount: f64) {
+    let (width, height) = (width as usize, height as usize);
+    if amount <= 0.0 || width == 0 || height == 0 || data.len() < width * height * 3 {
+        return;
+    }
+
+    let source = data.to_vec();
+    // Edge pixels repeat the border
+    let at = |x: isize, y: isize, channel: usize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        source[(y * width + x) * 3 + channel] as f64
+    };
+    const KERNEL: [[f64; 3]; 3] = [[1.0, 2.0, 1.0], [2.0, 4.0, 2.0], [1.0, 2.0, 1.0]];
+
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            for channel in 0..3 {
+                let mut blurred = 0.0;
+                for (dy, row) in KERNEL.iter().enumerate() {
+                    for (dx, weight) in row.iter().enumerate() {
+                        blurred += weight * at(x + dx as isize - 1, y + dy as isize - 1, channel);
+                    }
+                }
+                let original = at(x, y, channel);
+                let value = original + amount * (original - blurred / 16.0);
+                data[(y as usize * width + x as usize) * 3 + channel] =
+                    value.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One-pixel-wide white vertical lines every `period` columns on black
+    fn stripes(width: u32, height: u32, period: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|_| (0..width).flat_map(move |x| [if x % period == 0 { 255 } else { 0 }; 3]))
+            .collect()
+    }
+
+    #[test]
+    fn test_nearest_keeps_sampled_pixels() {
+        // Every sampled column is a line, so nearest shows solid white
+        let data = stripes(40, 4, 10);
+        let resized = resize(&data, (40, 4), (4, 2), Scaler::Nearest);
+        assert_eq!(resized.len(), 4 * 2 * 3);
+        assert!(resized.iter().all(|&value| value == 255));
+    }
+
+    #[test]
+    fn test_area_averages_covered_pixels() {
+        // One line in every ten columns averages to a tenth of white
+        let data = stripes(40, 4, 10);
+        let resized = resize(&data, (40, 4), (4, 2), Scaler::Area);
+        assert!(resized.iter().all(|&value| value == 26));
+    }
+
+    #[test]
+    fn test_filters_produce_full_grids() {
+        let data = stripes(64, 48, 3);
+        for scaler in [Scaler::Bilinear, Scaler::Lanczos] {
+            assert_eq!(resize(&data, (64, 48), (8, 6), scaler).len(), 8 * 6 * 3);
+        }
+        // Short data is padded with black rather than rejected
+        assert_eq!(
+            resize(&data[..100], (64, 48), (8, 6), Scaler::Lanczos).len(),
+            8 * 6 * 3
+        );
+    }
+
+    #[test]
+    fn test_sharpen_raises_edge_contrast() {
+        // Left half dark gray, right half light gray
+        let mut data: Vec<u8> = (0..4)
+            .flat_map(|_| (0..4).flat_map(|x| [if x < 2 { 64 } else { 192 }; 3]))
+            .collect();
+        sharpen(&mut data, 4, 4, 1.0);
+        let row: Vec<u8> = data.chunks_exact(3).take(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(row[0], 64);
+        assert!(row[1] < 64);
+        assert!(row[2] > 192);
+        assert_eq!(row[3], 192);
+
+        // Flat pictures are left alone
+        let mut flat = vec![100; 4 * 4 * 3];
+        sharpen(&mut flat, 4, 4, 1.0);
+        assert!(flat.iter().all(|&value| value == 100));
+    }
+}
@@ -0,0 +1,239 @@
+//! Scheduled playback starts and countdowns
+//!
+//! A `--start-at-clock` time is resolved to its next occurrence in local time
+//! and converted to a monotonic deadline once, so adjusting the system clock
+//! during the wait does not move the start. Players on machines with
+//! synchronized clocks given the same start time begin together.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A time of day in local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl ClockTime {
+    /// Seconds since local midnight
+    pub fn seconds_of_day(&self) -> i64 {
+        self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+}
+
+impl FromStr for ClockTime {
+    type Err = anyhow::Error;
+
+    /// Parse `HH:MM` or `HH:MM:SS` in 24-hour time
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        if !(2..=3).contains(&parts.len()) {
+            return Err(anyhow!("Expected HH:MM or HH:MM:SS, got '{}'", s));
+        }
+
+        let field = |index: usize, max: u8, name: &str| -> Result<u8> {
+            let Some(part) = parts.get(index) else {
+                return Ok(0);
+            };
+            match part.parse::<u8>() {
+                Ok(value) if value <= max => Ok(value),
+                _ => Err(anyhow!("Invalid {} '{}' in clock time '{}'", name, part, s)),
+            }
+        };
+
+        Ok(Self {
+            hour: field(0, 23, "hour")?,
+            minute: field(1, 59, "minute")?,
+            second: field(2, 59, "second")?,
+        })
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.second == 0 {
+            write!(f, "{:02}:{:02}", self.hour, self.minute)
+        } else {
+            write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+        }
+    }
+}
+
+/// Offset of local time from UTC in seconds at the given Unix time
+#[cfg(unix)]
+pub fn utc_offset(unix_seconds: i64) -> i64 {
+    let time = unix_seconds as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Offset of local time from UTC in seconds; UTC is assumed off Unix
+#[cfg(not(unix))]
+pub fn utc_offset(_unix_seconds: i64) -> i64 {
+    0
+}
+
+/// Time from `now` until the next occurrence of `time`.
+///
+/// `now` is a Unix time in seconds and `utc_offset` the local offset in
+/// seconds. A time that is now or already past today means tomorrow, except
+/// for the exact current second, which starts immediately.
+pub fn until_clock(time: ClockTime, now: f64, utc_offset: i64) -> Duration {
+    let local = now + utc_offset as f64;
+    let second_of_day = local.rem_euclid(SECONDS_PER_DAY as f64);
+    let mut wait = time.seconds_of_day() as f64 - second_of_day;
+    if wait <= -1.0 {
+        wait += SECONDS_PER_DAY as f64;
+    }
+    Duration::from_secs_f64(wait.max(0.0))
+}
+
+/// Format a number of seconds as `S`, `M:SS` or `H:MM:SS`
+pub fn format_remaining(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}:{:02}", minutes, seconds)
+    } else {
+        seconds.to_string()
+    }
+}
+
+/// When playback should begin and what to show until then
+#[derive(Debug, Clone)]
+pub struct StartSchedule {
+    deadline: Instant,
+    start_at: Option<ClockTime>,
+    countdown: Option<u64>,
+}
+
+impl StartSchedule {
+    /// Build a schedule, or `None` when playback should start right away.
+    ///
+    /// With both options, playback starts at the clock time and the countdown
+    /// only appears for its final `countdown` seconds.
+    pub fn new(start_at: Option<ClockTime>, countdown: Option<u64>) -> Option<Self> {
+        let wait = match (start_at, countdown) {
+            (Some(time), _) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                until_clock(time, now, utc_offset(now as i64))
+            }
+            (None, Some(seconds)) => Duration::from_secs(seconds),
+            (None, None) => return None,
+        };
+
+        Some(Self {
+            deadline: Instant::now() + wait,
+            start_at,
+            countdown,
+        })
+    }
+
+    /// Time left before playback starts
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether playback should start now
+    pub fn is_due(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Text for the big-text overlay while waiting
+    pub fn overlay_text(&self) -> String {
+        self.overlay_text_at(self.remaining())
+    }
+
+    fn overlay_text_at(&self, remaining: Duration) -> String {
+        // Round up so the display reads 1 during the final second
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        match (self.start_at, self.countdown) {
+            (Some(time), Some(countdown)) if seconds > countdown => {
+                format!("Starts at\n{}", time)
+            }
+            _ => format_remaining(seconds),
+        }
+    }
+
+    /// Status line shown below the countdown
+    pub fn status(&self) -> String {
+        match self.start_at {
+            Some(time) => format!(
+                "Starting at {} (in {}) | Q to cancel",
+                time,
+                format_remaining(self.remaining().as_secs())
+            ),
+            None => "Starting soon | Q to cancel".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clock_time() {
+        let time: ClockTime = "21:00".parse().unwrap();
+        assert_eq!((time.hour, time.minute, time.second), (21, 0, 0));
+        assert_eq!(time.to_string(), "21:00");
+        assert_eq!(
+            "7:05:09".parse::<ClockTime>().unwrap().to_string(),
+            "07:05:09"
+        );
+
+        assert!("24:00".parse::<ClockTime>().is_err());
+        assert!("12:60".parse::<ClockTime>().is_err());
+        assert!("21".parse::<ClockTime>().is_err());
+        assert!("ab:cd".parse::<ClockTime>().is_err());
+    }
+
+    #[test]
+    fn test_until_clock_wraps_to_tomorrow() {
+        let nine_pm: ClockTime = "21:00".parse().unwrap();
+        // 20:59:30 UTC on day 2
+        let now = (2 * SECONDS_PER_DAY + 20 * 3600 + 59 * 60 + 30) as f64;
+        assert_eq!(until_clock(nine_pm, now, 0), Duration::from_secs(30));
+        // One hour ahead of UTC, it is already 21:59:30 locally
+        assert_eq!(
+            until_clock(nine_pm, now, 3600),
+            Duration::from_secs((SECONDS_PER_DAY - 3570) as u64)
+        );
+        // Within the start second, play immediately
+        assert_eq!(until_clock(nine_pm, now + 30.5, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_overlay_text() {
+        assert_eq!(format_remaining(9), "9");
+        assert_eq!(format_remaining(75), "1:15");
+        assert_eq!(format_remaining(3725), "1:02:05");
+
+        let countdown = StartSchedule::new(None, Some(10)).unwrap();
+        assert_eq!(countdown.overlay_text_at(Duration::from_millis(9200)), "10");
+
+        let scheduled = StartSchedule {
+            deadline: Instant::now(),
+            start_at: Some("21:00".parse().unwrap()),
+            countdown: Some(10),
+        };
+        assert_eq!(
+            scheduled.overlay_text_at(Duration::from_secs(60)),
+            "Starts at\n21:00"
+        );
+        assert_eq!(scheduled.overlay_text_at(Duration::from_secs(3)), "3");
+    }
+}
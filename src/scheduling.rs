@@ -0,0 +1,160 @@
+//! CPU affinity and priority hints
+//!
+//! On a machine shared with other work, `--decode-cpu` and `--render-cpu`
+//! keep the decode thread and the render thread on separate cores, so they
+//! stop competing with each other and stay warm in their caches, and
+//! `--nice` lowers the player's priority so it yields to everything else.
+//! These are hints: where the platform or permissions do not allow them,
+//! playback goes on with a warning.
+//!
+//! A thread starts out with the affinity of the thread that started it, so
+//! the render thread is pinned only once a file's worker threads are
+//! running, and threads started later call [`release_thread`] to leave the
+//! render core.
+
+use anyhow::Result;
+
+/// CPUs the process could run on before the first thread was pinned
+#[cfg(target_os = "linux")]
+static UNPINNED: std::sync::OnceLock<libc::cpu_set_t> = std::sync::OnceLock::new();
+
+/// Keep the calling thread on `cpu` (counted from 0)
+#[cfg(target_os = "linux")]
+pub fn pin_thread(cpu: usize) -> Result<()> {
+    let max_cpus = 8 * std::mem::size_of::<libc::cpu_set_t>();
+    if cpu >= max_cpus {
+        anyhow::bail!("CPU {} is out of range", cpu);
+    }
+    unsafe {
+        // 0 is the calling thread
+        if UNPINNED.get().is_none() {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) == 0 {
+                let _ = UNPINNED.set(set);
+            }
+        }
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Thread affinity is only set on Linux
+#[cfg(not(target_os = "linux"))]
+pub fn pin_thread(cpu: usize) -> Result<()> {
+    anyhow::bail!("Pinning to CPU {} is not supported on this platform", cpu)
+}
+
+/// Let the calling thread run on every CPU the process could before any
+/// thread was pinned, undoing a pin it inherited from the thread that
+/// started it
+#[cfg(target_os = "linux")]
+pub fn release_thread() {
+    if let Some(set) = UNPINNED.get() {
+        let result =
+            unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), set) };
+        if result != 0 {
+            log::debug!(
+                "Failed to release thread from its CPU: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Nothing is pinned off Linux
+#[cfg(not(target_os = "linux"))]
+pub fn release_thread() {}
+
+/// Set the scheduling niceness, from -20 (favored) to 19 (yields most).
+///
+/// Niceness belongs to each thread on Linux, so every thread of the player
+/// is changed; threads started afterwards inherit it.
+#[cfg(target_os = "linux")]
+pub fn set_nice(nice: i32) -> Result<()> {
+    for entry in std::fs::read_dir("/proc/self/task")? {
+        let name = entry?.file_name();
+        let Some(thread) = name.to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, thread, nice) };
+        let error = std::io::Error::last_os_error();
+        // A thread may have ended since the listing
+        if result != 0 && error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(error.into());
+        }
+    }
+    Ok(())
+}
+
+/// Set the scheduling niceness of the whole process, from -20 (favored)
+/// to 19 (yields most)
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn set_nice(nice: i32) -> Result<()> {
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Priorities are only set on Unix
+#[cfg(not(unix))]
+pub fn set_nice(_nice: i32) -> Result<()> {
+    anyhow::bail!("--nice is not supported on this platform")
+}
+
+/// Pin the calling thread, named `label` in logs, if `cpu` is set; failures
+/// only warn
+pub fn pin_thread_or_warn(cpu: Option<usize>, label: &str) {
+    if let Some(cpu) = cpu {
+        match pin_thread(cpu) {
+            Ok(()) => log::debug!("Pinned {} thread to CPU {}", label, cpu),
+            Err(e) => log::warn!("Not pinning {} thread to CPU {}: {}", label, cpu, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pin_thread() {
+        // Pin a scratch thread so the test runner's threads are left alone
+        std::thread::spawn(|| {
+            assert!(pin_thread(usize::MAX).is_err());
+            assert!(pin_thread(100_000).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_started_threads_leave_the_pinned_cpu() {
+        fn cpus() -> usize {
+            unsafe {
+                let mut set: libc::cpu_set_t = std::mem::zeroed();
+                libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set);
+                libc::CPU_COUNT(&set) as usize
+            }
+        }
+        let available = cpus();
+        std::thread::spawn(move || {
+            pin_thread(0).unwrap();
+            assert_eq!(cpus(), 1);
+            let released = std::thread::spawn(|| {
+                release_thread();
+                cpus()
+            });
+            assert_eq!(released.join().unwrap(), available);
+        })
+        .join()
+        .unwrap();
+    }
+}
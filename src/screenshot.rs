@@ -0,0 +1,129 @@
+//! Saving the frame on screen
+//!
+//! `s` saves the frame being shown twice over: as plain text (`.txt`) and
+//! with 24-bit color escapes (`.ans`, as `--dump-ansi` writes frames). With
+//! `--screenshot-png` a picture of it is saved as well, drawn with the font
+//! GIF exports use. The files are named after the input and the moment of
+//! it, such as `talk-00-01-02.500.txt`, and go in `--screenshot-dir` (the
+//! current directory by default); a second shot of the same moment gets a
+//! numbered name instead of replacing the first.
+
+use crate::converter::AsciiFrame;
+use crate::raster::render_image;
+use crate::telnet::{encode_text, ColorDepth};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files a screenshot is saved as
+const TEXT_EXTENSIONS: [&str; 2] = ["txt", "ans"];
+const PNG_EXTENSION: &str = "png";
+
+/// Base file name for a screenshot of `input` at `position` seconds
+pub fn screenshot_stem(input: &Path, position: f64) -> String {
+    let name = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| "ascii-player".to_string());
+    let millis = (position.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{}-{:02}-{:02}-{:02}.{:03}",
+        name,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Save `frame` in `dir` as `stem.txt` and `stem.ans`, and `stem.png` if
+/// `png` is set; returns the files written
+pub fn save_screenshot_files(
+    frame: &AsciiFrame,
+    dir: &Path,
+    stem: &str,
+    png: bool,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut extensions = TEXT_EXTENSIONS.to_vec();
+    if png {
+        extensions.push(PNG_EXTENSION);
+    }
+    let taken = |stem: &str| {
+        extensions
+            .iter()
+            .any(|extension| dir.join(format!("{}.{}", stem, extension)).exists())
+    };
+    let stem = (1..)
+        .map(|n| match n {
+            1 => stem.to_string(),
+            n => format!("{}-{}", stem, n),
+        })
+        .find(|stem| !taken(stem))
+        .expect("some name is free");
+
+    let mut paths = Vec::new();
+    for extension in extensions {
+        let path = dir.join(format!("{}.{}", stem, extension));
+        match extension {
+            PNG_EXTENSION => render_image(frame)
+                .save(&path)
+                .with_context(|| format!("Failed to write {}", path.display()))?,
+            _ => {
+                let depth = if extension == "ans" {
+                    ColorDepth::TrueColor
+                } else {
+                    ColorDepth::Mono
+                };
+                std::fs::write(&path, encode_text(frame, depth))
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screenshot_stem() {
+        assert_eq!(
+            screenshot_stem(Path::new("/videos/talk.mp4"), 62.5),
+            "talk-00-01-02.500"
+        );
+        assert_eq!(
+            screenshot_stem(Path::new(""), 3725.0),
+            "ascii-player-01-02-05.000"
+        );
+    }
+
+    #[test]
+    fn test_save_screenshot_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("screenshots");
+        let frame = AsciiFrame::new(2, 1, vec!['#', '.'], vec![(255, 0, 0); 2]);
+
+        let first = save_screenshot_files(&frame, &dir, "clip-00-00-01.000", false).unwrap();
+        assert_eq!(
+            first,
+            [
+                dir.join("clip-00-00-01.000.txt"),
+                dir.join("clip-00-00-01.000.ans")
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(&first[0]).unwrap(), "#.\n");
+        assert!(std::fs::read_to_string(&first[1])
+            .unwrap()
+            .starts_with("\x1b[38;2;255;0;0m#."));
+
+        // The same moment again doesn't overwrite the first shot
+        let second = save_screenshot_files(&frame, &dir, "clip-00-00-01.000", true).unwrap();
+        assert_eq!(second[0], dir.join("clip-00-00-01.000-2.txt"));
+        assert_eq!(second[2], dir.join("clip-00-00-01.000-2.png"));
+        assert!(second[2].exists());
+    }
+}
@@ -0,0 +1,246 @@
+//! Arrow key seeking with a warm preview cache
+//!
+//! Holding ←/→ moves a seek target in [`SEEK_STEP`] jumps, and ↑/↓ in
+//! [`SEEK_STEP_LONG`] jumps, without reopening the video each time; the
+//! player seeks once the key has been let go for [`SEEK_SETTLE`]. Reopening
+//! and seeking the main decoder takes a moment, so while the key is held a
+//! second, lightweight decoder decodes the frame at the target and at the
+//! next few places the seek could land. The frame at the final target is
+//! then shown right away while playback catches up.
+//!
+//! While paused, `,` steps back a frame the same way: the decoder can only
+//! go forwards, so it seeks to just before the previous frame
+//! ([`frame_before`]) and shows the first frame it lands on.
+
+use crate::decoder::{FrameIterator, VideoDecoder, VideoFrame};
+use log::debug;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Seconds moved by each ←/→ press or key repeat
+pub const SEEK_STEP: f64 = 5.0;
+
+/// Seconds moved by each ↑/↓ press or key repeat
+pub const SEEK_STEP_LONG: f64 = 60.0;
+
+/// Time after the last seek key press before the player seeks
+pub const SEEK_SETTLE: Duration = Duration::from_millis(300);
+
+/// Landing points decoded ahead: the target and the next presses after it
+const LANDING_POINTS: usize = 3;
+
+/// Preview frames kept, oldest dropped first
+const MAX_PREVIEWS: usize = 8;
+
+/// Targets closer than this, in seconds, are the same landing point
+const SAME_TARGET: f64 = 0.001;
+
+/// Seek target built up while a seek key is held
+#[derive(Debug, Default)]
+pub struct HeldSeek {
+    target: Option<f64>,
+    last_press: Option<Instant>,
+}
+
+impl HeldSeek {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the target by `step` seconds, starting from `position` on the
+    /// first press, clamped to the video; returns the new target
+    pub fn press(&mut self, position: f64, step: f64, duration: f64) -> f64 {
+        let target = clamp_target(self.target.unwrap_or(position) + step, duration);
+        self.target = Some(target);
+        self.last_press = Some(Instant::now());
+        target
+    }
+
+    /// The target to seek to, once no key has been pressed for
+    /// [`SEEK_SETTLE`]
+    pub fn settled(&mut self) -> Option<f64> {
+        let last_press = self.last_press?;
+        if last_press.elapsed() < SEEK_SETTLE {
+            return None;
+        }
+        self.last_press = None;
+        self.target.take()
+    }
+}
+
+/// Decodes frames at likely seek landing points on its own thread
+pub struct SeekPrefetcher {
+    requests: Sender<Vec<f64>>,
+    previews: Arc<Mutex<Vec<(f64, VideoFrame)>>>,
+}
+
+impl SeekPrefetcher {
+    /// Start the prefetch thread for the video at `path`; its decoder is
+    /// only opened on the first request
+    pub fn spawn(path: &Path) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let previews = Arc::new(Mutex::new(Vec::new()));
+        let path = path.to_path_buf();
+        let shared = Arc::clone(&previews);
+        let spawned = thread::Builder::new()
+            .name("seek-prefetch".to_string())
+            .spawn(move || prefetch(&path, receiver, &shared));
+        if let Err(e) = spawned {
+            debug!("Seek prefetch unavailable: {}", e);
+        }
+        Self { requests, previews }
+    }
+
+    /// Decode the frames at `target` and the next landing points `step`
+    /// seconds apart, replacing any landing points still waiting
+    pub fn prefetch(&self, target: f64, step: f64, duration: f64) {
+        let _ = self.requests.send(landing_points(target, step, duration));
+    }
+
+    /// The preview frame decoded for `target`, if it is ready
+    pub fn take(&self, target: f64) -> Option<VideoFrame> {
+        let mut previews = self.previews.lock().unwrap_or_else(|e| e.into_inner());
+        let index = previews
+            .iter()
+            .position(|(point, _)| (point - target).abs() < SAME_TARGET)?;
+        Some(previews.remove(index).1)
+    }
+}
+
+/// Prefetch thread: decode the newest landing points until the player is
+/// gone
+fn prefetch(path: &Path, receiver: Receiver<Vec<f64>>, previews: &Mutex<Vec<(f64, VideoFrame)>>) {
+    let mut frames: Option<FrameIterator> = None;
+    let mut pending = VecDeque::new();
+    loop {
+        let waited = if pending.is_empty() {
+            match receiver.recv() {
+                Ok(points) => Some(points),
+                Err(_) => return,
+            }
+        } else {
+            None
+        };
+        // Only the newest landing points matter while the key repeats
+        if let Some(points) = receiver.try_iter().last().or(waited) {
+            pending = VecDeque::from(points);
+        }
+        let Some(point) = pending.pop_front() else {
+            continue;
+        };
+
+        let cached = previews
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|(cached, _)| (cached - point).abs() < SAME_TARGET);
+        if cached {
+            continue;
+        }
+
+        let frames = match frames {
+            Some(ref mut frames) => frames,
+            None => match VideoDecoder::new(path) {
+                Ok(decoder) => frames.insert(FrameIterator::new(decoder, None, None)),
+                Err(e) => {
+                    debug!("Seek prefetch decoder failed to open: {}", e);
+                    return;
+                }
+            },
+        };
+        match frames.seek(point).map(|_| frames.next()) {
+            Ok(Some(Ok(frame))) => {
+                debug!("Prefetched seek preview at {:.2}s", point);
+                let mut previews = previews.lock().unwrap_or_else(|e| e.into_inner());
+                if previews.len() >= MAX_PREVIEWS {
+                    previews.remove(0);
+                }
+                previews.push((point, frame));
+            }
+            Ok(Some(Err(e))) | Err(e) => debug!("Seek prefetch at {:.2}s failed: {}", point, e),
+            Ok(None) => {}
+        }
+    }
+}
+
+/// Seek target clamped to the video, or to the start if its length is
+/// unknown
+pub fn clamp_target(target: f64, duration: f64) -> f64 {
+    match duration {
+        duration if duration > 0.0 => target.clamp(0.0, duration),
+        _ => target.max(0.0),
+    }
+}
+
+/// Seek target landing on the frame before the one at `position`, for a
+/// video at `fps`. Seeks return the first frame at or after the target, so
+/// it sits half a frame before the previous frame's timestamp to allow for
+/// timestamps that aren't evenly spaced.
+pub fn frame_before(position: f64, fps: f64) -> f64 {
+    let frame = if fps > 0.0 { 1.0 / fps } else { 0.0 };
+    (position - frame * 1.5).max(0.0)
+}
+
+/// `target` and the places the next presses of a held key would reach
+fn landing_points(target: f64, step: f64, duration: f64) -> Vec<f64> {
+    let mut points: Vec<f64> = Vec::new();
+    for press in 0..LANDING_POINTS {
+        let point = clamp_target(target + step * press as f64, duration);
+        if points
+            .last()
+            .is_none_or(|last| (last - point).abs() >= SAME_TARGET)
+        {
+            points.push(point);
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_landing_points() {
+        assert_eq!(landing_points(10.0, 5.0, 60.0), vec![10.0, 15.0, 20.0]);
+        assert_eq!(landing_points(10.0, -5.0, 60.0), vec![10.0, 5.0, 0.0]);
+        // Points past the end collapse onto it
+        assert_eq!(landing_points(55.0, 5.0, 58.0), vec![55.0, 58.0]);
+        assert_eq!(clamp_target(-3.0, 0.0), 0.0);
+        assert_eq!(clamp_target(90.0, 0.0), 90.0);
+    }
+
+    #[test]
+    fn test_held_seek() {
+        let mut held = HeldSeek::new();
+        assert_eq!(held.settled(), None);
+        assert_eq!(held.press(30.0, SEEK_STEP, 100.0), 35.0);
+        // Repeats move on from the target, not from the playback position
+        assert_eq!(held.press(30.5, SEEK_STEP, 100.0), 40.0);
+        assert_eq!(held.settled(), None);
+
+        held.last_press = Some(Instant::now() - SEEK_SETTLE);
+        assert_eq!(held.settled(), Some(40.0));
+        assert_eq!(held.settled(), None);
+        assert_eq!(held.press(12.0, -SEEK_STEP, 100.0), 7.0);
+
+        // Long and short steps add up on the same target
+        assert_eq!(held.press(12.0, SEEK_STEP_LONG, 100.0), 67.0);
+        assert_eq!(held.press(12.0, SEEK_STEP_LONG, 100.0), 100.0);
+        assert_eq!(held.press(12.0, -SEEK_STEP_LONG, 100.0), 40.0);
+    }
+
+    #[test]
+    fn test_frame_before() {
+        // At 25 fps the previous frame is at 0.96s, the target between it
+        // and the one before
+        let target = frame_before(1.0, 25.0);
+        assert!(target > 0.92 && target < 0.96, "{}", target);
+        assert_eq!(frame_before(0.02, 25.0), 0.0);
+        assert_eq!(frame_before(3.0, 0.0), 3.0);
+    }
+}
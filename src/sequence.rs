@@ -0,0 +1,260 @@
+//! Image sequences played as video
+//!
+//! `ascii-player 'frames/*.png' --fps 12` plays the images matching a
+//! pattern as the frames of a video. Wildcards (`*` for any run of
+//! characters, `?` for one) are allowed in the file name, not in the
+//! directories above it. Matches are played in natural order, so
+//! `frame2.png` comes before `frame10.png`, and each image is loaded only
+//! when its turn comes. Frame times follow from `--fps`, or 25 frames per
+//! second without it.
+
+use crate::captions::ClosedCaptions;
+use crate::decoder::{FrameSource, VideoFrame, TIMESTAMP_EPSILON};
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use tracing::debug_span;
+
+/// Frame rate of a sequence played without `--fps`
+pub const DEFAULT_SEQUENCE_FPS: f64 = 25.0;
+
+/// Whether `path` is a pattern for an image sequence rather than a file:
+/// its name has wildcards and no file has that exact name
+pub fn is_image_pattern(path: &Path) -> bool {
+    let wildcards = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains(['*', '?']));
+    wildcards && !path.exists()
+}
+
+/// Frames loaded one by one from the images matching a pattern
+pub struct ImageSequence {
+    paths: Vec<PathBuf>,
+    /// Index of the next image to load
+    next: usize,
+    fps: f64,
+    /// Size of the first image
+    dimensions: (u32, u32),
+    /// Images carry no captions; kept to lend out as empty
+    captions: ClosedCaptions,
+}
+
+impl ImageSequence {
+    /// Find the images matching `pattern`, to be played at `fps`
+    pub fn open(pattern: &Path, fps: f64) -> Result<Self> {
+        let paths = expand(pattern)?;
+        let first = paths
+            .first()
+            .ok_or_else(|| anyhow!("No images match '{}'", pattern.display()))?;
+        let dimensions = image::image_dimensions(first)
+            .map_err(|e| anyhow!("Failed to read image '{}': {}", first.display(), e))?;
+        let fps = if fps > 0.0 { fps } else { DEFAULT_SEQUENCE_FPS };
+        debug!(
+            "Image sequence {}: {} images, {}x{}, {:.2} FPS",
+            pattern.display(),
+            paths.len(),
+            dimensions.0,
+            dimensions.1,
+            fps
+        );
+        Ok(Self {
+            paths,
+            next: 0,
+            fps,
+            dimensions,
+            captions: ClosedCaptions::default(),
+        })
+    }
+
+    /// Number of images in the sequence
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Whether the sequence has no images (never the case once opened)
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+impl FrameSource for ImageSequence {
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn duration(&self) -> f64 {
+        self.paths.len() as f64 / self.fps
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn seek_to(&mut self, timestamp: f64) -> Result<()> {
+        let index = (timestamp.max(0.0) * self.fps - TIMESTAMP_EPSILON).ceil() as usize;
+        self.next = index.min(self.paths.len());
+        debug!("Seeked to image {} ({:.2}s)", self.next, timestamp);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+        let _span = debug_span!("decode").entered();
+        let Some(path) = self.paths.get(self.next) else {
+            return Ok(None);
+        };
+        let image = image::open(path)
+            .map_err(|e| anyhow!("Failed to load image '{}': {}", path.display(), e))?
+            .to_rgb8();
+        let (width, height) = image.dimensions();
+        let frame = VideoFrame {
+            data: image.into_raw(),
+            width,
+            height,
+            timestamp: self.next as f64 / self.fps,
+            frame_number: self.next as u64 + 1,
+        };
+        self.next += 1;
+        Ok(Some(frame))
+    }
+
+    fn captions(&self) -> &ClosedCaptions {
+        &self.captions
+    }
+}
+
+/// Files matching `pattern`, in natural order
+fn expand(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let name_pattern: Vec<char> = pattern
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid image pattern '{}'", pattern.display()))?
+        .chars()
+        .collect();
+    let directory = match pattern.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let entries = std::fs::read_dir(directory)
+        .map_err(|e| anyhow!("Failed to list '{}': {}", directory.display(), e))?;
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| !kind.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| wildcard_match(&name_pattern, &name.chars().collect::<Vec<_>>()))
+        .collect();
+    names.sort_by(|a, b| natural_cmp(a, b));
+    Ok(names.into_iter().map(|name| directory.join(name)).collect())
+}
+
+/// Match `name` against a pattern of literal characters, `*` and `?`
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((&literal, rest)) => {
+            name.first() == Some(&literal) && wildcard_match(rest, &name[1..])
+        }
+    }
+}
+
+/// Compare names with runs of digits taken as numbers
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let a_digits = a.len() - a.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let b_digits = b.len() - b.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let (a_number, b_number) = (&a[..a_digits], &b[..b_digits]);
+            let ordering = compare_numbers(a_number, b_number);
+            a = &a[a_digits..];
+            b = &b[b_digits..];
+            ordering
+        } else {
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+            x.cmp(&y)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Compare two runs of digits by value, then by length so "01" and "1"
+/// have an order
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let (a_trimmed, b_trimmed) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a_trimmed
+        .len()
+        .cmp(&b_trimmed.len())
+        .then_with(|| a_trimmed.cmp(b_trimmed))
+        .then_with(|| a.len().cmp(&b.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        let matches = |pattern: &str, name: &str| {
+            wildcard_match(
+                &pattern.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(matches("*.png", "frame_0001.png"));
+        assert!(matches("frame_????.png", "frame_0001.png"));
+        assert!(!matches("*.png", "frame_0001.jpg"));
+        assert!(!matches("frame_???.png", "frame_0001.png"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn test_natural_order() {
+        let mut names = vec!["frame10.png", "frame2.png", "frame1.png", "frame02.png"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["frame1.png", "frame2.png", "frame02.png", "frame10.png"]
+        );
+    }
+
+    #[test]
+    fn test_image_sequence() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        for (index, shade) in [(1, 0u8), (2, 128), (10, 255)] {
+            image::RgbImage::from_pixel(4, 2, image::Rgb([shade; 3]))
+                .save(dir.join(format!("frame{}.png", index)))
+                .unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not a frame").unwrap();
+
+        let pattern = dir.join("frame*.png");
+        assert!(is_image_pattern(&pattern));
+        assert!(!is_image_pattern(&dir.join("frame1.png")));
+
+        let mut sequence = ImageSequence::open(&pattern, 12.0).unwrap();
+        assert_eq!(sequence.len(), 3);
+        assert_eq!(sequence.dimensions(), (4, 2));
+        assert!((sequence.duration() - 0.25).abs() < 1e-9);
+
+        let first = sequence.next_frame().unwrap().unwrap();
+        assert_eq!((first.width, first.height, first.data[0]), (4, 2, 0));
+        sequence.seek_to(2.0 / 12.0).unwrap();
+        let last = sequence.next_frame().unwrap().unwrap();
+        assert_eq!(last.data[0], 255);
+        assert!((last.timestamp - 2.0 / 12.0).abs() < 1e-9);
+        assert!(sequence.next_frame().unwrap().is_none());
+
+        assert!(ImageSequence::open(&dir.join("missing*.png"), 12.0).is_err());
+    }
+}
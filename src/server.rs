@@ -0,0 +1,1091 @@
+//! Telnet streaming server
+//!
+//! A single decode loop converts frames against a shared playback clock and
+//! publishes the latest encoded frame. Every connected client is sent that
+//! same frame, so viewers stay in step and late joiners start at the live
+//! position instead of the beginning. A line-based admin listener pauses and
+//! seeks the clock for everyone.
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::converter::AsciiFrame;
+use crate::seek::clamp_target;
+use crate::supervisor::Supervisor;
+use crate::telnet::{
+    encode_frame, ColorDepth, TelnetEvent, TelnetInput, NEGOTIATION, REQUEST_TERMINAL_TYPE,
+};
+use crate::termcaps::TermCaps;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{sleep, sleep_until, timeout};
+
+/// Clear the screen and hide the cursor when a client connects
+const SCREEN_SETUP: &str = "\x1b[2J\x1b[?25l";
+
+/// Sent to clients turned away by `--max-clients`
+const SERVER_FULL: &str = "Server full, try again later\r\n";
+
+/// Restore the cursor before a client disconnects
+const SCREEN_RESET: &str = "\x1b[0m\x1b[?25h\r\n";
+
+/// How long a new client has to report its terminal type
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long a client has to enter the auth token
+const AUTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest token accepted from a client
+const MAX_TOKEN_LENGTH: usize = 256;
+
+/// Connections still negotiating or entering the token; these don't count
+/// towards `--max-clients` until they authenticate
+const MAX_PENDING_CLIENTS: usize = 16;
+
+/// Longest sleep between clock checks while waiting for a frame
+const CLOCK_POLL: Duration = Duration::from_millis(50);
+
+/// How long an admin seek waits to hear whether the decoder managed it
+const SEEK_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Playback position shared by every client
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    anchor: Instant,
+    anchor_position: f64,
+    speed: f64,
+    paused: bool,
+    seek_generation: u64,
+    /// Input length for clamping seeks, 0 if unknown
+    duration: f64,
+    live: bool,
+    /// Latest seek the decoder has carried out or given up on
+    seeks_done: u64,
+    seek_failure: Option<(u64, String)>,
+}
+
+impl PlaybackClock {
+    /// Start a running clock at `position` seconds
+    pub fn new(position: f64, speed: f64) -> Self {
+        Self {
+            anchor: Instant::now(),
+            anchor_position: position,
+            speed,
+            paused: false,
+            seek_generation: 0,
+            duration: 0.0,
+            live: false,
+            seeks_done: 0,
+            seek_failure: None,
+        }
+    }
+
+    /// Keep seeks within `duration` of the opened input; live input can't
+    /// seek at all
+    pub fn set_source(&mut self, duration: f64, live: bool) {
+        self.duration = duration;
+        self.live = live;
+    }
+
+    /// Whether the input is a live device that can't seek
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Current position in seconds
+    pub fn position(&self) -> f64 {
+        self.position_at(Instant::now())
+    }
+
+    fn position_at(&self, now: Instant) -> f64 {
+        if self.paused {
+            self.anchor_position
+        } else {
+            self.anchor_position + now.duration_since(self.anchor).as_secs_f64() * self.speed
+        }
+    }
+
+    /// Playback speed factor
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Whether the clock is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Freeze the clock at its current position
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.anchor_position = self.position();
+            self.paused = true;
+        }
+    }
+
+    /// Continue from the paused position
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.anchor = Instant::now();
+            self.paused = false;
+        }
+    }
+
+    /// Jump to `position`, clamped to the input; the decode loop notices
+    /// through [`Self::seek_generation`]
+    pub fn seek(&mut self, position: f64) {
+        self.rebase(clamp_target(position, self.duration));
+        self.seek_generation += 1;
+    }
+
+    /// Move the clock to `position` without asking the decoder to seek, used
+    /// when the decoder itself decides where playback is (start or loop)
+    pub fn rebase(&mut self, position: f64) {
+        self.anchor = Instant::now();
+        self.anchor_position = position.max(0.0);
+    }
+
+    /// Incremented on every [`Self::seek`]
+    pub fn seek_generation(&self) -> u64 {
+        self.seek_generation
+    }
+
+    /// Record that the decoder handled seek `generation`, with the reason if
+    /// it failed
+    pub fn finish_seek(&mut self, generation: u64, failure: Option<String>) {
+        self.seeks_done = self.seeks_done.max(generation);
+        if let Some(failure) = failure {
+            self.seek_failure = Some((generation, failure));
+        }
+    }
+
+    /// How seek `generation` went; `None` until the decoder gets to it
+    pub fn seek_outcome(&self, generation: u64) -> Option<Result<(), String>> {
+        if self.seeks_done < generation {
+            return None;
+        }
+        match &self.seek_failure {
+            Some((failed, reason)) if *failed == generation => Some(Err(reason.clone())),
+            _ => Some(Ok(())),
+        }
+    }
+}
+
+/// Caps applied to each telnet client; none by default
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct ClientLimits {
+    /// Connections beyond this many are turned away
+    pub max_clients: Option<usize>,
+    /// Per-client bandwidth ceiling
+    pub bytes_per_second: Option<u64>,
+    /// Disconnect clients that neither take a frame nor send anything for
+    /// this long
+    pub idle_timeout: Option<Duration>,
+}
+
+impl ClientLimits {
+    /// Turn away connections beyond `max_clients`
+    pub fn with_max_clients(mut self, max_clients: Option<usize>) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    /// Cap each client at `bytes_per_second`
+    pub fn with_bandwidth(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.bytes_per_second = bytes_per_second;
+        self
+    }
+
+    /// Disconnect clients idle for `idle_timeout`
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+}
+
+/// Spaces out frames so a client stays under its bandwidth ceiling.
+///
+/// A frame that would exceed the ceiling is held back, and since clients
+/// only ever see the newest frame, slow clients drop to a lower frame rate
+/// instead of slowing the shared pipeline.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    bytes_per_second: u64,
+    next_send: Instant,
+}
+
+impl BandwidthLimiter {
+    /// Create a limiter allowing `bytes_per_second`
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next_send: Instant::now(),
+        }
+    }
+
+    /// Earliest time the next frame may be sent
+    pub fn next_send(&self) -> Instant {
+        self.next_send
+    }
+
+    /// Account for `bytes` sent at `now`
+    pub fn record(&mut self, bytes: usize, now: Instant) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        self.next_send = self.next_send.max(now) + cost;
+    }
+}
+
+/// Shared secret required from telnet clients and admin connections
+#[derive(Clone)]
+pub struct AccessToken(Arc<str>);
+
+impl AccessToken {
+    /// Wrap a token; surrounding whitespace is ignored
+    pub fn new(token: &str) -> Result<Self> {
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(anyhow!("Auth token is empty"));
+        }
+        Ok(Self(token.into()))
+    }
+
+    /// Read a token from the first line of a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read auth token from {}", path.display()))?;
+        Self::new(contents.lines().next().unwrap_or(""))
+    }
+
+    /// Compare without exiting early, so response timing doesn't reveal
+    /// how much of a guess was right
+    pub fn matches(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.trim().as_bytes();
+        let mut difference = expected.len() ^ candidate.len();
+        for (index, &byte) in expected.iter().enumerate() {
+            difference |= (byte ^ candidate.get(index).copied().unwrap_or(0)) as usize;
+        }
+        difference == 0
+    }
+}
+
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AccessToken(..)")
+    }
+}
+
+/// Clock shared between the decode loop and the admin listener
+pub type SharedClock = Arc<Mutex<PlaybackClock>>;
+
+/// Latest encoded frame for each color depth.
+///
+/// Frames are converted once and only encoded for depths that have a client
+/// watching, so clients on different terminals share the decode and
+/// conversion work.
+pub struct FrameChannels {
+    senders: [watch::Sender<Arc<str>>; 3],
+    max_depth: ColorDepth,
+    latest: Mutex<Option<(AsciiFrame, String)>>,
+}
+
+impl FrameChannels {
+    /// Create channels; clients never get more color than `max_depth`
+    pub fn new(max_depth: ColorDepth) -> Self {
+        Self {
+            senders: std::array::from_fn(|_| watch::channel(Arc::<str>::from("")).0),
+            max_depth,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// The depth actually served to a client asking for `depth`
+    pub fn clamp(&self, depth: ColorDepth) -> ColorDepth {
+        depth.min(self.max_depth)
+    }
+
+    /// Publish a frame to every depth that has a client
+    pub fn publish(&self, frame: AsciiFrame, status: String) {
+        for depth in ColorDepth::ALL {
+            let sender = &self.senders[depth.index()];
+            if sender.receiver_count() > 0 {
+                sender.send_replace(encode_frame(&frame, depth, &status).into());
+            }
+        }
+        *self.latest.lock().unwrap() = Some((frame, status));
+    }
+
+    /// Receive frames at `depth`, starting with the current one
+    pub fn subscribe(&self, depth: ColorDepth) -> watch::Receiver<Arc<str>> {
+        let sender = &self.senders[self.clamp(depth).index()];
+        // Nobody may have been watching at this depth, so its frame is stale
+        if let Some((ref frame, ref status)) = *self.latest.lock().unwrap() {
+            sender.send_replace(encode_frame(frame, self.clamp(depth), status).into());
+        }
+        sender.subscribe()
+    }
+
+    /// The most recently published frame
+    pub fn latest_frame(&self) -> Option<AsciiFrame> {
+        self.latest
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(frame, _)| frame.clone())
+    }
+}
+
+/// A command accepted on the admin connection, one per line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdminCommand {
+    Pause,
+    Resume,
+    Toggle,
+    /// Seek to an absolute position in seconds
+    Seek(f64),
+    /// Seek relative to the current position
+    SeekBy(f64),
+    Status,
+}
+
+impl FromStr for AdminCommand {
+    type Err = anyhow::Error;
+
+    /// Parse `pause`, `resume`, `toggle`, `seek 90`, `seek +10`, `seek -10` or `status`
+    fn from_str(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("").to_ascii_lowercase();
+        let argument = words.next();
+
+        let parsed = match (command.as_str(), argument) {
+            ("pause", None) => AdminCommand::Pause,
+            ("resume" | "play", None) => AdminCommand::Resume,
+            ("toggle", None) => AdminCommand::Toggle,
+            ("status", None) => AdminCommand::Status,
+            ("seek", Some(value)) => {
+                let seconds: f64 = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid seek position '{}'", value))?;
+                if value.starts_with(['+', '-']) {
+                    AdminCommand::SeekBy(seconds)
+                } else {
+                    AdminCommand::Seek(seconds)
+                }
+            }
+            _ => return Err(anyhow!("Unknown command '{}'", line.trim())),
+        };
+
+        if words.next().is_some() {
+            return Err(anyhow!("Unexpected arguments in '{}'", line.trim()));
+        }
+        Ok(parsed)
+    }
+}
+
+impl AdminCommand {
+    /// Apply the command to the clock and describe the result
+    pub fn apply(&self, clock: &mut PlaybackClock) -> Result<String> {
+        if matches!(self, AdminCommand::Seek(_) | AdminCommand::SeekBy(_)) && clock.is_live() {
+            return Err(anyhow!("Live input, not seeking"));
+        }
+        match *self {
+            AdminCommand::Pause => clock.pause(),
+            AdminCommand::Resume => clock.resume(),
+            AdminCommand::Toggle if clock.is_paused() => clock.resume(),
+            AdminCommand::Toggle => clock.pause(),
+            AdminCommand::Seek(position) => clock.seek(position),
+            AdminCommand::SeekBy(offset) => clock.seek(clock.position() + offset),
+            AdminCommand::Status => {}
+        }
+
+        Ok(format!(
+            "position={:.2} paused={}",
+            clock.position(),
+            clock.is_paused()
+        ))
+    }
+}
+
+/// Serve the input video to telnet clients until it ends
+pub async fn run(cli: &Cli, config: &Config) -> Result<()> {
+    let address = cli
+        .serve
+        .as_deref()
+        .ok_or_else(|| anyhow!("No listen address given"))?;
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen on {}", address))?;
+    info!("Serving {} on {}", cli.input_path().display(), address);
+
+    let clock: SharedClock = Arc::new(Mutex::new(PlaybackClock::new(0.0, cli.speed)));
+    let clients = Arc::new(AtomicUsize::new(0));
+    let max_depth = if cli.use_color() {
+        ColorDepth::TrueColor
+    } else {
+        ColorDepth::Mono
+    };
+    let channels = Arc::new(FrameChannels::new(max_depth));
+
+    let limits = ClientLimits::default()
+        .with_max_clients(cli.max_clients)
+        .with_bandwidth(cli.client_bandwidth.map(|kilobytes| kilobytes * 1024))
+        .with_idle_timeout(cli.idle_timeout.map(Duration::from_secs));
+    let token = cli.access_token()?;
+    if token.is_none() {
+        warn!("Server has no auth token; anyone who can connect can watch");
+    }
+    tokio::spawn(accept_clients(
+        listener,
+        channels.clone(),
+        clients.clone(),
+        limits,
+        token.clone(),
+    ));
+
+    if let Some(ref admin_address) = cli.admin {
+        let admin = TcpListener::bind(admin_address)
+            .await
+            .with_context(|| format!("Failed to listen on {}", admin_address))?;
+        info!("Admin commands accepted on {}", admin_address);
+        tokio::spawn(accept_admin(admin, clock.clone(), clients.clone(), token));
+    }
+
+    // Remote terminals are sized up front; the server's own terminal is
+    // only used as a default
+    let (width, height) = cli.get_terminal_size().unwrap_or((80, 24));
+    let frame_height = height.saturating_sub(1).max(1);
+    let converter = cli.build_converter(config, &TermCaps::default())?;
+
+    let mut frame_iter = cli.open_video()?;
+    clock
+        .lock()
+        .unwrap()
+        .set_source(frame_iter.decoder().duration(), cli.is_live());
+    let frame_interval = 1.0 / frame_iter.decoder().fps().max(1.0);
+    let mut seek_generation = 0;
+    let mut rebase = true;
+    let mut supervisor = Supervisor::new();
+    let mut published_paused = false;
+
+    loop {
+        let (position, paused, generation) = {
+            let clock = clock.lock().unwrap();
+            (clock.position(), clock.is_paused(), clock.seek_generation())
+        };
+
+        if generation != seek_generation {
+            debug!("Seeking all clients to {:.2}s", position);
+            // A failed seek leaves playback where the decoder is
+            let failure = frame_iter.seek(position).err().map(|e| {
+                warn!("Seek to {:.2}s failed: {:#}", position, e);
+                rebase = true;
+                format!("{:#}", e)
+            });
+            clock.lock().unwrap().finish_seek(generation, failure);
+            seek_generation = generation;
+        }
+
+        if paused {
+            // Mark the held frame as paused once, then wait
+            if !published_paused {
+                if let Some(frame) = channels.latest_frame() {
+                    channels.publish(frame, server_status(position, true, &clients));
+                }
+                published_paused = true;
+            }
+            sleep(CLOCK_POLL).await;
+            continue;
+        }
+        published_paused = false;
+
+        let frame = match frame_iter.next() {
+            Some(frame) => frame?,
+            None if cli.loop_playback => {
+                info!("Video ended, restarting loop for all clients");
+                frame_iter = cli.open_video()?;
+                rebase = true;
+                continue;
+            }
+            None => break,
+        };
+
+        // The first frame after opening defines where the clock stands
+        if rebase {
+            clock.lock().unwrap().rebase(frame.timestamp);
+            rebase = false;
+        } else if frame.timestamp + frame_interval < position {
+            // Behind the shared clock: drop frames until caught up
+            continue;
+        }
+
+        if !wait_until(&clock, frame.timestamp, seek_generation).await {
+            continue;
+        }
+
+        // A frame that panics the converter is skipped for every client
+        let Some(converted) = supervisor.run("converting", || {
+            converter.convert(&frame, width, frame_height)
+        }) else {
+            continue;
+        };
+        channels.publish(converted?, server_status(frame.timestamp, false, &clients));
+    }
+
+    info!("Playback finished, closing server");
+    Ok(())
+}
+
+/// Sleep until the clock reaches `timestamp`. Returns false if a seek or pause
+/// happened meanwhile and the frame should not be shown.
+async fn wait_until(clock: &SharedClock, timestamp: f64, seek_generation: u64) -> bool {
+    loop {
+        let (position, speed, interrupted) = {
+            let clock = clock.lock().unwrap();
+            (
+                clock.position(),
+                clock.speed(),
+                clock.is_paused() || clock.seek_generation() != seek_generation,
+            )
+        };
+        if interrupted {
+            return false;
+        }
+        if position >= timestamp {
+            return true;
+        }
+
+        let wait = Duration::from_secs_f64((timestamp - position) / speed.max(0.01));
+        sleep(wait.min(CLOCK_POLL)).await;
+    }
+}
+
+fn server_status(position: f64, paused: bool, clients: &AtomicUsize) -> String {
+    format!(
+        " {} {:.1}s | {} viewer(s)",
+        if paused { "PAUSED" } else { "LIVE" },
+        position,
+        clients.load(Ordering::Relaxed)
+    )
+}
+
+/// One place in a connection count, given back when dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    /// Take a place unless `limit` are already taken
+    fn take(count: &Arc<AtomicUsize>, limit: Option<usize>) -> Option<Self> {
+        count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |taken| match limit {
+                Some(limit) if taken >= limit => None,
+                _ => Some(taken + 1),
+            })
+            .ok()
+            .map(|_| Self(count.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+async fn accept_clients(
+    listener: TcpListener,
+    channels: Arc<FrameChannels>,
+    clients: Arc<AtomicUsize>,
+    limits: ClientLimits,
+    token: Option<AccessToken>,
+) {
+    let pending = Arc::new(AtomicUsize::new(0));
+    loop {
+        match listener.accept().await {
+            Ok((mut stream, peer)) => {
+                let full = limits
+                    .max_clients
+                    .is_some_and(|max| clients.load(Ordering::Relaxed) >= max);
+                let slot = if full {
+                    None
+                } else {
+                    Slot::take(&pending, Some(MAX_PENDING_CLIENTS))
+                };
+                let Some(slot) = slot else {
+                    info!("Turning away client {}: server full", peer);
+                    tokio::spawn(async move {
+                        let _ = stream.write_all(SERVER_FULL.as_bytes()).await;
+                    });
+                    continue;
+                };
+                debug!("Client {} connected", peer);
+
+                let channels = channels.clone();
+                let clients = clients.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = stream_to_client(
+                        stream,
+                        peer,
+                        &channels,
+                        slot,
+                        &clients,
+                        limits,
+                        token.as_ref(),
+                    )
+                    .await
+                    {
+                        debug!("Client {} error: {}", peer, e);
+                    }
+                    info!(
+                        "Client {} disconnected ({} watching)",
+                        peer,
+                        clients.load(Ordering::Relaxed)
+                    );
+                });
+            }
+            Err(e) => warn!("Failed to accept client: {}", e),
+        }
+    }
+}
+
+/// Send the live frame and every later one until the client leaves or presses q.
+///
+/// The client holds its pending slot until it authenticates, and only then
+/// takes a place among the `--max-clients` viewers.
+async fn stream_to_client(
+    stream: TcpStream,
+    peer: SocketAddr,
+    channels: &FrameChannels,
+    pending: Slot,
+    clients: &Arc<AtomicUsize>,
+    limits: ClientLimits,
+    token: Option<&AccessToken>,
+) -> Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    writer.write_all(NEGOTIATION).await?;
+
+    let mut telnet = TelnetInput::new();
+    let mut input = [0u8; 256];
+
+    // Give the client a moment to report its terminal; clients that don't
+    // speak telnet (nc, scripts) get plain characters
+    let mut depth = ColorDepth::Mono;
+    let negotiation = async {
+        loop {
+            let read = reader.read(&mut input).await?;
+            if read == 0 {
+                return Ok::<_, anyhow::Error>(None);
+            }
+            for event in telnet.feed(&input[..read]) {
+                match event {
+                    TelnetEvent::WillTerminalType => {
+                        writer.write_all(REQUEST_TERMINAL_TYPE).await?;
+                    }
+                    TelnetEvent::TerminalType(name) => return Ok(Some(name)),
+                    TelnetEvent::Data(_) => {}
+                }
+            }
+        }
+    };
+    match timeout(NEGOTIATION_TIMEOUT, negotiation).await {
+        Ok(Ok(Some(terminal))) => {
+            depth = channels.clamp(ColorDepth::from_terminal_type(&terminal));
+            debug!("Client terminal {} -> {:?}", terminal, depth);
+        }
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => debug!("Client did not report a terminal type"),
+    }
+
+    if let Some(token) = token {
+        writer.write_all(b"Token: ").await?;
+        let entered = match timeout(
+            AUTH_TIMEOUT,
+            read_telnet_line(&mut reader, &mut telnet, &mut input),
+        )
+        .await
+        {
+            Ok(entered) => entered?,
+            Err(_) => None,
+        };
+
+        if !entered.is_some_and(|entered| token.matches(&entered)) {
+            warn!("Client {} failed authentication", peer);
+            writer.write_all(b"\r\nAccess denied\r\n").await?;
+            return Ok(());
+        }
+        debug!("Client {} authenticated", peer);
+    }
+
+    drop(pending);
+    let Some(_viewer) = Slot::take(clients, limits.max_clients) else {
+        info!("Turning away client {}: server full", peer);
+        writer.write_all(SERVER_FULL.as_bytes()).await?;
+        return Ok(());
+    };
+    info!(
+        "Client {} joined ({} watching)",
+        peer,
+        clients.load(Ordering::Relaxed)
+    );
+
+    let mut limiter = limits.bytes_per_second.map(BandwidthLimiter::new);
+    // Viewers only watching are active as long as frames reach them
+    let mut last_activity = Instant::now();
+
+    write_with_timeout(&mut writer, SCREEN_SETUP.as_bytes(), limits.idle_timeout).await?;
+    // Late joiners start with the frame everyone else is seeing
+    let mut frames = channels.subscribe(depth);
+    // When the latest frame goes out, held back by the bandwidth ceiling;
+    // None while the client has it
+    let mut send_at = Some(Instant::now());
+    let next_send = |limiter: &Option<BandwidthLimiter>| {
+        limiter
+            .as_ref()
+            .map_or_else(Instant::now, BandwidthLimiter::next_send)
+    };
+
+    loop {
+        let send = async {
+            match send_at {
+                Some(send_at) => sleep_until(send_at.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+        let idle = async {
+            match limits.idle_timeout {
+                Some(idle_timeout) => sleep_until((last_activity + idle_timeout).into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = send => {
+                let frame = frames.borrow_and_update().clone();
+                write_with_timeout(&mut writer, frame.as_bytes(), limits.idle_timeout).await?;
+                last_activity = Instant::now();
+                if let Some(ref mut limiter) = limiter {
+                    limiter.record(frame.len(), last_activity);
+                }
+                send_at = None;
+            }
+            changed = frames.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                send_at.get_or_insert_with(|| next_send(&limiter));
+            }
+            _ = idle => {
+                debug!("Disconnecting idle client");
+                break;
+            }
+            read = reader.read(&mut input) => {
+                let read = read?;
+                if read == 0 {
+                    break;
+                }
+                last_activity = Instant::now();
+
+                let mut quit = false;
+                for event in telnet.feed(&input[..read]) {
+                    match event {
+                        TelnetEvent::Data(b'q' | 3) => quit = true,
+                        // Let users override the detected depth
+                        TelnetEvent::Data(key) => {
+                            if let Some(requested) = ColorDepth::from_key(key) {
+                                depth = channels.clamp(requested);
+                                frames = channels.subscribe(depth);
+                                writer.write_all(SCREEN_SETUP.as_bytes()).await?;
+                                send_at.get_or_insert_with(|| next_send(&limiter));
+                            }
+                        }
+                        TelnetEvent::TerminalType(terminal) => {
+                            depth = channels.clamp(ColorDepth::from_terminal_type(&terminal));
+                            frames = channels.subscribe(depth);
+                            send_at.get_or_insert_with(|| next_send(&limiter));
+                        }
+                        TelnetEvent::WillTerminalType => {}
+                    }
+                }
+                if quit {
+                    break;
+                }
+            }
+        }
+    }
+
+    writer.write_all(SCREEN_RESET.as_bytes()).await?;
+    Ok(())
+}
+
+/// Read typed input up to Enter; `None` if the client hangs up first
+async fn read_telnet_line(
+    reader: &mut tokio::net::tcp::OwnedReadHalf,
+    telnet: &mut TelnetInput,
+    input: &mut [u8],
+) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    loop {
+        let read = reader.read(input).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        for event in telnet.feed(&input[..read]) {
+            match event {
+                TelnetEvent::Data(b'\r' | b'\n') => {
+                    return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                }
+                TelnetEvent::Data(byte) if line.len() < MAX_TOKEN_LENGTH => line.push(byte),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Write to a client, giving up if it accepts nothing for `limit`
+async fn write_with_timeout(
+    writer: &mut tokio::net::tcp::OwnedWriteHalf,
+    bytes: &[u8],
+    limit: Option<Duration>,
+) -> Result<()> {
+    match limit {
+        Some(limit) => timeout(limit, writer.write_all(bytes))
+            .await
+            .map_err(|_| anyhow!("Client stopped reading"))??,
+        None => writer.write_all(bytes).await?,
+    }
+    Ok(())
+}
+
+async fn accept_admin(
+    listener: TcpListener,
+    clock: SharedClock,
+    clients: Arc<AtomicUsize>,
+    token: Option<AccessToken>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("Admin connection from {}", peer);
+                let clock = clock.clone();
+                let clients = clients.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_admin(stream, peer, clock, clients, token).await {
+                        debug!("Admin connection {} error: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept admin connection: {}", e),
+        }
+    }
+}
+
+/// Run admin commands from one connection. With a token, the first line
+/// must be `auth <token>`.
+async fn handle_admin(
+    stream: TcpStream,
+    peer: SocketAddr,
+    clock: SharedClock,
+    clients: Arc<AtomicUsize>,
+    token: Option<AccessToken>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut authenticated = token.is_none();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !authenticated {
+            let candidate = line.trim().strip_prefix("auth ").unwrap_or("");
+            if token.as_ref().is_some_and(|token| token.matches(candidate)) {
+                authenticated = true;
+                writer.write_all(b"ok authenticated\n").await?;
+                continue;
+            }
+            warn!("Admin connection {} failed authentication", peer);
+            writer.write_all(b"error authentication required\n").await?;
+            return Ok(());
+        }
+
+        let reply = match run_admin_command(&line, &clock).await {
+            Ok(reply) => format!("ok {} clients={}\n", reply, clients.load(Ordering::Relaxed)),
+            Err(e) => {
+                warn!("Admin: {} failed: {}", line.trim(), e);
+                format!("error {}\n", e)
+            }
+        };
+        writer.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Apply one admin line; seeks wait briefly for the decoder so a failed
+/// one is reported back
+async fn run_admin_command(line: &str, clock: &SharedClock) -> Result<String> {
+    let command = line.parse::<AdminCommand>()?;
+    let (reply, generation) = {
+        let mut clock = clock.lock().unwrap();
+        (command.apply(&mut clock)?, clock.seek_generation())
+    };
+    if command == AdminCommand::Status {
+        return Ok(reply);
+    }
+    info!("Admin: {} -> {}", line.trim(), reply);
+
+    if matches!(command, AdminCommand::Seek(_) | AdminCommand::SeekBy(_)) {
+        let waited = timeout(SEEK_REPLY_TIMEOUT, async {
+            loop {
+                if let Some(outcome) = clock.lock().unwrap().seek_outcome(generation) {
+                    return outcome;
+                }
+                sleep(CLOCK_POLL).await;
+            }
+        })
+        .await;
+        if let Ok(Err(reason)) = waited {
+            return Err(anyhow!("Seek failed: {}", reason));
+        }
+    }
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_pause_and_seek() {
+        let mut clock = PlaybackClock::new(10.0, 2.0);
+        let later = clock.anchor + Duration::from_secs(3);
+        assert_eq!(clock.position_at(later), 16.0);
+
+        clock.pause();
+        let paused_at = clock.position();
+        assert!(clock.is_paused());
+        assert_eq!(
+            clock.position_at(later + Duration::from_secs(60)),
+            paused_at
+        );
+
+        clock.seek(42.0);
+        assert_eq!(clock.seek_generation(), 1);
+        assert_eq!(clock.position(), 42.0);
+
+        // Rebasing moves the clock without requesting a decoder seek
+        clock.rebase(-1.0);
+        assert_eq!(clock.seek_generation(), 1);
+        assert_eq!(clock.position(), 0.0);
+    }
+
+    #[test]
+    fn test_slots_stop_at_limit_and_free_on_drop() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let first = Slot::take(&count, Some(2)).unwrap();
+        let _second = Slot::take(&count, Some(2)).unwrap();
+        assert!(Slot::take(&count, Some(2)).is_none());
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+
+        drop(first);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+        assert!(Slot::take(&count, Some(2)).is_some());
+        assert!(Slot::take(&count, None).is_some());
+    }
+
+    #[test]
+    fn test_bandwidth_limiter_spaces_frames() {
+        let mut limiter = BandwidthLimiter::new(1000);
+        let start = limiter.next_send();
+        limiter.record(500, start);
+        assert_eq!(limiter.next_send(), start + Duration::from_millis(500));
+
+        // Sending late doesn't bank unused bandwidth
+        let late = start + Duration::from_secs(5);
+        limiter.record(250, late);
+        assert_eq!(limiter.next_send(), late + Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_access_token_matches() {
+        let token = AccessToken::new(" s3cret\n").unwrap();
+        assert!(token.matches("s3cret"));
+        assert!(token.matches("s3cret\r"));
+        assert!(!token.matches("s3cre"));
+        assert!(!token.matches("s3cret!"));
+        assert!(!token.matches(""));
+        assert!(AccessToken::new("  ").is_err());
+        assert_eq!(format!("{:?}", token), "AccessToken(..)");
+    }
+
+    #[test]
+    fn test_parse_admin_commands() {
+        assert_eq!(
+            "pause".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Pause
+        );
+        assert_eq!(
+            "PLAY".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Resume
+        );
+        assert_eq!(
+            "seek 90.5".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Seek(90.5)
+        );
+        assert_eq!(
+            "seek -10".parse::<AdminCommand>().unwrap(),
+            AdminCommand::SeekBy(-10.0)
+        );
+        assert!("seek".parse::<AdminCommand>().is_err());
+        assert!("seek soon".parse::<AdminCommand>().is_err());
+        assert!("pause now".parse::<AdminCommand>().is_err());
+        assert!("rewind".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn test_admin_toggle_and_relative_seek() {
+        let mut clock = PlaybackClock::new(30.0, 1.0);
+        AdminCommand::Toggle.apply(&mut clock).unwrap();
+        assert!(clock.is_paused());
+
+        let reply = AdminCommand::SeekBy(-45.0).apply(&mut clock).unwrap();
+        assert_eq!(reply, "position=0.00 paused=true");
+        assert_eq!(clock.seek_generation(), 1);
+    }
+
+    #[test]
+    fn test_admin_seek_is_clamped_and_refused_on_live_input() {
+        let mut clock = PlaybackClock::new(0.0, 1.0);
+        clock.set_source(60.0, false);
+        clock.pause();
+        let reply = AdminCommand::Seek(500.0).apply(&mut clock).unwrap();
+        assert_eq!(reply, "position=60.00 paused=true");
+
+        // Failures reach whoever asked for that seek, and only them
+        assert_eq!(clock.seek_outcome(1), None);
+        clock.finish_seek(1, Some("corrupt index".to_string()));
+        assert_eq!(
+            clock.seek_outcome(1),
+            Some(Err("corrupt index".to_string()))
+        );
+        AdminCommand::Seek(5.0).apply(&mut clock).unwrap();
+        clock.finish_seek(2, None);
+        assert_eq!(clock.seek_outcome(2), Some(Ok(())));
+
+        clock.set_source(0.0, true);
+        assert!(AdminCommand::SeekBy(10.0).apply(&mut clock).is_err());
+        assert_eq!(clock.seek_generation(), 2);
+        assert!(AdminCommand::Pause.apply(&mut clock).is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+//! Uploading exported recordings for sharing
+//!
+//! `--export clip.cast --share` uploads the finished cast to asciinema.org
+//! and prints the address it can be watched at; `--share=URL` (or
+//! `share_url` in the config file) sends it to another server speaking the
+//! same upload API, such as a self-hosted asciinema server, which may also
+//! take .ascv recordings.
+//!
+//! The upload is a multipart POST of the file as the `asciicast` field,
+//! made with `curl`, so no TLS stack is built into the player. When
+//! asciinema has been set up on this machine, its install ID is sent as
+//! the password, so the recording can be claimed from the asciinema
+//! account linked to it. The ID only ever goes to asciinema.org over
+//! HTTPS, and reaches curl on its standard input rather than its command
+//! line, where other users could read it.
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Upload endpoint of asciinema.org
+pub const ASCIINEMA_UPLOAD_URL: &str = "https://asciinema.org/api/asciicasts";
+
+/// Check before exporting that `path` can be shared at `endpoint`:
+/// asciinema.org only takes casts, and curl needs a UTF-8 file name
+pub fn check_shareable(path: &Path, endpoint: &str) -> Result<()> {
+    if path.to_str().is_none() {
+        bail!("--share needs a UTF-8 file name, not {}", path.display());
+    }
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("cast") => Ok(()),
+        Some("ascv") if endpoint != ASCIINEMA_UPLOAD_URL => Ok(()),
+        Some("ascv") => bail!("asciinema.org only takes .cast recordings; export to a .cast file"),
+        _ => bail!("--share uploads .cast and .ascv recordings only"),
+    }
+}
+
+/// Upload `path` to `endpoint` and return the address of the shared recording
+pub fn upload(path: &Path, endpoint: &str) -> Result<String> {
+    let credentials = install_id()
+        .filter(|_| is_asciinema_org(endpoint))
+        .map(|id| (username(), id));
+    debug!("Uploading {} to {}", path.display(), endpoint);
+    let mut child = Command::new("curl")
+        .args(curl_args(path, endpoint, credentials.as_ref()))
+        .stdin(if credentials.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run curl for the upload: {}", e))?;
+    if let (Some(credentials), Some(mut stdin)) = (credentials.as_ref(), child.stdin.take()) {
+        stdin
+            .write_all(curl_config(credentials).as_bytes())
+            .context("Failed to pass the credentials to curl")?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| anyhow!("Failed to run curl for the upload: {}", e))?;
+
+    let body = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "Uploading {} to {} failed: {}",
+            path.display(),
+            endpoint,
+            stderr.trim()
+        );
+    }
+    response_url(&body).ok_or_else(|| anyhow!("The server answered without a URL: {}", body.trim()))
+}
+
+/// Arguments for a curl upload of `path`. With `credentials` (user name
+/// and install ID) curl reads them from [`curl_config`] on its stdin.
+fn curl_args(path: &Path, endpoint: &str, credentials: Option<&(String, String)>) -> Vec<OsString> {
+    // Quoted, so `;` and `,` in the name aren't read as field options
+    let field = format!(
+        "asciicast=@\"{}\"",
+        path.to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    );
+    let mut args: Vec<OsString> = ["--silent", "--show-error", "--fail", "-X", "POST"]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+    args.extend(["-H", "Accept: application/json", "-F"].map(OsString::from));
+    args.push(field.into());
+    if credentials.is_some() {
+        args.extend(["-K", "-"].map(OsString::from));
+    }
+    args.push(endpoint.into());
+    args
+}
+
+/// curl config setting `credentials` as the user to authenticate as
+fn curl_config((user, id): &(String, String)) -> String {
+    let value = format!("{}:{}", user, id)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    format!("user = \"{}\"\n", value)
+}
+
+/// Whether `endpoint` is asciinema.org over HTTPS, the only server the
+/// install ID is sent to
+fn is_asciinema_org(endpoint: &str) -> bool {
+    let Some(rest) = endpoint
+        .get(..8)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("https://"))
+        .map(|_| &endpoint[8..])
+    else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    // A user part could make asciinema.org look like the host
+    if authority.contains('@') {
+        return false;
+    }
+    let host = authority.split(':').next().unwrap_or_default();
+    host.eq_ignore_ascii_case("asciinema.org")
+}
+
+/// The address of the recording in the server's answer: the `url` of a
+/// JSON reply, or else the first URL in plain text
+fn response_url(body: &str) -> Option<String> {
+    if let Ok(reply) = serde_json::from_str::<serde_json::Value>(body) {
+        if let Some(url) = reply.get("url").and_then(|url| url.as_str()) {
+            return Some(url.to_string());
+        }
+    }
+    body.split_whitespace()
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(str::to_string)
+}
+
+/// Install ID saved by the asciinema client, if it has been set up
+fn install_id() -> Option<String> {
+    let path = asciinema_config_dir()?.join("install-id");
+    let id = std::fs::read_to_string(path).ok()?;
+    let id = id.trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// The asciinema client's config directory
+fn asciinema_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("ASCIINEMA_CONFIG_HOME") {
+        return Some(PathBuf::from(dir));
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("asciinema"))
+}
+
+/// User name sent with the install ID
+fn username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "ascii-player".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_url() {
+        assert_eq!(
+            response_url(r#"{"url":"https://asciinema.org/a/abc123","message":"View it"}"#),
+            Some("https://asciinema.org/a/abc123".to_string())
+        );
+        assert_eq!(
+            response_url("View the recording at:\n\n    https://example.com/a/42\n"),
+            Some("https://example.com/a/42".to_string())
+        );
+        assert_eq!(response_url("Unauthorized"), None);
+    }
+
+    #[test]
+    fn test_check_shareable() {
+        assert!(check_shareable(Path::new("clip.cast"), ASCIINEMA_UPLOAD_URL).is_ok());
+        assert!(check_shareable(Path::new("clip.ascv"), ASCIINEMA_UPLOAD_URL).is_err());
+        assert!(check_shareable(Path::new("clip.ascv"), "https://casts.example.com/api").is_ok());
+        assert!(check_shareable(Path::new("clip.gif"), ASCIINEMA_UPLOAD_URL).is_err());
+    }
+
+    #[test]
+    fn test_curl_args() {
+        let credentials = ("alice".to_string(), "1234".to_string());
+        let args = curl_args(
+            Path::new("clip.cast"),
+            ASCIINEMA_UPLOAD_URL,
+            Some(&credentials),
+        );
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-F", "asciicast=@\"clip.cast\""]));
+        // The install ID goes on stdin, never on the command line
+        assert!(args.windows(2).any(|pair| pair == ["-K", "-"]));
+        assert!(!args.iter().any(|arg| arg.contains("1234")));
+        assert_eq!(args.last().map(String::as_str), Some(ASCIINEMA_UPLOAD_URL));
+        assert_eq!(curl_config(&credentials), "user = \"alice:1234\"\n");
+        let quoted = ("a\"b".to_string(), "c\\d".to_string());
+        assert_eq!(curl_config(&quoted), "user = \"a\\\"b:c\\\\d\"\n");
+
+        let args = curl_args(Path::new("clip.cast"), "http://localhost:4000", None);
+        assert!(!args.iter().any(|arg| arg == "-K"));
+
+        let args = curl_args(Path::new("a;type=x,\"b\".cast"), ASCIINEMA_UPLOAD_URL, None);
+        assert!(args
+            .iter()
+            .any(|arg| arg == r#"asciicast=@"a;type=x,\"b\".cast""#));
+    }
+
+    #[test]
+    fn test_install_id_only_goes_to_asciinema_org() {
+        assert!(is_asciinema_org(ASCIINEMA_UPLOAD_URL));
+        assert!(is_asciinema_org("HTTPS://asciinema.org:443/api/asciicasts"));
+        assert!(!is_asciinema_org("http://asciinema.org/api/asciicasts"));
+        assert!(!is_asciinema_org("https://casts.example.com/api"));
+        assert!(!is_asciinema_org("https://asciinema.org.example.com/api"));
+        assert!(!is_asciinema_org("https://asciinema.org@example.com/api"));
+    }
+}
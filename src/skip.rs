@@ -0,0 +1,157 @@
+//! Skipping dead time
+//!
+//! `--skip-black` fast-forwards through long runs of black frames: the
+//! frames are still decoded, but neither shown nor waited for. `--skip-silence`
+//! jumps over long silences found by the audio loudness pass also used by
+//! `--waveform`. Short runs, such as fades and pauses between sentences,
+//! play normally.
+
+use crate::decoder::VideoFrame;
+
+/// Pixels darker than this (luma 0.0-1.0) count as black, as in FFmpeg's
+/// `blackdetect`
+const BLACK_PIXEL: f64 = 0.10;
+
+/// Share of black pixels that makes a frame black
+const BLACK_RATIO: f64 = 0.98;
+
+/// Black runs are skipped once they have lasted this long, in seconds
+const MIN_BLACK_RUN: f64 = 1.0;
+
+/// Seconds quieter than this RMS level (about -50 dBFS) are silent
+const SILENCE_LEVEL: f32 = 0.003;
+
+/// Only silences at least this many seconds long are skipped
+const MIN_SILENCE: usize = 3;
+
+/// Pixels sampled per frame when looking for black frames
+const BLACK_SAMPLES: usize = 4096;
+
+/// Whether nearly every pixel of `frame` is black
+pub fn is_black(frame: &VideoFrame) -> bool {
+    let pixels = frame.data.len() / 3;
+    if pixels == 0 {
+        return false;
+    }
+    let step = (pixels / BLACK_SAMPLES).max(1);
+    let mut sampled = 0;
+    let mut black = 0;
+    for pixel in frame.data.chunks_exact(3).step_by(step) {
+        let luma =
+            (0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64) / 255.0;
+        sampled += 1;
+        if luma < BLACK_PIXEL {
+            black += 1;
+        }
+    }
+    black as f64 >= sampled as f64 * BLACK_RATIO
+}
+
+/// Tracks runs of black frames
+#[derive(Debug, Default)]
+pub struct BlackSkipper {
+    run_start: Option<f64>,
+    skipped: f64,
+}
+
+impl BlackSkipper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note whether the frame at `timestamp` is black; true if it should be
+    /// skipped because the run has gone on too long
+    pub fn skip(&mut self, timestamp: f64, black: bool) -> bool {
+        if !black {
+            self.run_start = None;
+            return false;
+        }
+        let start = *self.run_start.get_or_insert(timestamp);
+        timestamp - start >= MIN_BLACK_RUN
+    }
+
+    /// Seconds skipped in the run that just ended, if one was skipped
+    pub fn finish_run(&mut self, timestamp: f64) -> Option<f64> {
+        let start = self.run_start?;
+        (timestamp - start > MIN_BLACK_RUN).then(|| {
+            let skipped = timestamp - start - MIN_BLACK_RUN;
+            self.skipped += skipped;
+            skipped
+        })
+    }
+
+    /// Seconds skipped so far
+    pub fn skipped(&self) -> f64 {
+        self.skipped
+    }
+}
+
+/// Where the silence around `position` ends, if it is long enough to skip.
+/// `levels` has the RMS level of each second.
+pub fn silence_end(levels: &[f32], position: f64) -> Option<f64> {
+    let silent = |level: &f32| *level < SILENCE_LEVEL;
+    let second = position.max(0.0) as usize;
+    if !levels.get(second).is_some_and(silent) {
+        return None;
+    }
+
+    let start = levels[..second]
+        .iter()
+        .rposition(|level| !silent(level))
+        .map_or(0, |loud| loud + 1);
+    let end = levels[second..]
+        .iter()
+        .position(|level| !silent(level))
+        .map_or(levels.len(), |loud| second + loud);
+    // Half a second of slack keeps a jump from being repeated
+    (end - start >= MIN_SILENCE && end as f64 - position > 0.5).then_some(end as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(value: u8) -> VideoFrame {
+        VideoFrame {
+            data: vec![value; 64 * 36 * 3],
+            width: 64,
+            height: 36,
+            timestamp: 0.0,
+            frame_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_skip_black() {
+        assert!(is_black(&frame(8)));
+        assert!(!is_black(&frame(40)));
+
+        let mut skipper = BlackSkipper::new();
+        // The first second of a run plays, the rest is skipped
+        assert!(!skipper.skip(10.0, true));
+        assert!(!skipper.skip(10.5, true));
+        assert!(skipper.skip(11.0, true));
+        assert!(skipper.skip(14.0, true));
+        assert_eq!(skipper.finish_run(14.5), Some(3.5));
+        assert!(!skipper.skip(14.5, false));
+        assert_eq!(skipper.finish_run(15.0), None);
+
+        // A short fade is not a skipped run
+        assert!(!skipper.skip(20.0, true));
+        assert_eq!(skipper.finish_run(20.5), None);
+        assert_eq!(skipper.skipped(), 3.5);
+    }
+
+    #[test]
+    fn test_skip_silence() {
+        let levels = [0.2, 0.0, 0.001, 0.0, 0.0, 0.3, 0.0, 0.0, 0.1];
+        assert_eq!(silence_end(&levels, 1.2), Some(5.0));
+        assert_eq!(silence_end(&levels, 4.2), Some(5.0));
+        // Too close to the end of the silence to bother
+        assert_eq!(silence_end(&levels, 4.6), None);
+        assert_eq!(silence_end(&levels, 0.5), None);
+        // Two seconds is a pause, not dead time
+        assert_eq!(silence_end(&levels, 6.0), None);
+        assert_eq!(silence_end(&[0.0; 4], 0.0), Some(4.0));
+    }
+}
@@ -0,0 +1,410 @@
+//! Line commands for frontends that spawn the player
+//!
+//! With `--slave-mode`, playback also takes newline-delimited commands on
+//! stdin: the admin commands of `--serve` (`pause`, `resume`, `toggle`,
+//! `seek 12.5`, `seek +10`, `status`) plus `quit` and `screenshot [FILE]`.
+//! Each command is answered on stderr with one `ok ...` or `error ...` line,
+//! as on the admin port. Keys still work, read from the terminal, so stdin
+//! should be a pipe rather than the terminal itself.
+//!
+//! `--nvim` takes the same commands on a named pipe instead, since a Neovim
+//! terminal buffer owns the player's stdin, and answers on a second pipe,
+//! since its stderr is the terminal the video is drawn on. The paths are
+//! printed as the first lines of output, `ascii-player-fifo=PATH` and
+//! `ascii-player-replies=PATH`, for the Lua plugin in `contrib/nvim` to pick
+//! up. Replies sent while nobody reads the reply pipe are dropped.
+
+use crate::converter::AsciiFrame;
+use crate::screenshot::{save_screenshot_files, screenshot_stem};
+use crate::server::AdminCommand;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+#[cfg(unix)]
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// A command accepted on stdin in slave mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlaveCommand {
+    /// Same as on the admin connection
+    Admin(AdminCommand),
+    Quit,
+    /// Save the frame on screen as text, to the given file or a numbered one
+    Screenshot(Option<PathBuf>),
+}
+
+impl FromStr for SlaveCommand {
+    type Err = anyhow::Error;
+
+    /// Parse `quit`, `screenshot`, `screenshot FILE` or an admin command
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match (command.to_ascii_lowercase().as_str(), argument.trim()) {
+            ("quit" | "exit", "") => Ok(SlaveCommand::Quit),
+            ("screenshot", "") => Ok(SlaveCommand::Screenshot(None)),
+            ("screenshot", path) => Ok(SlaveCommand::Screenshot(Some(PathBuf::from(path)))),
+            _ => line.parse().map(SlaveCommand::Admin),
+        }
+    }
+}
+
+/// First output line in `--nvim` mode, followed by the control FIFO path
+pub const FIFO_ANNOUNCEMENT: &str = "ascii-player-fifo=";
+/// Second output line in `--nvim` mode, followed by the reply FIFO path
+pub const REPLY_ANNOUNCEMENT: &str = "ascii-player-replies=";
+
+/// Commands arriving on stdin or a FIFO, read on a background thread
+pub struct CommandReader {
+    receiver: UnboundedReceiver<String>,
+    closed: bool,
+}
+
+impl CommandReader {
+    /// Start reading stdin
+    pub fn spawn() -> Self {
+        Self::from_reader(BufReader::new(std::io::stdin()))
+    }
+
+    /// Read commands from any line source
+    pub fn from_reader<R: BufRead + Send + 'static>(reader: R) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        thread::spawn(move || forward_lines(reader, &sender));
+        Self {
+            receiver,
+            closed: false,
+        }
+    }
+
+    /// Read commands from a FIFO, opening it again whenever a writer closes
+    /// it, so each command can come from a separate write
+    pub fn from_fifo(path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        thread::spawn(move || {
+            // Opening blocks until a writer connects
+            while let Ok(file) = File::open(&path) {
+                if !forward_lines(BufReader::new(file), &sender) {
+                    break;
+                }
+            }
+        });
+        Self {
+            receiver,
+            closed: false,
+        }
+    }
+
+    /// Next command, if one has arrived. Unparseable lines are answered with
+    /// an error here and skipped.
+    pub fn try_next(&mut self) -> Option<SlaveCommand> {
+        while !self.closed {
+            match self.receiver.try_recv() {
+                Ok(line) => {
+                    if let Some(command) = parse_line(&line) {
+                        return Some(command);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.close(),
+            }
+        }
+        None
+    }
+
+    /// Wait for the next command; None once the source is closed
+    pub async fn next(&mut self) -> Option<SlaveCommand> {
+        while !self.closed {
+            match self.receiver.recv().await {
+                Some(line) => {
+                    if let Some(command) = parse_line(&line) {
+                        return Some(command);
+                    }
+                }
+                None => self.close(),
+            }
+        }
+        None
+    }
+
+    fn close(&mut self) {
+        // The frontend closing stdin doesn't stop playback
+        debug!("Stdin closed, no more slave commands");
+        self.closed = true;
+    }
+}
+
+/// The command on a line, answering unparseable lines with an error
+fn parse_line(line: &str) -> Option<SlaveCommand> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    line.parse().map_err(|e| reply_error(&e)).ok()
+}
+
+/// Send each line on, returning false once nobody is listening
+fn forward_lines<R: BufRead>(reader: R, sender: &UnboundedSender<String>) -> bool {
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        if sender.send(line).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// A named pipe in the temp directory, removed when dropped
+#[cfg(unix)]
+pub struct ControlFifo {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl ControlFifo {
+    /// Create `ascii-player-PID-NAME.fifo`
+    pub fn create(name: &str) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path =
+            std::env::temp_dir().join(format!("ascii-player-{}-{}.fifo", std::process::id(), name));
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to create FIFO {}", path.display()));
+        }
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlFifo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The reply FIFO replies go to instead of stderr, once set
+#[cfg(unix)]
+static REPLY_FIFO: Mutex<Option<ReplyFifo>> = Mutex::new(None);
+
+/// The write end of a reply FIFO, opened when the first reply finds a
+/// reader and kept open so the reader doesn't see the end of its input
+#[cfg(unix)]
+struct ReplyFifo {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+#[cfg(unix)]
+impl ReplyFifo {
+    /// Write a reply line without ever blocking playback
+    fn send(&mut self, line: &str) {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if self.file.is_none() {
+            // Fails at once rather than waiting while nobody reads
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path)
+            {
+                Ok(file) => self.file = Some(file),
+                Err(e) => {
+                    debug!("Dropping reply '{}': {}", line, e);
+                    return;
+                }
+            }
+        }
+        if let Some(ref mut file) = self.file {
+            if let Err(e) = writeln!(file, "{}", line) {
+                debug!("Dropping reply '{}': {}", line, e);
+                // The reader went away; open again for the next one
+                if e.kind() == std::io::ErrorKind::BrokenPipe {
+                    self.file = None;
+                }
+            }
+        }
+    }
+}
+
+/// Send replies to the FIFO at `path` instead of stderr
+#[cfg(unix)]
+pub fn reply_to_fifo(path: PathBuf) {
+    *REPLY_FIFO.lock().unwrap_or_else(PoisonError::into_inner) =
+        Some(ReplyFifo { path, file: None });
+}
+
+fn send_reply(line: &str) {
+    #[cfg(unix)]
+    if let Some(ref mut fifo) = *REPLY_FIFO.lock().unwrap_or_else(PoisonError::into_inner) {
+        fifo.send(line);
+        return;
+    }
+    eprintln!("{}", line);
+}
+
+/// Answer the last command
+pub fn reply(message: &str) {
+    send_reply(&format!("ok {}", message));
+}
+
+/// Report a failed command
+pub fn reply_error(error: &anyhow::Error) {
+    send_reply(&format!("error {}", error));
+}
+
+/// Save `frame`, if there is one yet, the way `S` does: named after `input`
+/// and the moment in `dir`, or after `path` next to it. Returns the files
+/// written, the `.txt` copy first.
+pub fn take_screenshot(
+    frame: Option<&AsciiFrame>,
+    path: Option<&Path>,
+    input: &Path,
+    dir: &Path,
+    png: bool,
+) -> Result<Vec<PathBuf>> {
+    let frame = frame.ok_or_else(|| anyhow!("No frame on screen yet"))?;
+    match path {
+        Some(path) => {
+            let stem = path
+                .file_stem()
+                .ok_or_else(|| anyhow!("Invalid screenshot path {}", path.display()))?;
+            let dir = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            save_screenshot_files(frame, dir, &stem.to_string_lossy(), png)
+        }
+        None => save_screenshot_files(frame, dir, &screenshot_stem(input, frame.timestamp), png),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_slave_commands() {
+        assert_eq!("quit".parse::<SlaveCommand>().unwrap(), SlaveCommand::Quit);
+        assert_eq!(
+            "seek 12.5".parse::<SlaveCommand>().unwrap(),
+            SlaveCommand::Admin(AdminCommand::Seek(12.5))
+        );
+        assert_eq!(
+            "screenshot shots/a b.txt".parse::<SlaveCommand>().unwrap(),
+            SlaveCommand::Screenshot(Some(PathBuf::from("shots/a b.txt")))
+        );
+        assert!("quit now".parse::<SlaveCommand>().is_err());
+
+        let mut commands = CommandReader::from_reader(Cursor::new("pause\n\nbogus\nscreenshot\n"));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut received = Vec::new();
+        while received.len() < 2 && Instant::now() < deadline {
+            received.extend(commands.try_next());
+        }
+        assert_eq!(
+            received,
+            vec![
+                SlaveCommand::Admin(AdminCommand::Pause),
+                SlaveCommand::Screenshot(None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_awaiting_commands() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut commands = CommandReader::from_reader(Cursor::new("\nbogus\nquit\n"));
+        runtime.block_on(async {
+            assert_eq!(commands.next().await, Some(SlaveCommand::Quit));
+            // The blank line and the bogus one are skipped, then input ends
+            assert_eq!(commands.next().await, None);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands_on_a_fifo() {
+        let fifo = ControlFifo::create("test-commands").unwrap();
+        let path = fifo.path().to_path_buf();
+        let mut commands = CommandReader::from_fifo(path.clone());
+
+        // Two writers in turn, as a plugin sending one command at a time
+        for line in ["toggle\n", "quit\n"] {
+            std::fs::write(&path, line).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut received = Vec::new();
+        while received.len() < 2 && Instant::now() < deadline {
+            received.extend(commands.try_next());
+        }
+        assert_eq!(
+            received,
+            vec![
+                SlaveCommand::Admin(AdminCommand::Toggle),
+                SlaveCommand::Quit
+            ]
+        );
+
+        drop(fifo);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replies_on_a_fifo() {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let fifo = ControlFifo::create("test-replies").unwrap();
+        let mut replies = ReplyFifo {
+            path: fifo.path().to_path_buf(),
+            file: None,
+        };
+        // Without a reader the reply is dropped instead of blocking
+        replies.send("ok dropped");
+        assert!(replies.file.is_none());
+
+        let reader = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(fifo.path())
+            .unwrap();
+        replies.send("ok status");
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).unwrap();
+        assert_eq!(line, "ok status\n");
+    }
+
+    #[test]
+    fn test_take_screenshot() {
+        let mut frame = AsciiFrame::new(3, 2, "ab cd ".chars().collect(), vec![(0, 0, 0); 6]);
+        frame.timestamp = 62.5;
+        let dir = tempfile::tempdir().unwrap();
+        let input = Path::new("/videos/talk.mp4");
+
+        let named = dir.path().join("shot.txt");
+        let paths =
+            take_screenshot(Some(&frame), Some(&named), input, Path::new("."), false).unwrap();
+        assert_eq!(paths, [named.clone(), dir.path().join("shot.ans")]);
+        assert_eq!(std::fs::read_to_string(&named).unwrap(), "ab \ncd \n");
+
+        let shots = dir.path().join("shots");
+        let paths = take_screenshot(Some(&frame), None, input, &shots, false).unwrap();
+        assert_eq!(paths[0], shots.join("talk-00-01-02.500.txt"));
+
+        assert!(take_screenshot(None, Some(&named), input, &shots, false).is_err());
+    }
+}
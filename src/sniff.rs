@@ -0,0 +1,283 @@
+//! Early content sniffing of input files
+//!
+//! FFmpeg probes anything it is given, and a text file, archive or program
+//! passed by mistake fails late with a probe error that says little about
+//! the actual problem. Before a local file is opened, its first bytes are
+//! compared with the signatures of known media containers and of common
+//! non-media formats. Files recognized as something other than media, and
+//! plain text, are rejected with an [`UnsupportedFormat`] error naming what
+//! the file looks like. Text formats FFmpeg plays (HLS playlists, DASH
+//! manifests, concat lists, SDP files) are recognized as media. Unrecognized binary files still go to FFmpeg, which
+//! reads many raw streams without a signature, as do URLs and devices.
+
+use anyhow::Result;
+use log::debug;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Bytes read from the start of a file; enough for every signature below
+/// and for the MPEG-TS sync byte of the second packet
+const SNIFF_LENGTH: usize = 512;
+
+/// The input is not something the player can decode
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unsupported format: '{}' looks like {detected}, not a video", path.display())]
+pub struct UnsupportedFormat {
+    pub path: PathBuf,
+    /// What the file was detected as, e.g. "a PDF document"
+    pub detected: String,
+}
+
+/// What the first bytes of a file say about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sniffed {
+    /// A media container or stream FFmpeg reads
+    Media(&'static str),
+    /// A known format that is not media
+    Other(&'static str),
+    /// No known signature
+    Unknown,
+}
+
+/// Media signatures as (offset, magic bytes, name)
+const MEDIA_SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (4, b"ftyp", "MP4/QuickTime"),
+    (4, b"moov", "QuickTime"),
+    (4, b"mdat", "QuickTime"),
+    (0, b"\x1a\x45\xdf\xa3", "Matroska/WebM"),
+    (0, b"OggS", "Ogg"),
+    (0, b"FLV", "FLV"),
+    (0, b"\x00\x00\x01\xba", "MPEG program stream"),
+    (0, b"\x00\x00\x01\xb3", "MPEG video"),
+    (0, b"\x00\x00\x00\x01", "H.264/HEVC stream"),
+    (0, b"\x30\x26\xb2\x75\x8e\x66\xcf\x11", "ASF/WMV"),
+    (0, b"YUV4MPEG2", "Y4M"),
+    (0, b"DKIF", "IVF"),
+    (0, b"GIF8", "GIF"),
+    (0, b"\x89PNG", "PNG"),
+    (0, b"\xff\xd8\xff", "JPEG"),
+    (0, b"ID3", "MP3"),
+    (0, b"fLaC", "FLAC"),
+];
+
+/// Text formats FFmpeg reads as media, as (case-insensitive start of the
+/// text, name): playlists, manifests, concat lists and SDP descriptions
+const TEXT_MEDIA_SIGNATURES: &[(&str, &str)] = &[
+    ("#extm3u", "an M3U/HLS playlist"),
+    ("<mpd", "a DASH manifest"),
+    ("ffconcat version", "an FFmpeg concat list"),
+    ("v=0", "an SDP session description"),
+];
+
+/// Non-media signatures as (offset, magic bytes, description)
+const OTHER_SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"%PDF", "a PDF document"),
+    (0, b"PK\x03\x04", "a ZIP archive"),
+    (0, b"\x1f\x8b", "a gzip archive"),
+    (0, b"BZh", "a bzip2 archive"),
+    (0, b"\xfd7zXZ\x00", "an xz archive"),
+    (0, b"7z\xbc\xaf\x27\x1c", "a 7-Zip archive"),
+    (0, b"Rar!", "a RAR archive"),
+    (0, b"\x7fELF", "an ELF executable"),
+    (0, b"MZ", "a Windows executable"),
+    (0, b"\xcf\xfa\xed\xfe", "a Mach-O executable"),
+    (0, b"SQLite format 3\x00", "an SQLite database"),
+    (257, b"ustar", "a tar archive"),
+];
+
+/// Identify a file from its first bytes
+pub fn sniff(header: &[u8]) -> Sniffed {
+    let matches = |&&(offset, magic, _): &&(usize, &[u8], &str)| {
+        header.get(offset..offset + magic.len()) == Some(magic)
+    };
+    if let Some((_, _, name)) = MEDIA_SIGNATURES.iter().find(matches) {
+        return Sniffed::Media(name);
+    }
+    // RIFF holds both video (AVI) and other data (WAVE audio, WebP images)
+    if header.starts_with(b"RIFF") {
+        return match header.get(8..12) {
+            Some(b"AVI ") => Sniffed::Media("AVI"),
+            Some(b"WAVE") => Sniffed::Media("WAV"),
+            Some(b"WEBP") => Sniffed::Media("WebP"),
+            _ => Sniffed::Unknown,
+        };
+    }
+    // MPEG-TS: a sync byte at the start of each 188-byte packet
+    if header.len() > 188 && header[0] == 0x47 && header[188] == 0x47 {
+        return Sniffed::Media("MPEG transport stream");
+    }
+    if let Some((_, _, description)) = OTHER_SIGNATURES.iter().find(matches) {
+        return Sniffed::Other(description);
+    }
+    if is_text(header) {
+        return match text_media(header) {
+            Some(name) => Sniffed::Media(name),
+            None => Sniffed::Other(text_kind(header)),
+        };
+    }
+    Sniffed::Unknown
+}
+
+/// Text files: valid UTF-8 (a character may be cut off at the end) without
+/// control characters other than whitespace
+fn is_text(header: &[u8]) -> bool {
+    let text = match std::str::from_utf8(header) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&header[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    !text.is_empty()
+        && !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0c'))
+}
+
+/// The text format FFmpeg reads as media that `header` starts with, if any.
+/// A DASH manifest may follow an XML declaration.
+fn text_media(header: &[u8]) -> Option<&'static str> {
+    let text = String::from_utf8_lossy(header).to_ascii_lowercase();
+    let mut start = text.trim_start();
+    if start.starts_with("<?xml") {
+        start = start
+            .split_once("?>")
+            .map_or("", |(_, rest)| rest.trim_start());
+    }
+    TEXT_MEDIA_SIGNATURES
+        .iter()
+        .find(|(prefix, _)| start.starts_with(prefix))
+        .map(|&(_, name)| name)
+}
+
+/// A closer description of a text file
+fn text_kind(header: &[u8]) -> &'static str {
+    let text = String::from_utf8_lossy(header);
+    let start = text.trim_start().to_ascii_lowercase();
+    if start.starts_with("#!") {
+        "a script"
+    } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "an HTML page"
+    } else if start.starts_with("<?xml") || start.starts_with("<svg") {
+        "an XML document"
+    } else if start.starts_with('{') || start.starts_with('[') {
+        "JSON"
+    } else {
+        "plain text"
+    }
+}
+
+/// Reject `path` early if it is clearly not media. URLs, devices and files
+/// that cannot be read are left for FFmpeg to judge.
+pub fn check_input(path: &Path) -> Result<()> {
+    if path.to_string_lossy().contains("://") {
+        return Ok(());
+    }
+    // Only regular files: reading from a pipe or capture device would
+    // take data FFmpeg needs
+    if !std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        return Ok(());
+    }
+    let Ok(file) = File::open(path) else {
+        return Ok(());
+    };
+    let mut header = Vec::with_capacity(SNIFF_LENGTH);
+    file.take(SNIFF_LENGTH as u64).read_to_end(&mut header)?;
+
+    let detected = match sniff(&header) {
+        _ if header.is_empty() => "an empty file",
+        Sniffed::Media(name) => {
+            debug!("Input {} looks like {}", path.display(), name);
+            return Ok(());
+        }
+        Sniffed::Unknown => {
+            debug!("Input {} has no known signature", path.display());
+            return Ok(());
+        }
+        Sniffed::Other(description) => description,
+    };
+    Err(UnsupportedFormat {
+        path: path.to_path_buf(),
+        detected: detected.to_string(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_signatures() {
+        assert_eq!(
+            sniff(b"\x00\x00\x00\x20ftypisom\x00\x00\x02\x00"),
+            Sniffed::Media("MP4/QuickTime")
+        );
+        assert_eq!(
+            sniff(b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81"),
+            Sniffed::Media("Matroska/WebM")
+        );
+        assert_eq!(
+            sniff(b"RIFF\x24\x00\x00\x00AVI LIST"),
+            Sniffed::Media("AVI")
+        );
+        let mut transport = vec![0u8; 376];
+        transport[0] = 0x47;
+        transport[188] = 0x47;
+        assert_eq!(sniff(&transport), Sniffed::Media("MPEG transport stream"));
+
+        assert_eq!(sniff(b"%PDF-1.7\n"), Sniffed::Other("a PDF document"));
+        assert_eq!(
+            sniff(b"PK\x03\x04\x14\x00"),
+            Sniffed::Other("a ZIP archive")
+        );
+        assert_eq!(sniff(b"dummy video content"), Sniffed::Other("plain text"));
+        assert_eq!(sniff(b"#!/bin/sh\necho hi\n"), Sniffed::Other("a script"));
+        assert_eq!(sniff("{\"caf\u{e9}\"".as_bytes()), Sniffed::Other("JSON"));
+        // A multi-byte character cut off by the read is still text
+        assert_eq!(
+            sniff(&"na\u{ef}ve".as_bytes()[..3]),
+            Sniffed::Other("plain text")
+        );
+        assert_eq!(sniff(b"\x00\x13\x99\xfe\x01"), Sniffed::Unknown);
+        // Text that FFmpeg plays is media
+        assert_eq!(
+            sniff(b"#EXTM3U\n#EXT-X-VERSION:3\n"),
+            Sniffed::Media("an M3U/HLS playlist")
+        );
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?>\n<MPD xmlns=\"urn:mpeg:dash\">"),
+            Sniffed::Media("a DASH manifest")
+        );
+        assert_eq!(
+            sniff(b"ffconcat version 1.0\nfile a.mp4\n"),
+            Sniffed::Media("an FFmpeg concat list")
+        );
+        assert_eq!(
+            sniff(b"v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\n"),
+            Sniffed::Media("an SDP session description")
+        );
+    }
+
+    #[test]
+    fn test_check_input() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let text = dir.join("video.mp4");
+        std::fs::write(&text, b"dummy video content").unwrap();
+        let error = check_input(&text).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UnsupportedFormat>().unwrap().detected,
+            "plain text"
+        );
+        assert!(error.to_string().contains("looks like plain text"));
+
+        let video = dir.join("real.mp4");
+        std::fs::write(&video, b"\x00\x00\x00\x20ftypisom").unwrap();
+        assert!(check_input(&video).is_ok());
+        // Missing files and URLs are left to FFmpeg
+        assert!(check_input(&dir.join("missing.mp4")).is_ok());
+        assert!(check_input(Path::new("https://example.com/a.txt")).is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+//! Playback speed that varies over the video
+//!
+//! `--speed-curve` reads keyframes of the speed at points of the video, as a
+//! JSON list, either bare or wrapped in an object:
+//! `[{"time": 12.0, "speed": 1.0}, {"time": 13.0, "speed": 0.25}]` or
+//! `{"keyframes": [...]}`. Between two keyframes the speed ramps linearly
+//! from one to the other; before the first and after the last it stays at
+//! theirs. Two keyframes at the same time switch speed there at once, so a
+//! section can be slowed down without a ramp.
+//!
+//! The curve multiplies the speed set with `--speed` and `+`/`-`. Audio is
+//! stretched on stretches of constant speed and muted while the speed ramps,
+//! since it would need restretching at every frame.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// Speeds a keyframe may set
+pub const CURVE_SPEED_RANGE: RangeInclusive<f64> = 0.05..=8.0;
+
+/// The speed at a point of the video
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct SpeedKeyframe {
+    pub time: f64,
+    pub speed: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonSpeedCurve {
+    Bare(Vec<SpeedKeyframe>),
+    Wrapped { keyframes: Vec<SpeedKeyframe> },
+}
+
+/// Keyframes sorted by time
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedCurve {
+    keyframes: Vec<SpeedKeyframe>,
+}
+
+impl SpeedCurve {
+    /// Build a curve, keeping keyframes at the same time in the order given
+    pub fn new(mut keyframes: Vec<SpeedKeyframe>) -> Result<Self> {
+        if keyframes.is_empty() {
+            return Err(anyhow!("Speed curve has no keyframes"));
+        }
+        for keyframe in &keyframes {
+            if !(keyframe.time >= 0.0 && keyframe.time.is_finite()) {
+                return Err(anyhow!("Invalid keyframe time {}", keyframe.time));
+            }
+            if !CURVE_SPEED_RANGE.contains(&keyframe.speed) {
+                return Err(anyhow!(
+                    "Keyframe speed {} at {}s must be between {} and {}",
+                    keyframe.speed,
+                    keyframe.time,
+                    CURVE_SPEED_RANGE.start(),
+                    CURVE_SPEED_RANGE.end()
+                ));
+            }
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(Self { keyframes })
+    }
+
+    /// Load a curve from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read speed curve '{}': {}", path.display(), e))?;
+        Self::parse(&contents)
+            .map_err(|e| anyhow!("Invalid speed curve '{}': {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let keyframes = match serde_json::from_str::<JsonSpeedCurve>(contents)? {
+            JsonSpeedCurve::Bare(keyframes) | JsonSpeedCurve::Wrapped { keyframes } => keyframes,
+        };
+        Self::new(keyframes)
+    }
+
+    /// Speed at `time` seconds into the video
+    pub fn at(&self, time: f64) -> f64 {
+        match self.segment(time) {
+            Some((from, to)) => {
+                let progress = (time - from.time) / (to.time - from.time);
+                from.speed + (to.speed - from.speed) * progress
+            }
+            None if time < self.keyframes[0].time => self.keyframes[0].speed,
+            None => self.keyframes[self.keyframes.len() - 1].speed,
+        }
+    }
+
+    /// Whether the speed is changing at `time`
+    pub fn ramping_at(&self, time: f64) -> bool {
+        self.segment(time)
+            .is_some_and(|(from, to)| from.speed != to.speed)
+    }
+
+    /// The keyframes `time` lies between, None before the first and from
+    /// the last on
+    fn segment(&self, time: f64) -> Option<(SpeedKeyframe, SpeedKeyframe)> {
+        if time < self.keyframes[0].time {
+            return None;
+        }
+        self.keyframes
+            .windows(2)
+            .find(|pair| time < pair[1].time)
+            .map(|pair| (pair[0], pair[1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramps_between_keyframes() {
+        let curve = SpeedCurve::parse(
+            r#"{"keyframes": [
+                {"time": 12.0, "speed": 0.25},
+                {"time": 10.0, "speed": 1.0},
+                {"time": 20.0, "speed": 0.25},
+                {"time": 20.0, "speed": 2.0}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(curve.at(0.0), 1.0);
+        assert_eq!(curve.at(11.0), 0.625);
+        assert!(curve.ramping_at(11.0));
+        assert_eq!(curve.at(15.0), 0.25);
+        assert!(!curve.ramping_at(15.0));
+        // Keyframes at the same time switch without a ramp
+        assert_eq!(curve.at(19.99), 0.25);
+        assert_eq!(curve.at(20.0), 2.0);
+        assert!(!curve.ramping_at(20.0));
+        assert_eq!(curve.at(100.0), 2.0);
+    }
+
+    #[test]
+    fn test_rejects_bad_keyframes() {
+        assert!(SpeedCurve::parse("[]").is_err());
+        assert!(SpeedCurve::parse(r#"[{"time": 1.0, "speed": 0.0}]"#).is_err());
+        assert!(SpeedCurve::parse(r#"[{"time": -1.0, "speed": 1.0}]"#).is_err());
+        assert!(SpeedCurve::parse(r#"[{"time": 1.0, "speed": 1.0}]"#).is_ok());
+    }
+}
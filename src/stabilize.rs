@@ -0,0 +1,199 @@
+//! Digital stabilization for shaky footage
+//!
+//! Camera shake moves the whole picture a few pixels from frame to frame,
+//! and after downscaling that makes most cells flip characters. With
+//! `--stabilize`, consecutive frames are block matched on a small luminance
+//! copy to estimate the global shift, and each frame is cropped to a window
+//! that follows a smoothed version of the accumulated camera path instead of
+//! the shaky one. The window gives up [`STABILIZE_MARGIN`] of the picture on
+//! every side, which bounds how much shake can be absorbed.
+
+use crate::crop::{crop_frame, CropRect};
+use crate::decoder::VideoFrame;
+use anyhow::Result;
+
+/// Share of the width and height cropped from each side
+pub const STABILIZE_MARGIN: f64 = 0.08;
+
+/// Width of the luminance copy used for motion estimation
+const ANALYSIS_WIDTH: u32 = 160;
+/// Largest shift searched for, in analysis pixels
+const SEARCH_RADIUS: i32 = 6;
+/// Weight of the previous smoothed position; higher follows pans more slowly
+const SMOOTHING: f64 = 0.9;
+
+/// Luminance of a frame at analysis size
+struct Thumbnail {
+    luma: Vec<u8>,
+    width: usize,
+    height: usize,
+    /// Source pixels per thumbnail pixel
+    scale: f64,
+}
+
+impl Thumbnail {
+    fn new(frame: &VideoFrame) -> Self {
+        let width = frame.width.clamp(1, ANALYSIS_WIDTH);
+        let scale = frame.width as f64 / width as f64;
+        let height = ((frame.height as f64 / scale) as u32).max(1);
+
+        let mut luma = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let source_y = ((y as f64 * scale) as u32).min(frame.height.saturating_sub(1));
+            for x in 0..width {
+                let source_x = ((x as f64 * scale) as u32).min(frame.width.saturating_sub(1));
+                let index = ((source_y * frame.width + source_x) * 3) as usize;
+                luma.push(match frame.data.get(index..index + 3) {
+                    Some(&[r, g, b]) => {
+                        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+                    }
+                    _ => 0,
+                });
+            }
+        }
+
+        Self {
+            luma,
+            width: width as usize,
+            height: height as usize,
+            scale,
+        }
+    }
+
+    /// Shift `(dx, dy)` of the picture content from `previous` to `self`
+    fn shift_from(&self, previous: &Thumbnail) -> (i32, i32) {
+        let radius = SEARCH_RADIUS as usize;
+        if self.width <= radius * 2 || self.height <= radius * 2 {
+            return (0, 0);
+        }
+
+        let cost = |dx: i32, dy: i32| -> u64 {
+            let mut total = 0u64;
+            for y in radius..self.height - radius {
+                let before_row = (y as i32 - dy) as usize * self.width;
+                let row = y * self.width;
+                for x in radius..self.width - radius {
+                    let before = previous.luma[before_row + (x as i32 - dx) as usize];
+                    total += self.luma[row + x].abs_diff(before) as u64;
+                }
+            }
+            total
+        };
+
+        // Ties keep the smaller shift, so flat pictures stay put
+        let mut best = ((0, 0), cost(0, 0));
+        for dy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            for dx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                let candidate = cost(dx, dy);
+                if candidate < best.1 {
+                    best = ((dx, dy), candidate);
+                }
+            }
+        }
+        best.0
+    }
+}
+
+/// Crops frames to cancel camera shake
+#[derive(Default)]
+pub struct Stabilizer {
+    previous: Option<Thumbnail>,
+    last_timestamp: f64,
+    /// Accumulated content shift in source pixels
+    path: (f64, f64),
+    /// `path` with the shake smoothed out
+    smoothed: (f64, f64),
+}
+
+impl Stabilizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Crop `frame` to its stabilized window. Tracking starts over after a
+    /// resize or a jump back in time.
+    pub fn stabilize(&mut self, frame: VideoFrame) -> Result<VideoFrame> {
+        let thumbnail = Thumbnail::new(&frame);
+        let previous = self.previous.take().filter(|previous| {
+            previous.width == thumbnail.width
+                && previous.height == thumbnail.height
+                && frame.timestamp >= self.last_timestamp
+        });
+
+        match previous {
+            Some(ref previous) => {
+                let (dx, dy) = thumbnail.shift_from(previous);
+                self.path.0 += dx as f64 * thumbnail.scale;
+                self.path.1 += dy as f64 * thumbnail.scale;
+                self.smoothed.0 = self.smoothed.0 * SMOOTHING + self.path.0 * (1.0 - SMOOTHING);
+                self.smoothed.1 = self.smoothed.1 * SMOOTHING + self.path.1 * (1.0 - SMOOTHING);
+            }
+            None => {
+                self.path = (0.0, 0.0);
+                self.smoothed = (0.0, 0.0);
+            }
+        }
+        self.previous = Some(thumbnail);
+        self.last_timestamp = frame.timestamp;
+
+        let margin_x = (frame.width as f64 * STABILIZE_MARGIN).round();
+        let margin_y = (frame.height as f64 * STABILIZE_MARGIN).round();
+        if margin_x < 1.0 || margin_y < 1.0 {
+            return Ok(frame);
+        }
+
+        // Following the content by its unsmoothed motion keeps it in place
+        let x = (margin_x + self.path.0 - self.smoothed.0).clamp(0.0, margin_x * 2.0);
+        let y = (margin_y + self.path.1 - self.smoothed.1).clamp(0.0, margin_y * 2.0);
+        crop_frame(
+            &frame,
+            CropRect {
+                x: x.round() as u32,
+                y: y.round() as u32,
+                width: frame.width - margin_x as u32 * 2,
+                height: frame.height - margin_y as u32 * 2,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noise-like texture shifted right by `shift` pixels
+    fn textured_frame(shift: u32, timestamp: f64) -> VideoFrame {
+        let (width, height) = (160, 90);
+        let mut data = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let source_x = (x + 1000 - shift) as u64;
+                let hash = (source_x * 73_856_093) ^ (y as u64 * 19_349_663);
+                let value = (hash >> 7) as u8;
+                data.extend_from_slice(&[value, value, value]);
+            }
+        }
+        VideoFrame {
+            data,
+            width,
+            height,
+            timestamp,
+            frame_number: (timestamp * 25.0) as u64,
+        }
+    }
+
+    #[test]
+    fn test_stabilizer_cancels_shake() {
+        let mut stabilizer = Stabilizer::new();
+        let steady = stabilizer.stabilize(textured_frame(0, 0.0)).unwrap();
+        assert_eq!((steady.width, steady.height), (134, 76));
+
+        // The camera jerks; the crop follows so the picture doesn't move
+        let shaken = stabilizer.stabilize(textured_frame(3, 0.04)).unwrap();
+        assert_eq!(shaken.data, steady.data);
+
+        // A jump back in time starts over from the centered window
+        let rewound = stabilizer.stabilize(textured_frame(3, 0.0)).unwrap();
+        assert_ne!(rewound.data, steady.data);
+    }
+}
@@ -0,0 +1,258 @@
+//! Playback status shown outside the player
+//!
+//! Status reporters mirror what is playing somewhere else: a SketchyBar item
+//! with `--sketchybar-item`, the terminal's title bar with `--set-title`.
+//! Every reporter gets the status on every frame and only sends an update
+//! when its own text changes.
+
+use anyhow::Result;
+use log::{debug, warn};
+use std::io::Write;
+use std::process::Command;
+
+/// What is playing and where
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackStatus<'a> {
+    /// File name shown to the user
+    pub name: &'a str,
+    /// Seconds into the video
+    pub position: f64,
+    /// Length of the video in seconds, 0 if unknown
+    pub duration: f64,
+    pub paused: bool,
+}
+
+impl PlaybackStatus<'_> {
+    fn icon(&self) -> &'static str {
+        if self.paused {
+            "⏸"
+        } else {
+            "▶"
+        }
+    }
+}
+
+/// Something that displays the playback status
+pub trait StatusReporter {
+    /// Show the current status
+    fn update(&mut self, status: &PlaybackStatus) -> Result<()>;
+
+    /// Remove the status when playback ends
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// Registry updating every registered reporter
+#[derive(Default)]
+pub struct StatusReporters {
+    reporters: Vec<Box<dyn StatusReporter>>,
+}
+
+impl StatusReporters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, reporter: Box<dyn StatusReporter>) {
+        self.reporters.push(reporter);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reporters.is_empty()
+    }
+
+    /// Show the status on every reporter
+    pub fn update(&mut self, status: &PlaybackStatus) {
+        for reporter in &mut self.reporters {
+            if let Err(e) = reporter.update(status) {
+                warn!("Status update failed: {}", e);
+            }
+        }
+    }
+
+    /// Clear every reporter
+    pub fn clear(&mut self) {
+        for reporter in &mut self.reporters {
+            if let Err(e) = reporter.clear() {
+                warn!("Clearing status failed: {}", e);
+            }
+        }
+    }
+}
+
+/// `mm:ss`, or `h:mm:ss` past an hour
+pub fn format_clock(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Labels a SketchyBar item with the file and whether it is playing
+pub struct SketchyBar {
+    item_name: String,
+    label: Option<String>,
+}
+
+impl SketchyBar {
+    pub fn new(item_name: String) -> Self {
+        Self {
+            item_name,
+            label: None,
+        }
+    }
+
+    fn set_label(&mut self, label: String) -> Result<()> {
+        if self.label.as_ref() == Some(&label) {
+            return Ok(());
+        }
+
+        let output = Command::new("sketchybar")
+            .args(["--set", &self.item_name, "label", &label])
+            .output();
+
+        match output {
+            Ok(result) => {
+                if result.status.success() {
+                    debug!("SketchyBar updated: {} -> {}", self.item_name, label);
+                } else {
+                    warn!(
+                        "SketchyBar command failed: {}",
+                        String::from_utf8_lossy(&result.stderr)
+                    );
+                }
+            }
+            Err(e) => {
+                warn!("Failed to execute sketchybar command: {}", e);
+            }
+        }
+        self.label = Some(label);
+        Ok(())
+    }
+}
+
+impl StatusReporter for SketchyBar {
+    fn update(&mut self, status: &PlaybackStatus) -> Result<()> {
+        self.set_label(format!("{} {}", status.icon(), status.name))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.set_label(String::new())
+    }
+}
+
+impl Drop for SketchyBar {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+/// Shows progress in the terminal's title bar
+///
+/// The old title is saved on the terminal's title stack (`CSI 22 t`) when
+/// the first title is set and restored from it (`CSI 23 t`) on clear, since
+/// terminals don't reliably report their title.
+pub struct TerminalTitle<W: Write> {
+    output: W,
+    title: Option<String>,
+}
+
+impl<W: Write> TerminalTitle<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            title: None,
+        }
+    }
+}
+
+impl<W: Write> StatusReporter for TerminalTitle<W> {
+    fn update(&mut self, status: &PlaybackStatus) -> Result<()> {
+        let mut title = format!(
+            "{} {} — {}",
+            status.icon(),
+            status.name,
+            format_clock(status.position)
+        );
+        if status.duration > 0.0 {
+            title.push('/');
+            title.push_str(&format_clock(status.duration));
+        }
+        // Control characters in a file name would end the sequence early
+        title.retain(|c| !c.is_control());
+        if self.title.as_ref() == Some(&title) {
+            return Ok(());
+        }
+
+        if self.title.is_none() {
+            self.output.write_all(b"\x1b[22;0t")?;
+        }
+        // OSC 0 sets both the icon name and the window title
+        write!(self.output, "\x1b]0;{}\x07", title)?;
+        self.output.flush()?;
+        self.title = Some(title);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        if self.title.take().is_some() {
+            self.output.write_all(b"\x1b[23;0t")?;
+            self.output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for TerminalTitle<W> {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(position: f64, paused: bool) -> PlaybackStatus<'static> {
+        PlaybackStatus {
+            name: "movie.mkv",
+            position,
+            duration: 2700.0,
+            paused,
+        }
+    }
+
+    #[test]
+    fn test_terminal_title() {
+        let mut output = Vec::new();
+        {
+            let mut title = TerminalTitle::new(&mut output);
+            title.update(&status(754.2, false)).unwrap();
+            // Same second, nothing new to send
+            title.update(&status(754.9, false)).unwrap();
+            title.update(&status(755.0, true)).unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\x1b[22;0t\x1b]0;▶ movie.mkv — 12:34/45:00\x07\
+             \x1b]0;⏸ movie.mkv — 12:35/45:00\x07\x1b[23;0t"
+        );
+        assert_eq!(format_clock(3725.0), "1:02:05");
+    }
+
+    #[test]
+    fn test_sketchybar_integration() {
+        let mut sb = SketchyBar::new("test_item".to_string());
+
+        // These tests will only work if sketchybar is installed
+        // In a real environment, you might want to mock the Command execution
+        let result = sb.update(&status(0.0, false));
+        assert!(result.is_ok());
+
+        let result = sb.clear();
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,204 @@
+//! Time-stretching of audio without changing its pitch
+//!
+//! Playing samples faster or slower than they were recorded shifts every
+//! voice up or down with the speed. WSOLA (waveform similarity overlap-add)
+//! instead cuts the audio into overlapping segments taken at the playback
+//! speed and joins them at the normal rate: each segment is picked from
+//! within [`SEARCH_SECONDS`] of where the speed puts it, at the offset whose
+//! waveform best matches what the previous segment would have continued
+//! with, and cross-faded in so the joins don't click. Speech and music stay
+//! at their pitch up to about twice or half the speed.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::ops::RangeInclusive;
+
+/// Speeds at which stretched audio still sounds natural
+pub const STRETCH_RANGE: RangeInclusive<f64> = 0.5..=2.0;
+
+/// Length of the cross-fade between segments, which is also how much output
+/// each segment adds
+const OVERLAP_SECONDS: f64 = 0.02;
+
+/// How far either side of its nominal position a segment may be taken from
+const SEARCH_SECONDS: f64 = 0.01;
+
+/// Offsets tried on the first pass of the search; the best is refined to
+/// the sample afterwards
+const COARSE_STEP: usize = 4;
+
+/// Stretches interleaved audio by a constant speed
+#[derive(Debug)]
+pub struct TimeStretch {
+    speed: f64,
+    channels: usize,
+    /// Frames (samples of every channel) per segment overlap
+    overlap: usize,
+    /// Frames either side of the nominal position searched for a match
+    search: usize,
+    /// Input not yet used, interleaved
+    input: VecDeque<f32>,
+    /// Where the next segment nominally starts, in frames into `input`
+    position: f64,
+    /// What the previous segment continues with, faded out under the next
+    tail: Vec<f32>,
+}
+
+impl TimeStretch {
+    /// Stretch `channels` interleaved channels at `rate` frames per second to
+    /// play at `speed`
+    pub fn new(rate: u32, channels: u16, speed: f64) -> Self {
+        let channels = usize::from(channels.max(1));
+        let overlap = ((rate as f64 * OVERLAP_SECONDS) as usize).max(1);
+        let search = (rate as f64 * SEARCH_SECONDS) as usize;
+        Self {
+            speed,
+            channels,
+            overlap,
+            search,
+            input: VecDeque::new(),
+            position: search as f64,
+            // Starting from silence fades the audio in after seeks
+            tail: vec![0.0; overlap * channels],
+        }
+    }
+
+    /// Add `samples` and return the stretched audio they complete. Output
+    /// lags input by up to a segment and the search window.
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.input.extend(samples);
+        let frames = self.input.len() / self.channels;
+        let mut output = Vec::new();
+
+        // A segment and its continuation must fit whichever offset wins
+        while (self.position as usize) + self.search + 2 * self.overlap <= frames {
+            let start = self.best_start(self.position as usize);
+            self.overlap_add(start, &mut output);
+            self.position += self.overlap as f64 * self.speed;
+        }
+
+        // Drop what no later search can reach
+        let used = (self.position as usize).saturating_sub(self.search);
+        self.input.drain(..used * self.channels);
+        self.position -= used as f64;
+        output
+    }
+
+    /// Start of the segment near `nominal` best matching the tail
+    fn best_start(&self, nominal: usize) -> usize {
+        let first = nominal.saturating_sub(self.search);
+        let last = nominal + self.search;
+        let mut best = (f64::MIN, nominal);
+        for start in (first..=last).step_by(COARSE_STEP) {
+            best = self.compare(start, best);
+        }
+        let (_, coarse) = best;
+        let fine = coarse.saturating_sub(COARSE_STEP - 1).max(first)
+            ..=(coarse + COARSE_STEP - 1).min(last);
+        for start in fine {
+            best = self.compare(start, best);
+        }
+        best.1
+    }
+
+    /// `best` or the segment at `start`, whichever correlates better with the
+    /// tail
+    fn compare(&self, start: usize, best: (f64, usize)) -> (f64, usize) {
+        let (mut correlation, mut energy) = (0.0, 0.0);
+        for (i, &tail) in self.tail.iter().enumerate() {
+            let sample = self.input[start * self.channels + i] as f64;
+            correlation += tail as f64 * sample;
+            energy += sample * sample;
+        }
+        let score = correlation / energy.sqrt().max(f64::EPSILON);
+        if score > best.0 {
+            (score, start)
+        } else {
+            best
+        }
+    }
+
+    /// Cross-fade the tail into the segment at `start`, append the result to
+    /// `output` and keep what follows the segment as the next tail
+    fn overlap_add(&mut self, start: usize, output: &mut Vec<f32>) {
+        let offset = start * self.channels;
+        for frame in 0..self.overlap {
+            let fade = 0.5 - 0.5 * (PI * (frame as f64 + 0.5) / self.overlap as f64).cos();
+            for channel in 0..self.channels {
+                let i = frame * self.channels + channel;
+                let incoming = self.input[offset + i] as f64;
+                output.push((self.tail[i] as f64 * (1.0 - fade) + incoming * fade) as f32);
+            }
+        }
+        let continuation = offset + self.overlap * self.channels;
+        for (i, tail) in self.tail.iter_mut().enumerate() {
+            *tail = self.input[continuation + i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `seconds` of a mono sine wave at `frequency`
+    fn sine(rate: u32, frequency: f64, seconds: f64) -> Vec<f32> {
+        (0..(rate as f64 * seconds) as usize)
+            .map(|n| (2.0 * PI * frequency * n as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Upward zero crossings per second of `samples`
+    fn frequency(rate: u32, samples: &[f32]) -> f64 {
+        let crossings = samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        crossings as f64 * rate as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn test_stretch_keeps_pitch_and_changes_length() {
+        let rate = 8000;
+        let input = sine(rate, 440.0, 2.0);
+        for speed in [0.5, 1.5, 2.0] {
+            let mut stretch = TimeStretch::new(rate, 1, speed);
+            let output: Vec<f32> = input
+                .chunks(512)
+                .flat_map(|chunk| stretch.process(chunk))
+                .collect();
+
+            let expected = input.len() as f64 / speed;
+            let ratio = output.len() as f64 / expected;
+            assert!(
+                (0.9..=1.0).contains(&ratio),
+                "{}x: {} of {}",
+                speed,
+                output.len(),
+                expected
+            );
+
+            // Skip the fade in from silence
+            let heard = frequency(rate, &output[output.len() / 4..]);
+            assert!(
+                (heard - 440.0).abs() < 15.0,
+                "{}x: heard {} Hz",
+                speed,
+                heard
+            );
+        }
+    }
+
+    #[test]
+    fn test_channels_stay_apart() {
+        // Left silent, right constant: no segment may swap them
+        let input: Vec<f32> = (0..8000).flat_map(|_| [0.0, 0.5]).collect();
+        let mut stretch = TimeStretch::new(8000, 2, 1.5);
+        let output = stretch.process(&input);
+        assert!(!output.is_empty());
+        assert!(output.chunks_exact(2).all(|frame| frame[0] == 0.0));
+        assert!(output[output.len() / 2..]
+            .chunks_exact(2)
+            .all(|frame| (frame[1] - 0.5).abs() < 1e-6));
+    }
+}
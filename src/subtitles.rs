@@ -0,0 +1,367 @@
+//! Embedded subtitle tracks
+//!
+//! Bitmap subtitles (PGS, DVB, VobSub) arrive as paletted images positioned
+//! on a canvas, usually the video resolution. They can't be rendered as text,
+//! so active cues are alpha-blended onto the decoded RGB frame before ASCII
+//! conversion.
+
+use crate::decoder::VideoFrame;
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use log::{debug, warn};
+
+/// An RGBA image placed on the subtitle canvas
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleBitmap {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Row-major RGBA pixels
+    pub pixels: Vec<[u8; 4]>,
+}
+
+/// A subtitle event and the time it is displayed
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleCue {
+    pub start: f64,
+    /// End time; `None` until a later event replaces the cue
+    pub end: Option<f64>,
+    pub bitmaps: Vec<SubtitleBitmap>,
+}
+
+impl SubtitleCue {
+    /// Whether the cue is shown at `timestamp`
+    pub fn is_active(&self, timestamp: f64) -> bool {
+        timestamp >= self.start && self.end.is_none_or(|end| timestamp < end)
+    }
+}
+
+/// Decoded cues waiting to be shown, ordered by start time
+#[derive(Debug, Clone, Default)]
+pub struct SubtitleTimeline {
+    cues: Vec<SubtitleCue>,
+}
+
+impl SubtitleTimeline {
+    /// Add a cue. Bitmap formats signal the end of a cue by sending the next
+    /// one (often empty), so open-ended cues are closed when a later cue starts.
+    pub fn push(&mut self, cue: SubtitleCue) {
+        for previous in &mut self.cues {
+            if previous.start <= cue.start && previous.end.is_none_or(|end| end > cue.start) {
+                previous.end = Some(cue.start);
+            }
+        }
+
+        if !cue.bitmaps.is_empty() {
+            let position = self.cues.partition_point(|c| c.start <= cue.start);
+            self.cues.insert(position, cue);
+        }
+    }
+
+    /// Cues shown at `timestamp`; cues that ended earlier are dropped
+    pub fn active(&mut self, timestamp: f64) -> impl Iterator<Item = &SubtitleCue> {
+        self.cues
+            .retain(|cue| cue.end.is_none_or(|end| end > timestamp));
+        self.cues.iter().filter(move |cue| cue.is_active(timestamp))
+    }
+
+    /// Forget all cues (after seeking)
+    pub fn clear(&mut self) {
+        self.cues.clear();
+    }
+
+    /// Number of cues held
+    pub fn len(&self) -> usize {
+        self.cues.len()
+    }
+
+    /// Whether no cues are held
+    pub fn is_empty(&self) -> bool {
+        self.cues.is_empty()
+    }
+}
+
+/// Alpha-blend a bitmap onto an RGB frame.
+///
+/// `canvas` is the coordinate space of the bitmap; it is scaled to the frame
+/// when the subtitle canvas differs from the video resolution.
+pub fn composite(frame: &mut VideoFrame, bitmap: &SubtitleBitmap, canvas: (u32, u32)) {
+    let (canvas_width, canvas_height) = canvas;
+    if canvas_width == 0 || canvas_height == 0 || bitmap.width == 0 || bitmap.height == 0 {
+        return;
+    }
+
+    let scale_x = frame.width as f64 / canvas_width as f64;
+    let scale_y = frame.height as f64 / canvas_height as f64;
+
+    let left = (bitmap.x as f64 * scale_x) as u32;
+    let top = (bitmap.y as f64 * scale_y) as u32;
+    let right = (((bitmap.x + bitmap.width) as f64 * scale_x).ceil() as u32).min(frame.width);
+    let bottom = (((bitmap.y + bitmap.height) as f64 * scale_y).ceil() as u32).min(frame.height);
+
+    for y in top..bottom {
+        let source_y = ((y as f64 / scale_y) as u32)
+            .saturating_sub(bitmap.y)
+            .min(bitmap.height - 1);
+        for x in left..right {
+            let source_x = ((x as f64 / scale_x) as u32)
+                .saturating_sub(bitmap.x)
+                .min(bitmap.width - 1);
+            let [r, g, b, a] = bitmap.pixels[(source_y * bitmap.width + source_x) as usize];
+            if a == 0 {
+                continue;
+            }
+
+            let index = ((y * frame.width + x) * 3) as usize;
+            let alpha = a as u32;
+            for (channel, value) in [r, g, b].into_iter().enumerate() {
+                let base = frame.data[index + channel] as u32;
+                frame.data[index + channel] =
+                    ((value as u32 * alpha + base * (255 - alpha)) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// A subtitle stream being decoded alongside the video
+pub struct SubtitleStream {
+    stream_index: usize,
+    decoder: ffmpeg::codec::decoder::Subtitle,
+    time_base: ffmpeg::Rational,
+    canvas: (u32, u32),
+    timeline: SubtitleTimeline,
+    warned_text: bool,
+}
+
+impl SubtitleStream {
+    /// Open the `track`-th subtitle stream of the input (0-based)
+    pub fn open(
+        input: &ffmpeg::format::context::Input,
+        track: usize,
+        video_dimensions: (u32, u32),
+    ) -> Result<Self> {
+        let stream = input
+            .streams()
+            .filter(|stream| stream.parameters().medium() == ffmpeg::media::Type::Subtitle)
+            .nth(track)
+            .ok_or_else(|| anyhow!("Subtitle track {} not found", track))?;
+
+        let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .map_err(|e| anyhow!("Failed to create subtitle codec context: {}", e))?;
+        let decoder = context
+            .decoder()
+            .subtitle()
+            .map_err(|e| anyhow!("Failed to create subtitle decoder: {}", e))?;
+
+        // Bitmap codecs position rects on their own canvas, which defaults to
+        // the video size when the stream doesn't declare one
+        let (width, height) = unsafe {
+            let context = decoder.as_ptr();
+            ((*context).width, (*context).height)
+        };
+        let canvas = if width > 0 && height > 0 {
+            (width as u32, height as u32)
+        } else {
+            video_dimensions
+        };
+
+        debug!(
+            "Using subtitle stream {} (track {}), canvas {}x{}",
+            stream.index(),
+            track,
+            canvas.0,
+            canvas.1
+        );
+
+        Ok(Self {
+            stream_index: stream.index(),
+            decoder,
+            time_base: stream.time_base(),
+            canvas,
+            timeline: SubtitleTimeline::default(),
+            warned_text: false,
+        })
+    }
+
+    /// Index of the container stream carrying the subtitles
+    pub fn stream_index(&self) -> usize {
+        self.stream_index
+    }
+
+    /// Decode a subtitle packet into the timeline
+    pub fn decode(&mut self, packet: &ffmpeg::Packet) -> Result<()> {
+        let mut subtitle = ffmpeg::Subtitle::new();
+        if !self
+            .decoder
+            .decode(packet, &mut subtitle)
+            .map_err(|e| anyhow!("Failed to decode subtitle: {}", e))?
+        {
+            return Ok(());
+        }
+
+        let Some(pts) = packet.pts() else {
+            return Ok(());
+        };
+        let pts =
+            pts as f64 * self.time_base.numerator() as f64 / self.time_base.denominator() as f64;
+
+        let mut bitmaps = Vec::new();
+        for rect in subtitle.rects() {
+            match rect {
+                ffmpeg::subtitle::Rect::Bitmap(bitmap) => {
+                    if let Some(bitmap) = unsafe { read_bitmap(&bitmap) } {
+                        bitmaps.push(bitmap);
+                    }
+                }
+                ffmpeg::subtitle::Rect::Text(_) | ffmpeg::subtitle::Rect::Ass(_) => {
+                    if !self.warned_text {
+                        warn!("Text subtitle tracks are not supported; only bitmap subtitles are burned in");
+                        self.warned_text = true;
+                    }
+                }
+                ffmpeg::subtitle::Rect::None(_) => {}
+            }
+        }
+
+        let start = pts + subtitle.start() as f64 / 1000.0;
+        // Some formats leave the end unset (0 or u32::MAX) and rely on the next event
+        let end = match subtitle.end() {
+            end if end == 0 || end == u32::MAX => None,
+            end => Some(pts + end as f64 / 1000.0),
+        };
+
+        self.timeline.push(SubtitleCue {
+            start,
+            end,
+            bitmaps,
+        });
+        Ok(())
+    }
+
+    /// Burn the cues active at the frame's timestamp into its pixels
+    pub fn burn_in(&mut self, frame: &mut VideoFrame) {
+        let canvas = self.canvas;
+        let timestamp = frame.timestamp;
+        for cue in self.timeline.active(timestamp) {
+            for bitmap in &cue.bitmaps {
+                composite(frame, bitmap, canvas);
+            }
+        }
+    }
+
+    /// Drop pending cues and decoder state after a seek
+    pub fn reset(&mut self) {
+        self.decoder.flush();
+        self.timeline.clear();
+    }
+}
+
+/// Expand a paletted subtitle rect into RGBA pixels
+///
+/// # Safety
+///
+/// The bitmap must come from a decoded `AVSubtitle` that is still alive.
+unsafe fn read_bitmap(bitmap: &ffmpeg::subtitle::Bitmap) -> Option<SubtitleBitmap> {
+    let rect = &*bitmap.as_ptr();
+    let (width, height) = (rect.w.max(0) as u32, rect.h.max(0) as u32);
+    if width == 0 || height == 0 || rect.data[0].is_null() || rect.data[1].is_null() {
+        return None;
+    }
+
+    let stride = rect.linesize[0] as usize;
+    let indices = std::slice::from_raw_parts(rect.data[0], stride * height as usize);
+    // PAL8 palette: native-endian 0xAARRGGBB entries
+    let palette = std::slice::from_raw_parts(rect.data[1] as *const u32, 256);
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let argb = palette[indices[y * stride + x] as usize];
+            pixels.push([
+                (argb >> 16) as u8,
+                (argb >> 8) as u8,
+                argb as u8,
+                (argb >> 24) as u8,
+            ]);
+        }
+    }
+
+    Some(SubtitleBitmap {
+        x: rect.x.max(0) as u32,
+        y: rect.y.max(0) as u32,
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f64, end: Option<f64>) -> SubtitleCue {
+        SubtitleCue {
+            start,
+            end,
+            bitmaps: vec![SubtitleBitmap {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+                pixels: vec![[255, 255, 255, 255]],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_next_cue_closes_open_cue() {
+        let mut timeline = SubtitleTimeline::default();
+        timeline.push(cue(1.0, None));
+        assert_eq!(timeline.active(5.0).count(), 1);
+
+        // An empty event clears the screen
+        timeline.push(SubtitleCue {
+            start: 6.0,
+            end: None,
+            bitmaps: Vec::new(),
+        });
+        assert_eq!(timeline.active(5.5).count(), 1);
+        assert_eq!(timeline.active(6.0).count(), 0);
+        assert!(timeline.is_empty());
+    }
+
+    #[test]
+    fn test_timed_cue_expires() {
+        let mut timeline = SubtitleTimeline::default();
+        timeline.push(cue(2.0, Some(3.0)));
+        assert_eq!(timeline.active(1.0).count(), 0);
+        assert_eq!(timeline.active(2.5).count(), 1);
+        assert_eq!(timeline.active(3.0).count(), 0);
+    }
+
+    #[test]
+    fn test_composite_blends_and_scales() {
+        let mut frame = VideoFrame {
+            data: vec![0; 4 * 2 * 3],
+            width: 4,
+            height: 2,
+            timestamp: 0.0,
+            frame_number: 0,
+        };
+        // A 1x1 half-transparent white bitmap on a 2x1 canvas covers the left half
+        let bitmap = SubtitleBitmap {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            pixels: vec![[255, 255, 255, 128]],
+        };
+        composite(&mut frame, &bitmap, (2, 1));
+
+        assert_eq!(&frame.data[0..3], &[128, 128, 128]);
+        assert_eq!(&frame.data[3..6], &[128, 128, 128]);
+        assert_eq!(&frame.data[6..9], &[0, 0, 0]);
+        assert_eq!(&frame.data[12..15], &[128, 128, 128]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Recovering from panics while converting and rendering frames
+//!
+//! A panic on one malformed frame should cost that frame, not the session.
+//! `Supervisor::run` catches panics from a frame's conversion or rendering,
+//! logs them and lets playback move on to the next frame. Panics anywhere
+//! else still end the program, but the hook installed by
+//! `install_panic_hook` takes the terminal out of raw mode first so the
+//! message is readable and the shell is usable.
+
+use crate::termstate;
+use crossterm::cursor::Show;
+use crossterm::style::ResetColor;
+use crossterm::terminal::{disable_raw_mode, is_raw_mode_enabled};
+use log::error;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    /// Whether this thread is inside `Supervisor::run`
+    static SUPERVISED: Cell<bool> = const { Cell::new(false) };
+    /// Message of the last supervised panic on this thread
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Replace the panic hook: supervised panics are recorded quietly for the
+/// supervisor to log, and others restore the terminal before the default
+/// report
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if SUPERVISED.with(Cell::get) {
+            let location = info
+                .location()
+                .map(|location| format!(" at {}:{}", location.file(), location.line()))
+                .unwrap_or_default();
+            let message = format!("{}{}", payload_message(info.payload()), location);
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(message));
+            return;
+        }
+
+        if is_raw_mode_enabled().unwrap_or(false) {
+            let _ = crossterm::execute!(std::io::stdout(), Show, ResetColor);
+            let _ = disable_raw_mode();
+            termstate::clear();
+        }
+        default_hook(info);
+    }));
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Runs per-frame work and counts the panics it recovered from
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    panics: u64,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `work` for one frame. A panic is logged with `stage` (e.g.
+    /// "converting") and turned into `None` so the caller can skip the frame.
+    pub fn run<T>(&mut self, stage: &str, work: impl FnOnce() -> T) -> Option<T> {
+        let outer = SUPERVISED.with(|supervised| supervised.replace(true));
+        let result = panic::catch_unwind(AssertUnwindSafe(work));
+        SUPERVISED.with(|supervised| supervised.set(outer));
+
+        match result {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                self.panics += 1;
+                let message = LAST_PANIC
+                    .with(|last| last.borrow_mut().take())
+                    .unwrap_or_else(|| payload_message(payload.as_ref()));
+                error!("Panic while {} a frame, skipping it: {}", stage, message);
+                None
+            }
+        }
+    }
+
+    /// Panics recovered from so far
+    pub fn panics(&self) -> u64 {
+        self.panics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supervisor_recovers_from_panics() {
+        install_panic_hook();
+        let mut supervisor = Supervisor::new();
+
+        assert_eq!(supervisor.run("converting", || 42), Some(42));
+        let skipped: Option<u8> = supervisor.run("converting", || {
+            let cells: Vec<u8> = Vec::new();
+            cells[3]
+        });
+        assert_eq!(skipped, None);
+        assert_eq!(
+            supervisor.run("rendering", || "next frame"),
+            Some("next frame")
+        );
+        assert_eq!(supervisor.panics(), 1);
+        assert!(!SUPERVISED.with(Cell::get));
+    }
+}
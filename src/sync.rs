@@ -0,0 +1,414 @@
+//! Watching together from several terminals
+//!
+//! One player started with `--sync-host` accepts TCP connections from
+//! players started with `--sync-join HOST:PORT`, each playing its own copy
+//! of the video. Pausing, resuming and seeking on any of them is sent to the
+//! host, which passes it on to everyone else, so anyone in the group can
+//! drive playback. The host also sends its position once a second; a player
+//! that has drifted further than `DRIFT_THRESHOLD` from it seeks to catch up.
+//!
+//! Messages are text lines like the wall clock's: `ascii-sync 1 seek 42.5`,
+//! `ascii-sync 1 pause 12.3`, `ascii-sync 1 position 12.3 playing`. Lines
+//! this release doesn't understand are ignored.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Address `--sync-host` listens on without one given; only this machine
+/// can join until a reachable address is given
+pub const DEFAULT_SYNC_HOST_ADDR: &str = "127.0.0.1:4300";
+
+/// First word of every sync message
+const SYNC_MAGIC: &str = "ascii-sync";
+/// Sync message format version
+const SYNC_VERSION: u32 = 1;
+
+/// How often the host sends its position
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Players further than this from the host's position seek to it
+const DRIFT_THRESHOLD: f64 = 1.0;
+/// Pausing or resuming more than this away from the sender seeks to it
+const PAUSE_TOLERANCE: f64 = 0.25;
+/// Longest line read from a peer; longer ones drop the connection
+const MAX_LINE_LENGTH: usize = 256;
+
+/// A change in playback, or the host's position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMessage {
+    /// Paused at a position in seconds
+    Pause(f64),
+    /// Resumed from a position in seconds
+    Resume(f64),
+    /// Seeked to a position in seconds
+    Seek(f64),
+    /// Where the host is, sent every `HEARTBEAT_INTERVAL`
+    Position { position: f64, paused: bool },
+}
+
+impl SyncMessage {
+    pub fn encode(&self) -> String {
+        let body = match *self {
+            SyncMessage::Pause(position) => format!("pause {}", position),
+            SyncMessage::Resume(position) => format!("resume {}", position),
+            SyncMessage::Seek(position) => format!("seek {}", position),
+            SyncMessage::Position { position, paused } => format!(
+                "position {} {}",
+                position,
+                if paused { "paused" } else { "playing" }
+            ),
+        };
+        format!("{} {} {}", SYNC_MAGIC, SYNC_VERSION, body)
+    }
+
+    /// Parse a message, or `None` if it is not a sync message this release
+    /// understands
+    pub fn decode(message: &str) -> Option<Self> {
+        let mut words = message.split_whitespace();
+        if words.next()? != SYNC_MAGIC || words.next()?.parse::<u32>().ok()? != SYNC_VERSION {
+            return None;
+        }
+        let kind = words.next()?;
+        let position: f64 = words.next()?.parse().ok().filter(|p: &f64| p.is_finite())?;
+        let message = match kind {
+            "pause" => SyncMessage::Pause(position),
+            "resume" => SyncMessage::Resume(position),
+            "seek" => SyncMessage::Seek(position),
+            "position" => SyncMessage::Position {
+                position,
+                paused: match words.next()? {
+                    "paused" => true,
+                    "playing" => false,
+                    _ => return None,
+                },
+            },
+            _ => return None,
+        };
+        words.next().is_none().then_some(message)
+    }
+
+    /// How a player at `position`, paused or not, follows this message:
+    /// whether to be paused, and where to seek if the sender is too far off
+    pub fn follow(&self, position: f64, paused: bool) -> (bool, Option<f64>) {
+        let seek_if =
+            |target: f64, tolerance: f64| ((target - position).abs() > tolerance).then_some(target);
+        match *self {
+            SyncMessage::Pause(target) => (true, seek_if(target, PAUSE_TOLERANCE)),
+            SyncMessage::Resume(target) => (false, seek_if(target, PAUSE_TOLERANCE)),
+            SyncMessage::Seek(target) => (paused, Some(target)),
+            SyncMessage::Position {
+                position: target,
+                paused,
+            } => (paused, seek_if(target, DRIFT_THRESHOLD)),
+        }
+    }
+}
+
+/// Line senders of the open connections, by connection number
+type Peers = Arc<Mutex<Vec<(usize, UnboundedSender<String>)>>>;
+
+/// This player's end of a watch-together group
+pub struct SyncSession {
+    /// Whether this player hosts the group and keeps its time
+    hosting: bool,
+    local_addr: Option<SocketAddr>,
+    peers: Peers,
+    incoming: UnboundedReceiver<SyncMessage>,
+    last_heartbeat: Option<Instant>,
+    closed: bool,
+}
+
+impl SyncSession {
+    /// Listen for players joining on `addr`
+    pub async fn host(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to host watch-together on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+        info!("Hosting watch-together on {}", local_addr);
+
+        let peers = Peers::default();
+        let (sender, incoming) = mpsc::unbounded_channel();
+        let accepting = peers.clone();
+        tokio::spawn(async move {
+            let mut next_id = 0;
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        info!("{} joined the watch-together", peer);
+                        connect(stream, next_id, accepting.clone(), sender.clone(), true);
+                        next_id += 1;
+                    }
+                    Err(e) => warn!("Failed to accept a watch-together player: {}", e),
+                }
+            }
+        });
+
+        Ok(Self {
+            hosting: true,
+            local_addr: Some(local_addr),
+            peers,
+            incoming,
+            last_heartbeat: None,
+            closed: false,
+        })
+    }
+
+    /// Join the group hosted at `addr` (HOST:PORT)
+    pub async fn join(addr: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("Failed to join watch-together at {}", addr))?;
+        info!("Joined watch-together at {}", addr);
+
+        let peers = Peers::default();
+        let (sender, incoming) = mpsc::unbounded_channel();
+        connect(stream, 0, peers.clone(), sender, false);
+        Ok(Self {
+            hosting: false,
+            local_addr: None,
+            peers,
+            incoming,
+            last_heartbeat: None,
+            closed: false,
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.hosting
+    }
+
+    /// Address the host listens on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Number of players connected to this one
+    pub fn peer_count(&self) -> usize {
+        self.peers.lock().unwrap().len()
+    }
+
+    /// Tell the rest of the group about a change made here
+    pub fn send(&self, message: SyncMessage) {
+        debug!("Sync: sending {:?}", message);
+        send_line(&self.peers, None, &message.encode());
+    }
+
+    /// Send the host's position if it is time to; does nothing when joined
+    pub fn heartbeat(&mut self, position: f64, paused: bool) {
+        let due = self
+            .last_heartbeat
+            .is_none_or(|last| last.elapsed() >= HEARTBEAT_INTERVAL);
+        if self.hosting && due {
+            self.last_heartbeat = Some(Instant::now());
+            send_line(
+                &self.peers,
+                None,
+                &SyncMessage::Position { position, paused }.encode(),
+            );
+        }
+    }
+
+    /// Wait for the next message from the group; None once the host is gone
+    pub async fn next(&mut self) -> Option<SyncMessage> {
+        if self.closed {
+            return None;
+        }
+        let message = self.incoming.recv().await;
+        if message.is_none() {
+            warn!("Lost the watch-together host, playing on alone");
+            self.closed = true;
+        }
+        message
+    }
+}
+
+/// Send `line` to every peer but `except`
+fn send_line(peers: &Peers, except: Option<usize>, line: &str) {
+    for (id, sender) in peers.lock().unwrap().iter() {
+        if Some(*id) != except {
+            let _ = sender.send(line.to_string());
+        }
+    }
+}
+
+/// Read messages from `stream` into `incoming` and write the lines sent to
+/// peer `id` to it. The host relays what it reads to its other peers.
+fn connect(
+    stream: TcpStream,
+    id: usize,
+    peers: Peers,
+    incoming: UnboundedSender<SyncMessage>,
+    relay: bool,
+) {
+    let peer = stream.peer_addr().ok();
+    let (reader, mut writer) = stream.into_split();
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+    peers.lock().unwrap().push((id, sender));
+
+    tokio::spawn(async move {
+        while let Some(line) = outgoing.recv().await {
+            if writer
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let line = match read_line(&mut reader).await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Sync: {}", e);
+                    break;
+                }
+            };
+            let Some(message) = SyncMessage::decode(&line) else {
+                debug!("Sync: ignoring '{}'", line.trim());
+                continue;
+            };
+            if relay {
+                send_line(&peers, Some(id), &message.encode());
+            }
+            if incoming.send(message).is_err() {
+                break;
+            }
+        }
+        peers.lock().unwrap().retain(|(peer_id, _)| *peer_id != id);
+        if let Some(peer) = peer {
+            info!("{} left the watch-together", peer);
+        }
+    });
+}
+
+/// Read one line of at most `MAX_LINE_LENGTH` bytes; `None` once the peer
+/// hangs up
+async fn read_line(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = reader
+        .take(MAX_LINE_LENGTH as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LENGTH {
+        return Err(anyhow!("Line longer than {} bytes", MAX_LINE_LENGTH));
+    }
+    Ok(Some(String::from_utf8_lossy(&line).trim_end().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_messages() {
+        for message in [
+            SyncMessage::Pause(12.5),
+            SyncMessage::Resume(0.0),
+            SyncMessage::Seek(90.25),
+            SyncMessage::Position {
+                position: 3.0,
+                paused: true,
+            },
+        ] {
+            assert_eq!(SyncMessage::decode(&message.encode()), Some(message));
+        }
+        assert_eq!(
+            SyncMessage::decode("ascii-sync 1 seek 42"),
+            Some(SyncMessage::Seek(42.0))
+        );
+        assert_eq!(SyncMessage::decode("ascii-sync 2 seek 42"), None);
+        assert_eq!(SyncMessage::decode("ascii-sync 1 seek NaN"), None);
+        assert_eq!(SyncMessage::decode("ascii-wall 1 4.0 1.0"), None);
+    }
+
+    #[test]
+    fn test_following() {
+        assert_eq!(SyncMessage::Pause(10.1).follow(10.0, false), (true, None));
+        assert_eq!(
+            SyncMessage::Resume(20.0).follow(10.0, true),
+            (false, Some(20.0))
+        );
+        // Seeking keeps a paused player paused
+        assert_eq!(
+            SyncMessage::Seek(10.0).follow(10.0, true),
+            (true, Some(10.0))
+        );
+        let heartbeat = |position| SyncMessage::Position {
+            position,
+            paused: false,
+        };
+        assert_eq!(heartbeat(10.5).follow(10.0, true), (false, None));
+        assert_eq!(heartbeat(15.0).follow(10.0, false), (false, Some(15.0)));
+    }
+
+    #[test]
+    fn test_read_line_is_capped() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut input = "ascii-sync 1 seek 42\r\nlast".as_bytes();
+            assert_eq!(
+                read_line(&mut input).await.unwrap().as_deref(),
+                Some("ascii-sync 1 seek 42")
+            );
+            assert_eq!(
+                read_line(&mut input).await.unwrap().as_deref(),
+                Some("last")
+            );
+            assert_eq!(read_line(&mut input).await.unwrap(), None);
+
+            let flood = "x".repeat(MAX_LINE_LENGTH * 4);
+            assert!(read_line(&mut flood.as_bytes()).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_group_relay() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut host = SyncSession::host("127.0.0.1:0".parse().unwrap())
+                .await
+                .unwrap();
+            let addr = host.local_addr().unwrap().to_string();
+            let mut first = SyncSession::join(&addr).await.unwrap();
+            let mut second = SyncSession::join(&addr).await.unwrap();
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while host.peer_count() < 2 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(host.peer_count(), 2);
+
+            // A guest's pause reaches the host and the other guest
+            first.send(SyncMessage::Pause(4.0));
+            let wait = Duration::from_secs(2);
+            let received = tokio::time::timeout(wait, host.next()).await.unwrap();
+            assert_eq!(received, Some(SyncMessage::Pause(4.0)));
+            let received = tokio::time::timeout(wait, second.next()).await.unwrap();
+            assert_eq!(received, Some(SyncMessage::Pause(4.0)));
+
+            // Only the host sends its position
+            second.heartbeat(1.0, false);
+            host.heartbeat(4.0, true);
+            let heartbeat = SyncMessage::Position {
+                position: 4.0,
+                paused: true,
+            };
+            let received = tokio::time::timeout(wait, first.next()).await.unwrap();
+            assert_eq!(received, Some(heartbeat));
+            assert!(host.is_host() && !first.is_host());
+        });
+    }
+}
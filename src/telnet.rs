@@ -0,0 +1,426 @@
+//! Telnet protocol handling and ANSI encoding for remote clients
+//!
+//! Clients report their terminal type through the telnet TERMINAL-TYPE option
+//! (RFC 1091). It is mapped to a [`ColorDepth`], and each frame is encoded
+//! once per depth in use, so a plain VT100 is sent bare characters while a
+//! modern terminal gets truecolor escapes from the same converted frame.
+
+use crate::converter::{AsciiFrame, CellAttribute};
+use std::fmt::Write as _;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const TERMINAL_TYPE: u8 = 24;
+const TERMINAL_TYPE_IS: u8 = 0;
+const TERMINAL_TYPE_SEND: u8 = 1;
+
+/// Longest subnegotiation kept; TERMINAL-TYPE and NAWS replies fit easily,
+/// and anything past this is dropped
+const MAX_SUBNEGOTIATION: usize = 64;
+
+/// Sent on connect: the server echoes (so clients don't) and suppresses
+/// go-ahead, which puts most clients into character mode, and asks for the
+/// terminal type
+pub const NEGOTIATION: &[u8] = &[
+    IAC,
+    WILL,
+    ECHO,
+    IAC,
+    WILL,
+    SUPPRESS_GO_AHEAD,
+    IAC,
+    DO,
+    TERMINAL_TYPE,
+];
+
+/// Ask a client that agreed to TERMINAL-TYPE for its terminal name
+pub const REQUEST_TERMINAL_TYPE: &[u8] = &[IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_SEND, IAC, SE];
+
+/// Colors a remote terminal can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColorDepth {
+    /// Characters only
+    Mono,
+    /// The xterm 256-color palette
+    Ansi256,
+    /// 24-bit RGB
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Every depth, from least to most capable
+    pub const ALL: [ColorDepth; 3] = [ColorDepth::Mono, ColorDepth::Ansi256, ColorDepth::TrueColor];
+
+    /// Guess the depth from a reported terminal type such as `xterm-256color`.
+    /// Unknown terminals get plain characters, which display everywhere.
+    pub fn from_terminal_type(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        let contains_any = |words: &[&str]| words.iter().any(|word| name.contains(word));
+
+        if contains_any(&["truecolor", "24bit", "direct"])
+            || contains_any(&["kitty", "alacritty", "wezterm", "iterm", "foot", "contour"])
+        {
+            ColorDepth::TrueColor
+        } else if name.contains("256") {
+            ColorDepth::Ansi256
+        } else if name == "linux" || name.starts_with("vt") || contains_any(&["dumb", "unknown"]) {
+            ColorDepth::Mono
+        } else if contains_any(&[
+            "xterm", "screen", "tmux", "rxvt", "putty", "konsole", "gnome",
+        ]) {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Mono
+        }
+    }
+
+    /// Depth selected by a key press: `1` mono, `2` 256 colors, `3` truecolor
+    pub fn from_key(key: u8) -> Option<Self> {
+        match key {
+            b'1' => Some(ColorDepth::Mono),
+            b'2' => Some(ColorDepth::Ansi256),
+            b'3' => Some(ColorDepth::TrueColor),
+            _ => None,
+        }
+    }
+
+    /// Position in [`Self::ALL`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Something a client sent, with telnet commands decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelnetEvent {
+    /// A byte typed by the user
+    Data(u8),
+    /// The client agreed to report its terminal type
+    WillTerminalType,
+    /// The client's terminal type
+    TerminalType(String),
+}
+
+#[derive(Debug, Default)]
+enum ParseState {
+    #[default]
+    Data,
+    Command,
+    Option(u8),
+    Subnegotiation(Vec<u8>),
+    SubnegotiationCommand(Vec<u8>),
+}
+
+/// Incremental parser separating telnet commands from typed input
+#[derive(Debug, Default)]
+pub struct TelnetInput {
+    state: ParseState,
+}
+
+impl TelnetInput {
+    /// Create a parser
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse received bytes; commands may be split across calls
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<TelnetEvent> {
+        let mut events = Vec::new();
+
+        for &byte in bytes {
+            self.state = match std::mem::take(&mut self.state) {
+                ParseState::Data if byte == IAC => ParseState::Command,
+                ParseState::Data => {
+                    events.push(TelnetEvent::Data(byte));
+                    ParseState::Data
+                }
+                ParseState::Command => match byte {
+                    IAC => {
+                        events.push(TelnetEvent::Data(IAC));
+                        ParseState::Data
+                    }
+                    SB => ParseState::Subnegotiation(Vec::new()),
+                    WILL..=DONT => ParseState::Option(byte),
+                    _ => ParseState::Data,
+                },
+                ParseState::Option(command) => {
+                    if command == WILL && byte == TERMINAL_TYPE {
+                        events.push(TelnetEvent::WillTerminalType);
+                    }
+                    ParseState::Data
+                }
+                ParseState::Subnegotiation(buffer) if byte == IAC => {
+                    ParseState::SubnegotiationCommand(buffer)
+                }
+                ParseState::Subnegotiation(mut buffer) => {
+                    if buffer.len() < MAX_SUBNEGOTIATION {
+                        buffer.push(byte);
+                    }
+                    ParseState::Subnegotiation(buffer)
+                }
+                ParseState::SubnegotiationCommand(buffer) if byte == SE => {
+                    if let [TERMINAL_TYPE, TERMINAL_TYPE_IS, name @ ..] = buffer.as_slice() {
+                        events.push(TelnetEvent::TerminalType(
+                            String::from_utf8_lossy(name).into_owned(),
+                        ));
+                    }
+                    ParseState::Data
+                }
+                ParseState::SubnegotiationCommand(mut buffer) => {
+                    // IAC IAC inside a subnegotiation is a literal 255
+                    if buffer.len() < MAX_SUBNEGOTIATION {
+                        buffer.push(byte);
+                    }
+                    ParseState::Subnegotiation(buffer)
+                }
+            };
+        }
+
+        events
+    }
+}
+
+/// Nearest color in the xterm 256-color palette
+pub fn ansi256(color: (u8, u8, u8)) -> u8 {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    };
+    let distance = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+    };
+
+    let (r, g, b) = (level(color.0), level(color.1), level(color.2));
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube_color = (
+        CUBE_LEVELS[r as usize],
+        CUBE_LEVELS[g as usize],
+        CUBE_LEVELS[b as usize],
+    );
+
+    // The grayscale ramp runs from 8 to 238 in steps of 10
+    let average = (color.0 as u32 + color.1 as u32 + color.2 as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_step;
+
+    if distance(color, (gray, gray, gray)) < distance(color, cube_color) {
+        232 + gray_step
+    } else {
+        cube_index
+    }
+}
+
+fn push_color(output: &mut String, layer: u8, color: (u8, u8, u8), depth: ColorDepth) {
+    let _ = match depth {
+        ColorDepth::TrueColor => write!(
+            output,
+            "\x1b[{};2;{};{};{}m",
+            layer, color.0, color.1, color.2
+        ),
+        ColorDepth::Ansi256 => write!(output, "\x1b[{};5;{}m", layer, ansi256(color)),
+        ColorDepth::Mono => Ok(()),
+    };
+}
+
+/// Encode a frame as ANSI text for a remote terminal.
+///
+/// The cursor is homed rather than the screen cleared, so clients redraw in
+/// place without flicker. Color escapes are only emitted when a color changes.
+pub fn encode_frame(frame: &AsciiFrame, depth: ColorDepth, status: &str) -> String {
+    let width = frame.width as usize;
+    let mut output = String::with_capacity(frame.characters.len() * 4 + 64);
+    output.push_str("\x1b[H");
+    push_rows(&mut output, frame, depth, "\x1b[K\r\n");
+
+    let status: String = status.chars().take(width).collect();
+    let _ = write!(output, "\x1b[7m{}\x1b[0m\x1b[K", status);
+    output
+}
+
+/// Encode a frame as standalone text, one line per row, with colors unless
+/// `depth` is mono. Used for copying frames to the clipboard.
+pub fn encode_text(frame: &AsciiFrame, depth: ColorDepth) -> String {
+    let mut output = String::with_capacity(frame.characters.len() * 4);
+    push_rows(&mut output, frame, depth, "\n");
+    output
+}
+
+/// Append each row of `frame`, ending every row with `row_end`
+fn push_rows(output: &mut String, frame: &AsciiFrame, depth: ColorDepth, row_end: &str) {
+    let width = frame.width as usize;
+    let use_colors = depth != ColorDepth::Mono;
+    for (row, characters) in frame.characters.chunks(width.max(1)).enumerate() {
+        let mut current_fg = None;
+        let mut current_bg = None;
+        let mut current_attribute = CellAttribute::Normal;
+
+        for (column, &character) in characters.iter().enumerate() {
+            let index = row * width + column;
+            // Cells missing from a short color or attribute list keep the
+            // current style rather than panicking
+            if use_colors {
+                if let Some(&fg) = frame.fg_colors.get(index) {
+                    if current_fg != Some(fg) {
+                        push_color(output, 38, fg, depth);
+                        current_fg = Some(fg);
+                    }
+                }
+                if let Some(&bg) = frame
+                    .bg_colors
+                    .as_ref()
+                    .and_then(|colors| colors.get(index))
+                {
+                    if current_bg != Some(bg) {
+                        push_color(output, 48, bg, depth);
+                        current_bg = Some(bg);
+                    }
+                }
+            }
+            if let Some(&attribute) = frame
+                .attributes
+                .as_ref()
+                .and_then(|attributes| attributes.get(index))
+            {
+                if attribute != current_attribute {
+                    output.push_str(match attribute {
+                        CellAttribute::Normal => "\x1b[22m",
+                        CellAttribute::Bold => "\x1b[22;1m",
+                        CellAttribute::Dim => "\x1b[22;2m",
+                    });
+                    current_attribute = attribute;
+                }
+            }
+            output.push(character);
+        }
+
+        if use_colors || current_attribute != CellAttribute::Normal {
+            output.push_str("\x1b[0m");
+        }
+        output.push_str(row_end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_type_depth() {
+        assert_eq!(
+            ColorDepth::from_terminal_type("XTERM-256COLOR"),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_terminal_type("xterm-kitty"),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(ColorDepth::from_terminal_type("xterm"), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::from_terminal_type("VT100"), ColorDepth::Mono);
+        assert_eq!(ColorDepth::from_terminal_type("linux"), ColorDepth::Mono);
+        assert_eq!(ColorDepth::from_terminal_type("adm3a"), ColorDepth::Mono);
+    }
+
+    #[test]
+    fn test_parse_terminal_type_negotiation() {
+        let mut input = TelnetInput::new();
+        assert_eq!(
+            input.feed(&[IAC, WILL, TERMINAL_TYPE, b'q']),
+            vec![TelnetEvent::WillTerminalType, TelnetEvent::Data(b'q')]
+        );
+
+        // Split across reads
+        let mut reply = vec![IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_IS];
+        reply.extend_from_slice(b"XTERM-256COLOR");
+        reply.extend_from_slice(&[IAC, SE]);
+        assert!(input.feed(&reply[..6]).is_empty());
+        assert_eq!(
+            input.feed(&reply[6..]),
+            vec![TelnetEvent::TerminalType("XTERM-256COLOR".to_string())]
+        );
+
+        // Other options are skipped, escaped 255 is data
+        assert_eq!(
+            input.feed(&[IAC, DO, ECHO, IAC, IAC]),
+            vec![TelnetEvent::Data(IAC)]
+        );
+    }
+
+    #[test]
+    fn test_long_subnegotiation_is_capped() {
+        let mut input = TelnetInput::new();
+        let mut reply = vec![IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_IS];
+        reply.extend(std::iter::repeat_n(b'x', 100_000));
+        assert!(input.feed(&reply).is_empty());
+        match &input.state {
+            ParseState::Subnegotiation(buffer) => assert_eq!(buffer.len(), MAX_SUBNEGOTIATION),
+            state => panic!("unexpected state {:?}", state),
+        }
+
+        // The end still parses, with the name cut short
+        let events = input.feed(&[IAC, SE, b'q']);
+        assert_eq!(
+            events,
+            vec![
+                TelnetEvent::TerminalType("x".repeat(MAX_SUBNEGOTIATION - 2)),
+                TelnetEvent::Data(b'q')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256((0, 0, 0)), 16);
+        assert_eq!(ansi256((255, 255, 255)), 231);
+        assert_eq!(ansi256((255, 0, 0)), 196);
+        assert_eq!(ansi256((128, 128, 128)), 244);
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        let frame = AsciiFrame {
+            characters: vec!['a', 'b', 'c', 'd'],
+            fg_colors: vec![(1, 2, 3), (1, 2, 3), (9, 9, 9), (9, 9, 9)],
+            bg_colors: None,
+            attributes: None,
+            width: 2,
+            height: 2,
+            timestamp: 0.0,
+            frame_number: 0,
+        };
+
+        let plain = encode_frame(&frame, ColorDepth::Mono, "status");
+        assert_eq!(
+            plain,
+            "\x1b[Hab\x1b[K\r\ncd\x1b[K\r\n\x1b[7mst\x1b[0m\x1b[K"
+        );
+
+        // Repeated colors are not re-sent
+        let colored = encode_frame(&frame, ColorDepth::TrueColor, "");
+        assert_eq!(colored.matches("\x1b[38;2;1;2;3m").count(), 1);
+        assert_eq!(colored.matches("\x1b[38;2;9;9;9m").count(), 1);
+
+        let palette = encode_frame(&frame, ColorDepth::Ansi256, "");
+        assert_eq!(palette.matches("\x1b[38;5;16m").count(), 1);
+        assert_eq!(palette.matches("\x1b[38;5;232m").count(), 1);
+
+        let styled = frame.with_attributes(vec![
+            CellAttribute::Bold,
+            CellAttribute::Normal,
+            CellAttribute::Dim,
+            CellAttribute::Dim,
+        ]);
+        assert_eq!(
+            encode_text(&styled, ColorDepth::Mono),
+            "\x1b[22;1ma\x1b[22mb\n\x1b[22;2mcd\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Terminal capability detection
+//!
+//! At startup the player works out what the terminal can show, so playing
+//! a file needs no flags to look right: colors are sent at the depth the
+//! terminal supports, terminals without a UTF-8 locale get an ASCII ramp,
+//! frames are drawn as synchronized updates where the terminal supports
+//! them, and the known cell shape replaces the usual 1:2 guess when fitting
+//! the picture.
+//!
+//! Most of this comes from the environment (`$COLORTERM`, `$TERM`,
+//! `$TERM_PROGRAM`, the locale). On Unix the terminal is also asked
+//! directly: whether it supports synchronized output (DECRQM 2026), kitty
+//! graphics, and sixel (in its device attributes). The device attributes
+//! query goes last; every terminal answers it, so the probe ends as soon
+//! as the answers are in instead of waiting for a timeout.
+
+use crate::telnet::ColorDepth;
+use log::debug;
+
+/// Longest wait for the terminal to answer a query
+#[cfg(unix)]
+const QUERY_TIMEOUT_MS: i32 = 200;
+
+/// What the terminal running the player can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TermCaps {
+    /// Colors the terminal can show
+    pub color_depth: ColorDepth,
+    /// The locale is UTF-8, so block and shade glyphs display
+    pub unicode: bool,
+    /// Sixel graphics
+    pub sixel: bool,
+    /// The kitty graphics protocol
+    pub kitty_graphics: bool,
+    /// Frames can be drawn as synchronized updates (DEC mode 2026)
+    pub synchronized_output: bool,
+    /// Size of one cell in pixels, as `(width, height)`, if reported
+    pub cell_pixels: Option<(u16, u16)>,
+}
+
+impl Default for TermCaps {
+    /// What the player assumed before detection: truecolor and Unicode
+    fn default() -> Self {
+        Self {
+            color_depth: ColorDepth::TrueColor,
+            unicode: true,
+            sixel: false,
+            kitty_graphics: false,
+            synchronized_output: false,
+            cell_pixels: None,
+        }
+    }
+}
+
+impl TermCaps {
+    /// Probe the terminal on stdout
+    pub fn detect() -> Self {
+        let mut caps = Self::from_env(|name| std::env::var(name).ok());
+        caps.cell_pixels = crossterm::terminal::window_size()
+            .ok()
+            .and_then(|size| cell_pixels((size.columns, size.rows), (size.width, size.height)));
+        if let Some(reply) = query_terminal() {
+            caps.apply_replies(&reply);
+        }
+        debug!("Terminal capabilities: {:?}", caps);
+        caps
+    }
+
+    /// Capabilities known from environment variables, read with `var`
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let term = var("TERM").unwrap_or_default().to_ascii_lowercase();
+        let program = var("TERM_PROGRAM").unwrap_or_default().to_ascii_lowercase();
+        let is_program = |names: &[&str]| names.iter().any(|name| program.contains(name));
+
+        let color_depth = if var("COLORTERM")
+            .is_some_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+            || var("WT_SESSION").is_some()
+            || is_program(&["iterm", "wezterm", "vscode", "ghostty"])
+        {
+            ColorDepth::TrueColor
+        } else if term.is_empty() {
+            // No terminal type at all, e.g. the Windows console: keep the
+            // player's usual output
+            ColorDepth::TrueColor
+        } else {
+            ColorDepth::from_terminal_type(&term)
+        };
+
+        // The first locale variable set decides, as in setlocale(3);
+        // Windows consoles have no locale variables but show Unicode
+        let unicode = match var("LC_ALL")
+            .or_else(|| var("LC_CTYPE"))
+            .or_else(|| var("LANG"))
+        {
+            Some(locale) => {
+                let locale = locale.to_ascii_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            }
+            None => cfg!(windows),
+        };
+
+        let kitty_graphics = term.contains("kitty")
+            || var("KITTY_WINDOW_ID").is_some()
+            || is_program(&["wezterm", "ghostty"]);
+        let sixel = term.contains("sixel")
+            || ["foot", "mlterm", "contour"]
+                .iter()
+                .any(|name| term.starts_with(name))
+            || is_program(&["wezterm"]);
+        let synchronized_output = ["kitty", "foot", "alacritty", "contour", "wezterm"]
+            .iter()
+            .any(|name| term.contains(name))
+            || var("WT_SESSION").is_some()
+            || is_program(&["iterm", "wezterm", "ghostty", "vscode"]);
+
+        Self {
+            color_depth,
+            unicode,
+            sixel,
+            kitty_graphics,
+            synchronized_output,
+            cell_pixels: None,
+        }
+    }
+
+    /// Width over height of a cell, if its pixel size is known
+    pub fn cell_aspect(&self) -> Option<f64> {
+        self.cell_pixels
+            .map(|(width, height)| width as f64 / height as f64)
+    }
+
+    /// Update the capabilities from the terminal's answers to the probe
+    fn apply_replies(&mut self, reply: &str) {
+        // DECRPM: 1 set, 2 reset, both mean the mode is supported
+        if let Some(state) = reply
+            .split_once("\x1b[?2026;")
+            .and_then(|(_, rest)| rest.chars().next())
+        {
+            self.synchronized_output = matches!(state, '1' | '2');
+        }
+        if reply.contains("\x1b_Gi=31;OK") {
+            self.kitty_graphics = true;
+        }
+        // Device attributes list the extensions; 4 is sixel
+        if let Some((attributes, _)) = reply
+            .rsplit_once("\x1b[?")
+            .and_then(|(_, last)| last.split_once('c'))
+        {
+            self.sixel |= attributes.split(';').skip(1).any(|value| value == "4");
+        }
+    }
+}
+
+/// Size of one cell from the terminal size in cells and in pixels; None
+/// when the terminal does not report pixels
+fn cell_pixels((columns, rows): (u16, u16), (width, height): (u16, u16)) -> Option<(u16, u16)> {
+    if columns == 0 || rows == 0 {
+        return None;
+    }
+    let cell = (width / columns, height / rows);
+    (cell.0 > 0 && cell.1 > 0).then_some(cell)
+}
+
+/// Ask about synchronized output, kitty graphics and device attributes
+fn query_terminal() -> Option<String> {
+    const PROBE: &str = concat!(
+        "\x1b[?2026$p",
+        "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\",
+        "\x1b[c",
+    );
+    // Everything is in once the device attributes, answered last, are
+    let reply = query(PROBE, |reply| {
+        let reply = String::from_utf8_lossy(reply);
+        reply
+            .rsplit_once("\x1b[?")
+            .is_some_and(|(_, last)| last.contains('c'))
+    })?;
+    Some(String::from_utf8_lossy(&reply).into_owned())
+}
+
+/// Send `request` to the terminal and collect its reply until `done` says
+/// it is complete or the terminal stops answering. None when stdin and
+/// stdout are not a terminal.
+#[cfg(unix)]
+pub(crate) fn query(request: &str, done: impl Fn(&[u8]) -> bool) -> Option<Vec<u8>> {
+    use crossterm::terminal;
+    use std::io::{IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let was_raw = terminal::is_raw_mode_enabled().unwrap_or(false);
+    if !was_raw {
+        terminal::enable_raw_mode().ok()?;
+    }
+
+    let mut stdout = std::io::stdout();
+    let sent = write!(stdout, "{}", request).and_then(|_| stdout.flush());
+    let mut reply = Vec::new();
+    if sent.is_ok() {
+        let mut poll = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Terminals ignoring the request stay silent, hence the timeout
+        while unsafe { libc::poll(&mut poll, 1, QUERY_TIMEOUT_MS) } > 0 {
+            let mut buffer = [0u8; 64];
+            let read = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), 64) };
+            if read <= 0 {
+                break;
+            }
+            reply.extend_from_slice(&buffer[..read as usize]);
+            if done(&reply) {
+                break;
+            }
+        }
+    }
+
+    if !was_raw {
+        let _ = terminal::disable_raw_mode();
+    }
+    Some(reply)
+}
+
+/// Terminal queries need Unix; other platforms rely on the environment
+#[cfg(not(unix))]
+pub(crate) fn query(_request: &str, _done: impl Fn(&[u8]) -> bool) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn caps(vars: &[(&str, &str)]) -> TermCaps {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        TermCaps::from_env(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_caps_from_env() {
+        let kitty = caps(&[("TERM", "xterm-kitty"), ("LANG", "en_US.UTF-8")]);
+        assert_eq!(kitty.color_depth, ColorDepth::TrueColor);
+        assert!(kitty.unicode && kitty.kitty_graphics && kitty.synchronized_output);
+        assert!(!kitty.sixel);
+
+        let xterm = caps(&[
+            ("TERM", "xterm-256color"),
+            ("LANG", "en_US.UTF-8"),
+            ("LC_ALL", "C"),
+        ]);
+        assert_eq!(xterm.color_depth, ColorDepth::Ansi256);
+        assert!(!xterm.unicode && !xterm.synchronized_output);
+
+        // COLORTERM wins over the terminal type
+        let gnome = caps(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]);
+        assert_eq!(gnome.color_depth, ColorDepth::TrueColor);
+
+        assert_eq!(caps(&[("TERM", "dumb")]).color_depth, ColorDepth::Mono);
+        assert_eq!(caps(&[]).color_depth, ColorDepth::TrueColor);
+        assert!(caps(&[("TERM", "foot"), ("LC_CTYPE", "de_DE.utf8")]).sixel);
+    }
+
+    #[test]
+    fn test_probe_replies() {
+        let mut caps = TermCaps::default();
+        caps.apply_replies("\x1b[?2026;2$y\x1b_Gi=31;OK\x1b\\\x1b[?62;4;22c");
+        assert!(caps.synchronized_output && caps.kitty_graphics && caps.sixel);
+
+        // Mode 2026 unknown (0) and no sixel in the attributes
+        let mut caps = TermCaps {
+            synchronized_output: true,
+            ..TermCaps::default()
+        };
+        caps.apply_replies("\x1b[?2026;0$y\x1b[?64;1;22c");
+        assert!(!caps.synchronized_output && !caps.kitty_graphics && !caps.sixel);
+
+        assert_eq!(cell_pixels((80, 24), (800, 480)), Some((10, 20)));
+        assert_eq!(cell_pixels((80, 24), (0, 0)), None);
+        let caps = TermCaps {
+            cell_pixels: Some((9, 18)),
+            ..TermCaps::default()
+        };
+        assert_eq!(caps.cell_aspect(), Some(0.5));
+    }
+}
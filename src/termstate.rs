@@ -0,0 +1,253 @@
+//! Crash-safe record of the terminal modes the player changed
+//!
+//! While playing, the terminal is in raw mode with the cursor hidden and
+//! possibly focus reporting on. A player killed before it can clean up
+//! (`kill -9`, a closed SSH session, an abort) leaves the shell unusable
+//! until the user thinks of running `reset`. The renderer therefore writes
+//! a small state file when it changes the terminal and removes it when it
+//! restores it. A state file left by a process that is no longer running
+//! means that process died mid-playback: the next start on the same
+//! terminal puts it back, as does `ascii-player fix-terminal`.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Terminal modes changed by one running player
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminalState {
+    pub pid: u32,
+    /// Terminal device the player ran on, if known
+    pub tty: Option<String>,
+    pub raw_mode: bool,
+    pub cursor_hidden: bool,
+    pub focus_events: bool,
+    pub synchronized_output: bool,
+}
+
+impl TerminalState {
+    /// State of this process on the current terminal, no modes changed yet
+    pub fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            tty: current_tty(),
+            ..Self::default()
+        }
+    }
+
+    /// Every mode the player may change, for resetting blind
+    pub fn all() -> Self {
+        Self {
+            raw_mode: true,
+            cursor_hidden: true,
+            focus_events: true,
+            synchronized_output: true,
+            ..Self::current()
+        }
+    }
+
+    /// Escape sequences undoing the recorded modes
+    pub fn reset_sequences(&self) -> String {
+        let mut sequences = String::from("\x1b[0m");
+        if self.synchronized_output {
+            sequences.push_str("\x1b[?2026l");
+        }
+        if self.focus_events {
+            sequences.push_str("\x1b[?1004l");
+        }
+        if self.cursor_hidden {
+            sequences.push_str("\x1b[?25h");
+        }
+        sequences
+    }
+
+    /// Save the state, replacing this process's earlier record
+    pub fn record(&self) -> Result<()> {
+        let path = state_path(self.pid)
+            .ok_or_else(|| anyhow!("Cannot determine state location (HOME is not set)"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Remove this process's record once the terminal is restored
+pub fn clear() {
+    if let Some(path) = state_path(std::process::id()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Restore the current terminal if a player that ran on it died without
+/// cleaning up; returns whether anything was reset
+pub fn recover() -> bool {
+    // Without a terminal there is nothing to restore, and no way to tell
+    // which records belong here
+    let Some(tty) = current_tty() else {
+        return false;
+    };
+    let stale: Vec<_> = records()
+        .into_iter()
+        .filter(|(_, state)| state.tty.as_ref() == Some(&tty) && !is_running(state.pid))
+        .collect();
+    let Some((_, state)) = stale.first() else {
+        return false;
+    };
+
+    info!(
+        "Restoring the terminal left in raw mode by process {}",
+        state.pid
+    );
+    if let Err(e) = reset(state) {
+        debug!("Failed to reset the terminal: {}", e);
+    }
+    for (path, _) in &stale {
+        let _ = std::fs::remove_file(path);
+    }
+    true
+}
+
+/// Reset every mode the player may have changed on the current terminal,
+/// whatever the records say, and drop the records of dead players on it
+pub fn fix_terminal() -> Result<()> {
+    reset(&TerminalState::all())?;
+    let tty = current_tty();
+    for (path, state) in records() {
+        if state.tty == tty && !is_running(state.pid) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
+
+/// Put the terminal back into the modes undone by `state`
+fn reset(state: &TerminalState) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{}", state.reset_sequences())?;
+    stdout.flush()?;
+    if state.raw_mode {
+        restore_line_mode()?;
+    }
+    Ok(())
+}
+
+/// Directory holding one state file per running player.
+///
+/// `$XDG_STATE_HOME/ascii-player/terminal`, falling back to
+/// `~/.local/state/ascii-player/terminal`.
+fn state_dir() -> Option<PathBuf> {
+    let state_dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(state_dir.join("ascii-player").join("terminal"))
+}
+
+fn state_path(pid: u32) -> Option<PathBuf> {
+    Some(state_dir()?.join(format!("{}.json", pid)))
+}
+
+/// Every readable record, with its file
+fn records() -> Vec<(PathBuf, TerminalState)> {
+    let Some(entries) = state_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let state = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+            Some((path, state))
+        })
+        .collect()
+}
+
+/// Device of the terminal on stdout, e.g. `/dev/pts/3`
+#[cfg(unix)]
+fn current_tty() -> Option<String> {
+    let name = unsafe { libc::ttyname(libc::STDOUT_FILENO) };
+    if name.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn current_tty() -> Option<String> {
+    None
+}
+
+/// Whether process `pid` still exists
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks; EPERM means it exists under another user
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Other platforms cannot tell, so records are only cleared by
+/// `fix-terminal`
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+/// Turn line editing, echo and output processing back on. The raw mode
+/// was entered by another process, so there is no saved mode to go back
+/// to; these are the flags `stty sane` sets.
+#[cfg(unix)]
+fn restore_line_mode() -> Result<()> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        termios.c_iflag |= libc::ICRNL | libc::IXON | libc::BRKINT;
+        termios.c_oflag |= libc::OPOST | libc::ONLCR;
+        termios.c_lflag |= libc::ICANON | libc::ECHO | libc::ECHOE | libc::ISIG | libc::IEXTEN;
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_line_mode() -> Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_sequences() {
+        let state = TerminalState {
+            cursor_hidden: true,
+            ..TerminalState::default()
+        };
+        assert_eq!(state.reset_sequences(), "\x1b[0m\x1b[?25h");
+        assert_eq!(
+            TerminalState::all().reset_sequences(),
+            "\x1b[0m\x1b[?2026l\x1b[?1004l\x1b[?25h"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_running() {
+        assert!(is_running(std::process::id()));
+        assert!(!is_running(u32::MAX));
+    }
+}
@@ -0,0 +1,124 @@
+//! A one-line text field for typing over the picture
+//!
+//! Keys edit the text at a cursor, as in a shell prompt: arrows, Home and
+//! End move it, Backspace and Delete remove around it. [`TextInput::visible`]
+//! gives the part of the line around the cursor that fits a width, with the
+//! cursor drawn in, ready for an overlay.
+
+use crossterm::event::KeyCode;
+
+/// Character drawn at the cursor
+pub const CURSOR: char = '█';
+
+/// What a key press did to a [`TextInput`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputResult {
+    Editing,
+    /// Enter: the text is done
+    Submitted,
+    /// Esc: the text is abandoned
+    Cancelled,
+}
+
+/// Text being typed, with a cursor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextInput {
+    text: Vec<char>,
+    /// Characters before the cursor
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> InputResult {
+        match code {
+            KeyCode::Enter => return InputResult::Submitted,
+            KeyCode::Esc => return InputResult::Cancelled,
+            KeyCode::Char(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            _ => {}
+        }
+        InputResult::Editing
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// At most `width` characters of the text with [`CURSOR`] at the
+    /// cursor, scrolled to keep the cursor in view
+    pub fn visible(&self, width: usize) -> String {
+        self.visible_with(width, CURSOR)
+    }
+
+    /// Like [`TextInput::visible`], drawing the cursor as `cursor`
+    pub fn visible_with(&self, width: usize, cursor: char) -> String {
+        let mut line = self.text.clone();
+        if self.cursor == line.len() {
+            line.push(cursor);
+        } else {
+            line[self.cursor] = cursor;
+        }
+        let width = width.max(1);
+        let start = (self.cursor + 1).saturating_sub(width);
+        line.iter().skip(start).take(width).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> TextInput {
+        let mut input = TextInput::new();
+        for c in text.chars() {
+            input.handle_key(KeyCode::Char(c));
+        }
+        input
+    }
+
+    #[test]
+    fn test_editing_at_the_cursor() {
+        let mut input = typed("goal");
+        assert_eq!(input.visible(20), "goal█");
+        input.handle_key(KeyCode::Home);
+        for c in "late ".chars() {
+            input.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(input.text(), "late goal");
+        input.handle_key(KeyCode::Delete);
+        input.handle_key(KeyCode::Backspace);
+        assert_eq!(input.text(), "lateoal");
+        assert_eq!(input.visible(20), "late█al");
+        assert_eq!(input.handle_key(KeyCode::Enter), InputResult::Submitted);
+        assert_eq!(input.handle_key(KeyCode::Esc), InputResult::Cancelled);
+    }
+
+    #[test]
+    fn test_scrolls_to_the_cursor() {
+        let mut input = typed("offside at the far post");
+        assert_eq!(input.visible(6), " post█");
+        input.handle_key(KeyCode::Home);
+        assert_eq!(input.visible(6), "█ffsid");
+    }
+}
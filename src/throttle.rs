@@ -0,0 +1,283 @@
+//! Staying under an output bandwidth budget
+//!
+//! Over a slow SSH link a terminal takes frames slower than the player
+//! writes them, and playback falls further and further behind. With
+//! `--max-bandwidth 200kbps` the bytes written to the terminal are measured,
+//! and while they exceed the budget the output is made cheaper one step at a
+//! time: first only cells that visibly changed are redrawn, then colors drop
+//! to the 256-color palette, then frames are skipped, and last colors go.
+//! Once output has stayed well under the budget for a while, a step is
+//! taken back.
+
+use crate::telnet::ColorDepth;
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Span the output rate is measured over
+const WINDOW: Duration = Duration::from_secs(2);
+/// Time at a step before going down another, so the rate reflects it
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+/// Time well under the budget before going back up a step
+const RECOVER_TIME: Duration = Duration::from_secs(10);
+/// Share of the budget output must stay under to go back up; every step
+/// at most halves the output, so going back stays within the budget
+const RECOVER_SHARE: f64 = 0.5;
+
+/// A data rate in bytes per second, parsed from bits (`200kbps`, `1.5Mbps`)
+/// or bytes per second (`25KB/s`, `64KiB/s`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bandwidth(pub u64);
+
+impl FromStr for Bandwidth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| anyhow!("Invalid bandwidth '{}'", s))?;
+
+        let unit = unit.trim().to_ascii_lowercase();
+        let bytes_per_second = if let Some(prefix) = unit.strip_suffix("bps") {
+            let multiplier = match prefix {
+                "" => 1.0,
+                "k" => 1e3,
+                "m" => 1e6,
+                "g" => 1e9,
+                _ => return Err(anyhow!("Unknown bandwidth unit in '{}'", s)),
+            };
+            number * multiplier / 8.0
+        } else if let Some(prefix) = unit.strip_suffix("/s") {
+            let prefix = prefix
+                .strip_suffix("ib")
+                .or_else(|| prefix.strip_suffix('b'))
+                .unwrap_or(prefix);
+            let multiplier: u64 = match prefix {
+                "" => 1,
+                "k" => 1 << 10,
+                "m" => 1 << 20,
+                "g" => 1 << 30,
+                _ => return Err(anyhow!("Unknown bandwidth unit in '{}'", s)),
+            };
+            number * multiplier as f64
+        } else {
+            return Err(anyhow!(
+                "Bandwidth '{}' needs a unit, e.g. 200kbps or 64KiB/s",
+                s
+            ));
+        };
+        if bytes_per_second < 1.0 {
+            return Err(anyhow!("Bandwidth '{}' is too low", s));
+        }
+        Ok(Self(bytes_per_second.round() as u64))
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}kbps", kilobits(self.0 as f64))
+    }
+}
+
+fn kilobits(bytes_per_second: f64) -> f64 {
+    bytes_per_second * 8.0 / 1000.0
+}
+
+/// How faithfully frames are drawn at a step of the throttle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fidelity {
+    /// Redraw only cells that visibly differ from the screen
+    pub diff_only: bool,
+    /// Most colors to send, whatever the terminal shows
+    pub max_depth: ColorDepth,
+    /// Draw every nth frame
+    pub frame_divisor: u32,
+    /// What this step gives up, for the status line
+    pub label: &'static str,
+}
+
+/// Steps from full output to the cheapest, each at most halving the bytes
+const STEPS: [Fidelity; 6] = [
+    Fidelity {
+        diff_only: false,
+        max_depth: ColorDepth::TrueColor,
+        frame_divisor: 1,
+        label: "",
+    },
+    Fidelity {
+        diff_only: true,
+        max_depth: ColorDepth::TrueColor,
+        frame_divisor: 1,
+        label: "changes only",
+    },
+    Fidelity {
+        diff_only: true,
+        max_depth: ColorDepth::Ansi256,
+        frame_divisor: 1,
+        label: "256 colors",
+    },
+    Fidelity {
+        diff_only: true,
+        max_depth: ColorDepth::Ansi256,
+        frame_divisor: 2,
+        label: "256 colors, 1/2 rate",
+    },
+    Fidelity {
+        diff_only: true,
+        max_depth: ColorDepth::Ansi256,
+        frame_divisor: 4,
+        label: "256 colors, 1/4 rate",
+    },
+    Fidelity {
+        diff_only: true,
+        max_depth: ColorDepth::Mono,
+        frame_divisor: 4,
+        label: "no colors, 1/4 rate",
+    },
+];
+
+/// Steps output fidelity down and up to keep the measured rate in budget
+#[derive(Debug, Clone)]
+pub struct OutputThrottle {
+    budget: Bandwidth,
+    /// Total bytes written as of each sample, oldest first
+    samples: VecDeque<(Instant, u64)>,
+    step: usize,
+    /// When the step last changed, or the throttle started
+    changed: Instant,
+    /// Since when output has been well under the budget
+    under_since: Option<Instant>,
+}
+
+impl OutputThrottle {
+    pub fn new(budget: Bandwidth) -> Self {
+        Self {
+            budget,
+            samples: VecDeque::new(),
+            step: 0,
+            changed: Instant::now(),
+            under_since: None,
+        }
+    }
+
+    /// Record that `total_bytes` have been written as of `now`. Returns the
+    /// new fidelity when the output should change.
+    pub fn record(&mut self, total_bytes: u64, now: Instant) -> Option<Fidelity> {
+        self.samples.push_back((now, total_bytes));
+        while self
+            .samples
+            .front()
+            .is_some_and(|&(time, _)| now.saturating_duration_since(time) > WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        let rate = self.rate()?;
+        let budget = self.budget.0 as f64;
+
+        if rate <= budget * RECOVER_SHARE {
+            self.under_since.get_or_insert(now);
+        } else {
+            self.under_since = None;
+        }
+
+        let settled = now.saturating_duration_since(self.changed) >= SETTLE_TIME;
+        let recovered = self
+            .under_since
+            .is_some_and(|since| now.saturating_duration_since(since) >= RECOVER_TIME);
+        if rate > budget && settled && self.step + 1 < STEPS.len() {
+            self.step += 1;
+        } else if recovered && self.step > 0 {
+            self.step -= 1;
+        } else {
+            return None;
+        }
+        // Measure the new step afresh
+        self.changed = now;
+        self.under_since = None;
+        self.samples.clear();
+        self.samples.push_back((now, total_bytes));
+        Some(self.fidelity())
+    }
+
+    /// Bytes per second written over the last samples, None until they
+    /// span long enough to tell
+    pub fn rate(&self) -> Option<f64> {
+        let (&(first, first_bytes), &(last, last_bytes)) =
+            (self.samples.front()?, self.samples.back()?);
+        let span = last.saturating_duration_since(first).as_secs_f64();
+        (span >= WINDOW.as_secs_f64() / 4.0)
+            .then(|| last_bytes.saturating_sub(first_bytes) as f64 / span)
+    }
+
+    /// How frames are drawn now
+    pub fn fidelity(&self) -> Fidelity {
+        STEPS[self.step]
+    }
+
+    /// Measured rate against the budget, with what is given up, e.g.
+    /// "180/200kbps 256 colors"
+    pub fn status(&self) -> String {
+        let rate = match self.rate() {
+            Some(rate) => format!("{:.0}", kilobits(rate)),
+            None => "--".to_string(),
+        };
+        let mut status = format!("{}/{}", rate, self.budget);
+        let label = self.fidelity().label;
+        if !label.is_empty() {
+            status.push(' ');
+            status.push_str(label);
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bandwidth() {
+        assert_eq!("200kbps".parse::<Bandwidth>().unwrap().0, 25_000);
+        assert_eq!("1.5Mbps".parse::<Bandwidth>().unwrap().0, 187_500);
+        assert_eq!("64KiB/s".parse::<Bandwidth>().unwrap().0, 64 << 10);
+        assert_eq!("100B/s".parse::<Bandwidth>().unwrap().0, 100);
+        assert!("200".parse::<Bandwidth>().is_err());
+        assert!("200xbps".parse::<Bandwidth>().is_err());
+        assert!("0kbps".parse::<Bandwidth>().is_err());
+        assert_eq!(Bandwidth(25_000).to_string(), "200kbps");
+    }
+
+    #[test]
+    fn test_stepping_down_and_up() {
+        let start = Instant::now();
+        let mut throttle = OutputThrottle::new(Bandwidth(1000));
+        let mut total = 0;
+        let mut changes = Vec::new();
+        // Twice the budget: one step down every SETTLE_TIME
+        for tenth in 1..=45 {
+            total += 200;
+            let now = start + Duration::from_millis(tenth * 100);
+            changes.extend(throttle.record(total, now));
+        }
+        assert_eq!(changes.len(), 2);
+        assert_eq!(throttle.fidelity(), STEPS[2]);
+        assert!(throttle.status().ends_with("/8kbps 256 colors"));
+
+        // Well under the budget for RECOVER_TIME goes back up one step
+        changes.clear();
+        for tenth in 46..=180 {
+            total += 20;
+            let now = start + Duration::from_millis(tenth * 100);
+            changes.extend(throttle.record(total, now));
+        }
+        assert_eq!(changes, [STEPS[1]]);
+        assert!(throttle.fidelity().diff_only);
+    }
+}
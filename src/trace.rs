@@ -0,0 +1,202 @@
+//! Per-stage timing from `tracing` spans
+//!
+//! The decoder, converter and renderer open `decode`, `convert`, `render` and
+//! `flush` spans for every frame. `StageLayer` turns their durations into the
+//! running totals shown by the stats line, and `--trace` additionally writes
+//! every span to a Chrome trace file that opens in `chrome://tracing` or
+//! Perfetto.
+
+use anyhow::{Context as _, Result};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A step of the per-frame pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Decode,
+    Convert,
+    Render,
+    Flush,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Decode, Stage::Convert, Stage::Render, Stage::Flush];
+
+    /// Name of the span that times this stage
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Decode => "decode",
+            Stage::Convert => "convert",
+            Stage::Render => "render",
+            Stage::Flush => "flush",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|stage| stage.name() == name)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Timings of one stage
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTiming {
+    pub count: u64,
+    pub total: Duration,
+    pub last: Duration,
+}
+
+impl StageTiming {
+    /// Mean time per span
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+/// Running timings of every stage, shared with the layer that records them
+#[derive(Debug, Clone, Default)]
+pub struct StageStats {
+    timings: Arc<Mutex<[StageTiming; 4]>>,
+}
+
+impl StageStats {
+    /// Timings recorded so far for a stage
+    pub fn get(&self, stage: Stage) -> StageTiming {
+        self.timings.lock().unwrap()[stage.index()]
+    }
+
+    fn record(&self, stage: Stage, elapsed: Duration) {
+        let mut timings = self.timings.lock().unwrap();
+        let timing = &mut timings[stage.index()];
+        timing.count += 1;
+        timing.total += elapsed;
+        timing.last = elapsed;
+    }
+}
+
+impl fmt::Display for StageStats {
+    /// Average milliseconds per stage, e.g. `decode 2.1ms convert 0.8ms ...`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, stage) in Stage::ALL.into_iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            let average = self.get(stage).average().as_secs_f64() * 1000.0;
+            write!(f, "{} {:.1}ms", stage.name(), average)?;
+        }
+        Ok(())
+    }
+}
+
+/// Start time stored on stage spans
+struct Started(Instant);
+
+/// Layer that feeds stage span durations into `StageStats`
+pub struct StageLayer {
+    stats: StageStats,
+}
+
+impl StageLayer {
+    pub fn new(stats: StageStats) -> Self {
+        Self { stats }
+    }
+}
+
+impl<S> Layer<S> for StageLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if Stage::from_name(attrs.metadata().name()).is_none() {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(stage) = Stage::from_name(span.name()) else {
+            return;
+        };
+        let started = span.extensions().get::<Started>().map(|started| started.0);
+        if let Some(started) = started {
+            self.stats.record(stage, started.elapsed());
+        }
+    }
+}
+
+/// Keeps the trace file open; it is completed when this is dropped
+pub struct TraceGuard {
+    _chrome: Option<tracing_chrome::FlushGuard>,
+}
+
+/// Install the global subscriber: stage timings always, and a Chrome trace
+/// written to `trace_path` if given
+pub fn init(trace_path: Option<&Path>) -> Result<(StageStats, TraceGuard)> {
+    let stats = StageStats::default();
+    let registry = tracing_subscriber::registry().with(StageLayer::new(stats.clone()));
+
+    let chrome = match trace_path {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .writer(file)
+                .include_args(true)
+                .build();
+            tracing::subscriber::set_global_default(registry.with(layer))
+                .context("Failed to install trace subscriber")?;
+            Some(guard)
+        }
+        None => {
+            tracing::subscriber::set_global_default(registry)
+                .context("Failed to install trace subscriber")?;
+            None
+        }
+    };
+
+    Ok((stats, TraceGuard { _chrome: chrome }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::debug_span;
+
+    #[test]
+    fn test_stage_spans_are_timed() {
+        let stats = StageStats::default();
+        let subscriber = tracing_subscriber::registry().with(StageLayer::new(stats.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                let _decode = debug_span!("decode").entered();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let _other = debug_span!("unrelated").entered();
+        });
+
+        let decode = stats.get(Stage::Decode);
+        assert_eq!(decode.count, 3);
+        assert!(decode.average() >= Duration::from_millis(1));
+        assert_eq!(stats.get(Stage::Render), StageTiming::default());
+        assert!(stats.to_string().starts_with("decode "));
+    }
+}
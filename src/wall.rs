@@ -0,0 +1,394 @@
+//! Video walls: one video split across several terminals
+//!
+//! Each ascii-player started with `--wall COLSxROWS --tile COL,ROW` crops its
+//! tile out of every decoded frame and draws it on its own terminal. Tile 0,0
+//! leads: it plays on its own clock and publishes its position over UDP to
+//! `--sync-addr` (a multicast group by default). The other tiles follow that
+//! clock, waiting when they are ahead, dropping frames when slightly behind
+//! and seeking when far off, so the wall stays in step across machines.
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::crop::{crop_frame, CropRect};
+use crate::recording::wait_for;
+use crate::renderer::Renderer;
+use crate::termcaps::TermCaps;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Multicast group and port the wall clock is shared on by default
+pub const DEFAULT_SYNC_ADDR: &str = "239.255.42.99:4299";
+
+/// First word of every clock message
+const CLOCK_MAGIC: &str = "ascii-wall";
+/// Clock message format version
+const CLOCK_VERSION: u32 = 1;
+
+/// How often the leader publishes its position
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+/// Followers drop frames more than this far behind the leader
+const FRAME_TOLERANCE: f64 = 0.05;
+/// Followers seek when this far from the leader
+const SEEK_THRESHOLD: f64 = 2.0;
+
+/// Grid of terminals making up the wall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WallLayout {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl WallLayout {
+    /// Part of a `width` x `height` frame shown by `tile`
+    pub fn tile_rect(&self, tile: Tile, width: u32, height: u32) -> CropRect {
+        let span = |index: u16, count: u16, size: u32| {
+            let start = size * index as u32 / count as u32;
+            let end = size * (index as u32 + 1) / count as u32;
+            (start, (end - start).max(1))
+        };
+        let (x, width) = span(tile.column, self.columns, width);
+        let (y, height) = span(tile.row, self.rows, height);
+        CropRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, tile: Tile) -> bool {
+        tile.column < self.columns && tile.row < self.rows
+    }
+}
+
+impl FromStr for WallLayout {
+    type Err = String;
+
+    /// Parse `COLSxROWS`, e.g. `2x2` or `3x1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (columns, rows) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("Invalid wall layout '{}', expected e.g. 2x2", s))?;
+        let parse = |value: &str| match value.trim().parse::<u16>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(format!("Invalid wall layout '{}', expected e.g. 2x2", s)),
+        };
+        Ok(Self {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+impl fmt::Display for WallLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.columns, self.rows)
+    }
+}
+
+/// Position of one terminal in the wall, counted from the top left
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tile {
+    pub column: u16,
+    pub row: u16,
+}
+
+impl Tile {
+    /// Whether this tile runs the clock the others follow
+    pub fn is_leader(&self) -> bool {
+        *self == Tile::default()
+    }
+}
+
+impl FromStr for Tile {
+    type Err = String;
+
+    /// Parse `COL,ROW`, e.g. `1,0`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid tile '{}', expected COL,ROW such as 1,0", s);
+        let (column, row) = s.split_once(',').ok_or_else(invalid)?;
+        Ok(Self {
+            column: column.trim().parse().map_err(|_| invalid())?,
+            row: row.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Playback position shared by the leader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockMessage {
+    /// Timestamp of the frame the leader is showing
+    pub position: f64,
+    /// Playback speed, to extrapolate between messages
+    pub speed: f64,
+}
+
+impl ClockMessage {
+    pub fn encode(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            CLOCK_MAGIC, CLOCK_VERSION, self.position, self.speed
+        )
+    }
+
+    /// Parse a message, or `None` if it is not a wall clock message this
+    /// release understands
+    pub fn decode(message: &str) -> Option<Self> {
+        let mut words = message.split_whitespace();
+        if words.next()? != CLOCK_MAGIC || words.next()?.parse::<u32>().ok()? != CLOCK_VERSION {
+            return None;
+        }
+        Some(Self {
+            position: words.next()?.parse().ok()?,
+            speed: words.next()?.parse().ok()?,
+        })
+    }
+}
+
+/// UDP endpoint of the shared clock
+pub struct WallClock {
+    socket: UdpSocket,
+    addr: SocketAddrV4,
+    /// Last message heard and when
+    latest: Option<(ClockMessage, Instant)>,
+}
+
+impl WallClock {
+    /// Socket the leader publishes from
+    pub fn leader(addr: SocketAddrV4) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        if addr.ip().is_multicast() {
+            socket.set_multicast_ttl_v4(1)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+        Ok(Self {
+            socket,
+            addr,
+            latest: None,
+        })
+    }
+
+    /// Socket a follower listens on; several followers on one machine can
+    /// share the port
+    pub fn follower(addr: SocketAddrV4) -> Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket
+            .bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, addr.port()).into())
+            .with_context(|| format!("Failed to listen for the wall clock on {}", addr))?;
+        if addr.ip().is_multicast() {
+            socket.join_multicast_v4(addr.ip(), &Ipv4Addr::UNSPECIFIED)?;
+        }
+
+        let socket: UdpSocket = socket.into();
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            addr,
+            latest: None,
+        })
+    }
+
+    /// Port the socket is bound to
+    pub fn local_port(&self) -> Result<u16> {
+        Ok(self.socket.local_addr()?.port())
+    }
+
+    /// Send the leader's position
+    pub fn publish(&self, message: ClockMessage) -> Result<()> {
+        self.socket
+            .send_to(message.encode().as_bytes(), self.addr)?;
+        Ok(())
+    }
+
+    /// The leader's position now, extrapolated from the latest message, or
+    /// `None` before one has arrived
+    pub fn position(&mut self) -> Option<f64> {
+        let mut buffer = [0u8; 256];
+        while let Ok(length) = self.socket.recv(&mut buffer) {
+            let text = String::from_utf8_lossy(&buffer[..length]);
+            if let Some(message) = ClockMessage::decode(&text) {
+                self.latest = Some((message, Instant::now()));
+            }
+        }
+
+        self.latest.map(|(message, received)| {
+            message.position + received.elapsed().as_secs_f64() * message.speed
+        })
+    }
+}
+
+/// Play one tile of a video wall
+pub async fn run_wall(cli: &Cli, config: &Config, layout: WallLayout) -> Result<()> {
+    let tile = cli.tile;
+    let mut clock = if tile.is_leader() {
+        WallClock::leader(cli.sync_addr)?
+    } else {
+        WallClock::follower(cli.sync_addr)?
+    };
+    info!(
+        "Wall {} tile {},{} ({}) syncing on {}",
+        layout,
+        tile.column,
+        tile.row,
+        if tile.is_leader() {
+            "leader"
+        } else {
+            "follower"
+        },
+        cli.sync_addr
+    );
+
+    let mut renderer = Renderer::new(cli.transparent(), cli.use_color())?;
+    renderer.init()?;
+    let result = play_tile(cli, config, layout, &mut clock, &mut renderer).await;
+    renderer.cleanup()?;
+    result
+}
+
+async fn play_tile(
+    cli: &Cli,
+    config: &Config,
+    layout: WallLayout,
+    clock: &mut WallClock,
+    renderer: &mut Renderer,
+) -> Result<()> {
+    let tile = cli.tile;
+    let converter = cli.build_converter(config, &TermCaps::default())?;
+    let speed = cli.speed.max(0.01);
+    let mut frame_iter = cli.open_video()?;
+
+    if !tile.is_leader() {
+        renderer.display_message(&format!("Waiting for tile 0,0 on {}...", cli.sync_addr))?;
+        while clock.position().is_none() {
+            if !wait_for(Instant::now() + Duration::from_millis(50)).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    // The leader's own clock: when it started and from which timestamp
+    let mut started: Option<(Instant, f64)> = None;
+    let mut next_publish = Instant::now();
+
+    loop {
+        let frame = match frame_iter.next() {
+            Some(frame) => frame?,
+            None if cli.loop_playback => {
+                frame_iter = cli.open_video()?;
+                started = None;
+                continue;
+            }
+            None => break,
+        };
+
+        let deadline = if tile.is_leader() {
+            let (start, first) = *started.get_or_insert((Instant::now(), frame.timestamp));
+            start + Duration::from_secs_f64(((frame.timestamp - first) / speed).max(0.0))
+        } else {
+            let Some(position) = clock.position() else {
+                continue;
+            };
+            let offset = frame.timestamp - position;
+            if offset.abs() > SEEK_THRESHOLD {
+                debug!("Tile is {:.2}s off the leader, seeking", offset);
+                if offset > 0.0 {
+                    // Behind us means the leader restarted or looped
+                    frame_iter = cli.open_video()?;
+                }
+                frame_iter.seek(position)?;
+                continue;
+            }
+            if offset < -FRAME_TOLERANCE {
+                continue;
+            }
+            Instant::now() + Duration::from_secs_f64(offset.max(0.0) / speed)
+        };
+
+        if !wait_for(deadline).await? {
+            return Ok(());
+        }
+
+        if tile.is_leader() && Instant::now() >= next_publish {
+            let message = ClockMessage {
+                position: frame.timestamp,
+                speed,
+            };
+            if let Err(e) = clock.publish(message) {
+                debug!("Failed to publish wall clock: {}", e);
+            }
+            next_publish = Instant::now() + PUBLISH_INTERVAL;
+        }
+
+        let rect = layout.tile_rect(tile, frame.width, frame.height);
+        let tile_frame = crop_frame(&frame, rect)?;
+        let (width, height) = renderer.dimensions();
+        renderer.render_frame(&converter.convert(&tile_frame, width, height)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_and_tiles() {
+        let layout: WallLayout = "3x2".parse().unwrap();
+        assert_eq!(
+            layout,
+            WallLayout {
+                columns: 3,
+                rows: 2
+            }
+        );
+        assert!("0x2".parse::<WallLayout>().is_err());
+        assert!("2".parse::<WallLayout>().is_err());
+
+        let tile: Tile = "2,1".parse().unwrap();
+        assert!(layout.contains(tile) && !tile.is_leader());
+        assert!(!layout.contains("3,0".parse().unwrap()));
+
+        assert_eq!(
+            layout.tile_rect(tile, 1920, 1080),
+            CropRect {
+                x: 1280,
+                y: 540,
+                width: 640,
+                height: 540
+            }
+        );
+    }
+
+    #[test]
+    fn test_clock_over_udp() {
+        let message = ClockMessage {
+            position: 12.5,
+            speed: 1.0,
+        };
+        assert_eq!(ClockMessage::decode(&message.encode()), Some(message));
+        assert_eq!(ClockMessage::decode("ascii-wall 9 1.0 1.0"), None);
+
+        let mut follower = WallClock::follower(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = follower.local_port().unwrap();
+        let leader = WallClock::leader(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)).unwrap();
+        assert_eq!(follower.position(), None);
+
+        leader.publish(message).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let position = loop {
+            if let Some(position) = follower.position() {
+                break position;
+            }
+            assert!(Instant::now() < deadline, "clock message never arrived");
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert!((12.5..13.0).contains(&position));
+    }
+}
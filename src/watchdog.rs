@@ -0,0 +1,129 @@
+//! Deadline on the work done for a single frame
+//!
+//! Converting and rendering run on the playback thread, between input
+//! handling and the frame timer. One pathological frame, such as a huge
+//! terminal with a heavy style, can take long enough that playback falls
+//! behind the media clock and keys go unanswered. `FrameWatchdog` times that
+//! work against `--frame-timeout`: a frame that took too long is still
+//! drawn, but the frames the media clock has moved past meanwhile are
+//! dropped before conversion so playback catches up at once. When every
+//! frame is that slow, playback shows what it can keep up with.
+
+use log::warn;
+use std::time::{Duration, Instant};
+
+/// Default for `--frame-timeout`, in milliseconds
+pub const DEFAULT_FRAME_TIMEOUT_MS: u64 = 250;
+
+/// Times per-frame work and tracks how far playback has to catch up
+#[derive(Debug, Default)]
+pub struct FrameWatchdog {
+    /// Longest a stage may take for one frame; None never times out
+    deadline: Option<Duration>,
+    /// Media times between the frame that overran and where the clock got
+    /// to meanwhile; frames in between are dropped
+    resync: Option<(f64, f64)>,
+    timeouts: u64,
+}
+
+impl FrameWatchdog {
+    /// A watchdog allowing `deadline` per stage of a frame; zero disables it
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            deadline: (!deadline.is_zero()).then_some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// Whether `stage` (e.g. "converting") of the frame at `timestamp`,
+    /// begun at `started`, ran past the deadline. An overrun is logged and
+    /// counted, and the media time that passed meanwhile at `speed` is
+    /// skipped by [`Self::behind`].
+    pub fn overran(&mut self, stage: &str, started: Instant, timestamp: f64, speed: f64) -> bool {
+        let Some(deadline) = self.deadline else {
+            return false;
+        };
+        let elapsed = started.elapsed();
+        if elapsed <= deadline {
+            return false;
+        }
+
+        self.timeouts += 1;
+        let target = timestamp + elapsed.as_secs_f64() * speed;
+        self.resync = Some(match self.resync {
+            Some((from, to)) => (from.min(timestamp), to.max(target)),
+            None => (timestamp, target),
+        });
+        warn!(
+            "{} frame at {:.2}s took {}ms (over {}ms), resynchronizing to {:.2}s",
+            stage,
+            timestamp,
+            elapsed.as_millis(),
+            deadline.as_millis(),
+            target
+        );
+        true
+    }
+
+    /// Whether the frame at `timestamp` is one the media clock has already
+    /// passed after an overrun, and should be dropped without converting.
+    /// A frame past the catch-up point, or before the frame that overran
+    /// (after a seek back or a loop), ends the catching up.
+    pub fn behind(&mut self, timestamp: f64) -> bool {
+        match self.resync {
+            Some((from, to)) if timestamp > from && timestamp < to => true,
+            Some(_) => {
+                self.resync = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Frames that ran past the deadline so far
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrun_skips_to_the_media_clock() {
+        let mut watchdog = FrameWatchdog::new(Duration::from_millis(10));
+        assert!(!watchdog.overran("converting", Instant::now(), 1.0, 1.0));
+        assert!(!watchdog.behind(1.04));
+
+        let started = Instant::now() - Duration::from_millis(500);
+        assert!(watchdog.overran("converting", started, 1.0, 2.0));
+        assert_eq!(watchdog.timeouts(), 1);
+
+        // Half a second at double speed is a second of video
+        assert!(watchdog.behind(1.5));
+        assert!(watchdog.behind(1.99));
+        assert!(!watchdog.behind(5.0));
+        // Caught up: later frames are kept even if timestamps jump back
+        assert!(!watchdog.behind(1.5));
+    }
+
+    #[test]
+    fn test_seek_back_ends_catching_up() {
+        let mut watchdog = FrameWatchdog::new(Duration::from_millis(10));
+        let started = Instant::now() - Duration::from_millis(500);
+        assert!(watchdog.overran("rendering", started, 10.0, 1.0));
+        assert!(watchdog.behind(10.2));
+        assert!(!watchdog.behind(0.0));
+        assert!(!watchdog.behind(10.2));
+    }
+
+    #[test]
+    fn test_zero_deadline_never_times_out() {
+        let mut watchdog = FrameWatchdog::new(Duration::ZERO);
+        let started = Instant::now() - Duration::from_secs(5);
+        assert!(!watchdog.overran("rendering", started, 0.0, 1.0));
+        assert!(!watchdog.behind(0.1));
+        assert_eq!(watchdog.timeouts(), 0);
+    }
+}
@@ -0,0 +1,333 @@
+//! Audio waveform under the progress bar
+//!
+//! `--waveform` measures the loudness (RMS) of each second of the audio
+//! track in a background pass when a video is opened, and draws it as a row
+//! of bars above the status line so loud sections are easy to find. The
+//! part already played is highlighted. Levels are cached per input, so the
+//! pass runs once per file.
+
+use crate::converter::AsciiFrame;
+use crate::decoder::source_location;
+use crate::overlay::OVERLAY_COLOR;
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Bars from quiet to loud
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Color of the part not yet played
+const UNPLAYED_COLOR: (u8, u8, u8) = (110, 110, 110);
+
+/// Color of the column at the playback position
+const POSITION_COLOR: (u8, u8, u8) = (255, 200, 0);
+
+/// Loudness of an audio track, one RMS level per second
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waveform {
+    levels: Vec<f32>,
+}
+
+impl Waveform {
+    pub fn new(levels: Vec<f32>) -> Self {
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    /// Decode the best audio stream of `input` and measure every second
+    pub fn analyze(input: &Path) -> Result<Self> {
+        let _ = ffmpeg::init();
+        let mut input_context = ffmpeg::format::input(&input)
+            .map_err(|e| anyhow!("Failed to open '{}': {}", input.display(), e))?;
+        let stream = input_context
+            .streams()
+            .best(ffmpeg::media::Type::Audio)
+            .ok_or_else(|| anyhow!("No audio stream found in '{}'", input.display()))?;
+        let stream_index = stream.index();
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().audio())
+            .map_err(|e| anyhow!("Failed to create audio decoder: {}", e))?;
+
+        let mut levels = LevelMeter::default();
+        let mut frame = ffmpeg::frame::Audio::empty();
+        for (stream, packet) in input_context.packets() {
+            if stream.index() != stream_index {
+                continue;
+            }
+            if let Err(e) = decoder.send_packet(&packet) {
+                debug!("Skipping audio packet: {}", e);
+                continue;
+            }
+            while decoder.receive_frame(&mut frame).is_ok() {
+                levels.add_frame(&frame)?;
+            }
+        }
+        let _ = decoder.send_eof();
+        while decoder.receive_frame(&mut frame).is_ok() {
+            levels.add_frame(&frame)?;
+        }
+        Ok(Self::new(levels.finish()))
+    }
+
+    /// Cached levels of `input`, analyzing and caching them if there are none
+    pub fn load_or_analyze(input: &Path) -> Result<Self> {
+        let cache = cache_path(input);
+        if let Some(ref path) = cache {
+            if let Ok(contents) = std::fs::read_to_string(path) {
+                if let Ok(waveform) = serde_json::from_str(&contents) {
+                    debug!("Loaded waveform from {}", path.display());
+                    return Ok(waveform);
+                }
+            }
+        }
+
+        let waveform = Self::analyze(input)?;
+        if let Some(ref path) = cache {
+            let saved = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(path, serde_json::to_string(&waveform)?));
+            if let Err(e) = saved {
+                warn!("Failed to cache waveform: {}", e);
+            }
+        }
+        Ok(waveform)
+    }
+
+    /// Bar and color for each of `width` columns, with `position` of
+    /// `duration` seconds played
+    pub fn row(&self, width: usize, position: f64, duration: f64) -> Vec<(char, (u8, u8, u8))> {
+        let seconds = self.levels.len();
+        if seconds == 0 || width == 0 {
+            return Vec::new();
+        }
+        let loudest = self.levels.iter().copied().fold(f32::EPSILON, f32::max);
+        let duration = if duration > 0.0 {
+            duration
+        } else {
+            seconds as f64
+        };
+        let played = ((position / duration).clamp(0.0, 1.0) * width as f64) as usize;
+
+        (0..width)
+            .map(|column| {
+                // Loudest second under the column, so short peaks stay visible
+                let first = column * seconds / width;
+                let last = ((column + 1) * seconds / width).max(first + 1).min(seconds);
+                let level = self.levels[first..last].iter().copied().fold(0.0, f32::max);
+                let bar = ((level / loudest) * (BARS.len() - 1) as f32).round() as usize;
+                let color = match column.cmp(&played) {
+                    std::cmp::Ordering::Less => OVERLAY_COLOR,
+                    std::cmp::Ordering::Equal => POSITION_COLOR,
+                    std::cmp::Ordering::Greater => UNPLAYED_COLOR,
+                };
+                (BARS[bar.min(BARS.len() - 1)], color)
+            })
+            .collect()
+    }
+
+    /// Draw the waveform across `row` of `frame`
+    pub fn draw(&self, frame: &mut AsciiFrame, row: u16, position: f64, duration: f64) {
+        if row >= frame.height {
+            return;
+        }
+        let width = frame.width as usize;
+        let start = row as usize * width;
+        for (column, (bar, color)) in self.row(width, position, duration).into_iter().enumerate() {
+            frame.characters[start + column] = bar;
+            frame.fg_colors[start + column] = color;
+        }
+    }
+}
+
+/// Adds up samples into one RMS level per second
+#[derive(Default)]
+struct LevelMeter {
+    levels: Vec<f32>,
+    sum: f64,
+    count: u32,
+}
+
+impl LevelMeter {
+    fn add_frame(&mut self, frame: &ffmpeg::frame::Audio) -> Result<()> {
+        let format = frame.format();
+        let (size, read) = sample_reader(format)
+            .ok_or_else(|| anyhow!("Unsupported audio sample format {:?}", format))?;
+        let channels = usize::from(frame.channels()).max(1);
+        let rate = frame.rate().max(1);
+
+        for index in 0..frame.samples() {
+            let mut square = 0.0;
+            for channel in 0..channels {
+                let (plane, offset) = if frame.is_planar() {
+                    (channel, index)
+                } else {
+                    (0, index * channels + channel)
+                };
+                let data = frame.data(plane);
+                let bytes = data
+                    .get(offset * size..(offset + 1) * size)
+                    .unwrap_or_default();
+                if bytes.len() == size {
+                    square += f64::from(read(bytes)).powi(2);
+                }
+            }
+            self.sum += square / channels as f64;
+            self.count += 1;
+            if self.count == rate {
+                self.push();
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self) {
+        self.levels
+            .push((self.sum / f64::from(self.count)).sqrt() as f32);
+        self.sum = 0.0;
+        self.count = 0;
+    }
+
+    fn finish(mut self) -> Vec<f32> {
+        if self.count > 0 {
+            self.push();
+        }
+        self.levels
+    }
+}
+
+/// Reads one sample from its bytes as -1.0..1.0
+type SampleReader = fn(&[u8]) -> f32;
+
+/// Bytes per sample and how to read one
+fn sample_reader(format: ffmpeg::format::Sample) -> Option<(usize, SampleReader)> {
+    use ffmpeg::format::Sample;
+    let reader: (usize, SampleReader) = match format {
+        Sample::U8(_) => (1, |b: &[u8]| (f32::from(b[0]) - 128.0) / 128.0),
+        Sample::I16(_) => (2, |b: &[u8]| {
+            f32::from(i16::from_ne_bytes([b[0], b[1]])) / 32768.0
+        }),
+        Sample::I32(_) => (4, |b: &[u8]| {
+            i32::from_ne_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+        }),
+        Sample::F32(_) => (4, |b: &[u8]| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+        Sample::F64(_) => (8, |b: &[u8]| {
+            f64::from_ne_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+        }),
+        Sample::I64(_) | Sample::None => return None,
+    };
+    Some(reader)
+}
+
+/// Cache file for the levels of `input`, named after its location, size and
+/// modification time so edited files are measured again
+fn cache_path(input: &Path) -> Option<PathBuf> {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    let mut key = source_location(input);
+    if let Ok(metadata) = std::fs::metadata(input) {
+        key.push_str(&format!(":{}", metadata.len()));
+        if let Ok(modified) = metadata.modified() {
+            key.push_str(&format!(":{:?}", modified));
+        }
+    }
+    Some(
+        cache_dir
+            .join("ascii-player")
+            .join("waveforms")
+            .join(format!("{:016x}.json", fnv1a(key.as_bytes()))),
+    )
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A waveform being measured on another thread
+pub struct WaveformLoader {
+    receiver: Receiver<Waveform>,
+    waveform: Option<Waveform>,
+}
+
+impl WaveformLoader {
+    /// Start measuring `input`; failures are logged and leave no waveform
+    pub fn spawn(input: &Path) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let input = input.to_path_buf();
+        thread::spawn(move || match Waveform::load_or_analyze(&input) {
+            Ok(waveform) => {
+                info!("Waveform ready: {}s of audio", waveform.levels().len());
+                let _ = sender.send(waveform);
+            }
+            Err(e) => warn!("No waveform: {}", e),
+        });
+        Self {
+            receiver,
+            waveform: None,
+        }
+    }
+
+    /// The waveform, once measured
+    pub fn get(&mut self) -> Option<&Waveform> {
+        if self.waveform.is_none() {
+            self.waveform = self.receiver.try_recv().ok();
+        }
+        self.waveform.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overlay::blank_frame;
+
+    #[test]
+    fn test_waveform_row() {
+        let waveform = Waveform::new(vec![0.0, 0.1, 0.2, 0.4, 0.0, 0.0, 0.0, 0.05]);
+
+        let row = waveform.row(4, 2.0, 8.0);
+        let bars: String = row.iter().map(|(bar, _)| *bar).collect();
+        // Each column shows the loudest of its two seconds
+        assert_eq!(bars, "▃█▁▂");
+        assert_eq!(row[0].1, OVERLAY_COLOR);
+        assert_eq!(row[1].1, POSITION_COLOR);
+        assert_eq!(row[3].1, UNPLAYED_COLOR);
+
+        // More columns than seconds repeat each second
+        let wide: String = waveform
+            .row(16, 0.0, 8.0)
+            .iter()
+            .map(|(bar, _)| *bar)
+            .collect();
+        assert_eq!(wide.chars().count(), 16);
+        assert!(wide.starts_with("▁▁▃▃"));
+
+        let mut frame = blank_frame(4, 3);
+        waveform.draw(&mut frame, 2, 8.0, 8.0);
+        assert_eq!(frame.characters[8..].iter().collect::<String>(), bars);
+        assert!(frame.characters[..8].iter().all(|&c| c == ' '));
+    }
+
+    #[test]
+    fn test_waveform_cache_key() {
+        let a = cache_path(Path::new("https://example.com/a.mp4"));
+        let b = cache_path(Path::new("https://example.com/b.mp4"));
+        if let (Some(a), Some(b)) = (a, b) {
+            assert_ne!(a, b);
+            assert_eq!(a.extension().unwrap(), "json");
+        }
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+    }
+}
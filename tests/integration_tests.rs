@@ -241,11 +241,7 @@ mod converter_tests {
 
     #[test]
     fn test_transparent_conversion() {
-        let config = ConversionConfig {
-            transparent: true,
-            alpha_threshold: Some(128),
-            ..Default::default()
-        };
+        let config = ConversionConfig::default().with_transparent(true, Some(128));
         let converter = FrameConverter::new(config);
 
         // Create a frame with low brightness (should be transparent)
@@ -264,15 +260,14 @@ mod renderer_tests {
     use std::time::Duration;
 
     fn create_test_ascii_frame() -> AsciiFrame {
-        AsciiFrame {
-            characters: vec!['#', ' ', '@', '.'],
-            fg_colors: vec![(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)],
-            bg_colors: Some(vec![(0, 0, 0), (0, 0, 0), (0, 0, 0), (0, 0, 0)]),
-            width: 2,
-            height: 2,
-            timestamp: 1.0,
-            frame_number: 42,
-        }
+        AsciiFrame::new(
+            2,
+            2,
+            vec!['#', ' ', '@', '.'],
+            vec![(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)],
+        )
+        .with_background(vec![(0, 0, 0), (0, 0, 0), (0, 0, 0), (0, 0, 0)])
+        .with_timestamp(1.0, 42)
     }
 
     #[test]